@@ -0,0 +1,129 @@
+//! Throughput of the signing path: the raw key operations, then `POST /sign`
+//! end to end against an in-process server.
+//!
+//! ```text
+//! cargo bench --bench signing
+//! ```
+//!
+//! Plain `std::time` loops rather than a benchmark framework, so it runs
+//! with the crate's own dependencies. Results are tracked in
+//! `benches/RESULTS.md`.
+
+use ecdsa_lib::KeyPair;
+use lab4::clock::SystemClock;
+use lab4::config::{KeyStatsConfig, ServerConfig};
+use lab4::ecdsa_requests::verify_signature;
+use lab4::signer::TenantSigners;
+use lab4::{EcdsaSignedTimestamp, server};
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Runs `f` `iterations` times and prints the rate
+fn bench(name: &str, iterations: u32, mut f: impl FnMut()) {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    report(name, iterations, start.elapsed());
+}
+
+fn report(name: &str, iterations: u32, elapsed: Duration) {
+    println!(
+        "{:<36} {:>9.1} µs/op {:>9.0} ops/s",
+        name,
+        elapsed.as_secs_f64() * 1e6 / f64::from(iterations),
+        f64::from(iterations) / elapsed.as_secs_f64()
+    );
+}
+
+fn key_operations() {
+    let keypair = KeyPair::from_seed(&[1u8; 32]);
+    let public_key = keypair.to_public_key();
+    let data = b"Hello, VTS!2025-06-02T05:05:35.784383Z";
+    let signature = keypair.sign(data);
+    let raw = signature.to_vec();
+
+    bench("KeyPair::sign", 2000, || {
+        black_box(keypair.sign(black_box(data)));
+    });
+    bench("PublicKey::verify", 2000, || {
+        black_box(public_key.verify(black_box(data), &signature));
+    });
+    bench("PublicKey::verify_encoded (raw)", 2000, || {
+        black_box(public_key.verify_encoded(black_box(data), &raw));
+    });
+}
+
+/// `requests` timestamps of `message_bytes`-byte messages from
+/// `concurrency` clients at once. The server keeps no state files, like a
+/// deployment with them on tmpfs.
+async fn http_path(requests: u32, concurrency: u32, message_bytes: usize) {
+    let config = ServerConfig {
+        high_water_file: String::new(),
+        serial_file: String::new(),
+        key_stats: KeyStatsConfig {
+            file: String::new(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/sign", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        server::run_server_with_signers(
+            Box::new(KeyPair::from_seed(&[2u8; 32])),
+            TenantSigners::new(),
+            config,
+            Box::new(SystemClock),
+            listener,
+        )
+        .await
+        .unwrap()
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let client = reqwest::Client::new();
+    let body = serde_json::json!({ "message": "b".repeat(message_bytes) });
+    let sign = |client: reqwest::Client, url: Arc<str>, body: serde_json::Value| async move {
+        let resp = client.post(&*url).json(&body).send().await.unwrap();
+        resp.json::<EcdsaSignedTimestamp>().await.unwrap()
+    };
+    let url: Arc<str> = url.into();
+
+    // Warm up the connections (and check the answer verifies)
+    let key = lab4::ecdsa_requests::nonblocking::request_key(url.trim_end_matches("/sign"))
+        .await
+        .unwrap();
+    let signed = sign(client.clone(), url.clone(), body.clone()).await;
+    assert!(verify_signature(&signed, &key));
+
+    let start = Instant::now();
+    let per_client = requests / concurrency;
+    let tasks: Vec<_> = (0..concurrency)
+        .map(|_| {
+            let (client, url, body) = (client.clone(), url.clone(), body.clone());
+            tokio::spawn(async move {
+                for _ in 0..per_client {
+                    black_box(sign(client.clone(), url.clone(), body.clone()).await);
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    report(
+        &format!("POST /sign ({} B, {} clients)", message_bytes, concurrency),
+        per_client * concurrency,
+        start.elapsed(),
+    );
+}
+
+fn main() {
+    key_operations();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(http_path(4000, 1, 16));
+    runtime.block_on(http_path(4000, 32, 16));
+    runtime.block_on(http_path(200, 1, 256 * 1024));
+}
@@ -0,0 +1,13 @@
+//! Compiles `proto/vts.proto` for the `grpc` feature (with `protox`, so no
+//! `protoc` install is needed).
+
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/vts.proto");
+        let fds = protox::compile(["proto/vts.proto"], ["proto"]).expect("invalid vts.proto");
+        tonic_build::configure()
+            .compile_fds(fds)
+            .expect("failed to generate gRPC code");
+    }
+}
@@ -0,0 +1,233 @@
+//! BIP-32 hierarchical key derivation (the `hd` feature)
+//!
+//! Derives child key pairs from one master seed along a path such as
+//! `m/0'/2025'/3`, so per-day or per-tenant keys can all be recovered from a
+//! single backed-up seed. Only private derivation is provided (there are no
+//! extended public keys); on secp256k1 this is the same scheme SLIP-10 uses.
+//! An index ending in `'` (or `h`) is hardened: its key cannot be linked to
+//! its parent's without the parent's private key.
+
+use crate::KeyPair;
+use hmac::{Hmac, Mac};
+use k256::ecdsa::{SigningKey, VerifyingKey};
+use k256::elliptic_curve::PrimeField;
+use k256::sha2::Sha512;
+use k256::{FieldBytes, Scalar};
+use zeroize::{ZeroizeOnDrop, Zeroizing};
+
+/// Added to an index to make it hardened (`i'` is `i + HARDENED`)
+pub const HARDENED: u32 = 1 << 31;
+
+/// HMAC key of the master key derivation, fixed by BIP-32
+const MASTER_KEY: &[u8] = b"Bitcoin seed";
+
+/// A key pair together with the chain code needed to derive its children
+pub struct ExtendedKey {
+    keypair: KeyPair,
+    chain_code: Zeroizing<[u8; 32]>,
+}
+
+/// The chain code is in `Zeroizing` and `KeyPair` wipes itself
+impl ZeroizeOnDrop for ExtendedKey {}
+
+impl ExtendedKey {
+    /// The master key of `seed` (16 to 64 bytes, as BIP-32 requires)
+    pub fn master(seed: &[u8]) -> std::io::Result<Self> {
+        if !(16..=64).contains(&seed.len()) {
+            return Err(invalid("Seed must be 16 to 64 bytes"));
+        }
+        let i = hmac_sha512(MASTER_KEY, &[seed]);
+        let keypair = KeyPair::from_private_key_bytes(&i[..32])
+            .map_err(|_| invalid("Seed gives an invalid master key"))?;
+        Ok(Self::new(keypair, &i[32..]))
+    }
+
+    /// The child at `index` (add HARDENED for a hardened child).
+    ///
+    /// BIP-32 leaves the (astronomically rare) invalid child to the caller,
+    /// who should skip to the next index; this returns `InvalidData` for it.
+    pub fn derive_child(&self, index: u32) -> std::io::Result<Self> {
+        // 1) I = HMAC-SHA512(chain code, data || index), where data is the
+        //    private key for hardened children and the public key otherwise
+        let private_key = Zeroizing::new(self.keypair.signing_key.to_bytes());
+        let public_key = self.keypair.verifying_key.to_encoded_point(true);
+        let data: &[&[u8]] = if index >= HARDENED {
+            &[&[0], private_key.as_slice()]
+        } else {
+            &[public_key.as_bytes()]
+        };
+        let i = hmac_sha512(
+            self.chain_code.as_slice(),
+            &[data, &[&index.to_be_bytes()]].concat(),
+        );
+
+        // 2) Child key = I[..32] + parent key (mod n)
+        let tweak = Option::<Scalar>::from(Scalar::from_repr(*FieldBytes::from_slice(&i[..32])))
+            .ok_or_else(|| invalid("Invalid child key at this index"))?;
+        let child = tweak + self.keypair.signing_key.as_nonzero_scalar().as_ref();
+        let signing_key = SigningKey::from_bytes(&child.to_bytes())
+            .map_err(|_| invalid("Invalid child key at this index"))?;
+        let keypair = KeyPair {
+            verifying_key: VerifyingKey::from(&signing_key),
+            signing_key,
+        };
+        Ok(Self::new(keypair, &i[32..]))
+    }
+
+    /// The descendant at `path`, e.g. `m/0'/1/2h`, relative to this key
+    pub fn derive_path(&self, path: &str) -> std::io::Result<Self> {
+        let mut key = self.duplicate()?;
+        for index in parse_path(path)? {
+            key = key.derive_child(index)?;
+        }
+        Ok(key)
+    }
+
+    /// The key pair, for signing
+    pub fn keypair(&self) -> &KeyPair {
+        &self.keypair
+    }
+
+    /// The key pair, dropping the chain code
+    pub fn into_keypair(self) -> KeyPair {
+        self.keypair
+    }
+
+    /// The chain code (secret: with it and the public key, non-hardened
+    /// children's public keys can be derived)
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    fn new(keypair: KeyPair, chain_code: &[u8]) -> Self {
+        let mut code = Zeroizing::new([0u8; 32]);
+        code.copy_from_slice(chain_code);
+        Self {
+            keypair,
+            chain_code: code,
+        }
+    }
+
+    /// A copy of this key (`KeyPair` is deliberately not `Clone`)
+    fn duplicate(&self) -> std::io::Result<Self> {
+        let private_key = Zeroizing::new(self.keypair.signing_key.to_bytes());
+        let keypair = KeyPair::from_private_key_bytes(&private_key)?;
+        Ok(Self::new(keypair, self.chain_code.as_slice()))
+    }
+}
+
+/// The key pair at `path` under the master key of `seed`
+pub fn derive(seed: &[u8], path: &str) -> std::io::Result<KeyPair> {
+    Ok(ExtendedKey::master(seed)?.derive_path(path)?.into_keypair())
+}
+
+/// Parses `m/44'/0h/7` into indices (hardened ones with HARDENED added).
+/// The leading `m` is optional; `m` alone is the empty path.
+pub fn parse_path(path: &str) -> std::io::Result<Vec<u32>> {
+    if path.is_empty() || path == "m" {
+        return Ok(Vec::new());
+    }
+    let path = path.strip_prefix("m/").unwrap_or(path);
+    path.split('/')
+        .map(|part| {
+            let (digits, hardened) = match part.strip_suffix(['\'', 'h']) {
+                Some(digits) => (digits, true),
+                None => (part, false),
+            };
+            let index: u32 = digits
+                .parse()
+                .ok()
+                .filter(|index| *index < HARDENED)
+                .ok_or_else(|| invalid(&format!("Invalid path component {:?}", part)))?;
+            Ok(if hardened { index + HARDENED } else { index })
+        })
+        .collect()
+}
+
+/// HMAC-SHA512 of the concatenated `parts` under `key`
+fn hmac_sha512(key: &[u8], parts: &[&[u8]]) -> Zeroizing<[u8; 64]> {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC takes keys of any size");
+    for part in parts {
+        mac.update(part);
+    }
+    Zeroizing::new(mac.finalize().into_bytes().into())
+}
+
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// BIP-32 test vector 1
+    const SEED: &str = "000102030405060708090a0b0c0d0e0f";
+
+    fn private_key_hex(key: &ExtendedKey) -> String {
+        hex::encode(key.keypair().signing_key.to_bytes())
+    }
+
+    #[test]
+    fn bip32_test_vector_1() {
+        let master = ExtendedKey::master(&hex::decode(SEED).unwrap()).unwrap();
+        assert_eq!(
+            private_key_hex(&master),
+            "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35"
+        );
+        assert_eq!(
+            hex::encode(master.chain_code()),
+            "873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508"
+        );
+
+        for (path, private_key) in [
+            (
+                "m/0'",
+                "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea",
+            ),
+            (
+                "m/0'/1",
+                "3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368",
+            ),
+            (
+                "m/0h/1/2h/2/1000000000",
+                "471b76e389e528d6de6d816857e012c5455051cad6660850e58372a6c3e6e7c8",
+            ),
+        ] {
+            assert_eq!(
+                private_key_hex(&master.derive_path(path).unwrap()),
+                private_key,
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn derive_matches_step_by_step() {
+        let seed = [42u8; 32];
+        let by_path = derive(&seed, "m/7'/3").unwrap();
+        let master = ExtendedKey::master(&seed).unwrap();
+        let by_step = master
+            .derive_child(7 + HARDENED)
+            .unwrap()
+            .derive_child(3)
+            .unwrap();
+        assert_eq!(by_path.to_public_key(), by_step.keypair().to_public_key());
+        assert_eq!(
+            derive(&seed, "m").unwrap().to_public_key(),
+            master.keypair().to_public_key()
+        );
+    }
+
+    #[test]
+    fn bad_paths_and_seeds() {
+        assert_eq!(parse_path("m").unwrap(), Vec::<u32>::new());
+        assert_eq!(parse_path("1/2'").unwrap(), vec![1, 2 + HARDENED]);
+        for path in ["m/", "m//1", "m/x", "m/2147483648", "m/1''", "m0"] {
+            assert!(parse_path(path).is_err(), "{}", path);
+        }
+        assert!(ExtendedKey::master(&[0u8; 15]).is_err());
+        assert!(ExtendedKey::master(&[0u8; 65]).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! A public key that knows its own text forms
+//!
+//! The wire format is the compressed SEC1 encoding (33 bytes), written as
+//! Base64 by `Display` and serde, or as hex with `{:x}`. `FromStr` and
+//! `Deserialize` accept either, plus uncompressed SEC1.
+
+use crate::{KeyPair, VerifyOutcome};
+use base64::{engine::general_purpose, Engine as _};
+use k256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use std::fmt;
+use std::str::FromStr;
+
+/// A secp256k1 public (verifying) key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PublicKey(VerifyingKey);
+
+fn invalid_key() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid public key")
+}
+
+impl PublicKey {
+    /// Parse SEC1 bytes (compressed or uncompressed)
+    pub fn from_sec1_bytes(bytes: &[u8]) -> std::io::Result<Self> {
+        VerifyingKey::from_sec1_bytes(bytes)
+            .map(PublicKey)
+            .map_err(|_| invalid_key())
+    }
+
+    /// Parse Base64 of the SEC1 bytes
+    pub fn from_base64(s: &str) -> std::io::Result<Self> {
+        let bytes = general_purpose::STANDARD
+            .decode(s.trim())
+            .map_err(|_| invalid_key())?;
+        Self::from_sec1_bytes(&bytes)
+    }
+
+    /// Parse hex of the SEC1 bytes
+    pub fn from_hex(s: &str) -> std::io::Result<Self> {
+        let bytes = hex::decode(s.trim()).map_err(|_| invalid_key())?;
+        Self::from_sec1_bytes(&bytes)
+    }
+
+    /// Compressed SEC1 bytes (33 bytes), as written to `public_key.bin`
+    pub fn to_sec1_bytes(&self) -> Vec<u8> {
+        self.0.to_encoded_point(true).as_bytes().to_vec()
+    }
+
+    /// The underlying k256 key
+    pub fn verifying_key(&self) -> &VerifyingKey {
+        &self.0
+    }
+
+    /// Verify a signature over `message`
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+        self.0.verify(message, signature).is_ok()
+    }
+
+    /// Verify a raw (`r || s`) or DER signature over `message`. High-S DER
+    /// signatures are normalized first, as in `KeyPair::signature_from_der`.
+    pub fn verify_encoded(&self, message: &[u8], signature: &[u8]) -> VerifyOutcome {
+        let candidates = [
+            Signature::try_from(signature).ok(),
+            KeyPair::signature_from_der(signature).ok(),
+        ];
+        let mut parsed = candidates.iter().flatten().peekable();
+        if parsed.peek().is_none() {
+            return VerifyOutcome::BadEncoding;
+        }
+        if parsed.any(|sig| self.verify(message, sig)) {
+            VerifyOutcome::Ok
+        } else {
+            VerifyOutcome::BadSignature
+        }
+    }
+}
+
+/// Batches at least this big are split across threads (`parallel`); below
+/// it, spawning costs more than it saves
+#[cfg(feature = "parallel")]
+const PARALLEL_MIN_BATCH: usize = 64;
+
+impl PublicKey {
+    /// `verify_encoded` of every `(message, signature)` pair, in order.
+    /// With the `parallel` feature, large batches are spread over all cores.
+    pub fn verify_batch<M, S>(&self, items: &[(M, S)]) -> Vec<VerifyOutcome>
+    where
+        M: AsRef<[u8]> + Sync,
+        S: AsRef<[u8]> + Sync,
+    {
+        #[cfg(feature = "parallel")]
+        if items.len() >= PARALLEL_MIN_BATCH {
+            return self.verify_parallel(items);
+        }
+        self.verify_serial(items)
+    }
+
+    fn verify_serial<M: AsRef<[u8]>, S: AsRef<[u8]>>(
+        &self,
+        items: &[(M, S)],
+    ) -> Vec<VerifyOutcome> {
+        items
+            .iter()
+            .map(|(message, signature)| self.verify_encoded(message.as_ref(), signature.as_ref()))
+            .collect()
+    }
+
+    /// One contiguous chunk per thread, so the results come back in order
+    #[cfg(feature = "parallel")]
+    fn verify_parallel<M, S>(&self, items: &[(M, S)]) -> Vec<VerifyOutcome>
+    where
+        M: AsRef<[u8]> + Sync,
+        S: AsRef<[u8]> + Sync,
+    {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk = items.len().div_ceil(threads);
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = items
+                .chunks(chunk)
+                .map(|chunk| scope.spawn(move || self.verify_serial(chunk)))
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                })
+                .collect()
+        })
+    }
+}
+
+impl From<VerifyingKey> for PublicKey {
+    fn from(key: VerifyingKey) -> Self {
+        PublicKey(key)
+    }
+}
+
+/// Base64 of the compressed SEC1 bytes
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&general_purpose::STANDARD.encode(self.to_sec1_bytes()))
+    }
+}
+
+/// Hex of the compressed SEC1 bytes
+impl fmt::LowerHex for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.to_sec1_bytes()))
+    }
+}
+
+/// Hex if the string is nothing but hex digits (Base64 of a key never is in
+/// practice), Base64 otherwise
+impl FromStr for PublicKey {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.len().is_multiple_of(2) && s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            Self::from_hex(s)
+        } else {
+            Self::from_base64(s)
+        }
+    }
+}
+
+impl serde::Serialize for PublicKey {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for PublicKey {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
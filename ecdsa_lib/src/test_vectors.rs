@@ -0,0 +1,71 @@
+//! Known-answer test vectors
+//!
+//! Fixed private keys, messages and the exact signatures `KeyPair::sign`
+//! must produce for them. Because signing is deterministic (RFC 6979), other
+//! implementations (e.g. Python grading scripts) can use these to cross-check
+//! their byte formats: keys and signatures are hex, the private key is the
+//! 32-byte scalar, the public key is compressed SEC1 and the signature is
+//! raw `r || s` (64 bytes, low-S), over SHA-256 of `message`.
+//!
+//! The last vector of each key is in the VTS wire format, where the signed
+//! bytes are `message + time-signed`.
+
+/// One known-answer vector
+pub struct TestVector {
+    /// Hex-encoded 32-byte private scalar
+    pub private_key: &'static str,
+    /// Hex-encoded compressed SEC1 public key
+    pub public_key: &'static str,
+    /// Exact bytes that are signed
+    pub message: &'static [u8],
+    /// Hex-encoded `r || s` signature
+    pub signature: &'static str,
+}
+
+/// secp256k1 private key 1 (public key is the generator point)
+const KEY_ONE: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+const KEY_ONE_PUB: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+/// The private key used in the RFC 6979 appendix A.2.5 examples
+const KEY_RFC6979: &str = "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721";
+const KEY_RFC6979_PUB: &str = "032c8c31fc9f990c6b55e3865a184a4ce50e09481f2eaeb3e60ec1cea13a6ae645";
+
+/// All known-answer vectors
+pub const VECTORS: &[TestVector] = &[
+    TestVector {
+        private_key: KEY_ONE,
+        public_key: KEY_ONE_PUB,
+        message: b"",
+        signature: "77c8d336572f6f466055b5f70f433851f8f535f6c4fc71133a6cfd71079d03b70ed9f5eb8aa5b266abac35d416c3207e7a538bf5f37649727d7a9823b1069577",
+    },
+    TestVector {
+        private_key: KEY_ONE,
+        public_key: KEY_ONE_PUB,
+        message: b"sample",
+        signature: "58db657bcd631038bea07b4941172f0167aca98f12b55e3176bd1c35435d65013a78e73d8ff8ab554e13c10f6390d81a882f91945d6275493882676170b53a57",
+    },
+    TestVector {
+        private_key: KEY_ONE,
+        public_key: KEY_ONE_PUB,
+        message: b"Hello, VTS!2025-06-02T05:05:35.784383Z",
+        signature: "b6ba14d428af9348349cd030d69d33f266f87e7c454b09acb97b6d698f3b8cef7798f3630e73050e740639f5a999447fce7145ad56f43207115d2fce4719f12f",
+    },
+    TestVector {
+        private_key: KEY_RFC6979,
+        public_key: KEY_RFC6979_PUB,
+        message: b"",
+        signature: "725f4086bb24a2617205d2594c7dd8644b1e3329b90144ba65cea39cc9bff45d2b5457da4e41448045d484fc5ef401e13ebe92e46f7d8620e855a7625fbec41a",
+    },
+    TestVector {
+        private_key: KEY_RFC6979,
+        public_key: KEY_RFC6979_PUB,
+        message: b"sample",
+        signature: "432310e32cb80eb6503a26ce83cc165c783b870845fb8aad6d970889fcd7a6c8530128b6b81c548874a6305d93ed071ca6e05074d85863d4056ce89b02bfab69",
+    },
+    TestVector {
+        private_key: KEY_RFC6979,
+        public_key: KEY_RFC6979_PUB,
+        message: b"Hello, VTS!2025-06-02T05:05:35.784383Z",
+        signature: "740265f8f8d44f7bd9cdd31d52aa5b94f50b9366aab90947a0415728e6c04df65c047c9f6bba2c76ef315d73d14290a10cd28b59d7ff9bbcc14160fb78593249",
+    },
+];
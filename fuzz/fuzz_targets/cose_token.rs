@@ -0,0 +1,24 @@
+//! COSE_Sign1 tokens, as `lab4::cose::verify` reads them
+
+#![no_main]
+
+use ecdsa_lib::KeyPair;
+use lab4::EcdsaVerificationKey;
+use libfuzzer_sys::fuzz_target;
+use std::sync::LazyLock;
+
+static KEY: LazyLock<EcdsaVerificationKey> = LazyLock::new(|| EcdsaVerificationKey {
+    request: "GET".to_string(),
+    time_requested: String::new(),
+    public_key: KeyPair::from_seed(&[0x42; 32]).to_public_key().to_string(),
+    format: None,
+    signature_alg: None,
+    hash_alg: None,
+    kid: None,
+    not_before: None,
+    not_after: None,
+});
+
+fuzz_target!(|token: &[u8]| {
+    let _ = lab4::cose::verify(token, &KEY);
+});
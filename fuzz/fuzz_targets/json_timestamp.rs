@@ -0,0 +1,39 @@
+//! JSON bodies of `GET /key` and `POST /sign` / `POST /renew` responses,
+//! including renewals nested in `message`
+
+#![no_main]
+
+use lab4::ecdsa_requests::{VerifyOptions, verify_signature_with};
+use lab4::{EcdsaSignedTimestamp, EcdsaVerificationKey};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((key, signed)) = split(data) else {
+        return;
+    };
+    let key: Result<EcdsaVerificationKey, _> = serde_json::from_slice(key);
+    let Ok(signed) = serde_json::from_slice::<EcdsaSignedTimestamp>(signed) else {
+        return;
+    };
+    let _ = signed.algorithms();
+    let _ = signed.to_token();
+    if let Ok(key) = key {
+        let _ = key.algorithms();
+        let options = VerifyOptions {
+            require_canonical: true,
+            max_skew: Some(chrono::Duration::minutes(5)),
+            ..Default::default()
+        };
+        let _ = verify_signature_with(&signed, &key, &options);
+    }
+    let mut renewed = signed.renewed();
+    while let Some(inner) = renewed {
+        renewed = inner.renewed();
+    }
+});
+
+/// The key's JSON, then a NUL, then the timestamp's
+fn split(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let at = data.iter().position(|&b| b == 0)?;
+    Some((&data[..at], &data[at + 1..]))
+}
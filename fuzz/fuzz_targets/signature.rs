@@ -0,0 +1,15 @@
+//! Raw and DER signatures and SEC1 public keys, as bytes
+
+#![no_main]
+
+use ecdsa_lib::{KeyPair, PublicKey};
+use libfuzzer_sys::fuzz_target;
+use std::sync::LazyLock;
+
+static KEY: LazyLock<PublicKey> = LazyLock::new(|| KeyPair::from_seed(&[0x42; 32]).to_public_key());
+
+fuzz_target!(|data: &[u8]| {
+    let _ = PublicKey::from_sec1_bytes(data);
+    let _ = KeyPair::signature_from_der(data);
+    let _ = KEY.verify_encoded(b"Hello, VTS!2025-06-02T05:05:35.784383Z", data);
+});
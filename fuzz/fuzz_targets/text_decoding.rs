@@ -0,0 +1,48 @@
+//! The Base64 / hex fields of `GET /key` and `POST /sign` responses, and
+//! `time-signed`
+
+#![no_main]
+
+use lab4::ecdsa_requests::{VerifyOptions, verify_signature_with};
+use lab4::{EcdsaSignedTimestamp, EcdsaVerificationKey, wire};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|s: &str| {
+    let _ = s.parse::<ecdsa_lib::PublicKey>();
+    let _ = wire::parse_time(s);
+    let _ = wire::is_canonical(s);
+
+    for format in [None, Some("hex"), Some("base64"), Some("other")] {
+        let key = EcdsaVerificationKey {
+            request: "GET".to_string(),
+            time_requested: String::new(),
+            public_key: s.to_string(),
+            format: format.map(str::to_string),
+            signature_alg: None,
+            hash_alg: None,
+            kid: None,
+            not_before: None,
+            not_after: None,
+        };
+        let _ = key.key();
+        let signed = EcdsaSignedTimestamp {
+            request: "POST".to_string(),
+            message: s.to_string(),
+            message_b64: None,
+            time_signed: s.to_string(),
+            signature: s.to_string(),
+            accuracy: None,
+            format: format.map(str::to_string),
+            signature_alg: None,
+            hash_alg: None,
+            kid: None,
+            policy: None,
+        };
+        let _ = signed.signature_bytes();
+        let options = VerifyOptions {
+            require_canonical: true,
+            ..Default::default()
+        };
+        let _ = verify_signature_with(&signed, &key, &options);
+    }
+});
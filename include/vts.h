@@ -0,0 +1,64 @@
+/*
+ * vts.h: C interface to VTS timestamp verification
+ *
+ * Link against the lab4 cdylib built with the `ffi` feature:
+ *
+ *     cargo build --release --no-default-features --features ffi
+ *     cc app.c -Iinclude -Ltarget/release -llab4
+ *
+ * Keys are raw SEC1 bytes (public_key.bin, or the decoded "public-key" of
+ * GET /key). Nothing is allocated for the caller. Keep in sync with
+ * src/ffi.rs.
+ */
+#ifndef VTS_H
+#define VTS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define VTS_OK 0
+/* A required pointer is null */
+#define VTS_ERR_NULL (-1)
+/* The public key isn't a SEC1 secp256k1 point */
+#define VTS_ERR_BAD_KEY (-2)
+/* The signature is neither raw r || s nor DER, or the token is malformed */
+#define VTS_ERR_BAD_ENCODING (-3)
+/* Well-formed, but not signed by this key over this data */
+#define VTS_ERR_BAD_SIGNATURE (-4)
+/* A string argument isn't UTF-8 */
+#define VTS_ERR_BAD_UTF8 (-5)
+
+/* Claims of a verified JWS or COSE token */
+typedef struct VtsClaims {
+    uint8_t msg_hash[32]; /* SHA-256 of the timestamped message */
+    int64_t iat;          /* issue time, seconds since the Unix epoch */
+    uint64_t serial;      /* serial of the timestamp within its tenant */
+    uint8_t kid[32];      /* RFC 7638 thumbprint of the signing key */
+} VtsClaims;
+
+/* Checks sig (raw r || s or DER) over msg followed by time (the
+ * "time-signed" string) under pubkey. */
+int vts_verify_signature(const uint8_t *msg, size_t msg_len, const char *time,
+                         const uint8_t *sig, size_t sig_len,
+                         const uint8_t *pubkey, size_t pubkey_len);
+
+/* Verifies a compact JWS token; writes its claims to claims_out unless it
+ * is NULL. */
+int vts_verify_jws(const char *token, const uint8_t *pubkey, size_t pubkey_len,
+                   VtsClaims *claims_out);
+
+/* Verifies a COSE_Sign1 token; writes its claims to claims_out unless it
+ * is NULL. */
+int vts_verify_cose(const uint8_t *token, size_t token_len,
+                    const uint8_t *pubkey, size_t pubkey_len,
+                    VtsClaims *claims_out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* VTS_H */
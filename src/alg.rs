@@ -0,0 +1,143 @@
+//! Registry of the signature and hash algorithms the wire protocol names
+//!
+//! `GET /key` and `POST /sign` responses carry `signature-alg` and
+//! `hash-alg`, and a `/sign` request may ask for specific ones. Verifiers
+//! dispatch on them, so another curve or hash can be added here later
+//! without old clients misreading new signatures: a client that doesn't
+//! know an algorithm reports it as unsupported instead of failing to verify.
+//! Responses without the fields (older servers) mean the defaults.
+//!
+//! [`DigestAlg`] is separate: it is what a client hashed a file with before
+//! sending only the digest (`"digest-alg"`), and it ends up in the message
+//! the server signs.
+
+use serde::{Deserialize, Serialize};
+
+/// How `message + time-signed` is signed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub enum SignatureAlg {
+    /// ECDSA on secp256k1, the only algorithm so far
+    #[default]
+    #[serde(rename = "ecdsa-secp256k1")]
+    EcdsaSecp256k1,
+}
+
+/// What the signature algorithm hashes the signed bytes with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub enum HashAlg {
+    #[default]
+    #[serde(rename = "sha-256")]
+    Sha256,
+}
+
+/// Digest of a hash-then-sign request: the client hashes the content and
+/// sends `digest` and `digest-alg`, and the server timestamps the message
+/// `<name>:<hex digest>`, so the algorithm is signed along with the digest
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub enum DigestAlg {
+    #[default]
+    #[serde(rename = "sha-256")]
+    Sha256,
+    #[serde(rename = "sha-384")]
+    Sha384,
+    #[serde(rename = "sha-512")]
+    Sha512,
+    /// Hashing with it needs the `blake3` feature; checking the name and
+    /// length doesn't
+    #[serde(rename = "blake3")]
+    Blake3,
+}
+
+impl SignatureAlg {
+    /// Every algorithm this version knows
+    pub const SUPPORTED: &'static [SignatureAlg] = &[SignatureAlg::EcdsaSecp256k1];
+
+    /// The name on the wire, e.g. `"ecdsa-secp256k1"`
+    pub fn name(&self) -> &'static str {
+        match self {
+            SignatureAlg::EcdsaSecp256k1 => "ecdsa-secp256k1",
+        }
+    }
+
+    /// The algorithm called `name`, `None` if it isn't supported
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::SUPPORTED
+            .iter()
+            .copied()
+            .find(|alg| alg.name() == name)
+    }
+}
+
+impl HashAlg {
+    /// Every algorithm this version knows
+    pub const SUPPORTED: &'static [HashAlg] = &[HashAlg::Sha256];
+
+    /// The name on the wire, e.g. `"sha-256"` (as in the IANA hash registry)
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlg::Sha256 => "sha-256",
+        }
+    }
+
+    /// The algorithm called `name`, `None` if it isn't supported
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::SUPPORTED
+            .iter()
+            .copied()
+            .find(|alg| alg.name() == name)
+    }
+}
+
+impl DigestAlg {
+    /// Every algorithm this version knows
+    pub const SUPPORTED: &'static [DigestAlg] = &[
+        DigestAlg::Sha256,
+        DigestAlg::Sha384,
+        DigestAlg::Sha512,
+        DigestAlg::Blake3,
+    ];
+
+    /// The name on the wire, e.g. `"sha-384"`
+    pub fn name(&self) -> &'static str {
+        match self {
+            DigestAlg::Sha256 => "sha-256",
+            DigestAlg::Sha384 => "sha-384",
+            DigestAlg::Sha512 => "sha-512",
+            DigestAlg::Blake3 => "blake3",
+        }
+    }
+
+    /// The algorithm called `name`, `None` if it isn't supported
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::SUPPORTED
+            .iter()
+            .copied()
+            .find(|alg| alg.name() == name)
+    }
+
+    /// Length of a digest, in bytes
+    pub fn digest_len(&self) -> usize {
+        match self {
+            DigestAlg::Sha256 | DigestAlg::Blake3 => 32,
+            DigestAlg::Sha384 => 48,
+            DigestAlg::Sha512 => 64,
+        }
+    }
+
+    /// The message the server timestamps for `digest`: `<name>:<hex>`
+    pub fn message(&self, digest: &[u8]) -> String {
+        format!("{}:{}", self.name(), hex::encode(digest))
+    }
+
+    /// The algorithm and digest of a [`Self::message`], `None` for any
+    /// other message
+    pub fn parse_message(message: &str) -> Option<(Self, Vec<u8>)> {
+        let (name, digest) = message.split_once(':')?;
+        let alg = Self::from_name(name)?;
+        let digest = hex::decode(digest).ok()?;
+        (digest.len() == alg.digest_len()).then_some((alg, digest))
+    }
+}
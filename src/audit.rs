@@ -0,0 +1,1222 @@
+//! Signed, hash-chained audit log
+//!
+//! With `audit_file` set, every issued timestamp appends one JSON line to
+//! the file: what kind of request it was, for which client, the message hash,
+//! serial and `time-signed`. Each line carries the SHA-256 of the line before
+//! it (`prev`) and the default tenant's signature over everything else
+//! (`sig`), so editing, dropping or reordering lines after the fact breaks
+//! the chain. `vts-audit verify` checks a whole file.
+//!
+//! The log is also the server's record of what it has timestamped: lines
+//! are indexed by message hash for `GET /timestamp/by-hash/{sha256}`, which
+//! hands them out as proofs.
+//!
+//! `export` packs the lines after a given `seq` into a signed, compressed
+//! archive and `import` appends one to another copy of the log (a backup or
+//! a mirror) after checking it (`vts-admin export` / `vts-admin import`).
+//!
+//! `AuditLog::compact` (the `[retention]` job) moves the oldest lines into
+//! `<audit_file>.compacted`, keeping what identifies each timestamp (hash,
+//! time, serial, ...) and the hash of its line, but not its signatures. A
+//! signed [`Checkpoint`] carries the Merkle root (see `merkle`) over the
+//! compacted entries, so each one can still be proven ([`CompactedProof`])
+//! and the chain still links through to the lines that remain.
+//!
+//! Lines also go into one Merkle tree per UTC day (see `day`), whose signed
+//! [`DayRoot`] is served at `GET /log/day/{date}` once the day is over,
+//! and into one tree over the whole log, whose signed [`TreeHead`] peers
+//! co-sign, and whose growth `consistency_proof` proves (see
+//! `transparency`).
+
+mod day;
+
+pub use crate::transparency::{ConsistencyProof, Cosignature, InclusionProof, TreeHead};
+pub use day::{DayProof, DayRoot};
+
+use crate::clock::replace_file;
+use crate::signer::Signer;
+use crate::{jws, merkle, wire};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, NaiveDate, Utc};
+use day::Days;
+use ecdsa_lib::{KeyPair, PublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+
+/// `prev` of the first line
+pub const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    /// Line number, from 1
+    pub seq: u64,
+    /// `time-signed` of the timestamp
+    pub time: String,
+    /// `sign` or `renew` (with `_jws` / `_cose` for tokens), or `grpc_sign`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// `None` for the default tenant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// IP address of the peer, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    /// Hex SHA-256 of the message
+    pub hash: String,
+    /// Serial of the timestamp within its tenant
+    pub serial: u64,
+    /// Base64 signature over `message + time`, as issued (absent from lines
+    /// written before it was recorded)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// `[policies]` name the timestamp was issued under, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    /// Key id of the key that signed this line
+    pub kid: String,
+    /// Hex SHA-256 of the previous line (`GENESIS` for the first)
+    pub prev: String,
+    /// Base64 DER signature over the line without `sig`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sig: Option<String>,
+}
+
+impl AuditEntry {
+    /// The bytes `sig` is over: this entry as JSON, without `sig`
+    fn signed_bytes(&self) -> Vec<u8> {
+        let unsigned = AuditEntry {
+            sig: None,
+            ..self.clone()
+        };
+        serde_json::to_vec(&unsigned).expect("audit entry serializes")
+    }
+
+    /// SHA-256 of this entry's line as written (the next line's `prev`)
+    pub fn line_hash(&self) -> [u8; 32] {
+        Sha256::digest(serde_json::to_vec(self).expect("audit entry serializes")).into()
+    }
+
+    /// Whether `sig` is `public_key`'s signature over this line. Says
+    /// nothing about its place in the chain; `verify_file` checks that.
+    pub fn verify(&self, public_key: &PublicKey) -> bool {
+        self.sig
+            .as_deref()
+            .and_then(|sig| general_purpose::STANDARD.decode(sig).ok())
+            .and_then(|der| KeyPair::signature_from_der(&der).ok())
+            .is_some_and(|signature| public_key.verify(&self.signed_bytes(), &signature))
+    }
+}
+
+/// What a request adds to the log; the chain fields are filled in by `append`
+pub struct AuditRecord<'a> {
+    pub time: &'a str,
+    pub kind: &'a str,
+    pub tenant: Option<&'a str>,
+    pub client: Option<String>,
+    pub hash: &'a str,
+    pub serial: u64,
+    /// Base64 signature of the timestamp
+    pub signature: Option<String>,
+    pub policy: Option<&'a str>,
+}
+
+/// What compaction keeps of a line: everything but the signatures and the
+/// client address
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CompactedEntry {
+    pub seq: u64,
+    pub time: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub hash: String,
+    pub serial: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    /// Hex SHA-256 of the original line (the next line's `prev`), so an
+    /// archived copy of it can still be checked
+    pub line: String,
+}
+
+impl CompactedEntry {
+    fn new(entry: &AuditEntry, line: &str) -> Self {
+        Self {
+            seq: entry.seq,
+            time: entry.time.clone(),
+            kind: entry.kind.clone(),
+            tenant: entry.tenant.clone(),
+            hash: entry.hash.clone(),
+            serial: entry.serial,
+            policy: entry.policy.clone(),
+            line: hex::encode(Sha256::digest(line.as_bytes())),
+        }
+    }
+
+    /// This entry's leaf of the checkpoint's Merkle tree: its JSON line
+    pub fn leaf(&self) -> [u8; 32] {
+        merkle::leaf_hash(
+            serde_json::to_string(self)
+                .expect("compacted entry serializes")
+                .as_bytes(),
+        )
+    }
+}
+
+/// First line of the compacted file, signing the entries after it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Checkpoint {
+    /// Entries compacted: `seq` 1 to `count`
+    pub count: u64,
+    /// Hex Merkle root over the entries' `leaf`s, in `seq` order
+    pub root: String,
+    /// When the compaction that wrote it ran
+    pub time: String,
+    /// Key id of the key that signed it
+    pub kid: String,
+    /// Base64 DER signature over the checkpoint without `sig`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sig: Option<String>,
+}
+
+impl Checkpoint {
+    fn signed_bytes(&self) -> Vec<u8> {
+        let unsigned = Checkpoint {
+            sig: None,
+            ..self.clone()
+        };
+        serde_json::to_vec(&unsigned).expect("checkpoint serializes")
+    }
+
+    /// Whether `sig` is `public_key`'s signature over this checkpoint
+    pub fn verify(&self, public_key: &PublicKey) -> bool {
+        self.kid == jws::key_id(public_key)
+            && self
+                .sig
+                .as_deref()
+                .and_then(|sig| general_purpose::STANDARD.decode(sig).ok())
+                .and_then(|der| KeyPair::signature_from_der(&der).ok())
+                .is_some_and(|signature| public_key.verify(&self.signed_bytes(), &signature))
+    }
+}
+
+/// A compacted entry, with what shows the checkpoint covers it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CompactedProof {
+    pub entry: CompactedEntry,
+    /// Hex sibling hashes from the entry's leaf (index `seq - 1`) up to
+    /// `checkpoint.root`, bottom first
+    pub proof: Vec<String>,
+    pub checkpoint: Checkpoint,
+}
+
+impl CompactedProof {
+    /// Whether the checkpoint is signed by `public_key` and covers `entry`
+    pub fn verify(&self, public_key: &PublicKey) -> bool {
+        let proof: Option<Vec<[u8; 32]>> = self
+            .proof
+            .iter()
+            .map(|hash| hex::decode(hash).ok()?.try_into().ok())
+            .collect();
+        let root: Option<[u8; 32]> = hex::decode(&self.checkpoint.root)
+            .ok()
+            .and_then(|root| root.try_into().ok());
+        let (Some(proof), Some(root)) = (proof, root) else {
+            return false;
+        };
+        self.checkpoint.verify(public_key)
+            && self.entry.seq >= 1
+            && merkle::verify_inclusion(
+                &self.entry.leaf(),
+                self.entry.seq - 1,
+                self.checkpoint.count,
+                &proof,
+                &root,
+            )
+    }
+}
+
+/// Where the compacted entries of the log at `path` are kept
+pub fn compacted_path(path: impl AsRef<Path>) -> PathBuf {
+    let mut name = path.as_ref().as_os_str().to_owned();
+    name.push(".compacted");
+    PathBuf::from(name)
+}
+
+/// The compacted file of a log, read and checked
+#[derive(Default)]
+struct Compacted {
+    checkpoint: Option<Checkpoint>,
+    entries: Vec<CompactedEntry>,
+    leaves: Vec<[u8; 32]>,
+}
+
+impl Compacted {
+    /// The compacted file of the log at `path`, checked against
+    /// `public_key`; empty if there is none
+    fn read(path: &Path, public_key: &PublicKey) -> std::io::Result<Self> {
+        let contents = match fs::read_to_string(compacted_path(path)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut lines = contents.split_terminator('\n');
+        let checkpoint: Checkpoint = lines
+            .next()
+            .and_then(|line| serde_json::from_str(line).ok())
+            .ok_or_else(|| invalid("Compacted log has no checkpoint".to_string()))?;
+        if !checkpoint.verify(public_key) {
+            return Err(invalid(
+                "Compacted log checkpoint is not signed by the key".to_string(),
+            ));
+        }
+        let mut compacted = Self::default();
+        for (i, line) in lines.enumerate() {
+            let seq = i as u64 + 1;
+            let entry: CompactedEntry = serde_json::from_str(line)
+                .map_err(|e| invalid(format!("Compacted entry {}: {}", seq, e)))?;
+            if entry.seq != seq {
+                return Err(invalid(format!(
+                    "Compacted entry {}: seq is {}",
+                    seq, entry.seq
+                )));
+            }
+            compacted.leaves.push(entry.leaf());
+            compacted.entries.push(entry);
+        }
+        if compacted.entries.len() as u64 != checkpoint.count
+            || hex::encode(merkle::root(&compacted.leaves)) != checkpoint.root
+            || !contents.ends_with('\n')
+        {
+            return Err(invalid(
+                "Compacted entries don't match the checkpoint".to_string(),
+            ));
+        }
+        compacted.checkpoint = Some(checkpoint);
+        Ok(compacted)
+    }
+
+    /// The `seq` and hash of the last compacted line, where the log file
+    /// carries on from
+    fn base(&self) -> (u64, String) {
+        match self.entries.last() {
+            Some(last) => (last.seq, last.line.clone()),
+            None => (0, GENESIS.to_string()),
+        }
+    }
+
+    /// `contents` without the lines at its start that are compacted
+    /// already: a compaction that stopped between its two writes left them
+    fn strip<'a>(&self, contents: &'a str) -> std::io::Result<&'a str> {
+        let mut rest = contents;
+        while let Some((line, after)) = rest.split_once('\n') {
+            let Ok(entry) = serde_json::from_str::<AuditEntry>(line) else {
+                break;
+            };
+            if entry.seq == 0 || entry.seq > self.entries.len() as u64 {
+                break;
+            }
+            if self.entries[entry.seq as usize - 1].line
+                != hex::encode(Sha256::digest(line.as_bytes()))
+            {
+                return Err(invalid(format!(
+                    "Line {} differs from its compacted entry",
+                    entry.seq
+                )));
+            }
+            rest = after;
+        }
+        Ok(rest)
+    }
+}
+
+/// The log at `path`: its compacted entries and the lines after them
+fn read_log(path: &Path, public_key: &PublicKey) -> std::io::Result<(Compacted, String)> {
+    let compacted = Compacted::read(path, public_key)?;
+    let contents = fs::read_to_string(path)?;
+    let full = compacted.strip(&contents)?.to_string();
+    Ok((compacted, full))
+}
+
+/// Where the chain stands: the open file, last `seq` and hash of the last
+/// line, every line in the file by message hash, and what was compacted
+struct Chain {
+    file: File,
+    seq: u64,
+    prev: String,
+    by_hash: HashMap<String, Vec<AuditEntry>>,
+    compacted: Compacted,
+    /// Indexes into `compacted.entries`, by message hash
+    compacted_by_hash: HashMap<String, Vec<usize>>,
+    /// Every line's leaf, compacted or not, by UTC day
+    days: Days,
+    /// The last head signed (or adopted from an upstream), served again
+    /// while no line was added since
+    head: Option<TreeHead>,
+}
+
+/// The audit log the server appends to
+pub struct AuditLog {
+    path: PathBuf,
+    signer: Arc<dyn Signer>,
+    public_key: PublicKey,
+    kid: String,
+    chain: Mutex<Chain>,
+}
+
+impl AuditLog {
+    /// Opens `path` for appending (creating it owner-only), continuing the
+    /// chain from its last line (or from its compacted entries)
+    pub fn open(path: &str, signer: Arc<dyn Signer>) -> std::io::Result<Self> {
+        // 1) Resume from the last line, which must be whole
+        let public_key = signer.public_key();
+        let compacted = Compacted::read(Path::new(path), &public_key)?;
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let full = compacted.strip(&contents)?;
+        if full.len() != contents.len() {
+            replace_file(Path::new(path), full.as_bytes())?;
+        }
+        let (seq, prev) = match full {
+            "" => compacted.base(),
+            contents => {
+                let last = contents
+                    .strip_suffix('\n')
+                    .and_then(|rest| rest.rsplit('\n').next())
+                    .and_then(|line| Some((line, serde_json::from_str::<AuditEntry>(line).ok()?)))
+                    .ok_or_else(|| {
+                        invalid(format!(
+                            "Last line of {} is not a whole audit entry (check it with vts-audit verify)",
+                            path
+                        ))
+                    })?;
+                (last.1.seq, hex::encode(Sha256::digest(last.0.as_bytes())))
+            }
+        };
+
+        // 2) Index what is already there (lines that don't parse are for
+        // `vts-audit verify` to report)
+        let mut days = Days::default();
+        for entry in &compacted.entries {
+            let line_hash = hex::decode(&entry.line)
+                .ok()
+                .and_then(|hash| hash.try_into().ok())
+                .ok_or_else(|| invalid(format!("Compacted entry {}: bad line hash", entry.seq)))?;
+            days.push(&entry.time, entry.seq, line_hash)?;
+        }
+        let mut by_hash: HashMap<String, Vec<AuditEntry>> = HashMap::new();
+        for (line, entry) in full
+            .lines()
+            .filter_map(|line| Some((line, serde_json::from_str::<AuditEntry>(line).ok()?)))
+        {
+            days.push(
+                &entry.time,
+                entry.seq,
+                Sha256::digest(line.as_bytes()).into(),
+            )?;
+            by_hash.entry(entry.hash.clone()).or_default().push(entry);
+        }
+        let compacted_by_hash = index_compacted(&compacted.entries);
+
+        // 3) Open for appending only
+        let file = open_append(Path::new(path))?;
+
+        Ok(Self {
+            path: PathBuf::from(path),
+            kid: jws::key_id(&public_key),
+            public_key,
+            signer,
+            chain: Mutex::new(Chain {
+                file,
+                seq,
+                prev,
+                by_hash,
+                compacted,
+                compacted_by_hash,
+                days,
+                head: None,
+            }),
+        })
+    }
+
+    /// Key id of the key the log is signed with
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    /// `seq` of the last line (0 while the log is empty)
+    pub fn last_seq(&self) -> u64 {
+        self.chain.lock().unwrap().seq
+    }
+
+    /// Up to `limit` lines after `seq` `since`, exactly as in the file.
+    /// Compacted lines are gone: asking for them is a `NotFound` error.
+    pub fn lines_after(&self, since: u64, limit: usize) -> std::io::Result<Vec<String>> {
+        // Under the lock, so no line is read half-written
+        let chain = self.chain.lock().unwrap();
+        let (base, _) = chain.compacted.base();
+        if since < base {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Entries up to {} are compacted", base),
+            ));
+        }
+        let contents = fs::read_to_string(&self.path)?;
+        Ok(contents
+            .split_terminator('\n')
+            .skip((since - base).try_into().unwrap_or(usize::MAX))
+            .take(limit)
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Appends `lines` written by another server (a mirror's copy of the
+    /// upstream log), exactly as they are. They must continue the chain:
+    /// the next `seq`s, the first `prev` the hash of the last line here, and
+    /// each signed by this log's key. Nothing is written if one doesn't.
+    pub fn extend(&self, lines: &[String]) -> std::io::Result<Vec<AuditEntry>> {
+        let mut chain = self.chain.lock().unwrap();
+        let (mut seq, mut prev) = (chain.seq, chain.prev.clone());
+        let mut entries = Vec::with_capacity(lines.len());
+        for line in lines {
+            seq += 1;
+            let entry = check_line(line, seq, &prev, &self.kid, &self.public_key)
+                .map_err(|reason| invalid(format!("Line {}: {}", seq, reason)))?;
+            let line_hash: [u8; 32] = Sha256::digest(line.as_bytes()).into();
+            prev = hex::encode(line_hash);
+            entries.push((entry, line_hash));
+        }
+
+        let appended: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        chain.file.write_all(appended.as_bytes())?;
+        chain.file.sync_data()?;
+        chain.seq = seq;
+        chain.prev = prev;
+        for (entry, line_hash) in &entries {
+            chain.days.push(&entry.time, entry.seq, *line_hash)?;
+            chain
+                .by_hash
+                .entry(entry.hash.clone())
+                .or_default()
+                .push(entry.clone());
+        }
+        Ok(entries.into_iter().map(|(entry, _)| entry).collect())
+    }
+
+    /// `export` of the `count` lines after `seq` `since`, signed with this
+    /// log's key: one sealed segment of it, and its manifest
+    pub fn export_segment(
+        &self,
+        since: u64,
+        count: u64,
+    ) -> std::io::Result<(ArchiveManifest, Vec<u8>)> {
+        // Under the lock, so no line is read half-written
+        let chain = self.chain.lock().unwrap();
+        if since + count > chain.seq {
+            return Err(invalid(format!(
+                "The log has {} entries, not {}",
+                chain.seq,
+                since + count
+            )));
+        }
+        let contents = fs::read_to_string(&self.path)?;
+        let base = chain.compacted.base();
+        export_contents(&contents, &base, since, count, self.signer.as_ref())
+    }
+
+    /// Moves the oldest lines, those written before `before` and at most up
+    /// to `seq` `up_to`, into the compacted file, signing a new checkpoint
+    /// over all compacted entries at `now`. Returns how many were moved.
+    pub fn compact(&self, before: DateTime<Utc>, up_to: u64, now: &str) -> std::io::Result<u64> {
+        let mut chain = self.chain.lock().unwrap();
+
+        // 1) The lines to move: a run from the start of the file
+        let contents = fs::read_to_string(&self.path)?;
+        let mut moved = Vec::new();
+        let mut rest = contents.as_str();
+        while let Some((line, after)) = rest.split_once('\n') {
+            let entry: AuditEntry = serde_json::from_str(line)
+                .map_err(|e| invalid(format!("Not an audit entry: {}", e)))?;
+            let old = wire::parse_time(&entry.time).is_ok_and(|time| time < before);
+            if !old || entry.seq > up_to {
+                break;
+            }
+            moved.push(CompactedEntry::new(&entry, line));
+            rest = after;
+        }
+        if moved.is_empty() {
+            return Ok(0);
+        }
+
+        // 2) Sign a checkpoint over everything compacted so far
+        let mut entries = chain.compacted.entries.clone();
+        let mut leaves = chain.compacted.leaves.clone();
+        leaves.extend(moved.iter().map(CompactedEntry::leaf));
+        entries.extend(moved);
+        let mut checkpoint = Checkpoint {
+            count: entries.len() as u64,
+            root: hex::encode(merkle::root(&leaves)),
+            time: now.to_string(),
+            kid: self.kid.clone(),
+            sig: None,
+        };
+        let signature = self
+            .signer
+            .sign(&checkpoint.signed_bytes())
+            .map_err(std::io::Error::other)?;
+        checkpoint.sig =
+            Some(general_purpose::STANDARD.encode(KeyPair::signature_to_der(&signature)));
+
+        // 3) The compacted file first, so a crash before the log file is
+        // rewritten only leaves lines `open` drops again
+        let mut text = format!(
+            "{}\n",
+            serde_json::to_string(&checkpoint).expect("checkpoint serializes")
+        );
+        for entry in &entries {
+            text.push_str(&serde_json::to_string(entry).expect("compacted entry serializes"));
+            text.push('\n');
+        }
+        replace_file(&compacted_path(&self.path), text.as_bytes())?;
+        replace_file(&self.path, rest.as_bytes())?;
+        chain.file = open_append(&self.path)?;
+
+        let count = checkpoint.count;
+        let moved = count - chain.compacted.entries.len() as u64;
+        chain.by_hash.retain(|_, found| {
+            found.retain(|entry| entry.seq > count);
+            !found.is_empty()
+        });
+        chain.compacted_by_hash = index_compacted(&entries);
+        chain.compacted = Compacted {
+            checkpoint: Some(checkpoint),
+            entries,
+            leaves,
+        };
+        Ok(moved)
+    }
+
+    /// The signed root of the UTC day `date`, which must be over: no line
+    /// may be written for it any more
+    pub fn day_root(&self, date: NaiveDate) -> std::io::Result<DayRoot> {
+        let mut chain = self.chain.lock().unwrap();
+        if let Some(signed) = chain.days.signed(date) {
+            return Ok(signed);
+        }
+        let (count, root) = chain.days.root(date);
+        let mut day_root = DayRoot {
+            date: date.to_string(),
+            count,
+            root: hex::encode(root),
+            kid: self.kid.clone(),
+            sig: None,
+        };
+        let signature = self
+            .signer
+            .sign(&day_root.signed_bytes())
+            .map_err(std::io::Error::other)?;
+        day_root.sig =
+            Some(general_purpose::STANDARD.encode(KeyPair::signature_to_der(&signature)));
+        chain.days.set_signed(date, day_root.clone());
+        Ok(day_root)
+    }
+
+    /// The signed root of `date`, if it was signed (or adopted) since its
+    /// last line
+    pub fn signed_day_root(&self, date: NaiveDate) -> Option<DayRoot> {
+        self.chain.lock().unwrap().days.signed(date)
+    }
+
+    /// Days a later line shows are over that have no signed root yet
+    pub fn unsigned_days(&self) -> Vec<NaiveDate> {
+        let chain = self.chain.lock().unwrap();
+        let mut dates = chain.days.closed();
+        dates.retain(|date| chain.days.signed(*date).is_none());
+        dates
+    }
+
+    /// Keeps `root`, signed elsewhere with this log's key (by a mirror's
+    /// upstream), as the signed root of its day. It must verify and have
+    /// that day's count and root here; `InvalidData` if not.
+    pub fn adopt_day_root(&self, root: DayRoot) -> std::io::Result<()> {
+        let date = NaiveDate::parse_from_str(&root.date, "%Y-%m-%d")
+            .map_err(|_| invalid(format!("Day root for {}: bad date", root.date)))?;
+        if !root.verify(&self.public_key) {
+            return Err(invalid(format!("Day root for {}: bad signature", date)));
+        }
+        let mut chain = self.chain.lock().unwrap();
+        let (count, hash) = chain.days.root(date);
+        if (root.count, root.root.as_str()) != (count, hex::encode(hash).as_str()) {
+            return Err(invalid(format!(
+                "Day root for {}: another tree than the {} lines here",
+                date, count
+            )));
+        }
+        chain.days.set_signed(date, root);
+        Ok(())
+    }
+
+    /// The Merkle root of the UTC day `date`, `None` if it has no lines
+    pub fn day_root_hash(&self, date: NaiveDate) -> Option<[u8; 32]> {
+        let mut chain = self.chain.lock().unwrap();
+        chain.days.has_lines(date).then(|| chain.days.root(date).1)
+    }
+
+    /// Where line `seq` sits in its day's tree, and which day that is
+    pub fn day_proof(&self, seq: u64) -> Option<(NaiveDate, DayProof)> {
+        self.chain.lock().unwrap().days.proof(seq)
+    }
+
+    /// The signed head over every line so far, signed at `now` unless no
+    /// line was added since the last one
+    pub fn tree_head(&self, now: &str) -> std::io::Result<TreeHead> {
+        let mut chain = self.chain.lock().unwrap();
+        let leaves: Vec<[u8; 32]> = chain.days.leaves().copied().collect();
+        if let Some(head) = &chain.head
+            && head.count == leaves.len() as u64
+        {
+            return Ok(head.clone());
+        }
+        let mut head = TreeHead {
+            count: leaves.len() as u64,
+            root: hex::encode(merkle::root(&leaves)),
+            time: now.to_string(),
+            kid: self.kid.clone(),
+            sig: None,
+        };
+        head.sig = Some(self.sign(&head.signed_bytes())?);
+        chain.head = Some(head.clone());
+        Ok(head)
+    }
+
+    /// The last head signed or adopted, however many lines came since
+    pub fn signed_head(&self) -> Option<TreeHead> {
+        self.chain.lock().unwrap().head.clone()
+    }
+
+    /// Keeps `head`, signed elsewhere with this log's key (by a mirror's
+    /// upstream), as the head of this log's first `head.count` lines. It
+    /// must verify and have their root; `InvalidData` if not.
+    pub fn adopt_head(&self, head: TreeHead) -> std::io::Result<()> {
+        if !head.verify(&self.public_key) {
+            return Err(invalid(format!(
+                "Head at {} lines: bad signature",
+                head.count
+            )));
+        }
+        let mut chain = self.chain.lock().unwrap();
+        let leaves: Vec<[u8; 32]> = chain.days.leaves().copied().collect();
+        let matches = usize::try_from(head.count)
+            .ok()
+            .filter(|count| *count <= leaves.len())
+            .is_some_and(|count| hex::encode(merkle::root(&leaves[..count])) == head.root);
+        if !matches {
+            return Err(invalid(format!(
+                "Head at {} lines: another tree than the {} lines here",
+                head.count,
+                leaves.len()
+            )));
+        }
+        chain.head = Some(head);
+        Ok(())
+    }
+
+    /// The Merkle root over the first `count` lines, `None` if there are
+    /// fewer
+    pub fn root_at(&self, count: u64) -> Option<[u8; 32]> {
+        let chain = self.chain.lock().unwrap();
+        let leaves: Vec<[u8; 32]> = chain.days.leaves().copied().collect();
+        let count = usize::try_from(count).ok()?;
+        (count <= leaves.len()).then(|| merkle::root(&leaves[..count]))
+    }
+
+    /// That the log at `new` lines extends the log at `old` lines, `None`
+    /// if `old` is past `new` or the log has fewer than `new` lines
+    pub fn consistency_proof(&self, old: u64, new: u64) -> Option<ConsistencyProof> {
+        let chain = self.chain.lock().unwrap();
+        let leaves: Vec<[u8; 32]> = chain.days.leaves().copied().collect();
+        let (old_len, new_len) = (usize::try_from(old).ok()?, usize::try_from(new).ok()?);
+        if old_len > new_len || new_len > leaves.len() {
+            return None;
+        }
+        let proof = merkle::consistency_proof(&leaves[..new_len], old_len);
+        Some(ConsistencyProof {
+            old,
+            new,
+            proof: proof.iter().map(hex::encode).collect(),
+        })
+    }
+
+    /// Line `seq` as written, with where it sits in the tree over the first
+    /// `count` lines; `None` unless it is one of them and the log has
+    /// `count`. A compacted line is gone: a `NotFound` error.
+    pub fn inclusion_proof(&self, seq: u64, count: u64) -> std::io::Result<Option<InclusionProof>> {
+        if seq == 0 || seq > count {
+            return Ok(None);
+        }
+        let Some(line) = self.lines_after(seq - 1, 1)?.pop() else {
+            return Ok(None);
+        };
+        let chain = self.chain.lock().unwrap();
+        let leaves: Vec<[u8; 32]> = chain.days.leaves().copied().collect();
+        let (Ok(index), Ok(count_len)) = (usize::try_from(seq - 1), usize::try_from(count)) else {
+            return Ok(None);
+        };
+        if count_len > leaves.len() {
+            return Ok(None);
+        }
+        let proof = merkle::inclusion_proof(&leaves[..count_len], index);
+        Ok(Some(InclusionProof {
+            seq,
+            count,
+            line,
+            proof: proof.iter().map(hex::encode).collect(),
+        }))
+    }
+
+    /// This log's key vouching, at `now`, for another log's `head` (check
+    /// the head first)
+    pub fn cosign(&self, head: &TreeHead, now: &str) -> std::io::Result<Cosignature> {
+        let mut cosignature = Cosignature {
+            log: head.kid.clone(),
+            count: head.count,
+            root: head.root.clone(),
+            time: now.to_string(),
+            kid: self.kid.clone(),
+            sig: None,
+        };
+        cosignature.sig = Some(self.sign(&cosignature.signed_bytes())?);
+        Ok(cosignature)
+    }
+
+    /// Base64 DER signature over `bytes` with the log's key
+    fn sign(&self, bytes: &[u8]) -> std::io::Result<String> {
+        let signature = self.signer.sign(bytes).map_err(std::io::Error::other)?;
+        Ok(general_purpose::STANDARD.encode(KeyPair::signature_to_der(&signature)))
+    }
+
+    /// The latest checkpoint, `None` if nothing was compacted yet
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        self.chain.lock().unwrap().compacted.checkpoint.clone()
+    }
+
+    /// Every compacted entry for the message with hex SHA-256 `hash`,
+    /// oldest first, each with its proof against the latest checkpoint
+    pub fn compacted_by_hash(&self, hash: &str) -> Vec<CompactedProof> {
+        let chain = self.chain.lock().unwrap();
+        let compacted = &chain.compacted;
+        let Some(checkpoint) = &compacted.checkpoint else {
+            return Vec::new();
+        };
+        chain
+            .compacted_by_hash
+            .get(hash)
+            .into_iter()
+            .flatten()
+            .map(|&i| CompactedProof {
+                entry: compacted.entries[i].clone(),
+                proof: merkle::inclusion_proof(&compacted.leaves, i)
+                    .iter()
+                    .map(hex::encode)
+                    .collect(),
+                checkpoint: checkpoint.clone(),
+            })
+            .collect()
+    }
+
+    /// The highest serial `tenant` (`None` for the default one) has in the
+    /// log, compacted entries included; 0 if none
+    pub fn last_serial(&self, tenant: Option<&str>) -> u64 {
+        let chain = self.chain.lock().unwrap();
+        let logged = chain
+            .by_hash
+            .values()
+            .flatten()
+            .map(|e| (&e.tenant, e.serial));
+        let compacted = chain
+            .compacted
+            .entries
+            .iter()
+            .map(|e| (&e.tenant, e.serial));
+        logged
+            .chain(compacted)
+            .filter(|(t, _)| t.as_deref() == tenant)
+            .map(|(_, serial)| serial)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Every line for the message with hex SHA-256 `hash`, oldest first
+    pub fn by_hash(&self, hash: &str) -> Vec<AuditEntry> {
+        let chain = self.chain.lock().unwrap();
+        chain.by_hash.get(hash).cloned().unwrap_or_default()
+    }
+
+    /// `seq` of the line of `tenant`'s timestamp `serial`, compacted or not
+    pub fn seq_of_serial(&self, tenant: Option<&str>, serial: u64) -> Option<u64> {
+        let chain = self.chain.lock().unwrap();
+        let compacted = chain
+            .compacted
+            .entries
+            .iter()
+            .map(|entry| (entry.seq, entry.serial, entry.tenant.as_deref()));
+        let whole = chain
+            .by_hash
+            .values()
+            .flatten()
+            .map(|entry| (entry.seq, entry.serial, entry.tenant.as_deref()));
+        compacted
+            .chain(whole)
+            .find(|(_, found, of)| *found == serial && *of == tenant)
+            .map(|(seq, _, _)| seq)
+    }
+
+    /// Signs `record` into the next line and appends it, synced to disk
+    /// before this returns
+    pub fn append(&self, record: AuditRecord) -> std::io::Result<AuditEntry> {
+        let mut chain = self.chain.lock().unwrap();
+        let mut entry = AuditEntry {
+            seq: chain.seq + 1,
+            time: record.time.to_string(),
+            kind: record.kind.to_string(),
+            tenant: record.tenant.map(str::to_string),
+            client: record.client,
+            hash: record.hash.to_string(),
+            serial: record.serial,
+            signature: record.signature,
+            policy: record.policy.map(str::to_string),
+            kid: self.kid.clone(),
+            prev: chain.prev.clone(),
+            sig: None,
+        };
+        let signed = entry.signed_bytes();
+        let signature = self.signer.sign(&signed).map_err(std::io::Error::other)?;
+        if !self.public_key.verify(&signed, &signature) {
+            return Err(invalid(
+                "Audit signature failed self-verification".to_string(),
+            ));
+        }
+        entry.sig = Some(general_purpose::STANDARD.encode(KeyPair::signature_to_der(&signature)));
+
+        let line = serde_json::to_string(&entry).expect("audit entry serializes");
+        chain.file.write_all(format!("{}\n", line).as_bytes())?;
+        chain.file.sync_data()?;
+        let line_hash: [u8; 32] = Sha256::digest(line.as_bytes()).into();
+        chain.seq = entry.seq;
+        chain.prev = hex::encode(line_hash);
+        chain.days.push(&entry.time, entry.seq, line_hash)?;
+        chain
+            .by_hash
+            .entry(entry.hash.clone())
+            .or_default()
+            .push(entry.clone());
+        Ok(entry)
+    }
+}
+
+/// Checks every line of the audit log at `path`: the `seq` numbering, the
+/// `prev` chain and each signature against `public_key`, after the signed
+/// checkpoint of its compacted entries if it has any. Returns the number
+/// of entries, compacted ones included, or an error naming the first bad
+/// line.
+pub fn verify_file(path: impl AsRef<Path>, public_key: &PublicKey) -> std::io::Result<u64> {
+    let (compacted, contents) = read_log(path.as_ref(), public_key)?;
+    verify_contents(&contents, &compacted.base(), public_key)
+}
+
+/// `verify_file` of a log already read, whose lines follow the line `base`:
+/// its `seq` and hash
+fn verify_contents(
+    contents: &str,
+    base: &(u64, String),
+    public_key: &PublicKey,
+) -> std::io::Result<u64> {
+    let kid = jws::key_id(public_key);
+    let mut prev = base.1.clone();
+    let mut count = base.0;
+    for (i, line) in contents.split_terminator('\n').enumerate() {
+        let n = base.0 + i as u64 + 1;
+        check_line(line, n, &prev, &kid, public_key)
+            .map_err(|reason| invalid(format!("Line {}: {}", n, reason)))?;
+        prev = hex::encode(Sha256::digest(line.as_bytes()));
+        count = n;
+    }
+    // A line the server was still writing when it stopped
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        return Err(invalid(format!("Line {}: truncated", count + 1)));
+    }
+    Ok(count)
+}
+
+/// Checks that `line` is entry `seq` of a log whose line before hashes to
+/// `prev`, signed by `public_key` (key id `kid`); the reason if not
+fn check_line(
+    line: &str,
+    seq: u64,
+    prev: &str,
+    kid: &str,
+    public_key: &PublicKey,
+) -> Result<AuditEntry, String> {
+    let entry: AuditEntry =
+        serde_json::from_str(line).map_err(|e| format!("not an audit entry: {}", e))?;
+    if entry.seq != seq {
+        return Err(format!("seq is {}", entry.seq));
+    }
+    if entry.prev != prev {
+        return Err("prev does not match the previous line".to_string());
+    }
+    if entry.kid != kid {
+        return Err(format!("signed by key {}", entry.kid));
+    }
+    let sig = entry
+        .sig
+        .as_deref()
+        .and_then(|sig| general_purpose::STANDARD.decode(sig).ok())
+        .and_then(|der| KeyPair::signature_from_der(&der).ok());
+    if sig.is_none() {
+        return Err("missing or malformed sig".to_string());
+    }
+    if !entry.verify(public_key) {
+        return Err("signature does not verify".to_string());
+    }
+    Ok(entry)
+}
+
+/// Format of `export` archives; `import` takes no other
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// Largest archive `import` decompresses (1 GiB)
+const MAX_ARCHIVE_BYTES: usize = 1 << 30;
+
+/// First line of an archive, describing and signing the lines after it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub version: u32,
+    /// `seq` of the line before the first one in the archive (0 = all)
+    pub since: u64,
+    /// Hex SHA-256 of that line (`GENESIS` for 0), the first line's `prev`
+    pub prev: String,
+    /// Lines in the archive
+    pub count: u64,
+    /// Hex SHA-256 of the lines, each with its newline, as in the log
+    pub lines_sha256: String,
+    /// Key id of the key that signed the manifest
+    pub kid: String,
+    /// Base64 DER signature over the manifest without `sig`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sig: Option<String>,
+}
+
+impl ArchiveManifest {
+    fn signed_bytes(&self) -> Vec<u8> {
+        let unsigned = ArchiveManifest {
+            sig: None,
+            ..self.clone()
+        };
+        serde_json::to_vec(&unsigned).expect("manifest serializes")
+    }
+}
+
+/// Archives the lines of the log at `path` after `seq` `since`, signed by
+/// `signer`: the manifest line and the lines, zlib-compressed. The whole log
+/// must verify under `signer`'s key first, so a broken log isn't vouched for.
+pub fn export(path: impl AsRef<Path>, since: u64, signer: &dyn Signer) -> std::io::Result<Vec<u8>> {
+    let (compacted, contents) = read_log(path.as_ref(), &signer.public_key())?;
+    export_contents(&contents, &compacted.base(), since, u64::MAX, signer)
+        .map(|(_, archive)| archive)
+}
+
+/// `export` of a log already read (the lines after `base`, as for
+/// `verify_contents`), of at most `count` lines after `since`, with the
+/// archive's manifest
+fn export_contents(
+    contents: &str,
+    base: &(u64, String),
+    since: u64,
+    count: u64,
+    signer: &dyn Signer,
+) -> std::io::Result<(ArchiveManifest, Vec<u8>)> {
+    // 1) The log as it is now, checked
+    let public_key = signer.public_key();
+    let total = verify_contents(contents, base, &public_key)?;
+    if since < base.0 {
+        return Err(invalid(format!(
+            "Entries up to {} are compacted; export from there on",
+            base.0
+        )));
+    }
+    if since > total {
+        return Err(invalid(format!(
+            "The log has {} entries, none after {}",
+            total, since
+        )));
+    }
+    let lines: Vec<&str> = contents.split_terminator('\n').collect();
+    let prev = match since - base.0 {
+        0 => base.1.clone(),
+        n => hex::encode(Sha256::digest(lines[n as usize - 1].as_bytes())),
+    };
+    let end = since.saturating_add(count).min(total);
+    let body: String = lines[(since - base.0) as usize..(end - base.0) as usize]
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect();
+
+    // 2) Sign the manifest
+    let mut manifest = ArchiveManifest {
+        version: ARCHIVE_VERSION,
+        since,
+        prev,
+        count: end - since,
+        lines_sha256: hex::encode(Sha256::digest(body.as_bytes())),
+        kid: jws::key_id(&public_key),
+        sig: None,
+    };
+    let signature = signer
+        .sign(&manifest.signed_bytes())
+        .map_err(std::io::Error::other)?;
+    manifest.sig = Some(general_purpose::STANDARD.encode(KeyPair::signature_to_der(&signature)));
+
+    // 3) Compress
+    let archive = format!(
+        "{}\n{}",
+        serde_json::to_string(&manifest).expect("manifest serializes"),
+        body
+    );
+    let compressed = miniz_oxide::deflate::compress_to_vec_zlib(archive.as_bytes(), 6);
+    Ok((manifest, compressed))
+}
+
+/// Checks `archive` (from `export`) against `public_key` and appends its
+/// lines to the log at `into`, creating it owner-only if missing. The log
+/// must already hold every line up to the archive's `since`; lines it holds
+/// beyond that must be the archive's, and are skipped. Returns the manifest
+/// and the number of lines appended.
+///
+/// Nothing is written unless the manifest signature, the lines' hash and
+/// the whole resulting log check out. Don't import into the `audit_file` of
+/// a server that is running: it appends to it too.
+pub fn import(
+    archive: &[u8],
+    public_key: &PublicKey,
+    into: impl AsRef<Path>,
+) -> std::io::Result<(ArchiveManifest, u64)> {
+    // 1) Unpack and check the manifest
+    let archive =
+        miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(archive, MAX_ARCHIVE_BYTES)
+            .map_err(|e| invalid(format!("Not an archive: {:?}", e.status)))?;
+    let archive =
+        String::from_utf8(archive).map_err(|_| invalid("Archive is not UTF-8".to_string()))?;
+    let (manifest, body) = archive
+        .split_once('\n')
+        .ok_or_else(|| invalid("Archive has no manifest".to_string()))?;
+    let manifest: ArchiveManifest = serde_json::from_str(manifest)
+        .map_err(|e| invalid(format!("Bad archive manifest: {}", e)))?;
+    if manifest.version != ARCHIVE_VERSION {
+        return Err(invalid(format!(
+            "Archive version {} is not supported",
+            manifest.version
+        )));
+    }
+    let signature = manifest
+        .sig
+        .as_deref()
+        .and_then(|sig| general_purpose::STANDARD.decode(sig).ok())
+        .and_then(|der| KeyPair::signature_from_der(&der).ok());
+    if manifest.kid != jws::key_id(public_key)
+        || !signature.is_some_and(|sig| public_key.verify(&manifest.signed_bytes(), &sig))
+    {
+        return Err(invalid(
+            "Archive manifest is not signed by the key".to_string(),
+        ));
+    }
+    let lines: Vec<&str> = body.split_terminator('\n').collect();
+    if hex::encode(Sha256::digest(body.as_bytes())) != manifest.lines_sha256
+        || lines.len() as u64 != manifest.count
+        || (!body.is_empty() && !body.ends_with('\n'))
+    {
+        return Err(invalid(
+            "Archive lines don't match the manifest".to_string(),
+        ));
+    }
+
+    // 2) Line it up with what the log already has
+    let into = into.as_ref();
+    let compacted = Compacted::read(into, public_key)?;
+    let base = compacted.base();
+    let existing = match fs::read_to_string(into) {
+        Ok(contents) => compacted.strip(&contents)?.to_string(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let have: Vec<&str> = existing.split_terminator('\n').collect();
+    if manifest.since < base.0 {
+        return Err(invalid(format!(
+            "Archive starts at entry {}, but the log has compacted up to {}",
+            manifest.since, base.0
+        )));
+    }
+    let since = (manifest.since - base.0) as usize;
+    if have.len() < since {
+        return Err(invalid(format!(
+            "Archive starts after entry {}, but the log has only {}",
+            manifest.since,
+            base.0 + have.len() as u64
+        )));
+    }
+    let overlap = (have.len() - since).min(lines.len());
+    if have[since..since + overlap] != lines[..overlap] {
+        return Err(invalid(format!(
+            "Archive differs from the log after entry {}",
+            manifest.since
+        )));
+    }
+    let new: String = lines[overlap..]
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect();
+
+    // 3) Check the result as a whole, then append
+    verify_contents(&format!("{}{}", existing, new), &base, public_key)?;
+    if !new.is_empty() {
+        let mut file = open_append(into)?;
+        file.write_all(new.as_bytes())?;
+        file.sync_data()?;
+    }
+    let appended = (lines.len() - overlap) as u64;
+    Ok((manifest, appended))
+}
+
+/// Opens `path` for appending, creating it owner-only
+fn open_append(path: &Path) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.append(true).create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+fn index_compacted(entries: &[CompactedEntry]) -> HashMap<String, Vec<usize>> {
+    let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        index.entry(entry.hash.clone()).or_default().push(i);
+    }
+    index
+}
+
+fn invalid(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
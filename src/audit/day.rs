@@ -0,0 +1,176 @@
+//! Daily Merkle trees over the audit log
+//!
+//! Each line is a leaf, `merkle::leaf_hash(SHA-256(line))`, of the tree for
+//! the UTC date of its `time`; a compacted entry still has its line's hash,
+//! so its leaf is unchanged. A day is closed once the server clock is past
+//! it (issued times only move forward, so it gets no more lines), and its
+//! root is signed as a [`DayRoot`]. Keeping one small root per day is then
+//! enough to check any line of that day, with its [`DayProof`].
+
+use super::invalid;
+use crate::{jws, merkle, wire};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::NaiveDate;
+use ecdsa_lib::{KeyPair, PublicKey};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+/// Signed root of one UTC day's tree (`GET /log/day/{date}`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DayRoot {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    /// Lines written that day
+    pub count: u64,
+    /// Hex Merkle root over their leaves, in `seq` order
+    pub root: String,
+    /// Key id of the key that signed it
+    pub kid: String,
+    /// Base64 DER signature over the root without `sig`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sig: Option<String>,
+}
+
+impl DayRoot {
+    pub(super) fn signed_bytes(&self) -> Vec<u8> {
+        let unsigned = DayRoot {
+            sig: None,
+            ..self.clone()
+        };
+        serde_json::to_vec(&unsigned).expect("day root serializes")
+    }
+
+    /// Whether `sig` is `public_key`'s signature over this root
+    pub fn verify(&self, public_key: &PublicKey) -> bool {
+        self.kid == jws::key_id(public_key)
+            && self
+                .sig
+                .as_deref()
+                .and_then(|sig| general_purpose::STANDARD.decode(sig).ok())
+                .and_then(|der| KeyPair::signature_from_der(&der).ok())
+                .is_some_and(|signature| public_key.verify(&self.signed_bytes(), &signature))
+    }
+}
+
+/// Where one line sits in its day's tree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DayProof {
+    pub seq: u64,
+    /// Position of the line among the day's lines
+    pub index: u64,
+    /// Hex sibling hashes from the line's leaf up to the root, bottom first
+    pub proof: Vec<String>,
+}
+
+impl DayProof {
+    /// Whether the line with SHA-256 `line_hash` is this proof's line of the
+    /// day `root` signs (check `root.verify` too)
+    pub fn verify(&self, line_hash: &[u8; 32], root: &DayRoot) -> bool {
+        let proof: Option<Vec<[u8; 32]>> = self
+            .proof
+            .iter()
+            .map(|hash| hex::decode(hash).ok()?.try_into().ok())
+            .collect();
+        let expected: Option<[u8; 32]> = hex::decode(&root.root)
+            .ok()
+            .and_then(|hash| hash.try_into().ok());
+        let (Some(proof), Some(expected)) = (proof, expected) else {
+            return false;
+        };
+        merkle::verify_inclusion(
+            &merkle::leaf_hash(line_hash),
+            self.index,
+            root.count,
+            &proof,
+            &expected,
+        )
+    }
+}
+
+/// One day's leaves, with its root once asked for
+#[derive(Default)]
+struct Day {
+    first_seq: u64,
+    leaves: Vec<[u8; 32]>,
+    root: Option<[u8; 32]>,
+    signed: Option<DayRoot>,
+}
+
+/// Every day's tree, by date
+#[derive(Default)]
+pub(super) struct Days {
+    days: BTreeMap<NaiveDate, Day>,
+}
+
+impl Days {
+    /// Adds the line `seq`, written at `time`, with SHA-256 `line_hash`
+    pub fn push(&mut self, time: &str, seq: u64, line_hash: [u8; 32]) -> std::io::Result<()> {
+        let date = wire::parse_time(time)
+            .map_err(|e| invalid(format!("Line {}: bad time: {}", seq, e)))?
+            .date_naive();
+        let day = self.days.entry(date).or_default();
+        if day.leaves.is_empty() {
+            day.first_seq = seq;
+        }
+        day.leaves.push(merkle::leaf_hash(&line_hash));
+        day.root = None;
+        day.signed = None;
+        Ok(())
+    }
+
+    /// The line count and Merkle root of `date`
+    pub fn root(&mut self, date: NaiveDate) -> (u64, [u8; 32]) {
+        let day = self.days.entry(date).or_default();
+        let root = *day.root.get_or_insert_with(|| merkle::root(&day.leaves));
+        (day.leaves.len() as u64, root)
+    }
+
+    /// Whether `date` has any lines
+    pub fn has_lines(&self, date: NaiveDate) -> bool {
+        self.days
+            .get(&date)
+            .is_some_and(|day| !day.leaves.is_empty())
+    }
+
+    /// The signed root of `date` if it was signed since its last line
+    pub fn signed(&self, date: NaiveDate) -> Option<DayRoot> {
+        self.days.get(&date).and_then(|day| day.signed.clone())
+    }
+
+    /// Keeps `root` as the signed root of its day
+    pub fn set_signed(&mut self, date: NaiveDate, root: DayRoot) {
+        self.days.entry(date).or_default().signed = Some(root);
+    }
+
+    /// Dates with lines before the last date with lines, which (issued
+    /// times only moving forward) get no more lines
+    pub fn closed(&self) -> Vec<NaiveDate> {
+        let mut dates: Vec<NaiveDate> = self
+            .days
+            .iter()
+            .filter(|(_, day)| !day.leaves.is_empty())
+            .map(|(date, _)| *date)
+            .collect();
+        dates.pop();
+        dates
+    }
+
+    /// Every line's leaf, in `seq` order
+    pub fn leaves(&self) -> impl Iterator<Item = &[u8; 32]> {
+        self.days.values().flat_map(|day| &day.leaves)
+    }
+
+    /// The proof of line `seq` in its day's tree, and that day
+    pub fn proof(&self, seq: u64) -> Option<(NaiveDate, DayProof)> {
+        let (date, day) = self.days.iter().find(|(_, day)| {
+            seq >= day.first_seq && seq - day.first_seq < day.leaves.len() as u64
+        })?;
+        let index = seq - day.first_seq;
+        let proof = merkle::inclusion_proof(&day.leaves, index as usize)
+            .iter()
+            .map(hex::encode)
+            .collect();
+        Some((*date, DayProof { seq, index, proof }))
+    }
+}
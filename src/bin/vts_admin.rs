@@ -0,0 +1,335 @@
+//! `vts-admin export --since SEQ [--out ARCHIVE] [AUDIT_FILE]`
+//! `vts-admin import ARCHIVE [--into AUDIT_FILE] [--key PUBLIC_KEY_FILE]`
+//! `vts-admin cert [--tenant NAME] [--cn NAME] [--days N] [--out CERT_FILE]
+//!                 [--issuer-key ROOT_PRIVATE_KEY_FILE] [--issuer-cn NAME]`
+//! `vts-admin csr [--tenant NAME] [--cn NAME] [--out CSR_FILE]`
+//! `vts-admin root-key ROOT_PRIVATE_KEY_FILE ROOT_PUBLIC_KEY_FILE`
+//!
+//! Moves the audit log (see `lab4::audit`) between machines: `export` signs
+//! and compresses the entries after `seq` SEQ with the default tenant's key
+//! (to stdout without `--out`), `import` checks an archive against the
+//! public key and appends it to a backup or mirror copy of the log. Files
+//! default to the ones in `vts.toml` / `VTS_*`, relative to the data
+//! directory.
+//!
+//! `cert` writes a self-signed X.509 certificate (PEM) for a tenant's key,
+//! valid for `--days` (365) from now, and `csr` a certification request to
+//! have a CA issue one instead (see `lab4::x509`); point `cert_file` at
+//! the result. With `--issuer-key`, the certificate is issued by an offline
+//! root key (made with `root-key`) instead: clients with that root's public
+//! key as `trust_root` then accept any key it certified.
+
+use ecdsa_lib::KeyPair;
+use ecdsa_lib::PublicKey;
+use lab4::audit;
+use lab4::config::{
+    ENV_PREFIX, ServerConfig, keys_from_env, load_config, process_env, tenant_env_prefix,
+};
+use lab4::signer::key_pair_signer;
+use lab4::x509;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use zeroize::Zeroizing;
+
+const USAGE: &str = "usage: vts-admin export --since SEQ [--out ARCHIVE] [AUDIT_FILE]\n       \
+                     vts-admin import ARCHIVE [--into AUDIT_FILE] [--key PUBLIC_KEY_FILE]\n       \
+                     vts-admin cert [--tenant NAME] [--cn NAME] [--days N] [--out CERT_FILE]\n       \
+                                    [--issuer-key ROOT_PRIVATE_KEY_FILE] [--issuer-cn NAME]\n       \
+                     vts-admin csr [--tenant NAME] [--cn NAME] [--out CSR_FILE]\n       \
+                     vts-admin root-key ROOT_PRIVATE_KEY_FILE ROOT_PUBLIC_KEY_FILE";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to read config: {}", e);
+            exit(1);
+        }
+    };
+    match args.first().map(String::as_str) {
+        Some("export") => export(&args[1..], &config),
+        Some("import") => import(&args[1..], &config),
+        Some("cert") => certify(&args[1..], &config, false),
+        Some("csr") => certify(&args[1..], &config, true),
+        Some("root-key") => root_key(&args[1..]),
+        _ => usage(),
+    }
+}
+
+fn export(args: &[String], config: &ServerConfig) {
+    // 1) Arguments, falling back to the server's configuration
+    let (mut since, mut out, mut audit_file) = (None, None, None);
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--since" => since = rest.next().and_then(|v| v.parse::<u64>().ok()),
+            "--out" => out = rest.next().cloned(),
+            _ if audit_file.is_none() && !arg.starts_with('-') => audit_file = Some(arg.clone()),
+            _ => usage(),
+        }
+    }
+    let Some(since) = since else { usage() };
+    let audit_file = audit_file_or_configured(audit_file, config);
+
+    // 2) The default tenant's key (never generated: an archive signed by a
+    // new key is of no use)
+    let signer = load_signer(config, None);
+
+    // 3) Archive and write it out
+    let archive = match audit::export(&audit_file, since, &signer) {
+        Ok(archive) => archive,
+        Err(e) => {
+            eprintln!("{}: FAILED: {}", audit_file.display(), e);
+            exit(1);
+        }
+    };
+    let written = match &out {
+        Some(path) => std::fs::write(path, &archive),
+        None => std::io::stdout().write_all(&archive),
+    };
+    if let Err(e) = written {
+        eprintln!("Failed to write the archive: {}", e);
+        exit(1);
+    }
+    eprintln!(
+        "{}: exported the entries after {} ({} bytes)",
+        audit_file.display(),
+        since,
+        archive.len()
+    );
+}
+
+fn import(args: &[String], config: &ServerConfig) {
+    // 1) Arguments, falling back to the server's configuration
+    let (mut archive, mut into, mut key_file) = (None, None, None);
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--into" => into = rest.next().cloned(),
+            "--key" => key_file = rest.next().cloned(),
+            _ if archive.is_none() && !arg.starts_with('-') => archive = Some(arg.clone()),
+            _ => usage(),
+        }
+    }
+    let Some(archive) = archive else { usage() };
+    let into = audit_file_or_configured(into, config);
+    let key_file = key_file
+        .map(PathBuf::from)
+        .unwrap_or_else(|| in_data_dir(config, &config.public_key_file));
+
+    // 2) Check the archive and append it
+    let public_key =
+        match std::fs::read(&key_file).and_then(|bytes| PublicKey::from_sec1_bytes(&bytes)) {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("Failed to read public key {}: {}", key_file.display(), e);
+                exit(1);
+            }
+        };
+    let bytes = match std::fs::read(&archive) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", archive, e);
+            exit(1);
+        }
+    };
+    match audit::import(&bytes, &public_key, &into) {
+        Ok((manifest, appended)) => println!(
+            "{}: OK, {} of {} entries after {} appended",
+            into.display(),
+            appended,
+            manifest.count,
+            manifest.since
+        ),
+        Err(e) => {
+            eprintln!("{}: FAILED: {}", archive, e);
+            exit(1);
+        }
+    }
+}
+
+fn certify(args: &[String], config: &ServerConfig, request: bool) {
+    // 1) Arguments
+    let (mut tenant, mut common_name, mut days, mut out) = (None, None, Some(365), None);
+    let (mut issuer_key, mut issuer_name) = (None, None);
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--tenant" => tenant = rest.next().cloned(),
+            "--cn" => common_name = rest.next().cloned(),
+            "--days" if !request => days = rest.next().and_then(|v| v.parse::<i64>().ok()),
+            "--out" => out = rest.next().cloned(),
+            "--issuer-key" if !request => issuer_key = rest.next().cloned(),
+            "--issuer-cn" if !request => issuer_name = rest.next().cloned(),
+            _ => usage(),
+        }
+    }
+    let Some(days) = days.filter(|&days| days > 0) else {
+        usage()
+    };
+    let common_name = common_name.unwrap_or_else(|| match &tenant {
+        Some(name) => format!("VTS timestamping ({})", name),
+        None => "VTS timestamping".to_string(),
+    });
+
+    // 2) Certificate or request, signed with the tenant's key, or the
+    // certificate with the root's
+    let signer = load_signer(config, tenant.as_deref());
+    let now = chrono::Utc::now();
+    let not_after = now + chrono::Duration::days(days);
+    let made = match (request, &issuer_key) {
+        (true, _) => x509::request(&signer, &common_name)
+            .map(|der| x509::to_pem(&der, "CERTIFICATE REQUEST")),
+        (false, None) => x509::self_signed(&signer, &common_name, now, not_after)
+            .map(|der| x509::to_pem(&der, "CERTIFICATE")),
+        (false, Some(path)) => {
+            let root = load_root(path);
+            let issuer_name = issuer_name.as_deref().unwrap_or("VTS root");
+            let public_key = signer.to_public_key();
+            x509::issue(
+                &public_key,
+                &common_name,
+                &root,
+                issuer_name,
+                now,
+                not_after,
+            )
+            .map(|der| x509::to_pem(&der, "CERTIFICATE"))
+        }
+    };
+    let pem = match made {
+        Ok(pem) => pem,
+        Err(e) => {
+            eprintln!("Failed to sign: {}", e);
+            exit(1);
+        }
+    };
+    let written = match &out {
+        Some(path) => std::fs::write(path, &pem),
+        None => std::io::stdout().write_all(pem.as_bytes()),
+    };
+    if let Err(e) = written {
+        eprintln!("Failed to write the {}: {}", what(request), e);
+        exit(1);
+    }
+    eprintln!(
+        "Wrote a {} for {} (key {})",
+        what(request),
+        common_name,
+        lab4::jws::key_id(&signer.to_public_key())
+    );
+}
+
+/// Makes the offline root key that issues certificates with `cert
+/// --issuer-key`; refuses to replace existing files
+fn root_key(args: &[String]) {
+    let [private_key_file, public_key_file] = args else {
+        usage()
+    };
+    let root = KeyPair::generate();
+    if let Err(e) = root.save_to_files_secure(private_key_file, public_key_file, false) {
+        eprintln!("Failed to write the root key: {}", e);
+        exit(1);
+    }
+    if cfg!(not(unix)) {
+        eprintln!(
+            "Warning: {} has its directory's default permissions; private key files are only restricted to their owner on Unix",
+            private_key_file
+        );
+    }
+    eprintln!(
+        "Wrote a root key (public key {}); keep {} offline",
+        root.to_public_key(),
+        private_key_file
+    );
+}
+
+/// The root key in `path` (its raw private key, as `root-key` writes it)
+fn load_root(path: &str) -> KeyPair {
+    let loaded = std::fs::read(path)
+        .map(Zeroizing::new)
+        .and_then(|private_key| KeyPair::from_private_key_bytes(&private_key));
+    match loaded {
+        Ok(root) => root,
+        Err(e) => {
+            eprintln!("Failed to read the root key {}: {}", path, e);
+            exit(1);
+        }
+    }
+}
+
+fn what(request: bool) -> &'static str {
+    if request {
+        "certification request"
+    } else {
+        "certificate"
+    }
+}
+
+/// A tenant's key (the default tenant's for `None`), from
+/// `VTS_[TENANT_]PRIVATE_KEY` or its key files
+fn load_signer(config: &ServerConfig, tenant: Option<&str>) -> KeyPair {
+    let (prefix, (private_key_file, public_key_file)) = match tenant {
+        None => (
+            ENV_PREFIX.to_string(),
+            (
+                config.private_key_file.clone(),
+                config.public_key_file.clone(),
+            ),
+        ),
+        Some(name) => match config.tenants.get(name) {
+            Some(tenant) => (tenant_env_prefix(name), tenant.key_files(name)),
+            None => {
+                eprintln!("No tenant '{}' in the config", name);
+                exit(1);
+            }
+        },
+    };
+    let keys = match keys_from_env(&process_env, &prefix) {
+        Ok(Some(keys)) => keys,
+        Ok(None) => {
+            let read = |path: &str| std::fs::read(in_data_dir(config, path));
+            match (read(&private_key_file), read(&public_key_file)) {
+                (Ok(private_key), Ok(public_key)) => (private_key, public_key),
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!("Failed to read the key files: {}", e);
+                    exit(1);
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
+    let (private_key, public_key) = (Zeroizing::new(keys.0), keys.1);
+    match key_pair_signer(&private_key, &public_key) {
+        Ok(signer) => signer,
+        Err(e) => {
+            eprintln!("Failed to load the key pair: {}", e);
+            exit(1);
+        }
+    }
+}
+
+fn in_data_dir(config: &ServerConfig, path: &str) -> PathBuf {
+    Path::new(&config.data_dir).join(path)
+}
+
+fn audit_file_or_configured(path: Option<String>, config: &ServerConfig) -> PathBuf {
+    match path {
+        Some(path) => PathBuf::from(path),
+        None if !config.audit_file.is_empty() => in_data_dir(config, &config.audit_file),
+        None => {
+            eprintln!("No audit file given, and audit_file is not set\n{}", USAGE);
+            exit(2);
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    exit(2);
+}
@@ -0,0 +1,70 @@
+//! `vts-audit verify [AUDIT_FILE] [--key PUBLIC_KEY_FILE]`
+//!
+//! Checks an audit log written by the server (see `lab4::audit`). Both files
+//! default to the ones in `vts.toml` / `VTS_*`, relative to the data directory.
+
+use ecdsa_lib::PublicKey;
+use lab4::audit::verify_file;
+use lab4::config::load_config;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+const USAGE: &str = "usage: vts-audit verify [AUDIT_FILE] [--key PUBLIC_KEY_FILE]";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) != Some("verify") {
+        eprintln!("{}", USAGE);
+        exit(2);
+    }
+
+    // 1) Arguments, falling back to the server's configuration
+    let (mut audit_file, mut key_file) = (None, None);
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--key" => key_file = rest.next().cloned(),
+            _ if audit_file.is_none() && !arg.starts_with('-') => audit_file = Some(arg.clone()),
+            _ => {
+                eprintln!("{}", USAGE);
+                exit(2);
+            }
+        }
+    }
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to read config: {}", e);
+            exit(1);
+        }
+    };
+    let in_data_dir = |path: &str| -> PathBuf { Path::new(&config.data_dir).join(path) };
+    let audit_file = match audit_file {
+        Some(path) => PathBuf::from(path),
+        None if !config.audit_file.is_empty() => in_data_dir(&config.audit_file),
+        None => {
+            eprintln!("No audit file given, and audit_file is not set\n{}", USAGE);
+            exit(2);
+        }
+    };
+    let key_file = key_file
+        .map(PathBuf::from)
+        .unwrap_or_else(|| in_data_dir(&config.public_key_file));
+
+    // 2) Check every line against the public key
+    let public_key =
+        match std::fs::read(&key_file).and_then(|bytes| PublicKey::from_sec1_bytes(&bytes)) {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("Failed to read public key {}: {}", key_file.display(), e);
+                exit(1);
+            }
+        };
+    match verify_file(&audit_file, &public_key) {
+        Ok(entries) => println!("{}: OK, {} entries", audit_file.display(), entries),
+        Err(e) => {
+            eprintln!("{}: FAILED: {}", audit_file.display(), e);
+            exit(1);
+        }
+    }
+}
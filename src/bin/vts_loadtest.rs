@@ -0,0 +1,359 @@
+//! `vts-loadtest URL [--rps N] [--duration SECS] [--concurrency N]
+//!               [--verify-share F] [--message-bytes N] [--tenant NAME]`
+//!
+//! Drives a server at `--rps` requests per second for `--duration` seconds
+//! (100 and 10 by default) and reports what it sustained: requests done
+//! per second and a latency histogram with p50/p95/p99, per operation.
+//! Every timestamp returned is verified against the key `GET /key` serves
+//! (fetched again once if a check fails, in case the key was rotated).
+//!
+//! A share `--verify-share` (0.0 by default) of the requests are what a
+//! verifier does instead: fetch the key and check the last timestamp
+//! issued. The load is open-loop: a request due while `--concurrency` (64)
+//! are still waiting is skipped and counted, rather than sending the rest
+//! late. Exits 1 if any signature failed to verify or any request failed.
+
+use lab4::ecdsa_requests::verify_signature;
+use lab4::{ApiError, EcdsaSignedTimestamp, EcdsaVerificationKey};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::process::exit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: vts-loadtest URL [--rps N] [--duration SECS] [--concurrency N]\n                    \
+                     [--verify-share F] [--message-bytes N] [--tenant NAME]";
+
+/// Upper bounds of the histogram's buckets, in milliseconds; one more
+/// bucket holds the rest
+const BUCKETS_MS: [u64; 12] = [1, 2, 4, 8, 16, 32, 64, 128, 256, 512, 1024, 2048];
+
+struct Options {
+    url: String,
+    rps: u32,
+    duration: Duration,
+    concurrency: usize,
+    verify_share: f64,
+    message_bytes: usize,
+    tenant: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Op {
+    Sign,
+    Verify,
+}
+
+impl Op {
+    fn name(&self) -> &'static str {
+        match self {
+            Op::Sign => "sign",
+            Op::Verify => "verify",
+        }
+    }
+}
+
+/// What the run has seen so far
+#[derive(Default)]
+struct Stats {
+    latencies: BTreeMap<Op, Vec<Duration>>,
+    /// Failed requests, by error
+    errors: BTreeMap<String, u64>,
+    bad_signatures: u64,
+    /// Due while `concurrency` requests were waiting
+    skipped: u64,
+}
+
+/// Shared by every request task
+struct Run {
+    options: Options,
+    /// One pool of connections for the whole run
+    client: reqwest::Client,
+    key: Mutex<Arc<EcdsaVerificationKey>>,
+    last: Mutex<Option<Arc<EcdsaSignedTimestamp>>>,
+    stats: Mutex<Stats>,
+    in_flight: AtomicUsize,
+}
+
+#[tokio::main]
+async fn main() {
+    let options = parse_args(std::env::args().skip(1).collect());
+
+    // 1) The key every timestamp is checked against
+    let client = reqwest::Client::new();
+    let key = match fetch_key(&client, &options).await {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("{}: failed to fetch the key: {}", options.url, e);
+            exit(1);
+        }
+    };
+    eprintln!(
+        "{}: {} requests/s for {}s, {:.0}% verifications, up to {} at once",
+        options.url,
+        options.rps,
+        options.duration.as_secs(),
+        options.verify_share * 100.0,
+        options.concurrency
+    );
+    let run = Arc::new(Run {
+        options,
+        client,
+        key: Mutex::new(Arc::new(key)),
+        last: Mutex::new(None),
+        stats: Mutex::new(Stats::default()),
+        in_flight: AtomicUsize::new(0),
+    });
+
+    // 2) One request every 1/rps, as long as few enough are waiting
+    let start = Instant::now();
+    let mut ticks =
+        tokio::time::interval(Duration::from_secs_f64(1.0 / f64::from(run.options.rps)));
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Burst);
+    let total = u64::from(run.options.rps) * run.options.duration.as_secs();
+    let mut tasks = Vec::new();
+    for i in 0..total {
+        ticks.tick().await;
+        if run.in_flight.load(Ordering::SeqCst) >= run.options.concurrency {
+            lock(&run.stats).skipped += 1;
+            continue;
+        }
+        let share = run.options.verify_share;
+        let op = match ((i + 1) as f64 * share).floor() > (i as f64 * share).floor() {
+            true => Op::Verify,
+            false => Op::Sign,
+        };
+        run.in_flight.fetch_add(1, Ordering::SeqCst);
+        let run = run.clone();
+        tasks.push(tokio::spawn(async move {
+            request(&run, op, i).await;
+            run.in_flight.fetch_sub(1, Ordering::SeqCst);
+        }));
+    }
+    for task in tasks {
+        let _ = task.await;
+    }
+
+    // 3) The report
+    let elapsed = start.elapsed();
+    let stats = lock(&run.stats);
+    report(&stats, elapsed);
+    if stats.bad_signatures > 0 || !stats.errors.is_empty() {
+        exit(1);
+    }
+}
+
+/// One operation, recorded in `run.stats`
+async fn request(run: &Run, op: Op, i: u64) {
+    let options = &run.options;
+    let last = lock(&run.last).clone();
+    // Nothing to verify before the first timestamp
+    let op = match (op, &last) {
+        (Op::Verify, None) => Op::Sign,
+        (op, _) => op,
+    };
+    let started = Instant::now();
+    let result = match (op, last) {
+        (Op::Verify, Some(signed)) => fetch_key(&run.client, options)
+            .await
+            .map(|key| (signed, Some(Arc::new(key)))),
+        _ => {
+            let body = serde_json::json!({ "message": message(i, options.message_bytes) });
+            let request = run.client.post(url(options, "sign")).json(&body);
+            send::<EcdsaSignedTimestamp>(request)
+                .await
+                .map(|signed| (Arc::new(signed), None))
+        }
+    };
+    let elapsed = started.elapsed();
+
+    match result {
+        Ok((signed, fetched)) => {
+            let key = fetched.unwrap_or_else(|| lock(&run.key).clone());
+            let valid =
+                verify_signature(&signed, &key) || refreshed_key_verifies(run, &signed).await;
+            let mut stats = lock(&run.stats);
+            stats.latencies.entry(op).or_default().push(elapsed);
+            if !valid {
+                stats.bad_signatures += 1;
+            }
+            drop(stats);
+            if op == Op::Sign && valid {
+                *lock(&run.last) = Some(signed);
+            }
+        }
+        Err(e) => {
+            let mut stats = lock(&run.stats);
+            *stats
+                .errors
+                .entry(format!("{}: {}", op.name(), e))
+                .or_default() += 1;
+        }
+    }
+}
+
+/// After a failed check: whether `signed` verifies under the key the server
+/// serves now, which is kept if so
+async fn refreshed_key_verifies(run: &Run, signed: &EcdsaSignedTimestamp) -> bool {
+    let Ok(key) = fetch_key(&run.client, &run.options).await else {
+        return false;
+    };
+    let valid = verify_signature(signed, &key);
+    if valid {
+        *lock(&run.key) = Arc::new(key);
+    }
+    valid
+}
+
+async fn fetch_key(
+    client: &reqwest::Client,
+    options: &Options,
+) -> Result<EcdsaVerificationKey, String> {
+    send(client.get(url(options, "key"))).await
+}
+
+/// `path` on the server, under `/t/{tenant}` with `--tenant`
+fn url(options: &Options, path: &str) -> String {
+    match &options.tenant {
+        Some(tenant) => format!("{}/t/{}/{}", options.url, tenant, path),
+        None => format!("{}/{}", options.url, path),
+    }
+}
+
+/// The JSON answer to `request`; otherwise the error, in few enough words
+/// that equal failures are counted together
+async fn send<T: DeserializeOwned>(request: reqwest::RequestBuilder) -> Result<T, String> {
+    let response = request.send().await.map_err(|e| match e {
+        e if e.is_timeout() => "timed out".to_string(),
+        e if e.is_connect() => "could not connect".to_string(),
+        e => e.to_string(),
+    })?;
+    let status = response.status();
+    let body = response.bytes().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(match serde_json::from_slice::<ApiError>(&body) {
+            Ok(err) => format!("HTTP {} {}", status.as_u16(), err.code),
+            Err(_) => format!("HTTP {}", status.as_u16()),
+        });
+    }
+    serde_json::from_slice(&body).map_err(|e| format!("unexpected body: {}", e))
+}
+
+/// The `i`th message, padded to `len` bytes
+fn message(i: u64, len: usize) -> String {
+    let mut message = format!("vts-loadtest {}", i);
+    while message.len() < len {
+        message.push('.');
+    }
+    message
+}
+
+fn report(stats: &Stats, elapsed: Duration) {
+    let secs = elapsed.as_secs_f64();
+    for (op, latencies) in &stats.latencies {
+        let mut sorted = latencies.clone();
+        sorted.sort();
+        let ms = |q: f64| {
+            let rank = (q * sorted.len() as f64).ceil() as usize;
+            sorted[rank.saturating_sub(1)].as_secs_f64() * 1000.0
+        };
+        println!(
+            "{:<7} {:>8} done {:>9.1}/s  p50 {:>8.2}ms  p95 {:>8.2}ms  p99 {:>8.2}ms  max {:>8.2}ms",
+            op.name(),
+            sorted.len(),
+            sorted.len() as f64 / secs,
+            ms(0.5),
+            ms(0.95),
+            ms(0.99),
+            ms(1.0)
+        );
+        histogram(&sorted);
+    }
+    println!(
+        "{} bad signatures, {} failed, {} skipped (too many waiting) in {:.1}s",
+        stats.bad_signatures,
+        stats.errors.values().sum::<u64>(),
+        stats.skipped,
+        secs
+    );
+    for (error, count) in &stats.errors {
+        println!("  {:>8}  {}", count, error);
+    }
+}
+
+/// A bar per bucket, scaled to the fullest
+fn histogram(sorted: &[Duration]) {
+    let mut counts = [0u64; BUCKETS_MS.len() + 1];
+    for latency in sorted {
+        let ms = latency.as_millis() as u64;
+        let bucket = BUCKETS_MS
+            .iter()
+            .position(|&bound| ms < bound)
+            .unwrap_or(BUCKETS_MS.len());
+        counts[bucket] += 1;
+    }
+    let most = counts.iter().copied().max().unwrap_or(0).max(1);
+    for (bucket, &count) in counts.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let label = match BUCKETS_MS.get(bucket) {
+            Some(bound) => format!("< {}ms", bound),
+            None => format!(">= {}ms", BUCKETS_MS[BUCKETS_MS.len() - 1]),
+        };
+        println!(
+            "  {:>10} {:>8} {}",
+            label,
+            count,
+            "#".repeat((count * 40).div_ceil(most) as usize)
+        );
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Options {
+    let mut options = Options {
+        url: String::new(),
+        rps: 100,
+        duration: Duration::from_secs(10),
+        concurrency: 64,
+        verify_share: 0.0,
+        message_bytes: 32,
+        tenant: None,
+    };
+    let mut rest = args.into_iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || rest.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--rps" => options.rps = parse(value()),
+            "--duration" => options.duration = Duration::from_secs(parse(value())),
+            "--concurrency" => options.concurrency = parse(value()),
+            "--verify-share" => options.verify_share = parse(value()),
+            "--message-bytes" => options.message_bytes = parse(value()),
+            "--tenant" => options.tenant = Some(value()),
+            _ if options.url.is_empty() && !arg.starts_with('-') => options.url = arg,
+            _ => usage(),
+        }
+    }
+    if options.url.is_empty()
+        || options.rps == 0
+        || options.concurrency == 0
+        || !(0.0..=1.0).contains(&options.verify_share)
+    {
+        usage();
+    }
+    options
+}
+
+fn parse<T: std::str::FromStr>(value: String) -> T {
+    value.parse().unwrap_or_else(|_| usage())
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    exit(2);
+}
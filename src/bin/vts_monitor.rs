@@ -0,0 +1,86 @@
+//! `vts-monitor --key PUBLIC_KEY_FILE --state FILE [--interval SECS] [--once] URL...`
+//!
+//! Watches one log through every `URL` (the same server by other names,
+//! networks or proxies) for split views: see `lab4::monitor`. Each round,
+//! every `URL`'s `GET /log/root` head is checked against the pinned key
+//! and against the heads accepted before, kept in `--state` across runs.
+//! Alerts go to stdout and failed polls to stderr. Rounds are every
+//! `--interval` seconds (60 by default). With `--once` there is one round,
+//! and the exit code is 1 if it raised an alert or a poll failed.
+
+use ecdsa_lib::PublicKey;
+use lab4::monitor::Monitor;
+use std::process::exit;
+use std::time::Duration;
+
+const USAGE: &str =
+    "usage: vts-monitor --key PUBLIC_KEY_FILE --state FILE [--interval SECS] [--once] URL...";
+
+#[tokio::main]
+async fn main() {
+    // 1) Arguments
+    let (mut key_file, mut state, mut interval, mut once) = (None, None, 60, false);
+    let mut urls = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--key" => key_file = args.next(),
+            "--state" => state = args.next(),
+            "--interval" => match args.next().and_then(|v| v.parse::<u64>().ok()) {
+                Some(secs) if secs > 0 => interval = secs,
+                _ => usage(),
+            },
+            "--once" => once = true,
+            _ if !arg.starts_with('-') => urls.push(arg),
+            _ => usage(),
+        }
+    }
+    let (Some(key_file), Some(state)) = (key_file, state) else {
+        usage();
+    };
+    if urls.is_empty() {
+        usage();
+    }
+    let key = match std::fs::read(&key_file).and_then(|bytes| PublicKey::from_sec1_bytes(&bytes)) {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("Failed to read public key {}: {}", key_file, e);
+            exit(1);
+        }
+    };
+    let mut monitor = match Monitor::open(key, &state) {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            eprintln!("Failed to read state {}: {}", state, e);
+            exit(1);
+        }
+    };
+
+    // 2) Rounds over every vantage point
+    loop {
+        let mut failed = false;
+        for url in &urls {
+            match monitor.poll(url).await {
+                Ok(alerts) => {
+                    for alert in &alerts {
+                        println!("{}", alert);
+                    }
+                    failed |= !alerts.is_empty();
+                }
+                Err(e) => {
+                    eprintln!("{}: {}", url, e);
+                    failed = true;
+                }
+            }
+        }
+        if once {
+            exit(i32::from(failed));
+        }
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    exit(2);
+}
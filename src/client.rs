@@ -0,0 +1,1257 @@
+//! Reusable HTTP clients for a VTS server.
+//!
+//! The free functions in `ecdsa_requests` are handy for one-off calls;
+//! a [`VtsClient`] (or [`nonblocking::VtsClient`]) keeps one connection
+//! pool for all its requests and adds timeouts, retries with backoff and
+//! an explicit proxy. An [`Interceptor`] added with `with_interceptor`
+//! sees each request and response, and each failed check, on its way.
+//!
+//! A server on a UNIX socket (`listen = "unix:/run/vts.sock"`) is addressed
+//! as `http+unix://` with the socket path percent-encoded as the host:
+//! `VtsClient::new("http+unix://%2Frun%2Fvts.sock")`. Only the clients here
+//! speak it, not the free functions. Nor does a `vts-mock://` address
+//! (see `testing::MockVts`, with the `testing` feature).
+//!
+//! # Example
+//! ```no_run
+//! # #[cfg(feature = "blocking")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use lab4::client::{ClientOptions, VtsClient};
+//! use std::time::Duration;
+//!
+//! let client = VtsClient::with_options(
+//!     "http://127.0.0.1:8008",
+//!     ClientOptions {
+//!         timeout: Some(Duration::from_secs(5)),
+//!         retries: 3,
+//!         ..Default::default()
+//!     },
+//! )?;
+//! let key = client.request_key()?;
+//! for message in ["a", "b", "c"] {
+//!     let signed = client.request_timestamp(message)?;
+//!     assert!(lab4::ecdsa_requests::verify_signature(&signed, &key));
+//! }
+//! # Ok(()) }
+//! # #[cfg(not(feature = "blocking"))]
+//! # fn main() {}
+//! ```
+
+use crate::EcdsaVerificationKey;
+#[cfg(feature = "blocking")]
+use crate::alg::DigestAlg;
+#[cfg(feature = "blocking")]
+use crate::ecdsa_requests::{binary_body, digest_body, digest_reader, hash_reader};
+#[cfg(feature = "blocking")]
+use crate::email;
+use crate::http_sig::{self, ReplayGuard, SignatureError};
+#[cfg(feature = "blocking")]
+use crate::manifest::Manifest;
+#[cfg(feature = "blocking")]
+use crate::pdf;
+#[cfg(feature = "blocking")]
+use crate::revocation::RevocationList;
+#[cfg(feature = "blocking")]
+use crate::transparency::{ConsistencyProof, InclusionProof, LogError, TreeHead};
+use crate::x509::{self, EndorsementError};
+#[cfg(feature = "blocking")]
+use crate::{EcdsaSignedTimestamp, ServerVersion, SignedTime};
+use ecdsa_lib::PublicKey;
+use reqwest::StatusCode;
+#[cfg(feature = "blocking")]
+use serde_json::json;
+#[cfg(feature = "blocking")]
+use std::error::Error;
+#[cfg(feature = "blocking")]
+use std::io::Read;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+#[cfg(feature = "blocking")]
+use std::time::Instant;
+
+/// Header the server dedupes timestamp requests by (`[sign_cache]`)
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Scheme of a server on a UNIX socket (see the module docs)
+pub const UNIX_SCHEME: &str = "http+unix";
+
+/// How a [`VtsClient`] talks to the server
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    /// Limit on a whole request, from connecting to the end of the body
+    pub timeout: Option<Duration>,
+    /// Limit on establishing a connection
+    pub connect_timeout: Option<Duration>,
+    /// How many times to retry a request that failed to connect, timed out
+    /// or got 429, 502, 503 or 504. Timestamp requests are retried with
+    /// the same `Idempotency-Key`, so a server with `[sign_cache]` answers
+    /// a retry of a request it already signed with the same timestamp.
+    pub retries: u32,
+    /// Wait before the first retry, doubling for each one after
+    pub backoff: Duration,
+    /// Longest wait between attempts, including one asked for with
+    /// `Retry-After`
+    pub max_backoff: Duration,
+    /// Wait a random 50–100% of each backoff, so clients that failed
+    /// together don't all retry together
+    pub jitter: bool,
+    /// Proxy for all requests, e.g. `http://proxy.internal:3128`. Without
+    /// one, the `HTTP_PROXY`/`HTTPS_PROXY` environment variables apply.
+    pub proxy: Option<String>,
+    /// Refuse any response not signed by this key (RFC 9421, from a server
+    /// with `http_signatures = true`; see `http_sig`), so a proxy or cache
+    /// can't change one unnoticed
+    pub response_key: Option<PublicKey>,
+    /// With `response_key`: how far a signed response's `created` may be
+    /// from now. Each response's nonce is accepted once within it, so a
+    /// cached or replayed response is refused (`SignatureError::Replayed`
+    /// or `Stale`).
+    pub response_max_age: Duration,
+    /// Refuse a key from `request_key` unless its certificate at
+    /// `/key/cert` was issued by this root (`vts-admin cert --issuer-key`;
+    /// see `x509::verify_issued`), so a rotated key is trusted without
+    /// pinning it anew
+    pub trust_root: Option<PublicKey>,
+    /// Key to sign with, by id (`kid` in `request_keys`), for servers with
+    /// several; the primary key if `None`
+    pub kid: Option<String>,
+    /// `[policies]` name to issue timestamps under (`GET /policies` lists
+    /// them); the server's default if `None`
+    pub policy: Option<String>,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        ClientOptions {
+            timeout: Some(Duration::from_secs(30)),
+            connect_timeout: Some(Duration::from_secs(10)),
+            retries: 2,
+            backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(10),
+            jitter: true,
+            proxy: None,
+            response_key: None,
+            response_max_age: Duration::from_secs(300),
+            trust_root: None,
+            kid: None,
+            policy: None,
+        }
+    }
+}
+
+impl ClientOptions {
+    /// The wait before retry number `attempt + 1`: what the server asked
+    /// for in `Retry-After`, or else the exponential backoff
+    fn backoff(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        let wait = retry_after
+            .unwrap_or_else(|| self.backoff.saturating_mul(1 << attempt.min(16)))
+            .min(self.max_backoff);
+        if !self.jitter {
+            return wait;
+        }
+        // Any spread will do; the clock's nanoseconds are spread enough
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .subsec_nanos();
+        wait / 2 + wait.mul_f64(f64::from(nanos % 1000) / 2000.0)
+    }
+}
+
+/// A `Retry-After` given in seconds (the server's own form; HTTP dates
+/// from a proxy are ignored)
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let secs = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    secs.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// Statuses worth trying again: the server (or a proxy) is overloaded or
+/// briefly unavailable
+fn retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Failures to connect, or timeouts, over TCP or a UNIX socket
+fn retryable_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    if let Some(e) = error.downcast_ref::<reqwest::Error>() {
+        return e.is_connect() || e.is_timeout();
+    }
+    error.downcast_ref::<std::io::Error>().is_some_and(|e| {
+        matches!(
+            e.kind(),
+            std::io::ErrorKind::ConnectionRefused
+                | std::io::ErrorKind::NotFound
+                | std::io::ErrorKind::TimedOut
+        )
+    })
+}
+
+/// A fresh `Idempotency-Key` for one timestamp request and its retries
+fn idempotency_key() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!(
+        "vts-{:x}-{:x}-{:x}",
+        std::process::id(),
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Checks the signature of a response when `options.response_key` is set,
+/// and with `replays` that it is no replay
+fn check_signature(
+    options: &ClientOptions,
+    replays: &ReplayGuard,
+    status: StatusCode,
+    headers: &reqwest::header::HeaderMap,
+    body: &[u8],
+) -> Result<(), SignatureError> {
+    let Some(key) = &options.response_key else {
+        return Ok(());
+    };
+    let header = |name: &str| {
+        let value = headers.get(name)?.to_str().ok()?;
+        Some(value.to_string())
+    };
+    let signed = http_sig::verify(status.as_u16(), header, body, key)?;
+    replays.check(&signed, chrono::Utc::now().timestamp())
+}
+
+/// Checks, when `options.trust_root` is set, that `cert` (from `/key/cert`)
+/// is the root's endorsement of `key`
+fn check_endorsement(
+    options: &ClientOptions,
+    key: &EcdsaVerificationKey,
+    cert: &[u8],
+) -> Result<(), EndorsementError> {
+    let Some(root) = &options.trust_root else {
+        return Ok(());
+    };
+    let certified = x509::verify_issued(cert, root, chrono::Utc::now())?;
+    if key.key().as_ref() != Some(&certified.public_key) {
+        return Err(EndorsementError::OtherKey);
+    }
+    Ok(())
+}
+
+/// A `/sign` or `/renew` body, with the `kid` and `policy` from `options`
+/// if any
+fn with_options(options: &ClientOptions, mut body: serde_json::Value) -> serde_json::Value {
+    if let Some(kid) = &options.kid {
+        body["kid"] = kid.clone().into();
+    }
+    if let Some(policy) = &options.policy {
+        body["policy"] = policy.clone().into();
+    }
+    body
+}
+
+/// Body of `GET /keys`
+#[derive(serde::Deserialize)]
+struct Keys {
+    keys: Vec<EcdsaVerificationKey>,
+}
+
+/// `server_addr` with any trailing `/` removed, so paths can be appended
+fn base_addr(server_addr: &str) -> String {
+    server_addr.trim_end_matches('/').to_string()
+}
+
+/// One attempt at a request, as an [`Interceptor`] sees it
+#[derive(Debug, Clone)]
+pub struct RequestInfo {
+    /// e.g. `"POST"`
+    pub method: String,
+    pub url: String,
+    /// The JSON body; empty for a `GET`
+    pub body: Vec<u8>,
+    /// 0 for the first try, 1 for the first retry, ...
+    pub attempt: u32,
+}
+
+impl RequestInfo {
+    fn new(
+        method: &reqwest::Method,
+        url: &reqwest::Url,
+        body: Option<&[u8]>,
+        attempt: u32,
+    ) -> Self {
+        RequestInfo {
+            method: method.to_string(),
+            url: url.to_string(),
+            body: body.map(<[u8]>::to_vec).unwrap_or_default(),
+            attempt,
+        }
+    }
+}
+
+/// The answer to one attempt
+#[derive(Debug)]
+pub struct ResponseInfo<'a> {
+    /// `None` if there was none: the request failed to connect or timed out
+    pub status: Option<u16>,
+    pub body: &'a [u8],
+    /// From sending the request to the end of the body
+    pub elapsed: Duration,
+}
+
+/// A check of a response that failed, failing the call
+#[derive(Debug)]
+pub enum VerifyFailure<'a> {
+    /// The response's signature, with `ClientOptions::response_key`
+    Response(&'a SignatureError),
+    /// The key's certificate, with `ClientOptions::trust_root`
+    Endorsement(&'a EndorsementError),
+}
+
+/// Hooks into every request of a [`VtsClient`] (or
+/// [`nonblocking::VtsClient`]), for logging, metrics or a cache, added with
+/// `with_interceptor`. Each does nothing unless implemented.
+pub trait Interceptor: Send + Sync {
+    /// Before each attempt. A body returned here is taken as the server's
+    /// successful answer and nothing is sent (e.g. one kept from
+    /// `on_response`); the interceptors after this one aren't asked.
+    fn on_request(&self, _request: &RequestInfo) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// After each attempt, error statuses and retried attempts included
+    fn on_response(&self, _request: &RequestInfo, _response: &ResponseInfo) {}
+
+    /// When a response from `url` fails a check the client makes
+    fn on_verify_failure(&self, _url: &str, _failure: &VerifyFailure) {}
+}
+
+/// Lets a caller keep a handle to an interceptor it hands to a client
+impl<I: Interceptor + ?Sized> Interceptor for Arc<I> {
+    fn on_request(&self, request: &RequestInfo) -> Option<Vec<u8>> {
+        (**self).on_request(request)
+    }
+
+    fn on_response(&self, request: &RequestInfo, response: &ResponseInfo) {
+        (**self).on_response(request, response)
+    }
+
+    fn on_verify_failure(&self, url: &str, failure: &VerifyFailure) {
+        (**self).on_verify_failure(url, failure)
+    }
+}
+
+/// A client's interceptors, in the order they were added
+#[derive(Clone, Default)]
+struct Interceptors(Vec<Arc<dyn Interceptor>>);
+
+impl std::fmt::Debug for Interceptors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} interceptors", self.0.len())
+    }
+}
+
+impl Interceptors {
+    fn request(&self, request: &RequestInfo) -> Option<Vec<u8>> {
+        self.0.iter().find_map(|i| i.on_request(request))
+    }
+
+    fn response(&self, request: &RequestInfo, response: &ResponseInfo) {
+        for interceptor in &self.0 {
+            interceptor.on_response(request, response);
+        }
+    }
+
+    fn verify_failure(&self, url: &str, failure: &VerifyFailure) {
+        for interceptor in &self.0 {
+            interceptor.on_verify_failure(url, failure);
+        }
+    }
+}
+
+/// Blocking client for one server (or one tenant of it). Cloning is cheap
+/// and shares the connection pool.
+#[cfg(feature = "blocking")]
+#[derive(Debug, Clone)]
+pub struct VtsClient {
+    base: String,
+    http: reqwest::blocking::Client,
+    options: ClientOptions,
+    /// Nonces of the signed responses accepted, shared by the clones
+    replays: Arc<ReplayGuard>,
+    interceptors: Interceptors,
+}
+
+#[cfg(feature = "blocking")]
+impl VtsClient {
+    /// A client for `server_addr` with the default options
+    pub fn new(server_addr: &str) -> Result<Self, Box<dyn Error>> {
+        Self::with_options(server_addr, ClientOptions::default())
+    }
+
+    /// A client for `server_addr`; fails if `options.proxy` isn't a URL
+    pub fn with_options(server_addr: &str, options: ClientOptions) -> Result<Self, Box<dyn Error>> {
+        let mut builder = reqwest::blocking::Client::builder()
+            .timeout(options.timeout)
+            .pool_idle_timeout(Duration::from_secs(90));
+        if let Some(connect_timeout) = options.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(proxy) = &options.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        Ok(VtsClient {
+            base: base_addr(server_addr),
+            http: builder.build()?,
+            replays: Arc::new(ReplayGuard::new(options.response_max_age)),
+            interceptors: Interceptors::default(),
+            options,
+        })
+    }
+
+    /// The same client (and connection pool) for a named tenant
+    /// (`{server_addr}/t/{tenant}`)
+    pub fn for_tenant(&self, tenant: &str) -> Self {
+        VtsClient {
+            base: format!("{}/t/{}", self.base, tenant),
+            ..self.clone()
+        }
+    }
+
+    /// The same client with a different timeout per request, e.g. a
+    /// longer one for a big batch or a shorter one on a latency budget
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        let mut client = self.clone();
+        client.options.timeout = Some(timeout);
+        client
+    }
+
+    /// The client with `interceptor` called on each of its requests (and
+    /// those of its clones made after), after the ones added before
+    pub fn with_interceptor(mut self, interceptor: impl Interceptor + 'static) -> Self {
+        self.interceptors.0.push(Arc::new(interceptor));
+        self
+    }
+
+    /// The address requests go to, e.g. `http://127.0.0.1:8008/t/acme`
+    pub fn server_addr(&self) -> &str {
+        &self.base
+    }
+
+    /// See [`crate::ecdsa_requests::request_key`]. With a `trust_root`,
+    /// the key's certificate is fetched and checked too.
+    pub fn request_key(&self) -> Result<EcdsaVerificationKey, Box<dyn Error>> {
+        let url = format!("{}/key", self.base);
+        let key = serde_json::from_slice(&self.send(|http| http.get(&url))?)?;
+        if self.options.trust_root.is_some() {
+            let url = format!("{}/key/cert", self.base);
+            let cert = self.send(|http| http.get(&url))?;
+            check_endorsement(&self.options, &key, &cert).inspect_err(|e| {
+                self.interceptors
+                    .verify_failure(&url, &VerifyFailure::Endorsement(e))
+            })?;
+        }
+        Ok(key)
+    }
+
+    /// Every key the server signs with (`GET /keys`), the primary first;
+    /// pick the one for a timestamp with
+    /// [`crate::ecdsa_requests::select_key`]
+    pub fn request_keys(&self) -> Result<Vec<EcdsaVerificationKey>, Box<dyn Error>> {
+        let url = format!("{}/keys", self.base);
+        let keys: Keys = serde_json::from_slice(&self.send(|http| http.get(&url))?)?;
+        Ok(keys.keys)
+    }
+
+    /// The server's revocation list (`GET /revocations`), checked against
+    /// its keys; reject timestamps with
+    /// [`crate::revocation::RevocationList::check`]
+    pub fn request_revocations(&self) -> Result<RevocationList, Box<dyn Error>> {
+        let url = format!("{}/revocations", self.base);
+        let list: RevocationList = serde_json::from_slice(&self.send(|http| http.get(&url))?)?;
+        list.verify(&self.request_keys()?)?;
+        Ok(list)
+    }
+
+    /// The signed head over the server's audit log (`GET /log/root`),
+    /// checked against its keys. Keep it, and later check a newer one
+    /// against it with [`VtsClient::request_consistency`]. A client for
+    /// the server, not a tenant: the log is shared and signed with the
+    /// default tenant's key.
+    pub fn request_tree_head(&self) -> Result<TreeHead, Box<dyn Error>> {
+        let url = format!("{}/log/root", self.base);
+        let head: TreeHead = serde_json::from_slice(&self.send(|http| http.get(&url))?)?;
+        head.verify_with(&self.request_keys()?)?;
+        Ok(head)
+    }
+
+    /// Checks that `new` extends `old` (two heads from
+    /// [`VtsClient::request_tree_head`], oldest first): the lines `old`
+    /// covered are still there, unchanged, under `new`
+    pub fn request_consistency(
+        &self,
+        old: &TreeHead,
+        new: &TreeHead,
+    ) -> Result<(), Box<dyn Error>> {
+        let url = format!("{}/log/consistency", self.base);
+        let query = [("old", old.count), ("new", new.count)];
+        let proof: ConsistencyProof =
+            serde_json::from_slice(&self.send(|http| http.get(&url).query(&query))?)?;
+        match proof.verify(old, new) {
+            true => Ok(()),
+            false => Err(LogError::Inconsistent {
+                old: old.count,
+                new: new.count,
+            }
+            .into()),
+        }
+    }
+
+    /// Audit log line `seq` (a by-hash lookup's `proof.seq`) with its place
+    /// in the tree of `head`; check it against the timestamp with
+    /// [`crate::transparency::verify_timestamp_proof`]
+    pub fn request_inclusion_proof(
+        &self,
+        seq: u64,
+        head: &TreeHead,
+    ) -> Result<InclusionProof, Box<dyn Error>> {
+        let url = format!("{}/log/proof", self.base);
+        let query = [("seq", seq), ("count", head.count)];
+        Ok(serde_json::from_slice(
+            &self.send(|http| http.get(&url).query(&query))?,
+        )?)
+    }
+
+    /// See [`crate::ecdsa_requests::request_timestamp`].
+    pub fn request_timestamp(&self, message: &str) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        self.request_timestamp_with_key(message, &idempotency_key())
+    }
+
+    /// Like [`Self::request_timestamp`], with the caller's own
+    /// `Idempotency-Key`: reusing it after a failure the client gave up on
+    /// (e.g. after a restart) still gets the original timestamp back from
+    /// a server with `[sign_cache]`
+    pub fn request_timestamp_with_key(
+        &self,
+        message: &str,
+        idempotency_key: &str,
+    ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        let url = format!("{}/sign", self.base);
+        let body = with_options(&self.options, json!({ "message": message }));
+        let resp = self.send(|http| {
+            http.post(&url)
+                .header(IDEMPOTENCY_KEY, idempotency_key)
+                .json(&body)
+        })?;
+        Ok(serde_json::from_slice(&resp)?)
+    }
+
+    /// See [`crate::ecdsa_requests::request_binary_timestamp`].
+    pub fn request_binary_timestamp(
+        &self,
+        message: &[u8],
+    ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        let url = format!("{}/sign", self.base);
+        let body = with_options(&self.options, binary_body(message));
+        let key = idempotency_key();
+        let resp = self.send(|http| http.post(&url).header(IDEMPOTENCY_KEY, &key).json(&body))?;
+        Ok(serde_json::from_slice(&resp)?)
+    }
+
+    /// See [`crate::ecdsa_requests::request_timestamp_cose`].
+    pub fn request_timestamp_cose(&self, message: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let url = format!("{}/sign", self.base);
+        let body = with_options(&self.options, json!({ "message": message }));
+        let key = idempotency_key();
+        let resp = self.send(|http| {
+            http.post(&url)
+                .header(reqwest::header::ACCEPT, crate::cose::CONTENT_TYPE)
+                .header(IDEMPOTENCY_KEY, &key)
+                .json(&body)
+        })?;
+        Ok(resp)
+    }
+
+    /// See [`crate::ecdsa_requests::request_file_timestamp`].
+    pub fn request_file_timestamp(
+        &self,
+        reader: impl Read,
+    ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        self.request_timestamp(&hash_reader(reader)?)
+    }
+
+    /// See [`crate::ecdsa_requests::request_file_timestamp_with`].
+    pub fn request_file_timestamp_with(
+        &self,
+        reader: impl Read,
+        alg: DigestAlg,
+    ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        self.request_digest_timestamp(&digest_reader(reader, alg)?, alg)
+    }
+
+    /// Like `request_file_timestamp_with`, for a `digest` already computed
+    /// with `alg`
+    pub fn request_digest_timestamp(
+        &self,
+        digest: &[u8],
+        alg: DigestAlg,
+    ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        let url = format!("{}/sign", self.base);
+        let body = with_options(&self.options, digest_body(digest, alg));
+        let key = idempotency_key();
+        let resp = self.send(|http| http.post(&url).header(IDEMPOTENCY_KEY, &key).json(&body))?;
+        Ok(serde_json::from_slice(&resp)?)
+    }
+
+    /// See [`crate::ecdsa_requests::request_manifest_timestamp`].
+    pub fn request_manifest_timestamp(
+        &self,
+        manifest: &Manifest,
+    ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        self.request_file_timestamp_with(manifest.encode().as_bytes(), DigestAlg::Sha256)
+    }
+
+    /// See [`crate::ecdsa_requests::request_email_timestamp`].
+    pub fn request_email_timestamp(
+        &self,
+        raw: &[u8],
+    ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        let canonical = email::canonicalize(raw)?;
+        self.request_file_timestamp_with(canonical.as_bytes(), DigestAlg::Sha256)
+    }
+
+    /// See [`crate::ecdsa_requests::request_pdf_timestamp`].
+    pub fn request_pdf_timestamp(&self, pdf: &[u8]) -> Result<pdf::Proof, Box<dyn Error>> {
+        let url = format!("{}/sign", self.base);
+        let body = with_options(
+            &self.options,
+            digest_body(&pdf::digest(pdf)?, DigestAlg::Sha256),
+        );
+        let key = idempotency_key();
+        let resp = self.send(|http| http.post(&url).header(IDEMPOTENCY_KEY, &key).json(&body))?;
+        Ok(pdf::Proof::new(pdf, serde_json::from_slice(&resp)?)?)
+    }
+
+    /// See [`crate::ecdsa_requests::renew_timestamp`].
+    pub fn renew_timestamp(&self, token: &str) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        let url = format!("{}/renew", self.base);
+        let body = with_options(&self.options, json!({ "token": token }));
+        Ok(serde_json::from_slice(
+            &self.send(|http| http.post(&url).json(&body))?,
+        )?)
+    }
+
+    /// See [`crate::ecdsa_requests::request_version`].
+    pub fn request_version(&self) -> Result<ServerVersion, Box<dyn Error>> {
+        let url = format!("{}/version", self.base);
+        Ok(serde_json::from_slice(&self.send(|http| http.get(&url))?)?)
+    }
+
+    /// See [`crate::ecdsa_requests::request_time`].
+    pub fn request_time(&self, nonce: &str) -> Result<SignedTime, Box<dyn Error>> {
+        let url = format!("{}/time", self.base);
+        Ok(serde_json::from_slice(
+            &self.send(|http| http.get(&url).query(&[("nonce", nonce)]))?,
+        )?)
+    }
+
+    /// Sends the request `build` makes, retrying as `options` allow, and
+    /// returns the body; turns an error status into an `ApiError`, and a
+    /// bad signature (with `response_key`) into a `SignatureError`
+    fn send(
+        &self,
+        build: impl Fn(&reqwest::blocking::Client) -> reqwest::blocking::RequestBuilder,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut attempt = 0;
+        let (info, status, headers, body) = loop {
+            let mut request = build(&self.http);
+            if let Some(timeout) = self.options.timeout {
+                request = request.timeout(timeout);
+            }
+            let request = request.build()?;
+            let info = RequestInfo::new(
+                request.method(),
+                request.url(),
+                request.body().and_then(reqwest::blocking::Body::as_bytes),
+                attempt,
+            );
+            if let Some(body) = self.interceptors.request(&info) {
+                return Ok(body);
+            }
+            let started = Instant::now();
+            let result: Result<_, Box<dyn Error>> = match request {
+                #[cfg(unix)]
+                request if request.url().scheme() == UNIX_SCHEME => {
+                    unix::send_blocking(request, self.options.timeout).map_err(|e| e as _)
+                }
+                #[cfg(feature = "testing")]
+                request if request.url().scheme() == crate::testing::MOCK_SCHEME => {
+                    crate::testing::send_blocking(request).map_err(|e| e as _)
+                }
+                request => self.http.execute(request).map_err(Into::into),
+            };
+            // A body that fails to arrive isn't retried
+            let result = match result {
+                Ok(resp) => {
+                    let (status, headers) = (resp.status(), resp.headers().clone());
+                    Ok((status, headers, resp.bytes()?.to_vec()))
+                }
+                Err(e) => Err(e),
+            };
+            self.interceptors.response(
+                &info,
+                &ResponseInfo {
+                    status: result.as_ref().ok().map(|(status, ..)| status.as_u16()),
+                    body: result.as_ref().map_or(&[], |(.., body)| body),
+                    elapsed: started.elapsed(),
+                },
+            );
+            let (retry, wait) = match &result {
+                Ok((status, headers, _)) => (retryable_status(*status), retry_after(headers)),
+                Err(e) => (retryable_error(e.as_ref()), None),
+            };
+            if !retry || attempt >= self.options.retries {
+                let (status, headers, body) = result?;
+                break (info, status, headers, body);
+            }
+            std::thread::sleep(self.options.backoff(attempt, wait));
+            attempt += 1;
+        };
+        check_signature(&self.options, &self.replays, status, &headers, &body).inspect_err(
+            |e| {
+                self.interceptors
+                    .verify_failure(&info.url, &VerifyFailure::Response(e))
+            },
+        )?;
+        if !status.is_success() {
+            return Err(crate::ecdsa_requests::api_error(status, &body).into());
+        }
+        Ok(body)
+    }
+}
+
+/// The async [`VtsClient`], for callers already inside a Tokio runtime
+pub mod nonblocking {
+    use super::{
+        ClientOptions, IDEMPOTENCY_KEY, Interceptor, Interceptors, Keys, RequestInfo, ResponseInfo,
+        UNIX_SCHEME, VerifyFailure, base_addr, check_endorsement, check_signature, idempotency_key,
+        retry_after, retryable_error, retryable_status, with_options,
+    };
+    use crate::alg::DigestAlg;
+    use crate::ecdsa_requests::{binary_body, digest_body};
+    use crate::email;
+    use crate::http_sig::ReplayGuard;
+    use crate::manifest::Manifest;
+    use crate::pdf;
+    use crate::revocation::RevocationList;
+    use crate::transparency::{ConsistencyProof, InclusionProof, LogError, TreeHead};
+    use crate::{EcdsaSignedTimestamp, EcdsaVerificationKey, ServerVersion, SignedTime};
+    use serde_json::json;
+    use std::error::Error;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    /// Async client for one server (or one tenant of it). Cloning is cheap
+    /// and shares the connection pool.
+    #[derive(Debug, Clone)]
+    pub struct VtsClient {
+        base: String,
+        http: reqwest::Client,
+        options: ClientOptions,
+        /// Nonces of the signed responses accepted, shared by the clones
+        replays: Arc<ReplayGuard>,
+        interceptors: Interceptors,
+    }
+
+    impl VtsClient {
+        /// A client for `server_addr` with the default options
+        pub fn new(server_addr: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+            Self::with_options(server_addr, ClientOptions::default())
+        }
+
+        /// A client for `server_addr`; fails if `options.proxy` isn't a URL
+        pub fn with_options(
+            server_addr: &str,
+            options: ClientOptions,
+        ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+            let mut builder = reqwest::Client::builder().pool_idle_timeout(Duration::from_secs(90));
+            if let Some(connect_timeout) = options.connect_timeout {
+                builder = builder.connect_timeout(connect_timeout);
+            }
+            if let Some(proxy) = &options.proxy {
+                builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+            }
+            Ok(VtsClient {
+                base: base_addr(server_addr),
+                http: builder.build()?,
+                replays: Arc::new(ReplayGuard::new(options.response_max_age)),
+                interceptors: Interceptors::default(),
+                options,
+            })
+        }
+
+        /// The same client (and connection pool) for a named tenant
+        /// (`{server_addr}/t/{tenant}`)
+        pub fn for_tenant(&self, tenant: &str) -> Self {
+            VtsClient {
+                base: format!("{}/t/{}", self.base, tenant),
+                ..self.clone()
+            }
+        }
+
+        /// The same client with a different timeout per request, e.g. a
+        /// longer one for a big batch or a shorter one on a latency budget
+        pub fn with_timeout(&self, timeout: Duration) -> Self {
+            let mut client = self.clone();
+            client.options.timeout = Some(timeout);
+            client
+        }
+
+        /// See [`super::VtsClient::with_interceptor`].
+        pub fn with_interceptor(mut self, interceptor: impl Interceptor + 'static) -> Self {
+            self.interceptors.0.push(Arc::new(interceptor));
+            self
+        }
+
+        /// The address requests go to, e.g. `http://127.0.0.1:8008/t/acme`
+        pub fn server_addr(&self) -> &str {
+            &self.base
+        }
+
+        /// See [`crate::ecdsa_requests::nonblocking::request_key`]. With a
+        /// `trust_root`, the key's certificate is fetched and checked too.
+        pub async fn request_key(
+            &self,
+        ) -> Result<EcdsaVerificationKey, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/key", self.base);
+            let key = serde_json::from_slice(&self.send(|http| http.get(&url)).await?)?;
+            if self.options.trust_root.is_some() {
+                let url = format!("{}/key/cert", self.base);
+                let cert = self.send(|http| http.get(&url)).await?;
+                check_endorsement(&self.options, &key, &cert).inspect_err(|e| {
+                    self.interceptors
+                        .verify_failure(&url, &VerifyFailure::Endorsement(e))
+                })?;
+            }
+            Ok(key)
+        }
+
+        /// Async equivalent of [`super::VtsClient::request_keys`].
+        pub async fn request_keys(
+            &self,
+        ) -> Result<Vec<EcdsaVerificationKey>, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/keys", self.base);
+            let keys: Keys = serde_json::from_slice(&self.send(|http| http.get(&url)).await?)?;
+            Ok(keys.keys)
+        }
+
+        /// Async equivalent of [`super::VtsClient::request_revocations`].
+        pub async fn request_revocations(
+            &self,
+        ) -> Result<RevocationList, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/revocations", self.base);
+            let list: RevocationList =
+                serde_json::from_slice(&self.send(|http| http.get(&url)).await?)?;
+            list.verify(&self.request_keys().await?)?;
+            Ok(list)
+        }
+
+        /// Async equivalent of [`super::VtsClient::request_tree_head`].
+        pub async fn request_tree_head(&self) -> Result<TreeHead, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/log/root", self.base);
+            let head: TreeHead = serde_json::from_slice(&self.send(|http| http.get(&url)).await?)?;
+            head.verify_with(&self.request_keys().await?)?;
+            Ok(head)
+        }
+
+        /// Async equivalent of [`super::VtsClient::request_consistency`].
+        pub async fn request_consistency(
+            &self,
+            old: &TreeHead,
+            new: &TreeHead,
+        ) -> Result<(), Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/log/consistency", self.base);
+            let query = [("old", old.count), ("new", new.count)];
+            let proof: ConsistencyProof =
+                serde_json::from_slice(&self.send(|http| http.get(&url).query(&query)).await?)?;
+            match proof.verify(old, new) {
+                true => Ok(()),
+                false => Err(LogError::Inconsistent {
+                    old: old.count,
+                    new: new.count,
+                }
+                .into()),
+            }
+        }
+
+        /// Async equivalent of [`super::VtsClient::request_inclusion_proof`].
+        pub async fn request_inclusion_proof(
+            &self,
+            seq: u64,
+            head: &TreeHead,
+        ) -> Result<InclusionProof, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/log/proof", self.base);
+            let query = [("seq", seq), ("count", head.count)];
+            Ok(serde_json::from_slice(
+                &self.send(|http| http.get(&url).query(&query)).await?,
+            )?)
+        }
+
+        /// See [`crate::ecdsa_requests::nonblocking::request_timestamp`].
+        pub async fn request_timestamp(
+            &self,
+            message: &str,
+        ) -> Result<EcdsaSignedTimestamp, Box<dyn Error + Send + Sync>> {
+            self.request_timestamp_with_key(message, &idempotency_key())
+                .await
+        }
+
+        /// Async equivalent of [`super::VtsClient::request_timestamp_with_key`].
+        pub async fn request_timestamp_with_key(
+            &self,
+            message: &str,
+            idempotency_key: &str,
+        ) -> Result<EcdsaSignedTimestamp, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/sign", self.base);
+            let body = with_options(&self.options, json!({ "message": message }));
+            let resp = self
+                .send(|http| {
+                    http.post(&url)
+                        .header(IDEMPOTENCY_KEY, idempotency_key)
+                        .json(&body)
+                })
+                .await?;
+            Ok(serde_json::from_slice(&resp)?)
+        }
+
+        /// See [`crate::ecdsa_requests::nonblocking::request_binary_timestamp`].
+        pub async fn request_binary_timestamp(
+            &self,
+            message: &[u8],
+        ) -> Result<EcdsaSignedTimestamp, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/sign", self.base);
+            let body = with_options(&self.options, binary_body(message));
+            let key = idempotency_key();
+            let resp = self
+                .send(|http| http.post(&url).header(IDEMPOTENCY_KEY, &key).json(&body))
+                .await?;
+            Ok(serde_json::from_slice(&resp)?)
+        }
+
+        /// Async equivalent of [`super::VtsClient::request_digest_timestamp`].
+        pub async fn request_digest_timestamp(
+            &self,
+            digest: &[u8],
+            alg: DigestAlg,
+        ) -> Result<EcdsaSignedTimestamp, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/sign", self.base);
+            let body = with_options(&self.options, digest_body(digest, alg));
+            let key = idempotency_key();
+            let resp = self
+                .send(|http| http.post(&url).header(IDEMPOTENCY_KEY, &key).json(&body))
+                .await?;
+            Ok(serde_json::from_slice(&resp)?)
+        }
+
+        /// Async equivalent of [`super::VtsClient::request_manifest_timestamp`].
+        pub async fn request_manifest_timestamp(
+            &self,
+            manifest: &Manifest,
+        ) -> Result<EcdsaSignedTimestamp, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/sign", self.base);
+            let body = with_options(
+                &self.options,
+                digest_body(&manifest.root(), DigestAlg::Sha256),
+            );
+            let key = idempotency_key();
+            let resp = self
+                .send(|http| http.post(&url).header(IDEMPOTENCY_KEY, &key).json(&body))
+                .await?;
+            Ok(serde_json::from_slice(&resp)?)
+        }
+
+        /// Async equivalent of [`super::VtsClient::request_email_timestamp`].
+        pub async fn request_email_timestamp(
+            &self,
+            raw: &[u8],
+        ) -> Result<EcdsaSignedTimestamp, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/sign", self.base);
+            let body = with_options(
+                &self.options,
+                digest_body(&email::digest(raw)?, DigestAlg::Sha256),
+            );
+            let key = idempotency_key();
+            let resp = self
+                .send(|http| http.post(&url).header(IDEMPOTENCY_KEY, &key).json(&body))
+                .await?;
+            Ok(serde_json::from_slice(&resp)?)
+        }
+
+        /// Async equivalent of [`super::VtsClient::request_pdf_timestamp`].
+        pub async fn request_pdf_timestamp(
+            &self,
+            pdf: &[u8],
+        ) -> Result<pdf::Proof, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/sign", self.base);
+            let body = with_options(
+                &self.options,
+                digest_body(&pdf::digest(pdf)?, DigestAlg::Sha256),
+            );
+            let key = idempotency_key();
+            let resp = self
+                .send(|http| http.post(&url).header(IDEMPOTENCY_KEY, &key).json(&body))
+                .await?;
+            Ok(pdf::Proof::new(pdf, serde_json::from_slice(&resp)?)?)
+        }
+
+        /// See [`crate::ecdsa_requests::nonblocking::request_timestamp_cose`].
+        pub async fn request_timestamp_cose(
+            &self,
+            message: &str,
+        ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/sign", self.base);
+            let body = with_options(&self.options, json!({ "message": message }));
+            let key = idempotency_key();
+            let resp = self
+                .send(|http| {
+                    http.post(&url)
+                        .header(reqwest::header::ACCEPT, crate::cose::CONTENT_TYPE)
+                        .header(IDEMPOTENCY_KEY, &key)
+                        .json(&body)
+                })
+                .await?;
+            Ok(resp)
+        }
+
+        /// See [`crate::ecdsa_requests::nonblocking::renew_timestamp`].
+        pub async fn renew_timestamp(
+            &self,
+            token: &str,
+        ) -> Result<EcdsaSignedTimestamp, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/renew", self.base);
+            let body = with_options(&self.options, json!({ "token": token }));
+            let resp = self.send(|http| http.post(&url).json(&body)).await?;
+            Ok(serde_json::from_slice(&resp)?)
+        }
+
+        /// See [`crate::ecdsa_requests::nonblocking::request_version`].
+        pub async fn request_version(&self) -> Result<ServerVersion, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/version", self.base);
+            Ok(serde_json::from_slice(
+                &self.send(|http| http.get(&url)).await?,
+            )?)
+        }
+
+        /// See [`crate::ecdsa_requests::nonblocking::request_time`].
+        pub async fn request_time(
+            &self,
+            nonce: &str,
+        ) -> Result<SignedTime, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/time", self.base);
+            Ok(serde_json::from_slice(
+                &self
+                    .send(|http| http.get(&url).query(&[("nonce", nonce)]))
+                    .await?,
+            )?)
+        }
+
+        /// Sends the request `build` makes, retrying as `options` allow, and
+        /// returns the body; turns an error status into an `ApiError`, and
+        /// a bad signature (with `response_key`) into a `SignatureError`
+        async fn send(
+            &self,
+            build: impl Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+        ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+            let mut attempt = 0;
+            let (info, status, headers, body) = loop {
+                let mut request = build(&self.http);
+                if let Some(timeout) = self.options.timeout {
+                    request = request.timeout(timeout);
+                }
+                let request = request.build()?;
+                let info = RequestInfo::new(
+                    request.method(),
+                    request.url(),
+                    request.body().and_then(reqwest::Body::as_bytes),
+                    attempt,
+                );
+                if let Some(body) = self.interceptors.request(&info) {
+                    return Ok(body);
+                }
+                let started = Instant::now();
+                let result = match request {
+                    #[cfg(unix)]
+                    request if request.url().scheme() == UNIX_SCHEME => {
+                        super::unix::send(request, self.options.timeout).await
+                    }
+                    #[cfg(feature = "testing")]
+                    request if request.url().scheme() == crate::testing::MOCK_SCHEME => {
+                        crate::testing::send(request)
+                    }
+                    request => self.http.execute(request).await.map_err(Into::into),
+                };
+                // A body that fails to arrive isn't retried
+                let result = match result {
+                    Ok(resp) => {
+                        let (status, headers) = (resp.status(), resp.headers().clone());
+                        Ok((status, headers, resp.bytes().await?.to_vec()))
+                    }
+                    Err(e) => Err(e),
+                };
+                self.interceptors.response(
+                    &info,
+                    &ResponseInfo {
+                        status: result.as_ref().ok().map(|(status, ..)| status.as_u16()),
+                        body: result.as_ref().map_or(&[], |(.., body)| body),
+                        elapsed: started.elapsed(),
+                    },
+                );
+                let (retry, wait) = match &result {
+                    Ok((status, headers, _)) => (retryable_status(*status), retry_after(headers)),
+                    Err(e) => (retryable_error(e.as_ref()), None),
+                };
+                if !retry || attempt >= self.options.retries {
+                    let (status, headers, body) = result?;
+                    break (info, status, headers, body);
+                }
+                tokio::time::sleep(self.options.backoff(attempt, wait)).await;
+                attempt += 1;
+            };
+            check_signature(&self.options, &self.replays, status, &headers, &body).inspect_err(
+                |e| {
+                    self.interceptors
+                        .verify_failure(&info.url, &VerifyFailure::Response(e))
+                },
+            )?;
+            if !status.is_success() {
+                return Err(crate::ecdsa_requests::api_error(status, &body).into());
+            }
+            Ok(body)
+        }
+    }
+}
+
+/// `http+unix://` requests: reqwest builds them, hyper sends them over the
+/// socket, and the answer is handed back as a reqwest response
+#[cfg(unix)]
+mod unix {
+    use hyper014::Method;
+    use hyper014::body::Bytes;
+    use hyper014::header::{HOST, HeaderMap, HeaderValue};
+    use std::error::Error;
+    use std::time::Duration;
+
+    type BoxError = Box<dyn Error + Send + Sync>;
+
+    /// The socket path in the host of `url` (`%2Frun%2Fvts.sock`)
+    fn socket_path(url: &reqwest::Url) -> Result<String, BoxError> {
+        let host = url.host_str().unwrap_or_default();
+        let bytes = host.as_bytes();
+        let mut path = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let escaped = (bytes[i] == b'%')
+                .then(|| std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok())
+                .flatten()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match escaped {
+                Some(byte) => {
+                    path.push(byte);
+                    i += 3;
+                }
+                None => {
+                    path.push(bytes[i]);
+                    i += 1;
+                }
+            }
+        }
+        match String::from_utf8(path) {
+            Ok(path) if !path.is_empty() => Ok(path),
+            _ => Err(format!("No socket path in {}", url).into()),
+        }
+    }
+
+    /// One request on a fresh connection to the socket
+    async fn exchange(
+        method: Method,
+        url: &reqwest::Url,
+        headers: &HeaderMap,
+        body: Vec<u8>,
+        timeout: Option<Duration>,
+    ) -> Result<hyper014::Response<Bytes>, BoxError> {
+        let socket = socket_path(url)?;
+        let target = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let mut request = hyper014::Request::builder()
+            .method(method)
+            .uri(target)
+            .body(hyper014::Body::from(body))?;
+        *request.headers_mut() = headers.clone();
+        request
+            .headers_mut()
+            .insert(HOST, HeaderValue::from_static("localhost"));
+
+        let exchange = async {
+            let stream = tokio::net::UnixStream::connect(&socket).await?;
+            let (mut sender, connection) = hyper014::client::conn::handshake(stream).await?;
+            tokio::spawn(connection);
+            let (parts, body) = sender.send_request(request).await?.into_parts();
+            let body = hyper014::body::to_bytes(body).await?;
+            Ok::<_, BoxError>(hyper014::Response::from_parts(parts, body))
+        };
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, exchange)
+                .await
+                .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))?,
+            None => exchange.await,
+        }
+    }
+
+    fn body_bytes(body: Option<&[u8]>) -> Vec<u8> {
+        body.map(<[u8]>::to_vec).unwrap_or_default()
+    }
+
+    pub(super) async fn send(
+        request: reqwest::Request,
+        timeout: Option<Duration>,
+    ) -> Result<reqwest::Response, BoxError> {
+        let body = body_bytes(request.body().and_then(reqwest::Body::as_bytes));
+        let resp = exchange(
+            request.method().clone(),
+            request.url(),
+            request.headers(),
+            body,
+            timeout,
+        )
+        .await?;
+        Ok(reqwest::Response::from(resp))
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(super) fn send_blocking(
+        request: reqwest::blocking::Request,
+        timeout: Option<Duration>,
+    ) -> Result<reqwest::blocking::Response, BoxError> {
+        let body = body_bytes(request.body().and_then(reqwest::blocking::Body::as_bytes));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let resp = runtime.block_on(exchange(
+            request.method().clone(),
+            request.url(),
+            request.headers(),
+            body,
+            timeout,
+        ))?;
+        Ok(reqwest::blocking::Response::from(resp))
+    }
+}
@@ -0,0 +1,185 @@
+//! Time sources for the server
+//!
+//! Handlers never call `Utc::now()` for issued timestamps directly; they go
+//! through a `Clock` so tests can inject a `FakeClock`, and through a
+//! `MonotonicClock` so `time-signed` never goes backwards, even if the system
+//! clock jumps or the server restarts.
+
+use crate::wire;
+use crate::wire::TimePrecision;
+use chrono::{DateTime, Duration, Utc};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A source of the current UTC time
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Lets a test keep a handle to a clock it hands to the server
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+}
+
+/// The system clock
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to (for tests)
+pub struct FakeClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl FakeClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    /// Jump to `now` (which may be in the past)
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// Hands out timestamps that never go backwards, on top of any `Clock`: at
+/// microsecond precision (the default of `time-signed`) strictly
+/// increasing ones. At a coarser `precision`, times within one unit are
+/// issued the same, rather than running ahead of the clock under load.
+///
+/// The last issued timestamp (the high-water mark) is written to a file
+/// before it is returned, and read back on startup, so the guarantee also
+/// holds across restarts. The file is replaced atomically, so a crash or a
+/// full disk leaves the previous mark rather than a truncated one.
+pub struct MonotonicClock {
+    inner: Box<dyn Clock>,
+    /// `None` disables persistence
+    state_file: Option<String>,
+    last: Mutex<Option<DateTime<Utc>>>,
+    precision: TimePrecision,
+}
+
+impl MonotonicClock {
+    /// Wraps `inner`, resuming from the high-water mark in `state_file` if it
+    /// exists. An empty `state_file` keeps the mark in memory only.
+    pub fn new(inner: Box<dyn Clock>, state_file: &str) -> std::io::Result<Self> {
+        let state_file = (!state_file.is_empty()).then(|| state_file.to_string());
+        let last = match &state_file {
+            Some(path) if fs::exists(path)? => {
+                let contents = fs::read_to_string(path)?;
+                let mark = wire::parse_time(contents.trim()).map_err(|e| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Invalid high-water mark in {}: {}", path, e),
+                    )
+                })?;
+                Some(mark)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            inner,
+            state_file,
+            last: Mutex::new(last),
+            precision: TimePrecision::default(),
+        })
+    }
+
+    /// This clock, issuing times truncated to `precision`
+    pub fn with_precision(mut self, precision: TimePrecision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// The current time of the underlying clock, without any guarantee
+    pub fn now(&self) -> DateTime<Utc> {
+        self.inner.now()
+    }
+
+    /// The next timestamp to issue: the underlying clock's time truncated to
+    /// the precision, or, if the clock hasn't moved past the previous one,
+    /// 1µs after it (at microseconds) or the same time (coarser). Fails (and
+    /// issues nothing) if the mark can't be persisted.
+    pub fn next(&self) -> std::io::Result<DateTime<Utc>> {
+        let mut last = self.last.lock().unwrap();
+        let next = self.after(*last);
+        if let Some(path) = &self.state_file {
+            replace_file(Path::new(path), wire::canonical_time(next).as_bytes())?;
+        }
+        *last = Some(next);
+        Ok(next)
+    }
+
+    /// What `next` would return now, without issuing or persisting it
+    pub fn peek(&self) -> DateTime<Utc> {
+        self.after(*self.last.lock().unwrap())
+    }
+
+    fn after(&self, last: Option<DateTime<Utc>>) -> DateTime<Utc> {
+        let now = self.precision.truncate(self.inner.now());
+        match last {
+            Some(prev) if now <= prev => match self.precision {
+                TimePrecision::Micros => prev + Duration::microseconds(1),
+                // A mark from a finer precision is rounded up, not down
+                coarser => match coarser.truncate(prev) {
+                    whole if whole == prev => prev,
+                    whole => whole + coarser.unit(),
+                },
+            },
+            _ => now,
+        }
+    }
+}
+
+/// Replaces `path` with `contents`: written to a temporary file beside it
+/// (owner-only on Unix), fsynced and renamed over it, so a reader sees the
+/// old or the new contents, never part of them. The directory is fsynced
+/// too, so the new contents survive a crash.
+pub(crate) fn replace_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".tmp{}", std::process::id()));
+    let tmp = Path::new(&name);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let result = options
+        .open(tmp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(tmp);
+        return result;
+    }
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
@@ -1,9 +1,16 @@
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Utc};
 use ecdsa_lib::KeyPair;
 use serde::{Deserialize, Serialize};
 
+use crate::alg::DigestAlg;
+use crate::signer::key_pair_signer;
+use crate::wire::{Normalization, TimePrecision};
+
 #[derive(Serialize, Deserialize)]
 pub struct CryptoConfig {
     pub private_key: String,
@@ -13,28 +20,1650 @@ pub struct CryptoConfig {
 pub const PRIVATE_BIN: &str = "private_key.bin";
 pub const PUBLIC_BIN: &str = "public_key.bin";
 
+/// Raw (private, public) key bytes, as in the `.bin` files
+pub type KeyBytes = (Vec<u8>, Vec<u8>);
+
+/// Tenant name → raw (private, public) key bytes
+pub type TenantKeys = BTreeMap<String, KeyBytes>;
+
+/// Optional server configuration file, read from the working directory.
+pub const CONFIG_FILE: &str = "vts.toml";
+
+/// Default location of the persisted `time-signed` high-water mark
+pub const HIGH_WATER_FILE: &str = "time_high_water.txt";
+
+/// Default location of the persisted per-tenant serials
+pub const SERIAL_FILE: &str = "serials.json";
+
+/// Default limit on the size of a message to timestamp (1 MiB)
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 1 << 20;
+
+/// Default location of the persisted per-key signature counters
+pub const KEY_STATS_FILE: &str = "key_stats.json";
+
+/// Default location of the heads `[anchor]` had witnessed
+pub const ANCHOR_FILE: &str = "anchors.ndjson";
+
+/// Default port the server listens on
+pub const DEFAULT_PORT: u16 = 8008;
+
+/// Prefix of every environment variable the server reads
+pub const ENV_PREFIX: &str = "VTS_";
+
+/// Looks up an environment variable (`process_env` outside tests)
+pub type Env<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+/// The real process environment
+pub fn process_env(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+/// Contents of `vts.toml`. Every field has a default, so a missing file
+/// (or an empty one) means "single tenant, keys in `private_key.bin`/`public_key.bin`".
+#[derive(Debug, Deserialize)]
+pub struct ServerConfig {
+    /// Extra tenants, keyed by name. Each gets its own keypair, serial
+    /// counter and log, reachable via `/t/{name}/...` or `X-Tenant: {name}`.
+    #[serde(default)]
+    pub tenants: BTreeMap<String, TenantConfig>,
+    /// Named timestamp policies a request picks with `"policy"`
+    /// (`[policies.<name>]`); `default` exists even if not listed
+    #[serde(default)]
+    pub policies: BTreeMap<String, PolicyConfig>,
+    #[serde(default)]
+    pub sign_cache: SignCacheConfig,
+    /// Where the last issued `time-signed` is persisted so it never goes
+    /// backwards across restarts; an empty string keeps it in memory only.
+    #[serde(default = "default_high_water_file")]
+    pub high_water_file: String,
+    /// Where the last serial of every tenant is persisted so serials keep
+    /// increasing across restarts; an empty string keeps them in memory only.
+    #[serde(default = "default_serial_file")]
+    pub serial_file: String,
+    #[serde(default)]
+    pub ntp: NtpConfig,
+    #[serde(default)]
+    pub key_stats: KeyStatsConfig,
+    /// Largest message `/sign` accepts (`413` beyond it). The request body
+    /// may be slightly larger, for the JSON around the message.
+    #[serde(default = "default_max_message_bytes")]
+    pub max_message_bytes: usize,
+    /// Digest algorithms a hash-then-sign request may name in `digest-alg`
+    /// (all of `DigestAlg::SUPPORTED` by default)
+    #[serde(default = "default_digest_algs")]
+    pub digest_algs: Vec<DigestAlg>,
+    /// Sign with an AWS KMS key instead of the default tenant's key files
+    /// (only with the `kms` feature)
+    #[serde(default)]
+    pub kms: Option<KmsConfig>,
+    /// Port to listen on, on all interfaces
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// Where to listen instead, all at once: `HOST:PORT` (`[::]:8008` for
+    /// IPv6), an IP address to listen on at `port`, or `unix:PATH` for a
+    /// UNIX socket (e.g. behind a local reverse proxy). One address may be
+    /// given as a plain string.
+    #[serde(default, deserialize_with = "string_or_list")]
+    pub listen: Vec<String>,
+    /// Key files of the default tenant
+    #[serde(default = "default_private_key_file")]
+    pub private_key_file: String,
+    #[serde(default = "default_public_key_file")]
+    pub public_key_file: String,
+    /// X.509 certificate (PEM or DER) of the default tenant's key, served
+    /// at `GET /key/cert`; empty (the default) serves none (see `x509`)
+    #[serde(default)]
+    pub cert_file: String,
+    /// More keys the default tenant signs with when a request names their
+    /// `kid` (`[[extra_keys]]` tables); generated if missing, like the key
+    /// files above. The primary key stays the one at `GET /key`.
+    #[serde(default)]
+    pub extra_keys: Vec<KeyFiles>,
+    /// Public key files of keys the default tenant no longer signs with,
+    /// still listed at `GET /.well-known/jwks.json` so their timestamps
+    /// verify
+    #[serde(default)]
+    pub retired_public_key_files: Vec<String>,
+    /// When the default tenant's key may sign (`[key_validity]`); it is
+    /// the key in the key files, so update it after a rotation
+    #[serde(default)]
+    pub key_validity: KeyValidity,
+    /// Compromised keys, of any tenant, served signed at `GET /revocations`
+    /// (`[[revoked_keys]]` tables); a listed key stops signing at its
+    /// `compromised_at`
+    #[serde(default)]
+    pub revoked_keys: Vec<RevokedKey>,
+    /// `error`, `warn`, `info` (the default), `debug` or `trace`
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub latency: LatencyConfig,
+    /// Where relative key and state file paths point, see `prepare_data_dir`
+    #[serde(default = "default_data_dir")]
+    pub data_dir: String,
+    /// Signed, hash-chained log of every issued timestamp (see `audit`);
+    /// empty (the default) writes none
+    #[serde(default)]
+    pub audit_file: String,
+    /// What `/sign` does with a message it has timestamped before
+    #[serde(default)]
+    pub duplicates: DuplicatePolicy,
+    /// Whether a text message is signed as received or NFC-normalized
+    /// (see `wire`)
+    #[serde(default)]
+    pub normalization: Normalization,
+    /// `seconds`, `millis` or `micros` (the default): what `time-signed`
+    /// is truncated to, advertised at `GET /version`
+    #[serde(default)]
+    pub time_precision: TimePrecision,
+    /// Sign every response (RFC 9421 `Signature` over its status and
+    /// `Content-Digest`) with the tenant's key, see `http_sig`
+    #[serde(default)]
+    pub http_signatures: bool,
+    /// Only ever take a message's hash: `/sign` refuses plaintext
+    /// (anything but a `digest`), and logs show hashes, never messages
+    #[serde(default)]
+    pub privacy_mode: bool,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    #[serde(default)]
+    pub sign_pool: SignPoolConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub mirror: MirrorConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    /// Only with the `s3` feature
+    #[serde(default)]
+    pub archive: ArchiveConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    /// Only with the `client` feature
+    #[serde(default)]
+    pub gossip: GossipConfig,
+    /// Only with the `client` feature
+    #[serde(default)]
+    pub anchor: AnchorConfig,
+    /// Only with the `roughtime` feature
+    #[serde(default)]
+    pub roughtime: RoughtimeConfig,
+    /// Only with the `otlp` feature
+    #[serde(default)]
+    pub otlp: OtlpConfig,
+}
+
+/// `$XDG_DATA_HOME/vts` (usually `~/.local/share/vts`), or `/var/lib/vts`
+/// if there is no home directory
+fn default_data_dir() -> String {
+    dirs::data_dir()
+        .map(|dir| dir.join("vts"))
+        .unwrap_or_else(|| PathBuf::from("/var/lib/vts"))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// `"a"` as well as `["a", "b"]`
+fn string_or_list<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(d)? {
+        OneOrMany::One(one) if one.is_empty() => Vec::new(),
+        OneOrMany::One(one) => vec![one],
+        OneOrMany::Many(many) => many,
+    })
+}
+
+fn default_port() -> u16 {
+    DEFAULT_PORT
+}
+
+fn default_private_key_file() -> String {
+    PRIVATE_BIN.to_string()
+}
+
+fn default_public_key_file() -> String {
+    PUBLIC_BIN.to_string()
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_max_message_bytes() -> usize {
+    DEFAULT_MAX_MESSAGE_BYTES
+}
+
+fn default_digest_algs() -> Vec<DigestAlg> {
+    DigestAlg::SUPPORTED.to_vec()
+}
+
+/// The `[key_stats]` table: per-key signature counters and limit
+#[derive(Debug, Clone, Deserialize)]
+pub struct KeyStatsConfig {
+    /// Where the counters are persisted; an empty string keeps them in
+    /// memory only.
+    #[serde(default = "default_key_stats_file")]
+    pub file: String,
+    /// Refuse to sign (403) once a key has produced this many signatures,
+    /// forcing rotation; 0 (the default) means unlimited.
+    #[serde(default)]
+    pub max_signatures: u64,
+}
+
+impl Default for KeyStatsConfig {
+    fn default() -> Self {
+        Self {
+            file: default_key_stats_file(),
+            max_signatures: 0,
+        }
+    }
+}
+
+fn default_key_stats_file() -> String {
+    KEY_STATS_FILE.to_string()
+}
+
+/// The `[kms]` table: the AWS KMS key of the default tenant. Credentials
+/// come from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and
+/// `AWS_SESSION_TOKEN`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct KmsConfig {
+    /// Id, ARN or alias of an `ECC_SECG_P256K1` key with usage `SIGN_VERIFY`
+    pub key_id: String,
+    pub region: String,
+    /// Overrides `https://kms.{region}.amazonaws.com` (e.g. a VPC endpoint)
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Attempts per call, the first included, on throttling or 5xx
+    #[serde(default = "default_kms_max_attempts")]
+    pub max_attempts: u32,
+    /// Timeout of each attempt
+    #[serde(default = "default_kms_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_kms_max_attempts() -> u32 {
+    3
+}
+
+fn default_kms_timeout_ms() -> u64 {
+    2000
+}
+
+/// The `[ntp]` table: drift checking against NTP servers
+#[derive(Debug, Clone, Deserialize)]
+pub struct NtpConfig {
+    /// "host:port" of each server; empty (the default) disables the check
+    #[serde(default)]
+    pub servers: Vec<String>,
+    /// Seconds between measurements
+    #[serde(default = "default_ntp_interval_secs")]
+    pub interval_secs: u64,
+    /// Refuse to sign (503) while the measured offset is larger than this
+    #[serde(default = "default_ntp_max_drift_ms")]
+    pub max_drift_ms: u64,
+}
+
+impl Default for NtpConfig {
+    fn default() -> Self {
+        Self {
+            servers: Vec::new(),
+            interval_secs: default_ntp_interval_secs(),
+            max_drift_ms: default_ntp_max_drift_ms(),
+        }
+    }
+}
+
+fn default_ntp_interval_secs() -> u64 {
+    60
+}
+
+fn default_ntp_max_drift_ms() -> u64 {
+    1000
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            tenants: BTreeMap::new(),
+            policies: BTreeMap::new(),
+            sign_cache: SignCacheConfig::default(),
+            high_water_file: default_high_water_file(),
+            serial_file: default_serial_file(),
+            ntp: NtpConfig::default(),
+            key_stats: KeyStatsConfig::default(),
+            max_message_bytes: default_max_message_bytes(),
+            digest_algs: default_digest_algs(),
+            kms: None,
+            port: default_port(),
+            listen: Vec::new(),
+            private_key_file: default_private_key_file(),
+            public_key_file: default_public_key_file(),
+            cert_file: String::new(),
+            extra_keys: Vec::new(),
+            retired_public_key_files: Vec::new(),
+            key_validity: KeyValidity::default(),
+            revoked_keys: Vec::new(),
+            log_level: default_log_level(),
+            logging: LoggingConfig::default(),
+            latency: LatencyConfig::default(),
+            data_dir: default_data_dir(),
+            audit_file: String::new(),
+            duplicates: DuplicatePolicy::default(),
+            normalization: Normalization::default(),
+            time_precision: TimePrecision::default(),
+            http_signatures: false,
+            privacy_mode: false,
+            cors: CorsConfig::default(),
+            sign_pool: SignPoolConfig::default(),
+            limits: LimitsConfig::default(),
+            mirror: MirrorConfig::default(),
+            admin: AdminConfig::default(),
+            archive: ArchiveConfig::default(),
+            retention: RetentionConfig::default(),
+            gossip: GossipConfig::default(),
+            anchor: AnchorConfig::default(),
+            roughtime: RoughtimeConfig::default(),
+            otlp: OtlpConfig::default(),
+        }
+    }
+}
+
+fn default_high_water_file() -> String {
+    HIGH_WATER_FILE.to_string()
+}
+
+fn default_serial_file() -> String {
+    SERIAL_FILE.to_string()
+}
+
+/// `duplicates`: whether a repeated message gets a new timestamp or the
+/// first one it was given
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicatePolicy {
+    /// Every `/sign` issues a new timestamp (the default)
+    #[default]
+    New,
+    /// A message the tenant has timestamped before gets its earliest
+    /// timestamp back, marked `first-seen`. Found in the audit log, so this
+    /// needs `audit_file`. Renewals are always new.
+    FirstSeen,
+}
+
+impl std::str::FromStr for DuplicatePolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "new" => Ok(DuplicatePolicy::New),
+            "first-seen" => Ok(DuplicatePolicy::FirstSeen),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The `[logging]` table: what the per-request log lines show
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoggingConfig {
+    /// How a request's message (and its signature) appear in its line
+    #[serde(default)]
+    pub messages: MessageLogging,
+    /// Share of the lines of successful requests that carry a message to
+    /// log, 0.0 to 1.0 (the default, every one); errors are always logged
+    #[serde(default = "default_log_sample_rate")]
+    pub sample_rate: f64,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            messages: MessageLogging::default(),
+            sample_rate: default_log_sample_rate(),
+        }
+    }
+}
+
+fn default_log_sample_rate() -> f64 {
+    1.0
+}
+
+/// The `[latency]` table: response times by route, at `GET /metrics`
+#[derive(Debug, Clone, Deserialize)]
+pub struct LatencyConfig {
+    /// Latency objective: a slower request is logged with its context and
+    /// counted as slow; 0 logs none
+    #[serde(default = "default_slow_request_ms")]
+    pub slow_request_ms: u64,
+    /// How many of each route's last requests its percentiles cover
+    #[serde(default = "default_latency_window")]
+    pub window: usize,
+}
+
+impl Default for LatencyConfig {
+    fn default() -> Self {
+        Self {
+            slow_request_ms: default_slow_request_ms(),
+            window: default_latency_window(),
+        }
+    }
+}
+
+fn default_slow_request_ms() -> u64 {
+    1000
+}
+
+fn default_latency_window() -> usize {
+    1024
+}
+
+/// `[logging] messages`: how much of a message its log line shows
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MessageLogging {
+    /// The message and the signature, as they are
+    Full,
+    /// Only the message's SHA-256, never the signature (the default);
+    /// `privacy_mode` logs at most this
+    #[default]
+    Hash,
+    /// Neither
+    Omit,
+}
+
+impl std::str::FromStr for MessageLogging {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "full" => Ok(MessageLogging::Full),
+            "hash" => Ok(MessageLogging::Hash),
+            "omit" => Ok(MessageLogging::Omit),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The `[sign_cache]` table: reuse of already-issued timestamps
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct SignCacheConfig {
+    /// How long an issued timestamp may be handed out again; 0 disables the cache.
+    #[serde(default)]
+    pub window_secs: u64,
+    /// Also reuse timestamps for repeated identical messages that carry no
+    /// `Idempotency-Key` header (by default only idempotency keys are matched).
+    #[serde(default)]
+    pub by_message_hash: bool,
+}
+
+/// The `[cors]` table: which browser origins may call the server
+#[derive(Debug, Clone, Deserialize)]
+pub struct CorsConfig {
+    /// e.g. `["https://app.example.com"]`, or `["*"]` for any origin;
+    /// empty (the default) sends no CORS headers
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "default_cors_allowed_methods")]
+    pub allowed_methods: Vec<String>,
+    /// Request headers a page may send
+    #[serde(default = "default_cors_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+    /// How long browsers may cache a preflight answer
+    #[serde(default = "default_cors_max_age_secs")]
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: default_cors_allowed_methods(),
+            allowed_headers: default_cors_allowed_headers(),
+            max_age_secs: default_cors_max_age_secs(),
+        }
+    }
+}
+
+fn default_cors_allowed_methods() -> Vec<String> {
+    vec!["GET".to_string(), "POST".to_string()]
+}
+
+fn default_cors_allowed_headers() -> Vec<String> {
+    [
+        "content-type",
+        "x-tenant",
+        "idempotency-key",
+        "x-request-id",
+    ]
+    .map(str::to_string)
+    .to_vec()
+}
+
+fn default_cors_max_age_secs() -> u64 {
+    600
+}
+
+/// The `[sign_pool]` table: the threads signing runs on, off the async
+/// executor, and how much work may wait for them
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignPoolConfig {
+    /// Signatures made at once; 0 (the default) means one per CPU
+    #[serde(default)]
+    pub workers: usize,
+    /// Requests that may wait for a free worker; beyond that `/sign` is
+    /// refused with `503` and `Retry-After`
+    #[serde(default = "default_sign_pool_queue_depth")]
+    pub queue_depth: usize,
+    /// The `Retry-After` of those refusals
+    #[serde(default = "default_sign_pool_retry_after_secs")]
+    pub retry_after_secs: u64,
+}
+
+impl Default for SignPoolConfig {
+    fn default() -> Self {
+        Self {
+            workers: 0,
+            queue_depth: default_sign_pool_queue_depth(),
+            retry_after_secs: default_sign_pool_retry_after_secs(),
+        }
+    }
+}
+
+fn default_sign_pool_queue_depth() -> usize {
+    256
+}
+
+fn default_sign_pool_retry_after_secs() -> u64 {
+    1
+}
+
+/// The `[limits]` table: how much work the server takes on at once, so a
+/// burst is queued (and, past the queue, turned away) rather than served
+/// ever more slowly
+#[derive(Debug, Clone, Deserialize)]
+pub struct LimitsConfig {
+    /// Requests handled at once, over all routes; 0 means unlimited
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Requests that may wait for one of those slots; beyond that they are
+    /// refused with `503` and `Retry-After`
+    #[serde(default = "default_limits_queue_depth")]
+    pub queue_depth: usize,
+    /// How long a request may wait in the queue before it is refused the same way
+    #[serde(default = "default_limits_queue_timeout_ms")]
+    pub queue_timeout_ms: u64,
+    /// The `Retry-After` of those refusals
+    #[serde(default = "default_limits_retry_after_secs")]
+    pub retry_after_secs: u64,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_requests: default_max_concurrent_requests(),
+            queue_depth: default_limits_queue_depth(),
+            queue_timeout_ms: default_limits_queue_timeout_ms(),
+            retry_after_secs: default_limits_retry_after_secs(),
+        }
+    }
+}
+
+fn default_max_concurrent_requests() -> usize {
+    1024
+}
+
+fn default_limits_queue_depth() -> usize {
+    1024
+}
+
+fn default_limits_queue_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_limits_retry_after_secs() -> u64 {
+    1
+}
+
+/// The `[mirror]` table: serve a read-only copy of another server's log
+#[derive(Debug, Clone, Deserialize)]
+pub struct MirrorConfig {
+    /// Base URL of the signing server (`http://vts.example.com:8008`);
+    /// empty (the default) signs as usual. `lab4 --mirror URL` sets it.
+    #[serde(default)]
+    pub upstream: String,
+    /// Seconds between syncs of the upstream log
+    #[serde(default = "default_mirror_interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl Default for MirrorConfig {
+    fn default() -> Self {
+        Self {
+            upstream: String::new(),
+            interval_secs: default_mirror_interval_secs(),
+        }
+    }
+}
+
+fn default_mirror_interval_secs() -> u64 {
+    10
+}
+
+/// The `[archive]` table: seal the audit log into segments and upload them
+/// to S3-compatible object storage. Credentials come from
+/// `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArchiveConfig {
+    /// Base URL of the storage (`https://s3.eu-west-1.amazonaws.com`,
+    /// `http://minio:9000`); empty (the default) archives nothing
+    #[serde(default)]
+    pub endpoint: String,
+    /// Objects are `{endpoint}/{bucket}/{prefix}...` (path-style)
+    #[serde(default)]
+    pub bucket: String,
+    #[serde(default = "default_archive_region")]
+    pub region: String,
+    #[serde(default = "default_archive_prefix")]
+    pub prefix: String,
+    /// Entries per sealed segment
+    #[serde(default = "default_archive_segment_entries")]
+    pub segment_entries: u64,
+    /// Seconds between checks for a full segment
+    #[serde(default = "default_archive_interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            bucket: String::new(),
+            region: default_archive_region(),
+            prefix: default_archive_prefix(),
+            segment_entries: default_archive_segment_entries(),
+            interval_secs: default_archive_interval_secs(),
+        }
+    }
+}
+
+fn default_archive_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_archive_prefix() -> String {
+    "vts/".to_string()
+}
+
+fn default_archive_segment_entries() -> u64 {
+    10_000
+}
+
+fn default_archive_interval_secs() -> u64 {
+    60
+}
+
+/// The `[retention]` table: how long audit log lines are kept whole before
+/// they are compacted (see `audit::AuditLog::compact`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetentionConfig {
+    /// Days a line is kept with its signatures; 0 (the default) keeps them
+    /// forever
+    #[serde(default)]
+    pub full_days: u64,
+    /// Seconds between compactions
+    #[serde(default = "default_retention_interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            full_days: 0,
+            interval_secs: default_retention_interval_secs(),
+        }
+    }
+}
+
+fn default_retention_interval_secs() -> u64 {
+    3600
+}
+
+/// The `[gossip]` table: peer servers whose tree heads this one checks
+/// and co-signs, collecting their co-signatures of its own head in turn
+#[derive(Debug, Clone, Deserialize)]
+pub struct GossipConfig {
+    /// `[[gossip.peers]]` tables; none (the default) gossips with no one
+    #[serde(default)]
+    pub peers: Vec<GossipPeer>,
+    /// Seconds between rounds
+    #[serde(default = "default_gossip_interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl Default for GossipConfig {
+    fn default() -> Self {
+        Self {
+            peers: Vec::new(),
+            interval_secs: default_gossip_interval_secs(),
+        }
+    }
+}
+
+fn default_gossip_interval_secs() -> u64 {
+    60
+}
+
+/// One of the gossip `peers`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct GossipPeer {
+    /// Base URL of the peer (`http://vts-b.example.com:8008`)
+    pub url: String,
+    /// Key id of the peer's audit log key, as its `GET /log/root` gives
+    /// it: the key its `GET /key` serves must match
+    pub kid: String,
+}
+
+/// The `[anchor]` table: an external witness the tree head is posted to,
+/// keeping what it answers as evidence of when the log had those lines
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnchorConfig {
+    /// URL the head is `POST`ed to; empty (the default) anchors nowhere
+    #[serde(default)]
+    pub witness: String,
+    /// Where the witnessed heads are kept; an empty string keeps them in
+    /// memory only
+    #[serde(default = "default_anchor_file")]
+    pub file: String,
+    /// Seconds between anchorings (a head is only posted if it grew)
+    #[serde(default = "default_anchor_interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl Default for AnchorConfig {
+    fn default() -> Self {
+        Self {
+            witness: String::new(),
+            file: default_anchor_file(),
+            interval_secs: default_anchor_interval_secs(),
+        }
+    }
+}
+
+fn default_anchor_file() -> String {
+    ANCHOR_FILE.to_string()
+}
+
+fn default_anchor_interval_secs() -> u64 {
+    3600
+}
+
+/// The `[roughtime]` table: a Roughtime responder on a UDP port of its own
+#[derive(Debug, Clone, Deserialize)]
+pub struct RoughtimeConfig {
+    /// UDP address to answer on (`0.0.0.0:2002`); empty (the default)
+    /// answers on none
+    #[serde(default)]
+    pub listen: String,
+    /// Uncertainty claimed around each answer's time (`RADI`)
+    #[serde(default = "default_roughtime_radius_ms")]
+    pub radius_ms: u32,
+    /// How long each online key the default key delegates to is valid
+    #[serde(default = "default_roughtime_delegation_secs")]
+    pub delegation_secs: u64,
+}
+
+impl Default for RoughtimeConfig {
+    fn default() -> Self {
+        Self {
+            listen: String::new(),
+            radius_ms: default_roughtime_radius_ms(),
+            delegation_secs: default_roughtime_delegation_secs(),
+        }
+    }
+}
+
+fn default_roughtime_radius_ms() -> u32 {
+    1000
+}
+
+fn default_roughtime_delegation_secs() -> u64 {
+    86400
+}
+
+/// The `[otlp]` table: export of a trace of every request to an
+/// OpenTelemetry collector
+#[derive(Debug, Clone, Deserialize)]
+pub struct OtlpConfig {
+    /// Base URL of the collector's OTLP/HTTP receiver
+    /// (`http://otel-collector:4318`); empty (the default) traces nothing
+    #[serde(default)]
+    pub endpoint: String,
+    /// `service.name` of the spans
+    #[serde(default = "default_otlp_service_name")]
+    pub service_name: String,
+    /// Seconds between exports
+    #[serde(default = "default_otlp_interval_secs")]
+    pub interval_secs: u64,
+    /// Most spans waiting for an export; more are dropped
+    #[serde(default = "default_otlp_max_queue")]
+    pub max_queue: usize,
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            service_name: default_otlp_service_name(),
+            interval_secs: default_otlp_interval_secs(),
+            max_queue: default_otlp_max_queue(),
+        }
+    }
+}
+
+fn default_otlp_service_name() -> String {
+    "vts".to_string()
+}
+
+fn default_otlp_interval_secs() -> u64 {
+    5
+}
+
+fn default_otlp_max_queue() -> usize {
+    8192
+}
+
+/// The `[admin]` table: operational endpoints on a listener of their own
+#[derive(Debug, Clone, Deserialize)]
+pub struct AdminConfig {
+    /// Address to serve `/admin/*` on (`127.0.0.1:8009`); empty (the
+    /// default) serves no admin API
+    #[serde(default)]
+    pub listen: String,
+    /// File holding the bearer token admin requests must present (relative
+    /// to `data_dir`); `VTS_ADMIN_TOKEN` takes precedence
+    #[serde(default = "default_admin_token_file")]
+    pub token_file: String,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            listen: String::new(),
+            token_file: default_admin_token_file(),
+        }
+    }
+}
+
+fn default_admin_token_file() -> String {
+    "admin_token".to_string()
+}
+
+/// Shortest admin token accepted
+pub const MIN_ADMIN_TOKEN_LEN: usize = 16;
+
+/// The admin API's bearer token: `VTS_ADMIN_TOKEN` if set, else the
+/// contents of `config.token_file` (surrounding whitespace ignored). It is
+/// never generated, and must be at least `MIN_ADMIN_TOKEN_LEN` characters.
+pub fn load_admin_token(
+    config: &AdminConfig,
+    env: Env,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let token = match env(&format!("{}ADMIN_TOKEN", ENV_PREFIX)) {
+        Some(token) => token,
+        None => fs::read_to_string(&config.token_file).map_err(|e| {
+            format!(
+                "Failed to read admin token {} (create it, e.g. with `openssl rand -hex 32`): {}",
+                config.token_file, e
+            )
+        })?,
+    };
+    let token = token.trim().to_string();
+    if token.len() < MIN_ADMIN_TOKEN_LEN {
+        return Err(format!(
+            "The admin token must be at least {} characters",
+            MIN_ADMIN_TOKEN_LEN
+        )
+        .into());
+    }
+    Ok(token)
+}
+
+/// One `[tenants.<name>]` table
+#[derive(Debug, Default, Deserialize)]
+pub struct TenantConfig {
+    /// Defaults to `<name>_private_key.bin`
+    pub private_key_file: Option<String>,
+    /// Defaults to `<name>_public_key.bin`
+    pub public_key_file: Option<String>,
+    /// Certificate of the tenant's key, as `cert_file` is the default's
+    pub cert_file: Option<String>,
+    /// More keys of the tenant, as `extra_keys` are the default's
+    #[serde(default)]
+    pub extra_keys: Vec<KeyFiles>,
+    /// As `retired_public_key_files` are the default's
+    #[serde(default)]
+    pub retired_public_key_files: Vec<String>,
+    /// As `key_validity` is the default's
+    #[serde(default)]
+    pub key_validity: KeyValidity,
+}
+
+/// The files of one key pair, as in `[[extra_keys]]`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct KeyFiles {
+    pub private_key_file: String,
+    pub public_key_file: String,
+    /// `not_before` / `not_after` of this key
+    #[serde(flatten)]
+    pub validity: KeyValidity,
+}
+
+/// The policy a request gets without naming one, when `[policies]` has it
+pub const DEFAULT_POLICY: &str = "default";
+
+/// What a timestamp issued under a policy guarantees, checked at startup
+/// (`require_audit`) or on every request (`require_ntp`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct PolicyConfig {
+    /// For relying parties, at `GET /policies`
+    #[serde(default)]
+    pub description: String,
+    /// Every timestamp is in the audit log (needs `audit_file`)
+    #[serde(default)]
+    pub require_audit: bool,
+    /// Refuse to sign unless the NTP check has a fresh, good offset (needs
+    /// `[ntp] servers`)
+    #[serde(default)]
+    pub require_ntp: bool,
+}
+
+/// One of the `revoked_keys`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct RevokedKey {
+    /// The key's id, as `GET /keys` gives it
+    pub kid: String,
+    /// RFC 3339, as a string in TOML
+    pub compromised_at: DateTime<Utc>,
+    /// One line of free text, e.g. `key-compromise`
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// When a key may sign, as RFC 3339 strings in TOML; either end may be
+/// left open. The server refuses to sign outside it and `GET /key` says
+/// so, so clients can refuse timestamps outside it too.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct KeyValidity {
+    #[serde(default)]
+    pub not_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub not_after: Option<DateTime<Utc>>,
+}
+
+impl KeyValidity {
+    /// Whether `now` is inside the window
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        self.not_before.is_none_or(|t| t <= now) && self.not_after.is_none_or(|t| now <= t)
+    }
+}
+
+impl TenantConfig {
+    /// Key file paths for tenant `name`, applying the defaults
+    pub fn key_files(&self, name: &str) -> (String, String) {
+        (
+            self.private_key_file
+                .clone()
+                .unwrap_or_else(|| format!("{}_private_key.bin", name)),
+            self.public_key_file
+                .clone()
+                .unwrap_or_else(|| format!("{}_public_key.bin", name)),
+        )
+    }
+}
+
+/// Tenant names end up in URLs and default file names, so keep them simple.
+pub fn is_valid_tenant_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Reads `vts.toml` (or the file named by `VTS_CONFIG`) and applies the
+/// `VTS_*` environment variables on top, see `apply_env`. A missing file
+/// means the default configuration.
+pub fn load_config() -> Result<ServerConfig, Box<dyn std::error::Error>> {
+    let path = process_env("VTS_CONFIG").unwrap_or_else(|| CONFIG_FILE.to_string());
+    let mut config = load_config_from(path)?;
+    apply_env(&mut config, &process_env)?;
+    Ok(config)
+}
+
+/// Like `load_config`, but from an explicit path and without the environment.
+pub fn load_config_from(
+    path: impl AsRef<Path>,
+) -> Result<ServerConfig, Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(ServerConfig::default());
+    }
+    let config: ServerConfig = toml::from_str(&fs::read_to_string(path)?)?;
+    validate(&config)?;
+    Ok(config)
+}
+
+fn validate(config: &ServerConfig) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(bad) = config.tenants.keys().find(|n| !is_valid_tenant_name(n)) {
+        return Err(format!(
+            "Invalid tenant name '{}' (use letters, digits, '-' and '_')",
+            bad
+        )
+        .into());
+    }
+    if let Some(bad) = config.policies.keys().find(|n| !is_valid_tenant_name(n)) {
+        return Err(format!(
+            "Invalid policy name '{}' (use letters, digits, '-' and '_')",
+            bad
+        )
+        .into());
+    }
+    let archive = &config.archive;
+    if !archive.endpoint.is_empty() {
+        if archive.bucket.is_empty() || archive.bucket.contains('/') {
+            return Err(format!("Invalid [archive] bucket '{}'", archive.bucket).into());
+        }
+        if archive.segment_entries == 0 {
+            return Err("[archive] segment_entries must be at least 1".into());
+        }
+    }
+    if let Some(bad) = config
+        .gossip
+        .peers
+        .iter()
+        .find(|p| p.url.is_empty() || p.kid.is_empty())
+    {
+        return Err(format!("Invalid gossip peer '{}' (needs url and kid)", bad.url).into());
+    }
+    let roughtime = &config.roughtime;
+    if !roughtime.listen.is_empty() {
+        // `RADI` is in microseconds, in 32 bits
+        if roughtime.radius_ms == 0 || roughtime.radius_ms > u32::MAX / 1000 {
+            return Err(format!("[roughtime] radius_ms must be 1 to {}", u32::MAX / 1000).into());
+        }
+        if roughtime.delegation_secs < 60 {
+            return Err("[roughtime] delegation_secs must be at least 60".into());
+        }
+    }
+    let otlp = &config.otlp;
+    if !otlp.endpoint.is_empty() && (otlp.interval_secs == 0 || otlp.max_queue == 0) {
+        return Err("[otlp] interval_secs and max_queue must be at least 1".into());
+    }
+    if config.latency.window == 0 {
+        return Err("[latency] window must be at least 1".into());
+    }
+    if !(0.0..=1.0).contains(&config.logging.sample_rate) {
+        return Err("[logging] sample_rate must be 0.0 to 1.0".into());
+    }
+    if config.log_level.parse::<tracing::Level>().is_err() {
+        return Err(format!("Invalid log level '{}'", config.log_level).into());
+    }
+    // The signed list has one line per key, `kid` first
+    if let Some(bad) = config.revoked_keys.iter().find(|r| {
+        r.kid.is_empty() || r.kid.contains(char::is_whitespace) || r.reason.contains(['\n', '\r'])
+    }) {
+        return Err(format!(
+            "Invalid revoked key '{}' (kid without spaces, reason on one line)",
+            bad.kid
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Overrides `config` with the `VTS_*` variables that are set, so the
+/// environment wins over `vts.toml`, which wins over the defaults:
+///
+/// - `VTS_PORT`, `VTS_LISTEN` (comma-separated), `VTS_PRIVATE_KEY_FILE`, `VTS_PUBLIC_KEY_FILE`, `VTS_CERT_FILE`,
+///   `VTS_LOG_LEVEL`, `VTS_DATA_DIR`, `VTS_HIGH_WATER_FILE`, `VTS_SERIAL_FILE`, `VTS_MAX_MESSAGE_BYTES`
+/// - `VTS_LOGGING_MESSAGES` (`full`, `hash` or `omit`), `VTS_LOGGING_SAMPLE_RATE`
+/// - `VTS_LATENCY_SLOW_REQUEST_MS`, `VTS_LATENCY_WINDOW`
+/// - `VTS_AUDIT_FILE`, `VTS_DUPLICATES` (`new` or `first-seen`), `VTS_NORMALIZATION`
+///   (`exact` or `nfc`), `VTS_TIME_PRECISION` (`seconds`, `millis` or `micros`),
+///   `VTS_HTTP_SIGNATURES`, `VTS_PRIVACY_MODE`
+/// - `VTS_SIGN_CACHE_WINDOW_SECS`, `VTS_SIGN_CACHE_BY_MESSAGE_HASH`
+/// - `VTS_NTP_SERVERS` (comma-separated), `VTS_NTP_INTERVAL_SECS`, `VTS_NTP_MAX_DRIFT_MS`
+/// - `VTS_KEY_STATS_FILE`, `VTS_KEY_STATS_MAX_SIGNATURES`
+/// - `VTS_CORS_ALLOWED_ORIGINS`, `VTS_CORS_ALLOWED_METHODS`, `VTS_CORS_ALLOWED_HEADERS`
+///   (comma-separated), `VTS_CORS_MAX_AGE_SECS`
+/// - `VTS_SIGN_POOL_WORKERS`, `VTS_SIGN_POOL_QUEUE_DEPTH`, `VTS_SIGN_POOL_RETRY_AFTER_SECS`
+/// - `VTS_LIMITS_MAX_CONCURRENT_REQUESTS`, `VTS_LIMITS_QUEUE_DEPTH`, `VTS_LIMITS_QUEUE_TIMEOUT_MS`,
+///   `VTS_LIMITS_RETRY_AFTER_SECS`
+/// - `VTS_MIRROR_UPSTREAM`, `VTS_MIRROR_INTERVAL_SECS`
+/// - `VTS_ADMIN_LISTEN`, `VTS_ADMIN_TOKEN_FILE` (the token itself, `VTS_ADMIN_TOKEN`,
+///   is read by `load_admin_token`)
+/// - `VTS_ARCHIVE_ENDPOINT`, `VTS_ARCHIVE_BUCKET`, `VTS_ARCHIVE_REGION`, `VTS_ARCHIVE_PREFIX`,
+///   `VTS_ARCHIVE_SEGMENT_ENTRIES`, `VTS_ARCHIVE_INTERVAL_SECS`
+/// - `VTS_RETENTION_FULL_DAYS`, `VTS_RETENTION_INTERVAL_SECS`
+/// - `VTS_GOSSIP_INTERVAL_SECS` (the peers are only in `vts.toml`)
+/// - `VTS_ANCHOR_WITNESS`, `VTS_ANCHOR_FILE`, `VTS_ANCHOR_INTERVAL_SECS`
+/// - `VTS_ROUGHTIME_LISTEN`, `VTS_ROUGHTIME_RADIUS_MS`, `VTS_ROUGHTIME_DELEGATION_SECS`
+/// - `VTS_OTLP_ENDPOINT`, `VTS_OTLP_SERVICE_NAME`, `VTS_OTLP_INTERVAL_SECS`, `VTS_OTLP_MAX_QUEUE`
+/// - `VTS_KMS_KEY_ID`, `VTS_KMS_REGION`, `VTS_KMS_ENDPOINT`, `VTS_KMS_MAX_ATTEMPTS`,
+///   `VTS_KMS_TIMEOUT_MS` (the first two are required to add a `[kms]` table)
+/// - `VTS_TENANTS` (comma-separated) replaces the tenant list, and
+///   `VTS_TENANT_<NAME>_PRIVATE_KEY_FILE` / `..._PUBLIC_KEY_FILE` / `..._CERT_FILE` set a
+///   tenant's key and certificate files (see `tenant_env_prefix`)
+///
+/// Key material itself is read by `load_keys`, never stored in the config.
+pub fn apply_env(config: &mut ServerConfig, env: Env) -> Result<(), Box<dyn std::error::Error>> {
+    let var = |name: &str| env(&format!("{}{}", ENV_PREFIX, name));
+    fn parse<T: std::str::FromStr>(name: &str, value: String) -> Result<T, String> {
+        value
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid {}{} '{}'", ENV_PREFIX, name, value))
+    }
+    let list = |value: String| -> Vec<String> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+
+    if let Some(v) = var("PORT") {
+        config.port = parse("PORT", v)?;
+    }
+    if let Some(v) = var("LISTEN") {
+        config.listen = list(v);
+    }
+    if let Some(v) = var("PRIVATE_KEY_FILE") {
+        config.private_key_file = v;
+    }
+    if let Some(v) = var("PUBLIC_KEY_FILE") {
+        config.public_key_file = v;
+    }
+    if let Some(v) = var("CERT_FILE") {
+        config.cert_file = v;
+    }
+    if let Some(v) = var("LOG_LEVEL") {
+        config.log_level = v;
+    }
+    if let Some(v) = var("DATA_DIR") {
+        config.data_dir = v;
+    }
+    if let Some(v) = var("HIGH_WATER_FILE") {
+        config.high_water_file = v;
+    }
+    if let Some(v) = var("SERIAL_FILE") {
+        config.serial_file = v;
+    }
+    if let Some(v) = var("AUDIT_FILE") {
+        config.audit_file = v;
+    }
+    if let Some(v) = var("LOGGING_MESSAGES") {
+        config.logging.messages = parse("LOGGING_MESSAGES", v)?;
+    }
+    if let Some(v) = var("LOGGING_SAMPLE_RATE") {
+        config.logging.sample_rate = parse("LOGGING_SAMPLE_RATE", v)?;
+    }
+    if let Some(v) = var("LATENCY_SLOW_REQUEST_MS") {
+        config.latency.slow_request_ms = parse("LATENCY_SLOW_REQUEST_MS", v)?;
+    }
+    if let Some(v) = var("LATENCY_WINDOW") {
+        config.latency.window = parse("LATENCY_WINDOW", v)?;
+    }
+    if let Some(v) = var("DUPLICATES") {
+        config.duplicates = parse("DUPLICATES", v)?;
+    }
+    if let Some(v) = var("NORMALIZATION") {
+        config.normalization = parse("NORMALIZATION", v)?;
+    }
+    if let Some(v) = var("TIME_PRECISION") {
+        config.time_precision = parse("TIME_PRECISION", v)?;
+    }
+    if let Some(v) = var("HTTP_SIGNATURES") {
+        config.http_signatures = parse("HTTP_SIGNATURES", v)?;
+    }
+    if let Some(v) = var("PRIVACY_MODE") {
+        config.privacy_mode = parse("PRIVACY_MODE", v)?;
+    }
+    if let Some(v) = var("MAX_MESSAGE_BYTES") {
+        config.max_message_bytes = parse("MAX_MESSAGE_BYTES", v)?;
+    }
+    if let Some(v) = var("SIGN_CACHE_WINDOW_SECS") {
+        config.sign_cache.window_secs = parse("SIGN_CACHE_WINDOW_SECS", v)?;
+    }
+    if let Some(v) = var("SIGN_CACHE_BY_MESSAGE_HASH") {
+        config.sign_cache.by_message_hash = parse("SIGN_CACHE_BY_MESSAGE_HASH", v)?;
+    }
+    if let Some(v) = var("NTP_SERVERS") {
+        config.ntp.servers = list(v);
+    }
+    if let Some(v) = var("NTP_INTERVAL_SECS") {
+        config.ntp.interval_secs = parse("NTP_INTERVAL_SECS", v)?;
+    }
+    if let Some(v) = var("NTP_MAX_DRIFT_MS") {
+        config.ntp.max_drift_ms = parse("NTP_MAX_DRIFT_MS", v)?;
+    }
+    if let Some(v) = var("KEY_STATS_FILE") {
+        config.key_stats.file = v;
+    }
+    if let Some(v) = var("KEY_STATS_MAX_SIGNATURES") {
+        config.key_stats.max_signatures = parse("KEY_STATS_MAX_SIGNATURES", v)?;
+    }
+    if let Some(v) = var("CORS_ALLOWED_ORIGINS") {
+        config.cors.allowed_origins = list(v);
+    }
+    if let Some(v) = var("CORS_ALLOWED_METHODS") {
+        config.cors.allowed_methods = list(v);
+    }
+    if let Some(v) = var("CORS_ALLOWED_HEADERS") {
+        config.cors.allowed_headers = list(v);
+    }
+    if let Some(v) = var("CORS_MAX_AGE_SECS") {
+        config.cors.max_age_secs = parse("CORS_MAX_AGE_SECS", v)?;
+    }
+    if let Some(v) = var("SIGN_POOL_WORKERS") {
+        config.sign_pool.workers = parse("SIGN_POOL_WORKERS", v)?;
+    }
+    if let Some(v) = var("SIGN_POOL_QUEUE_DEPTH") {
+        config.sign_pool.queue_depth = parse("SIGN_POOL_QUEUE_DEPTH", v)?;
+    }
+    if let Some(v) = var("SIGN_POOL_RETRY_AFTER_SECS") {
+        config.sign_pool.retry_after_secs = parse("SIGN_POOL_RETRY_AFTER_SECS", v)?;
+    }
+    if let Some(v) = var("LIMITS_MAX_CONCURRENT_REQUESTS") {
+        config.limits.max_concurrent_requests = parse("LIMITS_MAX_CONCURRENT_REQUESTS", v)?;
+    }
+    if let Some(v) = var("LIMITS_QUEUE_DEPTH") {
+        config.limits.queue_depth = parse("LIMITS_QUEUE_DEPTH", v)?;
+    }
+    if let Some(v) = var("LIMITS_QUEUE_TIMEOUT_MS") {
+        config.limits.queue_timeout_ms = parse("LIMITS_QUEUE_TIMEOUT_MS", v)?;
+    }
+    if let Some(v) = var("LIMITS_RETRY_AFTER_SECS") {
+        config.limits.retry_after_secs = parse("LIMITS_RETRY_AFTER_SECS", v)?;
+    }
+    if let Some(v) = var("MIRROR_UPSTREAM") {
+        config.mirror.upstream = v;
+    }
+    if let Some(v) = var("MIRROR_INTERVAL_SECS") {
+        config.mirror.interval_secs = parse("MIRROR_INTERVAL_SECS", v)?;
+    }
+    if let Some(v) = var("ADMIN_LISTEN") {
+        config.admin.listen = v;
+    }
+    if let Some(v) = var("ADMIN_TOKEN_FILE") {
+        config.admin.token_file = v;
+    }
+    if let Some(v) = var("ARCHIVE_ENDPOINT") {
+        config.archive.endpoint = v;
+    }
+    if let Some(v) = var("ARCHIVE_BUCKET") {
+        config.archive.bucket = v;
+    }
+    if let Some(v) = var("ARCHIVE_REGION") {
+        config.archive.region = v;
+    }
+    if let Some(v) = var("ARCHIVE_PREFIX") {
+        config.archive.prefix = v;
+    }
+    if let Some(v) = var("ARCHIVE_SEGMENT_ENTRIES") {
+        config.archive.segment_entries = parse("ARCHIVE_SEGMENT_ENTRIES", v)?;
+    }
+    if let Some(v) = var("ARCHIVE_INTERVAL_SECS") {
+        config.archive.interval_secs = parse("ARCHIVE_INTERVAL_SECS", v)?;
+    }
+    if let Some(v) = var("RETENTION_FULL_DAYS") {
+        config.retention.full_days = parse("RETENTION_FULL_DAYS", v)?;
+    }
+    if let Some(v) = var("RETENTION_INTERVAL_SECS") {
+        config.retention.interval_secs = parse("RETENTION_INTERVAL_SECS", v)?;
+    }
+    if let Some(v) = var("GOSSIP_INTERVAL_SECS") {
+        config.gossip.interval_secs = parse("GOSSIP_INTERVAL_SECS", v)?;
+    }
+    if let Some(v) = var("ANCHOR_WITNESS") {
+        config.anchor.witness = v;
+    }
+    if let Some(v) = var("ANCHOR_FILE") {
+        config.anchor.file = v;
+    }
+    if let Some(v) = var("ANCHOR_INTERVAL_SECS") {
+        config.anchor.interval_secs = parse("ANCHOR_INTERVAL_SECS", v)?;
+    }
+    if let Some(v) = var("ROUGHTIME_LISTEN") {
+        config.roughtime.listen = v;
+    }
+    if let Some(v) = var("ROUGHTIME_RADIUS_MS") {
+        config.roughtime.radius_ms = parse("ROUGHTIME_RADIUS_MS", v)?;
+    }
+    if let Some(v) = var("ROUGHTIME_DELEGATION_SECS") {
+        config.roughtime.delegation_secs = parse("ROUGHTIME_DELEGATION_SECS", v)?;
+    }
+    if let Some(v) = var("OTLP_ENDPOINT") {
+        config.otlp.endpoint = v;
+    }
+    if let Some(v) = var("OTLP_SERVICE_NAME") {
+        config.otlp.service_name = v;
+    }
+    if let Some(v) = var("OTLP_INTERVAL_SECS") {
+        config.otlp.interval_secs = parse("OTLP_INTERVAL_SECS", v)?;
+    }
+    if let Some(v) = var("OTLP_MAX_QUEUE") {
+        config.otlp.max_queue = parse("OTLP_MAX_QUEUE", v)?;
+    }
+
+    // [kms]: start from the file's table, or from the two required values
+    if let (None, Some(key_id), Some(region)) = (&config.kms, var("KMS_KEY_ID"), var("KMS_REGION"))
+    {
+        config.kms = Some(KmsConfig {
+            key_id,
+            region,
+            endpoint: None,
+            max_attempts: default_kms_max_attempts(),
+            timeout_ms: default_kms_timeout_ms(),
+        });
+    }
+    if let Some(kms) = &mut config.kms {
+        if let Some(v) = var("KMS_KEY_ID") {
+            kms.key_id = v;
+        }
+        if let Some(v) = var("KMS_REGION") {
+            kms.region = v;
+        }
+        if let Some(v) = var("KMS_ENDPOINT") {
+            kms.endpoint = Some(v);
+        }
+        if let Some(v) = var("KMS_MAX_ATTEMPTS") {
+            kms.max_attempts = parse("KMS_MAX_ATTEMPTS", v)?;
+        }
+        if let Some(v) = var("KMS_TIMEOUT_MS") {
+            kms.timeout_ms = parse("KMS_TIMEOUT_MS", v)?;
+        }
+    }
+
+    if let Some(v) = var("TENANTS") {
+        config.tenants = list(v)
+            .into_iter()
+            .map(|name| (name, TenantConfig::default()))
+            .collect();
+    }
+    for (name, tenant) in &mut config.tenants {
+        let prefix = tenant_env_prefix(name);
+        if let Some(v) = env(&format!("{}PRIVATE_KEY_FILE", prefix)) {
+            tenant.private_key_file = Some(v);
+        }
+        if let Some(v) = env(&format!("{}PUBLIC_KEY_FILE", prefix)) {
+            tenant.public_key_file = Some(v);
+        }
+        if let Some(v) = env(&format!("{}CERT_FILE", prefix)) {
+            tenant.cert_file = Some(v);
+        }
+    }
+
+    validate(config)
+}
+
+/// Prefix of tenant `name`'s variables: `VTS_TENANT_<NAME>_`, with the name
+/// upper-cased and `-` turned into `_` (`course-a` → `VTS_TENANT_COURSE_A_`)
+pub fn tenant_env_prefix(name: &str) -> String {
+    format!(
+        "{}TENANT_{}_",
+        ENV_PREFIX,
+        name.to_ascii_uppercase().replace('-', "_")
+    )
+}
+
+/// Key material from `{prefix}PRIVATE_KEY` / `{prefix}PUBLIC_KEY` (Base64 of
+/// the raw `.bin` bytes), so keys can be passed to a container without
+/// mounting files. The public key may be left out; it is derived. Returns
+/// `None` if `{prefix}PRIVATE_KEY` is not set.
+pub fn keys_from_env(
+    env: Env,
+    prefix: &str,
+) -> Result<Option<KeyBytes>, Box<dyn std::error::Error>> {
+    let Some(private_b64) = env(&format!("{}PRIVATE_KEY", prefix)) else {
+        return Ok(None);
+    };
+    let decode = |name: &str, value: &str| {
+        general_purpose::STANDARD
+            .decode(value.trim())
+            .map_err(|_| format!("{}{} is not valid Base64", prefix, name))
+    };
+    let private_key = decode("PRIVATE_KEY", &private_b64)?;
+    let keypair = KeyPair::from_private_key_bytes(&private_key)
+        .map_err(|_| format!("{}PRIVATE_KEY is not a valid private key", prefix))?;
+    let derived = keypair
+        .public_key()
+        .to_encoded_point(true)
+        .as_bytes()
+        .to_vec();
+    let public_key = match env(&format!("{}PUBLIC_KEY", prefix)) {
+        Some(public_b64) => decode("PUBLIC_KEY", &public_b64)?,
+        None => derived.clone(),
+    };
+    if ecdsa_lib::PublicKey::from_sec1_bytes(&public_key).ok() != Some(keypair.to_public_key()) {
+        return Err(format!(
+            "{}PUBLIC_KEY does not belong to {}PRIVATE_KEY",
+            prefix, prefix
+        )
+        .into());
+    }
+    Ok(Some((private_key, public_key)))
+}
+
+/// Keys of the default tenant: from `VTS_PRIVATE_KEY` / `VTS_PUBLIC_KEY` if
+/// set, else loaded (or generated) at `config.private_key_file` /
+/// `config.public_key_file` (see `prepare_data_dir` for relative paths)
+pub fn load_keys(config: &ServerConfig) -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
+    match keys_from_env(&process_env, ENV_PREFIX)? {
+        Some(keys) => Ok(keys),
+        None => load_or_generate_keys_at(&config.private_key_file, &config.public_key_file),
+    }
+}
+
 /// We simply use the library's `.bin` files as our source of truth.
 /// On startup, if the `.bin` files don't exist, generate a new KeyPair and save them.
 /// Then return the raw key bytes (so server.rs can pass them around if needed).
+///
+/// The files are `private_key.bin` / `public_key.bin` in the default data
+/// directory (moved there from the working directory if that's where they are).
 pub fn load_or_generate_keys() -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
+    let mut config = ServerConfig::default();
+    prepare_data_dir(&mut config)?;
+    load_or_generate_keys_in(&config.data_dir)
+}
+
+/// Same as `load_or_generate_keys`, but with `private_key.bin` /
+/// `public_key.bin` in `dir` (created if missing). Nothing is moved in.
+pub fn load_or_generate_keys_in(
+    dir: impl AsRef<Path>,
+) -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    load_or_generate_keys_at(
+        &dir.join(PRIVATE_BIN).to_string_lossy(),
+        &dir.join(PUBLIC_BIN).to_string_lossy(),
+    )
+}
+
+/// Creates `config.data_dir` (owner-only on Unix) and points every relative
+/// file path of `config` into it: the default tenant's and every tenant's
+/// key files (extra and retired keys too), `high_water_file`,
+/// `serial_file`, `audit_file`, `key_stats.file`, `anchor.file` and
+/// `admin.token_file`. Absolute paths are left alone, and `data_dir = "."`
+/// keeps the old working-directory behaviour.
+///
+/// A file that exists at its old place in the working directory but not yet
+/// in the data directory is moved there, so upgrading keeps the same keys
+/// and state. Returns the files that were moved.
+pub fn prepare_data_dir(
+    config: &mut ServerConfig,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    prepare_data_dir_in(config, ".")
+}
+
+/// Same as `prepare_data_dir`, with relative paths (the data directory's
+/// too) taken relative to `working_dir` instead of the current directory
+pub fn prepare_data_dir_in(
+    config: &mut ServerConfig,
+    working_dir: impl AsRef<Path>,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    // 1) Create the directory
+    let working_dir = working_dir.as_ref();
+    let data_dir = working_dir.join(&config.data_dir);
+    if !data_dir.exists() {
+        fs::create_dir_all(&data_dir)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&data_dir, fs::Permissions::from_mode(0o700))?;
+        }
+    }
+    let in_cwd = fs::canonicalize(&data_dir)? == fs::canonicalize(working_dir)?;
+
+    // 2) Tenants' default file names become explicit, so they move too
+    for (name, tenant) in &mut config.tenants {
+        let (private_path, public_path) = tenant.key_files(name);
+        tenant.private_key_file = Some(private_path);
+        tenant.public_key_file = Some(public_path);
+    }
+    let mut paths = vec![
+        &mut config.private_key_file,
+        &mut config.public_key_file,
+        &mut config.cert_file,
+        &mut config.high_water_file,
+        &mut config.serial_file,
+        &mut config.audit_file,
+        &mut config.key_stats.file,
+        &mut config.anchor.file,
+        &mut config.admin.token_file,
+    ];
+    for keys in &mut config.extra_keys {
+        paths.extend([&mut keys.private_key_file, &mut keys.public_key_file]);
+    }
+    paths.extend(config.retired_public_key_files.iter_mut());
+    for tenant in config.tenants.values_mut() {
+        paths.extend(tenant.private_key_file.as_mut());
+        paths.extend(tenant.public_key_file.as_mut());
+        paths.extend(tenant.cert_file.as_mut());
+        for keys in &mut tenant.extra_keys {
+            paths.extend([&mut keys.private_key_file, &mut keys.public_key_file]);
+        }
+        paths.extend(tenant.retired_public_key_files.iter_mut());
+    }
+
+    // 3) Move what's still in the working directory, then repoint
+    let mut moved = Vec::new();
+    for path in paths {
+        // An empty path means "in memory only"
+        if path.is_empty() || Path::new(path.as_str()).is_absolute() {
+            continue;
+        }
+        let source = working_dir.join(path.as_str());
+        let target = data_dir.join(path.as_str());
+        if !in_cwd && source.exists() && !target.exists() {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            move_file(&source, &target)?;
+            moved.push(target.clone());
+        }
+        *path = target.to_string_lossy().into_owned();
+    }
+    Ok(moved)
+}
+
+/// `rename`, or copy and delete if the data directory is on another
+/// filesystem (`fs::copy` keeps the permissions, so a private key stays 0600)
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::File::open(to)?.sync_all()?;
+    fs::remove_file(from)
+}
+
+/// `save_to_files_secure` restricts a private key file to its owner on Unix
+/// only; elsewhere the file keeps its directory's default permissions
+pub(crate) fn warn_if_unrestricted(private_path: &str) {
+    if cfg!(not(unix)) {
+        tracing::warn!(
+            "{} was written with its directory's default permissions: private key files are only restricted to their owner on Unix",
+            private_path
+        );
+    }
+}
+
+/// Same as `load_or_generate_keys`, but for an explicit pair of key files.
+pub fn load_or_generate_keys_at(
+    private_path: &str,
+    public_path: &str,
+) -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
+    // Keys are written private first, so a crash in between leaves only the
+    // private key; its public key can be derived and written again
+    if Path::new(private_path).exists() && !Path::new(public_path).exists() {
+        let priv_bytes = fs::read(private_path)?;
+        let keypair = KeyPair::from_private_key_bytes(&priv_bytes)?;
+        keypair.save_to_files_secure(private_path, public_path, true)?;
+        warn_if_unrestricted(private_path);
+        return Ok((priv_bytes, fs::read(public_path)?));
+    }
+
     // If the private/public bin files don't exist, generate and write them:
-    if !Path::new(PRIVATE_BIN).exists() || !Path::new(PUBLIC_BIN).exists() {
+    if !Path::new(private_path).exists() || !Path::new(public_path).exists() {
         // 1) Generate a new keypair
         let keypair = KeyPair::generate();
 
-        // 2) Save to disk (two .bin files)
-        keypair.save_to_files(PRIVATE_BIN, PUBLIC_BIN)?;
+        // 2) Save to disk (two .bin files, the private one 0600, each written
+        //    atomically). If the public key exists without its private key
+        //    this fails rather than replace it.
+        keypair.save_to_files_secure(private_path, public_path, false)?;
+        warn_if_unrestricted(private_path);
 
         // 3) Read the raw bytes back out of those files:
-        let priv_bytes = fs::read(PRIVATE_BIN)?;
-        let pub_bytes = fs::read(PUBLIC_BIN)?;
+        let priv_bytes = fs::read(private_path)?;
+        let pub_bytes = fs::read(public_path)?;
 
         // Return both raw vectors
         return Ok((priv_bytes, pub_bytes));
     }
 
-    // Otherwise, both files exist → just read their contents:
-    let priv_bytes = fs::read(PRIVATE_BIN)?;
-    let pub_bytes = fs::read(PUBLIC_BIN)?;
+    // Otherwise, both files exist → read their contents, and check that
+    // they are one key pair now rather than when a client fails to verify
+    let priv_bytes = fs::read(private_path)?;
+    let pub_bytes = fs::read(public_path)?;
+    key_pair_signer(&priv_bytes, &pub_bytes)
+        .map_err(|e| format!("{} / {}: {}", private_path, public_path, e))?;
     Ok((priv_bytes, pub_bytes))
 }
+
+/// Loads (or generates) the key pair of every tenant in `config`, taking
+/// `VTS_TENANT_<NAME>_PRIVATE_KEY` / `..._PUBLIC_KEY` over the key files.
+pub fn load_or_generate_tenant_keys(
+    config: &ServerConfig,
+) -> Result<TenantKeys, Box<dyn std::error::Error>> {
+    let mut keys = BTreeMap::new();
+    for (name, tenant) in &config.tenants {
+        let tenant_keys = match keys_from_env(&process_env, &tenant_env_prefix(name))? {
+            Some(keys) => keys,
+            None => {
+                let (private_path, public_path) = tenant.key_files(name);
+                load_or_generate_keys_at(&private_path, &public_path)?
+            }
+        };
+        keys.insert(name.clone(), tenant_keys);
+    }
+    Ok(keys)
+}
@@ -0,0 +1,199 @@
+//! COSE_Sign1 (RFC 9052) timestamp tokens
+//!
+//! A compact binary alternative to the JSON and JWS responses, for
+//! constrained clients: with `Accept: application/cose` (or `?format=cose`),
+//! `POST /sign` returns a tagged COSE_Sign1 whose payload is a CBOR map
+//! `{"msg_hash": bstr, "iat": int, "serial": uint}` (plus `"policy": tstr`
+//! for a timestamp issued under a named policy, and `"day_root": bstr` as in
+//! `jws::JwsClaims::day_root`). It is signed with
+//! `ES256K` (COSE algorithm -47, RFC 8812) by the same key as `GET /key`; the
+//! unprotected `kid` is the raw RFC 7638 thumbprint of that key.
+
+use crate::EcdsaVerificationKey;
+use crate::jws::key_thumbprint;
+use ciborium::Value;
+use k256::ecdsa::Signature;
+
+/// Media type of a COSE_Sign1 token (RFC 9052 §11.1)
+pub const CONTENT_TYPE: &str = "application/cose; cose-type=\"cose-sign1\"";
+
+/// CBOR tag of a COSE_Sign1 message
+const TAG_SIGN1: u64 = 18;
+
+/// COSE header labels
+const HEADER_ALG: i64 = 1;
+const HEADER_KID: i64 = 4;
+
+/// COSE algorithm id of ES256K
+const ALG_ES256K: i64 = -47;
+
+/// Claims of a timestamp token
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoseClaims {
+    /// SHA-256 of the timestamped message
+    pub msg_hash: [u8; 32],
+    /// Issue time, in seconds since the Unix epoch
+    pub iat: i64,
+    /// Serial of the timestamp (as in `GET /log/stream`)
+    pub serial: u64,
+    /// Thumbprint of the signing key, see [`key_thumbprint`]
+    pub kid: [u8; 32],
+    /// `[policies]` name it was issued under, if any
+    pub policy: Option<String>,
+    /// Merkle root of the audit log's previous UTC day, see
+    /// `jws::JwsClaims::day_root`
+    pub day_root: Option<[u8; 32]>,
+}
+
+fn to_cbor(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    ciborium::into_writer(value, &mut out).expect("writing CBOR to a Vec cannot fail");
+    out
+}
+
+fn map_get<'a>(map: &'a [(Value, Value)], key: &Value) -> Option<&'a Value> {
+    map.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+/// The bytes actually signed: `["Signature1", protected, h'', payload]`
+fn sig_structure(protected: &[u8], payload: &[u8]) -> Vec<u8> {
+    to_cbor(&Value::Array(vec![
+        Value::Text("Signature1".to_string()),
+        Value::Bytes(protected.to_vec()),
+        Value::Bytes(Vec::new()),
+        Value::Bytes(payload.to_vec()),
+    ]))
+}
+
+/// The protected header and payload of the token for `claims`
+fn protected_and_payload(claims: &CoseClaims) -> (Vec<u8>, Vec<u8>) {
+    let protected = to_cbor(&Value::Map(vec![(
+        Value::Integer(HEADER_ALG.into()),
+        Value::Integer(ALG_ES256K.into()),
+    )]));
+    let mut fields = vec![
+        (
+            Value::Text("msg_hash".to_string()),
+            Value::Bytes(claims.msg_hash.to_vec()),
+        ),
+        (
+            Value::Text("iat".to_string()),
+            Value::Integer(claims.iat.into()),
+        ),
+        (
+            Value::Text("serial".to_string()),
+            Value::Integer(claims.serial.into()),
+        ),
+    ];
+    if let Some(policy) = &claims.policy {
+        fields.push((
+            Value::Text("policy".to_string()),
+            Value::Text(policy.clone()),
+        ));
+    }
+    if let Some(day_root) = &claims.day_root {
+        fields.push((
+            Value::Text("day_root".to_string()),
+            Value::Bytes(day_root.to_vec()),
+        ));
+    }
+    let payload = to_cbor(&Value::Map(fields));
+    (protected, payload)
+}
+
+/// What [`encode`] signs: the CBOR `Sig_structure` of the token for `claims`
+pub fn signing_input(claims: &CoseClaims) -> Vec<u8> {
+    let (protected, payload) = protected_and_payload(claims);
+    sig_structure(&protected, &payload)
+}
+
+/// Builds a tagged COSE_Sign1 over `claims`. `sign` must sign its input with
+/// the key `claims.kid` refers to; its error is returned as is.
+pub fn encode<E>(
+    claims: &CoseClaims,
+    sign: impl FnOnce(&[u8]) -> Result<Signature, E>,
+) -> Result<Vec<u8>, E> {
+    let (protected, payload) = protected_and_payload(claims);
+    let signature = sign(&sig_structure(&protected, &payload))?;
+
+    Ok(to_cbor(&Value::Tag(
+        TAG_SIGN1,
+        Box::new(Value::Array(vec![
+            Value::Bytes(protected),
+            Value::Map(vec![(
+                Value::Integer(HEADER_KID.into()),
+                Value::Bytes(claims.kid.to_vec()),
+            )]),
+            Value::Bytes(payload),
+            Value::Bytes(signature.to_vec()),
+        ])),
+    )))
+}
+
+/// Verifies a COSE_Sign1 token from `POST /sign` against `key` (from
+/// `GET /key`) and returns its claims. Returns `None` if the token is
+/// malformed, not `ES256K`, signed by another key, or its signature doesn't
+/// verify. The tag is optional, as RFC 9052 allows.
+///
+/// The caller still has to check `msg_hash` against the message it holds.
+pub fn verify(token: &[u8], key: &EcdsaVerificationKey) -> Option<CoseClaims> {
+    // 1) Unwrap [protected, unprotected, payload, signature]
+    let value: Value = ciborium::from_reader(token).ok()?;
+    let value = match value {
+        Value::Tag(TAG_SIGN1, inner) => *inner,
+        Value::Tag(..) => return None,
+        other => other,
+    };
+    let [protected, unprotected, payload, signature]: [Value; 4] =
+        value.into_array().ok()?.try_into().ok()?;
+    let (protected, payload, signature) = (
+        protected.into_bytes().ok()?,
+        payload.into_bytes().ok()?,
+        signature.into_bytes().ok()?,
+    );
+
+    // 2) Only ES256K, and only from this key
+    let protected_map = ciborium::from_reader::<Value, _>(protected.as_slice())
+        .ok()?
+        .into_map()
+        .ok()?;
+    let alg = map_get(&protected_map, &Value::Integer(HEADER_ALG.into()))?;
+    if *alg != Value::Integer(ALG_ES256K.into()) {
+        return None;
+    }
+    let public_key = key.key()?;
+    let kid = key_thumbprint(&public_key);
+    let unprotected = unprotected.into_map().ok()?;
+    if let Some(token_kid) = map_get(&unprotected, &Value::Integer(HEADER_KID.into()))
+        && token_kid.as_bytes()?.as_slice() != kid
+    {
+        return None;
+    }
+
+    // 3) Verify the signature over the Sig_structure
+    let sig = Signature::try_from(signature.as_slice()).ok()?;
+    if !public_key.verify(&sig_structure(&protected, &payload), &sig) {
+        return None;
+    }
+
+    // 4) Decode the claims
+    let claims = ciborium::from_reader::<Value, _>(payload.as_slice())
+        .ok()?
+        .into_map()
+        .ok()?;
+    let field = |name: &str| map_get(&claims, &Value::Text(name.to_string()));
+    Some(CoseClaims {
+        msg_hash: field("msg_hash")?.as_bytes()?.as_slice().try_into().ok()?,
+        iat: i64::try_from(field("iat")?.as_integer()?).ok()?,
+        serial: u64::try_from(field("serial")?.as_integer()?).ok()?,
+        kid,
+        policy: match field("policy") {
+            Some(policy) => Some(policy.as_text()?.to_string()),
+            None => None,
+        },
+        day_root: match field("day_root") {
+            Some(day_root) => Some(day_root.as_bytes()?.as_slice().try_into().ok()?),
+            None => None,
+        },
+    })
+}
@@ -0,0 +1,191 @@
+//! Timestamping an email (RFC 5322 message)
+//!
+//! A mail is rewritten in transit: headers are added and refolded, line
+//! endings change, trailing blank lines come and go. So what is timestamped
+//! is a canonical form of it, [`canonicalize`], in the spirit of DKIM's
+//! "relaxed" header canonicalization (RFC 6376 §3.4.2):
+//!
+//! ```text
+//! vts-email
+//! from:Alice <alice@example.com>
+//! to:bob@example.com
+//! subject:Quarterly report
+//! date:Mon, 2 Jun 2025 09:00:00 +0000
+//! message-id:<1234@example.com>
+//! body:<hex sha-256 of the body>
+//! ```
+//!
+//! Only the headers in [`HEADERS`] count, in that order (all occurrences
+//! of each, in the mail's order), unfolded with runs of whitespace made one
+//! space. The body hash is over its lines ending in CRLF, without trailing
+//! empty lines. Only the SHA-256 of the canonical form is sent (see
+//! `alg::DigestAlg`), so the server never sees the mail.
+//!
+//! The timestamp travels with the mail as an attachment ([`to_mime_part`],
+//! [`from_mime_part`]). [`verify`] checks it against the mail as it was
+//! when it was timestamped; a copy with the attachment added has another
+//! body.
+
+use crate::alg::DigestAlg;
+use crate::ecdsa_requests::verify_signature;
+use crate::{EcdsaSignedTimestamp, EcdsaVerificationKey};
+use base64::{Engine as _, engine::general_purpose};
+use k256::sha2::{Digest, Sha256};
+use std::fmt;
+
+/// The headers that are timestamped, lowercase, in canonical order
+pub const HEADERS: &[&str] = &["from", "to", "cc", "subject", "date", "message-id"];
+
+/// Media type of the attached timestamp: `EcdsaSignedTimestamp::to_token`
+pub const CONTENT_TYPE: &str = "application/vnd.vts.timestamp+json";
+
+/// File name of the attached timestamp
+pub const FILE_NAME: &str = "timestamp.vts.json";
+
+/// Why a mail couldn't be canonicalized
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmailError {
+    /// The header block isn't UTF-8
+    NotUtf8,
+    /// A header line has no `:` (the line)
+    MalformedHeader(String),
+    /// There is no `From:` header, which RFC 5322 requires
+    NoFrom,
+}
+
+impl fmt::Display for EmailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmailError::NotUtf8 => write!(f, "email headers are not UTF-8"),
+            EmailError::MalformedHeader(line) => write!(f, "malformed email header: {}", line),
+            EmailError::NoFrom => write!(f, "email has no From header"),
+        }
+    }
+}
+
+impl std::error::Error for EmailError {}
+
+/// The canonical form of the mail `raw` (see the module docs)
+pub fn canonicalize(raw: &[u8]) -> Result<String, EmailError> {
+    // 1) Split the header block from the body at the first empty line
+    let (headers, body) = split_message(raw);
+    let headers = std::str::from_utf8(headers).map_err(|_| EmailError::NotUtf8)?;
+
+    // 2) Unfold the headers, keeping the ones that count
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for line in headers.split('\n').map(|l| l.trim_end_matches('\r')) {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = fields.last_mut() {
+                value.push(' ');
+                value.push_str(line);
+            }
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| EmailError::MalformedHeader(line.to_string()))?;
+        fields.push((name.trim().to_ascii_lowercase(), value.to_string()));
+    }
+    if !fields.iter().any(|(name, _)| name == "from") {
+        return Err(EmailError::NoFrom);
+    }
+
+    // 3) The kept headers in canonical order, then the body hash
+    let mut text = "vts-email\n".to_string();
+    for wanted in HEADERS {
+        for (_, value) in fields.iter().filter(|(name, _)| name == wanted) {
+            let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+            text.push_str(&format!("{}:{}\n", wanted, value));
+        }
+    }
+    text.push_str(&format!("body:{}\n", hex::encode(body_hash(body))));
+    Ok(text)
+}
+
+/// The header block and the body, split at the first empty line (CRLF or
+/// LF); a mail without one is all headers
+fn split_message(raw: &[u8]) -> (&[u8], &[u8]) {
+    let mut start = 0;
+    while start < raw.len() {
+        let end = raw[start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(raw.len(), |i| start + i + 1);
+        let line = &raw[start..end];
+        if line == b"\n" || line == b"\r\n" {
+            return (&raw[..start], &raw[end..]);
+        }
+        start = end;
+    }
+    (raw, &[])
+}
+
+/// SHA-256 of the body's lines, each ending in CRLF, without trailing
+/// empty lines
+fn body_hash(body: &[u8]) -> [u8; 32] {
+    let mut lines: Vec<&[u8]> = body
+        .split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    let mut hasher = Sha256::new();
+    for line in lines {
+        hasher.update(line);
+        hasher.update(b"\r\n");
+    }
+    hasher.finalize().into()
+}
+
+/// SHA-256 of [`canonicalize`]: what `request_email_timestamp` sends
+pub fn digest(raw: &[u8]) -> Result<[u8; 32], EmailError> {
+    Ok(Sha256::digest(canonicalize(raw)?.as_bytes()).into())
+}
+
+/// Whether `signed` timestamps the mail `raw` (as it was then), under `key`
+pub fn verify(
+    raw: &[u8],
+    signed: &EcdsaSignedTimestamp,
+    key: &EcdsaVerificationKey,
+) -> Result<bool, EmailError> {
+    let message = DigestAlg::Sha256.message(&digest(raw)?);
+    Ok(signed.message == message && verify_signature(signed, key))
+}
+
+/// `signed` as a MIME body part to attach to the mail: its token as
+/// [`CONTENT_TYPE`], Base64 in lines of 76, with CRLF line endings
+pub fn to_mime_part(signed: &EcdsaSignedTimestamp) -> String {
+    let encoded = general_purpose::STANDARD.encode(signed.to_token());
+    let mut part = format!(
+        "Content-Type: {ct}; name=\"{name}\"\r\n\
+         Content-Disposition: attachment; filename=\"{name}\"\r\n\
+         Content-Transfer-Encoding: base64\r\n\r\n",
+        ct = CONTENT_TYPE,
+        name = FILE_NAME
+    );
+    for chunk in encoded.as_bytes().chunks(76) {
+        part.push_str(std::str::from_utf8(chunk).expect("Base64 is ASCII"));
+        part.push_str("\r\n");
+    }
+    part
+}
+
+/// The timestamp in a part from [`to_mime_part`], `None` if it isn't one
+pub fn from_mime_part(part: &str) -> Option<EcdsaSignedTimestamp> {
+    let (headers, body) = split_message(part.as_bytes());
+    let headers = std::str::from_utf8(headers).ok()?.to_ascii_lowercase();
+    if !headers.contains(CONTENT_TYPE) || !headers.contains("base64") {
+        return None;
+    }
+    let encoded: Vec<u8> = body
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    let token = general_purpose::STANDARD.decode(encoded).ok()?;
+    serde_json::from_slice(&token).ok()
+}
@@ -0,0 +1,218 @@
+//! C interface for verification (`include/vts.h`)
+//!
+//! With the `ffi` feature, the `cdylib` (`liblab4.so` / `lab4.dll`) exports
+//! these functions so C and C++ programs can check VTS timestamps and
+//! tokens without reimplementing the byte formats. Keys are the raw SEC1
+//! bytes (`public_key.bin`, or `public-key` decoded); nothing is allocated
+//! for the caller, so there is nothing to free.
+//!
+//! Every function returns `VTS_OK` (0) when the input verifies, or one of
+//! the negative `VTS_ERR_*` codes.
+
+use crate::EcdsaVerificationKey;
+use base64::{Engine as _, engine::general_purpose};
+use ecdsa_lib::PublicKey;
+use std::ffi::{CStr, c_char, c_int};
+
+pub const VTS_OK: c_int = 0;
+/// A required pointer is null
+pub const VTS_ERR_NULL: c_int = -1;
+/// The public key isn't a SEC1 secp256k1 point
+pub const VTS_ERR_BAD_KEY: c_int = -2;
+/// The signature is neither raw `r || s` nor DER, or the token is malformed
+pub const VTS_ERR_BAD_ENCODING: c_int = -3;
+/// Well-formed, but not signed by this key over this data
+pub const VTS_ERR_BAD_SIGNATURE: c_int = -4;
+/// A string argument isn't UTF-8
+pub const VTS_ERR_BAD_UTF8: c_int = -5;
+
+/// Claims of a verified JWS or COSE token
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct VtsClaims {
+    /// SHA-256 of the timestamped message
+    pub msg_hash: [u8; 32],
+    /// Issue time, in seconds since the Unix epoch
+    pub iat: i64,
+    /// Serial of the timestamp within its tenant
+    pub serial: u64,
+    /// RFC 7638 thumbprint of the signing key
+    pub kid: [u8; 32],
+}
+
+/// Checks `sig` (raw `r || s` or DER) over `msg` followed by `time` (the
+/// `time-signed` string, NUL-terminated) under `pubkey`.
+///
+/// # Safety
+/// `msg`, `sig` and `pubkey` must point to `msg_len`, `sig_len` and
+/// `pubkey_len` readable bytes (`msg` may be null if `msg_len` is 0), and
+/// `time` to a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vts_verify_signature(
+    msg: *const u8,
+    msg_len: usize,
+    time: *const c_char,
+    sig: *const u8,
+    sig_len: usize,
+    pubkey: *const u8,
+    pubkey_len: usize,
+) -> c_int {
+    // SAFETY: the caller guarantees the pointers and lengths
+    let (Some(msg), Some(sig), Some(pubkey)) = (unsafe {
+        (
+            bytes(msg, msg_len),
+            bytes(sig, sig_len),
+            bytes(pubkey, pubkey_len),
+        )
+    }) else {
+        return VTS_ERR_NULL;
+    };
+    if time.is_null() {
+        return VTS_ERR_NULL;
+    }
+    // SAFETY: non-null and NUL-terminated, as required
+    let Ok(time) = unsafe { CStr::from_ptr(time) }.to_str() else {
+        return VTS_ERR_BAD_UTF8;
+    };
+    let Ok(public_key) = PublicKey::from_sec1_bytes(pubkey) else {
+        return VTS_ERR_BAD_KEY;
+    };
+
+    let mut data = msg.to_vec();
+    data.extend_from_slice(time.as_bytes());
+    match public_key.verify_encoded(&data, sig) {
+        ecdsa_lib::VerifyOutcome::Ok => VTS_OK,
+        ecdsa_lib::VerifyOutcome::BadEncoding => VTS_ERR_BAD_ENCODING,
+        ecdsa_lib::VerifyOutcome::BadSignature => VTS_ERR_BAD_SIGNATURE,
+    }
+}
+
+/// Verifies a compact JWS token (NUL-terminated) under `pubkey` and, if
+/// `claims_out` isn't null, writes its claims there.
+///
+/// # Safety
+/// `token` must be a NUL-terminated string, `pubkey` must point to
+/// `pubkey_len` readable bytes, and `claims_out` must be null or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vts_verify_jws(
+    token: *const c_char,
+    pubkey: *const u8,
+    pubkey_len: usize,
+    claims_out: *mut VtsClaims,
+) -> c_int {
+    // SAFETY: the caller guarantees the pointer and length
+    let Some(pubkey) = (unsafe { bytes(pubkey, pubkey_len) }) else {
+        return VTS_ERR_NULL;
+    };
+    if token.is_null() {
+        return VTS_ERR_NULL;
+    }
+    // SAFETY: non-null and NUL-terminated, as required
+    let Ok(token) = unsafe { CStr::from_ptr(token) }.to_str() else {
+        return VTS_ERR_BAD_UTF8;
+    };
+    let key = match verification_key(pubkey) {
+        Ok(key) => key,
+        Err(code) => return code,
+    };
+    let Some(claims) = crate::jws::verify(token, &key) else {
+        return VTS_ERR_BAD_SIGNATURE;
+    };
+
+    // The claims of a verified token are the server's, so well-formed
+    let mut out = VtsClaims {
+        iat: claims.iat,
+        serial: claims.serial,
+        ..Default::default()
+    };
+    let (Ok(()), Some(kid)) = (
+        hex::decode_to_slice(&claims.msg_hash, &mut out.msg_hash),
+        general_purpose::URL_SAFE_NO_PAD
+            .decode(&claims.kid)
+            .ok()
+            .and_then(|kid| <[u8; 32]>::try_from(kid).ok()),
+    ) else {
+        return VTS_ERR_BAD_ENCODING;
+    };
+    out.kid = kid;
+    // SAFETY: null or writable, as required
+    unsafe { write_claims(claims_out, out) };
+    VTS_OK
+}
+
+/// Verifies a COSE_Sign1 token under `pubkey` and, if `claims_out` isn't
+/// null, writes its claims there.
+///
+/// # Safety
+/// `token` and `pubkey` must point to `token_len` and `pubkey_len`
+/// readable bytes, and `claims_out` must be null or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vts_verify_cose(
+    token: *const u8,
+    token_len: usize,
+    pubkey: *const u8,
+    pubkey_len: usize,
+    claims_out: *mut VtsClaims,
+) -> c_int {
+    // SAFETY: the caller guarantees the pointers and lengths
+    let (Some(token), Some(pubkey)) =
+        (unsafe { (bytes(token, token_len), bytes(pubkey, pubkey_len)) })
+    else {
+        return VTS_ERR_NULL;
+    };
+    let key = match verification_key(pubkey) {
+        Ok(key) => key,
+        Err(code) => return code,
+    };
+    let Some(claims) = crate::cose::verify(token, &key) else {
+        return VTS_ERR_BAD_SIGNATURE;
+    };
+    let out = VtsClaims {
+        msg_hash: claims.msg_hash,
+        iat: claims.iat,
+        serial: claims.serial,
+        kid: claims.kid,
+    };
+    // SAFETY: null or writable, as required
+    unsafe { write_claims(claims_out, out) };
+    VTS_OK
+}
+
+/// `len` bytes at `ptr`; `None` if `ptr` is null (unless `len` is 0)
+///
+/// # Safety
+/// A non-null `ptr` must point to `len` readable bytes.
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (ptr.is_null(), len) {
+        (true, 0) => Some(&[]),
+        (true, _) => None,
+        // SAFETY: non-null and `len` readable bytes, as required
+        (false, _) => Some(unsafe { std::slice::from_raw_parts(ptr, len) }),
+    }
+}
+
+/// # Safety
+/// `out` must be null or writable.
+unsafe fn write_claims(out: *mut VtsClaims, claims: VtsClaims) {
+    if !out.is_null() {
+        // SAFETY: non-null and writable, as required
+        unsafe { out.write(claims) };
+    }
+}
+
+/// `pubkey` as if it came from `GET /key`, which is what the token
+/// verifiers take
+fn verification_key(pubkey: &[u8]) -> Result<EcdsaVerificationKey, c_int> {
+    let public_key = PublicKey::from_sec1_bytes(pubkey).map_err(|_| VTS_ERR_BAD_KEY)?;
+    Ok(EcdsaVerificationKey {
+        request: "GET".to_string(),
+        time_requested: String::new(),
+        public_key: general_purpose::STANDARD.encode(public_key.to_sec1_bytes()),
+        format: None,
+        signature_alg: None,
+        hash_alg: None,
+        kid: None,
+        not_before: None,
+        not_after: None,
+    })
+}
@@ -0,0 +1,253 @@
+//! HTTP Message Signatures (RFC 9421) on responses, so a client can tell
+//! when a proxy or cache changed a body on the way (`http_signatures = true`)
+//!
+//! The server adds to each response a `Content-Digest` (RFC 9530, SHA-256
+//! of the body) and signs it with the tenant's key, together with the
+//! status and `Content-Type`:
+//!
+//! ```text
+//! Content-Digest: sha-256=:X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=:
+//! Signature-Input: vts=("@status" "content-type" "content-digest");created=1748840735;keyid="...";nonce="..."
+//! Signature: vts=:<Base64 of r ‖ s>:
+//! ```
+//!
+//! The signed bytes are the signature base of RFC 9421 §2.5, one
+//! `"component": value` line per covered component and the
+//! `"@signature-params"` line last. `keyid` is the JWS key id of the signing
+//! key (`jws::key_id`). Event streams are never signed: they have no end to
+//! digest.
+//!
+//! `nonce` is random per response, so even two identical bodies are
+//! signed apart. A [`ReplayGuard`] remembers the nonces it accepted and
+//! refuses a response seen before, or signed too long ago to remember:
+//! an old `GET /key` answer served again by a cache or an attacker.
+
+use crate::jws;
+use base64::{Engine as _, engine::general_purpose};
+use ecdsa_lib::PublicKey;
+use k256::ecdsa::Signature;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Label of the server's signature in `Signature-Input` and `Signature`
+pub const LABEL: &str = "vts";
+pub const CONTENT_DIGEST: &str = "content-digest";
+pub const SIGNATURE_INPUT: &str = "signature-input";
+pub const SIGNATURE: &str = "signature";
+
+/// Why a response's signature was not accepted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureError {
+    /// No `vts` signature (the server doesn't sign, or it was stripped)
+    Unsigned,
+    /// The headers are there but can't be parsed, or cover too little
+    Malformed(&'static str),
+    /// The body isn't the one `Content-Digest` describes
+    Digest,
+    /// Signed by another key (e.g. after a rotation); the key id it names
+    Key(String),
+    /// The signature doesn't verify
+    Invalid,
+    /// Its nonce was seen before: the response is a replay
+    Replayed,
+    /// Signed at `created` (Unix seconds), further from now than allowed
+    Stale(i64),
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::Unsigned => write!(f, "response is not signed"),
+            SignatureError::Malformed(why) => write!(f, "malformed response signature: {}", why),
+            SignatureError::Digest => write!(f, "response body does not match Content-Digest"),
+            SignatureError::Key(key_id) => write!(f, "response signed by another key ({})", key_id),
+            SignatureError::Invalid => write!(f, "response signature does not verify"),
+            SignatureError::Replayed => write!(f, "response was seen before (replayed)"),
+            SignatureError::Stale(created) => {
+                write!(f, "response was signed at {}, too long ago", created)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// `Content-Digest` of `body`: `sha-256=:<Base64 of SHA-256>:`
+pub fn content_digest(body: &[u8]) -> String {
+    format!(
+        "sha-256=:{}:",
+        general_purpose::STANDARD.encode(Sha256::digest(body))
+    )
+}
+
+/// The `@signature-params` value for `components`, as it appears after
+/// `vts=` in `Signature-Input`
+pub fn signature_params(components: &[&str], created: i64, key_id: &str, nonce: &str) -> String {
+    let quoted: Vec<String> = components.iter().map(|c| format!("\"{}\"", c)).collect();
+    format!(
+        "({});created={};keyid=\"{}\";nonce=\"{}\"",
+        quoted.join(" "),
+        created,
+        key_id,
+        nonce
+    )
+}
+
+/// The parameters of a signature that verified
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signed {
+    /// When it was signed, in Unix seconds
+    pub created: i64,
+    /// `None` from a server that predates nonces
+    pub nonce: Option<String>,
+}
+
+/// The bytes signed for a response with `status`, covering `components`
+/// (`@status` or lowercase header names, looked up with `header`)
+pub fn signature_base(
+    status: u16,
+    components: &[&str],
+    params: &str,
+    header: impl Fn(&str) -> Option<String>,
+) -> Result<String, SignatureError> {
+    let mut base = String::new();
+    for &component in components {
+        let value = match component {
+            "@status" => status.to_string(),
+            _ if component.starts_with('@') => {
+                return Err(SignatureError::Malformed("unsupported derived component"));
+            }
+            _ => {
+                header(component).ok_or(SignatureError::Malformed("a covered header is missing"))?
+            }
+        };
+        base.push_str(&format!("\"{}\": {}\n", component, value.trim()));
+    }
+    base.push_str(&format!("\"@signature-params\": {}", params));
+    Ok(base)
+}
+
+/// `Signature-Input` and `Signature` header values for a signed base
+pub fn headers(params: &str, signature: &Signature) -> (String, String) {
+    (
+        format!("{}={}", LABEL, params),
+        format!(
+            "{}=:{}:",
+            LABEL,
+            general_purpose::STANDARD.encode(signature.to_bytes())
+        ),
+    )
+}
+
+/// Checks the `vts` signature of a response with `status` and `body`
+/// against `key`; `header` looks up response headers by lowercase name
+pub fn verify(
+    status: u16,
+    header: impl Fn(&str) -> Option<String>,
+    body: &[u8],
+    key: &PublicKey,
+) -> Result<Signed, SignatureError> {
+    // 1) Our member of each header
+    let member = |name: &str| {
+        let value = header(name)?;
+        value.split(',').find_map(|member| {
+            let (label, rest) = member.trim().split_once('=')?;
+            (label == LABEL).then(|| rest.to_string())
+        })
+    };
+    let (Some(params), Some(signature)) = (member(SIGNATURE_INPUT), member(SIGNATURE)) else {
+        return Err(SignatureError::Unsigned);
+    };
+
+    // 2) What it covers: the status and the body, at least
+    let (list, parameters) = params
+        .strip_prefix('(')
+        .and_then(|rest| rest.split_once(')'))
+        .ok_or(SignatureError::Malformed("no component list"))?;
+    let components: Vec<&str> = list
+        .split_whitespace()
+        .map(|c| c.strip_prefix('"').and_then(|c| c.strip_suffix('"')))
+        .collect::<Option<_>>()
+        .ok_or(SignatureError::Malformed("unquoted component"))?;
+    if !components.contains(&"@status") || !components.contains(&CONTENT_DIGEST) {
+        return Err(SignatureError::Malformed(
+            "must cover @status and content-digest",
+        ));
+    }
+
+    // 3) The body is the one digested, and the key the expected one
+    let digest = content_digest(body);
+    let digests = header(CONTENT_DIGEST).unwrap_or_default();
+    if !digests.split(',').any(|d| d.trim() == digest) {
+        return Err(SignatureError::Digest);
+    }
+    let parameter = |name: &str| {
+        parameters
+            .split(';')
+            .find_map(|p| p.strip_prefix(name)?.strip_prefix('='))
+            .map(|v| v.trim_matches('"'))
+    };
+    let key_id = parameter("keyid").ok_or(SignatureError::Malformed("no keyid"))?;
+    if key_id != jws::key_id(key) {
+        return Err(SignatureError::Key(key_id.to_string()));
+    }
+
+    // 4) The signature over the base
+    let base = signature_base(status, &components, &params, &header)?;
+    let signature = signature
+        .strip_prefix(':')
+        .and_then(|s| s.strip_suffix(':'))
+        .and_then(|s| general_purpose::STANDARD.decode(s).ok())
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        .ok_or(SignatureError::Malformed("signature is not Base64 r ‖ s"))?;
+    if !key.verify(base.as_bytes(), &signature) {
+        return Err(SignatureError::Invalid);
+    }
+    Ok(Signed {
+        created: parameter("created")
+            .and_then(|c| c.parse().ok())
+            .ok_or(SignatureError::Malformed("no created"))?,
+        nonce: parameter("nonce").map(str::to_string),
+    })
+}
+
+/// Refuses responses it has seen before, by nonce. A response signed more
+/// than `max_age` from now (either way) is refused too, so nonces need
+/// only be kept that long.
+#[derive(Debug)]
+pub struct ReplayGuard {
+    max_age: Duration,
+    /// Accepted nonces, with their `created`
+    seen: Mutex<HashMap<String, i64>>,
+}
+
+impl ReplayGuard {
+    pub fn new(max_age: Duration) -> Self {
+        ReplayGuard {
+            max_age,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Accepts `signed`, at `now` (Unix seconds), if it has a nonce not
+    /// seen before and is recent enough
+    pub fn check(&self, signed: &Signed, now: i64) -> Result<(), SignatureError> {
+        let max_age = self.max_age.as_secs();
+        if signed.created.abs_diff(now) > max_age {
+            return Err(SignatureError::Stale(signed.created));
+        }
+        let nonce = signed
+            .nonce
+            .as_ref()
+            .ok_or(SignatureError::Malformed("no nonce"))?;
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, created| created.abs_diff(now) <= max_age);
+        if seen.insert(nonce.clone(), signed.created).is_some() {
+            return Err(SignatureError::Replayed);
+        }
+        Ok(())
+    }
+}
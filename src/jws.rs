@@ -0,0 +1,186 @@
+//! JWS (RFC 7515) timestamp tokens
+//!
+//! With `Accept: application/jose` (or `?format=jws`), `POST /sign` returns a
+//! compact JWS instead of the JSON body: `ES256K` (RFC 8812, ECDSA over
+//! secp256k1 with SHA-256) over the claims in [`JwsClaims`]. The `kid` is
+//! the RFC 7638 thumbprint of the signing key, so it can be matched against
+//! `GET /key` without any extra lookup.
+//!
+//! `GET /.well-known/jwks.json` serves the keys as a JWK Set (RFC 7517 §5)
+//! of [`Jwk`]s, for JOSE libraries that look keys up by `kid`.
+
+use crate::EcdsaVerificationKey;
+use base64::{Engine as _, engine::general_purpose};
+use ecdsa_lib::PublicKey;
+use k256::ecdsa::Signature;
+use k256::sha2::{Digest, Sha256};
+use serde::{Deserialize, Serialize};
+
+/// Media type of a compact JWS (RFC 7515 §9.2.1)
+pub const CONTENT_TYPE: &str = "application/jose";
+
+/// The only algorithm the server issues and `verify` accepts
+const ALG: &str = "ES256K";
+
+/// Claims of a timestamp token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JwsClaims {
+    /// Hex-encoded SHA-256 of the timestamped message
+    pub msg_hash: String,
+    /// Issue time, in seconds since the Unix epoch
+    pub iat: i64,
+    /// Serial of the timestamp (as in `GET /log/stream`)
+    pub serial: u64,
+    /// Thumbprint of the signing key, see [`key_id`]
+    pub kid: String,
+    /// `[policies]` name it was issued under, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    /// Hex Merkle root of the audit log's previous UTC day (as signed at
+    /// `GET /log/day/{date}`), if the server keeps one and that day has lines
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day_root: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    alg: String,
+    typ: String,
+    kid: String,
+}
+
+/// A public key as a JWK (RFC 7517, with the `EC` members of RFC 7518 §6.2)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    /// Base64url coordinates of the point
+    pub x: String,
+    pub y: String,
+    /// See [`key_id`]
+    pub kid: String,
+    /// Always `sig`
+    #[serde(rename = "use")]
+    pub use_: String,
+    pub alg: String,
+}
+
+/// Body of `GET /.well-known/jwks.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+impl Jwk {
+    pub fn new(public_key: &PublicKey) -> Self {
+        let point = public_key.verifying_key().to_encoded_point(false);
+        let b64 = general_purpose::URL_SAFE_NO_PAD;
+        Self {
+            kty: "EC".to_string(),
+            crv: "secp256k1".to_string(),
+            x: b64.encode(point.x().expect("uncompressed point has x")),
+            y: b64.encode(point.y().expect("uncompressed point has y")),
+            kid: key_id(public_key),
+            use_: "sig".to_string(),
+            alg: ALG.to_string(),
+        }
+    }
+
+    /// The key, if this is a secp256k1 `EC` JWK
+    pub fn key(&self) -> Option<PublicKey> {
+        let b64 = general_purpose::URL_SAFE_NO_PAD;
+        if self.kty != "EC" || self.crv != "secp256k1" {
+            return None;
+        }
+        let mut sec1 = vec![0x04];
+        sec1.extend(b64.decode(&self.x).ok().filter(|x| x.len() == 32)?);
+        sec1.extend(b64.decode(&self.y).ok().filter(|y| y.len() == 32)?);
+        PublicKey::from_sec1_bytes(&sec1).ok()
+    }
+}
+
+/// RFC 7638 JWK thumbprint (Base64url) of a public key
+pub fn key_id(public_key: &PublicKey) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(key_thumbprint(public_key))
+}
+
+/// The raw SHA-256 behind [`key_id`] (used as the COSE `kid`)
+pub fn key_thumbprint(public_key: &PublicKey) -> [u8; 32] {
+    let point = public_key.verifying_key().to_encoded_point(false);
+    let b64 = general_purpose::URL_SAFE_NO_PAD;
+    // Required members only, in lexicographic order, without whitespace
+    let jwk = format!(
+        r#"{{"crv":"secp256k1","kty":"EC","x":"{}","y":"{}"}}"#,
+        b64.encode(point.x().expect("uncompressed point has x")),
+        b64.encode(point.y().expect("uncompressed point has y"))
+    );
+    Sha256::digest(jwk.as_bytes()).into()
+}
+
+/// What [`encode`] signs: `base64url(header) "." base64url(claims)`
+pub fn signing_input(claims: &JwsClaims) -> String {
+    let b64 = general_purpose::URL_SAFE_NO_PAD;
+    let header = Header {
+        alg: ALG.to_string(),
+        typ: "JWT".to_string(),
+        kid: claims.kid.clone(),
+    };
+    format!(
+        "{}.{}",
+        b64.encode(serde_json::to_vec(&header).unwrap()),
+        b64.encode(serde_json::to_vec(claims).unwrap())
+    )
+}
+
+/// Builds a compact JWS over `claims`. `sign` must sign its input with the
+/// key `claims.kid` refers to; its error is returned as is.
+pub fn encode<E>(
+    claims: &JwsClaims,
+    sign: impl FnOnce(&[u8]) -> Result<Signature, E>,
+) -> Result<String, E> {
+    let b64 = general_purpose::URL_SAFE_NO_PAD;
+    let signing_input = signing_input(claims);
+    let signature = sign(signing_input.as_bytes())?;
+    Ok(format!(
+        "{}.{}",
+        signing_input,
+        b64.encode(signature.to_vec())
+    ))
+}
+
+/// Verifies a compact JWS from `POST /sign` against `key` (from `GET /key`)
+/// and returns its claims. Returns `None` if the token is malformed, not
+/// `ES256K`, signed by another key, or its signature doesn't verify.
+///
+/// The caller still has to check `msg_hash` against the message it holds.
+pub fn verify(token: &str, key: &EcdsaVerificationKey) -> Option<JwsClaims> {
+    let b64 = general_purpose::URL_SAFE_NO_PAD;
+
+    // 1) Split into header.payload.signature
+    let mut parts = token.split('.');
+    let (header_b64, claims_b64, sig_b64) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() {
+        return None;
+    }
+
+    // 2) Only ES256K, and only from this key
+    let header: Header = serde_json::from_slice(&b64.decode(header_b64).ok()?).ok()?;
+    let public_key = key.key()?;
+    let kid = key_id(&public_key);
+    if header.alg != ALG || header.kid != kid {
+        return None;
+    }
+
+    // 3) Verify the signature over "header.payload"
+    let sig = Signature::try_from(b64.decode(sig_b64).ok()?.as_slice()).ok()?;
+    let signing_input = &token[..header_b64.len() + 1 + claims_b64.len()];
+    if !public_key.verify(signing_input.as_bytes(), &sig) {
+        return None;
+    }
+
+    // 4) Decode the claims
+    let claims: JwsClaims = serde_json::from_slice(&b64.decode(claims_b64).ok()?).ok()?;
+    (claims.kid == kid).then_some(claims)
+}
@@ -0,0 +1,112 @@
+//! Per-key signature counters
+//!
+//! Every signature a key produces is counted against its RFC 7638 thumbprint
+//! (the JWS `kid`), along with the first and last `time-signed`. The counts
+//! are persisted like the time high-water mark, so `max_signatures` keeps a
+//! key from being used past its budget across restarts too; the only way
+//! past the limit is rotating the key.
+
+use crate::config::KeyStatsConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::Mutex;
+use utoipa::ToSchema;
+
+/// What one key has signed so far
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct KeyUsage {
+    /// Number of signatures produced
+    pub signatures: u64,
+    /// `time-signed` of the first signature
+    #[serde(rename = "first-signed")]
+    pub first_signed: Option<String>,
+    /// `time-signed` of the most recent signature
+    #[serde(rename = "last-signed")]
+    pub last_signed: Option<String>,
+}
+
+/// Why a signature could not be recorded
+#[derive(Debug)]
+pub enum RecordError {
+    /// The key already produced `max_signatures` signatures
+    Exhausted,
+    /// The counters couldn't be persisted
+    Io(std::io::Error),
+}
+
+/// Signature counters of every key, by key id
+pub struct KeyStats {
+    /// `None` keeps the counters in memory only
+    state_file: Option<String>,
+    /// 0 means unlimited
+    max_signatures: u64,
+    keys: Mutex<BTreeMap<String, KeyUsage>>,
+}
+
+impl KeyStats {
+    /// Resumes from `config.file` if it exists
+    pub fn new(config: &KeyStatsConfig) -> std::io::Result<Self> {
+        let state_file = (!config.file.is_empty()).then(|| config.file.clone());
+        let keys = match &state_file {
+            Some(path) if fs::exists(path)? => serde_json::from_str(&fs::read_to_string(path)?)
+                .map_err(|e| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Invalid key stats in {}: {}", path, e),
+                    )
+                })?,
+            _ => BTreeMap::new(),
+        };
+        Ok(Self {
+            state_file,
+            max_signatures: config.max_signatures,
+            keys: Mutex::new(keys),
+        })
+    }
+
+    /// The configured limit, `None` if unlimited
+    pub fn max_signatures(&self) -> Option<u64> {
+        (self.max_signatures > 0).then_some(self.max_signatures)
+    }
+
+    /// Usage of key `kid` (all zero if it never signed)
+    pub fn usage(&self, kid: &str) -> KeyUsage {
+        self.keys
+            .lock()
+            .unwrap()
+            .get(kid)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Counts one more signature by `kid` at `time_signed`, unless the key is
+    /// at its limit. The count is persisted before this returns, so a
+    /// signature is only made once it has been recorded.
+    pub fn record(&self, kid: &str, time_signed: &str) -> Result<KeyUsage, RecordError> {
+        let mut keys = self.keys.lock().unwrap();
+        let mut usage = keys.get(kid).cloned().unwrap_or_default();
+        if self.max_signatures > 0 && usage.signatures >= self.max_signatures {
+            return Err(RecordError::Exhausted);
+        }
+        usage.signatures += 1;
+        usage
+            .first_signed
+            .get_or_insert_with(|| time_signed.to_string());
+        usage.last_signed = Some(time_signed.to_string());
+
+        let previous = keys.insert(kid.to_string(), usage.clone());
+        if let Some(path) = &self.state_file {
+            let json = serde_json::to_string_pretty(&*keys).expect("key stats serialize");
+            if let Err(e) = fs::write(path, json) {
+                // Not persisted, so not counted
+                match previous {
+                    Some(previous) => keys.insert(kid.to_string(), previous),
+                    None => keys.remove(kid),
+                };
+                return Err(RecordError::Io(e));
+            }
+        }
+        Ok(usage)
+    }
+}
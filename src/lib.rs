@@ -4,11 +4,160 @@
 //! - `request_key(...)`
 //! - `request_timestamp(...)`
 //! - `verify_signature(...)`
+//!
+//! # Cargo features
+//! - `client`: async HTTP functions in `ecdsa_requests::nonblocking`
+//! - `blocking`: the blocking `request_key` / `request_timestamp` (implies `client`)
+//! - `server`: the VTS microservice (`server`, `config`, `audit` and the `lab4` and
+//!   `vts-audit` binaries)
+//! - `grpc`: the gRPC interface in `server::grpc` (implies `server`, off by default)
+//! - `roughtime`: the server's Roughtime responder over UDP (implies `server`, off by
+//!   default); the wire format and `roughtime::verify_response` are always built
+//! - `wasm`: verification-only JavaScript bindings in `wasm` (off by default)
+//! - `ffi`: the C verification interface in `ffi` (off by default)
+//! - `testing`: `testing::MockVts`, an in-memory server the clients can talk to
+//!   (implies `client`, off by default)
+//!
+//! JWS and COSE timestamp tokens (`jws`, `cose`) come with `tokens`, which
+//! `client`, `server`, `wasm` and `ffi` all turn on.
+//!
+//! `client`, `blocking` and `server` are on by default. With
+//! `default-features = false` only the response types and `verify_signature`
+//! are built, which depend on nothing heavier than `ecdsa_lib`, `k256`,
+//! `base64`, `hex`, `serde` and `chrono`.
 
+pub mod alg;
+#[cfg(feature = "server")]
+pub mod audit;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "server")]
+pub mod clock;
+#[cfg(feature = "server")]
 pub mod config;
+#[cfg(feature = "tokens")]
+pub mod cose;
+#[cfg(feature = "client")]
+pub mod email;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(any(feature = "server", feature = "client"))]
+pub mod http_sig;
+#[cfg(feature = "tokens")]
+pub mod jws;
+#[cfg(feature = "server")]
+pub mod key_stats;
+#[cfg(feature = "client")]
+pub mod manifest;
+pub mod merkle;
+#[cfg(feature = "client")]
+pub mod monitor;
+#[cfg(feature = "client")]
+pub mod multi;
+#[cfg(feature = "server")]
+pub mod ntp;
+#[cfg(feature = "client")]
+pub mod pdf;
+#[cfg(any(feature = "server", feature = "client"))]
+pub mod revocation;
+pub mod roughtime;
+#[cfg(feature = "server")]
+pub mod serials;
+#[cfg(feature = "server")]
 pub mod server;
+#[cfg(feature = "server")]
+pub mod signer;
+#[cfg(any(feature = "kms", feature = "s3"))]
+mod sigv4;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(any(feature = "server", feature = "client"))]
+pub mod transparency;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wire;
+#[cfg(any(feature = "server", feature = "client"))]
+pub mod x509;
+
+use alg::{DigestAlg, HashAlg, SignatureAlg};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Utc};
+use ecdsa_lib::PublicKey;
+use serde::{Deserialize, Serialize};
+
+/// Wire protocol versions the server serves (each under `/{version}/...`)
+/// and the client speaks, oldest first. The un-versioned paths are `v1`.
+pub const PROTOCOL_VERSIONS: &[&str] = &["v1"];
+
+/// Body returned by `GET /version`
+#[derive(Debug, Deserialize)]
+pub struct ServerVersion {
+    pub request: String,
+    /// The server's crate version, e.g. "0.1.0"
+    #[serde(rename = "server-version")]
+    pub server_version: String,
+    #[serde(rename = "protocol-versions")]
+    pub protocol_versions: Vec<String>,
+    /// Optional parts the server has on, e.g. "jws", "grpc" or "audit"
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// What the server truncates `time-signed` to; servers from before the
+    /// setting always sent microseconds
+    #[serde(rename = "time-precision", default)]
+    pub time_precision: wire::TimePrecision,
+}
+
+impl ServerVersion {
+    /// The newest protocol version both sides speak, if any
+    pub fn common_version(&self) -> Option<&'static str> {
+        PROTOCOL_VERSIONS
+            .iter()
+            .rev()
+            .find(|version| self.protocol_versions.iter().any(|v| v == *version))
+            .copied()
+    }
+}
+
+/// Body of every error response from the server, and the error the request
+/// functions return for one, so callers can match on `code` (e.g. with
+/// `err.downcast_ref::<ApiError>()`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct ApiError {
+    /// Stable, machine-readable reason, e.g. `"clock_drift"`,
+    /// `"invalid_json"` or `"unknown_tenant"`
+    pub code: String,
+    /// Human-readable description
+    #[serde(rename = "error")]
+    pub message: String,
+    /// Id of the failed request, as in the `X-Request-Id` response header
+    #[serde(
+        rename = "request-id",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub request_id: Option<String>,
+    /// The request field the error is about, e.g. `"message"` for a
+    /// missing or mistyped one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// HTTP status the error came with. Not part of the body: the request
+    /// functions fill it in, and it is `None` for errors made up locally.
+    #[serde(skip)]
+    pub status: Option<u16>,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)?;
+        if let Some(id) = &self.request_id {
+            write!(f, " [request {}]", id)?;
+        }
+        Ok(())
+    }
+}
 
-use serde::Deserialize;
+impl std::error::Error for ApiError {}
 
 #[derive(Debug, Deserialize)]
 pub struct EcdsaVerificationKey {
@@ -17,25 +166,268 @@ pub struct EcdsaVerificationKey {
     pub time_requested: String,
     #[serde(rename = "public-key")]
     pub public_key: String,
+    /// "hex" if the server was asked for hex instead of Base64
+    #[serde(default)]
+    pub format: Option<String>,
+    /// See `alg`; `None` from servers that predate the field
+    #[serde(rename = "signature-alg", default)]
+    pub signature_alg: Option<String>,
+    #[serde(rename = "hash-alg", default)]
+    pub hash_alg: Option<String>,
+    /// The key's id (`jws::key_id`), which timestamps signed by it carry;
+    /// `None` from servers that predate the field
+    #[serde(default)]
+    pub kid: Option<String>,
+    /// When the key may sign (RFC 3339); `None` for an open end, or from
+    /// servers that predate the fields
+    #[serde(rename = "not-before", default)]
+    pub not_before: Option<String>,
+    #[serde(rename = "not-after", default)]
+    pub not_after: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+impl EcdsaVerificationKey {
+    /// Whether the key may sign at `time`; `None` if `not_before` or
+    /// `not_after` isn't an RFC 3339 timestamp
+    pub fn is_valid_at(&self, time: DateTime<Utc>) -> Option<bool> {
+        let bound = |t: &Option<String>| t.as_deref().map(crate::wire::parse_time).transpose();
+        let (not_before, not_after) = (bound(&self.not_before).ok()?, bound(&self.not_after).ok()?);
+        Some(not_before.is_none_or(|t| t <= time) && not_after.is_none_or(|t| time <= t))
+    }
+
+    /// The algorithms the key is for, `None` if this client doesn't support
+    /// them
+    pub fn algorithms(&self) -> Option<(SignatureAlg, HashAlg)> {
+        algorithms(self.signature_alg.as_deref(), self.hash_alg.as_deref())
+    }
+
+    /// The parsed public key, whether `public_key` is Base64 or hex
+    pub fn key(&self) -> Option<PublicKey> {
+        match self.format.as_deref() {
+            Some("hex") => PublicKey::from_hex(&self.public_key).ok(),
+            Some("base64") => PublicKey::from_base64(&self.public_key).ok(),
+            _ => self.public_key.parse().ok(),
+        }
+    }
+}
+
+/// `request` is `"POST"` from `/sign` and `"RENEW"` from `/renew`
+#[derive(Debug, Serialize, Deserialize)]
 pub struct EcdsaSignedTimestamp {
     pub request: String,
+    /// The message, as text; empty for a binary one (see `message_b64`)
+    #[serde(default)]
     pub message: String,
+    /// A binary message (`message-b64`), Base64, in place of `message`
+    #[serde(
+        rename = "message-b64",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub message_b64: Option<String>,
     #[serde(rename = "time-signed")]
     pub time_signed: String,
     pub signature: String,
+    /// Bound on the server clock's error when signing (e.g. "±50ms"), if the
+    /// server checks its drift against NTP. Informational: it is not signed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accuracy: Option<String>,
+    /// "hex" if the server was asked for hex instead of Base64
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// See `alg`; `None` from servers that predate the field
+    #[serde(
+        rename = "signature-alg",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub signature_alg: Option<String>,
+    #[serde(rename = "hash-alg", default, skip_serializing_if = "Option::is_none")]
+    pub hash_alg: Option<String>,
+    /// Id of the key that signed it, to pick among a server's keys (see
+    /// `ecdsa_requests::select_key`); `None` from older servers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    /// `[policies]` name the server issued it under; signed along with
+    /// `time-signed` (see `wire::policy_signing_input`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+}
+
+impl EcdsaSignedTimestamp {
+    /// The algorithms of the signature, `None` if this client doesn't
+    /// support them
+    pub fn algorithms(&self) -> Option<(SignatureAlg, HashAlg)> {
+        algorithms(self.signature_alg.as_deref(), self.hash_alg.as_deref())
+    }
+
+    /// This timestamp as a token for `renew_timestamp`: its JSON
+    #[cfg(feature = "tokens")]
+    pub fn to_token(&self) -> String {
+        serde_json::to_string(self).expect("timestamp serializes")
+    }
+
+    /// The timestamp a renewal renews, if its message is a JSON timestamp
+    /// (`to_token` of it). Verify it with its own, possibly older, key.
+    #[cfg(feature = "tokens")]
+    pub fn renewed(&self) -> Option<EcdsaSignedTimestamp> {
+        if self.request != "RENEW" {
+            return None;
+        }
+        serde_json::from_str(&self.message).ok()
+    }
+
+    /// The signature bytes, whether `signature` is Base64 or hex
+    pub fn signature_bytes(&self) -> Option<Vec<u8>> {
+        decode_bytes(&self.signature, self.format.as_deref())
+    }
+
+    /// The bytes that were timestamped: `message` as UTF-8, or `message_b64`
+    /// decoded. `None` if `message_b64` isn't Base64.
+    pub fn message_bytes(&self) -> Option<Vec<u8>> {
+        match &self.message_b64 {
+            Some(b64) => general_purpose::STANDARD.decode(b64).ok(),
+            None => Some(self.message.as_bytes().to_vec()),
+        }
+    }
+
+    /// What the signature covers: the message bytes followed by
+    /// `time-signed` (and the policy, if any)
+    pub fn signed_data(&self) -> Option<Vec<u8>> {
+        Some(wire::policy_signing_input(
+            &self.message_bytes()?,
+            &self.time_signed,
+            self.policy.as_deref(),
+        ))
+    }
+}
+
+/// Body returned by `GET /time?nonce=...`: what the server's clock read,
+/// signed with the tenant's key, after the client picked `nonce`. Not a
+/// timestamp of anything; for clients that only need authenticated time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct SignedTime {
+    /// `"TIME"`
+    pub request: String,
+    /// The server clock, in the canonical form (see `wire`)
+    pub time: String,
+    /// The client's nonce, echoed
+    pub nonce: String,
+    /// Bound on the clock's error (e.g. "±50ms") when the server checks
+    /// its drift against NTP; signed, unlike a timestamp's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accuracy: Option<String>,
+    /// Key id of the key that signed it
+    pub kid: String,
+    /// Base64 DER signature over the JSON of the rest, without `sig`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sig: Option<String>,
+}
+
+#[cfg(feature = "tokens")]
+impl SignedTime {
+    /// The bytes `sig` covers
+    pub fn signed_bytes(&self) -> Vec<u8> {
+        let unsigned = SignedTime {
+            sig: None,
+            ..self.clone()
+        };
+        serde_json::to_vec(&unsigned).expect("signed time serializes")
+    }
+
+    /// Whether `public_key` signed this for `nonce`, the one the client
+    /// sent
+    pub fn verify(&self, public_key: &PublicKey, nonce: &str) -> bool {
+        self.request == "TIME"
+            && self.nonce == nonce
+            && self.kid == jws::key_id(public_key)
+            && self
+                .sig
+                .as_deref()
+                .and_then(|sig| general_purpose::STANDARD.decode(sig).ok())
+                .and_then(|der| ecdsa_lib::KeyPair::signature_from_der(&der).ok())
+                .is_some_and(|signature| public_key.verify(&self.signed_bytes(), &signature))
+    }
+
+    /// `time` parsed, `None` if it isn't RFC 3339
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        wire::parse_time(&self.time).ok()
+    }
+}
+
+/// Looks up named algorithms, taking a missing name as the default
+fn algorithms(
+    signature_alg: Option<&str>,
+    hash_alg: Option<&str>,
+) -> Option<(SignatureAlg, HashAlg)> {
+    let signature_alg = match signature_alg {
+        Some(name) => SignatureAlg::from_name(name)?,
+        None => SignatureAlg::default(),
+    };
+    let hash_alg = match hash_alg {
+        Some(name) => HashAlg::from_name(name)?,
+        None => HashAlg::default(),
+    };
+    Some((signature_alg, hash_alg))
+}
+
+/// Decodes a Base64 or hex field. Trusts `format` when the server sent one;
+/// otherwise an even-length string of only hex digits is taken as hex (a
+/// Base64 signature is practically never all hex digits).
+fn decode_bytes(value: &str, format: Option<&str>) -> Option<Vec<u8>> {
+    let looks_hex = value.len().is_multiple_of(2) && value.bytes().all(|b| b.is_ascii_hexdigit());
+    match format {
+        Some("hex") => hex::decode(value).ok(),
+        Some("base64") => general_purpose::STANDARD.decode(value).ok(),
+        _ if looks_hex => hex::decode(value).ok(),
+        _ => general_purpose::STANDARD.decode(value).ok(),
+    }
 }
 
 pub mod ecdsa_requests {
-    use super::{EcdsaSignedTimestamp, EcdsaVerificationKey};
-    use base64::{Engine as _, engine::general_purpose};
-    use k256::ecdsa::{Signature, VerifyingKey, signature::Verifier};
+    #[cfg(feature = "blocking")]
+    use super::SignedTime;
+    #[cfg(feature = "client")]
+    use super::{ApiError, ServerVersion};
+    use super::{DigestAlg, EcdsaSignedTimestamp, EcdsaVerificationKey, HashAlg, SignatureAlg};
+    use chrono::{DateTime, Duration, Utc};
+    use k256::sha2::{Digest, Sha256, Sha384, Sha512};
+    #[cfg(feature = "blocking")]
     use reqwest::blocking::Client;
+    #[cfg(feature = "blocking")]
     use serde_json::json;
+    #[cfg(feature = "blocking")]
     use std::error::Error;
+    use std::io::Read;
+    #[cfg(feature = "blocking")]
+    use std::sync::OnceLock;
+
+    /// The `ApiError` in an error response's body, or one made up from the
+    /// status if the body isn't one (e.g. from a proxy in between)
+    #[cfg(feature = "client")]
+    pub(crate) fn api_error(status: reqwest::StatusCode, body: &[u8]) -> ApiError {
+        let mut err = serde_json::from_slice(body).unwrap_or_else(|_| ApiError {
+            code: "http_error".to_string(),
+            message: format!("Server returned error: {}", status),
+            request_id: None,
+            field: None,
+            status: None,
+        });
+        err.status = Some(status.as_u16());
+        err
+    }
+
+    /// One client behind all the blocking functions, so repeated calls
+    /// reuse connections. See [`crate::client::VtsClient`] for timeouts,
+    /// retries and a proxy.
+    #[cfg(feature = "blocking")]
+    fn shared_client() -> &'static Client {
+        static CLIENT: OnceLock<Client> = OnceLock::new();
+        CLIENT.get_or_init(Client::new)
+    }
 
+    #[cfg(feature = "blocking")]
     /// Fetches the server's public key via HTTP GET.
     ///
     /// # Example
@@ -48,15 +440,17 @@ pub mod ecdsa_requests {
     /// ```
     pub fn request_key(server_addr: &str) -> Result<EcdsaVerificationKey, Box<dyn Error>> {
         let url = format!("{}/key", server_addr);
-        let client = Client::new();
+        let client = shared_client();
         let resp = client.get(&url).send()?;
         if !resp.status().is_success() {
-            return Err(format!("Server returned error: {}", resp.status()).into());
+            let status = resp.status();
+            return Err(api_error(status, &resp.bytes()?).into());
         }
         let key_struct: EcdsaVerificationKey = resp.json()?;
         Ok(key_struct)
     }
 
+    #[cfg(feature = "blocking")]
     /// Sends a message to be timestamped. Returns the server's full response struct.
     ///
     /// # Example
@@ -72,53 +466,804 @@ pub mod ecdsa_requests {
         message: &str,
     ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
         let url = format!("{}/sign", server_addr);
-        let client = Client::new();
+        let client = shared_client();
         let body = json!({ "message": message });
         let resp = client.post(&url).json(&body).send()?;
         if !resp.status().is_success() {
-            return Err(format!("Server returned error: {}", resp.status()).into());
+            let status = resp.status();
+            return Err(api_error(status, &resp.bytes()?).into());
         }
         let ts_struct: EcdsaSignedTimestamp = resp.json()?;
         Ok(ts_struct)
     }
 
+    #[cfg(feature = "blocking")]
+    /// Like `request_timestamp`, for arbitrary bytes: they are sent as
+    /// `message-b64` and signed as they are, followed by `time-signed`. The
+    /// result has them in `message_b64`; `verify_signature` checks it the
+    /// same way.
+    pub fn request_binary_timestamp(
+        server_addr: &str,
+        message: &[u8],
+    ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        let url = format!("{}/sign", server_addr);
+        let client = shared_client();
+        let body = binary_body(message);
+        let resp = client.post(&url).json(&body).send()?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            return Err(api_error(status, &resp.bytes()?).into());
+        }
+        Ok(resp.json()?)
+    }
+
+    /// The `/sign` body for a binary message
+    #[cfg(feature = "client")]
+    pub(crate) fn binary_body(message: &[u8]) -> serde_json::Value {
+        use base64::{Engine as _, engine::general_purpose};
+        serde_json::json!({ "message-b64": general_purpose::STANDARD.encode(message) })
+    }
+
+    #[cfg(feature = "blocking")]
+    /// Like `request_timestamp`, but asks for a binary COSE_Sign1 token
+    /// (`Accept: application/cose`) instead of the JSON body. Check it with
+    /// `verify_cose_token`.
+    pub fn request_timestamp_cose(
+        server_addr: &str,
+        message: &str,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let url = format!("{}/sign", server_addr);
+        let client = shared_client();
+        let body = json!({ "message": message });
+        let resp = client
+            .post(&url)
+            .header(reqwest::header::ACCEPT, crate::cose::CONTENT_TYPE)
+            .json(&body)
+            .send()?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            return Err(api_error(status, &resp.bytes()?).into());
+        }
+        Ok(resp.bytes()?.to_vec())
+    }
+
+    #[cfg(feature = "blocking")]
+    /// Timestamps a file (or any reader) without uploading it: the contents
+    /// are hashed locally in chunks and only the hex SHA-256 is sent as the
+    /// message. Check the result with `verify_file_timestamp`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use lab4::ecdsa_requests::request_file_timestamp;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let file = std::fs::File::open("thesis.pdf")?;
+    /// let signed = request_file_timestamp("http://127.0.0.1:8008", file)?;
+    /// println!("SHA-256 {} signed at {}", signed.message, signed.time_signed);
+    /// # Ok(()) }
+    /// ```
+    pub fn request_file_timestamp(
+        server_addr: &str,
+        reader: impl Read,
+    ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        let digest = hash_reader(reader)?;
+        request_timestamp(server_addr, &digest)
+    }
+
+    #[cfg(feature = "blocking")]
+    /// Like `request_file_timestamp`, hashing with `alg` and sending the
+    /// digest as `digest` / `digest-alg`. The server timestamps the message
+    /// `<alg>:<hex digest>` (see [`DigestAlg::message`]), if it allows
+    /// `alg`; `verify_file_timestamp` checks it the same way.
+    pub fn request_file_timestamp_with(
+        server_addr: &str,
+        reader: impl Read,
+        alg: DigestAlg,
+    ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        let url = format!("{}/sign", server_addr);
+        let body = digest_body(&digest_reader(reader, alg)?, alg);
+        let resp = shared_client().post(&url).json(&body).send()?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            return Err(api_error(status, &resp.bytes()?).into());
+        }
+        Ok(resp.json()?)
+    }
+
+    #[cfg(feature = "blocking")]
+    /// Timestamps every file under a directory with one request: build
+    /// the manifest with [`crate::manifest::Manifest::from_dir`], keep it,
+    /// and check files later with `Manifest::verify_file`
+    pub fn request_manifest_timestamp(
+        server_addr: &str,
+        manifest: &crate::manifest::Manifest,
+    ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        let url = format!("{}/sign", server_addr);
+        let body = digest_body(&manifest.root(), DigestAlg::Sha256);
+        let resp = shared_client().post(&url).json(&body).send()?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            return Err(api_error(status, &resp.bytes()?).into());
+        }
+        Ok(resp.json()?)
+    }
+
+    #[cfg(feature = "blocking")]
+    /// Timestamps the mail `raw` by the digest of its canonical form (see
+    /// [`crate::email`]). Attach the result with `email::to_mime_part` and
+    /// check it with `email::verify`.
+    pub fn request_email_timestamp(
+        server_addr: &str,
+        raw: &[u8],
+    ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        let url = format!("{}/sign", server_addr);
+        let body = digest_body(&crate::email::digest(raw)?, DigestAlg::Sha256);
+        let resp = shared_client().post(&url).json(&body).send()?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            return Err(api_error(status, &resp.bytes()?).into());
+        }
+        Ok(resp.json()?)
+    }
+
+    #[cfg(feature = "blocking")]
+    /// Timestamps the PDF `pdf` by the digest of its `/ByteRange` (see
+    /// [`crate::pdf`]), giving the detached proof to save at
+    /// `pdf::proof_path`
+    pub fn request_pdf_timestamp(
+        server_addr: &str,
+        pdf: &[u8],
+    ) -> Result<crate::pdf::Proof, Box<dyn Error>> {
+        let url = format!("{}/sign", server_addr);
+        let body = digest_body(&crate::pdf::digest(pdf)?, DigestAlg::Sha256);
+        let resp = shared_client().post(&url).json(&body).send()?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            return Err(api_error(status, &resp.bytes()?).into());
+        }
+        Ok(crate::pdf::Proof::new(pdf, resp.json()?)?)
+    }
+
+    /// The `/sign` body for a hash-then-sign request
+    #[cfg(feature = "client")]
+    pub(crate) fn digest_body(digest: &[u8], alg: DigestAlg) -> serde_json::Value {
+        serde_json::json!({ "digest": hex::encode(digest), "digest-alg": alg.name() })
+    }
+
+    #[cfg(feature = "blocking")]
+    /// Timestamps an earlier token again (`POST /renew`), for long-term
+    /// archival: the new signature, by the server's current key, covers
+    /// `token + time-signed`, so the token is known to have existed before
+    /// then even once its own key or algorithm can no longer be trusted.
+    /// `token` may be a JWS, Base64 COSE token or `to_token()` of a JSON
+    /// timestamp; the result's `message` is `token`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use lab4::ecdsa_requests::{request_timestamp, renew_timestamp};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let signed = request_timestamp("http://127.0.0.1:8008", "Hello")?;
+    /// let renewed = renew_timestamp("http://127.0.0.1:8008", &signed.to_token())?;
+    /// assert_eq!(renewed.renewed().unwrap().signature, signed.signature);
+    /// # Ok(()) }
+    /// ```
+    pub fn renew_timestamp(
+        server_addr: &str,
+        token: &str,
+    ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        let url = format!("{}/renew", server_addr);
+        let client = shared_client();
+        let body = json!({ "token": token });
+        let resp = client.post(&url).json(&body).send()?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            return Err(api_error(status, &resp.bytes()?).into());
+        }
+        Ok(resp.json()?)
+    }
+
+    #[cfg(feature = "blocking")]
+    /// Like `request_key`, but for a named tenant of a multi-tenant server
+    /// (`{server_addr}/t/{tenant}/key`).
+    pub fn request_key_for_tenant(
+        server_addr: &str,
+        tenant: &str,
+    ) -> Result<EcdsaVerificationKey, Box<dyn Error>> {
+        request_key(&format!("{}/t/{}", server_addr, tenant))
+    }
+
+    #[cfg(feature = "blocking")]
+    /// Like `request_timestamp`, but signed with the key of a named tenant
+    /// (`{server_addr}/t/{tenant}/sign`).
+    pub fn request_timestamp_for_tenant(
+        server_addr: &str,
+        tenant: &str,
+        message: &str,
+    ) -> Result<EcdsaSignedTimestamp, Box<dyn Error>> {
+        request_timestamp(&format!("{}/t/{}", server_addr, tenant), message)
+    }
+
+    #[cfg(feature = "blocking")]
+    /// Fetches the server's version, protocol versions and features via
+    /// `GET /version`.
+    pub fn request_version(server_addr: &str) -> Result<ServerVersion, Box<dyn Error>> {
+        let url = format!("{}/version", server_addr);
+        let resp = shared_client().get(&url).send()?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            return Err(api_error(status, &resp.bytes()?).into());
+        }
+        Ok(resp.json()?)
+    }
+
+    #[cfg(feature = "blocking")]
+    /// Fetches the server's clock via `GET /time`, signed together with
+    /// `nonce`. Pick a fresh, unpredictable nonce (e.g. 32 random bytes in
+    /// hex) and check the answer with `SignedTime::verify`: the time was
+    /// read after the nonce was picked.
+    pub fn request_time(server_addr: &str, nonce: &str) -> Result<SignedTime, Box<dyn Error>> {
+        let url = format!("{}/time", server_addr);
+        let resp = shared_client()
+            .get(&url)
+            .query(&[("nonce", nonce)])
+            .send()?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            return Err(api_error(status, &resp.bytes()?).into());
+        }
+        Ok(resp.json()?)
+    }
+
+    #[cfg(feature = "blocking")]
+    /// Picks the newest protocol version both sides speak and returns the
+    /// base address to pass to the other request functions, e.g.
+    /// `http://127.0.0.1:8008/v1`. A server without `/version` predates
+    /// versioning and gets `server_addr` back unchanged; one that only speaks
+    /// versions this client doesn't gives an `ApiError` with code
+    /// `"incompatible_version"`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use lab4::ecdsa_requests::{negotiate, request_timestamp};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let base = negotiate("http://127.0.0.1:8008")?;
+    /// let signed = request_timestamp(&base, "Hello")?;
+    /// # Ok(()) }
+    /// ```
+    pub fn negotiate(server_addr: &str) -> Result<String, Box<dyn Error>> {
+        let url = format!("{}/version", server_addr);
+        let resp = shared_client().get(&url).send()?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(server_addr.to_string());
+        }
+        if !resp.status().is_success() {
+            let status = resp.status();
+            return Err(api_error(status, &resp.bytes()?).into());
+        }
+        Ok(versioned_addr(server_addr, &resp.json()?)?)
+    }
+
+    /// `server_addr` under the newest protocol version `version` lists that
+    /// this client speaks too
+    #[cfg(feature = "client")]
+    fn versioned_addr(server_addr: &str, version: &ServerVersion) -> Result<String, ApiError> {
+        match version.common_version() {
+            Some(common) => Ok(format!("{}/{}", server_addr, common)),
+            None => Err(ApiError {
+                code: "incompatible_version".to_string(),
+                message: format!(
+                    "Server {} speaks protocol versions [{}], this client speaks [{}]",
+                    version.server_version,
+                    version.protocol_versions.join(", "),
+                    crate::PROTOCOL_VERSIONS.join(", ")
+                ),
+                request_id: None,
+                field: None,
+                status: None,
+            }),
+        }
+    }
+
     /// Verifies that `signed.signature` is a valid ECDSA over the bytes of
     /// `(signed.message + signed.time_signed)`, using only `key.public_key`.
+    /// The signature may be raw `r || s` or DER encoded.
     ///
     /// # Example
     /// ```no_run
-    /// # use lab4::ecdsa_requests::{request_key, request_timestamp, verify_signature};
+    /// # #[cfg(feature = "blocking")]
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # use lab4::ecdsa_requests::{request_key, request_timestamp, verify_signature};
     /// let key = request_key("http://127.0.0.1:8008")?;
     /// let signed = request_timestamp("http://127.0.0.1:8008", "Test")?;
     /// assert!(verify_signature(&signed, &key));
     /// # Ok(()) }
+    /// # #[cfg(not(feature = "blocking"))]
+    /// # fn main() {}
     /// ```
     pub fn verify_signature(signed: &EcdsaSignedTimestamp, key: &EcdsaVerificationKey) -> bool {
-        // 1) Recreate data = message + time_signed
-        let data = format!("{}{}", signed.message, signed.time_signed);
+        verify_signature_with(signed, key, &VerifyOptions::default()).is_ok()
+    }
+
+    /// The key among a server's `keys` (from `VtsClient::request_keys`) that
+    /// signed `signed`: the one with its `kid`, or the first (primary) one
+    /// for a timestamp without a `kid`, from a server with only one key
+    pub fn select_key<'a>(
+        keys: &'a [EcdsaVerificationKey],
+        signed: &EcdsaSignedTimestamp,
+    ) -> Option<&'a EcdsaVerificationKey> {
+        match &signed.kid {
+            Some(kid) => keys.iter().find(|key| key.kid.as_ref() == Some(kid)),
+            None => keys.first(),
+        }
+    }
+
+    /// Checks on `time_signed` that [`verify_signature_with`] makes on top
+    /// of the signature. The default makes none, like [`verify_signature`].
+    #[derive(Debug, Clone, Default)]
+    pub struct VerifyOptions {
+        /// Require `time_signed` to be an RFC 3339 timestamp (implied by
+        /// the two limits below)
+        pub require_rfc3339: bool,
+        /// Require `time_signed` to be exactly as the server writes it (see
+        /// [`crate::wire`]: UTC, six fractional digits, `Z`); implies
+        /// `require_rfc3339`
+        pub require_canonical: bool,
+        /// Reject timestamps more than this before or after local time
+        pub max_skew: Option<Duration>,
+        /// Reject timestamps more than this after local time;
+        /// `Some(Duration::zero())` rejects any timestamp in the future
+        pub max_future: Option<Duration>,
+        /// The local time to compare against, `None` for the system clock
+        pub now: Option<DateTime<Utc>>,
+    }
+
+    /// Why [`verify_signature_with`] accepted or rejected a timestamp
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum VerifyOutcome {
+        Ok,
+        /// `signature` isn't Base64/hex of a raw or DER signature
+        BadEncoding,
+        /// `public_key` isn't Base64/hex of a secp256k1 public key
+        BadKey,
+        /// The signature doesn't verify under the key
+        BadSignature,
+        /// `time_signed` isn't an RFC 3339 timestamp (or, with
+        /// `require_canonical`, not a canonical one)
+        BadTimestamp,
+        /// `time_signed` is further from local time than `max_skew`
+        TimeSkew,
+        /// `time_signed` is later than local time by more than `max_future`
+        InFuture,
+        /// `signature-alg` or `hash-alg` names an algorithm this client
+        /// doesn't support
+        UnsupportedAlgorithm,
+        /// `time_signed` is outside the key's `not-before` / `not-after`
+        OutsideKeyValidity,
+        /// The key was revoked (`GET /revocations`) at or before
+        /// `time_signed`; see `revocation::RevocationList::check`
+        Revoked,
+    }
+
+    impl VerifyOutcome {
+        pub fn is_ok(&self) -> bool {
+            *self == VerifyOutcome::Ok
+        }
+    }
+
+    impl From<ecdsa_lib::VerifyOutcome> for VerifyOutcome {
+        fn from(outcome: ecdsa_lib::VerifyOutcome) -> Self {
+            match outcome {
+                ecdsa_lib::VerifyOutcome::Ok => VerifyOutcome::Ok,
+                ecdsa_lib::VerifyOutcome::BadEncoding => VerifyOutcome::BadEncoding,
+                ecdsa_lib::VerifyOutcome::BadSignature => VerifyOutcome::BadSignature,
+            }
+        }
+    }
+
+    /// Like [`verify_signature`], plus the `time_signed` checks in
+    /// `options`, and says why verification failed. The signature is
+    /// checked first: `time_signed` is only worth looking at once it is
+    /// known to be what the server signed.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "blocking")]
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # use lab4::ecdsa_requests::*;
+    /// let key = request_key("http://127.0.0.1:8008")?;
+    /// let signed = request_timestamp("http://127.0.0.1:8008", "Test")?;
+    /// let options = VerifyOptions {
+    ///     max_skew: Some(chrono::Duration::minutes(5)),
+    ///     max_future: Some(chrono::Duration::seconds(1)),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(verify_signature_with(&signed, &key, &options), VerifyOutcome::Ok);
+    /// # Ok(()) }
+    /// # #[cfg(not(feature = "blocking"))]
+    /// # fn main() {}
+    /// ```
+    pub fn verify_signature_with(
+        signed: &EcdsaSignedTimestamp,
+        key: &EcdsaVerificationKey,
+        options: &VerifyOptions,
+    ) -> VerifyOutcome {
+        let outcome = signature_outcome(signed, key);
+        if !outcome.is_ok() {
+            return outcome;
+        }
+        let outcome = validity_outcome(signed, key);
+        if !outcome.is_ok() {
+            return outcome;
+        }
+        if !options.require_rfc3339
+            && !options.require_canonical
+            && options.max_skew.is_none()
+            && options.max_future.is_none()
+        {
+            return VerifyOutcome::Ok;
+        }
+        if options.require_canonical && !crate::wire::is_canonical(&signed.time_signed) {
+            return VerifyOutcome::BadTimestamp;
+        }
+
+        let Ok(time_signed) = crate::wire::parse_time(&signed.time_signed) else {
+            return VerifyOutcome::BadTimestamp;
+        };
+        let ahead = time_signed - options.now.unwrap_or_else(Utc::now);
+        if options.max_future.is_some_and(|max| ahead > max) {
+            return VerifyOutcome::InFuture;
+        }
+        if options.max_skew.is_some_and(|max| ahead.abs() > max) {
+            return VerifyOutcome::TimeSkew;
+        }
+        VerifyOutcome::Ok
+    }
+
+    /// [`verify_signature_with`] (default options) of many timestamps
+    /// under one key, e.g. an archive being re-checked; the outcomes come
+    /// back in order. The key is parsed once, and with the `parallel`
+    /// feature the signatures are checked on all cores.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "blocking")]
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # use lab4::ecdsa_requests::*;
+    /// # let archive: Vec<lab4::EcdsaSignedTimestamp> = Vec::new();
+    /// let key = request_key("http://127.0.0.1:8008")?;
+    /// let outcomes = verify_signatures_batch(&archive, &key);
+    /// let failed = outcomes.iter().filter(|o| !o.is_ok()).count();
+    /// println!("{} of {} failed", failed, archive.len());
+    /// # Ok(()) }
+    /// # #[cfg(not(feature = "blocking"))]
+    /// # fn main() {}
+    /// ```
+    pub fn verify_signatures_batch(
+        signed: &[EcdsaSignedTimestamp],
+        key: &EcdsaVerificationKey,
+    ) -> Vec<VerifyOutcome> {
+        let key_algorithms = key.algorithms();
+        let public_key = key.key();
+
+        // 1) Everything signature_outcome checks before the signature itself
+        let mut outcomes = vec![VerifyOutcome::Ok; signed.len()];
+        let mut pending = Vec::new();
+        let mut items = Vec::new();
+        for (i, signed) in signed.iter().enumerate() {
+            let (Some(algorithms), Some(key_algorithms)) = (signed.algorithms(), key_algorithms)
+            else {
+                outcomes[i] = VerifyOutcome::UnsupportedAlgorithm;
+                continue;
+            };
+            if algorithms != key_algorithms || public_key.is_none() {
+                outcomes[i] = VerifyOutcome::BadKey;
+                continue;
+            }
+            let (Some(sig_bytes), Some(data)) = (signed.signature_bytes(), signed.signed_data())
+            else {
+                outcomes[i] = VerifyOutcome::BadEncoding;
+                continue;
+            };
+            pending.push(i);
+            items.push((data, sig_bytes));
+        }
 
-        // 2) Base64‐decode public key and signature
-        let pub_bytes = match general_purpose::STANDARD.decode(&key.public_key) {
-            Ok(b) => b,
-            Err(_) => return false,
+        // 2) The signatures, all at once (secp256k1 / SHA-256 being the
+        //    only algorithms there are)
+        if let Some(public_key) = public_key {
+            for (i, outcome) in pending.into_iter().zip(public_key.verify_batch(&items)) {
+                outcomes[i] = match outcome.into() {
+                    VerifyOutcome::Ok => validity_outcome(&signed[i], key),
+                    outcome => outcome,
+                };
+            }
+        }
+        outcomes
+    }
+
+    /// Whether `time_signed` is inside the key's validity window (always,
+    /// for a key without one)
+    fn validity_outcome(
+        signed: &EcdsaSignedTimestamp,
+        key: &EcdsaVerificationKey,
+    ) -> VerifyOutcome {
+        if key.not_before.is_none() && key.not_after.is_none() {
+            return VerifyOutcome::Ok;
+        }
+        let Ok(time_signed) = crate::wire::parse_time(&signed.time_signed) else {
+            return VerifyOutcome::BadTimestamp;
+        };
+        match key.is_valid_at(time_signed) {
+            Some(true) => VerifyOutcome::Ok,
+            Some(false) => VerifyOutcome::OutsideKeyValidity,
+            None => VerifyOutcome::BadKey,
+        }
+    }
+
+    fn signature_outcome(
+        signed: &EcdsaSignedTimestamp,
+        key: &EcdsaVerificationKey,
+    ) -> VerifyOutcome {
+        // 1) Recreate data = message + time_signed (the message bytes, for
+        //    a binary one)
+        let Some(data) = signed.signed_data() else {
+            return VerifyOutcome::BadEncoding;
         };
-        let sig_bytes = match general_purpose::STANDARD.decode(&signed.signature) {
-            Ok(b) => b,
-            Err(_) => return false,
+
+        // 2) The algorithms, which the key and signature must agree on (a
+        //    key for other algorithms can't check the signature)
+        let (Some(algorithms), Some(key_algorithms)) = (signed.algorithms(), key.algorithms())
+        else {
+            return VerifyOutcome::UnsupportedAlgorithm;
         };
+        if algorithms != key_algorithms {
+            return VerifyOutcome::BadKey;
+        }
 
-        // 3) Parse into k256 types
-        let vk = match VerifyingKey::from_sec1_bytes(&pub_bytes) {
-            Ok(v) => v,
-            Err(_) => return false,
+        // 3) Parse the public key and decode the signature (Base64, or hex if
+        //    asked for)
+        let Some(public_key) = key.key() else {
+            return VerifyOutcome::BadKey;
         };
-        let sig = match Signature::try_from(sig_bytes.as_slice()) {
-            Ok(s) => s,
-            Err(_) => return false,
+        let Some(sig_bytes) = signed.signature_bytes() else {
+            return VerifyOutcome::BadEncoding;
+        };
+
+        // 4) Verify, by algorithm. The signature may be raw `r || s` or DER
+        //    (the server's `"encoding": "der"`); either is accepted.
+        match algorithms {
+            (SignatureAlg::EcdsaSecp256k1, HashAlg::Sha256) => {
+                public_key.verify_encoded(&data, &sig_bytes).into()
+            }
+        }
+    }
+
+    /// Verifies a COSE_Sign1 token (from `request_timestamp_cose`) against
+    /// `key` and returns its claims, or `None` if it doesn't verify. The
+    /// token only commits to the message's hash: compare `msg_hash` with
+    /// the SHA-256 of the message you sent.
+    #[cfg(feature = "client")]
+    pub fn verify_cose_token(
+        token: &[u8],
+        key: &EcdsaVerificationKey,
+    ) -> Option<crate::cose::CoseClaims> {
+        crate::cose::verify(token, key)
+    }
+
+    /// Hex SHA-256 of everything `reader` yields, read in chunks so large
+    /// files never have to be in memory. This is the `message` that
+    /// `request_file_timestamp` sends; async callers can pass it to
+    /// `nonblocking::request_timestamp` themselves.
+    pub fn hash_reader(reader: impl Read) -> std::io::Result<String> {
+        Ok(hex::encode(digest_reader(reader, DigestAlg::Sha256)?))
+    }
+
+    /// The `alg` digest of everything `reader` yields, in chunks like
+    /// `hash_reader`. `Blake3` needs the `blake3` feature.
+    pub fn digest_reader(reader: impl Read, alg: DigestAlg) -> std::io::Result<Vec<u8>> {
+        match alg {
+            DigestAlg::Sha256 => digest_chunks::<Sha256>(reader),
+            DigestAlg::Sha384 => digest_chunks::<Sha384>(reader),
+            DigestAlg::Sha512 => digest_chunks::<Sha512>(reader),
+            #[cfg(feature = "blake3")]
+            DigestAlg::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                for_each_chunk(reader, |chunk| {
+                    hasher.update(chunk);
+                })?;
+                Ok(hasher.finalize().as_bytes().to_vec())
+            }
+            #[cfg(not(feature = "blake3"))]
+            DigestAlg::Blake3 => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "BLAKE3 needs the blake3 feature",
+            )),
+        }
+    }
+
+    fn digest_chunks<D: Digest>(reader: impl Read) -> std::io::Result<Vec<u8>> {
+        let mut hasher = D::new();
+        for_each_chunk(reader, |chunk| hasher.update(chunk))?;
+        Ok(hasher.finalize().to_vec())
+    }
+
+    /// Feeds `reader` to `f` 64 KiB at a time
+    fn for_each_chunk(mut reader: impl Read, mut f: impl FnMut(&[u8])) -> std::io::Result<()> {
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => f(&buf[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Checks a timestamp from `request_file_timestamp` (or
+    /// `request_file_timestamp_with`, whose message names the digest
+    /// algorithm): `reader` must hash to `signed.message`, and the
+    /// signature must verify under `key`.
+    pub fn verify_file_timestamp(
+        reader: impl Read,
+        signed: &EcdsaSignedTimestamp,
+        key: &EcdsaVerificationKey,
+    ) -> std::io::Result<bool> {
+        let matches = match DigestAlg::parse_message(&signed.message) {
+            Some((alg, digest)) => digest_reader(reader, alg)? == digest,
+            None => hash_reader(reader)? == signed.message,
         };
+        Ok(matches && verify_signature(signed, key))
+    }
+
+    /// Async versions of the request functions, for callers already running
+    /// inside an async runtime (available with the `client` feature). Each
+    /// call makes its own connection; use [`crate::client::nonblocking::VtsClient`]
+    /// to share a pool between calls.
+    #[cfg(feature = "client")]
+    pub mod nonblocking {
+        use crate::{EcdsaSignedTimestamp, EcdsaVerificationKey, ServerVersion, SignedTime};
+        use reqwest::Client;
+        use serde_json::json;
+        use std::error::Error;
+
+        /// Async equivalent of [`super::request_key`].
+        pub async fn request_key(
+            server_addr: &str,
+        ) -> Result<EcdsaVerificationKey, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/key", server_addr);
+            let resp = Client::new().get(&url).send().await?;
+            if !resp.status().is_success() {
+                let status = resp.status();
+                return Err(super::api_error(status, &resp.bytes().await?).into());
+            }
+            Ok(resp.json().await?)
+        }
+
+        /// Async equivalent of [`super::request_timestamp`].
+        pub async fn request_timestamp(
+            server_addr: &str,
+            message: &str,
+        ) -> Result<EcdsaSignedTimestamp, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/sign", server_addr);
+            let body = json!({ "message": message });
+            let resp = Client::new().post(&url).json(&body).send().await?;
+            if !resp.status().is_success() {
+                let status = resp.status();
+                return Err(super::api_error(status, &resp.bytes().await?).into());
+            }
+            Ok(resp.json().await?)
+        }
+
+        /// Async equivalent of [`super::request_binary_timestamp`].
+        pub async fn request_binary_timestamp(
+            server_addr: &str,
+            message: &[u8],
+        ) -> Result<EcdsaSignedTimestamp, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/sign", server_addr);
+            let body = super::binary_body(message);
+            let resp = Client::new().post(&url).json(&body).send().await?;
+            if !resp.status().is_success() {
+                let status = resp.status();
+                return Err(super::api_error(status, &resp.bytes().await?).into());
+            }
+            Ok(resp.json().await?)
+        }
+
+        /// Async equivalent of [`super::request_timestamp_cose`].
+        pub async fn request_timestamp_cose(
+            server_addr: &str,
+            message: &str,
+        ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/sign", server_addr);
+            let body = json!({ "message": message });
+            let resp = Client::new()
+                .post(&url)
+                .header(reqwest::header::ACCEPT, crate::cose::CONTENT_TYPE)
+                .json(&body)
+                .send()
+                .await?;
+            if !resp.status().is_success() {
+                let status = resp.status();
+                return Err(super::api_error(status, &resp.bytes().await?).into());
+            }
+            Ok(resp.bytes().await?.to_vec())
+        }
+
+        /// Async equivalent of [`super::renew_timestamp`].
+        pub async fn renew_timestamp(
+            server_addr: &str,
+            token: &str,
+        ) -> Result<EcdsaSignedTimestamp, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/renew", server_addr);
+            let body = json!({ "token": token });
+            let resp = Client::new().post(&url).json(&body).send().await?;
+            if !resp.status().is_success() {
+                let status = resp.status();
+                return Err(super::api_error(status, &resp.bytes().await?).into());
+            }
+            Ok(resp.json().await?)
+        }
+
+        /// Async equivalent of [`super::request_key_for_tenant`].
+        pub async fn request_key_for_tenant(
+            server_addr: &str,
+            tenant: &str,
+        ) -> Result<EcdsaVerificationKey, Box<dyn Error + Send + Sync>> {
+            request_key(&format!("{}/t/{}", server_addr, tenant)).await
+        }
+
+        /// Async equivalent of [`super::request_timestamp_for_tenant`].
+        pub async fn request_timestamp_for_tenant(
+            server_addr: &str,
+            tenant: &str,
+            message: &str,
+        ) -> Result<EcdsaSignedTimestamp, Box<dyn Error + Send + Sync>> {
+            request_timestamp(&format!("{}/t/{}", server_addr, tenant), message).await
+        }
+
+        /// Async equivalent of [`super::request_version`].
+        pub async fn request_version(
+            server_addr: &str,
+        ) -> Result<ServerVersion, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/version", server_addr);
+            let resp = Client::new().get(&url).send().await?;
+            if !resp.status().is_success() {
+                let status = resp.status();
+                return Err(super::api_error(status, &resp.bytes().await?).into());
+            }
+            Ok(resp.json().await?)
+        }
+
+        /// Async equivalent of [`super::request_time`].
+        pub async fn request_time(
+            server_addr: &str,
+            nonce: &str,
+        ) -> Result<SignedTime, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/time", server_addr);
+            let resp = Client::new()
+                .get(&url)
+                .query(&[("nonce", nonce)])
+                .send()
+                .await?;
+            if !resp.status().is_success() {
+                let status = resp.status();
+                return Err(super::api_error(status, &resp.bytes().await?).into());
+            }
+            Ok(resp.json().await?)
+        }
 
-        // 4) Verify
-        vk.verify(data.as_bytes(), &sig).is_ok()
+        /// Async equivalent of [`super::negotiate`].
+        pub async fn negotiate(server_addr: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+            let url = format!("{}/version", server_addr);
+            let resp = Client::new().get(&url).send().await?;
+            if resp.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(server_addr.to_string());
+            }
+            if !resp.status().is_success() {
+                let status = resp.status();
+                return Err(super::api_error(status, &resp.bytes().await?).into());
+            }
+            Ok(super::versioned_addr(server_addr, &resp.json().await?)?)
+        }
     }
 }
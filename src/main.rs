@@ -1,15 +1,73 @@
-mod config;
-mod server;
-
-use config::load_or_generate_keys;
+use lab4::config::{load_config, load_keys, load_or_generate_tenant_keys, prepare_data_dir};
+use lab4::server;
 
 #[tokio::main]
 async fn main() {
-    // Initialize logging to stdout
-    tracing_subscriber::fmt::init();
+    // Read vts.toml (if any) and the VTS_* environment variables
+    let mut config = match load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to read config: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Initialize logging to stdout (the level is validated by load_config)
+    tracing_subscriber::fmt()
+        .with_max_level(config.log_level.parse::<tracing::Level>().unwrap())
+        .init();
 
-    // Load or generate keys
-    let (private_key, public_key) = match load_or_generate_keys() {
+    // `--mirror URL` serves a read-only copy of another server instead
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--mirror", Some(url)) => config.mirror.upstream = url,
+            _ => {
+                eprintln!("usage: lab4 [--mirror UPSTREAM_URL]");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // Keys and state live in the data directory, not wherever we were started
+    match prepare_data_dir(&mut config) {
+        Ok(moved) => {
+            for path in moved {
+                tracing::info!("Moved {} into the data directory", path.display());
+            }
+        }
+        Err(e) => {
+            tracing::error!(
+                "Failed to prepare data directory {}: {}",
+                config.data_dir,
+                e
+            );
+            std::process::exit(1);
+        }
+    }
+
+    // A mirror has no keys of its own: it serves the upstream's
+    if !config.mirror.upstream.is_empty() {
+        #[cfg(feature = "client")]
+        {
+            server::run_mirror(config).await.unwrap_or_else(|err| {
+                tracing::error!("Mirror error: {}", err);
+                std::process::exit(1);
+            });
+            return;
+        }
+        #[cfg(not(feature = "client"))]
+        {
+            tracing::error!(
+                "Asked to mirror {}, but the server was built without the client feature",
+                config.mirror.upstream
+            );
+            std::process::exit(1);
+        }
+    }
+
+    // Load or generate keys (or take them from VTS_PRIVATE_KEY)
+    let (private_key, public_key) = match load_keys(&config) {
         Ok(keys) => {
             tracing::info!("Loaded existing key pair");
             keys
@@ -19,11 +77,88 @@ async fn main() {
             std::process::exit(1);
         }
     };
+    let tenant_keys = match load_or_generate_tenant_keys(&config) {
+        Ok(keys) => keys,
+        Err(e) => {
+            tracing::error!("Failed to load or generate tenant keys: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    // Start the server and pass in the key pair
-    server::run_server(private_key, public_key)
+    // With `[kms]`, the default tenant signs through AWS KMS instead
+    if let Some(kms) = config.kms.clone() {
+        #[cfg(feature = "kms")]
+        {
+            run_with_kms(&kms, tenant_keys, config).await;
+            return;
+        }
+        #[cfg(not(feature = "kms"))]
+        {
+            tracing::error!(
+                "vts.toml has a [kms] table (key {}), but the server was built without the kms feature",
+                kms.key_id
+            );
+            std::process::exit(1);
+        }
+    }
+
+    // Start the server and pass in the key pairs
+    server::run_configured_server(private_key, public_key, tenant_keys, config)
         .await
         .unwrap_or_else(|err| {
             tracing::error!("Server error: {}", err);
+            std::process::exit(1);
         });
 }
+
+/// Serves the default tenant from the `[kms]` key and the other tenants from
+/// their key files, on `config.port`
+#[cfg(feature = "kms")]
+async fn run_with_kms(
+    kms: &lab4::config::KmsConfig,
+    tenant_keys: lab4::config::TenantKeys,
+    config: lab4::config::ServerConfig,
+) {
+    use lab4::signer::{Signer, TenantSigners, key_pair_signer, kms::KmsSigner};
+
+    let signer = match KmsSigner::connect(kms) {
+        Ok(signer) => {
+            tracing::info!("Signing with KMS key {}", kms.key_id);
+            signer
+        }
+        Err(e) => {
+            tracing::error!("Failed to connect to KMS key {}: {}", kms.key_id, e);
+            std::process::exit(1);
+        }
+    };
+    let tenant_signers: TenantSigners = match tenant_keys
+        .into_iter()
+        .map(|(name, (private_key, public_key))| {
+            let signer = key_pair_signer(&zeroize::Zeroizing::new(private_key), &public_key)?;
+            Ok((name, Box::new(signer) as Box<dyn Signer>))
+        })
+        .collect::<std::io::Result<_>>()
+    {
+        Ok(signers) => signers,
+        Err(e) => {
+            tracing::error!("Failed to load tenant keys: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let result = async {
+        let listener = server::Listener::bind(&config.listen, config.port).await?;
+        server::run_server_with_signers(
+            Box::new(signer),
+            tenant_signers,
+            config,
+            Box::new(lab4::clock::SystemClock),
+            listener,
+        )
+        .await
+    };
+    result.await.unwrap_or_else(|err| {
+        tracing::error!("Server error: {}", err);
+        std::process::exit(1);
+    });
+}
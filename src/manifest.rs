@@ -0,0 +1,109 @@
+//! Timestamping a whole directory tree at once
+//!
+//! [`Manifest::from_dir`] hashes every file under a directory, streaming
+//! each one like `ecdsa_requests::hash_reader`, into a map of relative
+//! path → hex SHA-256. One timestamp then covers the lot: the server signs
+//! the message `sha-256:<root>` (a hash-then-sign request, see
+//! `alg::DigestAlg`), where the root is the SHA-256 of [`Manifest::encode`]:
+//!
+//! ```text
+//! vts-manifest
+//! <hex sha-256> <path>
+//! ```
+//!
+//! with one line per file, sorted by path, each ending in `\n`. Keep the
+//! manifest (it serializes as JSON) next to the timestamp; with both, any
+//! single file can later be shown to have been covered
+//! ([`Manifest::verify_file`]) without the rest of the tree.
+
+use crate::alg::DigestAlg;
+use crate::ecdsa_requests::{hash_reader, verify_signature};
+use crate::{EcdsaSignedTimestamp, EcdsaVerificationKey};
+use k256::sha2::{Digest, Sha256};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Every file of a tree and its hash
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Path relative to the root, `/`-separated → hex SHA-256
+    pub files: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// Hashes every regular file under `root`. Symbolic links are skipped,
+    /// not followed, so the manifest only covers what is inside the tree.
+    /// Fails on a path that isn't UTF-8 or has a line break in it.
+    pub fn from_dir(root: impl AsRef<Path>) -> io::Result<Self> {
+        let mut manifest = Manifest::default();
+        manifest.add_dir(root.as_ref(), "")?;
+        Ok(manifest)
+    }
+
+    fn add_dir(&mut self, dir: &Path, prefix: &str) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().into_string().map_err(|name| {
+                invalid(format!("Path is not UTF-8: {}", name.to_string_lossy()))
+            })?;
+            if name.contains(['\n', '\r']) {
+                return Err(invalid(format!("Path has a line break: {:?}", name)));
+            }
+            let path = format!("{}{}", prefix, name);
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                self.add_dir(&entry.path(), &format!("{}/", path))?;
+            } else if file_type.is_file() {
+                let hash = hash_reader(File::open(entry.path())?)?;
+                self.files.insert(path, hash);
+            }
+        }
+        Ok(())
+    }
+
+    /// The bytes the root hashes (see the module docs)
+    pub fn encode(&self) -> String {
+        let mut text = "vts-manifest\n".to_string();
+        for (path, hash) in &self.files {
+            text.push_str(&format!("{} {}\n", hash, path));
+        }
+        text
+    }
+
+    /// SHA-256 of [`Self::encode`]
+    pub fn root(&self) -> [u8; 32] {
+        Sha256::digest(self.encode().as_bytes()).into()
+    }
+
+    /// The message a timestamp of this manifest carries
+    pub fn message(&self) -> String {
+        DigestAlg::Sha256.message(&self.root())
+    }
+
+    /// Whether `signed` timestamps this manifest, under `key`
+    pub fn verify(&self, signed: &EcdsaSignedTimestamp, key: &EcdsaVerificationKey) -> bool {
+        signed.message == self.message() && verify_signature(signed, key)
+    }
+
+    /// Whether `reader` is the file at `path` (relative, `/`-separated) as
+    /// this manifest has it, and `signed` timestamps the manifest
+    pub fn verify_file(
+        &self,
+        path: &str,
+        reader: impl Read,
+        signed: &EcdsaSignedTimestamp,
+        key: &EcdsaVerificationKey,
+    ) -> io::Result<bool> {
+        let Some(hash) = self.files.get(path) else {
+            return Ok(false);
+        };
+        Ok(hash_reader(reader)? == *hash && self.verify(signed, key))
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,175 @@
+//! Merkle trees as in RFC 6962 (Certificate Transparency) §2.1
+//!
+//! Leaves are hashed as `SHA-256(0x00 || data)` and inner nodes as
+//! `SHA-256(0x01 || left || right)`, so a leaf can't pass for a node. The
+//! compacted audit log (`audit::Checkpoint`) signs the root over its
+//! entries, and [`verify_inclusion`] checks one entry against it.
+//! [`verify_consistency`] checks that a tree only grew between two sizes
+//! (the log's signed tree heads, see `transparency`).
+
+use k256::sha2::{Digest, Sha256};
+
+/// `SHA-256(0x00 || data)`
+pub fn leaf_hash(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Largest power of two below `n` (`n` > 1)
+fn split(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+/// Root over the leaf hashes `leaves`; the hash of nothing for none
+pub fn root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    match leaves.len() {
+        0 => Sha256::digest([]).into(),
+        1 => leaves[0],
+        n => {
+            let k = split(n);
+            node_hash(&root(&leaves[..k]), &root(&leaves[k..]))
+        }
+    }
+}
+
+/// The sibling hashes from leaf `index` up to the root, bottom first
+pub fn inclusion_proof(leaves: &[[u8; 32]], index: usize) -> Vec<[u8; 32]> {
+    if leaves.len() <= 1 || index >= leaves.len() {
+        return Vec::new();
+    }
+    let k = split(leaves.len());
+    let (mut proof, sibling) = match index < k {
+        true => (inclusion_proof(&leaves[..k], index), root(&leaves[k..])),
+        false => (inclusion_proof(&leaves[k..], index - k), root(&leaves[..k])),
+    };
+    proof.push(sibling);
+    proof
+}
+
+/// Whether `leaf` is leaf `index` of a tree of `count` leaves with root
+/// `root`, by `proof` (RFC 9162 §2.1.3.2)
+pub fn verify_inclusion(
+    leaf: &[u8; 32],
+    index: u64,
+    count: u64,
+    proof: &[[u8; 32]],
+    root: &[u8; 32],
+) -> bool {
+    if index >= count {
+        return false;
+    }
+    let (mut f, mut s) = (index, count - 1);
+    let mut hash = *leaf;
+    for sibling in proof {
+        if s == 0 {
+            return false;
+        }
+        if f & 1 == 1 || f == s {
+            hash = node_hash(sibling, &hash);
+            while f & 1 == 0 && f != 0 {
+                f >>= 1;
+                s >>= 1;
+            }
+        } else {
+            hash = node_hash(&hash, sibling);
+        }
+        f >>= 1;
+        s >>= 1;
+    }
+    s == 0 && hash == *root
+}
+
+/// The hashes proving the tree over the first `old` of `leaves` is a prefix
+/// of the tree over all of them (RFC 6962 §2.1.2); empty for `old` 0 or
+/// all of them, or `old` past the end
+pub fn consistency_proof(leaves: &[[u8; 32]], old: usize) -> Vec<[u8; 32]> {
+    match old {
+        0 => Vec::new(),
+        old if old > leaves.len() => Vec::new(),
+        old => subproof(old, leaves, true),
+    }
+}
+
+/// `SUBPROOF(m, D[n], b)` of RFC 6962 §2.1.2
+fn subproof(m: usize, leaves: &[[u8; 32]], complete: bool) -> Vec<[u8; 32]> {
+    let n = leaves.len();
+    if m == n {
+        return match complete {
+            true => Vec::new(),
+            false => vec![root(leaves)],
+        };
+    }
+    let k = split(n);
+    let (mut proof, sibling) = match m <= k {
+        true => (subproof(m, &leaves[..k], complete), root(&leaves[k..])),
+        false => (subproof(m - k, &leaves[k..], false), root(&leaves[..k])),
+    };
+    proof.push(sibling);
+    proof
+}
+
+/// Whether the tree of `new` leaves with root `new_root` extends the one
+/// of `old` leaves with root `old_root`, by `proof` (RFC 9162 §2.1.4.2).
+/// Every tree extends the empty one.
+pub fn verify_consistency(
+    old: u64,
+    new: u64,
+    old_root: &[u8; 32],
+    new_root: &[u8; 32],
+    proof: &[[u8; 32]],
+) -> bool {
+    if old > new {
+        return false;
+    }
+    if old == 0 {
+        return proof.is_empty();
+    }
+    if old == new {
+        return proof.is_empty() && old_root == new_root;
+    }
+    // 1) The old root starts the path when it is a whole subtree
+    let mut path = Vec::with_capacity(proof.len() + 1);
+    if old.is_power_of_two() {
+        path.push(*old_root);
+    }
+    path.extend_from_slice(proof);
+    let Some((first, rest)) = path.split_first() else {
+        return false;
+    };
+
+    // 2) Rebuild both roots from it
+    let (mut f, mut s) = (old - 1, new - 1);
+    while f & 1 == 1 {
+        f >>= 1;
+        s >>= 1;
+    }
+    let (mut old_hash, mut new_hash) = (*first, *first);
+    for sibling in rest {
+        if s == 0 {
+            return false;
+        }
+        if f & 1 == 1 || f == s {
+            old_hash = node_hash(sibling, &old_hash);
+            new_hash = node_hash(sibling, &new_hash);
+            while f & 1 == 0 && f != 0 {
+                f >>= 1;
+                s >>= 1;
+            }
+        } else {
+            new_hash = node_hash(&new_hash, sibling);
+        }
+        f >>= 1;
+        s >>= 1;
+    }
+    s == 0 && old_hash == *old_root && new_hash == *new_root
+}
@@ -0,0 +1,208 @@
+//! Watching a log for split views (`vts-monitor`)
+//!
+//! A server that shows different vantage points different logs can sign a
+//! head for each, and each one verifies alone. The [`Monitor`] polls
+//! `GET /log/root` through several vantage points (hostnames, mirrors,
+//! networks) with a pinned key and keeps the heads it accepted in a state
+//! file. It raises an [`Alert`] when two heads have the same count but
+//! different roots. It also alerts when the vantage point that served a
+//! head can't prove with `GET /log/consistency` that it extends the
+//! longest head accepted so far, or is a prefix of it. A head in an alert
+//! is not accepted, so later heads are still checked against the first
+//! view.
+
+use crate::transparency::{ConsistencyProof, LogError, TreeHead};
+use ecdsa_lib::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// One head as a vantage point served it; the state file has a line for
+/// each one accepted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+    pub vantage: String,
+    /// When it was fetched (RFC 3339, by the monitor's clock)
+    pub seen: String,
+    #[serde(flatten)]
+    pub head: TreeHead,
+}
+
+/// Evidence the log was not the same for everyone
+#[derive(Debug, Clone, PartialEq)]
+pub enum Alert {
+    /// Two roots, both signed, for the same count
+    SplitView {
+        first: Observation,
+        second: Observation,
+    },
+    /// `new`'s vantage point could not prove it and `old` are one log
+    Inconsistent { old: Observation, new: Observation },
+}
+
+impl std::fmt::Display for Alert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Alert::SplitView { first, second } => write!(
+                f,
+                "ALERT: split view at {} lines: {} served root {} ({}), {} served root {} ({})",
+                first.head.count,
+                first.vantage,
+                first.head.root,
+                first.seen,
+                second.vantage,
+                second.head.root,
+                second.seen
+            ),
+            Alert::Inconsistent { old, new } => write!(
+                f,
+                "ALERT: {} served a head ({} lines, root {}) it can't prove is one log with the one {} served ({} lines, root {})",
+                new.vantage,
+                new.head.count,
+                new.head.root,
+                old.vantage,
+                old.head.count,
+                old.head.root
+            ),
+        }
+    }
+}
+
+/// The heads seen so far, kept in a state file across runs
+pub struct Monitor {
+    key: PublicKey,
+    state: PathBuf,
+    /// The head accepted at each count
+    heads: BTreeMap<u64, Observation>,
+    /// The longest head accepted, which new ones are checked against
+    latest: Option<Observation>,
+    http: reqwest::Client,
+}
+
+impl Monitor {
+    /// A monitor trusting heads signed by `key`, resuming from `state` (an
+    /// NDJSON file of [`Observation`]s, created if missing)
+    pub fn open(key: PublicKey, state: impl AsRef<Path>) -> std::io::Result<Self> {
+        let state = state.as_ref().to_path_buf();
+        let mut monitor = Monitor {
+            key,
+            state,
+            heads: BTreeMap::new(),
+            latest: None,
+            http: reqwest::Client::new(),
+        };
+        let file = match File::open(&monitor.state) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(monitor),
+            Err(e) => return Err(e),
+        };
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let observation: Observation =
+                serde_json::from_str(&line).map_err(std::io::Error::other)?;
+            if monitor
+                .latest
+                .as_ref()
+                .is_none_or(|latest| observation.head.count > latest.head.count)
+            {
+                monitor.latest = Some(observation.clone());
+            }
+            monitor.heads.insert(observation.head.count, observation);
+        }
+        Ok(monitor)
+    }
+
+    /// Fetches `vantage`'s head and checks it against every head seen.
+    /// A head not signed by the key, or a vantage point that doesn't
+    /// answer, is an error rather than an alert.
+    pub async fn poll(
+        &mut self,
+        vantage: &str,
+    ) -> Result<Vec<Alert>, Box<dyn Error + Send + Sync>> {
+        let base = vantage.trim_end_matches('/');
+
+        // 1) The head, signed by the pinned key
+        let resp = self.http.get(format!("{}/log/root", base)).send().await?;
+        if !resp.status().is_success() {
+            return Err(format!("GET /log/root answered {}", resp.status()).into());
+        }
+        let head: TreeHead = resp.json().await?;
+        if !head.verify(&self.key) {
+            return Err(LogError::Invalid.into());
+        }
+        let observation = Observation {
+            vantage: vantage.to_string(),
+            seen: crate::wire::canonical_time(chrono::Utc::now()),
+            head,
+        };
+
+        // 2) No other root for its count
+        match self.heads.get(&observation.head.count) {
+            Some(first) if first.head.root != observation.head.root => {
+                return Ok(vec![Alert::SplitView {
+                    first: first.clone(),
+                    second: observation,
+                }]);
+            }
+            Some(_) => return Ok(Vec::new()),
+            None => {}
+        }
+
+        // 3) One log with the longest head, by the vantage point's own proof
+        if let Some(latest) = self.latest.clone() {
+            let (old, new) = match observation.head.count > latest.head.count {
+                true => (&latest, &observation),
+                false => (&observation, &latest),
+            };
+            let query = [("old", old.head.count), ("new", new.head.count)];
+            let resp = self
+                .http
+                .get(format!("{}/log/consistency", base))
+                .query(&query)
+                .send()
+                .await?;
+            // A log too short for the longest head can't contain it
+            let consistent = match resp.status() {
+                reqwest::StatusCode::NOT_FOUND => false,
+                status if status.is_success() => resp
+                    .json::<ConsistencyProof>()
+                    .await?
+                    .verify(&old.head, &new.head),
+                status => return Err(format!("GET /log/consistency answered {}", status).into()),
+            };
+            if !consistent {
+                return Ok(vec![Alert::Inconsistent {
+                    old: latest,
+                    new: observation,
+                }]);
+            }
+        }
+        self.record(&observation)?;
+        if self
+            .latest
+            .as_ref()
+            .is_none_or(|latest| observation.head.count > latest.head.count)
+        {
+            self.latest = Some(observation.clone());
+        }
+        self.heads.insert(observation.head.count, observation);
+        Ok(Vec::new())
+    }
+
+    /// Appends `observation` to the state file
+    fn record(&self, observation: &Observation) -> std::io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.state)?;
+        let line = serde_json::to_string(observation).map_err(std::io::Error::other)?;
+        writeln!(file, "{}", line)?;
+        file.sync_data()
+    }
+}
@@ -0,0 +1,199 @@
+//! Timestamps of one hash from several servers (multi-authority)
+//!
+//! A single server's timestamp is only as trustworthy as that server's key.
+//! [`request`] asks each of N servers for a hash-then-sign timestamp of the
+//! same SHA-256 digest (the message `sha-256:<hex>`, see `alg::DigestAlg`)
+//! and bundles the answers into one [`MultiTimestamp`]. [`verify_multi`]
+//! then accepts it only if at least k of the n authority keys it is given
+//! each verify one of its timestamps, so one compromised (or unreachable)
+//! authority is not enough to forge or withhold it.
+
+use crate::alg::DigestAlg;
+use crate::ecdsa_requests::verify_signature;
+use crate::jws;
+use crate::{EcdsaSignedTimestamp, EcdsaVerificationKey};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+/// One authority's timestamp in a bundle
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Authority {
+    /// Address of the server it came from, for reference only: what counts
+    /// is the key it verifies under
+    pub server: String,
+    /// Its timestamp of `sha-256:<digest>`
+    pub timestamp: EcdsaSignedTimestamp,
+}
+
+/// Timestamps of one digest from several servers
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MultiTimestamp {
+    /// Hex SHA-256 every authority timestamped
+    pub digest: String,
+    /// In the order the servers were given; failed ones are left out
+    pub timestamps: Vec<Authority>,
+}
+
+impl MultiTimestamp {
+    /// This bundle as one token: its JSON
+    pub fn to_token(&self) -> String {
+        serde_json::to_string(self).expect("bundle serializes")
+    }
+
+    /// The bundle a [`Self::to_token`] token holds, if it is one
+    pub fn from_token(token: &str) -> Option<Self> {
+        serde_json::from_str(token).ok()
+    }
+}
+
+/// Fewer servers answered than the bundle needs
+#[derive(Debug)]
+pub struct MultiError {
+    /// The threshold asked for
+    pub needed: usize,
+    /// Timestamps received
+    pub answered: usize,
+    /// Each server that failed, with why
+    pub failures: Vec<(String, String)>,
+}
+
+impl fmt::Display for MultiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "only {} of the {} timestamps needed",
+            self.answered, self.needed
+        )?;
+        for (server, error) in &self.failures {
+            write!(f, "; {}: {}", server, error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MultiError {}
+
+/// The bundle of what the servers answered, or `MultiError` if that is
+/// fewer than `needed`
+fn bundle(
+    digest: &[u8; 32],
+    answers: Vec<(String, Result<EcdsaSignedTimestamp, String>)>,
+    needed: usize,
+) -> Result<MultiTimestamp, MultiError> {
+    let mut timestamps = Vec::new();
+    let mut failures = Vec::new();
+    for (server, answer) in answers {
+        match answer {
+            Ok(timestamp) => timestamps.push(Authority { server, timestamp }),
+            Err(e) => failures.push((server, e)),
+        }
+    }
+    if timestamps.len() < needed {
+        return Err(MultiError {
+            needed,
+            answered: timestamps.len(),
+            failures,
+        });
+    }
+    Ok(MultiTimestamp {
+        digest: hex::encode(digest),
+        timestamps,
+    })
+}
+
+/// Asks every client's server, one after the other, for a timestamp of the
+/// SHA-256 `digest`. Fails if fewer than `needed` answer.
+#[cfg(feature = "blocking")]
+pub fn request(
+    clients: &[crate::client::VtsClient],
+    digest: &[u8; 32],
+    needed: usize,
+) -> Result<MultiTimestamp, MultiError> {
+    let answers = clients
+        .iter()
+        .map(|client| {
+            let answer = client
+                .request_digest_timestamp(digest, DigestAlg::Sha256)
+                .map_err(|e| e.to_string());
+            (client.server_addr().to_string(), answer)
+        })
+        .collect();
+    bundle(digest, answers, needed)
+}
+
+/// Async versions of the request functions
+pub mod nonblocking {
+    use super::{MultiError, MultiTimestamp, bundle};
+    use crate::alg::DigestAlg;
+    use crate::client::nonblocking::VtsClient;
+
+    /// Like [`super::request`], asking all the servers at once
+    pub async fn request(
+        clients: &[VtsClient],
+        digest: &[u8; 32],
+        needed: usize,
+    ) -> Result<MultiTimestamp, MultiError> {
+        let mut tasks = tokio::task::JoinSet::new();
+        for (i, client) in clients.iter().cloned().enumerate() {
+            let digest = *digest;
+            tasks.spawn(async move {
+                let answer = client
+                    .request_digest_timestamp(&digest, DigestAlg::Sha256)
+                    .await
+                    .map_err(|e| e.to_string());
+                (i, answer)
+            });
+        }
+        let mut answers: Vec<Result<_, String>> = (0..clients.len())
+            .map(|_| Err("request failed".to_string()))
+            .collect();
+        while let Some(joined) = tasks.join_next().await {
+            if let Ok((i, answer)) = joined {
+                answers[i] = answer;
+            }
+        }
+        let answers = clients
+            .iter()
+            .map(|client| client.server_addr().to_string())
+            .zip(answers)
+            .collect();
+        bundle(digest, answers, needed)
+    }
+}
+
+/// Whether at least `k` (at least one) of the authority `keys` each verify
+/// some timestamp of `multi`, all of them of the SHA-256 `digest`. A key
+/// listed twice counts once, and so does a timestamp, so k distinct keys
+/// are needed.
+pub fn verify_multi(
+    multi: &MultiTimestamp,
+    digest: &[u8; 32],
+    keys: &[EcdsaVerificationKey],
+    k: usize,
+) -> bool {
+    if k == 0 || multi.digest != hex::encode(digest) {
+        return false;
+    }
+    let message = DigestAlg::Sha256.message(digest);
+    let mut used = vec![false; multi.timestamps.len()];
+    let mut seen = HashSet::new();
+    let mut valid = 0;
+    for key in keys {
+        let Some(public_key) = key.key() else {
+            continue;
+        };
+        if !seen.insert(jws::key_id(&public_key)) {
+            continue;
+        }
+        let found = (0..multi.timestamps.len()).find(|&i| {
+            let timestamp = &multi.timestamps[i].timestamp;
+            !used[i] && timestamp.message == message && verify_signature(timestamp, key)
+        });
+        if let Some(i) = found {
+            used[i] = true;
+            valid += 1;
+        }
+    }
+    valid >= k
+}
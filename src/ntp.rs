@@ -0,0 +1,187 @@
+//! NTP drift checking
+//!
+//! A minimal SNTP (RFC 4330) client and a background monitor that keeps the
+//! latest offset between the signing clock and the configured NTP servers.
+//! The server refuses to sign while the drift is above the configured limit,
+//! and reports an RFC 3161-style `accuracy` otherwise.
+
+use crate::clock::MonotonicClock;
+use crate::config::NtpConfig;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tracing::{info, warn};
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch (1970)
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+/// How long to wait for an NTP reply
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// One measurement against an NTP server
+#[derive(Debug, Clone, Copy)]
+pub struct NtpSample {
+    /// Server time minus local time (positive = local clock is behind)
+    pub offset: ChronoDuration,
+    /// Round-trip delay, excluding the server's processing time
+    pub delay: ChronoDuration,
+}
+
+impl NtpSample {
+    /// Upper bound on how far local time may be from the server's: |offset| + delay/2
+    pub fn error_bound(&self) -> ChronoDuration {
+        self.offset.abs() + self.delay / 2
+    }
+}
+
+fn to_ntp(t: DateTime<Utc>) -> [u8; 8] {
+    let secs = (t.timestamp() + NTP_UNIX_OFFSET) as u32;
+    let frac = ((t.timestamp_subsec_nanos() as u64) << 32) / 1_000_000_000;
+    let mut out = [0u8; 8];
+    out[..4].copy_from_slice(&secs.to_be_bytes());
+    out[4..].copy_from_slice(&(frac as u32).to_be_bytes());
+    out
+}
+
+fn from_ntp(b: &[u8]) -> DateTime<Utc> {
+    let secs = u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as i64 - NTP_UNIX_OFFSET;
+    let frac = u32::from_be_bytes([b[4], b[5], b[6], b[7]]) as u64;
+    let nanos = ((frac * 1_000_000_000) >> 32) as u32;
+    DateTime::from_timestamp(secs, nanos).unwrap_or_default()
+}
+
+fn invalid(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Queries `server` ("host:port") once, using `clock` as local time.
+pub async fn query(server: &str, clock: &MonotonicClock) -> std::io::Result<NtpSample> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(server).await?;
+
+    // LI = 0, VN = 4, Mode = 3 (client); our send time goes in the transmit field
+    let mut request = [0u8; 48];
+    request[0] = 0x23;
+    let t1 = clock.now();
+    let t1_bytes = to_ntp(t1);
+    request[40..48].copy_from_slice(&t1_bytes);
+    socket.send(&request).await?;
+
+    let mut reply = [0u8; 48];
+    let len = tokio::time::timeout(QUERY_TIMEOUT, socket.recv(&mut reply))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "NTP timeout"))??;
+    let t4 = clock.now();
+
+    if len < 48 {
+        return Err(invalid("Short NTP reply"));
+    }
+    if reply[0] & 0x07 != 4 {
+        return Err(invalid("NTP reply is not in server mode"));
+    }
+    if reply[1] == 0 {
+        return Err(invalid("NTP kiss-o'-death reply"));
+    }
+    if reply[24..32] != t1_bytes {
+        return Err(invalid("NTP reply does not match request"));
+    }
+
+    let t2 = from_ntp(&reply[32..40]);
+    let t3 = from_ntp(&reply[40..48]);
+    Ok(NtpSample {
+        offset: ((t2 - t1) + (t3 - t4)) / 2,
+        delay: (t4 - t1) - (t3 - t2),
+    })
+}
+
+/// Current drift between the signing clock and NTP, per `[ntp]`
+pub struct DriftMonitor {
+    config: NtpConfig,
+    latest: Mutex<Option<(Instant, NtpSample)>>,
+}
+
+/// Whether `/sign` may proceed, according to the drift monitor
+#[derive(Debug, PartialEq)]
+pub enum DriftStatus {
+    /// Drift is within limits; the `accuracy` to report
+    Ok(String),
+    /// No fresh measurement: sign, but claim no accuracy
+    Unknown,
+    /// Drift above `max_drift_ms`
+    TooLarge(ChronoDuration),
+}
+
+impl DriftMonitor {
+    pub fn new(config: NtpConfig) -> Self {
+        Self {
+            config,
+            latest: Mutex::new(None),
+        }
+    }
+
+    /// Queries every server and keeps the sample with the lowest delay.
+    pub async fn measure(&self, clock: &MonotonicClock) {
+        let mut best: Option<NtpSample> = None;
+        for server in &self.config.servers {
+            match query(server, clock).await {
+                Ok(sample) => {
+                    if best.is_none_or(|b| sample.delay < b.delay) {
+                        best = Some(sample);
+                    }
+                }
+                Err(e) => warn!(
+                    "{} NTP query to {} failed: {}",
+                    Utc::now().to_rfc3339(),
+                    server,
+                    e
+                ),
+            }
+        }
+        if let Some(sample) = best {
+            info!(
+                "{} NTP offset {}ms (delay {}ms)",
+                Utc::now().to_rfc3339(),
+                sample.offset.num_milliseconds(),
+                sample.delay.num_milliseconds()
+            );
+            *self.latest.lock().unwrap() = Some((Instant::now(), sample));
+        }
+    }
+
+    /// A measurement older than three intervals is considered stale.
+    pub fn status(&self) -> DriftStatus {
+        let stale_after = Duration::from_secs(self.config.interval_secs.max(1) * 3);
+        match *self.latest.lock().unwrap() {
+            Some((at, sample)) if at.elapsed() < stale_after => {
+                if sample.offset.abs()
+                    > ChronoDuration::milliseconds(self.config.max_drift_ms as i64)
+                {
+                    DriftStatus::TooLarge(sample.offset)
+                } else {
+                    let bound = sample.error_bound();
+                    // Round up to whole milliseconds
+                    let ms = (bound
+                        .num_microseconds()
+                        .unwrap_or(i64::MAX)
+                        .saturating_add(999))
+                        / 1000;
+                    DriftStatus::Ok(format!("±{}ms", ms))
+                }
+            }
+            _ => DriftStatus::Unknown,
+        }
+    }
+
+    /// Measures now and then every `interval_secs`, forever.
+    pub fn spawn(self: Arc<Self>, clock: Arc<MonotonicClock>) {
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(self.config.interval_secs.max(1)));
+            loop {
+                interval.tick().await;
+                self.measure(&clock).await;
+            }
+        });
+    }
+}
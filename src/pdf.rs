@@ -0,0 +1,140 @@
+//! Timestamping a PDF document
+//!
+//! A PDF prepared for signing (PAdES, ISO 32000-1 §12.8) has a
+//! `/ByteRange [a b c d]`: the bytes `a..a+b` and `c..c+d`, i.e. all of the
+//! file but the `/Contents` placeholder a signature goes into. [`digest`]
+//! hashes those ranges, or the whole file if it has none. The server then
+//! timestamps `sha-256:<hex>` (a hash-then-sign request, see
+//! `alg::DigestAlg`), and [`Proof`] keeps the timestamp with the range it
+//! covers, as a detached file next to the document ([`proof_path`]).
+//!
+//! The proof is not embedded in the PDF itself: that needs an incremental
+//! update written by a PDF library, which this crate doesn't have.
+
+use crate::alg::DigestAlg;
+use crate::ecdsa_requests::verify_signature;
+use crate::{EcdsaSignedTimestamp, EcdsaVerificationKey};
+use k256::sha2::{Digest, Sha256};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Why a document couldn't be hashed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PdfError {
+    /// It doesn't start with `%PDF-`
+    NotPdf,
+    /// Its `/ByteRange` isn't four numbers inside the file, in order
+    BadByteRange,
+    /// The timestamp is of another document
+    Mismatch,
+}
+
+impl fmt::Display for PdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PdfError::NotPdf => write!(f, "not a PDF document"),
+            PdfError::BadByteRange => write!(f, "invalid /ByteRange in PDF document"),
+            PdfError::Mismatch => write!(f, "timestamp is not of this PDF document"),
+        }
+    }
+}
+
+impl std::error::Error for PdfError {}
+
+/// A timestamp of a PDF, kept beside it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Proof {
+    /// The `/ByteRange` hashed, `None` for the whole file
+    #[serde(rename = "byte-range", skip_serializing_if = "Option::is_none")]
+    pub byte_range: Option<[usize; 4]>,
+    /// Hex SHA-256 of those bytes
+    pub digest: String,
+    /// The timestamp of `sha-256:<digest>`
+    pub timestamp: EcdsaSignedTimestamp,
+}
+
+impl Proof {
+    /// The proof of `pdf` that `timestamp` gives, if it timestamps it
+    pub fn new(pdf: &[u8], timestamp: EcdsaSignedTimestamp) -> Result<Self, PdfError> {
+        let digest = digest(pdf)?;
+        if timestamp.message != DigestAlg::Sha256.message(&digest) {
+            return Err(PdfError::Mismatch);
+        }
+        Ok(Proof {
+            byte_range: byte_range(pdf)?,
+            digest: hex::encode(digest),
+            timestamp,
+        })
+    }
+
+    /// Whether this proves `pdf` (its signed ranges unchanged), under `key`
+    pub fn verify(&self, pdf: &[u8], key: &EcdsaVerificationKey) -> Result<bool, PdfError> {
+        let digest = digest(pdf)?;
+        Ok(byte_range(pdf)? == self.byte_range
+            && hex::encode(digest) == self.digest
+            && self.timestamp.message == DigestAlg::Sha256.message(&digest)
+            && verify_signature(&self.timestamp, key))
+    }
+}
+
+/// Where the proof of the document at `path` goes: `report.pdf` →
+/// `report.pdf.vts.json`
+pub fn proof_path(path: impl AsRef<Path>) -> PathBuf {
+    let mut name = path.as_ref().as_os_str().to_owned();
+    name.push(".vts.json");
+    PathBuf::from(name)
+}
+
+/// SHA-256 of the bytes `/ByteRange` covers, or of all of `pdf`
+pub fn digest(pdf: &[u8]) -> Result<[u8; 32], PdfError> {
+    let mut hasher = Sha256::new();
+    match byte_range(pdf)? {
+        Some([a, b, c, d]) => {
+            hasher.update(&pdf[a..a + b]);
+            hasher.update(&pdf[c..c + d]);
+        }
+        None => hasher.update(pdf),
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// The last `/ByteRange` in `pdf` (an incremental update's comes after the
+/// ones it replaces), checked against the file's length
+pub fn byte_range(pdf: &[u8]) -> Result<Option<[usize; 4]>, PdfError> {
+    // 1) Only PDFs, so a misnamed file doesn't get a proof it can't use
+    if !pdf.starts_with(b"%PDF-") {
+        return Err(PdfError::NotPdf);
+    }
+
+    // 2) Find the last /ByteRange and read its four numbers
+    const KEY: &[u8] = b"/ByteRange";
+    let Some(at) = pdf.windows(KEY.len()).rposition(|w| w == KEY) else {
+        return Ok(None);
+    };
+    let rest = &pdf[at + KEY.len()..];
+    let close = rest
+        .iter()
+        .position(|&b| b == b']')
+        .ok_or(PdfError::BadByteRange)?;
+    let text = std::str::from_utf8(&rest[..close]).map_err(|_| PdfError::BadByteRange)?;
+    let text = text
+        .trim_start()
+        .strip_prefix('[')
+        .ok_or(PdfError::BadByteRange)?;
+    let numbers: Vec<usize> = text
+        .split_whitespace()
+        .map(|n| n.parse().map_err(|_| PdfError::BadByteRange))
+        .collect::<Result<_, _>>()?;
+    let [a, b, c, d]: [usize; 4] = numbers.try_into().map_err(|_| PdfError::BadByteRange)?;
+
+    // 3) The ranges must be in order and inside the file
+    let in_file = a
+        .checked_add(b)
+        .zip(c.checked_add(d))
+        .is_some_and(|(end1, end2)| end1 <= c && end2 <= pdf.len());
+    if !in_file {
+        return Err(PdfError::BadByteRange);
+    }
+    Ok(Some([a, b, c, d]))
+}
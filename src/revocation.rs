@@ -0,0 +1,163 @@
+//! Revocation list of compromised keys (`[[revoked_keys]]`, `GET /revocations`)
+//!
+//! A leaked key can't take back what it signed, but the operator can say
+//! from when its signatures stopped being the service's. Each entry names a
+//! key by its `kid` and the time it was compromised: timestamps it signed
+//! before then stay valid, later ones fail [`RevocationList::check`]. The
+//! server stops signing with a listed key at that time too.
+//!
+//! The server signs the list with the tenant's current key, over
+//! [`signing_input`]:
+//!
+//! ```text
+//! vts-revocations
+//! 2025-06-01T12:00:00.000000Z
+//! <kid> <compromised-at> <reason>
+//! ```
+//!
+//! with one line per entry, in order, each ending in `\n`.
+
+use crate::ecdsa_requests::VerifyOutcome;
+use crate::{EcdsaSignedTimestamp, EcdsaVerificationKey, jws, wire};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// One revoked key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct RevokedKey {
+    /// `jws::key_id` of the key
+    pub kid: String,
+    /// Timestamps it signed from this time on are rejected
+    #[serde(rename = "compromised-at")]
+    pub compromised_at: String,
+    /// Free text on one line, e.g. `key-compromise`
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// Body of `GET /revocations`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct RevocationList {
+    pub request: String,
+    #[serde(rename = "time-issued")]
+    pub time_issued: String,
+    pub revoked: Vec<RevokedKey>,
+    /// The key that signed the list
+    pub kid: String,
+    /// Base64 of `r || s` over [`signing_input`]
+    pub signature: String,
+}
+
+/// Why a revocation list was not accepted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevocationError {
+    /// Signed by none of the keys given; the key id it names
+    UnknownKey(String),
+    /// Signed by a key the list itself revokes before it was issued
+    RevokedSigner,
+    /// A time in it isn't RFC 3339, or the signature isn't Base64
+    Malformed,
+    /// The signature doesn't verify
+    Invalid,
+}
+
+impl fmt::Display for RevocationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RevocationError::UnknownKey(kid) => {
+                write!(f, "revocation list signed by an unknown key ({})", kid)
+            }
+            RevocationError::RevokedSigner => {
+                write!(f, "revocation list signed by a revoked key")
+            }
+            RevocationError::Malformed => write!(f, "revocation list is malformed"),
+            RevocationError::Invalid => write!(f, "revocation list signature does not verify"),
+        }
+    }
+}
+
+impl std::error::Error for RevocationError {}
+
+/// The bytes the server signs for a list issued at `time_issued`
+pub fn signing_input(time_issued: &str, revoked: &[RevokedKey]) -> String {
+    let mut input = format!("vts-revocations\n{}\n", time_issued);
+    for entry in revoked {
+        input.push_str(&format!(
+            "{} {} {}\n",
+            entry.kid, entry.compromised_at, entry.reason
+        ));
+    }
+    input
+}
+
+impl RevocationList {
+    /// Checks that one of `keys` (from `GET /keys`) signed the list, and
+    /// that the list doesn't revoke that key before it was issued
+    pub fn verify(&self, keys: &[EcdsaVerificationKey]) -> Result<(), RevocationError> {
+        // 1) The signing key, by its id
+        let public_key = keys
+            .iter()
+            .filter_map(|key| key.key())
+            .find(|key| jws::key_id(key) == self.kid)
+            .ok_or_else(|| RevocationError::UnknownKey(self.kid.clone()))?;
+
+        // 2) The signature over the list
+        let signature = general_purpose::STANDARD
+            .decode(&self.signature)
+            .map_err(|_| RevocationError::Malformed)?;
+        let input = signing_input(&self.time_issued, &self.revoked);
+        if !public_key
+            .verify_encoded(input.as_bytes(), &signature)
+            .is_ok()
+        {
+            return Err(RevocationError::Invalid);
+        }
+
+        // 3) Every time in it, and whether it disowns its own signer
+        let time_issued =
+            wire::parse_time(&self.time_issued).map_err(|_| RevocationError::Malformed)?;
+        for entry in &self.revoked {
+            let compromised_at =
+                wire::parse_time(&entry.compromised_at).map_err(|_| RevocationError::Malformed)?;
+            if entry.kid == self.kid && compromised_at <= time_issued {
+                return Err(RevocationError::RevokedSigner);
+            }
+        }
+        Ok(())
+    }
+
+    /// When the key `kid` was compromised, if the list revokes it (the
+    /// earliest time, if it is listed more than once)
+    pub fn compromised_at(&self, kid: &str) -> Option<DateTime<Utc>> {
+        self.revoked
+            .iter()
+            .filter(|entry| entry.kid == kid)
+            .filter_map(|entry| wire::parse_time(&entry.compromised_at).ok())
+            .min()
+    }
+
+    /// Whether `signed` still stands: `Revoked` if `key` was compromised
+    /// at or before its `time_signed`. The signature itself is not checked
+    /// here; see `verify_signature_with`.
+    pub fn check(
+        &self,
+        signed: &EcdsaSignedTimestamp,
+        key: &EcdsaVerificationKey,
+    ) -> VerifyOutcome {
+        let Some(public_key) = key.key() else {
+            return VerifyOutcome::BadKey;
+        };
+        let Some(compromised_at) = self.compromised_at(&jws::key_id(&public_key)) else {
+            return VerifyOutcome::Ok;
+        };
+        match wire::parse_time(&signed.time_signed) {
+            Ok(time_signed) if time_signed < compromised_at => VerifyOutcome::Ok,
+            Ok(_) => VerifyOutcome::Revoked,
+            Err(_) => VerifyOutcome::BadTimestamp,
+        }
+    }
+}
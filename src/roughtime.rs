@@ -0,0 +1,417 @@
+//! The Roughtime wire format, signed with the VTS keys
+//!
+//! Roughtime (draft-ietf-ntp-roughtime, after Google's protocol) answers a
+//! client's random nonce with a signed `(midpoint, radius)`. Every value is
+//! in a tagged [`Message`]; the answer signs a Merkle root over the nonces
+//! of a batch (`SREP`), with a short-lived online key that the long-term
+//! key delegates to for `MINT..MAXT` (`CERT`/`DELE`).
+//!
+//! This is the Google framing (64-byte nonces, SHA-512 tree, microsecond
+//! `MIDP`/`RADI`, 1024-byte requests), but its signatures are the VTS's
+//! ECDSA over secp256k1 (SHA-256, 64-byte `r || s`), not Ed25519, and `PUBK`
+//! is the online key's `x || y`. Stock Roughtime clients can parse the
+//! answers, and check them only with [`verify_response`] and the server's
+//! `GET /key`.
+//!
+//! A client that builds each nonce from the previous answer
+//! ([`chain_nonce`]) holds, once two answers contradict each other
+//! ([`inconsistent`]), signed proof that one of the servers lied.
+
+use chrono::{DateTime, Utc};
+use ecdsa_lib::{KeyPair, PublicKey};
+use k256::ecdsa::Signature;
+use k256::sha2::{Digest, Sha512};
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+/// A four-byte tag, e.g. `*b"NONC"`
+pub type Tag = [u8; 4];
+
+pub const SIG: Tag = *b"SIG\0";
+pub const NONC: Tag = *b"NONC";
+pub const PAD: Tag = *b"PAD\xff";
+pub const PATH: Tag = *b"PATH";
+pub const SREP: Tag = *b"SREP";
+pub const CERT: Tag = *b"CERT";
+pub const INDX: Tag = *b"INDX";
+pub const ROOT: Tag = *b"ROOT";
+pub const MIDP: Tag = *b"MIDP";
+pub const RADI: Tag = *b"RADI";
+pub const DELE: Tag = *b"DELE";
+pub const PUBK: Tag = *b"PUBK";
+pub const MINT: Tag = *b"MINT";
+pub const MAXT: Tag = *b"MAXT";
+
+/// Prefix of the bytes the online key signs: this, then `SREP`
+pub const RESPONSE_CONTEXT: &[u8] = b"RoughTime v1 response signature\0";
+/// Prefix of the bytes the long-term key signs: this, then `DELE`
+pub const DELEGATION_CONTEXT: &[u8] = b"RoughTime v1 delegation signature--\0";
+
+/// Length of a nonce, and of every tree hash
+pub const NONCE_LEN: usize = 64;
+/// Shortest request a server answers, so an answer is never larger than
+/// what asked for it
+pub const MIN_REQUEST_LEN: usize = 1024;
+
+/// Why a message or an answer was rejected
+#[derive(Debug, PartialEq, Eq)]
+pub enum RoughtimeError {
+    /// Not a well-formed tagged message
+    Malformed(&'static str),
+    /// A required tag is absent, or its value has the wrong length
+    Missing(Tag),
+    /// `CERT` is not signed by the long-term key
+    BadDelegation,
+    /// `SREP` is not signed by the delegated key
+    BadSignature,
+    /// `ROOT` doesn't cover our nonce at `INDX` along `PATH`
+    NotInTree,
+    /// `MIDP` is outside the delegation's `MINT..MAXT`
+    OutsideDelegation,
+}
+
+impl fmt::Display for RoughtimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(why) => write!(f, "malformed message: {}", why),
+            Self::Missing(tag) => write!(f, "missing or bad {}", String::from_utf8_lossy(tag)),
+            Self::BadDelegation => f.write_str("delegation is not signed by the server key"),
+            Self::BadSignature => f.write_str("response is not signed by the delegated key"),
+            Self::NotInTree => f.write_str("response does not cover the nonce"),
+            Self::OutsideDelegation => f.write_str("midpoint is outside the delegation"),
+        }
+    }
+}
+
+impl std::error::Error for RoughtimeError {}
+
+/// Tag/value pairs, in ascending order of the tags read as little-endian
+/// `u32`s, as the wire format requires
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Message {
+    fields: Vec<(Tag, Vec<u8>)>,
+}
+
+fn tag_order(tag: &Tag) -> u32 {
+    u32::from_le_bytes(*tag)
+}
+
+impl Message {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `tag` to `value`, whose length must be a multiple of 4
+    pub fn insert(&mut self, tag: Tag, value: Vec<u8>) {
+        assert!(
+            value.len().is_multiple_of(4),
+            "values are whole 32-bit words"
+        );
+        match self
+            .fields
+            .binary_search_by_key(&tag_order(&tag), |(t, _)| tag_order(t))
+        {
+            Ok(i) => self.fields[i].1 = value,
+            Err(i) => self.fields.insert(i, (tag, value)),
+        }
+    }
+
+    pub fn get(&self, tag: Tag) -> Option<&[u8]> {
+        self.fields
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /// `tag` as exactly `N` bytes
+    fn get_array<const N: usize>(&self, tag: Tag) -> Result<[u8; N], RoughtimeError> {
+        self.get(tag)
+            .and_then(|value| value.try_into().ok())
+            .ok_or(RoughtimeError::Missing(tag))
+    }
+
+    fn get_u32(&self, tag: Tag) -> Result<u32, RoughtimeError> {
+        self.get_array(tag).map(u32::from_le_bytes)
+    }
+
+    fn get_u64(&self, tag: Tag) -> Result<u64, RoughtimeError> {
+        self.get_array(tag).map(u64::from_le_bytes)
+    }
+
+    /// `tag`, itself a message
+    fn get_message(&self, tag: Tag) -> Result<Message, RoughtimeError> {
+        Message::decode(self.get(tag).ok_or(RoughtimeError::Missing(tag))?)
+    }
+
+    /// The number of tags, `n - 1` value offsets, the tags, then the values
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(self.fields.len() as u32).to_le_bytes());
+        let mut offset = 0;
+        for (_, value) in self.fields.iter().take(self.fields.len().saturating_sub(1)) {
+            offset += value.len() as u32;
+            out.extend_from_slice(&offset.to_le_bytes());
+        }
+        for (tag, _) in &self.fields {
+            out.extend_from_slice(tag);
+        }
+        for (_, value) in &self.fields {
+            out.extend_from_slice(value);
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, RoughtimeError> {
+        let word = |i: usize| -> Result<u32, RoughtimeError> {
+            bytes
+                .get(i * 4..i * 4 + 4)
+                .map(|w| u32::from_le_bytes(w.try_into().expect("4 bytes")))
+                .ok_or(RoughtimeError::Malformed("truncated header"))
+        };
+        if !bytes.len().is_multiple_of(4) {
+            return Err(RoughtimeError::Malformed("length is not a multiple of 4"));
+        }
+        let n = word(0)? as usize;
+        if n == 0 {
+            return Ok(Self::default());
+        }
+        let header = n
+            .checked_mul(8)
+            .filter(|&h| h <= bytes.len())
+            .ok_or(RoughtimeError::Malformed("truncated header"))?;
+        let values = &bytes[header..];
+        let mut starts = vec![0];
+        for i in 1..n {
+            starts.push(word(i)? as usize);
+        }
+        starts.push(values.len());
+        let mut fields = Vec::with_capacity(n);
+        for i in 0..n {
+            let tag: Tag = bytes[(n + i) * 4..(n + i) * 4 + 4]
+                .try_into()
+                .expect("4 bytes");
+            if fields
+                .last()
+                .is_some_and(|(last, _)| tag_order(last) >= tag_order(&tag))
+            {
+                return Err(RoughtimeError::Malformed("tags out of order"));
+            }
+            let (start, end) = (starts[i], starts[i + 1]);
+            if start > end || end > values.len() || !start.is_multiple_of(4) {
+                return Err(RoughtimeError::Malformed("bad value offset"));
+            }
+            fields.push((tag, values[start..end].to_vec()));
+        }
+        Ok(Self { fields })
+    }
+}
+
+/// `SHA-512(0x00 || nonce)`, a leaf of the answer's tree
+pub fn leaf_hash(nonce: &[u8]) -> [u8; 64] {
+    let mut hasher = Sha512::new();
+    hasher.update([0u8]);
+    hasher.update(nonce);
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &[u8], right: &[u8]) -> [u8; 64] {
+    let mut hasher = Sha512::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// A request for a timestamp of `nonce`, padded to [`MIN_REQUEST_LEN`]
+pub fn request(nonce: &[u8; NONCE_LEN]) -> Vec<u8> {
+    let mut message = Message::new();
+    message.insert(NONC, nonce.to_vec());
+    // Header of two tags (16 bytes) plus the nonce, then the padding
+    message.insert(PAD, vec![0; MIN_REQUEST_LEN - 16 - NONCE_LEN]);
+    message.encode()
+}
+
+/// The nonce of the next request in a chain: `SHA-512(previous || blind)`
+/// for the previous answer and a fresh random `blind`. Kept with the
+/// answers, the blinds show each request came after the answer before it.
+pub fn chain_nonce(previous: &[u8], blind: &[u8; NONCE_LEN]) -> [u8; NONCE_LEN] {
+    let mut hasher = Sha512::new();
+    hasher.update(previous);
+    hasher.update(blind);
+    hasher.finalize().into()
+}
+
+/// `time` in microseconds since the Unix epoch, as in `MIDP`, `MINT` and
+/// `MAXT`
+pub fn to_micros(time: DateTime<Utc>) -> u64 {
+    time.timestamp_micros().max(0) as u64
+}
+
+fn from_micros(micros: u64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp_micros(i64::try_from(micros).ok()?)
+}
+
+/// `PUBK` of `key`: its uncompressed point without the `0x04` prefix
+fn pubk(key: &PublicKey) -> Vec<u8> {
+    key.verifying_key().to_encoded_point(false).as_bytes()[1..].to_vec()
+}
+
+/// The `DELE` message of a delegation to `online`, the bytes the long-term
+/// key signs after [`DELEGATION_CONTEXT`]
+pub fn delegation(online: &PublicKey, min: DateTime<Utc>, max: DateTime<Utc>) -> Message {
+    let mut dele = Message::new();
+    dele.insert(PUBK, pubk(online));
+    dele.insert(MINT, to_micros(min).to_le_bytes().to_vec());
+    dele.insert(MAXT, to_micros(max).to_le_bytes().to_vec());
+    dele
+}
+
+/// The `CERT` message: `dele` with the long-term key's `signature` over
+/// [`DELEGATION_CONTEXT`] `||` `dele`
+pub fn certificate(dele: &Message, signature: &Signature) -> Message {
+    let mut cert = Message::new();
+    cert.insert(SIG, signature.to_bytes().to_vec());
+    cert.insert(DELE, dele.encode());
+    cert
+}
+
+/// The answer to a request for `nonce`, alone in its tree: `midpoint` ±
+/// `radius` signed with the `online` key that `cert` delegates to
+pub fn response(
+    nonce: &[u8; NONCE_LEN],
+    midpoint: DateTime<Utc>,
+    radius: Duration,
+    online: &KeyPair,
+    cert: &Message,
+) -> Vec<u8> {
+    let radius = u32::try_from(radius.as_micros()).unwrap_or(u32::MAX);
+    let mut srep = Message::new();
+    srep.insert(ROOT, leaf_hash(nonce).to_vec());
+    srep.insert(MIDP, to_micros(midpoint).to_le_bytes().to_vec());
+    srep.insert(RADI, radius.to_le_bytes().to_vec());
+    let srep = srep.encode();
+    let signature = online.sign(&[RESPONSE_CONTEXT, &srep].concat());
+
+    let mut answer = Message::new();
+    answer.insert(SIG, signature.to_bytes().to_vec());
+    answer.insert(PATH, Vec::new());
+    answer.insert(SREP, srep);
+    answer.insert(CERT, cert.encode());
+    answer.insert(INDX, 0u32.to_le_bytes().to_vec());
+    answer.encode()
+}
+
+/// A checked answer: the true time was within `radius` of `midpoint` when
+/// the server answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Verified {
+    pub midpoint: DateTime<Utc>,
+    pub radius: Duration,
+}
+
+impl Verified {
+    fn earliest(&self) -> DateTime<Utc> {
+        self.midpoint - chrono::Duration::from_std(self.radius).unwrap_or_default()
+    }
+
+    fn latest(&self) -> DateTime<Utc> {
+        self.midpoint + chrono::Duration::from_std(self.radius).unwrap_or_default()
+    }
+}
+
+/// Whether `later`, answered to a request chained from `earlier`'s answer,
+/// claims a time wholly before `earlier`'s: then one of the two servers
+/// lied, and the answers with the blinds prove it
+pub fn inconsistent(earlier: &Verified, later: &Verified) -> bool {
+    later.latest() < earlier.earliest()
+}
+
+fn signature_of(message: &Message) -> Result<Signature, RoughtimeError> {
+    let bytes: [u8; 64] = message.get_array(SIG)?;
+    Signature::from_slice(&bytes).map_err(|_| RoughtimeError::Missing(SIG))
+}
+
+/// Checks `response` to a request for `nonce` against the server's
+/// long-term `key` (its `GET /key`)
+pub fn verify_response(
+    response: &[u8],
+    nonce: &[u8; NONCE_LEN],
+    key: &PublicKey,
+) -> Result<Verified, RoughtimeError> {
+    let answer = Message::decode(response)?;
+
+    // 1) The long-term key delegates to the online key
+    let cert = answer.get_message(CERT)?;
+    let dele_bytes = cert.get(DELE).ok_or(RoughtimeError::Missing(DELE))?;
+    if !key.verify(
+        &[DELEGATION_CONTEXT, dele_bytes].concat(),
+        &signature_of(&cert)?,
+    ) {
+        return Err(RoughtimeError::BadDelegation);
+    }
+    let dele = Message::decode(dele_bytes)?;
+    let point: [u8; 64] = dele.get_array(PUBK)?;
+    let online = PublicKey::from_sec1_bytes(&[&[4u8][..], &point].concat())
+        .map_err(|_| RoughtimeError::Missing(PUBK))?;
+
+    // 2) The online key signed `SREP`
+    let srep_bytes = answer.get(SREP).ok_or(RoughtimeError::Missing(SREP))?;
+    if !online.verify(
+        &[RESPONSE_CONTEXT, srep_bytes].concat(),
+        &signature_of(&answer)?,
+    ) {
+        return Err(RoughtimeError::BadSignature);
+    }
+    let srep = Message::decode(srep_bytes)?;
+
+    // 3) Its root covers our nonce
+    let path = answer.get(PATH).ok_or(RoughtimeError::Missing(PATH))?;
+    if !path.len().is_multiple_of(NONCE_LEN) {
+        return Err(RoughtimeError::Missing(PATH));
+    }
+    let mut index = answer.get_u32(INDX)?;
+    let mut hash = leaf_hash(nonce);
+    for sibling in path.chunks(NONCE_LEN) {
+        hash = match index & 1 {
+            0 => node_hash(&hash, sibling),
+            _ => node_hash(sibling, &hash),
+        };
+        index >>= 1;
+    }
+    if index != 0 || srep.get_array::<64>(ROOT)? != hash {
+        return Err(RoughtimeError::NotInTree);
+    }
+
+    // 4) While the delegation held
+    let midpoint = srep.get_u64(MIDP)?;
+    if midpoint < dele.get_u64(MINT)? || midpoint > dele.get_u64(MAXT)? {
+        return Err(RoughtimeError::OutsideDelegation);
+    }
+    Ok(Verified {
+        midpoint: from_micros(midpoint).ok_or(RoughtimeError::Missing(MIDP))?,
+        radius: Duration::from_micros(srep.get_u32(RADI)?.into()),
+    })
+}
+
+/// Sends a request for `nonce` to the Roughtime server at `addr`
+/// (`host:port`) and returns its raw answer, unchecked
+pub fn query(addr: &str, nonce: &[u8; NONCE_LEN], timeout: Duration) -> io::Result<Vec<u8>> {
+    let server = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address"))?;
+    let local = match server {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect(server)?;
+    socket.send(&request(nonce))?;
+    let mut buf = vec![0; 4096];
+    let len = socket.recv(&mut buf)?;
+    buf.truncate(len);
+    Ok(buf)
+}
@@ -0,0 +1,74 @@
+//! Per-tenant timestamp serials
+//!
+//! Each tenant numbers its timestamps from 1. The last serial every tenant
+//! issued is persisted like the time high-water mark, so serials keep
+//! increasing across restarts and the audit log, token claims and
+//! `/timestamp/by-hash` never show one serial on two timestamps.
+
+use crate::clock::replace_file;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// The last serial of every tenant, by name (`""` for the default tenant)
+pub struct Serials {
+    /// `None` keeps the serials in memory only
+    state_file: Option<String>,
+    last: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Serials {
+    /// Resumes from `state_file` if it exists. An empty `state_file` keeps
+    /// the serials in memory only.
+    pub fn new(state_file: &str) -> std::io::Result<Self> {
+        let state_file = (!state_file.is_empty()).then(|| state_file.to_string());
+        let last = match &state_file {
+            Some(path) if fs::exists(path)? => serde_json::from_str(&fs::read_to_string(path)?)
+                .map_err(|e| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Invalid serials in {}: {}", path, e),
+                    )
+                })?,
+            _ => BTreeMap::new(),
+        };
+        Ok(Self {
+            state_file,
+            last: Mutex::new(last),
+        })
+    }
+
+    /// Serial of the most recent timestamp of `tenant` (0 = none yet)
+    pub fn last(&self, tenant: Option<&str>) -> u64 {
+        let last = self.last.lock().unwrap();
+        last.get(tenant.unwrap_or_default()).copied().unwrap_or(0)
+    }
+
+    /// Makes sure `tenant` continues after `serial` (e.g. the last one in
+    /// the audit log), without persisting it yet
+    pub fn resume_after(&self, tenant: Option<&str>, serial: u64) {
+        let mut last = self.last.lock().unwrap();
+        let entry = last
+            .entry(tenant.unwrap_or_default().to_string())
+            .or_default();
+        *entry = (*entry).max(serial);
+    }
+
+    /// The next serial of `tenant`. It is persisted before this returns, so
+    /// a serial is only handed out once; if that fails, nothing is.
+    pub fn next(&self, tenant: Option<&str>) -> std::io::Result<u64> {
+        let mut last = self.last.lock().unwrap();
+        let name = tenant.unwrap_or_default();
+        let previous = last.get(name).copied().unwrap_or(0);
+        last.insert(name.to_string(), previous + 1);
+        if let Some(path) = &self.state_file {
+            let json = serde_json::to_string_pretty(&*last).expect("serials serialize");
+            if let Err(e) = replace_file(Path::new(path), json.as_bytes()) {
+                last.insert(name.to_string(), previous);
+                return Err(e);
+            }
+        }
+        Ok(previous + 1)
+    }
+}
@@ -1,49 +1,1657 @@
+use crate::alg::{DigestAlg, HashAlg, SignatureAlg};
+use crate::audit::{
+    AuditEntry, AuditLog, AuditRecord, CompactedProof, ConsistencyProof, Cosignature, DayProof,
+    DayRoot, InclusionProof, TreeHead,
+};
+use crate::clock::{Clock, MonotonicClock, SystemClock};
+use crate::config::{
+    CorsConfig, DEFAULT_POLICY, DuplicatePolicy, KeyFiles, KeyValidity, MessageLogging,
+    PolicyConfig, RevokedKey, ServerConfig, SignCacheConfig, TenantKeys, load_or_generate_keys_at,
+};
+use crate::cose::{self, CoseClaims};
+use crate::jws::{self, JwsClaims};
+use crate::key_stats::{KeyStats, KeyUsage, RecordError};
+use crate::ntp::{DriftMonitor, DriftStatus};
+use crate::revocation;
+use crate::serials::Serials;
+use crate::signer::{Signer, TenantSigners, key_pair_signer};
+use crate::transparency::AnchorProof;
+use crate::wire::{self, Normalization, TimePrecision};
+use crate::x509;
+use crate::{ApiError, SignedTime};
 use axum::{
-    Router,
-    extract::Json,
-    http::StatusCode,
-    response::{IntoResponse, Json as JsonResponse},
+    Extension, Router, async_trait,
+    body::Body,
+    extract::{
+        ConnectInfo, DefaultBodyLimit, FromRequestParts, Json, Path, Query, Request, State,
+        rejection::JsonRejection,
+    },
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header, request::Parts},
+    middleware::{self, Next},
+    response::{
+        IntoResponse, Json as JsonResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
     routing::{get, post},
 };
 use base64::{Engine as _, engine::general_purpose};
-use chrono::Utc;
-use ecdsa_lib::KeyPair; // your library's KeyPair
+use chrono::{DateTime, NaiveDate, Utc};
+use ecdsa_lib::{KeyPair, PublicKey}; // your library's KeyPair
 use k256::ecdsa::Signature; // the Signature type
+use limits::RequestLimits;
+pub use listener::{Listener, UNIX_PREFIX};
+use pool::{PoolError, SignPool};
 use serde::{Deserialize, Serialize};
-use std::fs;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
 use std::net::SocketAddr;
-use tracing::{error, info};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+use zeroize::Zeroizing;
+
+mod admin;
+mod anchor;
+#[cfg(feature = "s3")]
+mod archive;
+#[cfg(feature = "client")]
+mod gossip;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod latency;
+mod limits;
+mod listener;
+#[cfg(feature = "client")]
+mod mirror;
+mod openapi;
+mod pool;
+mod response_sig;
+mod retention;
+#[cfg(feature = "roughtime")]
+mod roughtime;
+#[cfg(feature = "otlp")]
+mod trace;
+
+/// Longest `nonce` `GET /time` signs
+const MAX_TIME_NONCE_LEN: usize = 128;
+
+/// How many issued entries a slow `/log/stream` subscriber may fall behind
+/// before it starts missing entries.
+const LOG_STREAM_CAPACITY: usize = 1024;
 
 /// Body returned by GET /key
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct KeyResponse {
     request: &'static str,
-    #[serde(rename = "time-requested")]
-    time_requested: String,
+    #[serde(rename = "time-requested", with = "wire::serde_canonical")]
+    time_requested: DateTime<Utc>,
     #[serde(rename = "public-key")]
     public_key: String,
+    #[serde(skip_serializing_if = "TextFormat::is_base64")]
+    format: TextFormat,
+    #[serde(rename = "signature-alg")]
+    signature_alg: SignatureAlg,
+    #[serde(rename = "hash-alg")]
+    hash_alg: HashAlg,
+    kid: String,
+    /// The key's `key_validity`, when it has one
+    #[serde(rename = "not-before", skip_serializing_if = "Option::is_none")]
+    not_before: Option<String>,
+    #[serde(rename = "not-after", skip_serializing_if = "Option::is_none")]
+    not_after: Option<String>,
+}
+
+/// Body returned by GET /keys: every key the tenant signs with, the
+/// primary (`GET /key`'s) first
+#[derive(Serialize, ToSchema)]
+struct KeysResponse {
+    request: &'static str,
+    keys: Vec<KeyResponse>,
+}
+
+/// Body returned by GET /policies
+#[derive(Serialize, ToSchema)]
+struct PoliciesResponse {
+    request: &'static str,
+    policies: Vec<PolicyResponse>,
+}
+
+/// One of the `[policies]`
+#[derive(Serialize, ToSchema)]
+struct PolicyResponse {
+    name: String,
+    description: String,
+    #[serde(rename = "require-audit")]
+    require_audit: bool,
+    #[serde(rename = "require-ntp")]
+    require_ntp: bool,
+}
+
+/// Body returned by GET /version
+#[derive(Serialize, ToSchema)]
+struct VersionResponse {
+    request: &'static str,
+    #[serde(rename = "server-version")]
+    server_version: &'static str,
+    #[serde(rename = "protocol-versions")]
+    protocol_versions: &'static [&'static str],
+    features: Vec<&'static str>,
+    #[serde(rename = "time-precision")]
+    time_precision: TimePrecision,
+}
+
+/// Body returned by GET /key/stats
+#[derive(Serialize, ToSchema)]
+struct KeyStatsResponse {
+    request: &'static str,
+    #[serde(rename = "key-id")]
+    key_id: String,
+    #[serde(flatten)]
+    usage: KeyUsage,
+    /// `None` when unlimited
+    #[serde(rename = "max-signatures")]
+    max_signatures: Option<u64>,
+    /// Signatures left before the key must be rotated
+    remaining: Option<u64>,
 }
 
 /// Body returned by POST /sign
-#[derive(Serialize)]
-struct SignResponse {
+#[derive(Serialize, ToSchema)]
+struct SignResponse<'a> {
+    request: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'a str>,
+    /// A binary message, in place of `message`
+    #[serde(rename = "message-b64", skip_serializing_if = "Option::is_none")]
+    message_b64: Option<String>,
+    #[serde(rename = "time-signed", with = "wire::serde_canonical")]
+    time_signed: DateTime<Utc>,
+    signature: &'a str,
+    /// Clock error bound from the NTP drift check (not covered by the signature)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    accuracy: Option<String>,
+    /// Only present when the client asked for something other than raw
+    #[serde(skip_serializing_if = "SignatureEncoding::is_raw")]
+    encoding: SignatureEncoding,
+    /// Only present when the client asked for hex
+    #[serde(skip_serializing_if = "TextFormat::is_base64")]
+    format: TextFormat,
+    #[serde(rename = "signature-alg")]
+    signature_alg: SignatureAlg,
+    #[serde(rename = "hash-alg")]
+    hash_alg: HashAlg,
+    /// Id of the key that signed, as in `GET /keys`
+    kid: &'a str,
+    /// `[policies]` name it was issued under; signed with `time-signed`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    policy: Option<&'a str>,
+    /// Set on a timestamp issued earlier for the same message, handed out
+    /// again under `duplicates = "first-seen"`
+    #[serde(rename = "first-seen", skip_serializing_if = "std::ops::Not::not")]
+    first_seen: bool,
+}
+
+/// Body returned by POST /sign/preview
+#[derive(Serialize, ToSchema)]
+struct PreviewResponse<'a> {
+    request: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'a str>,
+    #[serde(rename = "message-b64", skip_serializing_if = "Option::is_none")]
+    message_b64: Option<String>,
+    /// The time a signature issued now would carry
+    #[serde(rename = "time-signed", with = "wire::serde_canonical")]
+    time_signed: DateTime<Utc>,
+    /// `json`, `jws` or `cose`: which bytes `signed-data` is
+    token: &'static str,
+    /// Exactly the bytes that would be signed, Base64 or hex
+    #[serde(rename = "signed-data")]
+    signed_data: String,
+    /// `signed-data` as text, except for COSE (CBOR)
+    #[serde(rename = "signed-text", skip_serializing_if = "Option::is_none")]
+    signed_text: Option<String>,
+    /// Only present when the client asked for hex
+    #[serde(skip_serializing_if = "TextFormat::is_base64")]
+    format: TextFormat,
+    #[serde(rename = "signature-alg")]
+    signature_alg: SignatureAlg,
+    #[serde(rename = "hash-alg")]
+    hash_alg: HashAlg,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    policy: Option<String>,
+}
+
+/// Body returned by GET /timestamp/by-hash/{sha256}
+#[derive(Serialize, ToSchema)]
+struct LookupResponse {
+    request: &'static str,
+    /// Hex SHA-256 that was looked up
+    hash: String,
+    /// Oldest first; empty if the message was never timestamped
+    timestamps: Vec<FoundTimestamp>,
+    /// Older timestamps compacted out of the audit log (`[retention]`):
+    /// without their signatures, each proven by the signed checkpoint
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    compacted: Vec<CompactedProof>,
+}
+
+/// Body returned by GET /log/day/{date}
+#[derive(Serialize, ToSchema)]
+struct DayResponse {
     request: &'static str,
-    message: String,
+    #[serde(flatten)]
+    root: DayRoot,
+    /// With `?seq=N`: where that line sits in the day's tree
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proof: Option<DayProof>,
+}
+
+/// Body returned by GET /log/root
+#[derive(Serialize, ToSchema)]
+struct RootResponse {
+    request: &'static str,
+    #[serde(flatten)]
+    head: TreeHead,
+    /// The gossip peers' latest co-signatures of this log, one per peer
+    /// (each of some head up to this one)
+    cosignatures: Vec<Cosignature>,
+    /// This server's latest co-signatures of its peers' heads
+    witnessed: Vec<Cosignature>,
+}
+
+/// Body returned by GET /log/consistency
+#[derive(Serialize, ToSchema)]
+struct ConsistencyResponse {
+    request: &'static str,
+    #[serde(flatten)]
+    proof: ConsistencyProof,
+}
+
+/// Body returned by GET /log/proof
+#[derive(Serialize, ToSchema)]
+struct InclusionResponse {
+    request: &'static str,
+    #[serde(flatten)]
+    proof: InclusionProof,
+}
+
+/// Body returned by GET /anchor/{serial}
+#[derive(Serialize, ToSchema)]
+struct AnchorResponse {
+    request: &'static str,
+    #[serde(flatten)]
+    proof: AnchorProof,
+}
+
+/// One timestamp found by hash
+#[derive(Serialize, ToSchema)]
+struct FoundTimestamp {
+    serial: u64,
     #[serde(rename = "time-signed")]
     time_signed: String,
-    signature: String,
+    /// The audit log `type`, e.g. `sign` or `renew_jws`
+    #[serde(rename = "type")]
+    kind: String,
+    /// Base64 signature over `message + time-signed`, whatever token was
+    /// handed out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+    /// `[policies]` name it was issued under, signed along with it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    policy: Option<String>,
+    /// The audit log line recording it, signed with the default tenant's key
+    proof: AuditEntry,
 }
 
-/// Body for POST /sign requests
-#[derive(Deserialize)]
+/// Body for POST /sign requests: a text `message`, or binary bytes as
+/// `message-b64` (see `Message`)
+#[derive(Deserialize, ToSchema)]
 struct SignRequest {
-    message: String,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(rename = "message-b64", default)]
+    message_b64: Option<String>,
+    #[serde(default)]
+    encoding: SignatureEncoding,
+    #[serde(default)]
+    format: TextFormat,
+    /// Algorithms the client insists on (any supported one if absent)
+    #[serde(flatten)]
+    algorithms: AlgorithmRequest,
+    /// Key to sign with, by id (`GET /keys`); the primary key if absent
+    #[serde(default)]
+    kid: Option<String>,
+    /// `[policies]` name to issue under (`GET /policies`); `default` if
+    /// absent and configured
+    #[serde(default)]
+    policy: Option<String>,
+    /// Hex digest of content the client hashed itself, in place of
+    /// `message` (see `digest_message`)
+    #[serde(default)]
+    digest: Option<String>,
+    /// What `digest` was hashed with, `sha-256` if absent
+    #[serde(rename = "digest-alg", default)]
+    #[schema(value_type = Option<DigestAlg>)]
+    digest_alg: Option<String>,
+}
+
+/// Body for POST /renew requests: the token to timestamp again
+#[derive(Deserialize, ToSchema)]
+struct RenewRequest {
+    token: String,
+    #[serde(default)]
+    encoding: SignatureEncoding,
+    #[serde(default)]
+    format: TextFormat,
+    #[serde(flatten)]
+    algorithms: AlgorithmRequest,
+    #[serde(default)]
+    kid: Option<String>,
+    #[serde(default)]
+    policy: Option<String>,
+}
+
+/// What a request timestamps. Either way the signature covers the bytes
+/// (the UTF-8 of a text message) followed by `time-signed`, and their
+/// SHA-256 is the message hash of JWS and COSE tokens and the audit log.
+enum Message {
+    Text(String),
+    /// Decoded from `message-b64`
+    Binary(Vec<u8>),
+}
+
+impl Message {
+    /// Exactly one of `message`, `message-b64` and the message of a
+    /// `digest` (see `digest_message`); anything else is `422 invalid_json`
+    fn from_request(
+        text: Option<String>,
+        b64: Option<String>,
+        digest: Option<String>,
+    ) -> Result<Self, ApiError> {
+        match (text, b64, digest) {
+            (Some(text), None, None) | (None, None, Some(text)) => Ok(Message::Text(text)),
+            (None, Some(b64), None) => general_purpose::STANDARD
+                .decode(b64.trim())
+                .map(Message::Binary)
+                .map_err(|_| {
+                    invalid_field("message-b64", "invalid value for 'message-b64': not Base64")
+                }),
+            (None, None, None) => Err(invalid_field("message", "missing field 'message'")),
+            (_, _, Some(_)) => Err(invalid_field(
+                "digest",
+                "give 'digest' instead of 'message' or 'message-b64', not with them",
+            )),
+            (Some(_), Some(_), None) => Err(invalid_field(
+                "message-b64",
+                "give either 'message' or 'message-b64', not both",
+            )),
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Message::Text(text) => text.as_bytes(),
+            Message::Binary(bytes) => bytes,
+        }
+    }
+
+    /// `message` and `message-b64` of a response
+    fn fields(&self) -> (Option<&str>, Option<String>) {
+        match self {
+            Message::Text(text) => (Some(text), None),
+            Message::Binary(bytes) => (None, Some(general_purpose::STANDARD.encode(bytes))),
+        }
+    }
+}
+
+/// `400 plaintext_not_allowed`, for a message `privacy_mode` won't see
+fn plaintext_not_allowed() -> ApiError {
+    ApiError::new(
+        StatusCode::BAD_REQUEST,
+        "plaintext_not_allowed",
+        "privacy_mode is on: send 'digest', the hash of the message, not the message",
+    )
+    .for_field(Some("message".to_string()))
+}
+
+/// `422 invalid_json`, blaming `field`
+fn invalid_field(field: &str, message: &str) -> ApiError {
+    ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_json", message)
+        .for_field(Some(field.to_string()))
+}
+
+/// The message of a hash-then-sign request, `<digest-alg>:<digest>` in
+/// lowercase hex (see `DigestAlg::message`), or `None` without a `digest`.
+/// `400 unsupported_algorithm` for an algorithm `digest_algs` doesn't
+/// allow, `422 invalid_json` for a digest that isn't hex of its length.
+fn digest_message(
+    shared: &Shared,
+    digest: Option<String>,
+    alg: Option<String>,
+) -> Result<Option<String>, ApiError> {
+    let Some(digest) = digest else {
+        return match alg {
+            Some(_) => Err(invalid_field("digest-alg", "'digest-alg' without 'digest'")),
+            None => Ok(None),
+        };
+    };
+    let name = alg.as_deref().unwrap_or(DigestAlg::default().name());
+    let Some(alg) = DigestAlg::from_name(name).filter(|alg| shared.digest_algs.contains(alg))
+    else {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "unsupported_algorithm",
+            format!("Unsupported algorithm: {}", name),
+        )
+        .for_field(Some("digest-alg".to_string())));
+    };
+    match hex::decode(digest.trim()) {
+        Ok(bytes) if bytes.len() == alg.digest_len() => Ok(Some(alg.message(&bytes))),
+        _ => Err(invalid_field(
+            "digest",
+            &format!(
+                "'digest' must be {} hex bytes of {}",
+                alg.digest_len(),
+                name
+            ),
+        )),
+    }
+}
+
+/// Puts a text message in the form `policy` signs (see `wire::Normalization`)
+fn normalize(text: &mut String, policy: Normalization) {
+    if let Cow::Owned(normalized) = policy.apply(text) {
+        *text = normalized;
+    }
+}
+
+/// For the logs: the text, or how many bytes a binary message has
+impl std::fmt::Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Message::Text(text) => f.write_str(text),
+            Message::Binary(bytes) => write!(f, "({} binary bytes)", bytes.len()),
+        }
+    }
+}
+
+/// `signature-alg` / `hash-alg` of a request, by name so that an unknown
+/// one is a clear `400` rather than a JSON error
+#[derive(Default, Deserialize, ToSchema)]
+struct AlgorithmRequest {
+    #[serde(rename = "signature-alg")]
+    #[schema(value_type = Option<SignatureAlg>)]
+    signature_alg: Option<String>,
+    #[serde(rename = "hash-alg")]
+    #[schema(value_type = Option<HashAlg>)]
+    hash_alg: Option<String>,
+}
+
+impl AlgorithmRequest {
+    /// The first requested algorithm the server doesn't sign with
+    fn unsupported(&self) -> Option<&str> {
+        let signature_alg = self.signature_alg.as_deref();
+        let hash_alg = self.hash_alg.as_deref();
+        signature_alg
+            .filter(|name| SignatureAlg::from_name(name) != Some(SignatureAlg::default()))
+            .or(hash_alg.filter(|name| HashAlg::from_name(name) != Some(HashAlg::default())))
+    }
+}
+
+/// Which endpoint a signing request came in on
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SignKind {
+    /// `POST /sign`: `message` is the client's message
+    Sign,
+    /// `POST /renew`: `message` is an earlier token
+    Renew,
+}
+
+impl SignKind {
+    /// `request` field of the JSON response
+    fn request(&self) -> &'static str {
+        match self {
+            SignKind::Sign => "POST",
+            SignKind::Renew => "RENEW",
+        }
+    }
+
+    /// Route, for log lines
+    fn path(&self) -> &'static str {
+        match self {
+            SignKind::Sign => "sign",
+            SignKind::Renew => "renew",
+        }
+    }
+
+    /// `type` of the audit log line
+    fn audit_kind(&self, format: ResponseFormat) -> &'static str {
+        match (self, format) {
+            (SignKind::Sign, ResponseFormat::Json) => "sign",
+            (SignKind::Sign, ResponseFormat::Jws) => "sign_jws",
+            (SignKind::Sign, ResponseFormat::Cose) => "sign_cose",
+            (SignKind::Renew, ResponseFormat::Json) => "renew",
+            (SignKind::Renew, ResponseFormat::Jws) => "renew_jws",
+            (SignKind::Renew, ResponseFormat::Cose) => "renew_cose",
+        }
+    }
+}
+
+/// How binary fields (`public-key`, `signature`) are written in JSON bodies:
+/// `{"format": "hex"}` in a `/sign` body, or `?format=hex` on `/key` and `/sign`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum TextFormat {
+    #[default]
+    Base64,
+    /// Lowercase hex, for tools that trip over Base64 padding
+    Hex,
+}
+
+impl TextFormat {
+    fn is_base64(&self) -> bool {
+        *self == TextFormat::Base64
+    }
+
+    /// `?format=hex` overrides the default
+    fn from_query(query: &HashMap<String, String>) -> Option<Self> {
+        query
+            .get("format")
+            .filter(|f| *f == "hex")
+            .map(|_| TextFormat::Hex)
+    }
+
+    fn encode(&self, bytes: &[u8]) -> String {
+        match self {
+            TextFormat::Base64 => general_purpose::STANDARD.encode(bytes),
+            TextFormat::Hex => hex::encode(bytes),
+        }
+    }
+}
+
+/// How the `signature` field is encoded (before Base64)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum SignatureEncoding {
+    /// Fixed-size `r || s` (64 bytes), also accepted as "compact"
+    #[default]
+    #[serde(alias = "compact")]
+    Raw,
+    /// ASN.1 DER, for OpenSSL interop
+    Der,
+}
+
+impl SignatureEncoding {
+    fn is_raw(&self) -> bool {
+        *self == SignatureEncoding::Raw
+    }
+
+    fn encode(&self, signature: &Signature) -> Vec<u8> {
+        match self {
+            SignatureEncoding::Raw => signature.to_vec(),
+            SignatureEncoding::Der => KeyPair::signature_to_der(signature),
+        }
+    }
+}
+
+/// What `POST /sign` returns on success
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ResponseFormat {
+    /// The `SignResponse` JSON body
+    Json,
+    /// A compact JWS token (`application/jose`), see `crate::jws`
+    Jws,
+    /// A COSE_Sign1 token (`application/cose`), see `crate::cose`
+    Cose,
+}
+
+impl ResponseFormat {
+    /// `token` field of a preview
+    fn name(&self) -> &'static str {
+        match self {
+            ResponseFormat::Json => "json",
+            ResponseFormat::Jws => "jws",
+            ResponseFormat::Cose => "cose",
+        }
+    }
+
+    /// `?format=jws|cose`, else the first token media type named in `Accept`
+    fn negotiate(headers: &HeaderMap, query: &HashMap<String, String>) -> Self {
+        match query.get("format").map(String::as_str) {
+            Some("jws") => return ResponseFormat::Jws,
+            Some("cose") => return ResponseFormat::Cose,
+            _ => {}
+        }
+        headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .find_map(|t| match t.split(';').next().unwrap_or("").trim() {
+                "application/jose" => Some(ResponseFormat::Jws),
+                "application/cose" => Some(ResponseFormat::Cose),
+                _ => None,
+            })
+            .unwrap_or(ResponseFormat::Json)
+    }
+}
+
+/// A successful `/sign` response, as issued (and possibly cached)
+#[derive(Clone, Debug, PartialEq)]
+enum Issued {
+    /// The serialized `SignResponse`
+    Json(Vec<u8>),
+    Jws(String),
+    Cose(Vec<u8>),
+}
+
+impl IntoResponse for Issued {
+    fn into_response(self) -> Response {
+        match self {
+            Issued::Json(body) => (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, "application/json")],
+                body,
+            )
+                .into_response(),
+            Issued::Jws(token) => (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, jws::CONTENT_TYPE)],
+                token,
+            )
+                .into_response(),
+            Issued::Cose(token) => (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, cose::CONTENT_TYPE)],
+                token,
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// One issued timestamp, as pushed to `GET /log/stream` subscribers
+#[derive(Clone, Serialize, ToSchema)]
+struct LogEntry {
+    serial: u64,
+    /// Hex-encoded SHA-256 of the signed message
+    hash: String,
+    #[serde(rename = "time-signed", with = "wire::serde_canonical")]
+    time_signed: DateTime<Utc>,
+    signature: String,
+}
+
+/// Header naming the tenant when the `/t/{tenant}` prefix isn't used
+pub const TENANT_HEADER: &str = "x-tenant";
+
+/// Room for the JSON around the message in a `/sign` body (the other
+/// fields, quoting and a reasonable amount of escaping)
+const SIGN_BODY_OVERHEAD: usize = 4096;
+
+/// Largest `/sign` body: a `max_message_bytes` message as `message-b64`
+/// (a third larger than as text), plus `SIGN_BODY_OVERHEAD`
+fn sign_body_limit(max_message_bytes: usize) -> usize {
+    max_message_bytes
+        .div_ceil(3)
+        .saturating_mul(4)
+        .saturating_add(SIGN_BODY_OVERHEAD)
+}
+
+/// Request id header, echoed back (or generated) on every response
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Set to `true` on a `/sign` response carrying a first-seen timestamp
+/// (the JSON body also says so in `first-seen`; tokens can't)
+pub const FIRST_SEEN_HEADER: &str = "x-first-seen";
+
+/// Header a client sets to make retries of the same `/sign` request safe
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// A `/sign` response that may be handed out again
+struct CachedTimestamp {
+    issued: Instant,
+    /// Hex SHA-256 of the message it was issued for
+    hash: String,
+    response: Issued,
+}
+
+/// Recently issued timestamps, keyed by `Idempotency-Key` (and, if
+/// `by_message_hash` is set, by message hash), per `[sign_cache]`
+struct SignCache {
+    window: Duration,
+    by_message_hash: bool,
+    entries: Mutex<HashMap<String, CachedTimestamp>>,
+}
+
+/// What the cache says about an incoming `/sign` request
+enum CacheLookup {
+    Hit(Issued),
+    /// The idempotency key was already used for a different message
+    Conflict,
+    Miss,
+}
+
+impl SignCache {
+    fn new(config: &SignCacheConfig) -> Self {
+        Self {
+            window: Duration::from_secs(config.window_secs),
+            by_message_hash: config.by_message_hash,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Cache key for a request signed by key `kid` (followed by `/` and
+    /// the policy, if any), or `None` if it must not be cached
+    fn key_for(
+        &self,
+        idempotency_key: Option<&str>,
+        hash: &str,
+        kid: &str,
+        encoding: SignatureEncoding,
+        format: ResponseFormat,
+        text: TextFormat,
+    ) -> Option<String> {
+        if self.window.is_zero() {
+            return None;
+        }
+        match idempotency_key {
+            Some(k) => Some(format!("idem:{}:{}:{:?}", k, kid, format)),
+            None if self.by_message_hash => Some(format!(
+                "hash:{}:{}:{:?}:{:?}:{:?}",
+                hash, kid, encoding, format, text
+            )),
+            None => None,
+        }
+    }
+
+    fn lookup(&self, key: &str, hash: &str) -> CacheLookup {
+        let entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(c) if c.issued.elapsed() < self.window => {
+                if c.hash == hash {
+                    CacheLookup::Hit(c.response.clone())
+                } else {
+                    CacheLookup::Conflict
+                }
+            }
+            _ => CacheLookup::Miss,
+        }
+    }
+
+    /// Forgets every cached response (their signatures are by a key the
+    /// tenant no longer has)
+    fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Stores a freshly issued response and returns the one to send: if a
+    /// concurrent request for the same key won the race, its response is kept.
+    fn insert(&self, key: String, hash: String, response: Issued) -> Issued {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, c| c.issued.elapsed() < self.window);
+        entries
+            .entry(key)
+            .or_insert(CachedTimestamp {
+                issued: Instant::now(),
+                hash,
+                response,
+            })
+            .response
+            .clone()
+    }
+}
+
+/// A tenant's signer and what is derived from its key, replaced as a
+/// whole by `POST /admin/rotate-key`
+struct TenantKey {
+    /// Built once (a file-based `KeyPair` is wiped when the key is dropped)
+    signer: Box<dyn Signer>,
+    public_key: PublicKey,
+    /// RFC 7638 thumbprint of `public_key`, the `kid` of JWS and COSE tokens
+    thumbprint: [u8; 32],
+    /// `thumbprint` in Base64url, as in JWS headers and `GET /key/stats`
+    key_id: String,
+    /// When it may sign (`key_validity`)
+    validity: KeyValidity,
+}
+
+impl TenantKey {
+    fn new(signer: Box<dyn Signer>, validity: KeyValidity) -> Self {
+        let public_key = signer.public_key();
+        Self {
+            signer,
+            thumbprint: jws::key_thumbprint(&public_key),
+            key_id: jws::key_id(&public_key),
+            public_key,
+            validity,
+        }
+    }
+
+    /// Ends the validity window at the key's `compromised_at` if it is
+    /// among `revoked`
+    fn revoked(mut self, revoked: &[RevokedKey]) -> Self {
+        let compromised_at = revoked
+            .iter()
+            .filter(|r| r.kid == self.key_id)
+            .map(|r| r.compromised_at)
+            .min();
+        if let Some(compromised_at) = compromised_at {
+            self.validity.not_after = Some(match self.validity.not_after {
+                Some(not_after) => not_after.min(compromised_at),
+                None => compromised_at,
+            });
+        }
+        self
+    }
+
+    /// Signs `input`, refusing any signature that doesn't verify against
+    /// the served public key
+    fn sign(&self, input: &[u8], now: DateTime<Utc>) -> Result<Signature, SignError> {
+        match self.signer.sign(input) {
+            Ok(signature) if self.public_key.verify(input, &signature) => Ok(signature),
+            // Never hand out a signature clients can't verify: this means the
+            // served public key is not the signing key's
+            Ok(_) => {
+                error!(
+                    "{} ALERT: signature by key {} failed self-verification; not returned",
+                    now.to_rfc3339(),
+                    self.key_id
+                );
+                Err(SignError::SelfCheck)
+            }
+            Err(e) => {
+                error!(
+                    "{} Signer of key {} failed: {}",
+                    now.to_rfc3339(),
+                    self.key_id,
+                    e
+                );
+                Err(SignError::Signer)
+            }
+        }
+    }
+}
+
+/// Keys and log of one tenant
+struct Tenant {
+    /// `None` for the default tenant served on the un-prefixed routes
+    name: Option<String>,
+    /// The current (primary) key; a request signs with one snapshot of it
+    /// throughout
+    key: RwLock<Arc<TenantKey>>,
+    /// The `extra_keys`, signing only when a request names them by `kid`;
+    /// not rotated with the primary
+    extra_keys: Vec<Arc<TenantKey>>,
+    /// Keys it no longer signs with: the `retired_public_key_files`, then
+    /// each primary key rotated out since startup
+    retired: Mutex<Vec<PublicKey>>,
+    /// Where a rotated key is written (`None` for keys that aren't files:
+    /// `[kms]`, a mirror's)
+    key_files: Option<(String, String)>,
+    /// DER certificate from `cert_file`, served at `GET /key/cert`
+    cert: Option<Vec<u8>>,
+    log_tx: broadcast::Sender<LogEntry>,
+    cache: SignCache,
+    shared: Arc<Shared>,
+}
+
+/// Server-wide state every tenant refers to
+struct Shared {
+    /// One clock for all tenants, so `time-signed` is monotonic server-wide
+    clock: Arc<MonotonicClock>,
+    /// `None` when `[ntp]` lists no servers
+    drift: Option<Arc<DriftMonitor>>,
+    /// Signature counters, by key id (tenants may share a key)
+    key_stats: KeyStats,
+    /// The last serial of every tenant
+    serials: Serials,
+    /// `max_message_bytes` from the config
+    max_message_bytes: usize,
+    /// `None` when `audit_file` is empty
+    audit: Option<AuditLog>,
+    /// `duplicates` from the config (`FirstSeen` only with `audit`)
+    duplicates: DuplicatePolicy,
+    normalization: Normalization,
+    /// `privacy_mode` from the config
+    privacy_mode: bool,
+    /// `[logging] messages`, at least `hash` in `privacy_mode`
+    message_logging: MessageLogging,
+    /// `[logging] sample_rate`
+    log_sample_rate: f64,
+    /// Lines `sampled` has been asked about
+    log_lines: AtomicU64,
+    /// `revoked_keys` from the config, served at `GET /revocations`
+    revoked_keys: Vec<RevokedKey>,
+    /// `[policies]` from the config, served at `GET /policies`
+    policies: BTreeMap<String, PolicyConfig>,
+    /// `digest_algs` from the config
+    digest_algs: Vec<DigestAlg>,
+    /// `[mirror] upstream` when this is a read-only mirror
+    mirror: Option<String>,
+    /// Set by `POST /admin/pause-signing`
+    paused: AtomicBool,
+    /// `[retention] full_days` (0 = never compact)
+    full_days: u64,
+    /// Last `seq` sealed into `[archive]`, when there is one: compaction
+    /// doesn't go past it
+    archived: Option<AtomicU64>,
+    /// `[gossip]` peers' co-signatures of this log, by peer key id
+    cosignatures: Mutex<BTreeMap<String, Cosignature>>,
+    /// This log's co-signatures of the peers' heads, by peer key id
+    witnessed: Mutex<BTreeMap<String, Cosignature>>,
+    /// The heads `[anchor]` had witnessed, when there is a witness
+    anchors: Option<anchor::Anchors>,
+    /// Where `issue` runs, off the async executor
+    pool: SignPool,
+}
+
+impl Shared {
+    /// Whether `privacy_mode` refuses to sign `message`: anything but the
+    /// message of a digest (`<digest-alg>:<hex>`, see `digest_message`)
+    fn refuses_plaintext(&self, message: &[u8]) -> bool {
+        self.privacy_mode
+            && !std::str::from_utf8(message)
+                .ok()
+                .and_then(DigestAlg::parse_message)
+                .is_some_and(|(alg, _)| self.digest_algs.contains(&alg))
+    }
+
+    /// `message` as a log line shows it, by `[logging] messages`: quoted,
+    /// only its SHA-256, or not at all
+    fn logged(&self, shown: &(impl std::fmt::Display + ?Sized), message: &[u8]) -> String {
+        match self.message_logging {
+            MessageLogging::Full => format!("message='{}'", shown),
+            MessageLogging::Hash => format!("hash={}", hex::encode(Sha256::digest(message))),
+            MessageLogging::Omit => "message=<omitted>".to_string(),
+        }
+    }
+
+    /// A signature as a log line shows it: only with `[logging] messages`
+    /// at `full`
+    fn logged_sig(&self, sig: &str) -> String {
+        match self.message_logging {
+            MessageLogging::Full => format!("sig='{}'", sig),
+            MessageLogging::Hash | MessageLogging::Omit => "sig=<omitted>".to_string(),
+        }
+    }
+
+    /// Whether to write the next line of a successful request carrying a
+    /// message: `sample_rate` of them, spread evenly
+    /// (`floor(n * rate)` steps once per line written)
+    fn sampled(&self) -> bool {
+        let rate = self.log_sample_rate;
+        if rate >= 1.0 {
+            return true;
+        }
+        let n = self.log_lines.fetch_add(1, Ordering::Relaxed) as f64;
+        ((n + 1.0) * rate).floor() > (n * rate).floor()
+    }
+
+    /// Merkle root of the audit log's UTC day before `time`, for the
+    /// `day_root` claim of a token issued then
+    fn day_root(&self, time: DateTime<Utc>) -> Option<[u8; 32]> {
+        self.audit
+            .as_ref()?
+            .day_root_hash(time.date_naive().pred_opt()?)
+    }
+
+    /// The policy a request is issued under: the one it names, else
+    /// `default` if the config has one. `default` may be named without
+    /// being configured; any other unknown name is `Err`.
+    fn policy(&self, requested: Option<String>) -> Result<Option<String>, String> {
+        match requested {
+            Some(name) if name == DEFAULT_POLICY || self.policies.contains_key(&name) => {
+                Ok(Some(name))
+            }
+            Some(name) => Err(name),
+            None => Ok(self
+                .policies
+                .contains_key(DEFAULT_POLICY)
+                .then(|| DEFAULT_POLICY.to_string())),
+        }
+    }
+}
+
+impl Tenant {
+    /// Fails if the tenant's `cert_file` can't be read or is for another key
+    fn new(
+        name: Option<String>,
+        signer: Box<dyn Signer>,
+        config: &ServerConfig,
+        shared: Arc<Shared>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let (log_tx, _) = broadcast::channel(LOG_STREAM_CAPACITY);
+        let key_files = match &name {
+            _ if shared.mirror.is_some() => None,
+            None if config.kms.is_some() => None,
+            None => Some((
+                config.private_key_file.clone(),
+                config.public_key_file.clone(),
+            )),
+            Some(name) => config.tenants.get(name).map(|t| t.key_files(name)),
+        };
+        let cert_file = match &name {
+            None => Some(config.cert_file.as_str()).filter(|path| !path.is_empty()),
+            Some(name) => config
+                .tenants
+                .get(name)
+                .and_then(|t| t.cert_file.as_deref()),
+        };
+        let cert = match cert_file {
+            Some(path) => Some(load_cert(path, &signer.public_key())?),
+            None => None,
+        };
+        // A mirror signs nothing, with any key
+        let extra_key_files = match &name {
+            _ if shared.mirror.is_some() => &[][..],
+            None => &config.extra_keys[..],
+            Some(name) => config
+                .tenants
+                .get(name)
+                .map_or(&[][..], |t| &t.extra_keys[..]),
+        };
+        let retired_files = match &name {
+            None => &config.retired_public_key_files[..],
+            Some(name) => config
+                .tenants
+                .get(name)
+                .map_or(&[][..], |t| &t.retired_public_key_files[..]),
+        };
+        let retired = retired_files
+            .iter()
+            .map(|path| load_public_key(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let validity = match &name {
+            None => config.key_validity,
+            Some(name) => config
+                .tenants
+                .get(name)
+                .map(|t| t.key_validity)
+                .unwrap_or_default(),
+        };
+        let revoked = &shared.revoked_keys;
+        let primary = TenantKey::new(signer, validity).revoked(revoked);
+        let mut extra_keys: Vec<Arc<TenantKey>> = Vec::new();
+        for files in extra_key_files {
+            let key = TenantKey::new(load_extra_key(files)?, files.validity).revoked(revoked);
+            if key.key_id == primary.key_id || extra_keys.iter().any(|k| k.key_id == key.key_id) {
+                return Err(format!("Key {} is listed twice", files.private_key_file).into());
+            }
+            extra_keys.push(Arc::new(key));
+        }
+        // Like a certificate, a key outside its window only gets a warning:
+        // signing with it is refused until it is replaced
+        let now = Utc::now();
+        for key in std::iter::once(&primary).chain(extra_keys.iter().map(|k| &**k)) {
+            if !key.validity.contains(now) {
+                let bound = |t: Option<DateTime<Utc>>| t.map_or("-".into(), wire::canonical_time);
+                warn!(
+                    "{} Key {} is outside its validity window ({} to {})",
+                    now.to_rfc3339(),
+                    key.key_id,
+                    bound(key.validity.not_before),
+                    bound(key.validity.not_after)
+                );
+            }
+        }
+        // Serials continue after the audit log's, which may be ahead of
+        // `serial_file` (e.g. one kept from before it was)
+        if let Some(audit) = &shared.audit {
+            let tenant = name.as_deref();
+            shared
+                .serials
+                .resume_after(tenant, audit.last_serial(tenant));
+        }
+        Ok(Self {
+            name,
+            key: RwLock::new(Arc::new(primary)),
+            extra_keys,
+            retired: Mutex::new(retired),
+            key_files,
+            cert,
+            log_tx,
+            cache: SignCache::new(&config.sign_cache),
+            shared,
+        })
+    }
+
+    fn key(&self) -> Arc<TenantKey> {
+        self.key.read().unwrap().clone()
+    }
+
+    /// Serial of the most recently issued timestamp (0 = none yet)
+    fn serial(&self) -> u64 {
+        self.shared.serials.last(self.name.as_deref())
+    }
+
+    /// Every key the tenant signs with, the primary first
+    fn keys(&self) -> Vec<Arc<TenantKey>> {
+        let mut keys = vec![self.key()];
+        keys.extend(self.extra_keys.iter().cloned());
+        keys
+    }
+
+    /// The public keys of `keys`, then the retired ones
+    fn verification_keys(&self) -> Vec<PublicKey> {
+        let mut keys: Vec<PublicKey> = self.keys().iter().map(|k| k.public_key).collect();
+        keys.extend(self.retired.lock().unwrap().iter().copied());
+        keys
+    }
+
+    /// The key with id `kid`, or the primary key for `None`
+    fn select_key(&self, kid: Option<&str>) -> Option<Arc<TenantKey>> {
+        match kid {
+            None => Some(self.key()),
+            Some(kid) => self.keys().into_iter().find(|key| key.key_id == kid),
+        }
+    }
+
+    /// Makes `signer` the tenant's key from now on; returns the old one.
+    /// Responses cached under the old key are dropped.
+    fn replace_key(&self, signer: Box<dyn Signer>) -> Arc<TenantKey> {
+        let old = std::mem::replace(
+            &mut *self.key.write().unwrap(),
+            Arc::new(TenantKey::new(signer, KeyValidity::default())),
+        );
+        self.cache.clear();
+        self.retired.lock().unwrap().push(old.public_key);
+        old
+    }
+}
+
+/// Loads (or generates) one of the `extra_keys`
+fn load_extra_key(files: &KeyFiles) -> Result<Box<dyn Signer>, Box<dyn std::error::Error>> {
+    let (private_key, public_key) =
+        load_or_generate_keys_at(&files.private_key_file, &files.public_key_file)?;
+    let private_key = Zeroizing::new(private_key);
+    Ok(Box::new(key_pair_signer(&private_key, &public_key)?))
+}
+
+/// Reads one of the `retired_public_key_files`
+fn load_public_key(path: &str) -> Result<PublicKey, Box<dyn std::error::Error>> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("Failed to read public key {}: {}", path, e))?;
+    PublicKey::from_sec1_bytes(&bytes)
+        .map_err(|_| format!("{} is not a secp256k1 public key", path).into())
+}
+
+/// Reads a PEM or DER certificate and checks that it certifies
+/// `public_key`; one outside its validity window only gets a warning
+fn load_cert(path: &str, public_key: &PublicKey) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("Failed to read certificate {}: {}", path, e))?;
+    let (info, der) = x509::decode(&bytes)
+        .and_then(|der| Some((x509::parse(&der)?, der)))
+        .ok_or_else(|| format!("{} is not a certificate of a secp256k1 key", path))?;
+    if info.public_key != *public_key {
+        return Err(format!("{} certifies a different key than the one signing", path).into());
+    }
+    let now = Utc::now();
+    if !info.is_valid_at(now) {
+        warn!(
+            "{} Certificate {} is only valid from {} to {}",
+            now.to_rfc3339(),
+            path,
+            info.not_before.to_rfc3339(),
+            info.not_after.to_rfc3339()
+        );
+    }
+    Ok(der)
+}
+
+impl Tenant {
+    /// The signing core shared by `POST /sign` and the gRPC service: checks
+    /// the clock, takes the next `time-signed`, signs `message + time-signed`
+    /// and publishes the entry to `/log/stream`. Logs its own failures.
+    ///
+    /// `key` is the tenant's key to sign with (see `select_key`); `policy`
+    /// the `[policies]` name to issue under, signed after `time-signed`
+    /// (see `Shared::policy`); `kind` and `client` are what the audit log
+    /// records about the request.
+    ///
+    /// A mirror (`[mirror] upstream`) refuses with `ReadOnly`.
+    ///
+    /// Under `duplicates = "first-seen"` a message timestamped before gets
+    /// that timestamp back (see `first_seen`) and nothing new is issued.
+    fn issue(
+        &self,
+        message: &[u8],
+        key: Arc<TenantKey>,
+        policy: Option<&str>,
+        encoding: SignatureEncoding,
+        kind: &str,
+        client: Option<SocketAddr>,
+    ) -> Result<IssuedTimestamp, SignError> {
+        let now = self.shared.clock.now();
+        let hash = &hex::encode(Sha256::digest(message));
+
+        if let Some(upstream) = &self.shared.mirror {
+            error!(
+                "{} Refusing to sign: read-only mirror of {}",
+                now.to_rfc3339(),
+                upstream
+            );
+            return Err(SignError::ReadOnly);
+        }
+        if self.shared.paused.load(Ordering::SeqCst) {
+            error!("{} Refusing to sign: signing is paused", now.to_rfc3339());
+            return Err(SignError::Paused);
+        }
+
+        if message.len() > self.shared.max_message_bytes {
+            error!(
+                "{} Refusing to sign: message of {} bytes exceeds {}",
+                now.to_rfc3339(),
+                message.len(),
+                self.shared.max_message_bytes
+            );
+            return Err(SignError::MessageTooLarge);
+        }
+
+        // Renewals are for a new time, so they are never deduplicated
+        if self.shared.duplicates == DuplicatePolicy::FirstSeen
+            && !kind.starts_with("renew")
+            && let Some(first) = self.first_seen(&key, message, hash, policy, encoding)
+        {
+            return Ok(first);
+        }
+
+        // Refuse to sign while the clock is known to be off
+        let accuracy = self.accuracy(now)?;
+        // A policy that promises an NTP-checked clock can't be kept before
+        // the first check
+        let require_ntp = policy
+            .and_then(|name| self.shared.policies.get(name))
+            .is_some_and(|p| p.require_ntp);
+        if require_ntp && accuracy.is_none() {
+            error!(
+                "{} Refusing to sign: policy '{}' needs a clock checked against NTP",
+                now.to_rfc3339(),
+                policy.unwrap_or_default()
+            );
+            return Err(SignError::ClockUnverified);
+        }
+
+        #[cfg(feature = "otlp")]
+        let sign = trace::span("sign");
+        // The issued time comes from the monotonic clock, so it is never earlier
+        // than any timestamp issued before (even across restarts)
+        let time_signed = self.shared.clock.next().map_err(|e| {
+            error!(
+                "{} Failed to persist time high-water mark: {}",
+                now.to_rfc3339(),
+                e
+            );
+            SignError::ClockState
+        })?;
+        if !key.validity.contains(time_signed) {
+            error!(
+                "{} Refusing to sign: key {} is outside its validity window",
+                now.to_rfc3339(),
+                key.key_id
+            );
+            return Err(SignError::KeyExpired);
+        }
+
+        // Count the signature against the key before making it, refusing
+        // once the key is at `[key_stats] max_signatures`
+        let timestamp_str = wire::canonical_time(time_signed);
+        self.record_signature(&key, &timestamp_str, now)?;
+
+        // Sign "message + timestamp" (+ " policy"):
+        // Use the same format that will be serialized to JSON
+        let data_to_sign = wire::policy_signing_input(message, &timestamp_str, policy);
+        let sig = key.sign(&data_to_sign, now)?;
+        let sig_bytes = encoding.encode(&sig);
+
+        let serial = self
+            .shared
+            .serials
+            .next(self.name.as_deref())
+            .map_err(|e| {
+                error!("{} Failed to persist serial: {}", now.to_rfc3339(), e);
+                SignError::Serial
+            })?;
+        let sig_base64 = general_purpose::STANDARD.encode(&sig_bytes);
+        #[cfg(feature = "otlp")]
+        let store = {
+            drop(sign);
+            trace::span("store")
+        };
+
+        // Nothing is handed out that the audit log doesn't have
+        if let Some(audit) = &self.shared.audit {
+            audit
+                .append(AuditRecord {
+                    time: &timestamp_str,
+                    kind,
+                    tenant: self.name.as_deref(),
+                    client: client.map(|addr| addr.ip().to_string()),
+                    hash,
+                    serial,
+                    signature: Some(sig_base64.clone()),
+                    policy,
+                })
+                .map_err(|e| {
+                    error!("{} Failed to write audit log: {}", now.to_rfc3339(), e);
+                    SignError::Audit
+                })?;
+        }
+
+        // Only build the entry if someone is listening
+        if self.log_tx.receiver_count() > 0 {
+            let entry = LogEntry {
+                serial,
+                hash: hash.to_string(),
+                time_signed,
+                signature: sig_base64,
+            };
+            // The last subscriber leaving in between is not an error
+            let _ = self.log_tx.send(entry);
+        }
+        #[cfg(feature = "otlp")]
+        drop(store);
+
+        Ok(IssuedTimestamp {
+            time_signed,
+            signature: sig_bytes,
+            serial,
+            accuracy,
+            first_seen: false,
+            key,
+        })
+    }
+
+    /// The clock's accuracy against NTP, if it was checked; `ClockDrift`
+    /// while it is known to be off
+    fn accuracy(&self, now: DateTime<Utc>) -> Result<Option<String>, SignError> {
+        match self.shared.drift.as_ref().map(|d| d.status()) {
+            Some(DriftStatus::TooLarge(offset)) => {
+                error!(
+                    "{} Refusing to sign: clock is {}ms off NTP",
+                    now.to_rfc3339(),
+                    offset.num_milliseconds()
+                );
+                Err(SignError::ClockDrift)
+            }
+            Some(DriftStatus::Ok(accuracy)) => Ok(Some(accuracy)),
+            Some(DriftStatus::Unknown) | None => Ok(None),
+        }
+    }
+
+    /// Counts a signature made at `time` against `key`, refusing once the
+    /// key is at `[key_stats] max_signatures`
+    fn record_signature(
+        &self,
+        key: &TenantKey,
+        time: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), SignError> {
+        self.shared
+            .key_stats
+            .record(&key.key_id, time)
+            .map_err(|e| match e {
+                RecordError::Exhausted => {
+                    error!(
+                        "{} Refusing to sign: key {} reached its signature limit",
+                        now.to_rfc3339(),
+                        key.key_id
+                    );
+                    SignError::KeyExhausted
+                }
+                RecordError::Io(e) => {
+                    error!("{} Failed to persist key stats: {}", now.to_rfc3339(), e);
+                    SignError::KeyStats
+                }
+            })?;
+        Ok(())
+    }
+
+    /// The clock, signed with the primary key together with the client's
+    /// `nonce` (`GET /time`). Refused when `issue` would refuse for the
+    /// clock or the key. It counts against the key like a signature, but
+    /// gets no serial and no audit log line.
+    fn attest_time(&self, nonce: String) -> Result<SignedTime, SignError> {
+        let now = self.shared.clock.now();
+        if self.shared.mirror.is_some() {
+            return Err(SignError::ReadOnly);
+        }
+        if self.shared.paused.load(Ordering::SeqCst) {
+            return Err(SignError::Paused);
+        }
+        let accuracy = self.accuracy(now)?;
+        let key = self.key();
+        // Truncated to the precision like `issue`'s time, and never earlier
+        // than a timestamp issued before; peeked, since it is not issued
+        let time_signed = self.shared.clock.peek();
+        if !key.validity.contains(time_signed) {
+            return Err(SignError::KeyExpired);
+        }
+        let time = wire::canonical_time(time_signed);
+        self.record_signature(&key, &time, now)?;
+        let mut signed = SignedTime {
+            request: "TIME".to_string(),
+            time,
+            nonce,
+            accuracy,
+            kid: key.key_id.clone(),
+            sig: None,
+        };
+        let signature = key.sign(&signed.signed_bytes(), now)?;
+        signed.sig = Some(general_purpose::STANDARD.encode(KeyPair::signature_to_der(&signature)));
+        Ok(signed)
+    }
+
+    /// The earliest timestamp the tenant issued for `message` (other than a
+    /// renewal) under `policy` whose signature is in the audit log and
+    /// verifies under `key`, in `encoding`. After a key rotation the
+    /// message is timestamped anew.
+    fn first_seen(
+        &self,
+        key: &Arc<TenantKey>,
+        message: &[u8],
+        hash: &str,
+        policy: Option<&str>,
+        encoding: SignatureEncoding,
+    ) -> Option<IssuedTimestamp> {
+        let audit = self.shared.audit.as_ref()?;
+        audit
+            .by_hash(hash)
+            .into_iter()
+            .filter(|entry| entry.tenant == self.name && !entry.kind.starts_with("renew"))
+            .filter(|entry| entry.policy.as_deref() == policy)
+            .find_map(|entry| {
+                let bytes = general_purpose::STANDARD
+                    .decode(entry.signature.as_deref()?)
+                    .ok()?;
+                let signature = Signature::from_slice(&bytes)
+                    .or_else(|_| Signature::from_der(&bytes))
+                    .ok()?;
+                let signed = wire::policy_signing_input(message, &entry.time, policy);
+                if !key.public_key.verify(&signed, &signature) {
+                    return None;
+                }
+                Some(IssuedTimestamp {
+                    time_signed: wire::parse_time(&entry.time).ok()?,
+                    signature: encoding.encode(&signature),
+                    serial: entry.serial,
+                    accuracy: None,
+                    first_seen: true,
+                    key: key.clone(),
+                })
+            })
+    }
+}
+
+/// A freshly signed timestamp, before it is shaped into a response
+struct IssuedTimestamp {
+    time_signed: DateTime<Utc>,
+    /// The signature over `message + time-signed`, in the requested encoding
+    signature: Vec<u8>,
+    serial: u64,
+    accuracy: Option<String>,
+    /// Issued earlier, handed out again under `duplicates = "first-seen"`
+    first_seen: bool,
+    /// The key it was signed with, which signs the token around it too
+    key: Arc<TenantKey>,
+}
+
+/// Why `Tenant::issue` refused to sign
+#[derive(Debug)]
+enum SignError {
+    /// The audit log couldn't be written
+    Audit,
+    ClockDrift,
+    ClockState,
+    /// The policy requires an NTP-checked clock, and it hasn't been
+    /// checked yet
+    ClockUnverified,
+    /// The response couldn't be serialized
+    Encoding,
+    /// The key is at `[key_stats] max_signatures` and must be rotated
+    KeyExhausted,
+    /// `time-signed` would be outside the key's `key_validity`
+    KeyExpired,
+    KeyStats,
+    /// Longer than `max_message_bytes`
+    MessageTooLarge,
+    /// Paused by `POST /admin/pause-signing`
+    Paused,
+    /// This is a read-only mirror (see `mirror`)
+    ReadOnly,
+    /// The signature does not verify against the served public key
+    SelfCheck,
+    /// The serial couldn't be persisted
+    Serial,
+    /// The `Signer` failed
+    Signer,
+}
+
+impl SignError {
+    /// Machine-readable `code` of the error body
+    fn code(&self) -> &'static str {
+        match self {
+            SignError::Audit => "audit_error",
+            SignError::ClockDrift => "clock_drift",
+            SignError::ClockState => "clock_state_error",
+            SignError::ClockUnverified => "clock_unverified",
+            SignError::Encoding => "encoding_error",
+            SignError::KeyExhausted => "key_exhausted",
+            SignError::KeyExpired => "key_expired",
+            SignError::KeyStats => "key_stats_error",
+            SignError::MessageTooLarge => "message_too_large",
+            SignError::Paused => "signing_paused",
+            SignError::ReadOnly => "read_only",
+            SignError::SelfCheck => "self_check_failed",
+            SignError::Serial => "serial_error",
+            SignError::Signer => "signer_error",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            SignError::ClockDrift | SignError::ClockUnverified | SignError::Paused => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            SignError::KeyExhausted | SignError::KeyExpired | SignError::ReadOnly => {
+                StatusCode::FORBIDDEN
+            }
+            SignError::MessageTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            SignError::Audit
+            | SignError::ClockState
+            | SignError::Encoding
+            | SignError::KeyStats
+            | SignError::SelfCheck
+            | SignError::Serial
+            | SignError::Signer => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn message(&self) -> &'static str {
+        match self {
+            SignError::Audit => "Audit log error",
+            SignError::ClockDrift => "Clock drift too large",
+            SignError::ClockState => "Clock state error",
+            SignError::ClockUnverified => "The clock has not been checked against NTP yet",
+            SignError::Encoding => "Response encoding error",
+            SignError::KeyExhausted => "Signature limit reached for this key",
+            SignError::KeyExpired => "The key is outside its validity window",
+            SignError::KeyStats => "Key stats error",
+            SignError::MessageTooLarge => "Message too large",
+            SignError::Paused => "Signing is paused by the operator",
+            SignError::ReadOnly => "Read-only mirror; sign at the upstream server",
+            SignError::SelfCheck => "Signature failed self-verification",
+            SignError::Serial => "Serial counter error",
+            SignError::Signer => "Signer error",
+        }
+    }
+}
+
+/// State shared by all handlers
+struct AppState {
+    default: Arc<Tenant>,
+    tenants: HashMap<String, Arc<Tenant>>,
+    /// What `GET /version` lists under `features`
+    features: Vec<&'static str>,
+    /// What every `time-signed` is truncated to
+    time_precision: TimePrecision,
+    /// Every route's response times, for `GET /metrics`
+    latency: Arc<latency::Latency>,
+}
+
+/// The tenant a request is for: the `/t/{tenant}` path prefix if present,
+/// else the `X-Tenant` header, else the default tenant. Unknown names are
+/// rejected with 404.
+struct TenantRef(Arc<Tenant>);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for TenantRef {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let from_path = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .and_then(|Path(params)| params.get("tenant").cloned());
+        let name = from_path.or_else(|| {
+            parts
+                .headers
+                .get(TENANT_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        });
+
+        match name {
+            None => Ok(TenantRef(state.default.clone())),
+            Some(name) => match state.tenants.get(&name) {
+                Some(tenant) => Ok(TenantRef(tenant.clone())),
+                None => {
+                    error!("{} Unknown tenant '{}'", Utc::now().to_rfc3339(), name);
+                    Err(ApiError::new(
+                        StatusCode::NOT_FOUND,
+                        "unknown_tenant",
+                        "Unknown tenant",
+                    ))
+                }
+            },
+        }
+    }
 }
 
 /// Builds and runs the server on port 8008
 ///
-/// We accept the raw private and public key bytes (from `.bin` files)
-/// on startup so we can reconstruct a `KeyPair` without reading from disk again.
+/// We accept the raw private and public key bytes (from `.bin` files) on
+/// startup and build each `KeyPair` once; the raw private key bytes are
+/// wiped from memory right after.
 pub async fn run_server(
     private_key_bytes: Vec<u8>,
     public_key_bytes: Vec<u8>,
@@ -54,137 +1662,2127 @@ pub async fn run_server(
 }
 
 /// Runs the server with a provided listener (useful for tests with ephemeral ports)
+///
+/// State files (the high-water mark, key statistics) go to the default
+/// data directory, as with the `vts` binary, not the working directory.
 pub async fn run_server_with_listener(
     private_key_bytes: Vec<u8>,
     public_key_bytes: Vec<u8>,
-    listener: tokio::net::TcpListener,
+    listener: impl Into<Listener>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let addr = listener.local_addr()?;
-    info!("VTS microservice starting on {}", addr);
+    let mut config = ServerConfig::default();
+    crate::config::prepare_data_dir(&mut config)?;
+    run_configured_server_with_listener(
+        private_key_bytes,
+        public_key_bytes,
+        TenantKeys::new(),
+        config,
+        Box::new(SystemClock),
+        listener,
+    )
+    .await
+}
 
-    // Build the router:
-    let app = Router::new()
+/// Like `run_server`, but applies `config` (from `vts.toml`, listening on
+/// `config.listen`, or else `config.port`) and also serves the given tenants (name → raw
+/// private/public key bytes) under `/t/{name}/...` and the `X-Tenant` header.
+pub async fn run_configured_server(
+    private_key_bytes: Vec<u8>,
+    public_key_bytes: Vec<u8>,
+    tenant_keys: TenantKeys,
+    config: ServerConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = Listener::bind(&config.listen, config.port).await?;
+    run_configured_server_with_listener(
+        private_key_bytes,
+        public_key_bytes,
+        tenant_keys,
+        config,
+        Box::new(SystemClock),
+        listener,
+    )
+    .await
+}
+
+/// Like `run_server_with_listener`, with config and tenants (see
+/// `run_configured_server`) and the given time source (tests pass a `FakeClock`)
+pub async fn run_configured_server_with_listener(
+    private_key_bytes: Vec<u8>,
+    public_key_bytes: Vec<u8>,
+    tenant_keys: TenantKeys,
+    config: ServerConfig,
+    clock: Box<dyn Clock>,
+    listener: impl Into<Listener>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Each KeyPair is built once here; the raw private key bytes are wiped
+    // right after
+    let keypair_signer = |private_key: Vec<u8>, public_key: &[u8]| {
+        let private_key = Zeroizing::new(private_key);
+        key_pair_signer(&private_key, public_key).map(|k| Box::new(k) as Box<dyn Signer>)
+    };
+    let default = keypair_signer(private_key_bytes, &public_key_bytes)?;
+    let tenants = tenant_keys
+        .into_iter()
+        .map(|(name, (private_key, public_key))| {
+            Ok((name, keypair_signer(private_key, &public_key)?))
+        })
+        .collect::<std::io::Result<_>>()?;
+    run_server_with_signers(default, tenants, config, clock, listener).await
+}
+
+/// Runs a read-only mirror of `config.mirror.upstream` (on `listen`, else `port`):
+/// the upstream's keys are served instead of local ones, and its audit log
+/// is copied into `audit_file` (see `mirror`)
+#[cfg(feature = "client")]
+pub async fn run_mirror(config: ServerConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = Listener::bind(&config.listen, config.port).await?;
+    run_mirror_with_listener(config, Box::new(SystemClock), listener).await
+}
+
+/// Like `run_mirror`, on the given listener and time source
+#[cfg(feature = "client")]
+pub async fn run_mirror_with_listener(
+    config: ServerConfig,
+    clock: Box<dyn Clock>,
+    listener: impl Into<Listener>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if config.mirror.upstream.is_empty() {
+        return Err("No [mirror] upstream to mirror".into());
+    }
+    let (signer, tenants) =
+        mirror::upstream_signers(&config.mirror.upstream, config.tenants.keys()).await?;
+    run_server_with_signers(signer, tenants, config, clock, listener).await
+}
+
+/// Like `run_configured_server_with_listener`, but signs through the given
+/// signers (default tenant, then named tenants) instead of key bytes, so the
+/// keys can live outside the process (see `signer`)
+pub async fn run_server_with_signers(
+    signer: Box<dyn Signer>,
+    tenant_signers: TenantSigners,
+    config: ServerConfig,
+    clock: Box<dyn Clock>,
+    listener: impl Into<Listener>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = listener.into();
+    info!("VTS microservice starting on {}", listener.describe());
+    if !tenant_signers.is_empty() {
+        let names: Vec<&str> = tenant_signers.keys().map(String::as_str).collect();
+        info!("Serving tenants: {}", names.join(", "));
+    }
+
+    let cors = Cors::new(&config.cors)?;
+    let clock = Arc::new(
+        MonotonicClock::new(clock, &config.high_water_file)?.with_precision(config.time_precision),
+    );
+    let drift = (!config.ntp.servers.is_empty()).then(|| {
+        let monitor = Arc::new(DriftMonitor::new(config.ntp.clone()));
+        monitor.clone().spawn(clock.clone());
+        monitor
+    });
+    let key_stats = KeyStats::new(&config.key_stats)?;
+    let serials = Serials::new(&config.serial_file)?;
+    if config.duplicates == DuplicatePolicy::FirstSeen && config.audit_file.is_empty() {
+        return Err("duplicates = \"first-seen\" needs audit_file".into());
+    }
+    let mirror = (!config.mirror.upstream.is_empty()).then(|| config.mirror.upstream.clone());
+    if mirror.is_some() && config.audit_file.is_empty() {
+        return Err("A mirror needs audit_file, for its copy of the upstream log".into());
+    }
+    if mirror.is_some() && config.http_signatures {
+        return Err("http_signatures needs a signing key, and a mirror has none".into());
+    }
+    if mirror.is_some() && !cfg!(feature = "client") {
+        return Err("[mirror] needs the client feature".into());
+    }
+    let archive = !config.archive.endpoint.is_empty();
+    if archive && !cfg!(feature = "s3") {
+        return Err("[archive] needs the s3 feature".into());
+    }
+    if archive && config.audit_file.is_empty() {
+        return Err("[archive] needs audit_file, the log it archives".into());
+    }
+    if archive && mirror.is_some() {
+        return Err("[archive] signs its segments, and a mirror has no key".into());
+    }
+    let retention = config.retention.full_days > 0;
+    if retention && config.audit_file.is_empty() {
+        return Err("[retention] needs audit_file, the log it compacts".into());
+    }
+    if retention && mirror.is_some() {
+        return Err("[retention] signs its checkpoints, and a mirror has no key".into());
+    }
+    let gossip = !config.gossip.peers.is_empty();
+    if gossip && !cfg!(feature = "client") {
+        return Err("[gossip] needs the client feature".into());
+    }
+    if gossip && config.audit_file.is_empty() {
+        return Err("[gossip] needs audit_file, the log peers co-sign".into());
+    }
+    if gossip && mirror.is_some() {
+        return Err("[gossip] co-signs peers' heads, and a mirror has no key".into());
+    }
+    let anchoring = !config.anchor.witness.is_empty();
+    if anchoring && !cfg!(feature = "client") {
+        return Err("[anchor] needs the client feature".into());
+    }
+    if anchoring && config.audit_file.is_empty() {
+        return Err("[anchor] needs audit_file, the log it anchors".into());
+    }
+    if anchoring && mirror.is_some() {
+        return Err("[anchor] signs the heads it posts, and a mirror has no key".into());
+    }
+    let roughtime = !config.roughtime.listen.is_empty();
+    if roughtime && !cfg!(feature = "roughtime") {
+        return Err("[roughtime] needs the roughtime feature".into());
+    }
+    if roughtime && mirror.is_some() {
+        return Err("[roughtime] signs its answers, and a mirror has no key".into());
+    }
+    let otlp = !config.otlp.endpoint.is_empty();
+    if otlp && !cfg!(feature = "otlp") {
+        return Err("[otlp] needs the otlp feature".into());
+    }
+    #[cfg(feature = "s3")]
+    let store = match archive {
+        true => Some(archive::ObjectStore::new(&config.archive)?),
+        false => None,
+    };
+    for (name, policy) in &config.policies {
+        if policy.require_audit && config.audit_file.is_empty() {
+            return Err(format!("Policy '{}' requires audit_file", name).into());
+        }
+        if policy.require_ntp && config.ntp.servers.is_empty() {
+            return Err(format!("Policy '{}' requires [ntp] servers", name).into());
+        }
+    }
+    // The audit log is signed with the default tenant's key
+    let signer: Arc<dyn Signer> = Arc::from(signer);
+    let audit = match config.audit_file.as_str() {
+        "" => None,
+        path => Some(AuditLog::open(path, signer.clone())?),
+    };
+    let shared = Arc::new(Shared {
+        clock,
+        drift,
+        key_stats,
+        serials,
+        max_message_bytes: config.max_message_bytes,
+        audit,
+        duplicates: config.duplicates,
+        normalization: config.normalization,
+        privacy_mode: config.privacy_mode,
+        message_logging: match config.privacy_mode {
+            true => config.logging.messages.max(MessageLogging::Hash),
+            false => config.logging.messages,
+        },
+        log_sample_rate: config.logging.sample_rate,
+        log_lines: AtomicU64::new(0),
+        revoked_keys: config.revoked_keys.clone(),
+        policies: config.policies.clone(),
+        digest_algs: config.digest_algs.clone(),
+        mirror,
+        paused: AtomicBool::new(false),
+        full_days: config.retention.full_days,
+        archived: archive.then(|| AtomicU64::new(0)),
+        cosignatures: Mutex::new(BTreeMap::new()),
+        witnessed: Mutex::new(BTreeMap::new()),
+        anchors: match anchoring {
+            true => Some(anchor::Anchors::open(&config.anchor.file)?),
+            false => None,
+        },
+        pool: SignPool::new(&config.sign_pool),
+    });
+    let tenants = tenant_signers
+        .into_iter()
+        .map(|(name, signer)| {
+            let tenant = Tenant::new(Some(name.clone()), signer, &config, shared.clone())?;
+            Ok((name, Arc::new(tenant)))
+        })
+        .collect::<Result<_, Box<dyn std::error::Error>>>()?;
+    let mut features = vec!["jws", "cose", "renew", "log-stream", "key-stats"];
+    if cfg!(feature = "grpc") {
+        features.push("grpc");
+    }
+    if shared.audit.is_some() {
+        features.push("audit");
+    }
+    if shared.drift.is_some() {
+        features.push("ntp");
+    }
+    if config.sign_cache.window_secs > 0 {
+        features.push("sign-cache");
+    }
+    if config.duplicates == DuplicatePolicy::FirstSeen {
+        features.push("first-seen");
+    }
+    if shared.mirror.is_some() {
+        features.push("mirror");
+    }
+    if archive {
+        features.push("archive");
+    }
+    if retention {
+        features.push("retention");
+    }
+    if gossip {
+        features.push("gossip");
+    }
+    if anchoring {
+        features.push("anchor");
+    }
+    if roughtime {
+        features.push("roughtime");
+    }
+    if otlp {
+        features.push("otlp");
+    }
+    if cors.is_some() {
+        features.push("cors");
+    }
+    if config.http_signatures {
+        features.push("http-signatures");
+    }
+    if config.privacy_mode {
+        features.push("privacy-mode");
+    }
+    let state = Arc::new(AppState {
+        default: Arc::new(Tenant::new(None, Box::new(signer), &config, shared)?),
+        tenants,
+        features,
+        time_precision: config.time_precision,
+        latency: Arc::new(latency::Latency::new(&config.latency)),
+    });
+    if !config.admin.listen.is_empty() {
+        admin::spawn(state.clone(), &config.admin).await?;
+    }
+    #[cfg(feature = "client")]
+    if let Some(upstream) = &state.default.shared.mirror {
+        info!("Mirroring {} (read-only)", upstream);
+        mirror::spawn(
+            state.clone(),
+            upstream.clone(),
+            Duration::from_secs(config.mirror.interval_secs),
+        );
+    }
+    #[cfg(feature = "s3")]
+    if let Some(store) = store {
+        info!(
+            "Archiving the audit log to {}/{}",
+            config.archive.endpoint, config.archive.bucket
+        );
+        archive::spawn(state.clone(), store);
+    }
+    if retention {
+        info!(
+            "Compacting audit log entries older than {} days",
+            config.retention.full_days
+        );
+        retention::spawn(
+            state.clone(),
+            Duration::from_secs(config.retention.interval_secs),
+        );
+    }
+    #[cfg(feature = "client")]
+    if gossip {
+        info!("Gossiping with {} peers", config.gossip.peers.len());
+        gossip::spawn(
+            state.clone(),
+            config.gossip.peers.clone(),
+            Duration::from_secs(config.gossip.interval_secs),
+        );
+    }
+    #[cfg(feature = "client")]
+    if anchoring {
+        info!("Anchoring the tree head with {}", config.anchor.witness);
+        anchor::spawn(
+            state.clone(),
+            config.anchor.witness.clone(),
+            Duration::from_secs(config.anchor.interval_secs),
+        );
+    }
+    #[cfg(feature = "roughtime")]
+    if roughtime {
+        roughtime::spawn(state.clone(), &config.roughtime).await?;
+    }
+
+    // Build the router. The same handlers serve the default tenant (or the
+    // `X-Tenant` header) on the plain routes and a named tenant under `/t/`.
+    let tenant_routes = Router::new()
         .route(
             "/key",
-            get({
-                let pub_bytes = public_key_bytes.clone();
-                move || handle_get_key(pub_bytes.clone())
-            }),
+            get(
+                |TenantRef(tenant): TenantRef, Query(query): Query<HashMap<String, String>>| {
+                    handle_get_key(
+                        tenant.key(),
+                        tenant.shared.clock.now(),
+                        TextFormat::from_query(&query).unwrap_or_default(),
+                    )
+                },
+            ),
         )
+        .route("/keys", get(handle_get_keys))
+        .route("/time", get(handle_get_time))
+        .route("/.well-known/jwks.json", get(handle_get_jwks))
+        .route("/revocations", get(handle_get_revocations))
+        .route("/policies", get(handle_get_policies))
+        .route("/key/stats", get(handle_get_key_stats))
+        .route("/key/cert", get(handle_get_key_cert))
         .route(
             "/sign",
-            post({
-                // We now pass the raw private_key_bytes and public_key_bytes
-                let priv_bytes = private_key_bytes.clone();
-                let pub_bytes = public_key_bytes.clone();
-                move |Json(payload): Json<SignRequest>| {
-                    handle_post_sign(payload, priv_bytes.clone(), pub_bytes.clone())
-                }
-            }),
+            post(
+                |TenantRef(tenant): TenantRef,
+                 client: Option<ConnectInfo<SocketAddr>>,
+                 headers: HeaderMap,
+                 Query(query): Query<HashMap<String, String>>,
+                 payload: Result<Json<SignRequest>, JsonRejection>| async move {
+                    let Json(mut payload) = payload.map_err(json_rejection)?;
+                    let idempotency_key = headers
+                        .get(IDEMPOTENCY_KEY_HEADER)
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string);
+                    let format = ResponseFormat::negotiate(&headers, &query);
+                    if let Some(text) = TextFormat::from_query(&query) {
+                        payload.format = text;
+                    }
+                    let client = client.map(|ConnectInfo(addr)| addr);
+                    handle_post_sign(
+                        SignKind::Sign,
+                        payload,
+                        idempotency_key,
+                        format,
+                        client,
+                        tenant,
+                    )
+                    .await
+                },
+            )
+            .layer(DefaultBodyLimit::max(sign_body_limit(
+                config.max_message_bytes,
+            ))),
+        )
+        .route(
+            "/sign/preview",
+            post(
+                |TenantRef(tenant): TenantRef,
+                 headers: HeaderMap,
+                 Query(query): Query<HashMap<String, String>>,
+                 payload: Result<Json<SignRequest>, JsonRejection>| async move {
+                    let Json(mut payload) = payload.map_err(json_rejection)?;
+                    if let Some(text) = TextFormat::from_query(&query) {
+                        payload.format = text;
+                    }
+                    let format = ResponseFormat::negotiate(&headers, &query);
+                    handle_post_sign_preview(payload, format, &tenant)
+                },
+            )
+            .layer(DefaultBodyLimit::max(sign_body_limit(
+                config.max_message_bytes,
+            ))),
+        )
+        .route(
+            "/renew",
+            post(
+                |TenantRef(tenant): TenantRef,
+                 client: Option<ConnectInfo<SocketAddr>>,
+                 headers: HeaderMap,
+                 Query(query): Query<HashMap<String, String>>,
+                 payload: Result<Json<RenewRequest>, JsonRejection>| async move {
+                    let Json(payload) = payload.map_err(json_rejection)?;
+                    let payload = SignRequest {
+                        message: Some(payload.token),
+                        message_b64: None,
+                        encoding: payload.encoding,
+                        format: TextFormat::from_query(&query).unwrap_or(payload.format),
+                        algorithms: payload.algorithms,
+                        kid: payload.kid,
+                        policy: payload.policy,
+                        digest: None,
+                        digest_alg: None,
+                    };
+                    let format = ResponseFormat::negotiate(&headers, &query);
+                    let client = client.map(|ConnectInfo(addr)| addr);
+                    handle_post_sign(SignKind::Renew, payload, None, format, client, tenant).await
+                },
+            )
+            .layer(DefaultBodyLimit::max(sign_body_limit(
+                config.max_message_bytes,
+            ))),
         )
-        .fallback(fallback_handler);
+        .route("/timestamp/by-hash/:sha256", get(handle_get_by_hash))
+        .route("/anchor/:serial", get(handle_get_anchor))
+        .route("/log/stream", get(handle_log_stream));
+    // Everything is under `/v1`; the un-versioned paths are the same
+    // protocol, kept for clients that predate versioning
+    let routes = Router::new()
+        .route("/log/entries", get(handle_get_log_entries))
+        .route("/log/day/:date", get(handle_get_log_day))
+        .route("/log/root", get(handle_get_log_root))
+        .route("/log/consistency", get(handle_get_log_consistency))
+        .route("/log/proof", get(handle_get_log_proof))
+        .merge(tenant_routes.clone())
+        .nest("/t/:tenant", tenant_routes);
+    let app = Router::new()
+        .route("/version", get(handle_get_version))
+        .route("/openapi.json", get(handle_get_openapi))
+        .route("/metrics", get(latency::handle_get_metrics))
+        .merge(routes.clone())
+        .nest("/v1", routes);
+    // gRPC shares the port: its requests are HTTP/2 POSTs under `/vts.v1.Vts/`
+    #[cfg(feature = "grpc")]
+    let app = app.route_service(&grpc::route_path(), grpc::service(state.clone()));
+    // On the routes only, so each request is timed under its route
+    let app = app
+        .route_layer(middleware::from_fn_with_state(
+            state.latency.clone(),
+            latency::time_requests,
+        ))
+        .fallback(fallback_handler)
+        .layer(middleware::map_response(method_not_allowed_body));
+    // Inside `request_id`, so shed requests still get an id
+    let app = match RequestLimits::new(&config.limits) {
+        Some(limits) => app.layer(middleware::from_fn_with_state(
+            Arc::new(limits),
+            limits::limit_requests,
+        )),
+        None => app,
+    };
+    let app = app.layer(middleware::from_fn(request_id));
+    // Outside `request_id`, so each request's span has its id
+    #[cfg(feature = "otlp")]
+    let app = if otlp {
+        let exporter = Arc::new(trace::Exporter::new(&config.otlp));
+        trace::spawn(exporter.clone(), &config.otlp);
+        app.layer(middleware::from_fn_with_state(
+            exporter,
+            trace::trace_requests,
+        ))
+    } else {
+        app
+    };
+    // Outside `request_id`, which still rewrites error bodies
+    let app = if config.http_signatures {
+        app.layer(middleware::from_fn_with_state(
+            state.clone(),
+            response_sig::sign_responses,
+        ))
+    } else {
+        app
+    };
+    // Outermost, so preflights never reach the routes and every response
+    // (errors included) carries the CORS headers
+    let app = match cors {
+        Some(cors) => app.layer(middleware::from_fn_with_state(Arc::new(cors), cors_headers)),
+        None => app,
+    };
+    let app = app.with_state(state);
 
-    // Bind and serve
-    axum::serve(listener, app).await?;
+    // Serve (with the peer address, for the audit log)
+    listener::serve(listener, app).await?;
     Ok(())
 }
 
-/// GET /key → returns Base64 of the public key
-async fn handle_get_key(public_key: Vec<u8>) -> impl IntoResponse {
-    let now = Utc::now();
-    let b64_pub = general_purpose::STANDARD.encode(&public_key);
-    let timestamp_str = now.to_rfc3339_opts(chrono::SecondsFormat::Micros, true);
+/// GET /key → returns Base64 (or with `?format=hex`, hex) of the public key
+async fn handle_get_key(
+    key: Arc<TenantKey>,
+    now: DateTime<Utc>,
+    format: TextFormat,
+) -> impl IntoResponse {
+    let resp = key_response(&key, now, format);
+    info!(
+        "{} Request: GET /key → responding with public key {}",
+        now.to_rfc3339(),
+        resp.public_key
+    );
+    (StatusCode::OK, JsonResponse(resp))
+}
 
-    let resp = KeyResponse {
+/// GET /time?nonce=... → the server clock, signed with the tenant's key
+/// together with the client's nonce
+async fn handle_get_time(
+    TenantRef(tenant): TenantRef,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Response, ApiError> {
+    let now = tenant.shared.clock.now();
+    let nonce = query
+        .get("nonce")
+        .filter(|nonce| {
+            (1..=MAX_TIME_NONCE_LEN).contains(&nonce.len())
+                && nonce.bytes().all(|b| b.is_ascii_graphic())
+        })
+        .cloned()
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_nonce",
+                format!(
+                    "Expected a nonce of 1 to {} printable ASCII characters",
+                    MAX_TIME_NONCE_LEN
+                ),
+            )
+        })?;
+    let signing = tenant.clone();
+    let signed = match tenant
+        .shared
+        .pool
+        .run(move || signing.attest_time(nonce))
+        .await
+    {
+        Ok(signed) => signed?,
+        Err(PoolError::Saturated) => {
+            let route = format!("GET {}/time", tenant_prefix(&tenant));
+            return Ok(saturated_response(&tenant, now, &route));
+        }
+        Err(PoolError::Panicked) => return Err(SignError::Signer.into()),
+    };
+    info!(
+        "{} Request: GET {}/time → {}",
+        now.to_rfc3339(),
+        tenant_prefix(&tenant),
+        signed.time
+    );
+    Ok((StatusCode::OK, JsonResponse(signed)).into_response())
+}
+
+/// GET /keys → every key the tenant signs with, as `GET /key` gives the
+/// primary one; a `/sign` request picks one by its `kid`
+async fn handle_get_keys(
+    TenantRef(tenant): TenantRef,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let now = tenant.shared.clock.now();
+    let format = TextFormat::from_query(&query).unwrap_or_default();
+    let keys: Vec<KeyResponse> = tenant
+        .keys()
+        .iter()
+        .map(|key| key_response(key, now, format))
+        .collect();
+    info!(
+        "{} Request: GET {}/keys → {} keys",
+        now.to_rfc3339(),
+        tenant_prefix(&tenant),
+        keys.len()
+    );
+    JsonResponse(KeysResponse {
         request: "GET",
-        time_requested: timestamp_str,
-        public_key: b64_pub.clone(),
+        keys,
+    })
+}
+
+/// GET /.well-known/jwks.json → the keys of `GET /keys` and the retired
+/// ones, as a JWK Set
+async fn handle_get_jwks(TenantRef(tenant): TenantRef) -> impl IntoResponse {
+    let keys: Vec<jws::Jwk> = tenant
+        .verification_keys()
+        .iter()
+        .map(jws::Jwk::new)
+        .collect();
+    info!(
+        "{} Request: GET {}/.well-known/jwks.json → {} keys",
+        tenant.shared.clock.now().to_rfc3339(),
+        tenant_prefix(&tenant),
+        keys.len()
+    );
+    JsonResponse(jws::JwkSet { keys })
+}
+
+/// GET /revocations → the `revoked_keys`, signed with the tenant's key
+/// (see `crate::revocation`)
+async fn handle_get_revocations(TenantRef(tenant): TenantRef) -> Result<Response, ApiError> {
+    let now = tenant.shared.clock.now();
+    if tenant.shared.mirror.is_some() {
+        return Err(SignError::ReadOnly.into());
+    }
+    let time_issued = wire::canonical_time(now);
+    let revoked: Vec<revocation::RevokedKey> = tenant
+        .shared
+        .revoked_keys
+        .iter()
+        .map(|r| revocation::RevokedKey {
+            kid: r.kid.clone(),
+            compromised_at: wire::canonical_time(r.compromised_at),
+            reason: r.reason.clone(),
+        })
+        .collect();
+    let input = revocation::signing_input(&time_issued, &revoked);
+
+    // On the sign pool, like every other signature (the signer may be a
+    // remote KMS)
+    let key = tenant.key();
+    let signing = key.clone();
+    let signature = match tenant
+        .shared
+        .pool
+        .run(move || signing.sign(input.as_bytes(), now))
+        .await
+    {
+        Ok(signature) => signature?,
+        Err(PoolError::Saturated) => {
+            let route = format!("GET {}/revocations", tenant_prefix(&tenant));
+            return Ok(saturated_response(&tenant, now, &route));
+        }
+        Err(PoolError::Panicked) => return Err(SignError::Signer.into()),
     };
     info!(
-        "{} Request: GET /key → responding with public key {}",
+        "{} Request: GET {}/revocations → {} revoked keys",
         now.to_rfc3339(),
-        b64_pub
+        tenant_prefix(&tenant),
+        revoked.len()
+    );
+    let list = revocation::RevocationList {
+        request: "GET".to_string(),
+        time_issued,
+        revoked,
+        kid: key.key_id.clone(),
+        signature: general_purpose::STANDARD.encode(signature.to_vec()),
+    };
+    Ok(JsonResponse(list).into_response())
+}
+
+/// GET /policies → the `[policies]` a request may name, with what each
+/// guarantees; `default` is listed even if not configured
+async fn handle_get_policies(TenantRef(tenant): TenantRef) -> impl IntoResponse {
+    let configured = &tenant.shared.policies;
+    let implicit = (!configured.contains_key(DEFAULT_POLICY))
+        .then(|| (DEFAULT_POLICY.to_string(), PolicyConfig::default()));
+    let policies: Vec<PolicyResponse> = implicit
+        .into_iter()
+        .chain(configured.clone())
+        .map(|(name, policy)| PolicyResponse {
+            name,
+            description: policy.description,
+            require_audit: policy.require_audit,
+            require_ntp: policy.require_ntp,
+        })
+        .collect();
+    info!(
+        "{} Request: GET {}/policies → {} policies",
+        tenant.shared.clock.now().to_rfc3339(),
+        tenant_prefix(&tenant),
+        policies.len()
     );
+    JsonResponse(PoliciesResponse {
+        request: "GET",
+        policies,
+    })
+}
+
+fn key_response(key: &TenantKey, now: DateTime<Utc>, format: TextFormat) -> KeyResponse {
+    let public_key = &key.public_key;
+    KeyResponse {
+        request: "GET",
+        time_requested: now,
+        public_key: match format {
+            TextFormat::Base64 => public_key.to_string(),
+            TextFormat::Hex => format!("{public_key:x}"),
+        },
+        format,
+        signature_alg: SignatureAlg::default(),
+        hash_alg: HashAlg::default(),
+        kid: key.key_id.clone(),
+        not_before: key.validity.not_before.map(wire::canonical_time),
+        not_after: key.validity.not_after.map(wire::canonical_time),
+    }
+}
+
+/// The policy a request names (see `Shared::policy`); `400 unknown_policy`
+/// if there is none by that name
+fn requested_policy(tenant: &Tenant, policy: Option<String>) -> Result<Option<String>, ApiError> {
+    tenant.shared.policy(policy).map_err(|name| {
+        error!(
+            "{} Unknown policy '{}' requested",
+            Utc::now().to_rfc3339(),
+            name
+        );
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "unknown_policy",
+            "No policy by that name",
+        )
+        .for_field(Some("policy".to_string()))
+    })
+}
+
+/// The tenant's key a request names with `kid`, or its primary key;
+/// `400 unknown_kid` if it has none by that id
+fn requested_key(tenant: &Tenant, kid: Option<&str>) -> Result<Arc<TenantKey>, ApiError> {
+    tenant.select_key(kid).ok_or_else(|| {
+        error!(
+            "{} Unknown key '{}' requested",
+            Utc::now().to_rfc3339(),
+            kid.unwrap_or_default()
+        );
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "unknown_kid",
+            "No key with that kid",
+        )
+        .for_field(Some("kid".to_string()))
+    })
+}
+
+/// GET /version → server version, protocol versions and features
+async fn handle_get_version(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    info!(
+        "{} Request: GET /version → responding",
+        Utc::now().to_rfc3339()
+    );
+    JsonResponse(VersionResponse {
+        request: "GET",
+        server_version: env!("CARGO_PKG_VERSION"),
+        protocol_versions: crate::PROTOCOL_VERSIONS,
+        features: state.features.clone(),
+        time_precision: state.time_precision,
+    })
+}
+
+/// GET /openapi.json → OpenAPI 3.0 description of the HTTP routes
+async fn handle_get_openapi() -> impl IntoResponse {
+    info!(
+        "{} Request: GET /openapi.json → responding",
+        Utc::now().to_rfc3339()
+    );
+    JsonResponse(openapi::document())
+}
+
+/// GET /key/stats → how many signatures the tenant's key has produced
+async fn handle_get_key_stats(TenantRef(tenant): TenantRef) -> impl IntoResponse {
+    let stats = &tenant.shared.key_stats;
+    let key_id = tenant.key().key_id.clone();
+    let usage = stats.usage(&key_id);
+    let max_signatures = stats.max_signatures();
+    info!(
+        "{} Request: GET {}/key/stats → {} signatures",
+        tenant.shared.clock.now().to_rfc3339(),
+        tenant_prefix(&tenant),
+        usage.signatures
+    );
+    let resp = KeyStatsResponse {
+        request: "GET",
+        key_id,
+        remaining: max_signatures.map(|max| max.saturating_sub(usage.signatures)),
+        usage,
+        max_signatures,
+    };
     (StatusCode::OK, JsonResponse(resp))
 }
 
-/// POST /sign (JSON body `{"message":"..."}`) → returns signature
+/// GET /key/cert → the X.509 certificate of the key (`cert_file`), as DER,
+/// or as PEM for `Accept: application/pem-certificate-chain`
+async fn handle_get_key_cert(
+    TenantRef(tenant): TenantRef,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let no_certificate =
+        |message: &str| ApiError::new(StatusCode::NOT_FOUND, "no_certificate", message);
+    let Some(cert) = &tenant.cert else {
+        return Err(no_certificate("No certificate is configured for this key"));
+    };
+    // A key rotated since startup has outgrown the certificate
+    let certified = x509::parse(cert).map(|info| info.public_key);
+    if certified != Some(tenant.key().public_key) {
+        return Err(no_certificate("The certificate is for a previous key"));
+    }
+    let pem = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains(x509::PEM_CONTENT_TYPE));
+    info!(
+        "{} Request: GET {}/key/cert → {} certificate",
+        tenant.shared.clock.now().to_rfc3339(),
+        tenant_prefix(&tenant),
+        if pem { "PEM" } else { "DER" }
+    );
+    let resp = if pem {
+        let body = x509::to_pem(cert, "CERTIFICATE");
+        ([(header::CONTENT_TYPE, x509::PEM_CONTENT_TYPE)], body).into_response()
+    } else {
+        ([(header::CONTENT_TYPE, x509::CONTENT_TYPE)], cert.clone()).into_response()
+    };
+    Ok(resp)
+}
+
+/// POST /sign (JSON body `{"message":"...", "encoding":"raw"|"der", "format":"base64"|"hex"}`) → returns signature
+///
+/// Signs with the selected tenant's key. We reconstruct `KeyPair` purely from
+/// its raw private-key bytes (no need to write `.bin` files).
+/// Every issued timestamp is also published to that tenant's `GET /log/stream` subscribers.
+///
+/// If `[sign_cache]` is enabled, a repeat of a request (same `Idempotency-Key`,
+/// or same message when `by_message_hash` is set) within the window gets the
+/// previously issued response back instead of a new signature. Reusing an
+/// idempotency key for a different message is rejected with 422.
 ///
-/// Now takes both raw private-key bytes and public-key bytes. We reconstruct
-/// `KeyPair` purely from these byte arrays (no need to write `.bin` files).
+/// With `Accept: application/jose` or `?format=jws` the response is a compact
+/// JWS over `{msg_hash, iat, serial, kid}` instead (see `crate::jws`), and with
+/// `Accept: application/cose` or `?format=cose` a COSE_Sign1 token (see `crate::cose`).
+///
+/// `POST /renew` is the same with the earlier token as the message, so the
+/// new signature covers `token + time-signed`. Renewals are never cached.
 async fn handle_post_sign(
+    kind: SignKind,
     payload: SignRequest,
-    private_key_bytes: Vec<u8>,
-    public_key_bytes: Vec<u8>,
-) -> impl IntoResponse {
-    let now = Utc::now();
-    let message = payload.message.clone();
-
-    // Reconstruct KeyPair directly from bytes (no file I/O). The library only
-    // provides `load_from_files`, but we can load from raw bytes by:
-    //
-    //  1) Write them to temporary files
-    //  2) Add a helper in `ecdsa_lib` to load from raw slices.
-    //
-    // Here, I will do the temporary-file approach. Can also add a
-    // `KeyPair::from_bytes(pub_key_bytes, priv_key_bytes)` method to `ecdsa_lib`.
-    //
-    // For now, write to a unique path to avoid race conditions, load, then delete.
-    let unique_id = std::process::id();
-    let priv_file = format!("private_key_{}.bin", unique_id);
-    let pub_file = format!("public_key_{}.bin", unique_id);
-
-    let _ = fs::write(&priv_file, &private_key_bytes);
-    let _ = fs::write(&pub_file, &public_key_bytes);
-
-    let keypair = match KeyPair::load_from_files(&priv_file, &pub_file) {
-        Ok(kp) => {
-            // Clean up temp files
-            let _ = fs::remove_file(&priv_file);
-            let _ = fs::remove_file(&pub_file);
-            kp
+    idempotency_key: Option<String>,
+    format: ResponseFormat,
+    client: Option<SocketAddr>,
+    tenant: Arc<Tenant>,
+) -> Result<Response, ApiError> {
+    let now = tenant.shared.clock.now();
+    #[cfg(feature = "otlp")]
+    let parse = trace::span("parse");
+    let SignRequest {
+        message,
+        message_b64,
+        encoding,
+        format: text_format,
+        algorithms,
+        kid,
+        policy,
+        digest,
+        digest_alg,
+    } = payload;
+    let digest = digest_message(&tenant.shared, digest, digest_alg)?;
+    let mut message = Message::from_request(message, message_b64, digest)?;
+    // A renewal's token is signed data already, so it is never normalized
+    if let (SignKind::Sign, Message::Text(text)) = (kind, &mut message) {
+        normalize(text, tenant.shared.normalization);
+    }
+    // A renewal's token carries only what was signed before
+    if kind == SignKind::Sign && tenant.shared.refuses_plaintext(message.as_bytes()) {
+        return Err(plaintext_not_allowed());
+    }
+    let digest: [u8; 32] = Sha256::digest(message.as_bytes()).into();
+    let hash = hex::encode(digest);
+
+    if let Some(name) = algorithms.unsupported() {
+        error!(
+            "{} Unsupported algorithm '{}' requested",
+            now.to_rfc3339(),
+            name
+        );
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "unsupported_algorithm",
+            format!("Unsupported algorithm: {}", name),
+        ));
+    }
+    let key = requested_key(&tenant, kid.as_deref())?;
+    let policy = requested_policy(&tenant, policy)?;
+
+    let cache_key = match kind {
+        SignKind::Sign => tenant.cache.key_for(
+            idempotency_key.as_deref(),
+            &hash,
+            &match &policy {
+                Some(policy) => format!("{}/{}", key.key_id, policy),
+                None => key.key_id.clone(),
+            },
+            encoding,
+            format,
+            text_format,
+        ),
+        SignKind::Renew => None,
+    };
+    if let Some(key) = &cache_key {
+        match tenant.cache.lookup(key, &hash) {
+            CacheLookup::Hit(cached) => {
+                if tenant.shared.sampled() {
+                    info!(
+                        "{} Request: POST {}/{} {} → returning cached timestamp",
+                        now.to_rfc3339(),
+                        tenant_prefix(&tenant),
+                        kind.path(),
+                        tenant.shared.logged(&message, message.as_bytes())
+                    );
+                }
+                return Ok(cached.into_response());
+            }
+            CacheLookup::Conflict => {
+                error!(
+                    "{} Idempotency key reused for a different message",
+                    now.to_rfc3339()
+                );
+                return Err(ApiError::new(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "idempotency_conflict",
+                    "Idempotency-Key already used for a different message",
+                ));
+            }
+            CacheLookup::Miss => {}
         }
-        Err(e) => {
-            // Clean up temp files even on error
-            let _ = fs::remove_file(&priv_file);
-            let _ = fs::remove_file(&pub_file);
-            error!("{} Failed to load KeyPair: {}", now.to_rfc3339(), e);
-            let err_body = serde_json::json!({ "error": "Key load error" });
-            return (StatusCode::INTERNAL_SERVER_ERROR, JsonResponse(err_body));
+    }
+
+    let job = SignJob {
+        kind,
+        message,
+        hash: hash.clone(),
+        digest,
+        key,
+        policy,
+        encoding,
+        text_format,
+        format,
+        client,
+    };
+    let signing = tenant.clone();
+    #[cfg(feature = "otlp")]
+    drop(parse);
+    let (body, first_seen) = match tenant.shared.pool.run(move || job.run(&signing)).await {
+        Ok(Ok(issued)) => issued,
+        Ok(Err(e)) => return Err(e.into()),
+        Err(PoolError::Saturated) => {
+            let route = format!("POST {}/{}", tenant_prefix(&tenant), kind.path());
+            return Ok(saturated_response(&tenant, now, &route));
         }
+        Err(PoolError::Panicked) => return Err(SignError::Signer.into()),
+    };
+
+    // **Return the successful response** (StatusCode::OK + JSON or JWS).
+    // A first-seen timestamp isn't cached: it is found again anyway.
+    if first_seen {
+        let mut response = body.into_response();
+        response
+            .headers_mut()
+            .insert(FIRST_SEEN_HEADER, HeaderValue::from_static("true"));
+        return Ok(response);
+    }
+    let body = match cache_key {
+        Some(key) => tenant.cache.insert(key, hash, body),
+        None => body,
     };
+    Ok(body.into_response())
+}
+
+/// POST /sign/preview (same body and `?format=` as `/sign`) → the bytes
+/// `/sign` would sign right now, without signing them
+///
+/// For JSON that is `message + time-signed`; for JWS the signing input
+/// `header.claims`; for COSE the `Sig_structure`. Nothing is issued: the
+/// clock isn't advanced, no serial is used, and nothing is counted against
+/// the key or written to the audit log, so the actual `/sign` may differ in
+/// `time-signed` (and, for tokens, `serial`).
+fn handle_post_sign_preview(
+    payload: SignRequest,
+    format: ResponseFormat,
+    tenant: &Tenant,
+) -> Result<Response, ApiError> {
+    let now = tenant.shared.clock.now();
+    let SignRequest {
+        message,
+        message_b64,
+        format: text_format,
+        algorithms,
+        kid,
+        policy,
+        digest,
+        digest_alg,
+        ..
+    } = payload;
+    let digest = digest_message(&tenant.shared, digest, digest_alg)?;
+    let mut message = Message::from_request(message, message_b64, digest)?;
+    if let Message::Text(text) = &mut message {
+        normalize(text, tenant.shared.normalization);
+    }
+    if tenant.shared.refuses_plaintext(message.as_bytes()) {
+        return Err(plaintext_not_allowed());
+    }
 
-    // Sign "message + timestamp":
-    // Use the same format that will be serialized to JSON
-    let timestamp_str = now.to_rfc3339_opts(chrono::SecondsFormat::Micros, true);
-    let data_to_sign = format!("{}{}", message, timestamp_str);
-    let sig: Signature = keypair.sign(data_to_sign.as_bytes());
-    let sig_b64 = general_purpose::STANDARD.encode(sig.to_vec());
+    if let Some(name) = algorithms.unsupported() {
+        error!(
+            "{} Unsupported algorithm '{}' requested",
+            now.to_rfc3339(),
+            name
+        );
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "unsupported_algorithm",
+            format!("Unsupported algorithm: {}", name),
+        ));
+    }
+    let key = requested_key(tenant, kid.as_deref())?;
+    let policy = requested_policy(tenant, policy)?;
+    if message.as_bytes().len() > tenant.shared.max_message_bytes {
+        return Err(SignError::MessageTooLarge.into());
+    }
 
-    let resp = SignResponse {
-        request: "POST",
-        message: message.clone(),
-        time_signed: timestamp_str,
-        signature: sig_b64.clone(),
+    let time_signed = tenant.shared.clock.peek();
+    let timestamp_str = wire::canonical_time(time_signed);
+    let serial = tenant.serial() + 1;
+    let digest: [u8; 32] = Sha256::digest(message.as_bytes()).into();
+    let signed = match format {
+        ResponseFormat::Json => {
+            wire::policy_signing_input(message.as_bytes(), &timestamp_str, policy.as_deref())
+        }
+        ResponseFormat::Jws => jws::signing_input(&JwsClaims {
+            msg_hash: hex::encode(digest),
+            iat: time_signed.timestamp(),
+            serial,
+            kid: key.key_id.clone(),
+            policy: policy.clone(),
+            day_root: tenant.shared.day_root(time_signed).map(hex::encode),
+        })
+        .into_bytes(),
+        ResponseFormat::Cose => cose::signing_input(&CoseClaims {
+            msg_hash: digest,
+            iat: time_signed.timestamp(),
+            serial,
+            kid: key.thumbprint,
+            policy: policy.clone(),
+            day_root: tenant.shared.day_root(time_signed),
+        }),
     };
 
+    if tenant.shared.sampled() {
+        info!(
+            "{} Request: POST {}/sign/preview {} → {} bytes",
+            now.to_rfc3339(),
+            tenant_prefix(tenant),
+            tenant.shared.logged(&message, message.as_bytes()),
+            signed.len()
+        );
+    }
+    let (message, message_b64) = message.fields();
+    let resp = PreviewResponse {
+        request: "PREVIEW",
+        message,
+        message_b64,
+        time_signed,
+        token: format.name(),
+        signed_data: text_format.encode(&signed),
+        signed_text: match format {
+            ResponseFormat::Cose => None,
+            _ => String::from_utf8(signed).ok(),
+        },
+        format: text_format,
+        signature_alg: SignatureAlg::default(),
+        hash_alg: HashAlg::default(),
+        policy,
+    };
+    Ok((StatusCode::OK, JsonResponse(resp)).into_response())
+}
+
+/// GET /timestamp/by-hash/{sha256} → every timestamp the tenant issued for
+/// the message with that hex SHA-256, with the audit log line as proof
+///
+/// The audit log is the record of what was issued, so this needs
+/// `audit_file`; without it the answer is `404 not_enabled`. Only the
+/// signature over `message + time-signed` is kept, not the JWS or COSE token
+/// itself: the holder of the message can check it like a JSON `/sign`
+/// response, and check `proof` against the default tenant's `GET /key`.
+async fn handle_get_by_hash(
+    TenantRef(tenant): TenantRef,
+    Path(params): Path<HashMap<String, String>>,
+) -> Result<Response, ApiError> {
+    let now = tenant.shared.clock.now();
+    let hash = params
+        .get("sha256")
+        .map(|h| h.to_ascii_lowercase())
+        .unwrap_or_default();
+    if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_hash",
+            "Expected a hex SHA-256",
+        ));
+    }
+    let Some(audit) = &tenant.shared.audit else {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_enabled",
+            "Lookup by hash needs the audit log",
+        ));
+    };
+
+    let timestamps: Vec<FoundTimestamp> = audit
+        .by_hash(&hash)
+        .into_iter()
+        .filter(|entry| entry.tenant == tenant.name)
+        .map(|entry| FoundTimestamp {
+            serial: entry.serial,
+            time_signed: entry.time.clone(),
+            kind: entry.kind.clone(),
+            signature: entry.signature.clone(),
+            policy: entry.policy.clone(),
+            proof: entry,
+        })
+        .collect();
+    let compacted: Vec<CompactedProof> = audit
+        .compacted_by_hash(&hash)
+        .into_iter()
+        .filter(|found| found.entry.tenant == tenant.name)
+        .collect();
     info!(
-        "{} Request: POST /sign message='{}' → response sig='{}'",
+        "{} Request: GET {}/timestamp/by-hash/{} → {} found",
         now.to_rfc3339(),
-        message,
-        sig_b64
+        tenant_prefix(&tenant),
+        hash,
+        compacted.len() + timestamps.len()
     );
+    let resp = LookupResponse {
+        request: "LOOKUP",
+        hash,
+        timestamps,
+        compacted,
+    };
+    Ok((StatusCode::OK, JsonResponse(resp)).into_response())
+}
+
+/// GET /anchor/{serial} → the audit log line of the tenant's timestamp
+/// `serial`, in the tree of the first head `[anchor]` had witnessed after
+/// it, with the witness's receipt. Needs `[anchor] witness`. A timestamp
+/// issued since the last anchoring is `404 not_anchored` until the next;
+/// a line compacted away (`[retention]`) is `410 compacted`.
+async fn handle_get_anchor(
+    TenantRef(tenant): TenantRef,
+    Path(params): Path<HashMap<String, String>>,
+) -> Result<Response, ApiError> {
+    let now = tenant.shared.clock.now();
+    let serial = params
+        .get("serial")
+        .and_then(|serial| serial.parse::<u64>().ok())
+        .filter(|serial| *serial >= 1)
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_serial",
+                "Expected a serial from 1",
+            )
+        })?;
+    let (Some(audit), Some(anchors)) = (&tenant.shared.audit, &tenant.shared.anchors) else {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_enabled",
+            "Anchoring needs [anchor] witness",
+        ));
+    };
+    let seq = audit
+        .seq_of_serial(tenant.name.as_deref(), serial)
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::NOT_FOUND,
+                "unknown_serial",
+                format!("No timestamp {} in the log", serial),
+            )
+        })?;
+    let anchor = anchors.covering(seq).ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_anchored",
+            format!("Timestamp {} is not anchored yet", serial),
+        )
+    })?;
 
-    // **Return the successful response** (StatusCode::OK + JSON)
-    (
+    let reading = tenant.clone();
+    let count = anchor.head.count;
+    let inclusion = tokio::task::spawn_blocking(move || {
+        let audit = reading.shared.audit.as_ref().expect("checked above");
+        audit.inclusion_proof(seq, count)
+    })
+    .await
+    .map_err(std::io::Error::other)
+    .and_then(|result| result)
+    .map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => ApiError::new(StatusCode::GONE, "compacted", e.to_string()),
+        _ => {
+            error!("{} Failed to prove inclusion: {}", now.to_rfc3339(), e);
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "audit_error",
+                "Audit log error",
+            )
+        }
+    })?
+    .ok_or_else(|| {
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "audit_error",
+            "Audit log error",
+        )
+    })?;
+    info!(
+        "{} Request: GET {}/anchor/{} → head at {} lines",
+        now.to_rfc3339(),
+        tenant_prefix(&tenant),
+        serial,
+        count
+    );
+    let resp = AnchorResponse {
+        request: "ANCHOR",
+        proof: AnchorProof {
+            serial,
+            anchor,
+            inclusion,
+        },
+    };
+    Ok((StatusCode::OK, JsonResponse(resp)).into_response())
+}
+
+/// Most lines `GET /log/entries` returns at once
+const MAX_LOG_ENTRIES: usize = 1000;
+
+/// GET /log/entries?since=SEQ&limit=N → the audit log lines after `seq`
+/// SEQ (default 0), at most N (default and cap 1000), as NDJSON exactly as
+/// written, so they can be checked like the file itself. All tenants share
+/// the one log. This is what a mirror syncs from; needs `audit_file`.
+/// Lines compacted away (`[retention]`) are `410 compacted`.
+async fn handle_get_log_entries(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Response, ApiError> {
+    let now = state.default.shared.clock.now();
+    let number = |name: &str, default: u64| match query.get(name) {
+        None => Ok(default),
+        Some(v) => v.parse::<u64>().map_err(|_| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_query",
+                format!("{} must be a non-negative integer", name),
+            )
+        }),
+    };
+    let since = number("since", 0)?;
+    let limit = number("limit", MAX_LOG_ENTRIES as u64)?.min(MAX_LOG_ENTRIES as u64) as usize;
+    if state.default.shared.audit.is_none() {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_enabled",
+            "The log needs audit_file",
+        ));
+    }
+
+    let reading = state.clone();
+    let lines = tokio::task::spawn_blocking(move || {
+        let audit = reading
+            .default
+            .shared
+            .audit
+            .as_ref()
+            .expect("checked above");
+        audit.lines_after(since, limit)
+    })
+    .await
+    .map_err(std::io::Error::other)
+    .and_then(|result| result)
+    .map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => ApiError::new(StatusCode::GONE, "compacted", e.to_string()),
+        _ => {
+            error!("{} Failed to read the audit log", now.to_rfc3339());
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "audit_error",
+                "Audit log error",
+            )
+        }
+    })?;
+    info!(
+        "{} Request: GET /log/entries since={} → {} entries",
+        now.to_rfc3339(),
+        since,
+        lines.len()
+    );
+    let body: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+    Ok((
         StatusCode::OK,
-        JsonResponse(serde_json::to_value(resp).unwrap()),
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        body,
+    )
+        .into_response())
+}
+
+/// GET /log/day/{date}[?seq=N] → the signed Merkle root of the audit log
+/// lines written that UTC day (`YYYY-MM-DD`), once it is over, and with
+/// `seq` the proof of that line. All tenants share the one log; needs
+/// `audit_file`. A day still going on is `409 day_open`. A mirror serves
+/// the upstream's root, synced once a later line is, and answers
+/// `503 not_synced` before then.
+async fn handle_get_log_day(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<HashMap<String, String>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Response, ApiError> {
+    let now = state.default.shared.clock.now();
+    let date = params
+        .get("date")
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_date",
+                "Expected a date as YYYY-MM-DD",
+            )
+        })?;
+    let seq = match query.get("seq") {
+        None => None,
+        Some(v) => Some(v.parse::<u64>().map_err(|_| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_query",
+                "seq must be a non-negative integer",
+            )
+        })?),
+    };
+    if state.default.shared.audit.is_none() {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_enabled",
+            "The log needs audit_file",
+        ));
+    }
+    // A mirror has the upstream's word for it, whatever its own clock says
+    if state.default.shared.mirror.is_none() && date >= now.date_naive() {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "day_open",
+            format!("{} is not over yet", date),
+        ));
+    }
+
+    let reading = state.clone();
+    let (root, proof) = tokio::task::spawn_blocking(move || {
+        let shared = &reading.default.shared;
+        let audit = shared.audit.as_ref().expect("checked above");
+        let proof = seq.map(|seq| audit.day_proof(seq));
+        let root = match shared.mirror {
+            Some(_) => Ok(audit.signed_day_root(date)),
+            None => audit.day_root(date).map(Some),
+        };
+        root.map(|root| (root, proof))
+    })
+    .await
+    .map_err(std::io::Error::other)
+    .and_then(|result| result)
+    .map_err(|e| {
+        error!("{} Failed to sign the day root: {}", now.to_rfc3339(), e);
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "audit_error",
+            "Audit log error",
+        )
+    })?;
+    let root = root.ok_or_else(|| {
+        ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "not_synced",
+            format!("The mirror has no root for {} from the upstream yet", date),
+        )
+    })?;
+    let proof = match proof {
+        None => None,
+        Some(Some((day, proof))) if day == date => Some(proof),
+        Some(_) => {
+            return Err(ApiError::new(
+                StatusCode::NOT_FOUND,
+                "unknown_seq",
+                format!("No line {} on {}", seq.unwrap_or_default(), date),
+            ));
+        }
+    };
+    info!(
+        "{} Request: GET /log/day/{} → {} entries",
+        now.to_rfc3339(),
+        date,
+        root.count
+    );
+    let resp = DayResponse {
+        request: "DAY",
+        root,
+        proof,
+    };
+    Ok((StatusCode::OK, JsonResponse(resp)).into_response())
+}
+
+/// GET /log/root: the signed head over the whole audit log, with what the
+/// `[gossip]` peers co-signed. A mirror serves the upstream's head it last
+/// synced to, `503 not_synced` before the first.
+async fn handle_get_log_root(State(state): State<Arc<AppState>>) -> Result<Response, ApiError> {
+    let now = state.default.shared.clock.now();
+    if state.default.shared.audit.is_none() {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_enabled",
+            "The log needs audit_file",
+        ));
+    }
+
+    let reading = state.clone();
+    let head = tokio::task::spawn_blocking(move || {
+        let shared = &reading.default.shared;
+        let audit = shared.audit.as_ref().expect("checked above");
+        match shared.mirror {
+            Some(_) => Ok(audit.signed_head()),
+            None => audit.tree_head(&wire::canonical_time(now)).map(Some),
+        }
+    })
+    .await
+    .map_err(std::io::Error::other)
+    .and_then(|result| result)
+    .map_err(|e| {
+        error!("{} Failed to sign the tree head: {}", now.to_rfc3339(), e);
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "audit_error",
+            "Audit log error",
+        )
+    })?
+    .ok_or_else(|| {
+        ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "not_synced",
+            "The mirror has no head from the upstream yet",
+        )
+    })?;
+    info!(
+        "{} Request: GET /log/root → {} entries",
+        now.to_rfc3339(),
+        head.count
+    );
+    let shared = &state.default.shared;
+    let resp = RootResponse {
+        request: "ROOT",
+        head,
+        cosignatures: shared
+            .cosignatures
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect(),
+        witnessed: shared.witnessed.lock().unwrap().values().cloned().collect(),
+    };
+    Ok((StatusCode::OK, JsonResponse(resp)).into_response())
+}
+
+/// GET /log/consistency?old=M&new=N → that the log at `N` lines (a
+/// `GET /log/root` head then) extends the log at `M`, for auditors holding
+/// both heads. Needs `audit_file`.
+async fn handle_get_log_consistency(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Response, ApiError> {
+    let now = state.default.shared.clock.now();
+    let number = |name: &str| {
+        query
+            .get(name)
+            .and_then(|v| v.parse::<u64>().ok())
+            .ok_or_else(|| {
+                ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "invalid_query",
+                    format!("{} must be a non-negative integer", name),
+                )
+            })
+    };
+    let (old, new) = (number("old")?, number("new")?);
+    if old > new {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_query",
+            "old must not be past new",
+        ));
+    }
+    if state.default.shared.audit.is_none() {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_enabled",
+            "The log needs audit_file",
+        ));
+    }
+
+    let reading = state.clone();
+    let proof = tokio::task::spawn_blocking(move || {
+        let audit = reading
+            .default
+            .shared
+            .audit
+            .as_ref()
+            .expect("checked above");
+        audit.consistency_proof(old, new)
+    })
+    .await
+    .map_err(|e| {
+        error!("{} Failed to prove consistency: {}", now.to_rfc3339(), e);
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "audit_error",
+            "Audit log error",
+        )
+    })?
+    .ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            "unknown_count",
+            format!("The log has fewer than {} lines", new),
+        )
+    })?;
+    info!(
+        "{} Request: GET /log/consistency old={} new={}",
+        now.to_rfc3339(),
+        old,
+        new
+    );
+    let resp = ConsistencyResponse {
+        request: "CONSISTENCY",
+        proof,
+    };
+    Ok((StatusCode::OK, JsonResponse(resp)).into_response())
+}
+
+/// GET /log/proof?seq=N&count=M → audit log line `N` as written, and that
+/// it is in the tree of the `GET /log/root` head at `M` lines (default: the
+/// current one). `N` is the `proof.seq` of a by-hash lookup. Needs
+/// `audit_file`; a line compacted away (`[retention]`) is `410 compacted`.
+async fn handle_get_log_proof(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Response, ApiError> {
+    let now = state.default.shared.clock.now();
+    let number = |name: &str| {
+        query
+            .get(name)
+            .map(|v| {
+                v.parse::<u64>().map_err(|_| {
+                    ApiError::new(
+                        StatusCode::BAD_REQUEST,
+                        "invalid_query",
+                        format!("{} must be a non-negative integer", name),
+                    )
+                })
+            })
+            .transpose()
+    };
+    let (seq, count) = (number("seq")?, number("count")?);
+    let Some(seq) = seq.filter(|seq| *seq >= 1) else {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_query",
+            "seq must be a line number from 1",
+        ));
+    };
+    if count.is_some_and(|count| seq > count) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_query",
+            "seq must not be past count",
+        ));
+    }
+    if state.default.shared.audit.is_none() {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_enabled",
+            "The log needs audit_file",
+        ));
+    }
+
+    let reading = state.clone();
+    let proof = tokio::task::spawn_blocking(move || {
+        let audit = reading
+            .default
+            .shared
+            .audit
+            .as_ref()
+            .expect("checked above");
+        audit.inclusion_proof(seq, count.unwrap_or_else(|| audit.last_seq()))
+    })
+    .await
+    .map_err(std::io::Error::other)
+    .and_then(|result| result)
+    .map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => ApiError::new(StatusCode::GONE, "compacted", e.to_string()),
+        _ => {
+            error!("{} Failed to prove inclusion: {}", now.to_rfc3339(), e);
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "audit_error",
+                "Audit log error",
+            )
+        }
+    })?
+    .ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            "unknown_seq",
+            format!("The log has no line {} under that count", seq),
+        )
+    })?;
+    info!(
+        "{} Request: GET /log/proof seq={} count={}",
+        now.to_rfc3339(),
+        seq,
+        proof.count
+    );
+    let resp = InclusionResponse {
+        request: "PROOF",
+        proof,
+    };
+    Ok((StatusCode::OK, JsonResponse(resp)).into_response())
+}
+
+/// The part of `/sign` and `/renew` that signs, run on the `SignPool`
+struct SignJob {
+    kind: SignKind,
+    message: Message,
+    /// Hex SHA-256 of `message`
+    hash: String,
+    digest: [u8; 32],
+    /// The key the request picked
+    key: Arc<TenantKey>,
+    /// The `[policies]` name it is issued under
+    policy: Option<String>,
+    encoding: SignatureEncoding,
+    text_format: TextFormat,
+    format: ResponseFormat,
+    client: Option<SocketAddr>,
+}
+
+impl SignJob {
+    /// Issues the timestamp and shapes it into the response body; `true`
+    /// with a first-seen timestamp
+    fn run(self, tenant: &Tenant) -> Result<(Issued, bool), SignError> {
+        let SignJob {
+            kind,
+            ref message,
+            ref hash,
+            digest,
+            key,
+            policy,
+            encoding,
+            text_format,
+            format,
+            client,
+        } = self;
+        let now = tenant.shared.clock.now();
+        let issued = tenant.issue(
+            message.as_bytes(),
+            key,
+            policy.as_deref(),
+            encoding,
+            kind.audit_kind(format),
+            client,
+        )?;
+        let IssuedTimestamp {
+            time_signed,
+            signature,
+            serial,
+            accuracy,
+            first_seen,
+            key,
+        } = issued;
+        #[cfg(feature = "otlp")]
+        let _respond = trace::span("respond");
+        let sig_text = text_format.encode(&signature);
+        let body = match format {
+            ResponseFormat::Json => Issued::Json({
+                let (text, b64) = message.fields();
+                serde_json::to_vec(&SignResponse {
+                    request: kind.request(),
+                    message: text,
+                    message_b64: b64,
+                    time_signed,
+                    signature: &sig_text,
+                    accuracy,
+                    encoding,
+                    format: text_format,
+                    signature_alg: SignatureAlg::default(),
+                    hash_alg: HashAlg::default(),
+                    kid: &key.key_id,
+                    policy: policy.as_deref(),
+                    first_seen,
+                })
+                .map_err(|e| {
+                    error!("{} Failed to encode response: {}", now.to_rfc3339(), e);
+                    SignError::Encoding
+                })?
+            }),
+            ResponseFormat::Jws => {
+                let claims = JwsClaims {
+                    msg_hash: hash.to_string(),
+                    iat: time_signed.timestamp(),
+                    serial,
+                    kid: key.key_id.clone(),
+                    policy,
+                    day_root: tenant.shared.day_root(time_signed).map(hex::encode),
+                };
+                Issued::Jws(jws::encode(&claims, |input| key.sign(input, now))?)
+            }
+            ResponseFormat::Cose => {
+                let claims = CoseClaims {
+                    msg_hash: digest,
+                    iat: time_signed.timestamp(),
+                    serial,
+                    kid: key.thumbprint,
+                    policy,
+                    day_root: tenant.shared.day_root(time_signed),
+                };
+                Issued::Cose(cose::encode(&claims, |input| key.sign(input, now))?)
+            }
+        };
+
+        if tenant.shared.sampled() {
+            info!(
+                "{} Request: POST {}/{} {} → response {}{}",
+                now.to_rfc3339(),
+                tenant_prefix(tenant),
+                kind.path(),
+                tenant.shared.logged(&message, message.as_bytes()),
+                tenant.shared.logged_sig(&sig_text),
+                if first_seen { " (first seen)" } else { "" }
+            );
+        }
+        Ok((body, first_seen))
+    }
+}
+
+/// `503 overloaded` for a request the sign pool's queue had no room for.
+/// Not a plain `ApiError`: it also carries `Retry-After`.
+fn saturated_response(tenant: &Tenant, now: DateTime<Utc>, route: &str) -> Response {
+    warn!(
+        "{} Signing queue full; refusing {}",
+        now.to_rfc3339(),
+        route
+    );
+    overloaded_response(
+        tenant.shared.pool.retry_after_secs,
+        "Too many signing requests, retry later",
+    )
+}
+
+/// `503` with `Retry-After`, for when the signing queue or `[limits]` is full
+fn overloaded_response(retry_after_secs: u64, message: &str) -> Response {
+    let mut response =
+        ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "overloaded", message).into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after_secs));
+    response
+}
+
+/// GET /log/stream → Server-Sent Events, one `timestamp` event per issued entry
+///
+/// Each event's data is the JSON `LogEntry` (serial, hash, time-signed,
+/// signature). Subscribers only see entries issued after they connect; if one
+/// falls more than `LOG_STREAM_CAPACITY` entries behind, the skipped entries
+/// are dropped and the gap is visible in the serial numbers.
+async fn handle_log_stream(
+    TenantRef(tenant): TenantRef,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    info!(
+        "{} Request: GET {}/log/stream → subscriber connected",
+        Utc::now().to_rfc3339(),
+        tenant_prefix(&tenant)
+    );
+    let stream = BroadcastStream::new(tenant.log_tx.subscribe()).filter_map(|item| match item {
+        Ok(entry) => Event::default()
+            .event("timestamp")
+            .json_data(&entry)
+            .ok()
+            .map(Ok),
+        Err(e) => {
+            warn!(
+                "{} /log/stream subscriber lagged: {}",
+                Utc::now().to_rfc3339(),
+                e
+            );
+            None
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Route prefix of a tenant for log lines ("" for the default tenant)
+fn tenant_prefix(tenant: &Tenant) -> String {
+    tenant
+        .name
+        .as_ref()
+        .map(|n| format!("/t/{}", n))
+        .unwrap_or_default()
+}
+
+impl ApiError {
+    fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        ApiError {
+            code: code.to_string(),
+            message: message.into(),
+            request_id: None,
+            field: None,
+            status: Some(status.as_u16()),
+        }
+    }
+
+    fn for_field(mut self, field: Option<String>) -> Self {
+        self.field = field;
+        self
+    }
+}
+
+/// Every error response: an `ApiError` body (`{"error": "...", "code": "..."}`,
+/// where `code` is stable and meant for programs, `error` for people). The
+/// `request_id` middleware fills in `request-id`. Handlers return
+/// `Result<_, ApiError>` rather than building error responses themselves.
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self
+            .status
+            .and_then(|s| StatusCode::from_u16(s).ok())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        (status, Extension(self.clone()), JsonResponse(self)).into_response()
+    }
+}
+
+impl From<SignError> for ApiError {
+    fn from(e: SignError) -> Self {
+        ApiError::new(e.status(), e.code(), e.message())
+    }
+}
+
+/// Uses the client's `X-Request-Id` if it is short and printable, otherwise
+/// makes one up. The id goes back in the `X-Request-Id` response header and
+/// into the body of error responses.
+async fn request_id(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(new_request_id);
+    // For the layers and handlers inside, e.g. `[latency]` logging
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        request.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    let mut response = next.run(request).await;
+    if let Some(mut err) = response.extensions_mut().remove::<ApiError>() {
+        err.request_id = Some(id.clone());
+        if let Ok(body) = serde_json::to_vec(&err) {
+            let (mut parts, _) = response.into_parts();
+            parts.headers.remove(header::CONTENT_LENGTH);
+            response = Response::from_parts(parts, Body::from(body));
+        }
+    }
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// The `[cors]` table, parsed into header values once at startup
+struct Cors {
+    /// `allowed_origins` contains `*`
+    any_origin: bool,
+    origins: Vec<HeaderValue>,
+    methods: HeaderValue,
+    headers: HeaderValue,
+    max_age: HeaderValue,
+}
+
+impl Cors {
+    /// `None` when no origins are allowed, i.e. CORS is off
+    fn new(config: &CorsConfig) -> Result<Option<Self>, String> {
+        if config.allowed_origins.is_empty() {
+            return Ok(None);
+        }
+        let invalid = |what: &str, value: &str| format!("Invalid CORS {} '{}'", what, value);
+        let origins = config
+            .allowed_origins
+            .iter()
+            .filter(|o| *o != "*")
+            .map(|o| HeaderValue::from_str(o).map_err(|_| invalid("origin", o)))
+            .collect::<Result<_, _>>()?;
+        for m in &config.allowed_methods {
+            Method::from_bytes(m.as_bytes()).map_err(|_| invalid("method", m))?;
+        }
+        for h in &config.allowed_headers {
+            HeaderName::from_bytes(h.as_bytes()).map_err(|_| invalid("header", h))?;
+        }
+        let join = |values: &[String]| HeaderValue::from_str(&values.join(", "));
+        Ok(Some(Self {
+            any_origin: config.allowed_origins.iter().any(|o| o == "*"),
+            origins,
+            methods: join(&config.allowed_methods).map_err(|e| e.to_string())?,
+            headers: join(&config.allowed_headers).map_err(|e| e.to_string())?,
+            max_age: HeaderValue::from(config.max_age_secs),
+        }))
+    }
+
+    /// What `Access-Control-Allow-Origin` says to `origin`, if it may call us
+    fn allow_origin(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        if self.any_origin {
+            Some(HeaderValue::from_static("*"))
+        } else {
+            self.origins.contains(origin).then(|| origin.clone())
+        }
+    }
+}
+
+/// Answers preflights (`OPTIONS` with `Access-Control-Request-Method`) from
+/// allowed origins with `204`, and adds `Access-Control-Allow-Origin` to
+/// their other responses; requests from any other origin are left alone,
+/// so the browser blocks them
+async fn cors_headers(State(cors): State<Arc<Cors>>, request: Request, next: Next) -> Response {
+    let allow_origin = request
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|origin| cors.allow_origin(origin));
+    let Some(allow_origin) = allow_origin else {
+        return next.run(request).await;
+    };
+    let preflight = request.method() == Method::OPTIONS
+        && request
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+    let mut response = if preflight {
+        let mut response = StatusCode::NO_CONTENT.into_response();
+        let headers = response.headers_mut();
+        headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, cors.methods.clone());
+        headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, cors.headers.clone());
+        headers.insert(header::ACCESS_CONTROL_MAX_AGE, cors.max_age.clone());
+        response
+    } else {
+        let mut response = next.run(request).await;
+        response.headers_mut().insert(
+            header::ACCESS_CONTROL_EXPOSE_HEADERS,
+            HeaderValue::from_static(REQUEST_ID_HEADER),
+        );
+        response
+    };
+    let headers = response.headers_mut();
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+    headers.append(header::VARY, HeaderValue::from_static("origin"));
+    response
+}
+
+/// `{start time}-{counter}` in hex: unique per process, and across restarts
+fn new_request_id() -> String {
+    static START: std::sync::OnceLock<i64> = std::sync::OnceLock::new();
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let start = *START.get_or_init(|| Utc::now().timestamp_micros());
+    format!("{:x}-{:x}", start, NEXT.fetch_add(1, Ordering::Relaxed))
+}
+
+/// A `/sign` body that isn't the expected JSON: malformed or mistyped JSON
+/// is `422 invalid_json` (naming the `field` at fault, if any), a string
+/// that isn't UTF-8 `422 invalid_utf8`, a missing `Content-Type` `415`, an
+/// oversized body `413`
+fn json_rejection(rejection: JsonRejection) -> ApiError {
+    let now = Utc::now();
+    error!(
+        "{} Rejected /sign body: {}",
+        now.to_rfc3339(),
+        rejection.body_text()
+    );
+    match rejection {
+        JsonRejection::JsonDataError(_) | JsonRejection::JsonSyntaxError(_) => {
+            match json_error(&rejection) {
+                Some(error) => describe_json_error(error),
+                None => ApiError::new(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "invalid_json",
+                    rejection.body_text(),
+                ),
+            }
+        }
+        JsonRejection::MissingJsonContentType(_) => ApiError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "unsupported_media_type",
+            "Expected Content-Type: application/json",
+        ),
+        _ if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "payload_too_large",
+            "Request body too large",
+        ),
+        _ => ApiError::new(rejection.status(), "bad_request", rejection.body_text()),
+    }
+}
+
+/// The serde error behind a JSON rejection, with the path to the field
+fn json_error(rejection: &JsonRejection) -> Option<&serde_path_to_error::Error<serde_json::Error>> {
+    let mut source = std::error::Error::source(rejection);
+    while let Some(error) = source {
+        if let Some(error) = error.downcast_ref() {
+            return Some(error);
+        }
+        source = error.source();
+    }
+    None
+}
+
+/// `missing field 'message'`, `invalid value for 'message': invalid type:
+/// integer '42', expected a string`, `invalid JSON at line 1 column 12: ...`
+fn describe_json_error(error: &serde_path_to_error::Error<serde_json::Error>) -> ApiError {
+    let inner = error.inner();
+    let position = format!(" at line {} column {}", inner.line(), inner.column());
+    let text = inner.to_string();
+    let text = text
+        .strip_suffix(&position)
+        .unwrap_or(&text)
+        .replace('`', "'");
+    let path = error.path().to_string();
+    let within = |name: &str| match path.as_str() {
+        "." => name.to_string(),
+        path => format!("{}.{}", path, name),
+    };
+    let unprocessable =
+        |code, message: String| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, code, message);
+
+    match inner.classify() {
+        serde_json::error::Category::Data => {
+            if let Some(name) = text
+                .strip_prefix("missing field '")
+                .and_then(|rest| rest.strip_suffix('\''))
+            {
+                let field = within(name);
+                unprocessable("invalid_json", format!("missing field '{}'", field))
+                    .for_field(Some(field))
+            } else if path == "." {
+                unprocessable("invalid_json", text)
+            } else {
+                unprocessable(
+                    "invalid_json",
+                    format!("invalid value for '{}': {}", path, text),
+                )
+                .for_field(Some(path))
+            }
+        }
+        // serde_json checks strings are UTF-8, and refuses escapes of
+        // half a surrogate pair
+        _ if text.contains("invalid unicode code point") || text.contains("surrogate") => {
+            unprocessable(
+                "invalid_utf8",
+                format!("string is not valid UTF-8{}", position),
+            )
+            .for_field((path != ".").then_some(path))
+        }
+        _ => unprocessable(
+            "invalid_json",
+            format!("invalid JSON{}: {}", position, text),
+        ),
+    }
+}
+
+/// Axum answers a known path with the wrong method with an empty `405` and
+/// an `Allow` header; give it the usual JSON body, keeping the header
+async fn method_not_allowed_body(response: Response) -> Response {
+    if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return response;
+    }
+    error!("{} Wrong method, returning 405", Utc::now().to_rfc3339());
+    let mut json = ApiError::new(
+        StatusCode::METHOD_NOT_ALLOWED,
+        "method_not_allowed",
+        "Method not allowed",
     )
+    .into_response();
+    if let Some(allow) = response.headers().get(header::ALLOW) {
+        json.headers_mut().insert(header::ALLOW, allow.clone());
+    }
+    json
 }
 
-/// Fallback for any unsupported route
-async fn fallback_handler() -> impl IntoResponse {
+/// Fallback for any unknown path
+async fn fallback_handler() -> ApiError {
     let now = Utc::now();
-    error!("{} Invalid request, returning 400", now.to_rfc3339());
-    (StatusCode::BAD_REQUEST, "Invalid request")
+    error!("{} Unknown path, returning 404", now.to_rfc3339());
+    ApiError::new(StatusCode::NOT_FOUND, "not_found", "Not found")
 }
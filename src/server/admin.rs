@@ -0,0 +1,405 @@
+//! Operational endpoints (`[admin] listen`), served on a listener of their
+//! own so they are never reachable through the public port:
+//!
+//! - `POST /admin/rotate-key[?tenant=NAME]`: a new key for the tenant (the
+//!   default one without `tenant`), written over its key files
+//! - `POST /admin/pause-signing`, `POST /admin/resume-signing`: refuse
+//!   every `/sign` and `/renew` with `503 signing_paused` meanwhile
+//! - `GET /admin/stats`: keys, serials and counters of every tenant
+//! - `POST /admin/compact`: compact the audit log now (`[retention]`)
+//! - `GET /admin/compaction`: how much of it is compacted, and the latest
+//!   checkpoint
+//!
+//! Every request needs `Authorization: Bearer <token>`, with the token from
+//! `config::load_admin_token`.
+
+use super::listener::{self, Listener};
+use super::{AppState, Tenant, TenantKey, retention, tenant_prefix};
+use crate::ApiError;
+use crate::audit::Checkpoint;
+use crate::config::{
+    AdminConfig, ENV_PREFIX, load_admin_token, process_env, tenant_env_prefix, warn_if_unrestricted,
+};
+use axum::{
+    Router,
+    extract::{Query, Request, State},
+    http::{StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Json as JsonResponse, Response},
+    routing::{get, post},
+};
+use chrono::Utc;
+use ecdsa_lib::KeyPair;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::{error, info, warn};
+
+/// What the admin handlers share
+struct AdminState {
+    app: Arc<AppState>,
+    /// SHA-256 of the token, compared in constant time
+    token_hash: [u8; 32],
+    started: Instant,
+    /// Held while a key is written and swapped in, so rotations don't interleave
+    rotating: Mutex<()>,
+}
+
+/// Body returned by POST /admin/rotate-key
+#[derive(Serialize)]
+struct RotateResponse {
+    request: &'static str,
+    /// `None` for the default tenant
+    tenant: Option<String>,
+    #[serde(rename = "previous-key-id")]
+    previous_key_id: String,
+    /// Still needed to verify what the old key signed
+    #[serde(rename = "previous-public-key")]
+    previous_public_key: String,
+    #[serde(rename = "key-id")]
+    key_id: String,
+    #[serde(rename = "public-key")]
+    public_key: String,
+}
+
+/// Body returned by POST /admin/pause-signing and /admin/resume-signing
+#[derive(Serialize)]
+struct PauseResponse {
+    request: &'static str,
+    paused: bool,
+}
+
+/// Body returned by GET /admin/stats
+#[derive(Serialize)]
+struct StatsResponse {
+    request: &'static str,
+    #[serde(rename = "uptime-secs")]
+    uptime_secs: u64,
+    paused: bool,
+    /// Upstream URL when this is a read-only mirror
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mirror: Option<String>,
+    /// Lines in the audit log, when there is one
+    #[serde(rename = "audit-entries", skip_serializing_if = "Option::is_none")]
+    audit_entries: Option<u64>,
+    tenants: Vec<TenantStats>,
+}
+
+/// Body returned by POST /admin/compact and GET /admin/compaction
+#[derive(Serialize)]
+struct CompactionResponse {
+    request: &'static str,
+    /// Entries just compacted, for POST /admin/compact
+    #[serde(skip_serializing_if = "Option::is_none")]
+    moved: Option<u64>,
+    /// `[retention] full_days` (0 = never compact)
+    #[serde(rename = "full-days")]
+    full_days: u64,
+    /// Entries kept without their signatures
+    #[serde(rename = "compacted-entries")]
+    compacted_entries: u64,
+    /// Entries still in the audit log as written
+    #[serde(rename = "full-entries")]
+    full_entries: u64,
+    /// Signs the compacted entries; `None` until something is compacted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checkpoint: Option<Checkpoint>,
+}
+
+#[derive(Serialize)]
+struct TenantStats {
+    /// `None` for the default tenant
+    tenant: Option<String>,
+    #[serde(rename = "key-id")]
+    key_id: String,
+    /// Serial of the last timestamp issued since startup
+    serial: u64,
+    /// Signatures by the current key (as in `GET /key/stats`)
+    signatures: u64,
+    #[serde(rename = "log-subscribers")]
+    log_subscribers: usize,
+}
+
+/// Binds `config.listen` and serves the admin API on it in the background
+pub(super) async fn spawn(
+    app: Arc<AppState>,
+    config: &AdminConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let token = load_admin_token(config, &process_env)?;
+    let listener = Listener::bind(&[&config.listen], 0)
+        .await
+        .map_err(|e| format!("Failed to bind the admin API to {}: {}", config.listen, e))?;
+    info!("Admin API on {}", listener.describe());
+
+    let state = Arc::new(AdminState {
+        app,
+        token_hash: Sha256::digest(token.as_bytes()).into(),
+        started: Instant::now(),
+        rotating: Mutex::new(()),
+    });
+    let router = Router::new()
+        .route("/admin/rotate-key", post(handle_rotate_key))
+        .route("/admin/pause-signing", post(handle_pause))
+        .route("/admin/resume-signing", post(handle_resume))
+        .route("/admin/stats", get(handle_stats))
+        .route("/admin/compact", post(handle_compact))
+        .route("/admin/compaction", get(handle_compaction))
+        .fallback(super::fallback_handler)
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state);
+    tokio::spawn(async move {
+        if let Err(e) = listener::serve(listener, router).await {
+            error!("Admin API error: {}", e);
+        }
+    });
+    Ok(())
+}
+
+/// Lets only requests with the admin bearer token through
+async fn authorize(State(state): State<Arc<AdminState>>, request: Request, next: Next) -> Response {
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    // Comparing hashes keeps the token's length out of the timing too
+    let hash: [u8; 32] = Sha256::digest(presented.trim().as_bytes()).into();
+    let differs = hash
+        .iter()
+        .zip(&state.token_hash)
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if presented.is_empty() || differs != 0 {
+        error!(
+            "{} Admin: rejected {} {} (bad or missing token)",
+            Utc::now().to_rfc3339(),
+            request.method(),
+            request.uri().path()
+        );
+        let mut resp = ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "Admin requests need the bearer token",
+        )
+        .into_response();
+        resp.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            header::HeaderValue::from_static("Bearer"),
+        );
+        return resp;
+    }
+    next.run(request).await
+}
+
+/// POST /admin/rotate-key[?tenant=NAME] → generates a key for the tenant,
+/// writes it over the tenant's key files and signs with it from then on
+///
+/// Keys that aren't files (`[kms]`, a mirror's) can't be rotated here, and
+/// neither can the default tenant's while it signs the audit log: lines
+/// after a rotation would no longer verify as one log.
+async fn handle_rotate_key(
+    State(state): State<Arc<AdminState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<Response, ApiError> {
+    let now = Utc::now();
+    let tenant: Arc<Tenant> = match query.get("tenant") {
+        None => state.app.default.clone(),
+        Some(name) => state.app.tenants.get(name).cloned().ok_or_else(|| {
+            ApiError::new(StatusCode::NOT_FOUND, "unknown_tenant", "Unknown tenant")
+        })?,
+    };
+    let Some((private_key_file, public_key_file)) = tenant.key_files.clone() else {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "not_rotatable",
+            "This tenant's key is not a key file",
+        ));
+    };
+    if tenant.name.is_none() && tenant.shared.audit.is_some() {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "audit_key",
+            "The default key signs the audit log; rotate it with the server stopped and a new audit_file",
+        ));
+    }
+
+    // Written before it is used, so a restart keeps signing with it
+    let rotating = state.clone();
+    let swapping = tenant.clone();
+    let (old, new): (Arc<TenantKey>, Arc<TenantKey>) = tokio::task::spawn_blocking(move || {
+        let _rotating = rotating.rotating.lock().unwrap();
+        let keypair = KeyPair::generate();
+        keypair.save_to_files_secure(&private_key_file, &public_key_file, true)?;
+        warn_if_unrestricted(&private_key_file);
+        let old = swapping.replace_key(Box::new(keypair));
+        Ok::<_, std::io::Error>((old, swapping.key()))
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|result| result.map_err(|e| e.to_string()))
+    .map_err(|e| {
+        error!("{} Admin: failed to rotate key: {}", now.to_rfc3339(), e);
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "rotate_failed",
+            "Failed to write the new key",
+        )
+    })?;
+
+    let prefix = match &tenant.name {
+        None => ENV_PREFIX.to_string(),
+        Some(name) => tenant_env_prefix(name),
+    };
+    if process_env(&format!("{}PRIVATE_KEY", prefix)).is_some() {
+        warn!(
+            "{} {}PRIVATE_KEY is set and will replace the rotated key on restart",
+            now.to_rfc3339(),
+            prefix
+        );
+    }
+    info!(
+        "{} Admin: rotated the key of {}: {} → {}",
+        now.to_rfc3339(),
+        match tenant_prefix(&tenant).as_str() {
+            "" => "the default tenant".to_string(),
+            prefix => prefix.to_string(),
+        },
+        old.key_id,
+        new.key_id
+    );
+    let resp = RotateResponse {
+        request: "ROTATE",
+        tenant: tenant.name.clone(),
+        previous_key_id: old.key_id.clone(),
+        previous_public_key: old.public_key.to_string(),
+        key_id: new.key_id.clone(),
+        public_key: new.public_key.to_string(),
+    };
+    Ok((StatusCode::OK, JsonResponse(resp)).into_response())
+}
+
+/// POST /admin/pause-signing → refuse to sign until resumed
+async fn handle_pause(State(state): State<Arc<AdminState>>) -> impl IntoResponse {
+    set_paused(&state, true)
+}
+
+/// POST /admin/resume-signing → sign again
+async fn handle_resume(State(state): State<Arc<AdminState>>) -> impl IntoResponse {
+    set_paused(&state, false)
+}
+
+fn set_paused(state: &AdminState, paused: bool) -> (StatusCode, JsonResponse<PauseResponse>) {
+    state
+        .app
+        .default
+        .shared
+        .paused
+        .store(paused, Ordering::SeqCst);
+    info!(
+        "{} Admin: signing {}",
+        Utc::now().to_rfc3339(),
+        if paused { "paused" } else { "resumed" }
+    );
+    let resp = PauseResponse {
+        request: if paused { "PAUSE" } else { "RESUME" },
+        paused,
+    };
+    (StatusCode::OK, JsonResponse(resp))
+}
+
+/// GET /admin/stats → uptime, whether signing is paused, and per tenant its
+/// key, last serial and signature count
+async fn handle_stats(State(state): State<Arc<AdminState>>) -> impl IntoResponse {
+    let shared = &state.app.default.shared;
+    let mut named: Vec<&Arc<Tenant>> = state.app.tenants.values().collect();
+    named.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut tenants = vec![&state.app.default];
+    tenants.extend(named);
+    let tenants = tenants
+        .into_iter()
+        .map(|tenant| {
+            let key = tenant.key();
+            TenantStats {
+                tenant: tenant.name.clone(),
+                signatures: shared.key_stats.usage(&key.key_id).signatures,
+                key_id: key.key_id.clone(),
+                serial: tenant.serial(),
+                log_subscribers: tenant.log_tx.receiver_count(),
+            }
+        })
+        .collect();
+    info!("{} Admin: stats", Utc::now().to_rfc3339());
+    let resp = StatsResponse {
+        request: "STATS",
+        uptime_secs: state.started.elapsed().as_secs(),
+        paused: shared.paused.load(Ordering::SeqCst),
+        mirror: shared.mirror.clone(),
+        audit_entries: shared.audit.as_ref().map(|audit| audit.last_seq()),
+        tenants,
+    };
+    (StatusCode::OK, JsonResponse(resp))
+}
+
+/// POST /admin/compact → compacts the audit log as the `[retention]` job
+/// would, without waiting for it
+async fn handle_compact(State(state): State<Arc<AdminState>>) -> Result<Response, ApiError> {
+    let shared = state.app.default.shared.clone();
+    if shared.full_days == 0 {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "retention_disabled",
+            "Compaction needs [retention] full_days",
+        ));
+    }
+    let moved = tokio::task::spawn_blocking(move || retention::compact(&shared))
+        .await
+        .map_err(std::io::Error::other)
+        .and_then(|result| result)
+        .map_err(|e| {
+            error!(
+                "{} Admin: failed to compact: {}",
+                Utc::now().to_rfc3339(),
+                e
+            );
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "compact_failed",
+                "Failed to compact the audit log",
+            )
+        })?;
+    info!(
+        "{} Admin: compacted {} audit log entries",
+        Utc::now().to_rfc3339(),
+        moved
+    );
+    let resp = compaction(&state, "COMPACT", Some(moved));
+    Ok((StatusCode::OK, JsonResponse(resp)).into_response())
+}
+
+/// GET /admin/compaction → compacted and full entry counts, and the
+/// checkpoint over the compacted ones
+async fn handle_compaction(State(state): State<Arc<AdminState>>) -> impl IntoResponse {
+    info!("{} Admin: compaction", Utc::now().to_rfc3339());
+    (
+        StatusCode::OK,
+        JsonResponse(compaction(&state, "COMPACTION", None)),
+    )
+}
+
+fn compaction(state: &AdminState, request: &'static str, moved: Option<u64>) -> CompactionResponse {
+    let shared = &state.app.default.shared;
+    let checkpoint = shared.audit.as_ref().and_then(|audit| audit.checkpoint());
+    let compacted_entries = checkpoint.as_ref().map_or(0, |checkpoint| checkpoint.count);
+    let last_seq = shared.audit.as_ref().map_or(0, |audit| audit.last_seq());
+    CompactionResponse {
+        request,
+        moved,
+        full_days: shared.full_days,
+        compacted_entries,
+        full_entries: last_seq - compacted_entries,
+        checkpoint,
+    }
+}
@@ -0,0 +1,189 @@
+//! Anchoring the log's head with an external witness (`[anchor] witness`)
+//!
+//! Every `interval_secs`, if the log grew since the last anchoring, the
+//! server signs its tree head and `POST`s it as JSON to the witness. What
+//! the witness answers (any JSON: a countersignature, a receipt, a
+//! calendar's pending proof) is kept with the head in `file`, one
+//! [`Anchor`] per line. `GET /anchor/{serial}` then gives the timestamp's
+//! line in the tree of the first head anchored after it, so the witness's
+//! evidence of when it saw that head covers the timestamp too.
+
+#[cfg(feature = "client")]
+use super::AppState;
+use crate::transparency::Anchor;
+#[cfg(feature = "client")]
+use crate::wire;
+use std::fs;
+#[cfg(feature = "client")]
+use std::fs::OpenOptions;
+#[cfg(feature = "client")]
+use std::io::Write;
+#[cfg(feature = "client")]
+use std::sync::Arc;
+use std::sync::Mutex;
+#[cfg(feature = "client")]
+use std::time::Duration;
+#[cfg(feature = "client")]
+use tracing::{error, info};
+
+/// The heads the witness answered for, oldest first
+pub(super) struct Anchors {
+    /// `None` keeps them in memory only
+    file: Option<String>,
+    anchors: Mutex<Vec<Anchor>>,
+}
+
+impl Anchors {
+    /// Resumes from `file` (NDJSON) if it exists. An empty `file` keeps
+    /// the anchors in memory only.
+    pub fn open(file: &str) -> std::io::Result<Self> {
+        let file = (!file.is_empty()).then(|| file.to_string());
+        let anchors = match &file {
+            Some(path) if fs::exists(path)? => fs::read_to_string(path)?
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| {
+                    serde_json::from_str(line).map_err(|e| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("Invalid anchor in {}: {}", path, e),
+                        )
+                    })
+                })
+                .collect::<std::io::Result<_>>()?,
+            _ => Vec::new(),
+        };
+        Ok(Self {
+            file,
+            anchors: Mutex::new(anchors),
+        })
+    }
+
+    /// Lines covered by the last anchored head (0 before the first)
+    pub fn last_count(&self) -> u64 {
+        let anchors = self.anchors.lock().unwrap();
+        anchors.last().map_or(0, |anchor| anchor.head.count)
+    }
+
+    /// The first anchor whose head covers line `seq`
+    pub fn covering(&self, seq: u64) -> Option<Anchor> {
+        let anchors = self.anchors.lock().unwrap();
+        anchors
+            .iter()
+            .find(|anchor| anchor.head.count >= seq)
+            .cloned()
+    }
+
+    /// Keeps `anchor`, synced to disk before this returns
+    #[cfg(feature = "client")]
+    fn push(&self, anchor: Anchor) -> std::io::Result<()> {
+        let mut anchors = self.anchors.lock().unwrap();
+        if let Some(path) = &self.file {
+            let line = serde_json::to_string(&anchor).expect("anchor serializes");
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", line)?;
+            file.sync_data()?;
+        }
+        anchors.push(anchor);
+        Ok(())
+    }
+}
+
+/// Anchors the head with `witness` every `interval`, for as long as
+/// the server runs
+#[cfg(feature = "client")]
+pub(super) fn spawn(state: Arc<AppState>, witness: String, interval: Duration) {
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        loop {
+            match anchor(&state, &client, &witness).await {
+                Ok(None) => {}
+                Ok(Some(count)) => info!(
+                    "{} Anchored the head at {} lines with {}",
+                    state.default.shared.clock.now().to_rfc3339(),
+                    count,
+                    witness
+                ),
+                Err(e) => error!(
+                    "{} Failed to anchor the head with {}: {}",
+                    state.default.shared.clock.now().to_rfc3339(),
+                    witness,
+                    e
+                ),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// Posts the head if the log grew since the last anchor; returns its
+/// count if it did
+#[cfg(feature = "client")]
+async fn anchor(
+    state: &Arc<AppState>,
+    client: &reqwest::Client,
+    witness: &str,
+) -> Result<Option<u64>, String> {
+    let shared = &state.default.shared;
+    let (Some(audit), Some(anchors)) = (&shared.audit, &shared.anchors) else {
+        return Ok(None);
+    };
+    if audit.last_seq() <= anchors.last_count() {
+        return Ok(None);
+    }
+
+    // 1) The head now
+    let now = wire::canonical_time(shared.clock.now());
+    let signing = state.clone();
+    let head = tokio::task::spawn_blocking(move || {
+        let audit = signing
+            .default
+            .shared
+            .audit
+            .as_ref()
+            .expect("checked above");
+        audit.tree_head(&now)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("failed to sign the head: {}", e))?;
+
+    // 2) What the witness makes of it
+    let time = wire::canonical_time(shared.clock.now());
+    let resp = client
+        .post(witness)
+        .json(&head)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("it answered {}", resp.status()));
+    }
+    let receipt: serde_json::Value = resp
+        .json()
+        .await
+        .map_err(|e| format!("its answer is not JSON: {}", e))?;
+
+    // 3) Kept, for `GET /anchor/{serial}`
+    let count = head.count;
+    let anchor = Anchor {
+        witness: witness.to_string(),
+        time,
+        head,
+        receipt,
+    };
+    let keeping = state.clone();
+    tokio::task::spawn_blocking(move || {
+        let anchors = keeping
+            .default
+            .shared
+            .anchors
+            .as_ref()
+            .expect("checked above");
+        anchors.push(anchor)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("failed to keep the anchor: {}", e))?;
+    Ok(Some(count))
+}
@@ -0,0 +1,206 @@
+//! Sealing the audit log into object storage (`[archive]`, the `s3` feature)
+//!
+//! Every `interval_secs`, each full run of `segment_entries` lines not yet
+//! archived is sealed: exported like `vts-admin export`
+//! (`AuditLog::export_segment`) and uploaded to
+//! `{prefix}segments/{first}-{last}.vtsa`. Its manifest, the segment's
+//! signed root (the `prev` it links to, its line count and hash), also goes
+//! up on its own as `...manifest.json`, so a segment can be checked against
+//! its neighbours without downloading them. `{prefix}head` holds the last
+//! sealed `seq`, so a restarted server carries on where it stopped.
+//!
+//! Segments are ordinary export archives: importing them in order with
+//! `vts-admin import` rebuilds the log. The local `audit_file` is kept as it
+//! is, except that `[retention]` compacts only lines sealed here.
+
+use super::AppState;
+use crate::config::ArchiveConfig;
+use crate::sigv4::{self, Credentials, Scope};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tracing::{error, info};
+
+type ArchiveError = Box<dyn std::error::Error + Send + Sync>;
+
+/// One bucket of S3-compatible storage, under a key prefix
+pub(super) struct ObjectStore {
+    config: ArchiveConfig,
+    credentials: Credentials,
+    client: reqwest::Client,
+}
+
+impl ObjectStore {
+    /// Reads the credentials; fails if they aren't set
+    pub fn new(config: &ArchiveConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            config: config.clone(),
+            credentials: Credentials::from_env()?,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+        })
+    }
+
+    /// The object `name` (under the prefix), `None` if there is none
+    async fn get(&self, name: &str) -> Result<Option<Vec<u8>>, ArchiveError> {
+        let response = self.request("GET", name, Vec::new()).await?;
+        match response.status() {
+            reqwest::StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(response.bytes().await?.to_vec())),
+            status => Err(format!("GET {}: HTTP {}", name, status).into()),
+        }
+    }
+
+    async fn put(&self, name: &str, body: Vec<u8>) -> Result<(), ArchiveError> {
+        let response = self.request("PUT", name, body).await?;
+        if !response.status().is_success() {
+            return Err(format!("PUT {}: HTTP {}", name, response.status()).into());
+        }
+        Ok(())
+    }
+
+    /// A path-style request for `{bucket}/{prefix}{name}`, signed with
+    /// SigV4 for the `s3` service
+    async fn request(
+        &self,
+        method: &str,
+        name: &str,
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response, ArchiveError> {
+        let endpoint = reqwest::Url::parse(&self.config.endpoint)?;
+        let host = match endpoint.port() {
+            Some(port) => format!("{}:{}", endpoint.host_str().unwrap_or_default(), port),
+            None => endpoint.host_str().unwrap_or_default().to_string(),
+        };
+        let path = sigv4::uri_encode(&format!(
+            "{}/{}/{}{}",
+            endpoint.path().trim_end_matches('/'),
+            self.config.bucket,
+            self.config.prefix,
+            name
+        ));
+        let payload_hash = hex::encode(Sha256::digest(&body));
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+        // Signed headers, sorted by name
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let scope = Scope {
+            region: &self.config.region,
+            service: "s3",
+        };
+        let authorization = sigv4::authorization(
+            &self.credentials,
+            &scope,
+            method,
+            &path,
+            &amz_date,
+            &headers,
+            &payload_hash,
+        );
+
+        let url = endpoint.join(&path)?;
+        let method = reqwest::Method::from_bytes(method.as_bytes())?;
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("authorization", authorization)
+            .body(body);
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(*name, value);
+        }
+        Ok(request.send().await?)
+    }
+}
+
+/// Seals full segments every `interval_secs`, for as long as the server runs
+pub(super) fn spawn(state: Arc<AppState>, store: ObjectStore) {
+    tokio::spawn(async move {
+        let interval = Duration::from_secs(store.config.interval_secs);
+        let mut sealed = None;
+        loop {
+            match seal(&state, &store, &mut sealed).await {
+                Ok(0) => {}
+                Ok(n) => info!(
+                    "{} Archived {} audit log segment(s), up to entry {}",
+                    state.default.shared.clock.now().to_rfc3339(),
+                    n,
+                    sealed.unwrap_or_default()
+                ),
+                Err(e) => error!(
+                    "{} Failed to archive the audit log (retrying in {}s): {}",
+                    state.default.shared.clock.now().to_rfc3339(),
+                    interval.as_secs(),
+                    e
+                ),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// Uploads every full segment after `sealed` (read from `head` the first
+/// time) and returns how many
+async fn seal(
+    state: &Arc<AppState>,
+    store: &ObjectStore,
+    sealed: &mut Option<u64>,
+) -> Result<u64, ArchiveError> {
+    // 1) Where the archive stands
+    let mut done = match *sealed {
+        Some(seq) => seq,
+        None => match store.get("head").await? {
+            Some(head) => String::from_utf8(head)?.trim().parse()?,
+            None => 0,
+        },
+    };
+    *sealed = Some(done);
+    mark_archived(state, done);
+
+    // 2) Seal each full segment, then move the head past it
+    let size = store.config.segment_entries;
+    let mut count = 0;
+    loop {
+        let reading = state.clone();
+        let segment = tokio::task::spawn_blocking(move || {
+            let audit = reading.default.shared.audit.as_ref()?;
+            (audit.last_seq() >= done + size).then(|| audit.export_segment(done, size))
+        })
+        .await?;
+        let Some(segment) = segment else {
+            return Ok(count);
+        };
+        let (manifest, archive) = segment?;
+        let name = format!("segments/{:012}-{:012}", done + 1, done + size);
+        store.put(&format!("{}.vtsa", name), archive).await?;
+        store
+            .put(
+                &format!("{}.manifest.json", name),
+                serde_json::to_vec(&manifest)?,
+            )
+            .await?;
+        store
+            .put("head", (done + size).to_string().into_bytes())
+            .await?;
+        done += size;
+        *sealed = Some(done);
+        mark_archived(state, done);
+        count += 1;
+    }
+}
+
+/// Lets `[retention]` compact up to `seq`
+fn mark_archived(state: &AppState, seq: u64) {
+    if let Some(archived) = &state.default.shared.archived {
+        archived.store(seq, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,199 @@
+//! Co-signing peers' tree heads (`[gossip] peers`)
+//!
+//! Every `interval_secs` the server fetches each peer's `GET /log/root`,
+//! checks the head against the peer's key (pinned by `kid`) and against
+//! the last head it saw from that peer, and co-signs it into its own
+//! `witnessed`. It also takes from the peer's `witnessed` the
+//! co-signature of this log's head, checks it covers the log as it was at
+//! that `count`, and serves it under `cosignatures`. So each peer holds
+//! signed evidence of the other's log at a time, and backdating an entry
+//! before it would need more than one server's key.
+//!
+//! A grown head is co-signed only with the peer's `GET /log/consistency`
+//! proof that it extends the last one; a head that doesn't, has fewer
+//! lines than before, or has another root for the same count, is reported
+//! and not co-signed.
+
+use super::AppState;
+use crate::audit::{ConsistencyProof, Cosignature, TreeHead};
+use crate::config::GossipPeer;
+use crate::ecdsa_requests::nonblocking;
+use crate::{jws, wire};
+use ecdsa_lib::PublicKey;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// The part of a peer's `GET /log/root` gossip reads
+#[derive(Deserialize)]
+struct PeerRoot {
+    #[serde(flatten)]
+    head: TreeHead,
+    #[serde(default)]
+    witnessed: Vec<Cosignature>,
+}
+
+/// What is known of one peer between rounds
+struct Peer {
+    config: GossipPeer,
+    /// Fetched once, matching `config.kid`
+    key: Option<PublicKey>,
+    /// The last head it served
+    head: Option<TreeHead>,
+}
+
+/// Gossips with `peers` every `interval`, for as long as the server runs
+pub(super) fn spawn(state: Arc<AppState>, peers: Vec<GossipPeer>, interval: Duration) {
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let mut peers: Vec<Peer> = peers
+            .into_iter()
+            .map(|config| Peer {
+                config,
+                key: None,
+                head: None,
+            })
+            .collect();
+        loop {
+            for peer in &mut peers {
+                if let Err(e) = round(&state, &client, peer).await {
+                    error!(
+                        "{} Gossip with {}: {}",
+                        state.default.shared.clock.now().to_rfc3339(),
+                        peer.config.url,
+                        e
+                    );
+                }
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// One exchange with `peer`
+async fn round(
+    state: &Arc<AppState>,
+    client: &reqwest::Client,
+    peer: &mut Peer,
+) -> Result<(), String> {
+    let shared = &state.default.shared;
+    if shared.audit.is_none() {
+        return Ok(());
+    }
+    let url = peer.config.url.trim_end_matches('/').to_string();
+
+    // 1) The peer's key, once, if it is the pinned one
+    let key = match peer.key {
+        Some(key) => key,
+        None => {
+            let key = nonblocking::request_key(&url)
+                .await
+                .map_err(|e| format!("failed to get its key: {}", e))?
+                .key()
+                .ok_or("it served an invalid key")?;
+            if jws::key_id(&key) != peer.config.kid {
+                return Err(format!(
+                    "it serves key {}, not the pinned {}",
+                    jws::key_id(&key),
+                    peer.config.kid
+                ));
+            }
+            *peer.key.insert(key)
+        }
+    };
+
+    // 2) Its head, signed by that key and extending the last one
+    let resp = client
+        .get(format!("{}/v1/log/root", url))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("GET /log/root answered {}", resp.status()));
+    }
+    let PeerRoot { head, witnessed } = resp.json().await.map_err(|e| e.to_string())?;
+    if !head.verify(&key) {
+        return Err("its tree head does not verify".to_string());
+    }
+    if let Some(last) = &peer.head
+        && !extends(client, &url, last, &head).await?
+    {
+        return Err(format!(
+            "ALERT: its head ({} lines, root {}) does not extend the last one ({} lines, root {}); not co-signing",
+            head.count, head.root, last.count, last.root
+        ));
+    }
+
+    // 3) Co-sign it, and pick its latest valid co-signature of this log
+    let now = wire::canonical_time(shared.clock.now());
+    let signing = state.clone();
+    let cosigning = head.clone();
+    let (cosignature, ours) = tokio::task::spawn_blocking(move || {
+        let audit = signing
+            .default
+            .shared
+            .audit
+            .as_ref()
+            .expect("checked above");
+        let ours = witnessed
+            .into_iter()
+            .filter(|c| c.log == audit.kid() && c.verify(&key))
+            .filter(|c| {
+                let root = audit.root_at(c.count).map(hex::encode);
+                root.as_ref() == Some(&c.root)
+            })
+            .max_by_key(|c| c.count);
+        audit.cosign(&cosigning, &now).map(|c| (c, ours))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("failed to co-sign: {}", e))?;
+    shared
+        .witnessed
+        .lock()
+        .unwrap()
+        .insert(peer.config.kid.clone(), cosignature);
+    info!(
+        "{} Co-signed the head of {} at {} lines",
+        shared.clock.now().to_rfc3339(),
+        url,
+        head.count
+    );
+    peer.head = Some(head);
+
+    // 4) Keep that co-signature unless the one kept covers more lines
+    if let Some(cosignature) = ours {
+        let mut cosignatures = shared.cosignatures.lock().unwrap();
+        let newer = cosignatures
+            .get(&peer.config.kid)
+            .is_none_or(|kept| cosignature.count >= kept.count);
+        if newer {
+            cosignatures.insert(peer.config.kid.clone(), cosignature);
+        }
+    }
+    Ok(())
+}
+
+/// Whether `head` extends `last`, by `url`'s consistency proof if it grew
+pub(super) async fn extends(
+    client: &reqwest::Client,
+    url: &str,
+    last: &TreeHead,
+    head: &TreeHead,
+) -> Result<bool, String> {
+    if head.count <= last.count {
+        return Ok(head.count == last.count && head.root == last.root);
+    }
+    let resp = client
+        .get(format!("{}/v1/log/consistency", url))
+        .query(&[("old", last.count), ("new", head.count)])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("GET /log/consistency answered {}", resp.status()));
+    }
+    let proof: ConsistencyProof = resp.json().await.map_err(|e| e.to_string())?;
+    Ok(proof.verify(last, head))
+}
@@ -0,0 +1,215 @@
+//! gRPC interface (`proto/vts.proto`), mounted on the same router as the
+//! HTTP routes so both share one port, and the same tenants, clock, serials
+//! and `/log/stream` through `Tenant::issue`.
+
+use super::{AppState, PoolError, SignError, SignatureEncoding, Tenant, TenantKey, tenant_prefix};
+use crate::ecdsa_requests::verify_signature;
+use crate::wire;
+use crate::{EcdsaSignedTimestamp, EcdsaVerificationKey};
+use axum::extract::ConnectInfo;
+use base64::{Engine as _, engine::general_purpose};
+use chrono::Utc;
+use proto::vts_server::{Vts, VtsServer};
+use proto::{
+    GetKeyReply, GetKeyRequest, SignTimestampReply, SignTimestampRequest, VerifyProofReply,
+    VerifyProofRequest,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+use tracing::{error, info};
+
+/// Generated messages, server and client (`proto::vts_client::VtsClient`)
+pub mod proto {
+    tonic::include_proto!("vts.v1");
+}
+
+/// Route prefix of the service, for mounting it on the axum router
+pub(super) fn route_path() -> String {
+    format!(
+        "/{}/*rpc",
+        <VtsServer<VtsService> as tonic::server::NamedService>::NAME
+    )
+}
+
+pub(super) fn service(state: Arc<AppState>) -> VtsServer<VtsService> {
+    VtsServer::new(VtsService { state })
+}
+
+/// Implements `proto::vts_server::Vts` over the server state
+pub struct VtsService {
+    state: Arc<AppState>,
+}
+
+impl VtsService {
+    /// Empty selects the default tenant, like the un-prefixed HTTP routes
+    fn tenant(&self, name: &str) -> Option<Arc<Tenant>> {
+        if name.is_empty() {
+            return Some(self.state.default.clone());
+        }
+        let tenant = self.state.tenants.get(name).cloned();
+        if tenant.is_none() {
+            error!("{} Unknown tenant '{}'", Utc::now().to_rfc3339(), name);
+        }
+        tenant
+    }
+}
+
+#[tonic::async_trait]
+impl Vts for VtsService {
+    async fn get_key(
+        &self,
+        request: Request<GetKeyRequest>,
+    ) -> Result<Response<GetKeyReply>, Status> {
+        let tenant = self
+            .tenant(&request.get_ref().tenant)
+            .ok_or_else(|| Status::not_found("Unknown tenant"))?;
+        let now = tenant.shared.clock.now();
+        info!(
+            "{} gRPC: GetKey {} → responding with public key",
+            now.to_rfc3339(),
+            tenant_prefix(&tenant)
+        );
+        Ok(Response::new(GetKeyReply {
+            time_requested: wire::canonical_time(now),
+            public_key: tenant.key().public_key.to_sec1_bytes(),
+        }))
+    }
+
+    async fn sign_timestamp(
+        &self,
+        request: Request<SignTimestampRequest>,
+    ) -> Result<Response<SignTimestampReply>, Status> {
+        let client = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| *addr);
+        let request = request.into_inner();
+        let tenant = self
+            .tenant(&request.tenant)
+            .ok_or_else(|| Status::not_found("Unknown tenant"))?;
+        let signing = tenant.clone();
+        let message = tenant
+            .shared
+            .normalization
+            .apply(&request.message)
+            .into_owned();
+        if tenant.shared.refuses_plaintext(message.as_bytes()) {
+            return Err(Status::invalid_argument(
+                "privacy_mode is on: send the digest message '<digest-alg>:<hex>', not plaintext",
+            ));
+        }
+        let reply_message = message.clone();
+        let issued = tenant
+            .shared
+            .pool
+            .run(move || {
+                let key = signing.key();
+                // The reply has no field for a policy, so none is signed
+                signing.issue(
+                    message.as_bytes(),
+                    key,
+                    None,
+                    SignatureEncoding::Raw,
+                    "grpc_sign",
+                    client,
+                )
+            })
+            .await
+            .map_err(|e| match e {
+                PoolError::Saturated => {
+                    Status::unavailable("Too many signing requests, retry later")
+                }
+                PoolError::Panicked => Status::internal(SignError::Signer.message()),
+            })?
+            .map_err(|e| match e {
+                SignError::ClockDrift | SignError::ClockUnverified | SignError::Paused => {
+                    Status::unavailable(e.message())
+                }
+                SignError::KeyExhausted => Status::resource_exhausted(e.message()),
+                SignError::MessageTooLarge => Status::invalid_argument(e.message()),
+                SignError::KeyExpired | SignError::ReadOnly => {
+                    Status::failed_precondition(e.message())
+                }
+                SignError::Audit
+                | SignError::ClockState
+                | SignError::Encoding
+                | SignError::KeyStats
+                | SignError::SelfCheck
+                | SignError::Serial
+                | SignError::Signer => Status::internal(e.message()),
+            })?;
+        if tenant.shared.sampled() {
+            info!(
+                "{} gRPC: SignTimestamp {} {} → serial {}",
+                tenant.shared.clock.now().to_rfc3339(),
+                tenant_prefix(&tenant),
+                tenant
+                    .shared
+                    .logged(&reply_message, reply_message.as_bytes()),
+                issued.serial
+            );
+        }
+        Ok(Response::new(SignTimestampReply {
+            message: reply_message,
+            time_signed: wire::canonical_time(issued.time_signed),
+            signature: issued.signature,
+            serial: issued.serial,
+            accuracy: issued.accuracy.unwrap_or_default(),
+            first_seen: issued.first_seen,
+        }))
+    }
+
+    async fn verify_proof(
+        &self,
+        request: Request<VerifyProofRequest>,
+    ) -> Result<Response<VerifyProofReply>, Status> {
+        let request = request.into_inner();
+        let tenant = self
+            .tenant(&request.tenant)
+            .ok_or_else(|| Status::not_found("Unknown tenant"))?;
+
+        // Same check a client does with `verify_signature`, under any of
+        // the tenant's keys
+        let key = |key: &TenantKey| EcdsaVerificationKey {
+            request: "GET".to_string(),
+            time_requested: String::new(),
+            public_key: key.public_key.to_string(),
+            format: None,
+            signature_alg: None,
+            hash_alg: None,
+            kid: None,
+            not_before: key.validity.not_before.map(wire::canonical_time),
+            not_after: key.validity.not_after.map(wire::canonical_time),
+        };
+        let signed = EcdsaSignedTimestamp {
+            request: "POST".to_string(),
+            message: request.message,
+            message_b64: None,
+            time_signed: request.time_signed,
+            signature: general_purpose::STANDARD.encode(&request.signature),
+            accuracy: None,
+            format: None,
+            signature_alg: None,
+            hash_alg: None,
+            kid: None,
+            policy: None,
+        };
+        let valid = tenant
+            .keys()
+            .iter()
+            .any(|k| verify_signature(&signed, &key(k)));
+        if tenant.shared.sampled() {
+            info!(
+                "{} gRPC: VerifyProof {} {} → {}",
+                Utc::now().to_rfc3339(),
+                tenant_prefix(&tenant),
+                tenant
+                    .shared
+                    .logged(&signed.message, signed.message.as_bytes()),
+                valid
+            );
+        }
+        Ok(Response::new(VerifyProofReply { valid }))
+    }
+}
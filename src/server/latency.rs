@@ -0,0 +1,194 @@
+//! Per-route latency (`[latency]`), served at `GET /metrics`
+//!
+//! Every routed request is timed from routing until its response is ready
+//! (a queue wait under `[limits]` is not counted, nor streaming a body).
+//! Each route, by method and path with the `/v1` and `/t/{tenant}`
+//! prefixes dropped, keeps its last `window` times for p50/p95/p99 and a
+//! running count and sum. A request slower than `slow_request_ms`, the
+//! latency objective, is counted against its route and logged with all
+//! that is known of it.
+
+use super::AppState;
+use crate::config::LatencyConfig;
+use axum::extract::{ConnectInfo, MatchedPath, Request, State};
+use axum::http::{HeaderValue, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// The quantiles `/metrics` reports
+const QUANTILES: [f64; 3] = [0.5, 0.95, 0.99];
+
+/// One route's times
+#[derive(Default)]
+struct RouteStats {
+    /// The last `window`, oldest first
+    recent: VecDeque<Duration>,
+    count: u64,
+    sum: Duration,
+    slow: u64,
+}
+
+/// The times of every route, by (method, route)
+pub(super) struct Latency {
+    routes: Mutex<BTreeMap<(String, String), RouteStats>>,
+    window: usize,
+    /// `None` with `slow_request_ms = 0`
+    slow: Option<Duration>,
+}
+
+impl Latency {
+    pub(super) fn new(config: &LatencyConfig) -> Self {
+        Self {
+            routes: Mutex::new(BTreeMap::new()),
+            window: config.window,
+            slow: (config.slow_request_ms > 0)
+                .then(|| Duration::from_millis(config.slow_request_ms)),
+        }
+    }
+
+    fn record(&self, method: &str, route: &str, elapsed: Duration, slow: bool) {
+        let mut routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = routes
+            .entry((method.to_string(), route.to_string()))
+            .or_default();
+        if stats.recent.len() == self.window {
+            stats.recent.pop_front();
+        }
+        stats.recent.push_back(elapsed);
+        stats.count += 1;
+        stats.sum += elapsed;
+        stats.slow += u64::from(slow);
+    }
+
+    /// The Prometheus text exposition of every route's times
+    fn render(&self) -> String {
+        let routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+        out.push_str("# HELP vts_request_duration_seconds Time to a response, over each route's last requests\n");
+        out.push_str("# TYPE vts_request_duration_seconds summary\n");
+        for ((method, route), stats) in routes.iter() {
+            let labels = format!("method=\"{}\",route=\"{}\"", method, route);
+            let mut sorted: Vec<Duration> = stats.recent.iter().copied().collect();
+            sorted.sort();
+            for q in QUANTILES {
+                let _ = writeln!(
+                    out,
+                    "vts_request_duration_seconds{{{},quantile=\"{}\"}} {}",
+                    labels,
+                    q,
+                    quantile(&sorted, q).as_secs_f64()
+                );
+            }
+            let _ = writeln!(
+                out,
+                "vts_request_duration_seconds_sum{{{}}} {}",
+                labels,
+                stats.sum.as_secs_f64()
+            );
+            let _ = writeln!(
+                out,
+                "vts_request_duration_seconds_count{{{}}} {}",
+                labels, stats.count
+            );
+        }
+        out.push_str("# HELP vts_slow_requests_total Requests slower than slow_request_ms\n");
+        out.push_str("# TYPE vts_slow_requests_total counter\n");
+        for ((method, route), stats) in routes.iter() {
+            let _ = writeln!(
+                out,
+                "vts_slow_requests_total{{method=\"{}\",route=\"{}\"}} {}",
+                method, route, stats.slow
+            );
+        }
+        out
+    }
+}
+
+/// Nearest-rank quantile `q` of `sorted`; zero if it is empty
+fn quantile(sorted: &[Duration], q: f64) -> Duration {
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    sorted
+        .get(rank.saturating_sub(1))
+        .copied()
+        .unwrap_or_default()
+}
+
+/// `matched` without the `/v1` and `/t/:tenant` prefixes, so every version
+/// and tenant of an endpoint is one route
+fn route_of(matched: &str) -> &str {
+    let route = matched.strip_prefix("/v1").unwrap_or(matched);
+    let route = route.strip_prefix("/t/:tenant").unwrap_or(route);
+    if route.is_empty() { "/" } else { route }
+}
+
+/// Middleware (a route layer, so the route is known): times the request,
+/// and logs it if it was slow
+pub(super) async fn time_requests(
+    State(latency): State<Arc<Latency>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("", |m| route_of(m.as_str()))
+        .to_string();
+    let uri = request.uri().clone();
+    let tenant = request
+        .headers()
+        .get(super::TENANT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let client = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| *addr);
+    // Set by `request_id`, which runs first
+    let request_id = request
+        .headers()
+        .get(super::REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-")
+        .to_string();
+
+    let response = next.run(request).await;
+    let elapsed = start.elapsed();
+    let slow = latency.slow.is_some_and(|slow| elapsed > slow);
+    latency.record(&method, &route, elapsed, slow);
+    if slow {
+        warn!(
+            "{} Slow request: {} {} (route {}) → {} in {}ms, over {}ms; tenant={} client={} request_id={}",
+            Utc::now().to_rfc3339(),
+            method,
+            uri,
+            route,
+            response.status().as_u16(),
+            elapsed.as_millis(),
+            latency.slow.unwrap_or_default().as_millis(),
+            tenant.as_deref().unwrap_or("-"),
+            client.map_or_else(|| "-".to_string(), |addr| addr.to_string()),
+            request_id
+        );
+    }
+    response
+}
+
+/// GET /metrics → every route's p50/p95/p99, count and sum, and its slow
+/// requests, for Prometheus
+pub(super) async fn handle_get_metrics(State(state): State<Arc<AppState>>) -> Response {
+    let mut response = state.latency.render().into_response();
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; version=0.0.4"),
+    );
+    response
+}
@@ -0,0 +1,75 @@
+//! Server-wide request limits (`[limits]`).
+//!
+//! At most `max_concurrent_requests` requests are handled at once; up to
+//! `queue_depth` more wait for a slot, each for at most `queue_timeout_ms`.
+//! Anything past that gets `503` with `Retry-After` straight away, so a
+//! burst costs the clients that can't be served a quick refusal instead of
+//! slowing everyone down. A streaming response (`/log/stream`) gives its
+//! slot back once the stream has started.
+
+use super::overloaded_response;
+use crate::config::LimitsConfig;
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+pub(super) struct RequestLimits {
+    /// One permit per request being handled
+    active: Arc<Semaphore>,
+    /// One permit per request being handled or queued
+    admitted: Arc<Semaphore>,
+    queue_timeout: Duration,
+    retry_after_secs: u64,
+}
+
+impl RequestLimits {
+    /// `None` when `max_concurrent_requests` is 0, i.e. unlimited
+    pub(super) fn new(config: &LimitsConfig) -> Option<Self> {
+        if config.max_concurrent_requests == 0 {
+            return None;
+        }
+        Some(Self {
+            active: Arc::new(Semaphore::new(config.max_concurrent_requests)),
+            admitted: Arc::new(Semaphore::new(
+                config
+                    .max_concurrent_requests
+                    .saturating_add(config.queue_depth),
+            )),
+            queue_timeout: Duration::from_millis(config.queue_timeout_ms),
+            retry_after_secs: config.retry_after_secs,
+        })
+    }
+}
+
+/// Middleware: waits for a slot, or sheds the request when the queue is
+/// full or the wait too long
+pub(super) async fn limit_requests(
+    State(limits): State<Arc<RequestLimits>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Ok(_admitted) = limits.admitted.clone().try_acquire_owned() else {
+        return shed(&limits, &request, "queue full");
+    };
+    let wait = tokio::time::timeout(limits.queue_timeout, limits.active.clone().acquire_owned());
+    let Ok(Ok(_active)) = wait.await else {
+        return shed(&limits, &request, "queued too long");
+    };
+    next.run(request).await
+}
+
+fn shed(limits: &RequestLimits, request: &Request, why: &str) -> Response {
+    warn!(
+        "{} Shedding {} {}: {}",
+        Utc::now().to_rfc3339(),
+        request.method(),
+        request.uri().path(),
+        why
+    );
+    overloaded_response(limits.retry_after_secs, "Server is busy, retry later")
+}
@@ -0,0 +1,199 @@
+//! Where the server accepts connections: TCP addresses (IPv4 and IPv6), and
+//! UNIX sockets for deployments behind a local reverse proxy
+//! (`listen = ["0.0.0.0:8008", "[::]:8008", "unix:/run/vts.sock"]`)
+
+use axum::Router;
+use std::net::{IpAddr, SocketAddr};
+
+/// Prefix of a UNIX socket path in `listen`
+pub const UNIX_PREFIX: &str = "unix:";
+
+/// What the server accepts connections on: one or more TCP addresses and
+/// (on Unix) UNIX domain sockets, all serving the same router
+pub struct Listener {
+    sockets: Vec<Socket>,
+}
+
+enum Socket {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+impl From<tokio::net::TcpListener> for Listener {
+    fn from(listener: tokio::net::TcpListener) -> Self {
+        Listener {
+            sockets: vec![Socket::Tcp(listener)],
+        }
+    }
+}
+
+#[cfg(unix)]
+impl From<tokio::net::UnixListener> for Listener {
+    fn from(listener: tokio::net::UnixListener) -> Self {
+        Listener {
+            sockets: vec![Socket::Unix(listener)],
+        }
+    }
+}
+
+impl Listener {
+    /// Binds every address in `listen`: `unix:PATH` for a UNIX socket, else
+    /// `HOST:PORT` (`[::]:8008` for IPv6), or just an IP address to listen on
+    /// it at `port`. An empty `listen` is `0.0.0.0:{port}`.
+    ///
+    /// IPv6 sockets take only IPv6 connections, so `0.0.0.0` and `[::]` can
+    /// share a port. A socket file left behind by a server that is gone is
+    /// replaced; one that still accepts connections is not.
+    pub async fn bind<S: AsRef<str>>(listen: &[S], port: u16) -> std::io::Result<Self> {
+        let mut sockets = Vec::with_capacity(listen.len().max(1));
+        if listen.is_empty() {
+            let addr = SocketAddr::from(([0, 0, 0, 0], port));
+            sockets.push(Socket::Tcp(bind_tcp(addr)?));
+        }
+        for listen in listen {
+            let listen = listen.as_ref();
+            let socket = match listen.strip_prefix(UNIX_PREFIX) {
+                Some(path) => bind_unix(path).await,
+                None => resolve(listen, port)
+                    .await
+                    .and_then(bind_tcp)
+                    .map(Socket::Tcp),
+            };
+            sockets.push(socket.map_err(|e| {
+                std::io::Error::new(e.kind(), format!("Failed to bind {}: {}", listen, e))
+            })?);
+        }
+        Ok(Listener { sockets })
+    }
+
+    /// What was bound, for the logs (`127.0.0.1:8008, unix:/run/vts.sock`)
+    pub fn describe(&self) -> String {
+        let describe = |socket: &Socket| match socket {
+            Socket::Tcp(listener) => listener
+                .local_addr()
+                .map(|addr| addr.to_string())
+                .unwrap_or_else(|e| e.to_string()),
+            #[cfg(unix)]
+            Socket::Unix(listener) => match listener.local_addr() {
+                Ok(addr) => match addr.as_pathname() {
+                    Some(path) => format!("{}{}", UNIX_PREFIX, path.display()),
+                    None => format!("{}(unnamed)", UNIX_PREFIX),
+                },
+                Err(e) => e.to_string(),
+            },
+        };
+        let described: Vec<String> = self.sockets.iter().map(describe).collect();
+        described.join(", ")
+    }
+
+    /// The TCP addresses bound, in `listen` order (e.g. to find the ports
+    /// picked for `:0`)
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.sockets
+            .iter()
+            .filter_map(|socket| match socket {
+                Socket::Tcp(listener) => listener.local_addr().ok(),
+                #[cfg(unix)]
+                Socket::Unix(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// `HOST:PORT`, or a bare IP address at `port`
+async fn resolve(listen: &str, port: u16) -> std::io::Result<SocketAddr> {
+    let bare = listen.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = bare.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, port));
+    }
+    tokio::net::lookup_host(listen)
+        .await?
+        .next()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no address"))
+}
+
+/// Like `TcpListener::bind`, but an IPv6 socket doesn't also take IPv4
+/// connections (which would collide with a separate `0.0.0.0` listener)
+fn bind_tcp(addr: SocketAddr) -> std::io::Result<tokio::net::TcpListener> {
+    use socket2::{Domain, Protocol, Socket, Type};
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    tokio::net::TcpListener::from_std(socket.into())
+}
+
+#[cfg(unix)]
+async fn bind_unix(path: &str) -> std::io::Result<Socket> {
+    use std::os::unix::fs::FileTypeExt;
+    let stale = std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket())
+        && tokio::net::UnixStream::connect(path).await.is_err();
+    if stale {
+        std::fs::remove_file(path)?;
+    }
+    Ok(Socket::Unix(tokio::net::UnixListener::bind(path)?))
+}
+
+#[cfg(not(unix))]
+async fn bind_unix(path: &str) -> std::io::Result<Socket> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("UNIX sockets are not supported here ({})", path),
+    ))
+}
+
+/// Serves `app` on every socket until one of them fails. TCP connections
+/// carry the peer address (`ConnectInfo<SocketAddr>`, for the audit log);
+/// UNIX ones have none.
+pub(super) async fn serve(listener: Listener, app: Router) -> std::io::Result<()> {
+    let mut serving = tokio::task::JoinSet::new();
+    for socket in listener.sockets {
+        let app = app.clone();
+        serving.spawn(async move {
+            match socket {
+                Socket::Tcp(listener) => {
+                    axum::serve(
+                        listener,
+                        app.into_make_service_with_connect_info::<SocketAddr>(),
+                    )
+                    .await
+                }
+                #[cfg(unix)]
+                Socket::Unix(listener) => serve_unix(listener, app).await,
+            }
+        });
+    }
+    while let Some(result) = serving.join_next().await {
+        result.map_err(std::io::Error::other)??;
+    }
+    Ok(())
+}
+
+/// `axum::serve` for a UNIX socket: each connection is handed to hyper
+/// directly, HTTP/1 or HTTP/2 (for gRPC) as the client speaks
+#[cfg(unix)]
+async fn serve_unix(listener: tokio::net::UnixListener, app: Router) -> std::io::Result<()> {
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use hyper_util::server::conn::auto::Builder;
+    use tower::ServiceExt;
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let app = app.clone();
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |request| app.clone().oneshot(request));
+            if let Err(e) = Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!("UNIX socket connection ended: {}", e);
+            }
+        });
+    }
+}
@@ -0,0 +1,256 @@
+//! Read-only mirror mode (`[mirror] upstream`, `lab4 --mirror URL`)
+//!
+//! A mirror keeps a copy of the upstream server's audit log in its own
+//! `audit_file` and serves the reads from it (`GET /key`,
+//! `/timestamp/by-hash/...`, `/log/*`) while refusing to sign. Every sync
+//! fetches the lines after the last one it has from `GET /log/entries`
+//! and appends them only if they continue its chain: the next `seq`s,
+//! `prev` linking to its last line and signed by the upstream key. An
+//! upstream whose log no longer extends the copy has rewritten its
+//! history; that is reported and syncing stops.
+//!
+//! The mirror signs no heads of its own. Each sync starts from the
+//! upstream's `GET /log/root` head, which must extend the last one synced
+//! by the upstream's `GET /log/consistency` proof, and copies the lines up
+//! to it. Once the copy has the head's root, the mirror serves that head
+//! at `GET /log/root`, and the upstream's root of each day a later line
+//! closed (checked against the copy too) at `GET /log/day`.
+
+use super::{AppState, LogEntry, Tenant, gossip};
+use crate::audit::{DayRoot, TreeHead};
+use crate::ecdsa_requests::nonblocking;
+use crate::signer::{Signer, SignerError, TenantSigners};
+use crate::wire;
+use ecdsa_lib::PublicKey;
+use k256::ecdsa::Signature;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Lines asked for per request
+const PAGE: usize = 1000;
+
+/// The upstream's public key, standing in for a signer: the mirror serves
+/// it and checks the log against it, but never signs
+struct VerifyOnly(PublicKey);
+
+impl Signer for VerifyOnly {
+    fn sign(&self, _message: &[u8]) -> Result<Signature, SignerError> {
+        Err("a read-only mirror does not sign".into())
+    }
+
+    fn public_key(&self) -> PublicKey {
+        self.0
+    }
+}
+
+/// The upstream's keys, for the default tenant and each of `tenants`
+pub(super) async fn upstream_signers<'a>(
+    upstream: &str,
+    tenants: impl Iterator<Item = &'a String>,
+) -> Result<(Box<dyn Signer>, TenantSigners), Box<dyn std::error::Error>> {
+    let key = |addr: String| async move {
+        nonblocking::request_key(&addr)
+            .await
+            .map_err(|e| format!("Failed to get the key from {}: {}", addr, e))?
+            .key()
+            .ok_or_else(|| format!("{} served an invalid key", addr))
+    };
+    let upstream = upstream.trim_end_matches('/');
+    let default = Box::new(VerifyOnly(key(upstream.to_string()).await?));
+    let mut signers = TenantSigners::new();
+    for name in tenants {
+        let public_key = key(format!("{}/t/{}", upstream, name)).await?;
+        signers.insert(name.clone(), Box::new(VerifyOnly(public_key)));
+    }
+    Ok((default, signers))
+}
+
+/// Syncs from `upstream` every `interval` until the upstream turns out to
+/// be inconsistent with the copy
+pub(super) fn spawn(state: Arc<AppState>, upstream: String, interval: Duration) {
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let upstream = upstream.trim_end_matches('/');
+        loop {
+            match sync(&state, &client, upstream).await {
+                Ok(0) => {}
+                Ok(n) => info!(
+                    "{} Mirrored {} entries from {}",
+                    state.default.shared.clock.now().to_rfc3339(),
+                    n,
+                    upstream
+                ),
+                Err(SyncError::Inconsistent(e)) => {
+                    error!(
+                        "{} ALERT: the log at {} does not extend the mirrored copy ({}); no longer syncing",
+                        state.default.shared.clock.now().to_rfc3339(),
+                        upstream,
+                        e
+                    );
+                    return;
+                }
+                Err(SyncError::Failed(e)) => error!(
+                    "{} Failed to sync from {}: {}",
+                    state.default.shared.clock.now().to_rfc3339(),
+                    upstream,
+                    e
+                ),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// Why a sync stopped
+enum SyncError {
+    /// Worth retrying (the upstream is down, say)
+    Failed(String),
+    /// The upstream's lines don't continue the copy
+    Inconsistent(std::io::Error),
+}
+
+/// Fetches the upstream's head, appends the lines up to it and keeps the
+/// head and the roots of the days now over to serve; returns how many
+/// lines were added
+async fn sync(
+    state: &Arc<AppState>,
+    client: &reqwest::Client,
+    upstream: &str,
+) -> Result<u64, SyncError> {
+    let Some(audit) = &state.default.shared.audit else {
+        return Ok(0);
+    };
+    let failed = |e: reqwest::Error| SyncError::Failed(e.to_string());
+    let inconsistent = |message: String| {
+        SyncError::Inconsistent(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            message,
+        ))
+    };
+
+    // 1) Its head, extending the last one synced by its consistency proof
+    let url = format!("{}/v1/log/root", upstream);
+    let resp = client.get(&url).send().await.map_err(failed)?;
+    if !resp.status().is_success() {
+        return Err(SyncError::Failed(format!(
+            "{} answered {}",
+            url,
+            resp.status()
+        )));
+    }
+    let head: TreeHead = resp.json().await.map_err(failed)?;
+    if let Some(last) = audit.signed_head()
+        && !gossip::extends(client, upstream, &last, &head)
+            .await
+            .map_err(SyncError::Failed)?
+    {
+        return Err(inconsistent(format!(
+            "its head ({} lines, root {}) does not extend the last one ({} lines, root {})",
+            head.count, head.root, last.count, last.root
+        )));
+    }
+    if audit.last_seq() > head.count {
+        return Err(inconsistent(format!(
+            "its head has {} lines, the copy {}",
+            head.count,
+            audit.last_seq()
+        )));
+    }
+
+    // 2) The lines up to it, continuing the copy
+    let mut added = 0;
+    while audit.last_seq() < head.count {
+        let url = format!(
+            "{}/v1/log/entries?since={}&limit={}",
+            upstream,
+            audit.last_seq(),
+            (head.count - audit.last_seq()).min(PAGE as u64)
+        );
+        let resp = client.get(&url).send().await.map_err(failed)?;
+        if !resp.status().is_success() {
+            return Err(SyncError::Failed(format!(
+                "{} answered {}",
+                url,
+                resp.status()
+            )));
+        }
+        let body = resp.text().await.map_err(failed)?;
+        let lines: Vec<String> = body.split_terminator('\n').map(str::to_string).collect();
+        if lines.is_empty() {
+            return Err(SyncError::Failed(format!(
+                "{} has no lines up to its head",
+                url
+            )));
+        }
+
+        let appending = state.clone();
+        let entries = tokio::task::spawn_blocking(move || {
+            let audit = appending
+                .default
+                .shared
+                .audit
+                .as_ref()
+                .expect("checked above");
+            audit.extend(&lines)
+        })
+        .await
+        .map_err(|e| SyncError::Failed(e.to_string()))?
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::InvalidData => SyncError::Inconsistent(e),
+            _ => SyncError::Failed(e.to_string()),
+        })?;
+        added += entries.len() as u64;
+
+        // Mirror `/log/stream` too
+        for entry in entries {
+            let tenant: Option<&Arc<Tenant>> = match &entry.tenant {
+                None => Some(&state.default),
+                Some(name) => state.tenants.get(name),
+            };
+            let (Some(tenant), Ok(time_signed)) = (tenant, wire::parse_time(&entry.time)) else {
+                continue;
+            };
+            if tenant.log_tx.receiver_count() > 0 {
+                let _ = tenant.log_tx.send(LogEntry {
+                    serial: entry.serial,
+                    hash: entry.hash,
+                    time_signed,
+                    signature: entry.signature.unwrap_or_default(),
+                });
+            }
+        }
+    }
+
+    // 3) Its head, and its roots of the days a later line closed, if they
+    // are of the copy's lines
+    let adopting = state.clone();
+    tokio::task::spawn_blocking(move || {
+        let audit = adopting
+            .default
+            .shared
+            .audit
+            .as_ref()
+            .expect("checked above");
+        audit.adopt_head(head)
+    })
+    .await
+    .map_err(|e| SyncError::Failed(e.to_string()))?
+    .map_err(SyncError::Inconsistent)?;
+    for date in audit.unsigned_days() {
+        let url = format!("{}/v1/log/day/{}", upstream, date);
+        let resp = client.get(&url).send().await.map_err(failed)?;
+        if !resp.status().is_success() {
+            return Err(SyncError::Failed(format!(
+                "{} answered {}",
+                url,
+                resp.status()
+            )));
+        }
+        let root: DayRoot = resp.json().await.map_err(failed)?;
+        audit
+            .adopt_day_root(root)
+            .map_err(SyncError::Inconsistent)?;
+    }
+    Ok(added)
+}
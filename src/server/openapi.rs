@@ -0,0 +1,623 @@
+//! OpenAPI 3.0 description of the HTTP interface, served at
+//! `GET /openapi.json` so non-Rust clients can generate bindings.
+//!
+//! The schemas are derived (`utoipa::ToSchema`) from the request and
+//! response types in `server.rs` and the crate's wire types, and every
+//! operation below names its body and responses by type, so a renamed or
+//! added field shows up here without touching this file;
+//! `test_openapi_matches_responses` checks live responses against it.
+
+use super::{
+    AlgorithmRequest, AnchorResponse, ConsistencyResponse, DayResponse, FoundTimestamp,
+    InclusionResponse, KeyResponse, KeyStatsResponse, KeysResponse, LogEntry, LookupResponse,
+    PoliciesResponse, PolicyResponse, PreviewResponse, RenewRequest, RootResponse, SignRequest,
+    SignResponse, SignatureEncoding, TextFormat, VersionResponse,
+};
+use crate::alg::{DigestAlg, HashAlg, SignatureAlg};
+use crate::audit::{
+    AuditEntry, Checkpoint, CompactedEntry, CompactedProof, ConsistencyProof, Cosignature,
+    DayProof, DayRoot, InclusionProof, TreeHead,
+};
+use crate::jws::{Jwk, JwkSet};
+use crate::key_stats::KeyUsage;
+use crate::revocation::{RevocationList, RevokedKey};
+use crate::transparency::{Anchor, AnchorProof};
+use crate::wire::TimePrecision;
+use crate::{ApiError, PROTOCOL_VERSIONS, SignedTime};
+use serde_json::Value;
+use utoipa::ToSchema;
+use utoipa::openapi::path::{OperationBuilder, Parameter, ParameterBuilder, ParameterIn};
+use utoipa::openapi::request_body::{RequestBody, RequestBodyBuilder};
+use utoipa::openapi::{
+    Components, ComponentsBuilder, Content, InfoBuilder, KnownFormat, ObjectBuilder,
+    OpenApiBuilder, PathItem, PathItemType, Paths, PathsBuilder, Ref, ResponseBuilder,
+    ResponsesBuilder, SchemaFormat, SchemaType,
+};
+use utoipa::openapi::{Required, Response};
+
+/// The document, with every route under `/v1` (the un-versioned aliases
+/// are the same operations)
+pub(super) fn document() -> Value {
+    let version = PROTOCOL_VERSIONS.last().copied().unwrap_or("v1");
+    let info = InfoBuilder::new()
+        .title("Verifiable Timestamp Service")
+        .version(env!("CARGO_PKG_VERSION"))
+        .description(Some(
+            "Signs `message + time-signed` with the service's secp256k1 key. \
+             Every operation is also served under `/t/{tenant}` for a named tenant, and \
+             without the version prefix for older clients.",
+        ));
+    let document = OpenApiBuilder::new()
+        .info(info)
+        .paths(paths(version))
+        .components(Some(components()))
+        .build();
+    serde_json::to_value(document).expect("the OpenAPI document is JSON")
+}
+
+/// Every operation under `/{version}`, and the un-versioned `/version`,
+/// `/openapi.json`, `/metrics` and `/.well-known/jwks.json`
+fn paths(version: &str) -> Paths {
+    let at = |path: &str| format!("/{}/{}", version, path);
+    PathsBuilder::new()
+        .path(
+            "/version",
+            get(
+                "Server version, protocol versions and features",
+                "getVersion",
+                vec![],
+                ResponsesBuilder::new().response("200", ok::<VersionResponse>()),
+            ),
+        )
+        .path(
+            "/openapi.json",
+            get(
+                "This document",
+                "getOpenApi",
+                vec![],
+                ResponsesBuilder::new().response("200", Response::new("OpenAPI 3.0 document")),
+            ),
+        )
+        .path(
+            "/metrics",
+            get(
+                "Response times by route (p50/p95/p99) and slow requests",
+                "getMetrics",
+                vec![],
+                ResponsesBuilder::new().response(
+                    "200",
+                    ResponseBuilder::new()
+                        .description("Prometheus text exposition")
+                        .content("text/plain", Content::new(string(None))),
+                ),
+            ),
+        )
+        .path(
+            at("key"),
+            get(
+                "The tenant's public key",
+                "getKey",
+                vec![tenant_header(), format_query(&["hex"])],
+                ResponsesBuilder::new()
+                    .response("200", ok::<KeyResponse>())
+                    .response("404", error("unknown_tenant")),
+            ),
+        )
+        .path(
+            at("keys"),
+            get(
+                "Every key the tenant signs with, the primary first",
+                "getKeys",
+                vec![tenant_header(), format_query(&["hex"])],
+                ResponsesBuilder::new()
+                    .response("200", ok::<KeysResponse>())
+                    .response("404", error("unknown_tenant")),
+            ),
+        )
+        .path(
+            at("time"),
+            get(
+                "The server clock, signed with the tenant's key together with the client's nonce",
+                "getTime",
+                vec![
+                    tenant_header(),
+                    query(
+                        "nonce",
+                        Required::True,
+                        "Echoed in the signed answer: 1 to 128 printable ASCII characters",
+                        string(None),
+                    ),
+                ],
+                ResponsesBuilder::new()
+                    .response("200", ok::<SignedTime>())
+                    .response("400", error("invalid_nonce"))
+                    .response("403", error("key_exhausted, key_expired or read_only (a mirror)"))
+                    .response("404", error("unknown_tenant"))
+                    .response("503", error("clock_drift or signing_paused")),
+            ),
+        )
+        .path(
+            "/.well-known/jwks.json",
+            get(
+                "The keys of `/keys` and the retired ones, as a JWK Set (RFC 7517)",
+                "getJwks",
+                vec![tenant_header()],
+                ResponsesBuilder::new()
+                    .response("200", ok::<JwkSet>())
+                    .response("404", error("unknown_tenant")),
+            ),
+        )
+        .path(
+            at("revocations"),
+            get(
+                "Compromised keys and since when, signed with the tenant's key",
+                "getRevocations",
+                vec![tenant_header()],
+                ResponsesBuilder::new()
+                    .response("200", ok::<RevocationList>())
+                    .response("403", error("read_only (a mirror)"))
+                    .response("404", error("unknown_tenant")),
+            ),
+        )
+        .path(
+            at("policies"),
+            get(
+                "The policies a timestamp request may name, and what each guarantees",
+                "getPolicies",
+                vec![tenant_header()],
+                ResponsesBuilder::new()
+                    .response("200", ok::<PoliciesResponse>())
+                    .response("404", error("unknown_tenant")),
+            ),
+        )
+        .path(
+            at("key/stats"),
+            get(
+                "How many signatures the tenant's key has produced",
+                "getKeyStats",
+                vec![tenant_header()],
+                ResponsesBuilder::new()
+                    .response("200", ok::<KeyStatsResponse>())
+                    .response("404", error("unknown_tenant")),
+            ),
+        )
+        .path(
+            at("key/cert"),
+            get(
+                "X.509 certificate of the tenant's key (`cert_file`)",
+                "getKeyCert",
+                vec![tenant_header()],
+                ResponsesBuilder::new()
+                    .response(
+                        "200",
+                        ResponseBuilder::new()
+                            .description("The certificate, DER or (when accepted) PEM")
+                            .content(
+                                "application/pkix-cert",
+                                Content::new(string(Some(KnownFormat::Binary))),
+                            )
+                            .content("application/pem-certificate-chain", Content::new(string(None))),
+                    )
+                    .response("404", error("unknown_tenant or no_certificate")),
+            ),
+        )
+        .path(
+            at("sign"),
+            post(
+                "Timestamp a message",
+                "sign",
+                vec![
+                    tenant_header(),
+                    format_query(&["hex", "jws", "cose"]),
+                    ParameterBuilder::new()
+                        .name("Idempotency-Key")
+                        .parameter_in(ParameterIn::Header)
+                        .required(Required::False)
+                        .description(Some("Retries with the same key get the same timestamp"))
+                        .schema(Some(string(None)))
+                        .build(),
+                ],
+                body::<SignRequest>(),
+                sign_responses(),
+            ),
+        )
+        .path(
+            at("sign/preview"),
+            post(
+                "The bytes `/sign` would sign now, without signing them",
+                "signPreview",
+                vec![tenant_header(), format_query(&["hex", "jws", "cose"])],
+                body::<SignRequest>(),
+                ResponsesBuilder::new()
+                    .response("200", ok::<PreviewResponse>())
+                    .response(
+                        "400",
+                        error("unsupported_algorithm, unknown_kid, unknown_policy or plaintext_not_allowed"),
+                    )
+                    .response("404", error("unknown_tenant"))
+                    .response("413", error("message_too_large or payload_too_large"))
+                    .response("415", error("unsupported_media_type"))
+                    .response("422", error("invalid_json or invalid_utf8")),
+            ),
+        )
+        .path(
+            at("renew"),
+            post(
+                "Timestamp an earlier token again with the current key",
+                "renew",
+                vec![tenant_header(), format_query(&["hex", "jws", "cose"])],
+                body::<RenewRequest>(),
+                sign_responses(),
+            ),
+        )
+        .path(
+            at("timestamp/by-hash/{sha256}"),
+            get(
+                "Every timestamp issued for a message, by its SHA-256",
+                "getByHash",
+                vec![
+                    tenant_header(),
+                    ParameterBuilder::new()
+                        .name("sha256")
+                        .parameter_in(ParameterIn::Path)
+                        .required(Required::True)
+                        .description(Some("Hex SHA-256 of the message"))
+                        .schema(Some(
+                            ObjectBuilder::new()
+                                .schema_type(SchemaType::String)
+                                .pattern(Some("^[0-9a-fA-F]{64}$")),
+                        ))
+                        .build(),
+                ],
+                ResponsesBuilder::new()
+                    .response("200", ok::<LookupResponse>())
+                    .response("400", error("invalid_hash"))
+                    .response("404", error("unknown_tenant or not_enabled (no audit log)")),
+            ),
+        )
+        .path(
+            at("anchor/{serial}"),
+            get(
+                "A timestamp's audit log line in the tree of the first head anchored with the `[anchor]` witness after it",
+                "getAnchor",
+                vec![
+                    tenant_header(),
+                    ParameterBuilder::new()
+                        .name("serial")
+                        .parameter_in(ParameterIn::Path)
+                        .required(Required::True)
+                        .description(Some("Serial of the timestamp within its tenant"))
+                        .schema(Some(integer(None)))
+                        .build(),
+                ],
+                ResponsesBuilder::new()
+                    .response("200", ok::<AnchorResponse>())
+                    .response("400", error("invalid_serial"))
+                    .response(
+                        "404",
+                        error("unknown_tenant, not_enabled (no `[anchor]` witness), unknown_serial or not_anchored (not yet)"),
+                    )
+                    .response("410", error("compacted (the line was compacted away)")),
+            ),
+        )
+        .path(
+            at("log/stream"),
+            get(
+                "Server-sent `timestamp` events for every issued timestamp",
+                "logStream",
+                vec![tenant_header()],
+                ResponsesBuilder::new()
+                    .response(
+                        "200",
+                        ResponseBuilder::new()
+                            .description("Event stream of LogEntry JSON")
+                            .content("text/event-stream", json::<LogEntry>()),
+                    )
+                    .response("404", error("unknown_tenant")),
+            ),
+        )
+        .path(
+            at("log/entries"),
+            get(
+                "Audit log lines after a `seq`, as written (all tenants; not under `/t/{tenant}`)",
+                "logEntries",
+                vec![
+                    query(
+                        "since",
+                        Required::False,
+                        "Return the lines after this `seq` (default 0)",
+                        integer(None),
+                    ),
+                    query(
+                        "limit",
+                        Required::False,
+                        "At most this many lines (default and cap 1000)",
+                        integer(Some(1000.0)),
+                    ),
+                ],
+                ResponsesBuilder::new()
+                    .response(
+                        "200",
+                        ResponseBuilder::new()
+                            .description("One AuditEntry JSON line per entry")
+                            .content("application/x-ndjson", json::<AuditEntry>()),
+                    )
+                    .response("400", error("invalid_query"))
+                    .response("404", error("not_enabled (no audit log)"))
+                    .response(
+                        "410",
+                        error("compacted (the lines after `since` were compacted by `[retention]`)"),
+                    ),
+            ),
+        )
+        .path(
+            at("log/day/{date}"),
+            get(
+                "Signed Merkle root of the audit log lines of a past UTC day (all tenants)",
+                "logDay",
+                vec![
+                    ParameterBuilder::new()
+                        .name("date")
+                        .parameter_in(ParameterIn::Path)
+                        .required(Required::True)
+                        .schema(Some(string(Some(KnownFormat::Date))))
+                        .build(),
+                    query(
+                        "seq",
+                        Required::False,
+                        "Also prove the line with this `seq`",
+                        integer(None),
+                    ),
+                ],
+                ResponsesBuilder::new()
+                    .response("200", ok::<DayResponse>())
+                    .response("400", error("invalid_date or invalid_query"))
+                    .response("404", error("not_enabled (no audit log) or unknown_seq"))
+                    .response("409", error("day_open (the day is not over)"))
+                    .response("503", error("not_synced (a mirror without the upstream's root yet)")),
+            ),
+        )
+        .path(
+            at("log/root"),
+            get(
+                "Signed Merkle root over the whole audit log, with the `[gossip]` peers' co-signatures",
+                "logRoot",
+                vec![],
+                ResponsesBuilder::new()
+                    .response("200", ok::<RootResponse>())
+                    .response("404", error("not_enabled (no audit log)"))
+                    .response("503", error("not_synced (a mirror without the upstream's head yet)")),
+            ),
+        )
+        .path(
+            at("log/consistency"),
+            get(
+                "That the audit log at `new` lines extends the log at `old` lines (RFC 6962 consistency proof)",
+                "logConsistency",
+                vec![
+                    query("old", Required::True, "Lines of the older tree head", integer(None)),
+                    query("new", Required::True, "Lines of the newer tree head", integer(None)),
+                ],
+                ResponsesBuilder::new()
+                    .response("200", ok::<ConsistencyResponse>())
+                    .response("400", error("invalid_query"))
+                    .response(
+                        "404",
+                        error("not_enabled (no audit log) or unknown_count (the log is shorter than `new`)"),
+                    ),
+            ),
+        )
+        .path(
+            at("log/proof"),
+            get(
+                "Audit log line `seq` as written, and that it is in the tree of the head at `count` lines (RFC 6962 inclusion proof)",
+                "logProof",
+                vec![
+                    query("seq", Required::True, "The line, e.g. a by-hash `proof.seq`", integer(None)),
+                    query(
+                        "count",
+                        Required::False,
+                        "Lines of the tree head (default: the current one)",
+                        integer(None),
+                    ),
+                ],
+                ResponsesBuilder::new()
+                    .response("200", ok::<InclusionResponse>())
+                    .response("400", error("invalid_query"))
+                    .response(
+                        "404",
+                        error("not_enabled (no audit log) or unknown_seq (no such line under `count`)"),
+                    )
+                    .response("410", error("compacted (the line was compacted away)")),
+            ),
+        )
+        .build()
+}
+
+/// Every type an operation or another schema refers to
+fn components() -> Components {
+    ComponentsBuilder::new()
+        .schema_from::<ApiError>()
+        .schema_from::<VersionResponse>()
+        .schema_from::<TimePrecision>()
+        .schema_from::<KeyResponse>()
+        .schema_from::<KeysResponse>()
+        .schema_from::<SignatureAlg>()
+        .schema_from::<HashAlg>()
+        .schema_from::<DigestAlg>()
+        .schema_from::<TextFormat>()
+        .schema_from::<SignatureEncoding>()
+        .schema_from::<SignedTime>()
+        .schema_from::<JwkSet>()
+        .schema_from::<Jwk>()
+        .schema_from::<RevocationList>()
+        .schema_from::<RevokedKey>()
+        .schema_from::<PoliciesResponse>()
+        .schema_from::<PolicyResponse>()
+        .schema_from::<KeyStatsResponse>()
+        .schema_from::<KeyUsage>()
+        .schema_from::<SignRequest>()
+        .schema_from::<AlgorithmRequest>()
+        .schema_from::<RenewRequest>()
+        .schema_from::<SignResponse>()
+        .schema_from::<PreviewResponse>()
+        .schema_from::<LookupResponse>()
+        .schema_from::<FoundTimestamp>()
+        .schema_from::<AuditEntry>()
+        .schema_from::<CompactedProof>()
+        .schema_from::<CompactedEntry>()
+        .schema_from::<Checkpoint>()
+        .schema_from::<LogEntry>()
+        .schema_from::<DayResponse>()
+        .schema_from::<DayRoot>()
+        .schema_from::<DayProof>()
+        .schema_from::<RootResponse>()
+        .schema_from::<TreeHead>()
+        .schema_from::<Cosignature>()
+        .schema_from::<ConsistencyResponse>()
+        .schema_from::<ConsistencyProof>()
+        .schema_from::<InclusionResponse>()
+        .schema_from::<InclusionProof>()
+        .schema_from::<AnchorResponse>()
+        .schema_from::<AnchorProof>()
+        .schema_from::<Anchor>()
+        .build()
+}
+
+/// Every status `/sign` and `/renew` can answer with
+fn sign_responses() -> ResponsesBuilder {
+    ResponsesBuilder::new()
+        .response(
+            "200",
+            ResponseBuilder::new()
+                .description("The timestamp, as JSON or as the negotiated token")
+                .content("application/json", json::<SignResponse>())
+                .content(crate::jws::CONTENT_TYPE, Content::new(string(None)))
+                .content(
+                    crate::cose::CONTENT_TYPE,
+                    Content::new(string(Some(KnownFormat::Binary))),
+                ),
+        )
+        .response(
+            "400",
+            error("unsupported_algorithm, unknown_kid, unknown_policy or plaintext_not_allowed"),
+        )
+        .response(
+            "403",
+            error("key_exhausted, key_expired or read_only (a mirror)"),
+        )
+        .response("404", error("unknown_tenant"))
+        .response("409", error("idempotency_conflict"))
+        .response("413", error("message_too_large or payload_too_large"))
+        .response("415", error("unsupported_media_type"))
+        .response("422", error("invalid_json or invalid_utf8"))
+        .response(
+            "500",
+            error("signer_error, self_check_failed, audit_error, ..."),
+        )
+        .response(
+            "503",
+            error("clock_drift, clock_unverified, signing_paused or overloaded (with Retry-After)"),
+        )
+}
+
+fn get(
+    summary: &str,
+    id: &str,
+    parameters: Vec<Parameter>,
+    responses: ResponsesBuilder,
+) -> PathItem {
+    let operation = OperationBuilder::new()
+        .summary(Some(summary))
+        .operation_id(Some(id))
+        .parameters(Some(parameters))
+        .responses(responses);
+    PathItem::new(PathItemType::Get, operation)
+}
+
+fn post(
+    summary: &str,
+    id: &str,
+    parameters: Vec<Parameter>,
+    body: RequestBody,
+    responses: ResponsesBuilder,
+) -> PathItem {
+    let operation = OperationBuilder::new()
+        .summary(Some(summary))
+        .operation_id(Some(id))
+        .parameters(Some(parameters))
+        .request_body(Some(body))
+        .responses(responses);
+    PathItem::new(PathItemType::Post, operation)
+}
+
+/// `T` as JSON, by reference to its schema
+fn json<'s, T: ToSchema<'s>>() -> Content {
+    Content::new(Ref::from_schema_name(T::schema().0))
+}
+
+fn ok<'s, T: ToSchema<'s>>() -> ResponseBuilder {
+    ResponseBuilder::new()
+        .description("OK")
+        .content("application/json", json::<T>())
+}
+
+/// An `ApiError` body; `description` lists its `code`s
+fn error(description: &str) -> ResponseBuilder {
+    ResponseBuilder::new()
+        .description(description)
+        .content("application/json", json::<ApiError>())
+}
+
+fn body<'s, T: ToSchema<'s>>() -> RequestBody {
+    RequestBodyBuilder::new()
+        .required(Some(Required::True))
+        .content("application/json", json::<T>())
+        .build()
+}
+
+fn tenant_header() -> Parameter {
+    ParameterBuilder::new()
+        .name("X-Tenant")
+        .parameter_in(ParameterIn::Header)
+        .required(Required::False)
+        .description(Some("Named tenant, instead of the default key pair"))
+        .schema(Some(string(None)))
+        .build()
+}
+
+fn format_query(values: &[&str]) -> Parameter {
+    let schema = ObjectBuilder::new()
+        .schema_type(SchemaType::String)
+        .enum_values(Some(values.iter().copied()));
+    ParameterBuilder::new()
+        .name("format")
+        .parameter_in(ParameterIn::Query)
+        .required(Required::False)
+        .schema(Some(schema))
+        .build()
+}
+
+fn query(name: &str, required: Required, description: &str, schema: ObjectBuilder) -> Parameter {
+    ParameterBuilder::new()
+        .name(name)
+        .parameter_in(ParameterIn::Query)
+        .required(required)
+        .description(Some(description))
+        .schema(Some(schema))
+        .build()
+}
+
+fn string(format: Option<KnownFormat>) -> ObjectBuilder {
+    ObjectBuilder::new()
+        .schema_type(SchemaType::String)
+        .format(format.map(SchemaFormat::KnownFormat))
+}
+
+/// A non-negative integer, at most `maximum`
+fn integer(maximum: Option<f64>) -> ObjectBuilder {
+    ObjectBuilder::new()
+        .schema_type(SchemaType::Integer)
+        .minimum(Some(0.0))
+        .maximum(maximum)
+}
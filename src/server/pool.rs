@@ -0,0 +1,91 @@
+//! The threads signing runs on (`[sign_pool]`).
+//!
+//! ECDSA, the self-check and the audit log append are CPU- or disk-bound,
+//! so they run on Tokio's blocking threads instead of the async executor,
+//! at most `workers` at a time. Up to `queue_depth` more requests wait for
+//! a worker; any beyond that are refused at once, rather than piling up.
+//! `/sign`, `/renew`, `/time` and `/revocations` all sign on it. A panic in
+//! the work is logged and answered as a signer error; the server carries on.
+
+use crate::config::SignPoolConfig;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tracing::error;
+
+pub(super) struct SignPool {
+    /// One permit per worker
+    workers: Arc<Semaphore>,
+    /// One permit per request running or waiting (`workers + queue_depth`)
+    admitted: Arc<Semaphore>,
+    /// Seconds a refused client is told to wait
+    pub(super) retry_after_secs: u64,
+}
+
+/// Why `SignPool::run` has no result
+#[derive(Debug)]
+pub(super) enum PoolError {
+    /// The pool and its queue are full
+    Saturated,
+    /// The work panicked
+    Panicked,
+}
+
+impl SignPool {
+    pub(super) fn new(config: &SignPoolConfig) -> Self {
+        let workers = match config.workers {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        Self {
+            workers: Arc::new(Semaphore::new(workers)),
+            admitted: Arc::new(Semaphore::new(workers.saturating_add(config.queue_depth))),
+            retry_after_secs: config.retry_after_secs,
+        }
+    }
+
+    /// Runs `work` on a blocking thread once a worker is free, or fails
+    /// right away if the queue is full. The permits go with `work`, so a
+    /// client hanging up doesn't free a worker that is still signing.
+    pub(super) async fn run<T: Send + 'static>(
+        &self,
+        work: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, PoolError> {
+        let admitted = self
+            .admitted
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| PoolError::Saturated)?;
+        // Never closed, so acquiring only waits
+        let worker = self
+            .workers
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| PoolError::Saturated)?;
+        // The blocking thread carries on the request's trace
+        #[cfg(feature = "otlp")]
+        let work = {
+            let trace = super::trace::current();
+            move || super::trace::in_scope(trace, work)
+        };
+        let task = tokio::task::spawn_blocking(move || {
+            let _permits = (admitted, worker);
+            work()
+        });
+        match task.await {
+            Ok(result) => Ok(result),
+            Err(e) if e.is_panic() => {
+                let panic = e.into_panic();
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("(no message)");
+                error!("Signing panicked: {}", message);
+                Err(PoolError::Panicked)
+            }
+            // The runtime is shutting down
+            Err(_) => Err(PoolError::Saturated),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Response signing (`http_signatures = true`), see `crate::http_sig`
+
+use super::{AppState, TENANT_HEADER, Tenant};
+use crate::ApiError;
+use crate::http_sig::{self, CONTENT_DIGEST, SIGNATURE, SIGNATURE_INPUT};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::Utc;
+use k256::elliptic_curve::rand_core::{OsRng, RngCore};
+use std::sync::Arc;
+use tracing::error;
+
+/// Adds `Content-Digest`, `Signature-Input` and `Signature` to every
+/// response but event streams (and gRPC's), signed with the key of the
+/// tenant the request was for: the default tenant's when it names none, or
+/// one that doesn't exist
+pub(super) async fn sign_responses(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let tenant = tenant_of(&state, &request);
+    let response = next.run(request).await;
+    let streamed = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|t| t.starts_with("text/event-stream") || t.starts_with("application/grpc"));
+    if streamed {
+        return response;
+    }
+
+    // 1) The whole body, to digest
+    let (mut parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!(
+                "{} Failed to read a response to sign: {}",
+                Utc::now().to_rfc3339(),
+                e
+            );
+            return ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Failed to sign the response",
+            )
+            .into_response();
+        }
+    };
+    let digest = http_sig::content_digest(&body);
+    parts.headers.insert(
+        CONTENT_DIGEST,
+        HeaderValue::from_str(&digest).expect("Base64 is a header value"),
+    );
+
+    // 2) Sign the status, type and digest with a fresh nonce, off the
+    // async threads (the signer may be a remote KMS)
+    let mut components = vec!["@status"];
+    if parts.headers.contains_key(header::CONTENT_TYPE) {
+        components.push("content-type");
+    }
+    components.push(CONTENT_DIGEST);
+    let key = tenant.key();
+    let now = Utc::now();
+    let params = http_sig::signature_params(&components, now.timestamp(), &key.key_id, &nonce());
+    let header = |name: &str| {
+        let value = parts.headers.get(name)?.to_str().ok()?;
+        Some(value.to_string())
+    };
+    let base = http_sig::signature_base(parts.status.as_u16(), &components, &params, header)
+        .expect("the covered headers are present");
+    let signed = tokio::task::spawn_blocking(move || key.sign(base.as_bytes(), now)).await;
+    // A failed signer is logged by `TenantKey::sign`; the response goes
+    // out unsigned, and clients that check it refuse it
+    if let Ok(Ok(signature)) = signed {
+        let (input, signature) = http_sig::headers(&params, &signature);
+        for (name, value) in [(SIGNATURE_INPUT, input), (SIGNATURE, signature)] {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                parts.headers.insert(name, value);
+            }
+        }
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// 128 random bits in Base64url, the `nonce` of one response
+fn nonce() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// The tenant `TenantRef` would pick, read off the path: routes aren't
+/// matched yet out here
+fn tenant_of(state: &AppState, request: &Request) -> Arc<Tenant> {
+    let path = request.uri().path();
+    let path = path.strip_prefix("/v1").unwrap_or(path);
+    let name = match path.strip_prefix("/t/") {
+        Some(rest) => rest.split('/').next(),
+        None => request
+            .headers()
+            .get(TENANT_HEADER)
+            .and_then(|v| v.to_str().ok()),
+    };
+    name.and_then(|name| state.tenants.get(name))
+        .unwrap_or(&state.default)
+        .clone()
+}
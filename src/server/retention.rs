@@ -0,0 +1,53 @@
+//! Compacting the audit log (`[retention] full_days`)
+//!
+//! Every `interval_secs`, lines older than `full_days` move into the
+//! compacted file (`audit::AuditLog::compact`). With `[archive]` only lines
+//! already sealed into the bucket are compacted, so the archive keeps every
+//! line whole. `POST /admin/compact` runs the same compaction at once.
+
+use super::{AppState, Shared};
+use crate::wire;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Compacts every `interval`, for as long as the server runs
+pub(super) fn spawn(state: Arc<AppState>, interval: Duration) {
+    tokio::spawn(async move {
+        loop {
+            let shared = state.default.shared.clone();
+            let result = tokio::task::spawn_blocking(move || compact(&shared)).await;
+            let now = state.default.shared.clock.now().to_rfc3339();
+            match result.map_err(std::io::Error::other).and_then(|r| r) {
+                Ok(0) => {}
+                Ok(n) => info!("{} Compacted {} audit log entries", now, n),
+                Err(e) => error!(
+                    "{} Failed to compact the audit log (retrying in {}s): {}",
+                    now,
+                    interval.as_secs(),
+                    e
+                ),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// Compacts the lines past `full_days` (and, with `[archive]`, sealed);
+/// returns how many. Blocks on the audit log.
+pub(super) fn compact(shared: &Shared) -> std::io::Result<u64> {
+    let Some(audit) = &shared.audit else {
+        return Ok(0);
+    };
+    let now = shared.clock.now();
+    let before = ChronoDuration::try_days(i64::try_from(shared.full_days).unwrap_or(i64::MAX))
+        .and_then(|age| now.checked_sub_signed(age))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+    let up_to = shared
+        .archived
+        .as_ref()
+        .map_or(u64::MAX, |archived| archived.load(Ordering::SeqCst));
+    audit.compact(before, up_to, &wire::canonical_time(now))
+}
@@ -0,0 +1,153 @@
+//! Roughtime over UDP (`[roughtime] listen`)
+//!
+//! Each request is answered alone (a tree of one nonce) with the clock's
+//! time and `radius_ms`, signed by an online key generated here. The
+//! default tenant's key delegates to it for `delegation_secs`; it is
+//! replaced halfway through, or as soon as that key is rotated. Like
+//! `/time`, nothing is answered while signing is paused or the clock is
+//! too far off NTP: Roughtime has no error replies, so those requests are
+//! dropped, as are ones shorter than `roughtime::MIN_REQUEST_LEN`.
+
+use super::{AppState, SignError, Tenant};
+use crate::config::RoughtimeConfig;
+use crate::roughtime::{self, Message};
+use crate::wire;
+use chrono::{DateTime, Utc};
+use ecdsa_lib::KeyPair;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tracing::{error, info};
+
+/// The online key and the default key's delegation to it
+struct Delegation {
+    online: KeyPair,
+    cert: Message,
+    /// Key id of the key that signed `cert`
+    kid: String,
+    /// When to replace it
+    renew_at: DateTime<Utc>,
+}
+
+/// A fresh online key, delegated to by `tenant`'s key from `now` for
+/// `validity` (or until the key's own validity ends). The delegation
+/// counts as one of the key's signatures.
+fn delegate(
+    tenant: &Tenant,
+    now: DateTime<Utc>,
+    validity: Duration,
+) -> Result<Delegation, SignError> {
+    let key = tenant.key();
+    if !key.validity.contains(now) {
+        return Err(SignError::KeyExpired);
+    }
+    tenant.record_signature(&key, &wire::canonical_time(now), now)?;
+    let validity = chrono::Duration::from_std(validity).map_err(|_| SignError::Signer)?;
+    let mut max = now + validity;
+    if let Some(not_after) = key.validity.not_after {
+        max = max.min(not_after);
+    }
+    let online = KeyPair::generate();
+    let dele = roughtime::delegation(&online.to_public_key(), now, max);
+    let signed = [roughtime::DELEGATION_CONTEXT, &dele.encode()].concat();
+    let signature = key.sign(&signed, now)?;
+    Ok(Delegation {
+        online,
+        cert: roughtime::certificate(&dele, &signature),
+        kid: key.key_id.clone(),
+        renew_at: now + validity / 2,
+    })
+}
+
+/// Binds `config.listen` and answers Roughtime requests on it, for as long
+/// as the server runs
+pub(super) async fn spawn(
+    state: Arc<AppState>,
+    config: &RoughtimeConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = UdpSocket::bind(&config.listen)
+        .await
+        .map_err(|e| format!("Failed to bind Roughtime to {}: {}", config.listen, e))?;
+    info!("Roughtime on udp://{}", socket.local_addr()?);
+    let radius = Duration::from_millis(config.radius_ms.into());
+    let validity = Duration::from_secs(config.delegation_secs);
+    tokio::spawn(async move {
+        let mut delegation: Option<Arc<Delegation>> = None;
+        let mut buf = vec![0; 4096];
+        loop {
+            let (len, peer) = match socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(e) => {
+                    error!("Roughtime receive error: {}", e);
+                    continue;
+                }
+            };
+            let tenant = &state.default;
+            let now = tenant.shared.clock.now();
+
+            // 1) A request worth answering, while signing is on
+            let Some(nonce) = nonce(&buf[..len]) else {
+                continue;
+            };
+            if tenant.shared.paused.load(Ordering::SeqCst) || tenant.accuracy(now).is_err() {
+                continue;
+            }
+
+            // 2) A delegation by the current key, renewed when due
+            let current = delegation
+                .as_ref()
+                .filter(|d| now < d.renew_at && d.kid == tenant.key().key_id);
+            let delegated = match current {
+                Some(delegated) => delegated.clone(),
+                None => {
+                    let signing = tenant.clone();
+                    let fresh =
+                        tokio::task::spawn_blocking(move || delegate(&signing, now, validity))
+                            .await
+                            .map_err(|_| SignError::Signer)
+                            .and_then(|fresh| fresh);
+                    match fresh {
+                        Ok(fresh) => {
+                            info!(
+                                "{} Roughtime: key {} delegated to a new online key",
+                                now.to_rfc3339(),
+                                fresh.kid
+                            );
+                            delegation.insert(Arc::new(fresh)).clone()
+                        }
+                        Err(e) => {
+                            error!(
+                                "{} Roughtime: failed to delegate: {}",
+                                now.to_rfc3339(),
+                                e.code()
+                            );
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            // 3) The signed answer
+            info!("{} Roughtime request from {}", now.to_rfc3339(), peer);
+            let answer =
+                roughtime::response(&nonce, now, radius, &delegated.online, &delegated.cert);
+            if let Err(e) = socket.send_to(&answer, peer).await {
+                error!("Roughtime send error to {}: {}", peer, e);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// The nonce of a request, if it is one this server answers
+fn nonce(request: &[u8]) -> Option<[u8; roughtime::NONCE_LEN]> {
+    if request.len() < roughtime::MIN_REQUEST_LEN {
+        return None;
+    }
+    Message::decode(request)
+        .ok()?
+        .get(roughtime::NONC)?
+        .try_into()
+        .ok()
+}
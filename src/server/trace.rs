@@ -0,0 +1,336 @@
+//! Request tracing (`[otlp]`, the `otlp` feature)
+//!
+//! Every request becomes a server span, continuing the trace of a W3C
+//! `traceparent` header if it came with a valid one, else starting a new
+//! trace. `/sign` and `/renew` add child spans for their steps: `parse`
+//! (checking the request, up to the signing queue), `sign` (the clock and
+//! the key), `store` (the audit log and `/log/stream`) and `respond`
+//! (encoding the body); gRPC `SignTimestamp` has `sign` and `store`.
+//!
+//! Finished requests are queued and sent every `interval_secs` as
+//! OTLP/HTTP JSON to `{endpoint}/v1/traces`. A request whose `traceparent`
+//! is not sampled (flags `00`) is not exported, like its caller's; with
+//! `max_queue` spans waiting, later ones are dropped rather than kept.
+
+use crate::config::OtlpConfig;
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use k256::elliptic_curve::rand_core::{OsRng, RngCore};
+use serde_json::json;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, warn};
+
+/// The header a caller's trace comes in
+pub(super) const TRACEPARENT_HEADER: &str = "traceparent";
+
+tokio::task_local! {
+    /// The trace of the request being served
+    static CURRENT: Arc<RequestTrace>;
+}
+
+/// What a `traceparent` header says: the trace, the caller's span in it,
+/// and whether the caller records it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct TraceParent {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    sampled: bool,
+}
+
+impl TraceParent {
+    /// `00-<32 hex trace id>-<16 hex span id>-<2 hex flags>`, lowercase,
+    /// with neither id all zeros. Later versions may append fields, which
+    /// are ignored; version `ff` is invalid.
+    pub(super) fn parse(header: &str) -> Option<Self> {
+        let parts: Vec<&str> = header.trim().split('-').collect();
+        let [version, trace_id, span_id, flags, rest @ ..] = parts.as_slice() else {
+            return None;
+        };
+        if !is_hex(version, 2) || *version == "ff" || (*version == "00" && !rest.is_empty()) {
+            return None;
+        }
+        let (trace_id, span_id, flags) = (
+            decode::<16>(trace_id)?,
+            decode::<8>(span_id)?,
+            decode::<1>(flags)?,
+        );
+        if trace_id == [0; 16] || span_id == [0; 8] {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            span_id,
+            sampled: flags[0] & 1 == 1,
+        })
+    }
+}
+
+fn is_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn decode<const N: usize>(s: &str) -> Option<[u8; N]> {
+    if !is_hex(s, 2 * N) {
+        return None;
+    }
+    hex::decode(s).ok()?.try_into().ok()
+}
+
+fn random_id<const N: usize>() -> [u8; N] {
+    let mut id = [0; N];
+    while id == [0; N] {
+        OsRng.fill_bytes(&mut id);
+    }
+    id
+}
+
+fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// A span attribute's value
+#[derive(Debug, Clone)]
+enum Value {
+    Str(String),
+    Int(i64),
+}
+
+/// One finished span
+#[derive(Debug)]
+struct SpanRecord {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent: Option<[u8; 8]>,
+    name: String,
+    /// The request's own span, rather than one of its steps
+    server: bool,
+    start: u64,
+    end: u64,
+    attributes: Vec<(&'static str, Value)>,
+    error: bool,
+}
+
+/// The spans of one request, collected until it is answered
+pub(super) struct RequestTrace {
+    trace_id: [u8; 16],
+    /// The request's own span, which every step's span is a child of
+    root: [u8; 8],
+    spans: Mutex<Vec<SpanRecord>>,
+}
+
+/// A step of the current request, timed until it is dropped
+pub(super) struct Span {
+    trace: Arc<RequestTrace>,
+    name: &'static str,
+    start: u64,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let record = SpanRecord {
+            trace_id: self.trace.trace_id,
+            span_id: random_id(),
+            parent: Some(self.trace.root),
+            name: self.name.to_string(),
+            server: false,
+            start: self.start,
+            end: now_nanos(),
+            attributes: Vec::new(),
+            error: false,
+        };
+        if let Ok(mut spans) = self.trace.spans.lock() {
+            spans.push(record);
+        }
+    }
+}
+
+/// Starts the step `name` of the request being served; `None` outside a
+/// traced request
+pub(super) fn span(name: &'static str) -> Option<Span> {
+    current().map(|trace| Span {
+        trace,
+        name,
+        start: now_nanos(),
+    })
+}
+
+/// The trace of the request being served, to carry into a blocking task
+pub(super) fn current() -> Option<Arc<RequestTrace>> {
+    CURRENT.try_with(Arc::clone).ok()
+}
+
+/// Runs `f` as part of `trace` (from `current`), e.g. on a blocking thread
+pub(super) fn in_scope<R>(trace: Option<Arc<RequestTrace>>, f: impl FnOnce() -> R) -> R {
+    match trace {
+        Some(trace) => CURRENT.sync_scope(trace, f),
+        None => f(),
+    }
+}
+
+/// Finished spans waiting for the next export
+pub(super) struct Exporter {
+    queue: Mutex<Vec<SpanRecord>>,
+    max_queue: usize,
+    dropped: AtomicU64,
+}
+
+impl Exporter {
+    pub(super) fn new(config: &OtlpConfig) -> Self {
+        Self {
+            queue: Mutex::new(Vec::new()),
+            max_queue: config.max_queue,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    fn push(&self, spans: Vec<SpanRecord>) {
+        let Ok(mut queue) = self.queue.lock() else {
+            return;
+        };
+        if queue.len() + spans.len() > self.max_queue {
+            self.dropped
+                .fetch_add(spans.len() as u64, Ordering::Relaxed);
+            return;
+        }
+        queue.extend(spans);
+    }
+}
+
+/// Middleware: serves the request inside its trace, then queues the
+/// request's span and its steps' for export
+pub(super) async fn trace_requests(
+    State(exporter): State<Arc<Exporter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let parent = request
+        .headers()
+        .get(TRACEPARENT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(TraceParent::parse);
+    let trace = Arc::new(RequestTrace {
+        trace_id: parent.map_or_else(random_id, |p| p.trace_id),
+        root: random_id(),
+        spans: Mutex::new(Vec::new()),
+    });
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let start = now_nanos();
+
+    let response = CURRENT.scope(trace.clone(), next.run(request)).await;
+    if parent.is_some_and(|p| !p.sampled) {
+        return response;
+    }
+
+    let status = response.status();
+    let mut attributes = vec![
+        ("http.request.method", Value::Str(method.clone())),
+        ("url.path", Value::Str(path.clone())),
+        (
+            "http.response.status_code",
+            Value::Int(status.as_u16().into()),
+        ),
+    ];
+    if let Some(id) = response
+        .headers()
+        .get(super::REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+    {
+        attributes.push(("vts.request_id", Value::Str(id.to_string())));
+    }
+    let mut spans = std::mem::take(&mut *trace.spans.lock().unwrap_or_else(|e| e.into_inner()));
+    spans.push(SpanRecord {
+        trace_id: trace.trace_id,
+        span_id: trace.root,
+        parent: parent.map(|p| p.span_id),
+        name: format!("{} {}", method, path),
+        server: true,
+        start,
+        end: now_nanos(),
+        attributes,
+        error: status.is_server_error(),
+    });
+    exporter.push(spans);
+    response
+}
+
+/// Sends the queued spans to `{endpoint}/v1/traces` every `interval_secs`,
+/// for as long as the server runs
+pub(super) fn spawn(exporter: Arc<Exporter>, config: &OtlpConfig) {
+    let url = format!("{}/v1/traces", config.endpoint.trim_end_matches('/'));
+    let service_name = config.service_name.clone();
+    let interval = std::time::Duration::from_secs(config.interval_secs);
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        loop {
+            tokio::time::sleep(interval).await;
+            let dropped = exporter.dropped.swap(0, Ordering::Relaxed);
+            if dropped > 0 {
+                warn!("OTLP queue full: dropped {} spans", dropped);
+            }
+            let spans =
+                std::mem::take(&mut *exporter.queue.lock().unwrap_or_else(|e| e.into_inner()));
+            if spans.is_empty() {
+                continue;
+            }
+            // A failed batch is not retried, so the queue stays bounded
+            let sent = client
+                .post(&url)
+                .json(&export_body(&service_name, &spans))
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+            if let Err(e) = sent {
+                error!("OTLP export of {} spans to {}: {}", spans.len(), url, e);
+            }
+        }
+    });
+}
+
+/// An `ExportTraceServiceRequest` in OTLP's JSON encoding
+fn export_body(service_name: &str, spans: &[SpanRecord]) -> serde_json::Value {
+    let value = |value: &Value| match value {
+        Value::Str(s) => json!({ "stringValue": s }),
+        // 64-bit integers are strings in protobuf's JSON
+        Value::Int(i) => json!({ "intValue": i.to_string() }),
+    };
+    let spans: Vec<_> = spans
+        .iter()
+        .map(|span| {
+            let mut out = json!({
+                "traceId": hex::encode(span.trace_id),
+                "spanId": hex::encode(span.span_id),
+                "name": span.name,
+                // SPAN_KIND_SERVER, SPAN_KIND_INTERNAL
+                "kind": if span.server { 2 } else { 1 },
+                "startTimeUnixNano": span.start.to_string(),
+                "endTimeUnixNano": span.end.to_string(),
+                "attributes": span.attributes.iter()
+                    .map(|(key, v)| json!({ "key": key, "value": value(v) }))
+                    .collect::<Vec<_>>(),
+                // STATUS_CODE_ERROR, STATUS_CODE_UNSET
+                "status": { "code": if span.error { 2 } else { 0 } },
+            });
+            if let Some(parent) = span.parent {
+                out["parentSpanId"] = json!(hex::encode(parent));
+            }
+            out
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{ "key": "service.name", "value": { "stringValue": service_name } }],
+            },
+            "scopeSpans": [{
+                "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    })
+}
@@ -0,0 +1,70 @@
+//! Where the signing keys live
+//!
+//! The server never signs with a private key directly: every signature goes
+//! through a [`Signer`]. The default is the `KeyPair` loaded from the `.bin`
+//! files, but anything that can produce ECDSA/secp256k1 signatures (an HSM,
+//! a YubiKey, a remote signing service) can be passed to
+//! `server::run_server_with_signers` instead. With the `kms` feature,
+//! `kms::KmsSigner` keeps the key in AWS KMS.
+
+use ecdsa_lib::{KeyPair, PublicKey};
+use k256::ecdsa::Signature;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[cfg(feature = "kms")]
+pub mod kms;
+
+/// Why a signer could not sign (e.g. the token was unplugged)
+pub type SignerError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Tenant name → its signer
+pub type TenantSigners = BTreeMap<String, Box<dyn Signer>>;
+
+/// A signing key, wherever it is kept
+pub trait Signer: Send + Sync {
+    /// ECDSA over SHA-256 of `message`, as `KeyPair::sign` computes it. It
+    /// must verify against `public_key()`; the server checks that it does.
+    fn sign(&self, message: &[u8]) -> Result<Signature, SignerError>;
+
+    /// The key `sign` signs with, served as `GET /key`
+    fn public_key(&self) -> PublicKey;
+}
+
+/// The file-based key: signing never fails
+impl Signer for KeyPair {
+    fn sign(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        Ok(KeyPair::sign(self, message))
+    }
+
+    fn public_key(&self) -> PublicKey {
+        self.to_public_key()
+    }
+}
+
+/// Lets a caller keep a handle to a signer it hands to the server
+impl<S: Signer + ?Sized> Signer for Arc<S> {
+    fn sign(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        (**self).sign(message)
+    }
+
+    fn public_key(&self) -> PublicKey {
+        (**self).public_key()
+    }
+}
+
+/// Builds the `KeyPair` signer of raw `.bin` key bytes, checking that the
+/// public key is the one of the private key
+pub fn key_pair_signer(private_key: &[u8], public_key: &[u8]) -> std::io::Result<KeyPair> {
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+    let keypair = KeyPair::from_private_key_bytes(private_key)
+        .map_err(|e| invalid(format!("Invalid private key: {}", e)))?;
+    let expected = PublicKey::from_sec1_bytes(public_key)
+        .map_err(|e| invalid(format!("Invalid public key: {}", e)))?;
+    if expected != keypair.to_public_key() {
+        return Err(invalid(
+            "Public key does not belong to the private key".to_string(),
+        ));
+    }
+    Ok(keypair)
+}
@@ -0,0 +1,275 @@
+//! AWS KMS signer (the `kms` feature)
+//!
+//! Keeps the key in AWS KMS: the server only ever sends SHA-256 digests to
+//! `Sign` and reads the public key once from `GetPublicKey`. The key must be
+//! an asymmetric `ECC_SECG_P256K1` key with usage `SIGN_VERIFY`, so its
+//! `ECDSA_SHA_256` signatures verify exactly like the file-based ones.
+//!
+//! Requests are signed with AWS Signature Version 4 using the credentials in
+//! `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and (optionally)
+//! `AWS_SESSION_TOKEN`, read once at startup. Throttling, 5xx responses and
+//! network errors are retried up to `max_attempts` times in total.
+
+use super::{Signer, SignerError};
+use crate::config::KmsConfig;
+use crate::sigv4::{self, Credentials, Scope};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::Utc;
+use ecdsa_lib::{KeyPair, PublicKey};
+use k256::ecdsa::Signature;
+use k256::pkcs8::DecodePublicKey;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// The only KMS algorithm that matches `KeyPair::sign`
+const SIGNING_ALGORITHM: &str = "ECDSA_SHA_256";
+
+/// First retry delay; doubled on every further attempt
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Call counts and latencies of a [`KmsSigner`], since startup
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct KmsStats {
+    /// `Sign` calls answered with a signature
+    pub signatures: u64,
+    /// `Sign` calls that failed after every attempt
+    pub failures: u64,
+    /// Attempts beyond the first, over all calls
+    pub retries: u64,
+    /// Latency of the most recent successful `Sign` (retries included)
+    pub last_latency: Duration,
+    /// Slowest successful `Sign` so far
+    pub max_latency: Duration,
+    /// Sum of all successful `Sign` latencies, for averages
+    pub total_latency: Duration,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GetPublicKeyResponse {
+    public_key: String,
+    #[serde(default)]
+    signing_algorithms: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SignResponse {
+    signature: String,
+}
+
+/// A [`Signer`] backed by one AWS KMS key
+pub struct KmsSigner {
+    kms: Kms,
+    public_key: PublicKey,
+}
+
+/// The HTTP side: endpoint, credentials and counters
+struct Kms {
+    config: KmsConfig,
+    endpoint: String,
+    credentials: Credentials,
+    client: reqwest::Client,
+    /// Drives the HTTP calls, independently of the server's runtime
+    runtime: Option<tokio::runtime::Runtime>,
+    stats: Mutex<KmsStats>,
+}
+
+impl KmsSigner {
+    /// Reads the credentials and fetches the public key of `config.key_id`,
+    /// failing if the key can't be used for `ECDSA_SHA_256` on secp256k1
+    pub fn connect(config: &KmsConfig) -> Result<Self, SignerError> {
+        let kms = Kms {
+            endpoint: config
+                .endpoint
+                .clone()
+                .unwrap_or_else(|| format!("https://kms.{}.amazonaws.com", config.region)),
+            config: config.clone(),
+            credentials: Credentials::from_env()?,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_millis(config.timeout_ms))
+                .build()?,
+            runtime: Some(
+                tokio::runtime::Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .thread_name("vts-kms")
+                    .enable_all()
+                    .build()?,
+            ),
+            stats: Mutex::new(KmsStats::default()),
+        };
+
+        // 1) GetPublicKey returns a DER SubjectPublicKeyInfo
+        let body = serde_json::json!({ "KeyId": kms.config.key_id });
+        let response: GetPublicKeyResponse =
+            serde_json::from_slice(&kms.block_on(kms.call_with_retries("GetPublicKey", &body))?)?;
+        if !response.signing_algorithms.is_empty()
+            && !response
+                .signing_algorithms
+                .iter()
+                .any(|a| a == SIGNING_ALGORITHM)
+        {
+            return Err(format!("KMS key does not support {}", SIGNING_ALGORITHM).into());
+        }
+
+        // 2) Only secp256k1 keys (the curve is part of the SPKI)
+        let der = general_purpose::STANDARD.decode(&response.public_key)?;
+        let key = k256::PublicKey::from_public_key_der(&der)
+            .map_err(|_| "KMS key is not a secp256k1 (ECC_SECG_P256K1) key")?;
+        let public_key = PublicKey::from_sec1_bytes(&key.to_sec1_bytes())?;
+        Ok(Self { kms, public_key })
+    }
+
+    /// Calls and latencies so far
+    pub fn stats(&self) -> KmsStats {
+        self.kms.stats.lock().unwrap().clone()
+    }
+}
+
+impl Kms {
+    /// Runs `future` to completion on the signer's own runtime. It runs on a
+    /// fresh thread, so this works from inside the server's runtime too.
+    fn block_on<T: Send>(&self, future: impl Future<Output = T> + Send) -> T {
+        let handle = self.runtime.as_ref().expect("runtime until drop").handle();
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| handle.block_on(future))
+                .join()
+                .expect("KMS call panicked")
+        })
+    }
+
+    /// Up to `max_attempts` calls of `action`, backing off between them.
+    /// Returns the response body of the first success.
+    async fn call_with_retries(
+        &self,
+        action: &str,
+        body: &serde_json::Value,
+    ) -> Result<Vec<u8>, SignerError> {
+        let body = serde_json::to_vec(body)?;
+        let mut delay = RETRY_DELAY;
+        let mut attempt = 1;
+        loop {
+            match self.call(action, &body).await {
+                Ok(response) => return Ok(response),
+                Err((e, retryable)) if retryable && attempt < self.config.max_attempts => {
+                    warn!(
+                        "KMS {} failed (attempt {}/{}), retrying: {}",
+                        action, attempt, self.config.max_attempts, e
+                    );
+                    self.stats.lock().unwrap().retries += 1;
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err((e, _)) => return Err(e),
+            }
+        }
+    }
+
+    /// One KMS call; the flag says whether it is worth retrying
+    async fn call(&self, action: &str, body: &[u8]) -> Result<Vec<u8>, (SignerError, bool)> {
+        let url = reqwest::Url::parse(&self.endpoint).map_err(|e| (e.into(), false))?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let target = format!("TrentService.{}", action);
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+        // Signed headers, sorted by name
+        let mut headers = vec![
+            ("content-type", "application/x-amz-json-1.1".to_string()),
+            ("host", host),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        headers.push(("x-amz-target", target));
+        let scope = Scope {
+            region: &self.config.region,
+            service: "kms",
+        };
+        let authorization = sigv4::authorization(
+            &self.credentials,
+            &scope,
+            "POST",
+            "/",
+            &amz_date,
+            &headers,
+            &hex::encode(Sha256::digest(body)),
+        );
+
+        let mut request = self
+            .client
+            .post(url)
+            .header("authorization", authorization)
+            .body(body.to_vec());
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(*name, value);
+        }
+        let response = request.send().await.map_err(|e| (e.into(), true))?;
+        let status = response.status();
+        let bytes = response.bytes().await.map_err(|e| (e.into(), true))?;
+        if status.is_success() {
+            return Ok(bytes.to_vec());
+        }
+        let text = String::from_utf8_lossy(&bytes);
+        let retryable = status.is_server_error() || text.contains("ThrottlingException");
+        Err((format!("HTTP {}: {}", status, text).into(), retryable))
+    }
+}
+
+impl Signer for KmsSigner {
+    fn sign(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        // `DIGEST` keeps the request small whatever the message size
+        let kms = &self.kms;
+        let body = serde_json::json!({
+            "KeyId": kms.config.key_id,
+            "Message": general_purpose::STANDARD.encode(Sha256::digest(message)),
+            "MessageType": "DIGEST",
+            "SigningAlgorithm": SIGNING_ALGORITHM,
+        });
+        let start = Instant::now();
+        let result = kms
+            .block_on(kms.call_with_retries("Sign", &body))
+            .and_then(|response| {
+                let response: SignResponse = serde_json::from_slice(&response)?;
+                let der = general_purpose::STANDARD.decode(&response.signature)?;
+                // KMS doesn't normalize S; signature_from_der does
+                Ok(KeyPair::signature_from_der(&der)?)
+            });
+
+        let latency = start.elapsed();
+        let mut stats = kms.stats.lock().unwrap();
+        match result {
+            Ok(_) => {
+                stats.signatures += 1;
+                stats.last_latency = latency;
+                stats.max_latency = stats.max_latency.max(latency);
+                stats.total_latency += latency;
+            }
+            Err(_) => stats.failures += 1,
+        }
+        result
+    }
+
+    fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+}
+
+/// Dropping a runtime inside another one panics, so the signer's runtime
+/// is shut down in the background instead
+impl Drop for Kms {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
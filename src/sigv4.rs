@@ -0,0 +1,108 @@
+//! AWS Signature Version 4, for the KMS signer and the S3 archive
+//!
+//! Only what those two send: no query strings, and header values that are
+//! already trimmed. Credentials come from `AWS_ACCESS_KEY_ID`,
+//! `AWS_SECRET_ACCESS_KEY` and (optionally) `AWS_SESSION_TOKEN`.
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+/// AWS credentials from the environment
+pub(crate) struct Credentials {
+    pub access_key_id: String,
+    secret_access_key: Zeroizing<String>,
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    pub fn from_env() -> Result<Self, String> {
+        let var = |name: &str| std::env::var(name).map_err(|_| format!("{} is not set", name));
+        Ok(Self {
+            access_key_id: var("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: Zeroizing::new(var("AWS_SECRET_ACCESS_KEY")?),
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+}
+
+/// Region and service a request is signed for
+pub(crate) struct Scope<'a> {
+    pub region: &'a str,
+    pub service: &'a str,
+}
+
+/// The `Authorization` header of a `method` request for `path` (already
+/// URI-encoded). `headers` are the signed headers, lowercase and sorted by
+/// name; `payload_hash` is the hex SHA-256 of the body.
+pub(crate) fn authorization(
+    credentials: &Credentials,
+    scope: &Scope,
+    method: &str,
+    path: &str,
+    amz_date: &str,
+    headers: &[(&str, String)],
+    payload_hash: &str,
+) -> String {
+    let date = &amz_date[..8];
+    let credential_scope = format!("{}/{}/{}/aws4_request", date, scope.region, scope.service);
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+
+    // 1) Canonical request
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let canonical_request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        method, path, canonical_headers, signed_headers, payload_hash
+    );
+
+    // 2) String to sign
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        credential_scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    // 3) Signing key, derived from the secret through the scope
+    let secret = Zeroizing::new(format!("AWS4{}", credentials.secret_access_key.as_str()));
+    let mut key = hmac_sha256(secret.as_bytes(), date.as_bytes());
+    for part in [scope.region, scope.service, "aws4_request"] {
+        key = hmac_sha256(key.as_slice(), part.as_bytes());
+    }
+    let signature = hmac_sha256(key.as_slice(), string_to_sign.as_bytes());
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id,
+        credential_scope,
+        signed_headers,
+        hex::encode(signature.as_slice())
+    )
+}
+
+/// `path` as SigV4 and S3 want it: everything but unreserved characters
+/// and `/` percent-encoded
+#[cfg(feature = "s3")]
+pub(crate) fn uri_encode(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Zeroizing<[u8; 32]> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(data);
+    Zeroizing::new(mac.finalize().into_bytes().into())
+}
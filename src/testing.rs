@@ -0,0 +1,617 @@
+//! An in-memory VTS server for unit tests (the `testing` feature)
+//!
+//! A [`MockVts`] speaks the wire protocol of `GET /key`, `GET /keys`,
+//! `GET /version` and `POST /sign` (JSON, JWS and COSE, text, binary and
+//! digest messages) without a socket: a [`crate::client::VtsClient`] or
+//! [`crate::client::nonblocking::VtsClient`] for its [`MockVts::url`]
+//! hands its requests straight to it. The key comes from a seed and the
+//! clock only moves when told, so a test gets the same bytes on every run,
+//! as a server with that key and clock would send them.
+//!
+//! Failures are injected with [`MockVts::fail_next`] (the next request) or
+//! [`MockVts::fail_always`] (every request until cleared).
+//!
+//! # Example
+//! ```
+//! # #[cfg(feature = "blocking")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use lab4::client::{ClientOptions, VtsClient};
+//! use lab4::ecdsa_requests::verify_signature;
+//! use lab4::testing::{Failure, MockVts};
+//!
+//! let mock = MockVts::new();
+//! let options = ClientOptions { retries: 0, ..Default::default() };
+//! let client = VtsClient::with_options(&mock.url(), options)?;
+//! let key = client.request_key()?;
+//! assert!(verify_signature(&client.request_timestamp("hello")?, &key));
+//!
+//! mock.fail_next(Failure::BadSignature);
+//! assert!(!verify_signature(&client.request_timestamp("hello")?, &key));
+//! mock.fail_next(Failure::Timeout);
+//! assert!(client.request_timestamp("hello").is_err());
+//! # Ok(()) }
+//! # #[cfg(not(feature = "blocking"))]
+//! # fn main() {}
+//! ```
+
+use crate::alg::{DigestAlg, HashAlg, SignatureAlg};
+use crate::cose::{self, CoseClaims};
+use crate::jws::{self, JwsClaims};
+use crate::wire::{self, TimePrecision};
+use crate::{ApiError, PROTOCOL_VERSIONS};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Duration, Utc};
+use ecdsa_lib::{KeyPair, PublicKey};
+use hyper014::body::Bytes;
+use hyper014::header::{ACCEPT, CONTENT_TYPE, HeaderMap};
+use hyper014::{Method, StatusCode};
+use k256::ecdsa::Signature;
+use k256::sha2::{Digest, Sha256};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+
+/// Scheme of a [`MockVts`] address, `vts-mock://<id>`
+pub const MOCK_SCHEME: &str = "vts-mock";
+
+/// Header a request's id comes in, and goes back in
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The seed of [`MockVts::new`]'s key
+const DEFAULT_SEED: [u8; 32] = [1; 32];
+
+/// Every live mock, by the id in its address
+static MOCKS: Mutex<BTreeMap<u64, Weak<Inner>>> = Mutex::new(BTreeMap::new());
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// What goes wrong with a request instead of its answer
+#[derive(Debug, Clone, PartialEq)]
+pub enum Failure {
+    /// The request times out, at once rather than after `timeout`, as an
+    /// `io::ErrorKind::TimedOut` error (which the clients retry)
+    Timeout,
+    /// The answer is `status` with an `ApiError` body of `code`, e.g.
+    /// `503` and `"overloaded"`
+    Status(u16, String),
+    /// A timestamp comes back with a signature that doesn't verify;
+    /// other requests are answered as usual
+    BadSignature,
+    /// The answer comes from another key: `GET /key` and `/keys` serve it
+    /// and `POST /sign` signs with it, under the usual `kid`
+    WrongKey,
+}
+
+/// A VTS server in memory, for testing code that calls one. Cloning is
+/// cheap and shares the server; it is gone once the last clone is dropped.
+#[derive(Clone)]
+pub struct MockVts {
+    id: u64,
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    key: KeyPair,
+    /// The key of `Failure::WrongKey`
+    other_key: KeyPair,
+    state: Mutex<State>,
+}
+
+struct State {
+    now: DateTime<Utc>,
+    /// Last `time-signed`, so the next is later
+    last: Option<DateTime<Utc>>,
+    serial: u64,
+    failures: VecDeque<Failure>,
+    always: Option<Failure>,
+    /// `METHOD path` of each request, oldest first
+    requests: Vec<String>,
+}
+
+impl Default for MockVts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockVts {
+    /// A mock with a fixed key, its clock at 2025-01-01T00:00:00Z
+    pub fn new() -> Self {
+        Self::with_seed(&DEFAULT_SEED)
+    }
+
+    /// A mock whose key is `KeyPair::from_seed(seed)`
+    pub fn with_seed(seed: &[u8; 32]) -> Self {
+        let mut other_seed = *seed;
+        other_seed[0] ^= 0xff;
+        let inner = Arc::new(Inner {
+            key: KeyPair::from_seed(seed),
+            other_key: KeyPair::from_seed(&other_seed),
+            state: Mutex::new(State {
+                now: DateTime::from_timestamp(1_735_689_600, 0).unwrap_or_default(),
+                last: None,
+                serial: 0,
+                failures: VecDeque::new(),
+                always: None,
+                requests: Vec::new(),
+            }),
+        });
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let mut mocks = lock(&MOCKS);
+        mocks.retain(|_, mock| mock.strong_count() > 0);
+        mocks.insert(id, Arc::downgrade(&inner));
+        MockVts { id, inner }
+    }
+
+    /// The address to give a client, `vts-mock://<id>`
+    pub fn url(&self) -> String {
+        format!("{}://{}", MOCK_SCHEME, self.id)
+    }
+
+    /// The key timestamps are signed with, as `GET /key` serves it
+    pub fn public_key(&self) -> PublicKey {
+        self.inner.key.to_public_key()
+    }
+
+    /// Moves the clock to `now`. Timestamps still never go backwards: each
+    /// is at least a microsecond after the one before, as from a server.
+    pub fn set_time(&self, now: DateTime<Utc>) {
+        lock(&self.inner.state).now = now;
+    }
+
+    /// Fails the next request not already set to fail with `failure`
+    pub fn fail_next(&self, failure: Failure) {
+        lock(&self.inner.state).failures.push_back(failure);
+    }
+
+    /// Fails every request with `failure` (after those of `fail_next`), or
+    /// none again with `None`
+    pub fn fail_always(&self, failure: Option<Failure>) {
+        lock(&self.inner.state).always = failure;
+    }
+
+    /// `METHOD path` of every request so far, e.g. `POST /sign`
+    pub fn requests(&self) -> Vec<String> {
+        lock(&self.inner.state).requests.clone()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Body of GET /key, as the server writes it
+#[derive(Serialize)]
+struct KeyResponse {
+    request: &'static str,
+    #[serde(rename = "time-requested", with = "wire::serde_canonical")]
+    time_requested: DateTime<Utc>,
+    #[serde(rename = "public-key")]
+    public_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'static str>,
+    #[serde(rename = "signature-alg")]
+    signature_alg: SignatureAlg,
+    #[serde(rename = "hash-alg")]
+    hash_alg: HashAlg,
+    kid: String,
+}
+
+#[derive(Serialize)]
+struct KeysResponse {
+    request: &'static str,
+    keys: Vec<KeyResponse>,
+}
+
+#[derive(Serialize)]
+struct VersionResponse {
+    request: &'static str,
+    #[serde(rename = "server-version")]
+    server_version: &'static str,
+    #[serde(rename = "protocol-versions")]
+    protocol_versions: &'static [&'static str],
+    features: Vec<&'static str>,
+    #[serde(rename = "time-precision")]
+    time_precision: TimePrecision,
+}
+
+/// Body of POST /sign, as the server writes it
+#[derive(Serialize)]
+struct SignResponse {
+    request: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(rename = "message-b64", skip_serializing_if = "Option::is_none")]
+    message_b64: Option<String>,
+    #[serde(rename = "time-signed", with = "wire::serde_canonical")]
+    time_signed: DateTime<Utc>,
+    signature: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoding: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'static str>,
+    #[serde(rename = "signature-alg")]
+    signature_alg: SignatureAlg,
+    #[serde(rename = "hash-alg")]
+    hash_alg: HashAlg,
+    kid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    policy: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SignRequest {
+    message: Option<String>,
+    #[serde(rename = "message-b64")]
+    message_b64: Option<String>,
+    digest: Option<String>,
+    #[serde(rename = "digest-alg")]
+    digest_alg: Option<String>,
+    encoding: Option<String>,
+    format: Option<String>,
+    kid: Option<String>,
+    policy: Option<String>,
+}
+
+/// How a timestamp comes back
+#[derive(Clone, Copy, PartialEq)]
+enum Token {
+    Json,
+    Jws,
+    Cose,
+}
+
+/// One request, as the client built it
+struct Exchange<'a> {
+    method: &'a Method,
+    url: &'a reqwest::Url,
+    headers: &'a HeaderMap,
+    body: &'a [u8],
+}
+
+impl Exchange<'_> {
+    fn query(&self, name: &str) -> Option<String> {
+        self.url
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    fn header(&self, name: impl hyper014::header::AsHeaderName) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+
+    /// The path without the `/v1` and `/t/{tenant}` prefixes; every tenant
+    /// of a mock shares its key
+    fn route(&self) -> &str {
+        let path = self.url.path();
+        let path = path.strip_prefix("/v1").unwrap_or(path);
+        match path
+            .strip_prefix("/t/")
+            .and_then(|rest| rest.find('/').map(|i| &rest[i..]))
+        {
+            Some(rest) => rest,
+            None => path,
+        }
+    }
+}
+
+impl Inner {
+    /// 1) Records the request, 2) applies the failure due, 3) answers it
+    fn answer(&self, exchange: &Exchange) -> Result<hyper014::Response<Bytes>, BoxError> {
+        let mut state = lock(&self.state);
+        state
+            .requests
+            .push(format!("{} {}", exchange.method, exchange.url.path()));
+        let failure = state.failures.pop_front().or_else(|| state.always.clone());
+        let request_id = exchange
+            .header(REQUEST_ID_HEADER)
+            .map_or_else(|| format!("mock-{}", state.requests.len()), str::to_string);
+
+        let result = match failure {
+            Some(Failure::Timeout) => {
+                return Err(std::io::Error::from(std::io::ErrorKind::TimedOut).into());
+            }
+            Some(Failure::Status(status, code)) => {
+                let status = StatusCode::from_u16(status)?;
+                let message = status.canonical_reason().unwrap_or("Mock failure");
+                Err((status, error_body(&code, message, None)))
+            }
+            Some(Failure::WrongKey) => self.route(&mut state, exchange, &self.other_key, false),
+            Some(Failure::BadSignature) => self.route(&mut state, exchange, &self.key, true),
+            None => self.route(&mut state, exchange, &self.key, false),
+        };
+        let (status, content_type, body) = match result {
+            Ok((content_type, body)) => (StatusCode::OK, content_type, body),
+            Err((status, mut err)) => {
+                err.request_id = Some(request_id.clone());
+                (status, "application/json", serde_json::to_vec(&err)?)
+            }
+        };
+        Ok(hyper014::Response::builder()
+            .status(status)
+            .header(CONTENT_TYPE, content_type)
+            .header(REQUEST_ID_HEADER, request_id)
+            .body(Bytes::from(body))?)
+    }
+
+    /// The answer from `key`, under the `kid` of the real key (as from a
+    /// server whose key was swapped under it)
+    fn route(&self, state: &mut State, exchange: &Exchange, key: &KeyPair, bad: bool) -> Answer {
+        let served = self.key.to_public_key();
+        let hex = exchange.query("format").as_deref() == Some("hex");
+        match (exchange.method, exchange.route()) {
+            (&Method::GET, "/key") => json(&key_response(key, &served, state.now, hex)),
+            (&Method::GET, "/keys") => json(&KeysResponse {
+                request: "GET",
+                keys: vec![key_response(key, &served, state.now, hex)],
+            }),
+            (&Method::GET, "/version") => json(&VersionResponse {
+                request: "GET",
+                server_version: env!("CARGO_PKG_VERSION"),
+                protocol_versions: PROTOCOL_VERSIONS,
+                features: Vec::new(),
+                time_precision: TimePrecision::Micros,
+            }),
+            (&Method::POST, "/sign") => {
+                // A bad signature is a good one over other bytes
+                let sign = |input: &[u8]| match bad {
+                    true => key.sign(&[input, b"!"].concat()),
+                    false => key.sign(input),
+                };
+                sign_response(state, exchange, &served, sign)
+            }
+            _ => Err((
+                StatusCode::NOT_FOUND,
+                error_body(
+                    "not_found",
+                    &format!("No route for {}", exchange.url.path()),
+                    None,
+                ),
+            )),
+        }
+    }
+}
+
+/// A successful answer: its `Content-Type` and body
+type Answer = Result<(&'static str, Vec<u8>), (StatusCode, ApiError)>;
+
+fn json(body: &impl Serialize) -> Answer {
+    serde_json::to_vec(body)
+        .map(|body| ("application/json", body))
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                error_body("internal", &e.to_string(), None),
+            )
+        })
+}
+
+fn key_response(key: &KeyPair, served: &PublicKey, now: DateTime<Utc>, hex: bool) -> KeyResponse {
+    let public_key = key.to_public_key();
+    KeyResponse {
+        request: "GET",
+        time_requested: now,
+        public_key: match hex {
+            true => format!("{public_key:x}"),
+            false => public_key.to_string(),
+        },
+        format: hex.then_some("hex"),
+        signature_alg: SignatureAlg::default(),
+        hash_alg: HashAlg::default(),
+        kid: jws::key_id(served),
+    }
+}
+
+/// POST /sign: the request checked as the server checks it, then the
+/// timestamp in the form asked for
+fn sign_response(
+    state: &mut State,
+    exchange: &Exchange,
+    served: &PublicKey,
+    sign: impl Fn(&[u8]) -> Signature,
+) -> Answer {
+    let kid = jws::key_id(served);
+    let invalid = |field: Option<&str>, message: String| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            error_body("invalid_json", &message, field),
+        )
+    };
+    let request: SignRequest = serde_json::from_slice(exchange.body).map_err(|e| {
+        invalid(
+            None,
+            format!(
+                "invalid JSON at line {} column {}: {}",
+                e.line(),
+                e.column(),
+                e.to_string().split(" at line ").next().unwrap_or_default()
+            ),
+        )
+    })?;
+    if request.kid.as_deref().is_some_and(|k| k != kid) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            error_body("unknown_kid", "Unknown key id", Some("kid")),
+        ));
+    }
+
+    // 1) The message: text, Base64 or `<digest-alg>:<hex digest>`
+    let (message, message_b64) = match (request.message, request.message_b64, request.digest) {
+        (Some(text), None, None) => (text, None),
+        (None, Some(b64), None) => {
+            let bytes = general_purpose::STANDARD.decode(b64.trim()).map_err(|_| {
+                invalid(
+                    Some("message-b64"),
+                    "invalid value for 'message-b64': not Base64".to_string(),
+                )
+            })?;
+            (String::new(), Some(bytes))
+        }
+        (None, None, Some(digest)) => {
+            let name = request.digest_alg.as_deref().unwrap_or("sha-256");
+            let alg = DigestAlg::from_name(name).ok_or_else(|| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    error_body(
+                        "unsupported_algorithm",
+                        &format!("Unsupported algorithm: {}", name),
+                        Some("digest-alg"),
+                    ),
+                )
+            })?;
+            match hex::decode(digest.trim()) {
+                Ok(bytes) if bytes.len() == alg.digest_len() => (alg.message(&bytes), None),
+                _ => {
+                    return Err(invalid(
+                        Some("digest"),
+                        format!(
+                            "'digest' must be {} hex bytes of {}",
+                            alg.digest_len(),
+                            name
+                        ),
+                    ));
+                }
+            }
+        }
+        (None, None, None) => {
+            return Err(invalid(
+                Some("message"),
+                "missing field 'message'".to_string(),
+            ));
+        }
+        _ => {
+            return Err(invalid(
+                Some("message"),
+                "give one of 'message', 'message-b64' and 'digest'".to_string(),
+            ));
+        }
+    };
+    let bytes = message_b64.as_deref().unwrap_or(message.as_bytes());
+
+    // 2) The time and serial, never going backwards
+    let time_signed = match state.last {
+        Some(last) if last >= state.now => last + Duration::microseconds(1),
+        _ => TimePrecision::Micros.truncate(state.now),
+    };
+    state.last = Some(time_signed);
+    state.serial += 1;
+    let policy = request.policy;
+
+    // 3) The token asked for, with `?format=` or `Accept`
+    let accept = exchange.header(ACCEPT).unwrap_or_default();
+    let token = match exchange.query("format").as_deref() {
+        Some("jws") => Token::Jws,
+        Some("cose") => Token::Cose,
+        _ if accept.contains("application/jose") => Token::Jws,
+        _ if accept.contains("application/cose") => Token::Cose,
+        _ => Token::Json,
+    };
+    let digest: [u8; 32] = Sha256::digest(bytes).into();
+    let never = |e: std::convert::Infallible| match e {};
+    match token {
+        Token::Jws => {
+            let claims = JwsClaims {
+                msg_hash: hex::encode(digest),
+                iat: time_signed.timestamp(),
+                serial: state.serial,
+                kid: kid.clone(),
+                policy,
+                day_root: None,
+            };
+            let token = jws::encode(&claims, |input| Ok(sign(input))).map_err(never)?;
+            Ok((jws::CONTENT_TYPE, token.into_bytes()))
+        }
+        Token::Cose => {
+            let claims = CoseClaims {
+                msg_hash: digest,
+                iat: time_signed.timestamp(),
+                serial: state.serial,
+                kid: jws::key_thumbprint(served),
+                policy,
+                day_root: None,
+            };
+            let token = cose::encode(&claims, |input| Ok(sign(input))).map_err(never)?;
+            Ok((cose::CONTENT_TYPE, token))
+        }
+        Token::Json => {
+            let time = wire::canonical_time(time_signed);
+            let signature = sign(&wire::policy_signing_input(bytes, &time, policy.as_deref()));
+            let der = request.encoding.as_deref() == Some("der");
+            let signature = match der {
+                true => KeyPair::signature_to_der(&signature),
+                false => signature.to_vec(),
+            };
+            let hex = request.format.as_deref() == Some("hex");
+            json(&SignResponse {
+                request: "POST",
+                message: message_b64.is_none().then_some(message),
+                message_b64: message_b64.map(|b| general_purpose::STANDARD.encode(b)),
+                time_signed,
+                signature: match hex {
+                    true => hex::encode(&signature),
+                    false => general_purpose::STANDARD.encode(&signature),
+                },
+                encoding: der.then_some("der"),
+                format: hex.then_some("hex"),
+                signature_alg: SignatureAlg::default(),
+                hash_alg: HashAlg::default(),
+                kid,
+                policy,
+            })
+        }
+    }
+}
+
+fn error_body(code: &str, message: &str, field: Option<&str>) -> ApiError {
+    ApiError {
+        code: code.to_string(),
+        message: message.to_string(),
+        request_id: None,
+        field: field.map(str::to_string),
+        status: None,
+    }
+}
+
+/// The mock a `vts-mock://<id>` request is for; as if nothing listened
+/// there once it is dropped
+fn mock_for(url: &reqwest::Url) -> Result<Arc<Inner>, BoxError> {
+    let id: Option<u64> = url.host_str().and_then(|host| host.parse().ok());
+    id.and_then(|id| lock(&MOCKS).get(&id)?.upgrade())
+        .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into())
+}
+
+pub(crate) fn send(request: reqwest::Request) -> Result<reqwest::Response, BoxError> {
+    let body = request
+        .body()
+        .and_then(reqwest::Body::as_bytes)
+        .unwrap_or_default();
+    let resp = mock_for(request.url())?.answer(&Exchange {
+        method: request.method(),
+        url: request.url(),
+        headers: request.headers(),
+        body,
+    })?;
+    Ok(reqwest::Response::from(resp))
+}
+
+#[cfg(feature = "blocking")]
+pub(crate) fn send_blocking(
+    request: reqwest::blocking::Request,
+) -> Result<reqwest::blocking::Response, BoxError> {
+    let body = request
+        .body()
+        .and_then(reqwest::blocking::Body::as_bytes)
+        .unwrap_or_default();
+    let resp = mock_for(request.url())?.answer(&Exchange {
+        method: request.method(),
+        url: request.url(),
+        headers: request.headers(),
+        body,
+    })?;
+    Ok(reqwest::blocking::Response::from(resp))
+}
@@ -0,0 +1,364 @@
+//! Checking the audit log from outside: signed tree heads, peers'
+//! co-signatures, consistency and inclusion proofs
+//!
+//! A [`TreeHead`] is the Merkle root over every line's leaf so far (the
+//! same leaves as the daily trees, in `seq` order), signed when it is
+//! served at `GET /log/root`. A peer server that checked a head signs a
+//! [`Cosignature`]: it saw this log at `count` lines with `root` no later
+//! than its own `time`. Lines covered by it can't be backdated afterwards
+//! without the peer's key as well (see `[gossip]`).
+//!
+//! A [`ConsistencyProof`] from `GET /log/consistency?old=M&new=N` shows
+//! that the head at `N` lines extends the one at `M` (RFC 6962 §2.1.2), so
+//! an auditor holding an old head can tell the log was only appended to
+//! since, without the lines themselves.
+//!
+//! An [`InclusionProof`] from `GET /log/proof?seq=N&count=M` carries line
+//! `N` as written and shows it is in the tree of a head at `M` lines.
+//! [`verify_timestamp_proof`] checks a timestamp all the way down: its
+//! signature, the line recording it, the line's place in the tree, and
+//! the head's signature.
+//!
+//! An [`Anchor`] is a head as an external witness received it (see
+//! `[anchor]`), with what the witness answered. `GET /anchor/{serial}`
+//! gives an [`AnchorProof`]: the timestamp's line in the tree of the first
+//! head anchored after it.
+
+use crate::ecdsa_requests::verify_signature;
+use crate::{EcdsaSignedTimestamp, EcdsaVerificationKey, jws, merkle};
+use base64::{Engine as _, engine::general_purpose};
+use ecdsa_lib::{KeyPair, PublicKey};
+use k256::sha2::{Digest, Sha256};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Root over the first `count` lines of the log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct TreeHead {
+    /// Lines covered: `seq` 1 to `count`, compacted ones included
+    pub count: u64,
+    /// Hex Merkle root over their leaves
+    pub root: String,
+    /// When it was signed
+    pub time: String,
+    /// Key id of the key that signed it
+    pub kid: String,
+    /// Base64 DER signature over the head without `sig`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sig: Option<String>,
+}
+
+impl TreeHead {
+    pub(crate) fn signed_bytes(&self) -> Vec<u8> {
+        let unsigned = TreeHead {
+            sig: None,
+            ..self.clone()
+        };
+        serde_json::to_vec(&unsigned).expect("tree head serializes")
+    }
+
+    /// Whether `sig` is `public_key`'s signature over this head
+    pub fn verify(&self, public_key: &PublicKey) -> bool {
+        verify_sig(&self.kid, &self.sig, &self.signed_bytes(), public_key)
+    }
+
+    /// Checks that one of `keys` (from `GET /keys` of the default tenant,
+    /// which signs the log) signed the head
+    pub fn verify_with(&self, keys: &[EcdsaVerificationKey]) -> Result<(), LogError> {
+        let public_key = keys
+            .iter()
+            .filter_map(|key| key.key())
+            .find(|key| jws::key_id(key) == self.kid)
+            .ok_or_else(|| LogError::UnknownKey(self.kid.clone()))?;
+        match self.verify(&public_key) {
+            true => Ok(()),
+            false => Err(LogError::Invalid),
+        }
+    }
+
+    fn root_hash(&self) -> Option<[u8; 32]> {
+        hex::decode(&self.root).ok()?.try_into().ok()
+    }
+}
+
+/// One server vouching for another's tree head
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Cosignature {
+    /// Key id of the log whose head it is
+    pub log: String,
+    pub count: u64,
+    /// Hex root of that log over `count` lines
+    pub root: String,
+    /// When the co-signer checked the head, by its own clock
+    pub time: String,
+    /// Key id of the co-signer
+    pub kid: String,
+    /// Base64 DER signature over the co-signature without `sig`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sig: Option<String>,
+}
+
+impl Cosignature {
+    pub(crate) fn signed_bytes(&self) -> Vec<u8> {
+        let unsigned = Cosignature {
+            sig: None,
+            ..self.clone()
+        };
+        serde_json::to_vec(&unsigned).expect("co-signature serializes")
+    }
+
+    /// Whether `sig` is the co-signer `public_key`'s signature over this
+    pub fn verify(&self, public_key: &PublicKey) -> bool {
+        verify_sig(&self.kid, &self.sig, &self.signed_bytes(), public_key)
+    }
+
+    /// Whether this co-signs `head` (the signatures aside)
+    pub fn covers(&self, head: &TreeHead) -> bool {
+        self.log == head.kid && self.count == head.count && self.root == head.root
+    }
+}
+
+/// That the log at `new` lines extends the log at `old` lines
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct ConsistencyProof {
+    pub old: u64,
+    pub new: u64,
+    /// Hex node hashes of RFC 6962 §2.1.2, in order
+    pub proof: Vec<String>,
+}
+
+impl ConsistencyProof {
+    /// Whether this proves `new` extends `old` (check their signatures
+    /// too, with [`TreeHead::verify`])
+    pub fn verify(&self, old: &TreeHead, new: &TreeHead) -> bool {
+        let proof: Option<Vec<[u8; 32]>> = self
+            .proof
+            .iter()
+            .map(|hash| hex::decode(hash).ok()?.try_into().ok())
+            .collect();
+        let (Some(proof), Some(old_root), Some(new_root)) =
+            (proof, old.root_hash(), new.root_hash())
+        else {
+            return false;
+        };
+        self.old == old.count
+            && self.new == new.count
+            && old.kid == new.kid
+            && merkle::verify_consistency(old.count, new.count, &old_root, &new_root, &proof)
+    }
+}
+
+/// Line `seq` of the log, and where it sits in the tree over `count` lines
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct InclusionProof {
+    pub seq: u64,
+    pub count: u64,
+    /// The audit log line, exactly as written; its leaf is
+    /// `SHA-256(0x00 || SHA-256(line))`
+    pub line: String,
+    /// Hex sibling hashes from the line's leaf (index `seq - 1`) up to the
+    /// root, bottom first
+    pub proof: Vec<String>,
+}
+
+impl InclusionProof {
+    /// Whether `line` is in the tree `head` signs (check `head.verify` too)
+    pub fn verify(&self, head: &TreeHead) -> bool {
+        let proof: Option<Vec<[u8; 32]>> = self
+            .proof
+            .iter()
+            .map(|hash| hex::decode(hash).ok()?.try_into().ok())
+            .collect();
+        let (Some(proof), Some(root)) = (proof, head.root_hash()) else {
+            return false;
+        };
+        let leaf = merkle::leaf_hash(&Sha256::digest(self.line.as_bytes()));
+        self.seq >= 1
+            && self.count == head.count
+            && merkle::verify_inclusion(&leaf, self.seq - 1, self.count, &proof, &root)
+    }
+}
+
+/// The fields of an audit log line that tie it to a timestamp
+#[derive(Deserialize)]
+struct LineRecord {
+    seq: u64,
+    time: String,
+    hash: String,
+    #[serde(default)]
+    signature: Option<String>,
+    #[serde(default)]
+    policy: Option<String>,
+}
+
+/// The outcome of one step of [`verify_timestamp_proof`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    Verified,
+    Failed,
+    /// Nothing to check it with: the pinned key doesn't parse, or the line
+    /// predates recording signatures
+    Unverifiable,
+}
+
+impl Check {
+    fn of(verified: bool) -> Self {
+        match verified {
+            true => Check::Verified,
+            false => Check::Failed,
+        }
+    }
+}
+
+/// What [`verify_timestamp_proof`] could and couldn't verify, step by step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofReport {
+    /// The timestamp's signature, with the pinned key
+    pub signature: Check,
+    /// The line records this timestamp: its message hash, `time-signed`,
+    /// policy and (if recorded) signature
+    pub line: Check,
+    /// The line is in the tree the head signs
+    pub inclusion: Check,
+    /// The head's signature, with the pinned key
+    pub tree_head: Check,
+}
+
+impl ProofReport {
+    /// Whether every step verified
+    pub fn is_verified(&self) -> bool {
+        [self.signature, self.line, self.inclusion, self.tree_head]
+            .iter()
+            .all(|check| *check == Check::Verified)
+    }
+}
+
+/// Checks the chain from `signed` to `head`: the timestamp's signature,
+/// the audit log line of `inclusion` recording it, that line's place in
+/// the tree of `head`, and `head`'s signature, both signatures with
+/// `pinned_key`. Every step is checked, so the report says which fail.
+pub fn verify_timestamp_proof(
+    signed: &EcdsaSignedTimestamp,
+    inclusion: &InclusionProof,
+    head: &TreeHead,
+    pinned_key: &EcdsaVerificationKey,
+) -> ProofReport {
+    let public_key = pinned_key.key();
+
+    // 1) The timestamp
+    let signature = match public_key {
+        Some(_) => Check::of(verify_signature(signed, pinned_key)),
+        None => Check::Unverifiable,
+    };
+
+    // 2) The line recording it
+    let hash = signed
+        .message_bytes()
+        .map(|message| hex::encode(Sha256::digest(message)));
+    let line = match serde_json::from_str::<LineRecord>(&inclusion.line) {
+        Ok(record)
+            if record.seq == inclusion.seq
+                && record.time == signed.time_signed
+                && hash.is_some_and(|hash| record.hash.eq_ignore_ascii_case(&hash))
+                && record.policy == signed.policy =>
+        {
+            match record.signature {
+                Some(recorded) => Check::of(
+                    general_purpose::STANDARD.decode(recorded).ok() == signed.signature_bytes(),
+                ),
+                None => Check::Unverifiable,
+            }
+        }
+        _ => Check::Failed,
+    };
+
+    // 3) The line in the tree, and 4) the tree's head
+    let inclusion = Check::of(inclusion.verify(head));
+    let tree_head = match public_key {
+        Some(public_key) => Check::of(head.verify(&public_key)),
+        None => Check::Unverifiable,
+    };
+    ProofReport {
+        signature,
+        line,
+        inclusion,
+        tree_head,
+    }
+}
+
+/// A head as the `[anchor]` witness received it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Anchor {
+    /// URL the head was posted to
+    pub witness: String,
+    /// When it was posted, by the server's clock
+    pub time: String,
+    pub head: TreeHead,
+    /// What the witness answered, exactly: its evidence of seeing the head,
+    /// to be checked by the witness's own rules
+    #[cfg_attr(feature = "server", schema(value_type = Object))]
+    pub receipt: serde_json::Value,
+}
+
+/// Timestamp `serial`'s line in the tree of an anchored head
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct AnchorProof {
+    pub serial: u64,
+    pub anchor: Anchor,
+    pub inclusion: InclusionProof,
+}
+
+impl AnchorProof {
+    /// [`verify_timestamp_proof`] of `signed` up to the anchored head; the
+    /// receipt is left to the witness's rules
+    pub fn verify(
+        &self,
+        signed: &EcdsaSignedTimestamp,
+        pinned_key: &EcdsaVerificationKey,
+    ) -> ProofReport {
+        verify_timestamp_proof(signed, &self.inclusion, &self.anchor.head, pinned_key)
+    }
+}
+
+/// Why a tree head or consistency proof was not accepted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogError {
+    /// Signed by none of the keys given; the key id it names
+    UnknownKey(String),
+    /// The signature doesn't verify
+    Invalid,
+    /// The newer head doesn't extend the older one: the log was rewritten,
+    /// or the server shows different clients different logs
+    Inconsistent { old: u64, new: u64 },
+}
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogError::UnknownKey(kid) => write!(f, "tree head signed by an unknown key ({})", kid),
+            LogError::Invalid => write!(f, "tree head signature does not verify"),
+            LogError::Inconsistent { old, new } => write!(
+                f,
+                "the log at {} lines does not extend the log at {} lines",
+                new, old
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LogError {}
+
+fn verify_sig(kid: &str, sig: &Option<String>, signed: &[u8], public_key: &PublicKey) -> bool {
+    kid == jws::key_id(public_key)
+        && sig
+            .as_deref()
+            .and_then(|sig| general_purpose::STANDARD.decode(sig).ok())
+            .and_then(|der| KeyPair::signature_from_der(&der).ok())
+            .is_some_and(|signature| public_key.verify(signed, &signature))
+}
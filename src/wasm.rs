@@ -0,0 +1,79 @@
+//! Verification in the browser, without a server round-trip
+//!
+//! With the `wasm` feature (and `default-features = false`), the crate
+//! builds for `wasm32-unknown-unknown` and exports these functions to
+//! JavaScript via `wasm-bindgen`:
+//!
+//! ```text
+//! wasm-pack build --target web --no-default-features --features wasm
+//! ```
+//!
+//! Timestamps and keys are passed as the JSON the server returns from
+//! `/sign` and `/key`, so a page can verify a response it got from
+//! anywhere. Nothing here touches the network or the clock.
+
+use crate::ecdsa_requests::{VerifyOptions, VerifyOutcome, verify_signature_with};
+use crate::{EcdsaSignedTimestamp, EcdsaVerificationKey};
+use k256::sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// Checks a `/sign` response against a `/key` response. Returns `"ok"` or
+/// why not: `"invalid_json"`, `"bad_encoding"`, `"bad_key"`,
+/// `"bad_signature"`, `"bad_timestamp"`, `"unsupported_algorithm"` or
+/// `"outside_key_validity"`.
+#[wasm_bindgen(js_name = verifyTimestamp)]
+pub fn verify_timestamp(signed_json: &str, key_json: &str) -> String {
+    let (Ok(signed), Some(key)) = (
+        serde_json::from_str::<EcdsaSignedTimestamp>(signed_json),
+        parse_key(key_json),
+    ) else {
+        return "invalid_json".to_string();
+    };
+    let outcome = match verify_signature_with(&signed, &key, &VerifyOptions::default()) {
+        VerifyOutcome::Ok => "ok",
+        VerifyOutcome::BadEncoding => "bad_encoding",
+        VerifyOutcome::BadKey => "bad_key",
+        VerifyOutcome::BadSignature => "bad_signature",
+        VerifyOutcome::BadTimestamp => "bad_timestamp",
+        VerifyOutcome::TimeSkew => "time_skew",
+        VerifyOutcome::InFuture => "in_future",
+        VerifyOutcome::UnsupportedAlgorithm => "unsupported_algorithm",
+        VerifyOutcome::OutsideKeyValidity => "outside_key_validity",
+        VerifyOutcome::Revoked => "revoked",
+    };
+    outcome.to_string()
+}
+
+/// Verifies a compact JWS token against a `/key` response and returns its
+/// claims as JSON, or `undefined` if it doesn't verify
+#[wasm_bindgen(js_name = verifyJws)]
+pub fn verify_jws(token: &str, key_json: &str) -> Option<String> {
+    let claims = crate::jws::verify(token, &parse_key(key_json)?)?;
+    serde_json::to_string(&claims).ok()
+}
+
+/// Verifies a COSE_Sign1 token against a `/key` response and returns its
+/// claims as JSON (`msg_hash` and `kid` in hex), or `undefined` if it
+/// doesn't verify
+#[wasm_bindgen(js_name = verifyCose)]
+pub fn verify_cose(token: &[u8], key_json: &str) -> Option<String> {
+    let claims = crate::cose::verify(token, &parse_key(key_json)?)?;
+    let claims = serde_json::json!({
+        "msg_hash": hex::encode(claims.msg_hash),
+        "iat": claims.iat,
+        "serial": claims.serial,
+        "kid": hex::encode(claims.kid),
+    });
+    Some(claims.to_string())
+}
+
+/// Hex SHA-256 of `bytes`: the `message` of a file timestamp (see
+/// `request_file_timestamp`), to compare with the file a page was given
+#[wasm_bindgen(js_name = sha256Hex)]
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+fn parse_key(key_json: &str) -> Option<EcdsaVerificationKey> {
+    serde_json::from_str(key_json).ok()
+}
@@ -0,0 +1,188 @@
+//! The one text form of a timestamp on the wire
+//!
+//! The server signs `message + time-signed` with `time-signed` as a string,
+//! and a verifier recomputes the same bytes from the string it received, so
+//! everything that writes a timestamp has to write it the same way: RFC 3339
+//! in UTC, with exactly six fractional digits and a `Z`
+//! (`2025-06-02T05:05:35.123456Z`). Anything finer than a microsecond is
+//! truncated, not rounded, matching the microseconds the clock issues. A
+//! server with a coarser `time_precision` issues times truncated to it, but
+//! still writes all six digits (`2025-06-02T05:05:35.000000Z`), so the
+//! form, and how a verifier rebuilds the bytes, never changes.
+//!
+//! The message side is bytes: the UTF-8 of a text `message` as the server
+//! received it, or the decoded `message-b64`. Text that looks the same can
+//! be different bytes ("é" as U+00E9, or "e" followed by the combining
+//! U+0301), and clients in different languages don't agree on which one
+//! they send, so the server's `normalization` decides: `exact` (the
+//! default) signs the bytes received, `nfc` signs the Unicode NFC form and
+//! returns it as `message`. Either way a verifier uses the returned
+//! `message`, never its own copy of the text.
+
+use chrono::{DateTime, Duration, DurationRound, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Fractional digits of a canonical timestamp: always microseconds, even
+/// when they are all zero
+pub const PRECISION: SecondsFormat = SecondsFormat::Micros;
+
+/// `time` in the canonical form
+pub fn canonical_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(PRECISION, true)
+}
+
+/// Whether `s` is exactly what [`canonical_time`] writes for some time
+pub fn is_canonical(s: &str) -> bool {
+    parse_time(s).is_ok_and(|time| canonical_time(time) == s)
+}
+
+/// Reads any RFC 3339 timestamp (any precision or offset) as UTC. Only
+/// the canonical form reads back to the same string via [`canonical_time`].
+pub fn parse_time(s: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
+    DateTime::parse_from_rfc3339(s).map(|t| t.with_timezone(&Utc))
+}
+
+/// `time_precision`: how finely the server's `time-signed` tells when a
+/// message was signed. Coarser times reveal less about the signer's
+/// activity; the canonical form keeps six digits either way.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum TimePrecision {
+    Seconds,
+    Millis,
+    /// The default, the finest the canonical form holds
+    #[default]
+    Micros,
+}
+
+impl TimePrecision {
+    /// The smallest step between two issued times
+    pub fn unit(self) -> Duration {
+        match self {
+            TimePrecision::Seconds => Duration::seconds(1),
+            TimePrecision::Millis => Duration::milliseconds(1),
+            TimePrecision::Micros => Duration::microseconds(1),
+        }
+    }
+
+    /// `time` truncated (never rounded) to this precision
+    pub fn truncate(self, time: DateTime<Utc>) -> DateTime<Utc> {
+        time.duration_trunc(self.unit())
+            .expect("truncating to at most a second cannot overflow")
+    }
+
+    /// Whether `time_signed` is canonical and has no digits finer than
+    /// this precision
+    pub fn holds(self, time_signed: &str) -> bool {
+        is_canonical(time_signed)
+            && parse_time(time_signed).is_ok_and(|time| self.truncate(time) == time)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TimePrecision::Seconds => "seconds",
+            TimePrecision::Millis => "millis",
+            TimePrecision::Micros => "micros",
+        }
+    }
+}
+
+impl std::str::FromStr for TimePrecision {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "seconds" => Ok(TimePrecision::Seconds),
+            "millis" => Ok(TimePrecision::Millis),
+            "micros" => Ok(TimePrecision::Micros),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The bytes a timestamp's signature covers: the message, then
+/// `time-signed` as written (see [`canonical_time`])
+pub fn signing_input(message: &[u8], time_signed: &str) -> Vec<u8> {
+    [message, time_signed.as_bytes()].concat()
+}
+
+/// [`signing_input`] of a timestamp issued under a named `policy`: the
+/// name follows `time-signed` after a space. Without one it is the plain
+/// input, so timestamps from before policies still verify.
+pub fn policy_signing_input(message: &[u8], time_signed: &str, policy: Option<&str>) -> Vec<u8> {
+    let mut input = signing_input(message, time_signed);
+    if let Some(policy) = policy {
+        input.push(b' ');
+        input.extend_from_slice(policy.as_bytes());
+    }
+    input
+}
+
+/// `normalization`: what the server does to a text message before signing
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Normalization {
+    /// Sign the bytes received (the default): the two spellings of "é" are
+    /// two different messages
+    #[default]
+    Exact,
+    /// Sign the NFC form (canonical composition), so they are one message
+    Nfc,
+}
+
+impl Normalization {
+    /// `message` as this policy signs it; borrowed if that is unchanged.
+    /// Binary (`message-b64`) messages and renewal tokens are never
+    /// normalized.
+    pub fn apply(self, message: &str) -> Cow<'_, str> {
+        match self {
+            Normalization::Exact => Cow::Borrowed(message),
+            Normalization::Nfc => {
+                icu_normalizer::ComposingNormalizerBorrowed::new_nfc().normalize(message)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Normalization {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "exact" => Ok(Normalization::Exact),
+            "nfc" => Ok(Normalization::Nfc),
+            _ => Err(()),
+        }
+    }
+}
+
+/// `#[serde(with = "crate::wire::serde_canonical")]` for a `DateTime<Utc>`
+/// field written as [`canonical_time`]. Reading only takes the canonical
+/// form, so a value read and written again comes out byte for byte the same.
+pub mod serde_canonical {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serializer, de::Error as _};
+
+    pub fn serialize<S: Serializer>(
+        time: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::canonical_time(*time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let time = super::parse_time(&s).map_err(D::Error::custom)?;
+        if super::canonical_time(time) != s {
+            return Err(D::Error::custom(format!(
+                "'{}' is not a canonical timestamp (UTC, six fractional digits, 'Z')",
+                s
+            )));
+        }
+        Ok(time)
+    }
+}
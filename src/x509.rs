@@ -0,0 +1,297 @@
+//! X.509 certificates for the timestamping key (`cert_file`, `GET /key/cert`)
+//!
+//! `vts-admin cert` makes a self-signed certificate for a tenant's key, and
+//! `vts-admin csr` a PKCS #10 request for a CA to issue one. Both mark the
+//! key as a time-stamping key the way RFC 3161 §2.3 asks: key usage
+//! `digitalSignature` and `nonRepudiation`, and a critical extended key
+//! usage of `id-kp-timeStamping` alone. The server serves whichever
+//! certificate `cert_file` names, as long as it certifies its key.
+//!
+//! `vts-admin cert --issuer-key` has an offline root key issue the
+//! certificate instead, and a client with that root as its `trust_root`
+//! checks every key it fetches against `/key/cert` (`verify_issued`): the
+//! server's key can then rotate without clients pinning each new one.
+//!
+//! Only the DER this needs is written and read here: enough to build the
+//! certificate and request, and to find the key, validity and usage in one.
+
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use ecdsa_lib::PublicKey;
+use std::fmt;
+
+#[cfg(feature = "server")]
+mod issue;
+#[cfg(feature = "server")]
+pub use issue::{issue, request, self_signed};
+
+/// `Content-Type` of a DER certificate (RFC 2585)
+pub const CONTENT_TYPE: &str = "application/pkix-cert";
+/// `Content-Type` of a PEM certificate (RFC 8555)
+pub const PEM_CONTENT_TYPE: &str = "application/pem-certificate-chain";
+
+// Contents of the object identifiers used
+const ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const SECP256K1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x0a];
+const EXT_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+const TIME_STAMPING: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x08];
+
+// Tags
+const BOOLEAN: u8 = 0x01;
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const SEQUENCE: u8 = 0x30;
+const CONTEXT_0: u8 = 0xa0;
+const CONTEXT_3: u8 = 0xa3;
+
+/// What `parse` finds in a certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertInfo {
+    pub public_key: PublicKey,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    /// Whether the extended key usage is `id-kp-timeStamping` alone
+    pub time_stamping: bool,
+}
+
+impl CertInfo {
+    /// Whether `now` is inside the validity window
+    pub fn is_valid_at(&self, now: DateTime<Utc>) -> bool {
+        self.not_before <= now && now <= self.not_after
+    }
+}
+
+/// Why a key's certificate doesn't endorse it (see `verify_issued`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EndorsementError {
+    /// Not a DER certificate for a secp256k1 key
+    Malformed,
+    /// Not signed by the trust root
+    NotIssued,
+    /// Not for timestamping alone
+    Usage,
+    /// `now` is outside its validity window
+    Expired,
+    /// It certifies another key than the one the server signs with
+    OtherKey,
+}
+
+impl fmt::Display for EndorsementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EndorsementError::Malformed => write!(f, "key certificate is malformed"),
+            EndorsementError::NotIssued => {
+                write!(f, "key certificate is not issued by the trust root")
+            }
+            EndorsementError::Usage => write!(f, "key certificate is not for timestamping"),
+            EndorsementError::Expired => write!(f, "key certificate is expired or not yet valid"),
+            EndorsementError::OtherKey => write!(f, "key certificate is for another key"),
+        }
+    }
+}
+
+impl std::error::Error for EndorsementError {}
+
+/// The key and validity window of a DER certificate; `None` if it isn't
+/// one for a secp256k1 key
+pub fn parse(der: &[u8]) -> Option<CertInfo> {
+    let (tbs, _, _) = signed_parts(der)?;
+    let (SEQUENCE, mut tbs, _) = read(tbs)? else {
+        return None;
+    };
+    let mut next = || {
+        let (tag, content, rest) = read(tbs)?;
+        tbs = rest;
+        Some((tag, content))
+    };
+    let (mut tag, _) = next()?;
+    if tag == CONTEXT_0 {
+        (tag, _) = next()?; // version, then the serial number
+    }
+    if tag != INTEGER {
+        return None;
+    }
+    let _signature_alg = next()?;
+    let _issuer = next()?;
+    let (SEQUENCE, validity) = next()? else {
+        return None;
+    };
+    let _subject = next()?;
+    let (SEQUENCE, spki) = next()? else {
+        return None;
+    };
+    // The unique ids of v2 can come before the extensions
+    let mut extensions = None;
+    while let Some((tag, content)) = next() {
+        if tag == CONTEXT_3 {
+            extensions = Some(content);
+        }
+    }
+
+    let (not_before, rest) = read_time(validity)?;
+    let (not_after, _) = read_time(rest)?;
+    let (SEQUENCE, algorithm, rest) = read(spki)? else {
+        return None;
+    };
+    let (OID, EC_PUBLIC_KEY, parameters) = read(algorithm)? else {
+        return None;
+    };
+    let (OID, SECP256K1, _) = read(parameters)? else {
+        return None;
+    };
+    let (BIT_STRING, [0, point @ ..], _) = read(rest)? else {
+        return None;
+    };
+    Some(CertInfo {
+        public_key: PublicKey::from_sec1_bytes(point).ok()?,
+        not_before,
+        not_after,
+        time_stamping: match extensions {
+            Some(extensions) => is_time_stamping(extensions)?,
+            None => false,
+        },
+    })
+}
+
+/// Checks that `der` is a certificate `issuer` signed for a timestamping
+/// key, valid at `now`, and returns what it certifies
+pub fn verify_issued(
+    der: &[u8],
+    issuer: &PublicKey,
+    now: DateTime<Utc>,
+) -> Result<CertInfo, EndorsementError> {
+    let info = parse(der).ok_or(EndorsementError::Malformed)?;
+    let (tbs, algorithm, signature) = signed_parts(der).ok_or(EndorsementError::Malformed)?;
+    if algorithm != ECDSA_WITH_SHA256 {
+        return Err(EndorsementError::NotIssued);
+    }
+    if !issuer.verify_encoded(tbs, signature).is_ok() {
+        return Err(EndorsementError::NotIssued);
+    }
+    if !info.time_stamping {
+        return Err(EndorsementError::Usage);
+    }
+    if !info.is_valid_at(now) {
+        return Err(EndorsementError::Expired);
+    }
+    Ok(info)
+}
+
+/// `der` in PEM, under `-----BEGIN {label}-----`
+pub fn to_pem(der: &[u8], label: &str) -> String {
+    let b64 = general_purpose::STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in b64.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).expect("Base64 is ASCII"));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+/// DER from a file holding either DER or the first PEM block
+pub fn decode(bytes: &[u8]) -> Option<Vec<u8>> {
+    let Ok(text) = std::str::from_utf8(bytes) else {
+        return Some(bytes.to_vec());
+    };
+    let Some(begin) = text.find("-----BEGIN ") else {
+        return Some(bytes.to_vec());
+    };
+    let body = text[begin..].split_once('\n')?.1;
+    let body = &body[..body.find("-----END ")?];
+    let b64: String = body.split_whitespace().collect();
+    general_purpose::STANDARD.decode(b64).ok()
+}
+
+/// The signed part of a certificate (tag and all), the OID of its
+/// signature algorithm, and the DER signature
+fn signed_parts(der: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let (SEQUENCE, certificate, _) = read(der)? else {
+        return None;
+    };
+    let (SEQUENCE, _, after_tbs) = read(certificate)? else {
+        return None;
+    };
+    let tbs = &certificate[..certificate.len() - after_tbs.len()];
+    let (SEQUENCE, algorithm, rest) = read(after_tbs)? else {
+        return None;
+    };
+    let (OID, algorithm, _) = read(algorithm)? else {
+        return None;
+    };
+    let (BIT_STRING, [0, signature @ ..], _) = read(rest)? else {
+        return None;
+    };
+    Some((tbs, algorithm, signature))
+}
+
+/// Whether the extended key usage in `extensions` (the contents of `[3]`)
+/// is timestamping alone; `None` if they can't be read
+fn is_time_stamping(extensions: &[u8]) -> Option<bool> {
+    let (SEQUENCE, mut extensions, _) = read(extensions)? else {
+        return None;
+    };
+    while !extensions.is_empty() {
+        let (SEQUENCE, extension, rest) = read(extensions)? else {
+            return None;
+        };
+        extensions = rest;
+        let (OID, oid, mut rest) = read(extension)? else {
+            return None;
+        };
+        if oid != EXT_KEY_USAGE {
+            continue;
+        }
+        if let Some((BOOLEAN, _, after)) = read(rest) {
+            rest = after;
+        }
+        let (OCTET_STRING, value, _) = read(rest)? else {
+            return None;
+        };
+        let (SEQUENCE, usages, _) = read(value)? else {
+            return None;
+        };
+        return Some(matches!(read(usages), Some((OID, TIME_STAMPING, []))));
+    }
+    Some(false)
+}
+
+fn read_time(der: &[u8]) -> Option<(DateTime<Utc>, &[u8])> {
+    let (tag, content, rest) = read(der)?;
+    let text = std::str::from_utf8(content).ok()?;
+    let text = match tag {
+        // Two-digit years: 50–99 are 19xx, the rest 20xx
+        UTC_TIME => match text.get(..2)?.parse::<u8>().ok()? {
+            50.. => format!("19{}", text),
+            _ => format!("20{}", text),
+        },
+        GENERALIZED_TIME => text.to_string(),
+        _ => return None,
+    };
+    let time = NaiveDateTime::parse_from_str(&text, "%Y%m%d%H%M%SZ").ok()?;
+    Some((time.and_utc(), rest))
+}
+
+/// The first element of `der`: its tag, contents and what follows it
+fn read(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = der.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = match first {
+        0..=0x7f => (first as usize, rest),
+        0x81..=0x84 => {
+            let n = (first & 0x7f) as usize;
+            let len = rest
+                .get(..n)?
+                .iter()
+                .fold(0usize, |len, &b| (len << 8) | b as usize);
+            (len, &rest[n..])
+        }
+        _ => return None,
+    };
+    (rest.len() >= len).then(|| (tag, &rest[..len], &rest[len..]))
+}
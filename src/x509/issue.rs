@@ -0,0 +1,195 @@
+//! Writing certificates and certification requests (`vts-admin cert`,
+//! `vts-admin csr`)
+
+use super::*;
+use crate::signer::{Signer, SignerError};
+use chrono::Datelike;
+use sha2::{Digest, Sha256};
+
+// Object identifiers and tags only written
+const COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+const SUBJECT_KEY_ID: &[u8] = &[0x55, 0x1d, 0x0e];
+const KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x0f];
+const BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+const AUTHORITY_KEY_ID: &[u8] = &[0x55, 0x1d, 0x23];
+const EXTENSION_REQUEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x0e];
+const UTF8_STRING: u8 = 0x0c;
+const SET: u8 = 0x31;
+
+/// A certificate for `signer`'s key, signed by that key, valid from
+/// `not_before` to `not_after`
+pub fn self_signed(
+    signer: &dyn Signer,
+    common_name: &str,
+    not_before: DateTime<Utc>,
+    not_after: DateTime<Utc>,
+) -> Result<Vec<u8>, SignerError> {
+    let public_key = signer.public_key();
+    issue(
+        &public_key,
+        common_name,
+        signer,
+        common_name,
+        not_before,
+        not_after,
+    )
+}
+
+/// A certificate for `public_key`, issued by the root key `issuer` (named
+/// `issuer_name`): what a client with that root as `trust_root` accepts
+pub fn issue(
+    public_key: &PublicKey,
+    common_name: &str,
+    issuer: &dyn Signer,
+    issuer_name: &str,
+    not_before: DateTime<Utc>,
+    not_after: DateTime<Utc>,
+) -> Result<Vec<u8>, SignerError> {
+    // Unique enough per key and start date; positive, and never 0-led
+    let mut serial: [u8; 16] = Sha256::new()
+        .chain_update(public_key.to_sec1_bytes())
+        .chain_update(not_before.timestamp().to_be_bytes())
+        .finalize()[..16]
+        .try_into()
+        .expect("16 of 32 bytes");
+    serial[0] = (serial[0] & 0x7f) | 0x40;
+
+    let tbs = sequence(&[
+        tlv(CONTEXT_0, &integer(&[2])),
+        integer(&serial),
+        sequence(&[tlv(OID, ECDSA_WITH_SHA256)]),
+        name(issuer_name),
+        sequence(&[time(not_before), time(not_after)]),
+        name(common_name),
+        subject_public_key_info(public_key),
+        tlv(
+            CONTEXT_3,
+            &extensions(public_key, Some(&issuer.public_key())),
+        ),
+    ]);
+    signed(issuer, tbs)
+}
+
+/// A PKCS #10 certification request for `signer`'s key, asking for the
+/// same extensions `self_signed` sets
+pub fn request(signer: &dyn Signer, common_name: &str) -> Result<Vec<u8>, SignerError> {
+    let public_key = signer.public_key();
+    let extension_request = sequence(&[
+        tlv(OID, EXTENSION_REQUEST),
+        tlv(SET, &extensions(&public_key, None)),
+    ]);
+    let info = sequence(&[
+        integer(&[0]),
+        name(common_name),
+        subject_public_key_info(&public_key),
+        tlv(CONTEXT_0, &extension_request),
+    ]);
+    signed(signer, info)
+}
+
+/// `to_be_signed` wrapped with its signature by `signer`, as a certificate
+/// and a certification request both are
+fn signed(signer: &dyn Signer, to_be_signed: Vec<u8>) -> Result<Vec<u8>, SignerError> {
+    let signature = signer.sign(&to_be_signed)?.to_der();
+    Ok(sequence(&[
+        to_be_signed,
+        sequence(&[tlv(OID, ECDSA_WITH_SHA256)]),
+        bit_string(signature.as_bytes()),
+    ]))
+}
+
+fn name(common_name: &str) -> Vec<u8> {
+    let attribute = sequence(&[
+        tlv(OID, COMMON_NAME),
+        tlv(UTF8_STRING, common_name.as_bytes()),
+    ]);
+    sequence(&[tlv(SET, &attribute)])
+}
+
+fn subject_public_key_info(public_key: &PublicKey) -> Vec<u8> {
+    let point = public_key.verifying_key().to_encoded_point(false);
+    sequence(&[
+        sequence(&[tlv(OID, EC_PUBLIC_KEY), tlv(OID, SECP256K1)]),
+        bit_string(point.as_bytes()),
+    ])
+}
+
+/// Key identifier of `public_key` (RFC 7093 method 1)
+fn key_id(public_key: &PublicKey) -> Vec<u8> {
+    let point = public_key.verifying_key().to_encoded_point(false);
+    Sha256::digest(point.as_bytes())[..20].to_vec()
+}
+
+/// The extensions of a timestamping key: not a CA, signing only, for
+/// timestamps only, its key identifier and (in a certificate) the issuer's
+fn extensions(public_key: &PublicKey, issuer: Option<&PublicKey>) -> Vec<u8> {
+    let extension = |oid: &[u8], critical: bool, value: Vec<u8>| {
+        let mut fields = vec![tlv(OID, oid)];
+        if critical {
+            fields.push(tlv(BOOLEAN, &[0xff]));
+        }
+        fields.push(tlv(OCTET_STRING, &value));
+        sequence(&fields)
+    };
+    let mut extensions = vec![
+        extension(BASIC_CONSTRAINTS, true, sequence(&[])),
+        // digitalSignature and nonRepudiation: the top 2 of 8 bits
+        extension(KEY_USAGE, true, tlv(BIT_STRING, &[6, 0xc0])),
+        extension(EXT_KEY_USAGE, true, sequence(&[tlv(OID, TIME_STAMPING)])),
+        extension(
+            SUBJECT_KEY_ID,
+            false,
+            tlv(OCTET_STRING, &key_id(public_key)),
+        ),
+    ];
+    if let Some(issuer) = issuer {
+        // keyIdentifier, [0] IMPLICIT
+        let value = sequence(&[tlv(0x80, &key_id(issuer))]);
+        extensions.push(extension(AUTHORITY_KEY_ID, false, value));
+    }
+    sequence(&extensions)
+}
+
+/// UTCTime through 2049, GeneralizedTime after (RFC 5280 §4.1.2.5)
+fn time(t: DateTime<Utc>) -> Vec<u8> {
+    match t.year() {
+        1950..=2049 => tlv(UTC_TIME, t.format("%y%m%d%H%M%SZ").to_string().as_bytes()),
+        _ => tlv(
+            GENERALIZED_TIME,
+            t.format("%Y%m%d%H%M%SZ").to_string().as_bytes(),
+        ),
+    }
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    match content.len() {
+        len @ 0..=0x7f => out.push(len as u8),
+        len => {
+            let bytes = len.to_be_bytes();
+            let skip = bytes.iter().take_while(|&&b| b == 0).count();
+            out.push(0x80 | (bytes.len() - skip) as u8);
+            out.extend_from_slice(&bytes[skip..]);
+        }
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn sequence(fields: &[Vec<u8>]) -> Vec<u8> {
+    tlv(SEQUENCE, &fields.concat())
+}
+
+/// A non-negative INTEGER from big-endian bytes
+fn integer(bytes: &[u8]) -> Vec<u8> {
+    let skip = bytes.iter().take_while(|&&b| b == 0).count();
+    let bytes = &bytes[skip.min(bytes.len().saturating_sub(1))..];
+    match bytes.first() {
+        Some(&first) if first >= 0x80 => tlv(INTEGER, &[&[0], bytes].concat()),
+        _ => tlv(INTEGER, bytes),
+    }
+}
+
+fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    tlv(BIT_STRING, &[&[0], bytes].concat())
+}
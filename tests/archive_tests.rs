@@ -0,0 +1,226 @@
+//! `[archive]` tests: a server sealing its audit log into a fake S3 bucket
+
+use axum::Router;
+use axum::body::Bytes;
+use axum::http::{HeaderMap, Method, StatusCode, Uri};
+use ecdsa_lib::KeyPair;
+use lab4::audit::{self, ArchiveManifest};
+use lab4::clock::SystemClock;
+use lab4::config::{ArchiveConfig, KeyStatsConfig, ServerConfig, TenantKeys};
+use lab4::ecdsa_requests::nonblocking;
+use lab4::server;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, Once};
+use tokio::time::{Duration, sleep};
+
+const REGION: &str = "test-region";
+
+static TEST_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// Objects by path (`/{bucket}/{key}`)
+type Bucket = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+/// Derives a KeyPair from the test id (so every run uses the same keys) and
+/// returns its raw (private, public) bytes
+fn generate_key_bytes() -> (Vec<u8>, Vec<u8>) {
+    let test_id = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let private_key_file = format!("archive_test_private_key_{}.bin", test_id);
+    let public_key_file = format!("archive_test_public_key_{}.bin", test_id);
+
+    let mut seed = [0u8; 32];
+    seed[0] = b'a';
+    seed[1..5].copy_from_slice(&test_id.to_be_bytes());
+    KeyPair::from_seed(&seed)
+        .save_to_files(&private_key_file, &public_key_file)
+        .unwrap();
+    let priv_bytes = fs::read(&private_key_file).unwrap();
+    let pub_bytes = fs::read(&public_key_file).unwrap();
+    let _ = fs::remove_file(&private_key_file);
+    let _ = fs::remove_file(&public_key_file);
+
+    (priv_bytes, pub_bytes)
+}
+
+fn set_credentials() {
+    static CREDENTIALS: Once = Once::new();
+    CREDENTIALS.call_once(|| {
+        // SAFETY: set once, before any test reads the environment
+        unsafe {
+            std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDTEST");
+            std::env::set_var("AWS_SECRET_ACCESS_KEY", "test-secret");
+        }
+    });
+}
+
+/// Answers GET and PUT of any path, checking each request is signed for
+/// S3 in `REGION` over the body it carries
+fn respond(
+    bucket: &Bucket,
+    method: Method,
+    path: &str,
+    headers: &HeaderMap,
+    body: Bytes,
+) -> (StatusCode, Vec<u8>) {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+    };
+    let scope = format!("/{}/s3/aws4_request", REGION);
+    if !header("authorization").starts_with("AWS4-HMAC-SHA256 Credential=AKIDTEST/")
+        || !header("authorization").contains(&scope)
+        || header("x-amz-content-sha256") != hex::encode(Sha256::digest(&body))
+    {
+        return (StatusCode::FORBIDDEN, b"SignatureDoesNotMatch".to_vec());
+    }
+    let mut objects = bucket.lock().unwrap();
+    match method {
+        Method::PUT => {
+            objects.insert(path.to_string(), body.to_vec());
+            (StatusCode::OK, Vec::new())
+        }
+        Method::GET => match objects.get(path) {
+            Some(object) => (StatusCode::OK, object.clone()),
+            None => (StatusCode::NOT_FOUND, b"NoSuchKey".to_vec()),
+        },
+        _ => (StatusCode::METHOD_NOT_ALLOWED, Vec::new()),
+    }
+}
+
+/// Starts a fake S3 endpoint and returns its URL
+async fn spawn_fake_s3(bucket: Bucket) -> String {
+    let app = Router::new().fallback(
+        move |method: Method, uri: Uri, headers: HeaderMap, body: Bytes| async move {
+            respond(&bucket, method, uri.path(), &headers, body)
+        },
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+fn archive_config(endpoint: String, audit_file: String) -> ServerConfig {
+    ServerConfig {
+        high_water_file: String::new(),
+        serial_file: String::new(),
+        key_stats: KeyStatsConfig {
+            file: String::new(),
+            ..Default::default()
+        },
+        audit_file,
+        archive: ArchiveConfig {
+            endpoint,
+            bucket: "vts-archive".to_string(),
+            region: REGION.to_string(),
+            segment_entries: 2,
+            interval_secs: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+async fn spawn_server(config: ServerConfig) -> String {
+    let (priv_bytes, pub_bytes) = generate_key_bytes();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        server::run_configured_server_with_listener(
+            priv_bytes,
+            pub_bytes,
+            TenantKeys::new(),
+            config,
+            Box::new(SystemClock),
+            listener,
+        )
+        .await
+        .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+    });
+    sleep(Duration::from_millis(100)).await;
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn test_full_segments_are_sealed_into_the_bucket() {
+    set_credentials();
+    let bucket = Bucket::default();
+    let endpoint = spawn_fake_s3(bucket.clone()).await;
+    let dir = tempfile::tempdir().unwrap();
+    let audit_file = dir.path().join("audit.log").to_string_lossy().into_owned();
+    let server_url = spawn_server(archive_config(endpoint, audit_file)).await;
+    let key = nonblocking::request_key(&server_url)
+        .await
+        .unwrap()
+        .key()
+        .unwrap();
+
+    // Five entries: two full segments, and one entry left over
+    for i in 0..5 {
+        nonblocking::request_timestamp(&server_url, &format!("entry {}", i))
+            .await
+            .unwrap();
+    }
+    let object = |name: &str| {
+        bucket
+            .lock()
+            .unwrap()
+            .get(&format!("/vts-archive/vts/{}", name))
+            .cloned()
+    };
+    for _ in 0..50 {
+        if object("head").as_deref() == Some(&b"4"[..]) {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(object("head").as_deref(), Some(&b"4"[..]));
+    assert!(object("segments/000000000005-000000000006.vtsa").is_none());
+
+    // Each segment's manifest is its signed root, linking to the one before
+    let first: ArchiveManifest = serde_json::from_slice(
+        &object("segments/000000000001-000000000002.manifest.json").unwrap(),
+    )
+    .unwrap();
+    let second: ArchiveManifest = serde_json::from_slice(
+        &object("segments/000000000003-000000000004.manifest.json").unwrap(),
+    )
+    .unwrap();
+    assert_eq!((first.since, first.count), (0, 2));
+    assert_eq!((second.since, second.count), (2, 2));
+    assert_eq!(first.prev, audit::GENESIS);
+
+    // Imported in order, the segments rebuild the log
+    let restored = dir.path().join("restored.log");
+    for name in [
+        "segments/000000000001-000000000002.vtsa",
+        "segments/000000000003-000000000004.vtsa",
+    ] {
+        let (_, appended) = audit::import(&object(name).unwrap(), &key, &restored).unwrap();
+        assert_eq!(appended, 2);
+    }
+    assert_eq!(audit::verify_file(&restored, &key).unwrap(), 4);
+}
+
+#[tokio::test]
+async fn test_archive_needs_audit_file() {
+    set_credentials();
+    let (priv_bytes, pub_bytes) = generate_key_bytes();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = archive_config("http://127.0.0.1:9".to_string(), String::new());
+    let err = server::run_configured_server_with_listener(
+        priv_bytes,
+        pub_bytes,
+        TenantKeys::new(),
+        config,
+        Box::new(SystemClock),
+        listener,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("audit_file"));
+}
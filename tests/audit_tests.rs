@@ -0,0 +1,349 @@
+//! Audit log: chaining across restarts, detection of edited logs and
+//! compaction
+
+use ecdsa_lib::KeyPair;
+use lab4::audit::{AuditLog, AuditRecord, GENESIS, compacted_path, export, import, verify_file};
+use lab4::{merkle, wire};
+use std::fs;
+use std::sync::Arc;
+
+fn record(serial: u64) -> AuditRecord<'static> {
+    AuditRecord {
+        time: "2030-06-01T12:00:00.000000Z",
+        kind: "sign",
+        tenant: None,
+        client: Some("127.0.0.1".to_string()),
+        hash: "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+        serial,
+        signature: None,
+        policy: None,
+    }
+}
+
+#[test]
+fn test_audit_log_chains_across_restarts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("audit.log").to_string_lossy().into_owned();
+    let signer = Arc::new(KeyPair::from_seed(&[11u8; 32]));
+    let public_key = signer.to_public_key();
+
+    // Two lines, a restart, then a third continuing the chain
+    let log = AuditLog::open(&path, signer.clone()).unwrap();
+    let first = log.append(record(1)).unwrap();
+    log.append(record(2)).unwrap();
+    drop(log);
+    let third = AuditLog::open(&path, signer)
+        .unwrap()
+        .append(record(3))
+        .unwrap();
+    assert_eq!(first.prev, GENESIS);
+    assert_eq!(third.seq, 3);
+    assert_eq!(verify_file(&path, &public_key).unwrap(), 3);
+
+    // Another key's view of the log fails
+    let other = KeyPair::from_seed(&[12u8; 32]).to_public_key();
+    assert!(verify_file(&path, &other).is_err());
+}
+
+#[test]
+fn test_edited_audit_log_fails_verification() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("audit.log");
+    let signer = Arc::new(KeyPair::from_seed(&[13u8; 32]));
+    let public_key = signer.to_public_key();
+    let log = AuditLog::open(&path.to_string_lossy(), signer).unwrap();
+    for serial in 1..=3 {
+        log.append(record(serial)).unwrap();
+    }
+    let original = fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = original.lines().collect();
+
+    let edits = [
+        // A changed field breaks the line's signature
+        (
+            "edited",
+            original.replacen("\"serial\":2", "\"serial\":7", 1),
+        ),
+        // A dropped line breaks the numbering and chain
+        ("dropped", format!("{}\n{}\n", lines[0], lines[2])),
+        // Reordered lines break the chain
+        (
+            "reordered",
+            format!("{}\n{}\n{}\n", lines[1], lines[0], lines[2]),
+        ),
+        // A half-written last line
+        ("truncated", original[..original.len() - 10].to_string()),
+    ];
+    for (what, contents) in edits {
+        fs::write(&path, contents).unwrap();
+        let err = verify_file(&path, &public_key).unwrap_err();
+        assert!(err.to_string().starts_with("Line "), "{}: {}", what, err);
+    }
+
+    // The server refuses to continue a log whose last line is cut off
+    let signer = Arc::new(KeyPair::from_seed(&[13u8; 32]));
+    assert!(AuditLog::open(&path.to_string_lossy(), signer).is_err());
+}
+
+#[test]
+fn test_audit_log_indexes_lines_by_hash() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("audit.log").to_string_lossy().into_owned();
+    let signer = Arc::new(KeyPair::from_seed(&[14u8; 32]));
+    let log = AuditLog::open(&path, signer.clone()).unwrap();
+    log.append(record(1)).unwrap();
+    log.append(AuditRecord {
+        hash: "00",
+        ..record(2)
+    })
+    .unwrap();
+    drop(log);
+
+    // Lines already in the file, then lines appended since
+    let log = AuditLog::open(&path, signer.clone()).unwrap();
+    log.append(record(3)).unwrap();
+    let found = log.by_hash(record(1).hash);
+    assert_eq!(found.iter().map(|e| e.serial).collect::<Vec<_>>(), [1, 3]);
+    assert!(found.iter().all(|e| e.verify(&signer.to_public_key())));
+    assert!(log.by_hash("ff").is_empty());
+}
+
+/// A log of `n` lines at `dir/name`, signed by `signer`
+fn log_of(dir: &tempfile::TempDir, name: &str, signer: &Arc<KeyPair>, n: u64) -> String {
+    let path = dir.path().join(name).to_string_lossy().into_owned();
+    let log = AuditLog::open(&path, signer.clone()).unwrap();
+    for serial in 1..=n {
+        log.append(record(serial)).unwrap();
+    }
+    path
+}
+
+#[test]
+fn test_export_and_import_mirror_the_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let signer = Arc::new(KeyPair::from_seed(&[15u8; 32]));
+    let public_key = signer.to_public_key();
+    let path = log_of(&dir, "audit.log", &signer, 3);
+    let mirror = dir.path().join("mirror.log");
+
+    // Everything into an empty mirror
+    let archive = export(&path, 0, signer.as_ref()).unwrap();
+    let (manifest, appended) = import(&archive, &public_key, &mirror).unwrap();
+    assert_eq!((manifest.since, manifest.count, appended), (0, 3, 3));
+    assert_eq!(manifest.prev, GENESIS);
+    assert_eq!(
+        fs::read_to_string(&mirror).unwrap(),
+        fs::read_to_string(&path).unwrap()
+    );
+
+    // Later entries continue it; importing them again changes nothing
+    let log = AuditLog::open(&path, signer.clone()).unwrap();
+    log.append(record(4)).unwrap();
+    log.append(record(5)).unwrap();
+    let archive = export(&path, 3, signer.as_ref()).unwrap();
+    assert_eq!(import(&archive, &public_key, &mirror).unwrap().1, 2);
+    assert_eq!(import(&archive, &public_key, &mirror).unwrap().1, 0);
+    // An overlapping archive only adds what is missing
+    log.append(record(6)).unwrap();
+    let archive = export(&path, 4, signer.as_ref()).unwrap();
+    assert_eq!(import(&archive, &public_key, &mirror).unwrap().1, 1);
+    assert_eq!(verify_file(&mirror, &public_key).unwrap(), 6);
+    assert_eq!(
+        fs::read_to_string(&mirror).unwrap(),
+        fs::read_to_string(&path).unwrap()
+    );
+    assert!(export(&path, 7, signer.as_ref()).is_err());
+}
+
+#[test]
+fn test_import_rejects_bad_archives() {
+    let dir = tempfile::tempdir().unwrap();
+    let signer = Arc::new(KeyPair::from_seed(&[16u8; 32]));
+    let public_key = signer.to_public_key();
+    let path = log_of(&dir, "audit.log", &signer, 4);
+    let mirror = dir.path().join("mirror.log");
+
+    // Another key, bytes that aren't an archive, a gap before the archive
+    let archive = export(&path, 2, signer.as_ref()).unwrap();
+    let other = KeyPair::from_seed(&[17u8; 32]).to_public_key();
+    assert!(import(&archive, &other, &mirror).is_err());
+    assert!(import(b"not an archive", &public_key, &mirror).is_err());
+    assert!(import(&archive, &public_key, &mirror).is_err());
+    assert!(!mirror.exists());
+
+    // An edited line, even inside a valid compressed archive, into a
+    // mirror that holds the two entries before it
+    let unpacked = miniz_oxide::inflate::decompress_to_vec_zlib(&archive).unwrap();
+    let edited = String::from_utf8(unpacked)
+        .unwrap()
+        .replacen("\"serial\":3", "\"serial\":9", 1);
+    let repacked = miniz_oxide::deflate::compress_to_vec_zlib(edited.as_bytes(), 6);
+    let head = export(&path, 0, signer.as_ref()).unwrap();
+    fs::write(
+        &mirror,
+        fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .take(2)
+            .map(|l| format!("{}\n", l))
+            .collect::<String>(),
+    )
+    .unwrap();
+    assert!(import(&repacked, &public_key, &mirror).is_err());
+    assert_eq!(verify_file(&mirror, &public_key).unwrap(), 2);
+
+    // A copy of some other log doesn't take it
+    let elsewhere = dir.path().join("elsewhere.log");
+    let log = AuditLog::open(&elsewhere.to_string_lossy(), signer.clone()).unwrap();
+    log.append(record(1)).unwrap();
+    log.append(AuditRecord {
+        hash: "00",
+        ..record(2)
+    })
+    .unwrap();
+    assert!(import(&head, &public_key, &elsewhere).is_err());
+    assert_eq!(import(&head, &public_key, &mirror).unwrap().1, 2);
+}
+
+#[test]
+fn test_merkle_proofs_verify_every_leaf() {
+    for count in 1..=9u8 {
+        let leaves: Vec<[u8; 32]> = (0..count).map(|i| merkle::leaf_hash(&[i])).collect();
+        let root = merkle::root(&leaves);
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = merkle::inclusion_proof(&leaves, index);
+            let index = index as u64;
+            assert!(merkle::verify_inclusion(
+                leaf,
+                index,
+                count as u64,
+                &proof,
+                &root
+            ));
+            // Not at another index, nor for another leaf
+            assert!(!merkle::verify_inclusion(
+                leaf,
+                index + 1,
+                count as u64,
+                &proof,
+                &root
+            ));
+            let other = merkle::leaf_hash(b"other");
+            assert!(!merkle::verify_inclusion(
+                &other,
+                index,
+                count as u64,
+                &proof,
+                &root
+            ));
+        }
+    }
+}
+
+#[test]
+fn test_merkle_consistency_proofs_verify_every_prefix() {
+    let leaves: Vec<[u8; 32]> = (0..9u8).map(|i| merkle::leaf_hash(&[i])).collect();
+    for new in 1..=leaves.len() {
+        let new_root = merkle::root(&leaves[..new]);
+        for old in 0..=new {
+            let old_root = merkle::root(&leaves[..old]);
+            let proof = merkle::consistency_proof(&leaves[..new], old);
+            let (old, new) = (old as u64, new as u64);
+            assert!(
+                merkle::verify_consistency(old, new, &old_root, &new_root, &proof),
+                "{} -> {}",
+                old,
+                new
+            );
+            if old == 0 {
+                continue;
+            }
+            // Not for a rewritten old tree, nor between other sizes
+            let rewritten = merkle::root(&[merkle::leaf_hash(b"other")]);
+            assert!(!merkle::verify_consistency(
+                old, new, &rewritten, &new_root, &proof
+            ));
+            assert!(!merkle::verify_consistency(
+                old, new, &old_root, &rewritten, &proof
+            ));
+            if old < new {
+                assert!(!merkle::verify_consistency(
+                    old + 1,
+                    new,
+                    &old_root,
+                    &new_root,
+                    &proof
+                ));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_compacted_entries_stay_provable() {
+    let dir = tempfile::tempdir().unwrap();
+    let signer = Arc::new(KeyPair::from_seed(&[18u8; 32]));
+    let public_key = signer.to_public_key();
+    let path = log_of(&dir, "audit.log", &signer, 5);
+    let later = wire::parse_time("2031-01-01T00:00:00.000000Z").unwrap();
+    let now = "2031-01-01T00:00:00.000000Z";
+
+    // The first three lines (`up_to`), not those after the cut-off time
+    let log = AuditLog::open(&path, signer.clone()).unwrap();
+    let earlier = wire::parse_time("2030-01-01T00:00:00.000000Z").unwrap();
+    assert_eq!(log.compact(earlier, u64::MAX, now).unwrap(), 0);
+    assert_eq!(log.compact(later, 3, now).unwrap(), 3);
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+    assert_eq!(verify_file(&path, &public_key).unwrap(), 5);
+    assert!(log.lines_after(0, 10).is_err());
+    assert_eq!(log.lines_after(3, 10).unwrap().len(), 2);
+    assert!(export(&path, 2, signer.as_ref()).is_err());
+
+    // The chain carries on through a restart; each entry has a proof
+    drop(log);
+    let log = AuditLog::open(&path, signer.clone()).unwrap();
+    assert_eq!(log.append(record(6)).unwrap().seq, 6);
+    assert_eq!(log.compact(later, u64::MAX, now).unwrap(), 3);
+    assert_eq!(verify_file(&path, &public_key).unwrap(), 6);
+    let found = log.compacted_by_hash(record(1).hash);
+    assert_eq!(found.len(), 6);
+    assert!(found.iter().all(|proof| proof.verify(&public_key)));
+    assert!(log.by_hash(record(1).hash).is_empty());
+    let mut edited = found[2].clone();
+    edited.entry.serial = 9;
+    assert!(!edited.verify(&public_key));
+    assert!(!found[0].verify(&KeyPair::from_seed(&[19u8; 32]).to_public_key()));
+
+    // A log whose compacted entries were edited doesn't open
+    drop(log);
+    let compacted = fs::read_to_string(compacted_path(&path)).unwrap();
+    fs::write(
+        compacted_path(&path),
+        compacted.replacen("\"serial\":2", "\"serial\":7", 1),
+    )
+    .unwrap();
+    assert!(AuditLog::open(&path, signer).is_err());
+    assert!(verify_file(&path, &public_key).is_err());
+}
+
+#[test]
+fn test_interrupted_compaction_is_finished_on_open() {
+    let dir = tempfile::tempdir().unwrap();
+    let signer = Arc::new(KeyPair::from_seed(&[20u8; 32]));
+    let public_key = signer.to_public_key();
+    let path = log_of(&dir, "audit.log", &signer, 4);
+    let original = fs::read_to_string(&path).unwrap();
+    let later = wire::parse_time("2031-01-01T00:00:00.000000Z").unwrap();
+    AuditLog::open(&path, signer.clone())
+        .unwrap()
+        .compact(later, 2, "2031-01-01T00:00:00.000000Z")
+        .unwrap();
+
+    // As if it stopped after writing the compacted file
+    fs::write(&path, &original).unwrap();
+    assert_eq!(verify_file(&path, &public_key).unwrap(), 4);
+    let log = AuditLog::open(&path, signer).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+    assert_eq!(log.append(record(5)).unwrap().seq, 5);
+    assert_eq!(verify_file(&path, &public_key).unwrap(), 5);
+}
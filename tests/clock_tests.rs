@@ -0,0 +1,95 @@
+//! Unit tests for the monotonic `time-signed` clock
+
+use chrono::{DateTime, Duration, Utc};
+use lab4::clock::{FakeClock, MonotonicClock};
+use lab4::wire::TimePrecision;
+use std::fs;
+use std::sync::Arc;
+
+fn t(s: &str) -> DateTime<Utc> {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_truncates_to_microseconds() {
+    let clock = MonotonicClock::new(
+        Box::new(FakeClock::new(t("2030-01-01T00:00:00.123456789Z"))),
+        "",
+    )
+    .unwrap();
+    assert_eq!(clock.next().unwrap(), t("2030-01-01T00:00:00.123456Z"));
+}
+
+#[test]
+fn test_never_goes_backwards() {
+    let fake = Arc::new(FakeClock::new(t("2030-01-01T00:00:00Z")));
+    let clock = MonotonicClock::new(Box::new(fake.clone()), "").unwrap();
+
+    let first = clock.next().unwrap();
+    // Same instant again → bumped by 1µs
+    assert_eq!(clock.next().unwrap(), first + Duration::microseconds(1));
+
+    // System clock jumps back a day
+    fake.set(first - Duration::days(1));
+    assert_eq!(clock.next().unwrap(), first + Duration::microseconds(2));
+
+    // Once the clock catches up, real time is used again
+    fake.set(first + Duration::seconds(5));
+    assert_eq!(clock.next().unwrap(), first + Duration::seconds(5));
+}
+
+#[test]
+fn test_high_water_mark_survives_restart() {
+    let path = "test_clock_high_water.txt";
+    let _ = fs::remove_file(path);
+
+    let first = MonotonicClock::new(Box::new(FakeClock::new(t("2030-06-01T12:00:00Z"))), path)
+        .unwrap()
+        .next()
+        .unwrap();
+
+    // "Restart" with a clock that is a year behind
+    let restarted =
+        MonotonicClock::new(Box::new(FakeClock::new(t("2029-06-01T12:00:00Z"))), path).unwrap();
+    let after_restart = restarted.next().unwrap();
+    let content = fs::read_to_string(path).unwrap();
+    let _ = fs::remove_file(path);
+    assert_eq!(content, "2030-06-01T12:00:00.000001Z");
+    // Replaced through a temporary file, which is gone once renamed
+    let tmp = format!("{}.tmp{}", path, std::process::id());
+    assert!(!std::path::Path::new(&tmp).exists());
+
+    assert_eq!(after_restart, first + Duration::microseconds(1));
+}
+
+#[test]
+fn test_corrupt_high_water_mark_is_an_error() {
+    let path = "test_clock_corrupt.txt";
+    fs::write(path, "not a timestamp").unwrap();
+    let result = MonotonicClock::new(Box::new(FakeClock::new(Utc::now())), path);
+    let _ = fs::remove_file(path);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_coarser_precision_truncates_and_never_runs_ahead() {
+    let fake = Arc::new(FakeClock::new(t("2030-01-01T00:00:00.987654Z")));
+    let clock = MonotonicClock::new(Box::new(fake.clone()), "")
+        .unwrap()
+        .with_precision(TimePrecision::Seconds);
+    assert_eq!(clock.next().unwrap(), t("2030-01-01T00:00:00Z"));
+    // Twice in the same second → the same time, not a second later
+    assert_eq!(clock.next().unwrap(), t("2030-01-01T00:00:00Z"));
+    fake.set(t("2030-01-01T00:00:01.5Z"));
+    assert_eq!(clock.peek(), t("2030-01-01T00:00:01Z"));
+
+    // A microsecond mark from before the switch is rounded up, not down
+    let path = "test_clock_precision_high_water.txt";
+    fs::write(path, "2030-01-01T00:00:05.000001Z").unwrap();
+    let restarted = MonotonicClock::new(Box::new(fake.clone()), path)
+        .unwrap()
+        .with_precision(TimePrecision::Millis);
+    let next = restarted.next().unwrap();
+    let _ = fs::remove_file(path);
+    assert_eq!(next, t("2030-01-01T00:00:05.001Z"));
+}
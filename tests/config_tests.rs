@@ -1,31 +1,861 @@
 //! Unit tests for Option A (.bin‐only) loading/generation
 
-use lab4::config::load_or_generate_keys;
+use base64::{Engine as _, engine::general_purpose};
+use ecdsa_lib::KeyPair;
+use lab4::alg::DigestAlg;
+use lab4::config::{
+    DuplicatePolicy, GossipPeer, MessageLogging, ServerConfig, TenantConfig, apply_env,
+    keys_from_env, load_admin_token, load_config_from, load_or_generate_keys_at,
+    load_or_generate_keys_in, prepare_data_dir_in, tenant_env_prefix,
+};
+use lab4::wire::Normalization;
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use tempfile::TempDir;
 
-const PRIV: &str = "private_key.bin";
-const PUB: &str = "public_key.bin";
+/// Every test works in its own directory, so tests (and a running server)
+/// never share key files
+fn temp_dir() -> TempDir {
+    tempfile::tempdir().unwrap()
+}
+
+/// `name` inside `dir`, as the `&str`-typed config fields want it
+fn path_in(dir: &TempDir, name: &str) -> String {
+    dir.path().join(name).to_string_lossy().into_owned()
+}
 
 #[test]
 fn test_generate_and_load_keys_bin() {
-    // 1) Remove any existing .bin files
-    let _ = fs::remove_file(PRIV);
-    let _ = fs::remove_file(PUB);
+    // 1) A fresh data directory (not created yet)
+    let tmp = temp_dir();
+    let dir = tmp.path().join("data");
+    let (priv_path, pub_path) = (dir.join("private_key.bin"), dir.join("public_key.bin"));
 
     // 2) First call: should create the two .bin files and return their bytes
-    let (priv_bytes1, pub_bytes1) = load_or_generate_keys().unwrap();
-    assert!(Path::new(PRIV).exists());
-    assert!(Path::new(PUB).exists());
+    let (priv_bytes1, pub_bytes1) = load_or_generate_keys_in(&dir).unwrap();
+    assert!(priv_path.exists());
+    assert!(pub_path.exists());
     assert!(!priv_bytes1.is_empty());
     assert!(!pub_bytes1.is_empty());
 
     // 3) Second call: should read the same bytes back from disk
-    let (priv_bytes2, pub_bytes2) = load_or_generate_keys().unwrap();
+    let (priv_bytes2, pub_bytes2) = load_or_generate_keys_in(&dir).unwrap();
     assert_eq!(priv_bytes1, priv_bytes2);
     assert_eq!(pub_bytes1, pub_bytes2);
+}
+
+#[test]
+fn test_files_in_working_directory_move_to_data_dir() {
+    // Key files of an older version, in its working directory
+    let cwd = temp_dir();
+    let (priv_name, pub_name) = ("migrate_private_key.bin", "migrate_public_key.bin");
+    KeyPair::from_seed(&[5u8; 32])
+        .save_to_files(&path_in(&cwd, priv_name), &path_in(&cwd, pub_name))
+        .unwrap();
+    let old_priv = fs::read(path_in(&cwd, priv_name)).unwrap();
+
+    let dir = "data";
+    let mut config = ServerConfig {
+        data_dir: dir.to_string(),
+        private_key_file: priv_name.to_string(),
+        public_key_file: pub_name.to_string(),
+        high_water_file: String::new(),
+        serial_file: String::new(),
+        ..Default::default()
+    };
+    config.key_stats.file = "/absolute/key_stats.json".to_string();
+    config
+        .tenants
+        .insert("cs55".to_string(), TenantConfig::default());
+    let moved = prepare_data_dir_in(&mut config, cwd.path()).unwrap();
+
+    // Both key files moved, and still load
+    assert_eq!(moved.len(), 2);
+    assert!(!cwd.path().join(priv_name).exists());
+    let loaded = load_or_generate_keys_at(&config.private_key_file, &config.public_key_file);
+    assert_eq!(loaded.unwrap().0, old_priv);
+
+    // Empty and absolute paths are left alone; tenant defaults now point in
+    assert_eq!(config.high_water_file, "");
+    assert_eq!(config.key_stats.file, "/absolute/key_stats.json");
+    assert_eq!(
+        Path::new(&config.tenants["cs55"].key_files("cs55").0),
+        cwd.path().join(dir).join("cs55_private_key.bin")
+    );
+}
+
+#[test]
+fn test_missing_config_file_is_default() {
+    let tmp = temp_dir();
+    let config = load_config_from(path_in(&tmp, "missing.toml")).unwrap();
+    assert!(config.tenants.is_empty());
+    assert_eq!(config.sign_cache.window_secs, 0);
+}
+
+#[test]
+fn test_tenants_table() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    fs::write(
+        path,
+        r#"
+[tenants.cs55]
+
+[tenants.cs69]
+private_key_file = "keys/cs69.key"
+public_key_file = "keys/cs69.pub"
+"#,
+    )
+    .unwrap();
+    let config = load_config_from(path).unwrap();
+
+    assert_eq!(config.tenants.len(), 2);
+    assert_eq!(
+        config.tenants["cs55"].key_files("cs55"),
+        (
+            "cs55_private_key.bin".to_string(),
+            "cs55_public_key.bin".to_string()
+        )
+    );
+    assert_eq!(
+        config.tenants["cs69"].key_files("cs69"),
+        ("keys/cs69.key".to_string(), "keys/cs69.pub".to_string())
+    );
+}
+
+#[test]
+fn test_sign_cache_table() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    fs::write(
+        path,
+        "[sign_cache]\nwindow_secs = 300\nby_message_hash = true\n",
+    )
+    .unwrap();
+    let config = load_config_from(path).unwrap();
+
+    assert_eq!(config.sign_cache.window_secs, 300);
+    assert!(config.sign_cache.by_message_hash);
+}
+
+#[test]
+fn test_duplicates_policy_and_env() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    let config = load_config_from(path_in(&tmp, "missing.toml")).unwrap();
+    assert_eq!(config.duplicates, DuplicatePolicy::New);
+
+    fs::write(path, "duplicates = \"first-seen\"\n").unwrap();
+    let mut config = load_config_from(path).unwrap();
+    assert_eq!(config.duplicates, DuplicatePolicy::FirstSeen);
+
+    apply_env(&mut config, &env_of(&[("VTS_DUPLICATES", "new")])).unwrap();
+    assert_eq!(config.duplicates, DuplicatePolicy::New);
+    let env = env_of(&[("VTS_DUPLICATES", "oldest")]);
+    assert!(apply_env(&mut config, &env).is_err());
+    fs::write(path, "duplicates = \"oldest\"\n").unwrap();
+    assert!(load_config_from(path).is_err());
+}
+
+#[test]
+fn test_logging_table_and_env() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    let logging = load_config_from(path_in(&tmp, "missing.toml"))
+        .unwrap()
+        .logging;
+    assert_eq!(
+        (logging.messages, logging.sample_rate),
+        (MessageLogging::Hash, 1.0)
+    );
+
+    fs::write(path, "[logging]\nmessages = \"full\"\nsample_rate = 0.25\n").unwrap();
+    let mut config = load_config_from(path).unwrap();
+    assert_eq!(
+        (config.logging.messages, config.logging.sample_rate),
+        (MessageLogging::Full, 0.25)
+    );
+    let env = env_of(&[
+        ("VTS_LOGGING_MESSAGES", "omit"),
+        ("VTS_LOGGING_SAMPLE_RATE", "0.5"),
+    ]);
+    apply_env(&mut config, &env).unwrap();
+    assert_eq!(
+        (config.logging.messages, config.logging.sample_rate),
+        (MessageLogging::Omit, 0.5)
+    );
+    let env = env_of(&[("VTS_LOGGING_MESSAGES", "redacted")]);
+    assert!(apply_env(&mut config, &env).is_err());
+    fs::write(path, "[logging]\nsample_rate = 1.5\n").unwrap();
+    assert!(load_config_from(path).is_err());
+}
+
+#[test]
+fn test_listen_and_env() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    let config = load_config_from(path_in(&tmp, "missing.toml")).unwrap();
+    assert!(config.listen.is_empty());
+
+    fs::write(path, "listen = \"unix:/run/vts.sock\"\n").unwrap();
+    let config = load_config_from(path).unwrap();
+    assert_eq!(config.listen, ["unix:/run/vts.sock"]);
+    fs::write(path, "listen = [\"0.0.0.0:8008\", \"[::]:8008\"]\n").unwrap();
+    let mut config = load_config_from(path).unwrap();
+    assert_eq!(config.listen, ["0.0.0.0:8008", "[::]:8008"]);
+    let env = env_of(&[("VTS_LISTEN", "127.0.0.1:9000, [::1]:9000")]);
+    apply_env(&mut config, &env).unwrap();
+    assert_eq!(config.listen, ["127.0.0.1:9000", "[::1]:9000"]);
+}
+
+#[test]
+fn test_normalization_and_env() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    let config = load_config_from(path_in(&tmp, "missing.toml")).unwrap();
+    assert_eq!(config.normalization, Normalization::Exact);
+
+    fs::write(path, "normalization = \"nfc\"\n").unwrap();
+    let mut config = load_config_from(path).unwrap();
+    assert_eq!(config.normalization, Normalization::Nfc);
+    apply_env(&mut config, &env_of(&[("VTS_NORMALIZATION", "exact")])).unwrap();
+    assert_eq!(config.normalization, Normalization::Exact);
+    let env = env_of(&[("VTS_NORMALIZATION", "nfd")]);
+    assert!(apply_env(&mut config, &env).is_err());
+    fs::write(path, "normalization = \"nfkc\"\n").unwrap();
+    assert!(load_config_from(path).is_err());
+}
+
+#[test]
+fn test_mirror_table_and_env() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    let config = load_config_from(path_in(&tmp, "missing.toml")).unwrap();
+    assert!(config.mirror.upstream.is_empty());
+    assert_eq!(config.mirror.interval_secs, 10);
+
+    fs::write(path, "[mirror]\nupstream = \"https://vts.example\"\n").unwrap();
+    let mut config = load_config_from(path).unwrap();
+    assert_eq!(config.mirror.upstream, "https://vts.example");
+    assert_eq!(config.mirror.interval_secs, 10);
+
+    let env = env_of(&[
+        ("VTS_MIRROR_UPSTREAM", "https://other.example"),
+        ("VTS_MIRROR_INTERVAL_SECS", "60"),
+    ]);
+    apply_env(&mut config, &env).unwrap();
+    assert_eq!(config.mirror.upstream, "https://other.example");
+    assert_eq!(config.mirror.interval_secs, 60);
+    let env = env_of(&[("VTS_MIRROR_INTERVAL_SECS", "often")]);
+    assert!(apply_env(&mut config, &env).is_err());
+}
+
+#[test]
+fn test_admin_table_env_and_token() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    let config = load_config_from(path_in(&tmp, "missing.toml")).unwrap();
+    assert!(config.admin.listen.is_empty());
+    assert_eq!(config.admin.token_file, "admin_token");
+
+    fs::write(path, "[admin]\nlisten = \"127.0.0.1:8009\"\n").unwrap();
+    let mut config = load_config_from(path).unwrap();
+    assert_eq!(config.admin.listen, "127.0.0.1:8009");
+    let env = env_of(&[
+        ("VTS_ADMIN_LISTEN", "[::1]:9009"),
+        ("VTS_ADMIN_TOKEN_FILE", &path_in(&tmp, "token")),
+    ]);
+    apply_env(&mut config, &env).unwrap();
+    assert_eq!(config.admin.listen, "[::1]:9009");
+
+    // The token comes from VTS_ADMIN_TOKEN, else the file; never generated
+    let no_env = env_of(&[]);
+    assert!(load_admin_token(&config.admin, &no_env).is_err());
+    fs::write(path_in(&tmp, "token"), "  0123456789abcdef\n").unwrap();
+    assert_eq!(
+        load_admin_token(&config.admin, &no_env).unwrap(),
+        "0123456789abcdef"
+    );
+    let env = env_of(&[("VTS_ADMIN_TOKEN", "fedcba9876543210fedcba")]);
+    assert_eq!(
+        load_admin_token(&config.admin, &env).unwrap(),
+        "fedcba9876543210fedcba"
+    );
+    let env = env_of(&[("VTS_ADMIN_TOKEN", "short")]);
+    assert!(load_admin_token(&config.admin, &env).is_err());
+}
+
+#[test]
+fn test_cors_table_and_env() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    assert!(
+        load_config_from(path_in(&tmp, "missing.toml"))
+            .unwrap()
+            .cors
+            .allowed_origins
+            .is_empty()
+    );
+
+    fs::write(
+        path,
+        "[cors]\nallowed_origins = [\"https://app.example\"]\nmax_age_secs = 60\n",
+    )
+    .unwrap();
+    let mut config = load_config_from(path).unwrap();
+    assert_eq!(config.cors.allowed_origins, ["https://app.example"]);
+    assert_eq!(config.cors.allowed_methods, ["GET", "POST"]);
+    assert!(
+        config
+            .cors
+            .allowed_headers
+            .contains(&"x-tenant".to_string())
+    );
+    assert_eq!(config.cors.max_age_secs, 60);
+
+    let env = env_of(&[
+        (
+            "VTS_CORS_ALLOWED_ORIGINS",
+            "https://a.example, https://b.example",
+        ),
+        ("VTS_CORS_ALLOWED_HEADERS", "content-type"),
+    ]);
+    apply_env(&mut config, &env).unwrap();
+    assert_eq!(
+        config.cors.allowed_origins,
+        ["https://a.example", "https://b.example"]
+    );
+    assert_eq!(config.cors.allowed_headers, ["content-type"]);
+    assert_eq!(config.cors.max_age_secs, 60);
+}
+
+#[test]
+fn test_invalid_tenant_name_rejected() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    fs::write(path, "[tenants.\"../etc\"]\n").unwrap();
+    assert!(load_config_from(path).is_err());
+}
+
+#[test]
+fn test_generated_private_key_is_owner_only_and_recoverable() {
+    let tmp = temp_dir();
+    let (private_path, public_path) = (
+        &path_in(&tmp, "private_key.bin"),
+        &path_in(&tmp, "public_key.bin"),
+    );
+
+    let (priv_bytes, pub_bytes) = load_or_generate_keys_at(private_path, public_path).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(private_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // A crash after writing only the private key: the public key is
+    // derived again rather than the private key replaced
+    fs::remove_file(public_path).unwrap();
+    let recovered = load_or_generate_keys_at(private_path, public_path).unwrap();
+    assert_eq!(recovered, (priv_bytes, pub_bytes));
+
+    // Without the private key there is nothing to recover from
+    fs::remove_file(private_path).unwrap();
+    assert!(load_or_generate_keys_at(private_path, public_path).is_err());
+}
+
+#[test]
+fn test_mismatched_key_files_rejected_on_load() {
+    let tmp = temp_dir();
+    let (private_path, public_path) = (
+        &path_in(&tmp, "private_key.bin"),
+        &path_in(&tmp, "public_key.bin"),
+    );
+    KeyPair::from_seed(&[6u8; 32])
+        .save_to_files(private_path, public_path)
+        .unwrap();
+    let other = tmp.path().join("other_public_key.bin");
+    KeyPair::from_seed(&[7u8; 32])
+        .save_to_files(
+            &path_in(&tmp, "other_private_key.bin"),
+            &other.to_string_lossy(),
+        )
+        .unwrap();
+
+    // Another key pair's public key, then one that doesn't parse
+    fs::copy(&other, public_path).unwrap();
+    let err = load_or_generate_keys_at(private_path, public_path).unwrap_err();
+    assert!(err.to_string().contains("does not belong"), "{}", err);
+    assert!(err.to_string().contains(public_path.as_str()), "{}", err);
+
+    fs::write(public_path, b"not a key").unwrap();
+    let err = load_or_generate_keys_at(private_path, public_path).unwrap_err();
+    assert!(err.to_string().contains("Invalid public key"), "{}", err);
+}
+
+/// An environment with just `vars` set
+fn env_of(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> + use<> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    move |name| vars.get(name).cloned()
+}
+
+#[test]
+fn test_env_overrides_config_file() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    fs::write(
+        path,
+        r#"
+max_message_bytes = 10
+log_level = "debug"
+
+[tenants.cs55]
+"#,
+    )
+    .unwrap();
+    let mut config = load_config_from(path).unwrap();
+
+    let env = env_of(&[
+        ("VTS_PORT", "9000"),
+        ("VTS_MAX_MESSAGE_BYTES", "20"),
+        ("VTS_PRIVATE_KEY_FILE", "/keys/private.bin"),
+        ("VTS_NTP_SERVERS", "a:123, b:123"),
+        ("VTS_TENANTS", "course-a,cs69"),
+        ("VTS_TENANT_COURSE_A_PUBLIC_KEY_FILE", "/keys/a.pub"),
+        ("VTS_KMS_KEY_ID", "alias/vts"),
+        ("VTS_KMS_REGION", "eu-west-1"),
+    ]);
+    apply_env(&mut config, &env).unwrap();
+
+    // The environment wins; what it doesn't set comes from the file
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.max_message_bytes, 20);
+    assert_eq!(config.log_level, "debug");
+    assert_eq!(config.private_key_file, "/keys/private.bin");
+    assert_eq!(config.public_key_file, "public_key.bin");
+    assert_eq!(config.ntp.servers, ["a:123", "b:123"]);
+    assert_eq!(
+        config.tenants.keys().collect::<Vec<_>>(),
+        ["course-a", "cs69"]
+    );
+    assert_eq!(
+        config.tenants["course-a"].key_files("course-a").1,
+        "/keys/a.pub"
+    );
+    assert_eq!(config.kms.unwrap().key_id, "alias/vts");
+
+    for bad in [
+        ("VTS_PORT", "eighty"),
+        ("VTS_LOG_LEVEL", "loud"),
+        ("VTS_TENANTS", "no spaces"),
+    ] {
+        let mut config = load_config_from(path_in(&tmp, "missing.toml")).unwrap();
+        assert!(
+            apply_env(&mut config, &env_of(&[bad])).is_err(),
+            "{:?}",
+            bad
+        );
+    }
+}
+
+#[test]
+fn test_keys_from_env() {
+    let keypair = KeyPair::from_seed(&[3u8; 32]);
+    let tmp = temp_dir();
+    let (private_path, public_path) = (
+        &path_in(&tmp, "private_key.bin"),
+        &path_in(&tmp, "public_key.bin"),
+    );
+    keypair.save_to_files(private_path, public_path).unwrap();
+    let private_key = fs::read(private_path).unwrap();
+    let public_key = fs::read(public_path).unwrap();
+    let b64 = |bytes: &[u8]| general_purpose::STANDARD.encode(bytes);
+
+    let prefix = tenant_env_prefix("course-a");
+    assert_eq!(prefix, "VTS_TENANT_COURSE_A_");
+    assert_eq!(keys_from_env(&env_of(&[]), &prefix).unwrap(), None);
+
+    // The public key is optional, and derived when missing
+    let private_var = format!("{}PRIVATE_KEY", prefix);
+    let only_private = env_of(&[(&private_var, &b64(&private_key))]);
+    assert_eq!(
+        keys_from_env(&only_private, &prefix).unwrap(),
+        Some((private_key.clone(), public_key.clone()))
+    );
+
+    // A public key of another key pair is refused
+    let other = KeyPair::from_seed(&[4u8; 32]).to_public_key();
+    let public_var = format!("{}PUBLIC_KEY", prefix);
+    let mismatched = env_of(&[
+        (&private_var, &b64(&private_key)),
+        (&public_var, &b64(&other.to_sec1_bytes())),
+    ]);
+    assert!(keys_from_env(&mismatched, &prefix).is_err());
+    assert!(keys_from_env(&env_of(&[(&private_var, "not base64!")]), &prefix).is_err());
+}
+
+#[test]
+fn test_sign_pool_table_and_env() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    let config = load_config_from(path_in(&tmp, "missing.toml")).unwrap();
+    assert_eq!(config.sign_pool.workers, 0);
+    assert_eq!(config.sign_pool.queue_depth, 256);
+    assert_eq!(config.sign_pool.retry_after_secs, 1);
+
+    fs::write(path, "[sign_pool]\nworkers = 2\nqueue_depth = 8\n").unwrap();
+    let mut config = load_config_from(path).unwrap();
+    assert_eq!(config.sign_pool.workers, 2);
+    assert_eq!(config.sign_pool.queue_depth, 8);
+    assert_eq!(config.sign_pool.retry_after_secs, 1);
+
+    let env = env_of(&[
+        ("VTS_SIGN_POOL_QUEUE_DEPTH", "0"),
+        ("VTS_SIGN_POOL_RETRY_AFTER_SECS", "5"),
+    ]);
+    apply_env(&mut config, &env).unwrap();
+    assert_eq!(config.sign_pool.workers, 2);
+    assert_eq!(config.sign_pool.queue_depth, 0);
+    assert_eq!(config.sign_pool.retry_after_secs, 5);
+
+    let env = env_of(&[("VTS_SIGN_POOL_WORKERS", "many")]);
+    assert!(apply_env(&mut config, &env).is_err());
+}
+
+#[test]
+fn test_limits_table_and_env() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    let config = load_config_from(path_in(&tmp, "missing.toml")).unwrap();
+    assert_eq!(config.limits.max_concurrent_requests, 1024);
+    assert_eq!(config.limits.queue_depth, 1024);
+    assert_eq!(config.limits.queue_timeout_ms, 10_000);
+
+    fs::write(path, "[limits]\nmax_concurrent_requests = 0\n").unwrap();
+    let mut config = load_config_from(path).unwrap();
+    assert_eq!(config.limits.max_concurrent_requests, 0);
+    assert_eq!(config.limits.queue_depth, 1024);
+
+    let env = env_of(&[
+        ("VTS_LIMITS_MAX_CONCURRENT_REQUESTS", "64"),
+        ("VTS_LIMITS_QUEUE_TIMEOUT_MS", "250"),
+    ]);
+    apply_env(&mut config, &env).unwrap();
+    assert_eq!(config.limits.max_concurrent_requests, 64);
+    assert_eq!(config.limits.queue_timeout_ms, 250);
+    assert_eq!(config.limits.retry_after_secs, 1);
+}
+
+#[test]
+fn test_key_validity_tables() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    fs::write(
+        path,
+        r#"
+[key_validity]
+not_after = "2030-01-01T00:00:00Z"
+
+[[extra_keys]]
+private_key_file = "old.key"
+public_key_file = "old.pub"
+not_before = "2024-01-01T00:00:00Z"
+not_after = "2025-01-01T00:00:00Z"
+
+[tenants.cs55.key_validity]
+not_before = "2024-06-01T00:00:00Z"
+"#,
+    )
+    .unwrap();
+    let config = load_config_from(path).unwrap();
+    let time = |s: &str| Some(s.parse::<chrono::DateTime<chrono::Utc>>().unwrap());
+
+    assert_eq!(config.key_validity.not_before, None);
+    assert_eq!(config.key_validity.not_after, time("2030-01-01T00:00:00Z"));
+    assert!(
+        config
+            .key_validity
+            .contains(time("2029-01-01T00:00:00Z").unwrap())
+    );
+    assert!(
+        !config
+            .key_validity
+            .contains(time("2031-01-01T00:00:00Z").unwrap())
+    );
+    let extra = &config.extra_keys[0];
+    assert_eq!(extra.public_key_file, "old.pub");
+    assert_eq!(extra.validity.not_before, time("2024-01-01T00:00:00Z"));
+    assert_eq!(extra.validity.not_after, time("2025-01-01T00:00:00Z"));
+    assert_eq!(
+        config.tenants["cs55"].key_validity.not_before,
+        time("2024-06-01T00:00:00Z")
+    );
+
+    fs::write(path, "[key_validity]\nnot_after = \"next year\"\n").unwrap();
+    assert!(load_config_from(path).is_err());
+}
+
+#[test]
+fn test_revoked_keys_tables() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    fs::write(
+        path,
+        r#"
+[[revoked_keys]]
+kid = "iZndHloCAbCd"
+compromised_at = "2025-03-01T12:00:00Z"
+reason = "key-compromise"
+"#,
+    )
+    .unwrap();
+    let config = load_config_from(path).unwrap();
+
+    assert_eq!(config.revoked_keys.len(), 1);
+    assert_eq!(config.revoked_keys[0].kid, "iZndHloCAbCd");
+    assert_eq!(
+        config.revoked_keys[0].compromised_at,
+        "2025-03-01T12:00:00Z"
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .unwrap()
+    );
+
+    // Each entry is one line of the signed list
+    fs::write(
+        path,
+        "[[revoked_keys]]\nkid = \"a b\"\ncompromised_at = \"2025-03-01T12:00:00Z\"\n",
+    )
+    .unwrap();
+    assert!(load_config_from(path).is_err());
+    fs::write(
+        path,
+        "[[revoked_keys]]\nkid = \"ab\"\ncompromised_at = \"2025-03-01T12:00:00Z\"\nreason = \"one\\ntwo\"\n",
+    )
+    .unwrap();
+    assert!(load_config_from(path).is_err());
+}
+
+#[test]
+fn test_policies_tables() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    fs::write(
+        path,
+        r#"
+[policies.high-assurance]
+description = "Audited, NTP-checked"
+require_audit = true
+require_ntp = true
+
+[policies.default]
+"#,
+    )
+    .unwrap();
+    let config = load_config_from(path).unwrap();
+
+    let names: Vec<&str> = config.policies.keys().map(String::as_str).collect();
+    assert_eq!(names, ["default", "high-assurance"]);
+    assert!(config.policies["high-assurance"].require_ntp);
+    assert!(!config.policies["default"].require_audit);
+
+    // The name is signed after `time-signed`, so it can't hold a space
+    fs::write(path, "[policies.\"high assurance\"]\n").unwrap();
+    assert!(load_config_from(path).is_err());
+}
+
+#[test]
+fn test_digest_algs_by_name() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    assert_eq!(
+        load_config_from(path).unwrap().digest_algs,
+        DigestAlg::SUPPORTED
+    );
+
+    fs::write(path, "digest_algs = [\"sha-512\", \"blake3\"]\n").unwrap();
+    assert_eq!(
+        load_config_from(path).unwrap().digest_algs,
+        [DigestAlg::Sha512, DigestAlg::Blake3]
+    );
+    fs::write(path, "digest_algs = [\"md5\"]\n").unwrap();
+    assert!(load_config_from(path).is_err());
+}
+
+#[test]
+fn test_archive_table() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    assert!(load_config_from(path).unwrap().archive.endpoint.is_empty());
+
+    fs::write(
+        path,
+        r#"
+[archive]
+endpoint = "http://minio:9000"
+bucket = "vts-archive"
+segment_entries = 500
+"#,
+    )
+    .unwrap();
+    let archive = load_config_from(path).unwrap().archive;
+    assert_eq!(archive.bucket, "vts-archive");
+    assert_eq!(archive.segment_entries, 500);
+    assert_eq!(
+        (archive.region.as_str(), archive.prefix.as_str()),
+        ("us-east-1", "vts/")
+    );
+
+    fs::write(path, "[archive]\nendpoint = \"http://minio:9000\"\n").unwrap();
+    assert!(load_config_from(path).is_err());
+}
+
+#[test]
+fn test_retention_table() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    let retention = load_config_from(path).unwrap().retention;
+    assert_eq!((retention.full_days, retention.interval_secs), (0, 3600));
+
+    fs::write(path, "[retention]\nfull_days = 90\n").unwrap();
+    let retention = load_config_from(path).unwrap().retention;
+    assert_eq!((retention.full_days, retention.interval_secs), (90, 3600));
+}
+
+#[test]
+fn test_gossip_table() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    let gossip = load_config_from(path).unwrap().gossip;
+    assert!(gossip.peers.is_empty());
+    assert_eq!(gossip.interval_secs, 60);
+
+    fs::write(
+        path,
+        "[gossip]\ninterval_secs = 5\n[[gossip.peers]]\nurl = \"http://vts-b:8008\"\nkid = \"abc\"\n",
+    )
+    .unwrap();
+    let gossip = load_config_from(path).unwrap().gossip;
+    assert_eq!(
+        gossip.peers,
+        vec![GossipPeer {
+            url: "http://vts-b:8008".to_string(),
+            kid: "abc".to_string(),
+        }]
+    );
+    assert_eq!(gossip.interval_secs, 5);
+
+    fs::write(
+        path,
+        "[[gossip.peers]]\nurl = \"http://vts-b:8008\"\nkid = \"\"\n",
+    )
+    .unwrap();
+    assert!(load_config_from(path).is_err());
+}
+
+#[test]
+fn test_anchor_table_and_env() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    let anchor = load_config_from(path).unwrap().anchor;
+    assert!(anchor.witness.is_empty());
+    assert_eq!(
+        (anchor.file.as_str(), anchor.interval_secs),
+        ("anchors.ndjson", 3600)
+    );
+
+    fs::write(
+        path,
+        "[anchor]\nwitness = \"https://witness.example/anchor\"\ninterval_secs = 600\n",
+    )
+    .unwrap();
+    let mut config = load_config_from(path).unwrap();
+    assert_eq!(
+        (config.anchor.witness.as_str(), config.anchor.interval_secs),
+        ("https://witness.example/anchor", 600)
+    );
+    let env = env_of(&[
+        ("VTS_ANCHOR_FILE", "/var/lib/vts/anchors.ndjson"),
+        ("VTS_ANCHOR_INTERVAL_SECS", "60"),
+    ]);
+    apply_env(&mut config, &env).unwrap();
+    assert_eq!(
+        (config.anchor.file.as_str(), config.anchor.interval_secs),
+        ("/var/lib/vts/anchors.ndjson", 60)
+    );
+}
+
+#[test]
+fn test_otlp_table_and_env() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    let otlp = load_config_from(path).unwrap().otlp;
+    assert!(otlp.endpoint.is_empty());
+    assert_eq!(
+        (
+            otlp.service_name.as_str(),
+            otlp.interval_secs,
+            otlp.max_queue
+        ),
+        ("vts", 5, 8192)
+    );
+
+    fs::write(
+        path,
+        "[otlp]\nendpoint = \"http://collector:4318\"\ninterval_secs = 1\n",
+    )
+    .unwrap();
+    let mut config = load_config_from(path).unwrap();
+    assert_eq!(
+        (config.otlp.endpoint.as_str(), config.otlp.interval_secs),
+        ("http://collector:4318", 1)
+    );
+    let env = env_of(&[
+        ("VTS_OTLP_SERVICE_NAME", "vts-eu"),
+        ("VTS_OTLP_MAX_QUEUE", "100"),
+    ]);
+    apply_env(&mut config, &env).unwrap();
+    assert_eq!(
+        (config.otlp.service_name.as_str(), config.otlp.max_queue),
+        ("vts-eu", 100)
+    );
+
+    fs::write(
+        path,
+        "[otlp]\nendpoint = \"http://collector:4318\"\nmax_queue = 0\n",
+    )
+    .unwrap();
+    assert!(load_config_from(path).is_err());
+}
+
+#[test]
+fn test_roughtime_table() {
+    let tmp = temp_dir();
+    let path = &path_in(&tmp, "vts.toml");
+    let roughtime = load_config_from(path).unwrap().roughtime;
+    assert!(roughtime.listen.is_empty());
+    assert_eq!(
+        (roughtime.radius_ms, roughtime.delegation_secs),
+        (1000, 86400)
+    );
+
+    fs::write(
+        path,
+        "[roughtime]\nlisten = \"0.0.0.0:2002\"\nradius_ms = 50\ndelegation_secs = 3600\n",
+    )
+    .unwrap();
+    let roughtime = load_config_from(path).unwrap().roughtime;
+    assert_eq!(roughtime.listen, "0.0.0.0:2002");
+    assert_eq!((roughtime.radius_ms, roughtime.delegation_secs), (50, 3600));
 
-    // 4) Clean up
-    let _ = fs::remove_file(PRIV);
-    let _ = fs::remove_file(PUB);
+    // RADI is microseconds in 32 bits
+    fs::write(
+        path,
+        "[roughtime]\nlisten = \"0.0.0.0:2002\"\nradius_ms = 5000000\n",
+    )
+    .unwrap();
+    assert!(load_config_from(path).is_err());
 }
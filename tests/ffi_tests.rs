@@ -0,0 +1,135 @@
+//! The C interface, called through its `extern "C"` functions as a C
+//! program would
+
+use ecdsa_lib::KeyPair;
+use k256::sha2::{Digest, Sha256};
+use lab4::cose::{self, CoseClaims};
+use lab4::ffi::*;
+use lab4::jws::{self, JwsClaims};
+use std::convert::Infallible;
+use std::ffi::CString;
+use std::ptr;
+
+const TIME: &str = "2030-01-01T00:00:00.000000Z";
+
+fn verify(msg: &[u8], time: &str, sig: &[u8], pubkey: &[u8]) -> i32 {
+    let time = CString::new(time).unwrap();
+    unsafe {
+        vts_verify_signature(
+            msg.as_ptr(),
+            msg.len(),
+            time.as_ptr(),
+            sig.as_ptr(),
+            sig.len(),
+            pubkey.as_ptr(),
+            pubkey.len(),
+        )
+    }
+}
+
+#[test]
+fn test_verify_signature_codes() {
+    let keypair = KeyPair::from_seed(&[5u8; 32]);
+    let pubkey = keypair.to_public_key().to_sec1_bytes();
+    let signature = keypair.sign(format!("Hello{}", TIME).as_bytes());
+    let raw = signature.to_vec();
+    let der = KeyPair::signature_to_der(&signature);
+
+    assert_eq!(verify(b"Hello", TIME, &raw, &pubkey), VTS_OK);
+    assert_eq!(verify(b"Hello", TIME, &der, &pubkey), VTS_OK);
+    assert_eq!(verify(b"Jello", TIME, &raw, &pubkey), VTS_ERR_BAD_SIGNATURE);
+    assert_eq!(
+        verify(b"Hello", TIME, &raw[..10], &pubkey),
+        VTS_ERR_BAD_ENCODING
+    );
+    assert_eq!(verify(b"Hello", TIME, &raw, &pubkey[1..]), VTS_ERR_BAD_KEY);
+
+    let time = CString::new(TIME).unwrap();
+    let code = unsafe {
+        vts_verify_signature(
+            ptr::null(),
+            5,
+            time.as_ptr(),
+            raw.as_ptr(),
+            raw.len(),
+            pubkey.as_ptr(),
+            pubkey.len(),
+        )
+    };
+    assert_eq!(code, VTS_ERR_NULL);
+}
+
+#[test]
+fn test_verify_tokens() {
+    let keypair = KeyPair::from_seed(&[6u8; 32]);
+    let public_key = keypair.to_public_key();
+    let pubkey = public_key.to_sec1_bytes();
+    let other = KeyPair::from_seed(&[7u8; 32])
+        .to_public_key()
+        .to_sec1_bytes();
+    let sign = |bytes: &[u8]| Ok::<_, Infallible>(keypair.sign(bytes));
+    let msg_hash: [u8; 32] = Sha256::digest(b"Hello").into();
+    let expected = VtsClaims {
+        msg_hash,
+        iat: 1_900_000_000,
+        serial: 9,
+        kid: jws::key_thumbprint(&public_key),
+    };
+
+    let token = jws::encode(
+        &JwsClaims {
+            msg_hash: hex::encode(msg_hash),
+            iat: expected.iat,
+            serial: expected.serial,
+            kid: jws::key_id(&public_key),
+            policy: None,
+            day_root: None,
+        },
+        sign,
+    )
+    .unwrap();
+    let token = CString::new(token).unwrap();
+    let mut claims = VtsClaims::default();
+    let code =
+        unsafe { vts_verify_jws(token.as_ptr(), pubkey.as_ptr(), pubkey.len(), &mut claims) };
+    assert_eq!(code, VTS_OK);
+    assert_eq!(claims, expected);
+    let code =
+        unsafe { vts_verify_jws(token.as_ptr(), other.as_ptr(), other.len(), ptr::null_mut()) };
+    assert_eq!(code, VTS_ERR_BAD_SIGNATURE);
+
+    let token = cose::encode(
+        &CoseClaims {
+            msg_hash,
+            iat: expected.iat,
+            serial: expected.serial,
+            kid: expected.kid,
+            policy: None,
+            day_root: None,
+        },
+        sign,
+    )
+    .unwrap();
+    let mut claims = VtsClaims::default();
+    let code = unsafe {
+        vts_verify_cose(
+            token.as_ptr(),
+            token.len(),
+            pubkey.as_ptr(),
+            pubkey.len(),
+            &mut claims,
+        )
+    };
+    assert_eq!(code, VTS_OK);
+    assert_eq!(claims, expected);
+    let code = unsafe {
+        vts_verify_cose(
+            token.as_ptr(),
+            token.len(),
+            other.as_ptr(),
+            other.len(),
+            ptr::null_mut(),
+        )
+    };
+    assert_eq!(code, VTS_ERR_BAD_SIGNATURE);
+}
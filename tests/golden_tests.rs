@@ -0,0 +1,238 @@
+//! Golden-file wire compatibility tests: the server, with a fixed key and a
+//! `FakeClock`, must answer canonical requests with exactly the bytes in
+//! `tests/golden/`, and the client must still read and verify what those
+//! files and the archived responses in `tests/golden/historical/` hold.
+//!
+//! A case is `NAME.request.json` (the body sent, if any) and
+//! `NAME.response.EXT`. After an intended wire change, regenerate them with
+//! `VTS_UPDATE_GOLDEN=1 cargo test --test golden_tests` and review the diff.
+
+use chrono::{DateTime, Utc};
+use ecdsa_lib::KeyPair;
+use lab4::clock::FakeClock;
+use lab4::config::{KeyStatsConfig, ServerConfig, TenantKeys};
+use lab4::ecdsa_requests::{verify_cose_token, verify_signature};
+use lab4::{ApiError, EcdsaSignedTimestamp, EcdsaVerificationKey, jws, server};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use tokio::task;
+use tokio::time::{Duration, sleep};
+
+/// The time every golden timestamp is issued at
+const SIGNED_AT: &str = "2025-06-02T05:05:35.784383Z";
+
+/// The message of every sign case
+const MESSAGE: &str = "Hello, VTS!";
+
+/// The request id of the error cases, so their bodies are fixed too
+const REQUEST_ID: &str = "golden-request";
+
+/// (name, method, path): the request is `name.request.json` if it exists,
+/// the expected answer `name.response.*`
+const CASES: &[(&str, &str, &str)] = &[
+    ("key", "GET", "/key"),
+    ("key-hex", "GET", "/key?format=hex"),
+    ("sign", "POST", "/sign"),
+    ("sign-hex", "POST", "/sign"),
+    ("sign-der", "POST", "/sign"),
+    ("sign-b64", "POST", "/sign"),
+    ("sign-jws", "POST", "/sign?format=jws"),
+    ("sign-cose", "POST", "/sign?format=cose"),
+    ("error-invalid-json", "POST", "/sign"),
+    ("error-missing-message", "POST", "/sign"),
+];
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn updating() -> bool {
+    std::env::var_os("VTS_UPDATE_GOLDEN").is_some()
+}
+
+/// The response file of `name`, whatever its extension
+fn response_file(name: &str) -> PathBuf {
+    let ext = match name {
+        "sign-jws" => "jws",
+        "sign-cose" => "cose",
+        _ => "json",
+    };
+    golden_dir().join(format!("{}.response.{}", name, ext))
+}
+
+fn read(path: &Path) -> Vec<u8> {
+    fs::read(path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+/// Spawns a server with the golden key, `[7; 32]` as seed, and its clock
+/// stopped at `SIGNED_AT`
+async fn spawn_golden_server() -> SocketAddr {
+    let dir = std::env::temp_dir().join(format!("vts-golden-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (private_key_file, public_key_file) = (dir.join("private.bin"), dir.join("public.bin"));
+    KeyPair::from_seed(&[7; 32])
+        .save_to_files(
+            private_key_file.to_str().unwrap(),
+            public_key_file.to_str().unwrap(),
+        )
+        .unwrap();
+    let priv_bytes = fs::read(&private_key_file).unwrap();
+    let pub_bytes = fs::read(&public_key_file).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    let config = ServerConfig {
+        high_water_file: String::new(),
+        serial_file: String::new(),
+        key_stats: KeyStatsConfig {
+            file: String::new(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let clock = FakeClock::new(SIGNED_AT.parse::<DateTime<Utc>>().unwrap());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    task::spawn(async move {
+        server::run_configured_server_with_listener(
+            priv_bytes,
+            pub_bytes,
+            TenantKeys::new(),
+            config,
+            Box::new(clock),
+            listener,
+        )
+        .await
+        .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+    });
+    sleep(Duration::from_millis(100)).await;
+    addr
+}
+
+/// What a fresh golden server answers to the case `name`
+async fn answer(name: &str, method: &str, path: &str) -> Vec<u8> {
+    let addr = spawn_golden_server().await;
+    let client = reqwest::Client::new();
+    let url = format!("http://{}{}", addr, path);
+    let request = match method {
+        "GET" => client.get(url),
+        _ => client.post(url),
+    };
+    let request_file = golden_dir().join(format!("{}.request.json", name));
+    let request = match request_file.exists() {
+        true => request
+            .header("Content-Type", "application/json")
+            .body(read(&request_file)),
+        false => request,
+    };
+    let response = request
+        .header("X-Request-Id", REQUEST_ID)
+        .send()
+        .await
+        .unwrap();
+    response.bytes().await.unwrap().to_vec()
+}
+
+fn golden_key() -> EcdsaVerificationKey {
+    serde_json::from_slice(&read(&golden_dir().join("key.response.json"))).unwrap()
+}
+
+#[tokio::test]
+async fn test_server_answers_match_golden_files() {
+    let mut mismatched = Vec::new();
+    for (name, method, path) in CASES {
+        let body = answer(name, method, path).await;
+        let file = response_file(name);
+        if updating() {
+            fs::write(&file, &body).unwrap();
+        } else if read(&file) != body {
+            mismatched.push(format!(
+                "{}: got\n{}",
+                file.display(),
+                String::from_utf8_lossy(&body)
+            ));
+        }
+    }
+    assert!(
+        mismatched.is_empty(),
+        "the wire format changed (regenerate with VTS_UPDATE_GOLDEN=1 if intended):\n{}",
+        mismatched.join("\n")
+    );
+}
+
+#[test]
+fn test_golden_timestamps_verify() {
+    let key = golden_key();
+    assert_eq!(key.kid.as_deref(), Some(golden_kid().as_str()));
+    let hex_key: EcdsaVerificationKey =
+        serde_json::from_slice(&read(&response_file("key-hex"))).unwrap();
+    for name in ["sign", "sign-der", "sign-b64"] {
+        let signed: EcdsaSignedTimestamp =
+            serde_json::from_slice(&read(&response_file(name))).unwrap();
+        assert_eq!(signed.time_signed, SIGNED_AT, "{}", name);
+        assert!(verify_signature(&signed, &key), "{}", name);
+    }
+    let signed: EcdsaSignedTimestamp =
+        serde_json::from_slice(&read(&response_file("sign-hex"))).unwrap();
+    assert_eq!(signed.format.as_deref(), Some("hex"));
+    assert!(verify_signature(&signed, &hex_key));
+}
+
+fn golden_kid() -> String {
+    jws::key_id(&KeyPair::from_seed(&[7; 32]).to_public_key())
+}
+
+#[test]
+fn test_golden_tokens_verify() {
+    let key = golden_key();
+    let hash: [u8; 32] = Sha256::digest(MESSAGE).into();
+    let signed_at = SIGNED_AT.parse::<DateTime<Utc>>().unwrap().timestamp();
+
+    let token = String::from_utf8(read(&response_file("sign-jws"))).unwrap();
+    let claims = jws::verify(&token, &key).unwrap();
+    assert_eq!(claims.msg_hash, hex::encode(hash));
+    assert_eq!(claims.iat, signed_at);
+    assert_eq!(claims.kid, golden_kid());
+
+    let claims = verify_cose_token(&read(&response_file("sign-cose")), &key).unwrap();
+    assert_eq!(claims.msg_hash, hash);
+    assert_eq!(claims.iat, signed_at);
+}
+
+#[test]
+fn test_golden_errors_parse() {
+    for (name, field) in [
+        ("error-invalid-json", None),
+        ("error-missing-message", Some("message")),
+    ] {
+        let err: ApiError = serde_json::from_slice(&read(&response_file(name))).unwrap();
+        assert_eq!(err.code, "invalid_json", "{}", name);
+        assert_eq!(err.field.as_deref(), field, "{}", name);
+        assert_eq!(err.request_id.as_deref(), Some(REQUEST_ID));
+    }
+}
+
+/// Responses as servers sent them before later fields were added, which
+/// clients must keep reading
+#[test]
+fn test_historical_responses_still_parse() {
+    let dir = golden_dir().join("historical");
+    let mut versions: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .filter_map(|entry| {
+            let name = entry.unwrap().file_name().into_string().unwrap();
+            name.strip_suffix(".key.json").map(str::to_string)
+        })
+        .collect();
+    versions.sort();
+    assert!(!versions.is_empty());
+    for version in versions {
+        let key: EcdsaVerificationKey =
+            serde_json::from_slice(&read(&dir.join(format!("{}.key.json", version)))).unwrap();
+        let signed: EcdsaSignedTimestamp =
+            serde_json::from_slice(&read(&dir.join(format!("{}.sign.json", version)))).unwrap();
+        assert_eq!(signed.message, MESSAGE, "{}", version);
+        assert!(verify_signature(&signed, &key), "{}", version);
+    }
+}
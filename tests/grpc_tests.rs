@@ -0,0 +1,181 @@
+//! gRPC tests: launches the server on an ephemeral port and talks to it with
+//! the generated tonic client, next to the HTTP API on the same port.
+
+use ecdsa_lib::KeyPair;
+use lab4::clock::SystemClock;
+use lab4::config::{KeyStatsConfig, ServerConfig, TenantKeys};
+use lab4::ecdsa_requests::{nonblocking, verify_signature};
+use lab4::server;
+use lab4::server::grpc::proto::vts_client::VtsClient;
+use lab4::server::grpc::proto::{GetKeyRequest, SignTimestampRequest, VerifyProofRequest};
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tokio::time::{Duration, sleep};
+
+static TEST_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// Derives a KeyPair from the test id (so every run uses the same keys) and
+/// returns its raw (private, public) bytes
+fn generate_key_bytes() -> (Vec<u8>, Vec<u8>) {
+    let test_id = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let private_key_file = format!("grpc_test_private_key_{}.bin", test_id);
+    let public_key_file = format!("grpc_test_public_key_{}.bin", test_id);
+
+    let mut seed = [0u8; 32];
+    seed[0] = b'g';
+    seed[1..5].copy_from_slice(&test_id.to_be_bytes());
+    KeyPair::from_seed(&seed)
+        .save_to_files(&private_key_file, &public_key_file)
+        .unwrap();
+    let priv_bytes = fs::read(&private_key_file).unwrap();
+    let pub_bytes = fs::read(&public_key_file).unwrap();
+    let _ = fs::remove_file(&private_key_file);
+    let _ = fs::remove_file(&public_key_file);
+
+    (priv_bytes, pub_bytes)
+}
+
+/// Spawns a server with a default key and one tenant, "course-a"
+async fn spawn_server() -> SocketAddr {
+    let (priv_bytes, pub_bytes) = generate_key_bytes();
+    let mut tenant_keys = TenantKeys::new();
+    tenant_keys.insert("course-a".to_string(), generate_key_bytes());
+    let config = ServerConfig {
+        high_water_file: String::new(),
+        serial_file: String::new(),
+        key_stats: KeyStatsConfig {
+            file: String::new(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        server::run_configured_server_with_listener(
+            priv_bytes,
+            pub_bytes,
+            tenant_keys,
+            config,
+            Box::new(SystemClock),
+            listener,
+        )
+        .await
+        .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+    });
+    sleep(Duration::from_millis(100)).await;
+    addr
+}
+
+#[tokio::test]
+async fn test_grpc_sign_matches_http_key() {
+    let addr = spawn_server().await;
+    let server_url = format!("http://{}", addr);
+    let mut client = VtsClient::connect(server_url.clone()).await.unwrap();
+
+    let key = client
+        .get_key(GetKeyRequest::default())
+        .await
+        .unwrap()
+        .into_inner();
+    let http_key = nonblocking::request_key(&server_url).await.unwrap();
+    assert_eq!(
+        base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &key.public_key),
+        http_key.public_key
+    );
+
+    let signed = client
+        .sign_timestamp(SignTimestampRequest {
+            tenant: String::new(),
+            message: "Over gRPC".to_string(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(signed.serial, 1);
+    assert_eq!(signed.signature.len(), 64);
+
+    // Verifies with the ordinary client check...
+    let as_http: lab4::EcdsaSignedTimestamp = serde_json::from_value(serde_json::json!({
+        "request": "POST",
+        "message": signed.message,
+        "time-signed": signed.time_signed,
+        "signature": base64::Engine::encode(
+            &base64::engine::general_purpose::STANDARD,
+            &signed.signature
+        ),
+    }))
+    .unwrap();
+    assert!(verify_signature(&as_http, &http_key));
+
+    // ...and the serial counter is shared with HTTP
+    nonblocking::request_timestamp(&server_url, "Over HTTP")
+        .await
+        .unwrap();
+    let next = client
+        .sign_timestamp(SignTimestampRequest {
+            tenant: String::new(),
+            message: "Over gRPC again".to_string(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(next.serial, 3);
+}
+
+#[tokio::test]
+async fn test_grpc_verify_proof() {
+    let addr = spawn_server().await;
+    let mut client = VtsClient::connect(format!("http://{}", addr))
+        .await
+        .unwrap();
+
+    let signed = client
+        .sign_timestamp(SignTimestampRequest {
+            tenant: "course-a".to_string(),
+            message: "Prove me".to_string(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let mut proof = VerifyProofRequest {
+        tenant: "course-a".to_string(),
+        message: signed.message.clone(),
+        time_signed: signed.time_signed.clone(),
+        signature: signed.signature.clone(),
+    };
+    let reply = client.verify_proof(proof.clone()).await.unwrap();
+    assert!(reply.into_inner().valid);
+
+    // Wrong tenant key, or altered message
+    proof.tenant = String::new();
+    assert!(
+        !client
+            .verify_proof(proof.clone())
+            .await
+            .unwrap()
+            .into_inner()
+            .valid
+    );
+    proof.tenant = "course-a".to_string();
+    proof.message.push('!');
+    assert!(!client.verify_proof(proof).await.unwrap().into_inner().valid);
+}
+
+#[tokio::test]
+async fn test_grpc_unknown_tenant_is_not_found() {
+    let addr = spawn_server().await;
+    let mut client = VtsClient::connect(format!("http://{}", addr))
+        .await
+        .unwrap();
+
+    let status = client
+        .get_key(GetKeyRequest {
+            tenant: "nobody".to_string(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+}
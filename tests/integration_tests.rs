@@ -1,26 +1,60 @@
 //! Integration tests: launches the server on an ephemeral port and uses the client API.
 
-use ecdsa_lib::KeyPair;
-use lab4::ecdsa_requests::verify_signature;
+use axum::response::IntoResponse;
+use ecdsa_lib::{KeyPair, PublicKey};
+use lab4::alg::DigestAlg;
+use lab4::audit::{
+    AuditEntry, CompactedProof, Cosignature, DayProof, DayRoot, TreeHead, verify_file,
+};
+use lab4::client::{self, ClientOptions, Interceptor, RequestInfo, ResponseInfo, VerifyFailure};
+use lab4::clock::{Clock, FakeClock, SystemClock};
+use lab4::config::{
+    AdminConfig, AnchorConfig, DuplicatePolicy, GossipConfig, GossipPeer, KeyFiles, KeyStatsConfig,
+    KeyValidity, LimitsConfig, MirrorConfig, NtpConfig, PolicyConfig, RevokedKey, ServerConfig,
+    SignCacheConfig, SignPoolConfig, TenantConfig, TenantKeys,
+};
+use lab4::ecdsa_requests::{
+    VerifyOptions, VerifyOutcome, nonblocking, request_email_timestamp, request_file_timestamp,
+    request_file_timestamp_with, request_manifest_timestamp, request_pdf_timestamp,
+    verify_cose_token, verify_file_timestamp, verify_signature, verify_signature_with,
+    verify_signatures_batch,
+};
+use lab4::email;
+use lab4::http_sig::{self, SignatureError};
+use lab4::jws;
+use lab4::manifest::Manifest;
+use lab4::monitor::{Alert, Monitor};
+use lab4::multi::{self, MultiTimestamp};
+use lab4::pdf::{self, PdfError};
+use lab4::revocation::RevocationError;
 use lab4::server;
-use reqwest;
-use serde_json;
+use lab4::signer::{Signer, SignerError, TenantSigners};
+use lab4::transparency::{self, AnchorProof, Check, InclusionProof, LogError};
+use lab4::wire::{Normalization, TimePrecision};
+use lab4::x509;
+use lab4::{ApiError, EcdsaVerificationKey};
+use sha2::{Digest, Sha256};
 use std::fs;
 use std::net::SocketAddr;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use tokio::task;
 use tokio::time::{Duration, sleep};
 
 static TEST_COUNTER: AtomicU32 = AtomicU32::new(0);
 
-async fn spawn_server() -> SocketAddr {
+/// Derives a KeyPair from the test id (so every run uses the same keys) and
+/// returns its raw (private, public) bytes
+fn generate_key_bytes() -> (Vec<u8>, Vec<u8>) {
     // Generate unique filenames for this test instance
     let test_id = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
     let private_key_file = format!("test_private_key_{}.bin", test_id);
     let public_key_file = format!("test_public_key_{}.bin", test_id);
 
-    // 1) Generate a fresh KeyPair and write to .bin files
-    let keypair = KeyPair::generate();
+    // 1) Derive the KeyPair and write to .bin files
+    let mut seed = [0u8; 32];
+    seed[..4].copy_from_slice(&test_id.to_be_bytes());
+    let keypair = KeyPair::from_seed(&seed);
     keypair
         .save_to_files(&private_key_file, &public_key_file)
         .unwrap();
@@ -33,15 +67,64 @@ async fn spawn_server() -> SocketAddr {
     let _ = fs::remove_file(&private_key_file);
     let _ = fs::remove_file(&public_key_file);
 
+    (priv_bytes, pub_bytes)
+}
+
+async fn spawn_server() -> SocketAddr {
+    spawn_tenant_server(&[]).await
+}
+
+async fn spawn_tenant_server(tenant_names: &[&str]) -> SocketAddr {
+    spawn_configured_server(tenant_names, test_config()).await
+}
+
+/// Default config, minus the on-disk time high-water mark and key stats
+/// (keeps tests hermetic)
+fn test_config() -> ServerConfig {
+    ServerConfig {
+        high_water_file: String::new(),
+        serial_file: String::new(),
+        key_stats: KeyStatsConfig {
+            file: String::new(),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+async fn spawn_configured_server(tenant_names: &[&str], config: ServerConfig) -> SocketAddr {
+    spawn_server_with_clock(tenant_names, config, Box::new(SystemClock)).await
+}
+
+/// Spawns a server with `config` and `clock`, a fresh default key plus one
+/// fresh key per tenant name
+async fn spawn_server_with_clock(
+    tenant_names: &[&str],
+    config: ServerConfig,
+    clock: Box<dyn Clock>,
+) -> SocketAddr {
+    let (priv_bytes, pub_bytes) = generate_key_bytes();
+    let tenant_keys: TenantKeys = tenant_names
+        .iter()
+        .map(|name| (name.to_string(), generate_key_bytes()))
+        .collect();
+
     // 4) Bind to an ephemeral port (0)
     let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
 
     // 5) Spawn the server with those raw key bytes and the listener
     task::spawn(async move {
-        server::run_server_with_listener(priv_bytes, pub_bytes, listener)
-            .await
-            .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+        server::run_configured_server_with_listener(
+            priv_bytes,
+            pub_bytes,
+            tenant_keys,
+            config,
+            clock,
+            listener,
+        )
+        .await
+        .unwrap_or_else(|e| eprintln!("Server error: {}", e));
     });
 
     // 6) Give the server a moment to start up
@@ -57,7 +140,7 @@ async fn test_get_key_and_structure() {
     // Use async reqwest instead of blocking client
     let client = reqwest::Client::new();
     let resp = client
-        .get(&format!("{}/key", server_url))
+        .get(format!("{}/key", server_url))
         .send()
         .await
         .unwrap();
@@ -82,7 +165,7 @@ async fn test_post_sign_and_verify() {
 
     // Get key
     let resp = client
-        .get(&format!("{}/key", server_url))
+        .get(format!("{}/key", server_url))
         .send()
         .await
         .unwrap();
@@ -91,7 +174,7 @@ async fn test_post_sign_and_verify() {
     // Post sign request
     let body = serde_json::json!({ "message": "Integration test!" });
     let resp = client
-        .post(&format!("{}/sign", server_url))
+        .post(format!("{}/sign", server_url))
         .json(&body)
         .send()
         .await
@@ -112,10 +195,5211 @@ async fn test_post_sign_and_verify() {
 }
 
 #[tokio::test]
-async fn test_invalid_route_returns_bad_request() {
+async fn test_nonblocking_client_round_trip() {
     let addr = spawn_server().await;
+    let server_url = format!("http://{}", addr);
+
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    let signed = nonblocking::request_timestamp(&server_url, "Async client!")
+        .await
+        .unwrap();
+
+    assert_eq!(signed.message, "Async client!");
+    assert!(verify_signature(&signed, &key));
+}
+
+#[tokio::test]
+async fn test_tenants_have_separate_keys() {
+    let addr = spawn_tenant_server(&["course-a", "course-b"]).await;
+    let server_url = format!("http://{}", addr);
+
+    let default_key = nonblocking::request_key(&server_url).await.unwrap();
+    let key_a = nonblocking::request_key_for_tenant(&server_url, "course-a")
+        .await
+        .unwrap();
+    let key_b = nonblocking::request_key_for_tenant(&server_url, "course-b")
+        .await
+        .unwrap();
+    assert_ne!(key_a.public_key, default_key.public_key);
+    assert_ne!(key_a.public_key, key_b.public_key);
+
+    let signed = nonblocking::request_timestamp_for_tenant(&server_url, "course-a", "Tenant A")
+        .await
+        .unwrap();
+    assert!(verify_signature(&signed, &key_a));
+    assert!(!verify_signature(&signed, &key_b));
+    assert!(!verify_signature(&signed, &default_key));
+}
+
+#[tokio::test]
+async fn test_tenant_selected_by_header() {
+    let addr = spawn_tenant_server(&["course-a"]).await;
+    let server_url = format!("http://{}", addr);
     let client = reqwest::Client::new();
-    let url = format!("http://{}/nonexistent", addr);
-    let resp = client.get(&url).send().await.unwrap();
-    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let key_a = nonblocking::request_key_for_tenant(&server_url, "course-a")
+        .await
+        .unwrap();
+    let via_header: lab4::EcdsaVerificationKey = client
+        .get(format!("{}/key", server_url))
+        .header(server::TENANT_HEADER, "course-a")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(via_header.public_key, key_a.public_key);
+}
+
+#[tokio::test]
+async fn test_signed_time_echoes_the_nonce() {
+    let addr = spawn_tenant_server(&["acme"]).await;
+    let server_url = format!("http://{}", addr);
+    let key = nonblocking::request_key(&server_url)
+        .await
+        .unwrap()
+        .key()
+        .unwrap();
+
+    // 1) The server clock, signed together with the nonce
+    let before = chrono::Utc::now() - chrono::Duration::seconds(1);
+    let signed = nonblocking::request_time(&server_url, "n-0001")
+        .await
+        .unwrap();
+    assert_eq!(signed.request, "TIME");
+    assert_eq!(signed.nonce, "n-0001");
+    assert!(signed.verify(&key, "n-0001"));
+    assert!(!signed.verify(&key, "n-0002"));
+    let time = signed.time().unwrap();
+    assert!(time >= before && time <= chrono::Utc::now());
+
+    // 2) A changed time doesn't verify
+    let mut tampered = signed.clone();
+    tampered.time = lab4::wire::canonical_time(time + chrono::Duration::seconds(60));
+    assert!(!tampered.verify(&key, "n-0001"));
+
+    // 3) A tenant's time is signed with its own key
+    let acme = nonblocking::request_key_for_tenant(&server_url, "acme")
+        .await
+        .unwrap()
+        .key()
+        .unwrap();
+    let client = client::nonblocking::VtsClient::new(&format!("{}/t/acme", server_url)).unwrap();
+    let signed = client.request_time("n-0003").await.unwrap();
+    assert!(signed.verify(&acme, "n-0003"));
+    assert!(!signed.verify(&key, "n-0003"));
+
+    // 4) The blocking client gets the same
+    let url = server_url.clone();
+    let signed = task::spawn_blocking(move || {
+        client::VtsClient::new(&url)
+            .unwrap()
+            .request_time("n-0004")
+            .unwrap()
+    })
+    .await
+    .unwrap();
+    assert!(signed.verify(&key, "n-0004"));
+
+    // 5) No nonce, or one with spaces, is refused
+    let http = reqwest::Client::new();
+    for query in ["", "?nonce=", "?nonce=a%20b"] {
+        let resp = http
+            .get(format!("{}/v1/time{}", server_url, query))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST, "{}", query);
+        let err: ApiError = resp.json().await.unwrap();
+        assert_eq!(err.code, "invalid_nonce");
+    }
+}
+
+#[tokio::test]
+async fn test_unknown_tenant_returns_not_found() {
+    let addr = spawn_tenant_server(&["course-a"]).await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .get(format!("http://{}/t/nobody/key", addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let resp = client
+        .post(format!("http://{}/sign", addr))
+        .header(server::TENANT_HEADER, "nobody")
+        .json(&serde_json::json!({ "message": "hi" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+/// POSTs `message` to /sign with an optional Idempotency-Key, returning status and body
+async fn post_sign(
+    addr: SocketAddr,
+    message: &str,
+    idempotency_key: Option<&str>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    let mut req = reqwest::Client::new()
+        .post(format!("http://{}/sign", addr))
+        .json(&serde_json::json!({ "message": message }));
+    if let Some(key) = idempotency_key {
+        req = req.header(server::IDEMPOTENCY_KEY_HEADER, key);
+    }
+    let resp = req.send().await.unwrap();
+    (resp.status(), resp.json().await.unwrap())
+}
+
+fn sign_cache_config(by_message_hash: bool) -> ServerConfig {
+    ServerConfig {
+        sign_cache: SignCacheConfig {
+            window_secs: 60,
+            by_message_hash,
+        },
+        ..test_config()
+    }
+}
+
+#[tokio::test]
+async fn test_idempotency_key_returns_cached_timestamp() {
+    let addr = spawn_configured_server(&[], sign_cache_config(false)).await;
+
+    let (_, first) = post_sign(addr, "Retry me", Some("req-1")).await;
+    sleep(Duration::from_millis(5)).await;
+    let (status, again) = post_sign(addr, "Retry me", Some("req-1")).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(first, again);
+
+    // Without the key (and without by_message_hash) a new timestamp is issued
+    let (_, fresh) = post_sign(addr, "Retry me", None).await;
+    assert_ne!(first["time-signed"], fresh["time-signed"]);
+
+    // Same key, different message → 422
+    let (status, _) = post_sign(addr, "Something else", Some("req-1")).await;
+    assert_eq!(status, reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_identical_messages_reuse_timestamp_when_enabled() {
+    let addr = spawn_configured_server(&[], sign_cache_config(true)).await;
+
+    let (_, first) = post_sign(addr, "Same hash", None).await;
+    sleep(Duration::from_millis(5)).await;
+    let (_, again) = post_sign(addr, "Same hash", None).await;
+    assert_eq!(first, again);
+
+    let (_, other) = post_sign(addr, "Different hash", None).await;
+    assert_ne!(first["signature"], other["signature"]);
+}
+
+#[tokio::test]
+async fn test_sign_cache_disabled_by_default() {
+    let addr = spawn_server().await;
+
+    let (_, first) = post_sign(addr, "No cache", Some("req-1")).await;
+    sleep(Duration::from_millis(5)).await;
+    let (_, again) = post_sign(addr, "No cache", Some("req-1")).await;
+    assert_ne!(first["time-signed"], again["time-signed"]);
+}
+
+#[tokio::test]
+async fn test_time_signed_comes_from_injected_clock() {
+    let start = "2030-01-01T00:00:00.123456Z"
+        .parse::<chrono::DateTime<chrono::Utc>>()
+        .unwrap();
+    let clock = Arc::new(FakeClock::new(start));
+    let addr = spawn_server_with_clock(&[], test_config(), Box::new(clock.clone())).await;
+
+    let (_, first) = post_sign(addr, "Fake time", None).await;
+    assert_eq!(first["time-signed"], "2030-01-01T00:00:00.123456Z");
+
+    // The clock jumps back an hour: issued times still move forward
+    clock.set(start - chrono::Duration::hours(1));
+    let (_, second) = post_sign(addr, "Fake time", None).await;
+    assert_eq!(second["time-signed"], "2030-01-01T00:00:00.123457Z");
+}
+
+#[tokio::test]
+async fn test_sign_preview_matches_signed_bytes() {
+    use base64::{Engine as _, engine::general_purpose};
+
+    let start = "2030-01-01T00:00:00.123456789Z"
+        .parse::<chrono::DateTime<chrono::Utc>>()
+        .unwrap();
+    let clock = Arc::new(FakeClock::new(start));
+    let addr = spawn_server_with_clock(&[], test_config(), Box::new(clock)).await;
+    let key = nonblocking::request_key(&format!("http://{}", addr))
+        .await
+        .unwrap()
+        .key()
+        .unwrap();
+    let client = reqwest::Client::new();
+    let post = |path: &str| {
+        let request = client
+            .post(format!("http://{}{}", addr, path))
+            .json(&serde_json::json!({ "message": "Preview me" }));
+        async move { request.send().await.unwrap() }
+    };
+
+    // JSON: `message + time-signed`, and previewing takes nothing away
+    let preview: serde_json::Value = post("/sign/preview").await.json().await.unwrap();
+    assert_eq!(preview["request"], "PREVIEW");
+    assert_eq!(preview["token"], "json");
+    assert_eq!(preview["time-signed"], "2030-01-01T00:00:00.123456Z");
+    assert_eq!(
+        preview["signed-text"],
+        "Preview me2030-01-01T00:00:00.123456Z"
+    );
+    assert!(preview.get("signature").is_none());
+    let again: serde_json::Value = post("/sign/preview").await.json().await.unwrap();
+    assert_eq!(again, preview);
+
+    // JWS: the signing input of the token `/sign` then issues
+    let preview: serde_json::Value = post("/sign/preview?format=jws").await.json().await.unwrap();
+    let token = post("/sign?format=jws").await.text().await.unwrap();
+    let (input, _) = token.rsplit_once('.').unwrap();
+    assert_eq!(preview["signed-text"], input);
+    let signed = general_purpose::STANDARD
+        .decode(preview["signed-data"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(signed, input.as_bytes());
+
+    // COSE: the issued signature (the token's last 64 bytes) is over the preview
+    let preview: serde_json::Value = post("/sign/preview?format=cose")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert!(preview.get("signed-text").is_none());
+    let signed = general_purpose::STANDARD
+        .decode(preview["signed-data"].as_str().unwrap())
+        .unwrap();
+    let token = post("/sign?format=cose").await.bytes().await.unwrap();
+    assert_eq!(
+        key.verify_encoded(&signed, &token[token.len() - 64..]),
+        ecdsa_lib::VerifyOutcome::Ok
+    );
+}
+
+/// Answers NTP client requests with the local time shifted by `offset`
+async fn spawn_fake_ntp(offset: chrono::Duration) -> String {
+    let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = socket.local_addr().unwrap().to_string();
+    task::spawn(async move {
+        let mut buf = [0u8; 48];
+        while let Ok((_, peer)) = socket.recv_from(&mut buf).await {
+            let now = chrono::Utc::now() + offset;
+            let secs = (now.timestamp() + 2_208_988_800) as u32;
+            let frac = (((now.timestamp_subsec_nanos() as u64) << 32) / 1_000_000_000) as u32;
+            let mut ts = [0u8; 8];
+            ts[..4].copy_from_slice(&secs.to_be_bytes());
+            ts[4..].copy_from_slice(&frac.to_be_bytes());
+
+            let mut reply = [0u8; 48];
+            reply[0] = 0x24; // VN = 4, Mode = 4 (server)
+            reply[1] = 1; // stratum 1
+            reply[24..32].copy_from_slice(&buf[40..48]); // originate = client transmit
+            reply[32..40].copy_from_slice(&ts); // receive
+            reply[40..48].copy_from_slice(&ts); // transmit
+            let _ = socket.send_to(&reply, peer).await;
+        }
+    });
+    addr
+}
+
+fn ntp_config(server: String) -> ServerConfig {
+    ServerConfig {
+        ntp: NtpConfig {
+            servers: vec![server],
+            interval_secs: 1,
+            max_drift_ms: 500,
+        },
+        ..test_config()
+    }
+}
+
+#[tokio::test]
+async fn test_sign_reports_accuracy_when_ntp_agrees() {
+    let ntp = spawn_fake_ntp(chrono::Duration::zero()).await;
+    let addr = spawn_configured_server(&[], ntp_config(ntp)).await;
+    sleep(Duration::from_millis(200)).await;
+
+    let (status, body) = post_sign(addr, "Accurate", None).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    let accuracy = body["accuracy"].as_str().unwrap();
+    assert!(
+        accuracy.starts_with('±') && accuracy.ends_with("ms"),
+        "{}",
+        accuracy
+    );
+
+    // The accuracy field doesn't affect verification
+    let key = nonblocking::request_key(&format!("http://{}", addr))
+        .await
+        .unwrap();
+    let signed: lab4::EcdsaSignedTimestamp = serde_json::from_value(body).unwrap();
+    assert!(verify_signature(&signed, &key));
+}
+
+#[tokio::test]
+async fn test_sign_refused_when_clock_drifts() {
+    let ntp = spawn_fake_ntp(chrono::Duration::seconds(10)).await;
+    let addr = spawn_configured_server(&[], ntp_config(ntp)).await;
+    sleep(Duration::from_millis(200)).await;
+
+    let (status, body) = post_sign(addr, "Too late", None).await;
+    assert_eq!(status, reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"], "Clock drift too large");
+}
+
+#[tokio::test]
+async fn test_accuracy_omitted_without_ntp() {
+    let addr = spawn_server().await;
+    let (_, body) = post_sign(addr, "No NTP", None).await;
+    assert!(body.get("accuracy").is_none());
+}
+
+/// POSTs `message` to /sign with the given signature `encoding`
+async fn post_sign_encoded(
+    addr: SocketAddr,
+    message: &str,
+    encoding: &str,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    let resp = reqwest::Client::new()
+        .post(format!("http://{}/sign", addr))
+        .json(&serde_json::json!({ "message": message, "encoding": encoding }))
+        .send()
+        .await
+        .unwrap();
+    let status = resp.status();
+    (status, resp.json().await.unwrap_or_default())
+}
+
+/// The server's own serialization of awkward messages verifies after the
+/// client reads it back (`tests/roundtrip_tests.rs` covers many more offline)
+#[tokio::test]
+async fn test_awkward_messages_round_trip() {
+    let server_url = format!("http://{}", spawn_server().await);
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    for message in [
+        String::new(),
+        "\0\u{1}\u{1f}\u{7f} \r\n\t".to_string(),
+        r#""quoted" \ back\slash /slash"#.to_string(),
+        "日本語 🦀 e\u{301} \u{202e}rtl \u{feff}\u{10ffff}".to_string(),
+        "é".repeat(200_000),
+    ] {
+        let signed = nonblocking::request_timestamp(&server_url, &message)
+            .await
+            .unwrap();
+        assert_eq!(signed.message, message);
+        assert!(verify_signature(&signed, &key));
+    }
+}
+
+#[tokio::test]
+async fn test_sign_with_der_and_compact_encodings() {
+    use base64::{Engine as _, engine::general_purpose};
+
+    let addr = spawn_server().await;
+    let key = nonblocking::request_key(&format!("http://{}", addr))
+        .await
+        .unwrap();
+
+    // DER: tagged in the response, starts with SEQUENCE, still verifies
+    let (status, body) = post_sign_encoded(addr, "DER please", "der").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(body["encoding"], "der");
+    let der = general_purpose::STANDARD
+        .decode(body["signature"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(der[0], 0x30);
+    assert!(verify_signature(
+        &serde_json::from_value(body).unwrap(),
+        &key
+    ));
+
+    // "compact" is an alias for the default raw r || s
+    let (status, body) = post_sign_encoded(addr, "Compact please", "compact").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert!(body.get("encoding").is_none());
+    let raw = general_purpose::STANDARD
+        .decode(body["signature"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(raw.len(), 64);
+    assert!(verify_signature(
+        &serde_json::from_value(body).unwrap(),
+        &key
+    ));
+
+    let (status, _) = post_sign_encoded(addr, "PEM please", "pem").await;
+    assert!(status.is_client_error());
+}
+
+#[tokio::test]
+async fn test_sign_returns_jws_when_negotiated() {
+    let addr = spawn_server().await;
+    let server_url = format!("http://{}", addr);
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    let client = reqwest::Client::new();
+    let body = serde_json::json!({ "message": "JWS please" });
+
+    // Via the Accept header
+    let resp = client
+        .post(format!("{}/sign", server_url))
+        .header("Accept", "application/jose")
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], jws::CONTENT_TYPE);
+    let token = resp.text().await.unwrap();
+
+    let claims = jws::verify(&token, &key).expect("token should verify");
+    assert_eq!(
+        claims.msg_hash,
+        hex::encode(Sha256::digest("JWS please".as_bytes()))
+    );
+    assert_eq!(claims.serial, 1);
+    assert_eq!(claims.kid, jws::key_id(&key.key().unwrap()));
+    assert!((chrono::Utc::now().timestamp() - claims.iat).abs() < 60);
+
+    // Via the query parameter
+    let token = client
+        .post(format!("{}/sign?format=jws", server_url))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(jws::verify(&token, &key).unwrap().serial, 2);
+
+    // Tampered claims or another key are rejected
+    let parts: Vec<&str> = token.split('.').collect();
+    let forged = format!("{}.{}.{}", parts[0], parts[0], parts[2]);
+    assert!(jws::verify(&forged, &key).is_none());
+    let other = nonblocking::request_key(&format!("http://{}", spawn_server().await))
+        .await
+        .unwrap();
+    assert!(jws::verify(&token, &other).is_none());
+
+    // Plain requests still get JSON
+    let (_, json) = post_sign(addr, "JSON please", None).await;
+    assert!(json["signature"].is_string());
+}
+
+#[tokio::test]
+async fn test_sign_returns_cose_when_negotiated() {
+    let addr = spawn_server().await;
+    let server_url = format!("http://{}", addr);
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+
+    let token = nonblocking::request_timestamp_cose(&server_url, "COSE please")
+        .await
+        .unwrap();
+    assert_eq!(token[0], 0xd2); // CBOR tag 18 (COSE_Sign1)
+    let claims = verify_cose_token(&token, &key).expect("token should verify");
+    assert_eq!(
+        claims.msg_hash,
+        <[u8; 32]>::from(Sha256::digest("COSE please".as_bytes()))
+    );
+    assert_eq!(claims.serial, 1);
+    assert!((chrono::Utc::now().timestamp() - claims.iat).abs() < 60);
+
+    // Any flipped byte breaks it
+    let mut forged = token.clone();
+    let last = forged.len() - 1;
+    forged[last] ^= 1;
+    assert!(verify_cose_token(&forged, &key).is_none());
+    let other = nonblocking::request_key(&format!("http://{}", spawn_server().await))
+        .await
+        .unwrap();
+    assert!(verify_cose_token(&token, &other).is_none());
+
+    // Also selectable by query parameter, with the COSE media type
+    let resp = reqwest::Client::new()
+        .post(format!("{}/sign?format=cose", server_url))
+        .json(&serde_json::json!({ "message": "COSE please" }))
+        .send()
+        .await
+        .unwrap();
+    assert!(
+        resp.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("application/cose")
+    );
+    let token = resp.bytes().await.unwrap();
+    assert_eq!(verify_cose_token(&token, &key).unwrap().serial, 2);
+}
+
+#[tokio::test]
+async fn test_file_timestamp_sends_only_the_hash() {
+    let addr = spawn_server().await;
+    let server_url = format!("http://{}", addr);
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    let contents: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+
+    let (signed, contents) = task::spawn_blocking(move || {
+        let signed = request_file_timestamp(&server_url, contents.as_slice()).unwrap();
+        (signed, contents)
+    })
+    .await
+    .unwrap();
+    assert_eq!(signed.message, hex::encode(Sha256::digest(&contents)));
+    assert!(verify_file_timestamp(contents.as_slice(), &signed, &key).unwrap());
+
+    let mut altered = contents.clone();
+    altered[100_000] ^= 1;
+    assert!(!verify_file_timestamp(altered.as_slice(), &signed, &key).unwrap());
+}
+
+#[tokio::test]
+async fn test_file_timestamp_with_digest_algs() {
+    let config = ServerConfig {
+        digest_algs: vec![DigestAlg::Sha256, DigestAlg::Sha384, DigestAlg::Blake3],
+        ..test_config()
+    };
+    let server_url = format!("http://{}", spawn_configured_server(&[], config).await);
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    let contents: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+
+    // The algorithm is part of the signed message
+    let url = server_url.clone();
+    let (signed, contents) = task::spawn_blocking(move || {
+        let signed =
+            request_file_timestamp_with(&url, contents.as_slice(), DigestAlg::Sha384).unwrap();
+        (signed, contents)
+    })
+    .await
+    .unwrap();
+    assert_eq!(
+        signed.message,
+        format!("sha-384:{}", hex::encode(sha2::Sha384::digest(&contents)))
+    );
+    assert!(verify_file_timestamp(contents.as_slice(), &signed, &key).unwrap());
+    let mut altered = contents.clone();
+    altered[100_000] ^= 1;
+    assert!(!verify_file_timestamp(altered.as_slice(), &signed, &key).unwrap());
+
+    // BLAKE3 hashing is the client's, behind its feature
+    let url = server_url.clone();
+    let blake3 = task::spawn_blocking(move || {
+        request_file_timestamp_with(&url, &b"file contents"[..], DigestAlg::Blake3)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap();
+    if cfg!(feature = "blake3") {
+        let signed = blake3.unwrap();
+        assert!(signed.message.starts_with("blake3:"));
+        assert!(verify_file_timestamp(&b"file contents"[..], &signed, &key).unwrap());
+    } else {
+        assert!(blake3.unwrap_err().contains("blake3 feature"));
+    }
+
+    // Only the allowed algorithms, and only digests of their length
+    let post = |body: serde_json::Value| {
+        let request = reqwest::Client::new()
+            .post(format!("{}/sign", server_url))
+            .json(&body);
+        async move {
+            let resp = request.send().await.unwrap();
+            (
+                resp.status(),
+                resp.json::<serde_json::Value>().await.unwrap(),
+            )
+        }
+    };
+    let (status, body) =
+        post(serde_json::json!({ "digest": "00".repeat(64), "digest-alg": "sha-512" })).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "unsupported_algorithm");
+    assert_eq!(body["field"], "digest-alg");
+    let (status, body) = post(serde_json::json!({ "digest": "00".repeat(31) })).await;
+    assert_eq!(status, reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["field"], "digest");
+    let (status, _) =
+        post(serde_json::json!({ "message": "both", "digest": "00".repeat(32) })).await;
+    assert_eq!(status, reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_manifest_timestamp_covers_each_file() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+    fs::write(dir.path().join("README.md"), "# Submission").unwrap();
+    fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+    fs::write(dir.path().join("src/nested/data.bin"), [0u8, 1, 2]).unwrap();
+    let manifest = Manifest::from_dir(dir.path()).unwrap();
+    let paths: Vec<&str> = manifest.files.keys().map(String::as_str).collect();
+    assert_eq!(paths, ["README.md", "src/main.rs", "src/nested/data.bin"]);
+
+    let server_url = format!("http://{}", spawn_server().await);
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    let url = server_url.clone();
+    let sent = manifest.clone();
+    let signed = task::spawn_blocking(move || request_manifest_timestamp(&url, &sent).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(signed.message, manifest.message());
+    assert!(manifest.verify(&signed, &key));
+
+    // Kept as JSON, the manifest proves any one file without the others
+    let kept: Manifest = serde_json::from_str(&serde_json::to_string(&manifest).unwrap()).unwrap();
+    assert!(
+        kept.verify_file("src/main.rs", &b"fn main() {}"[..], &signed, &key)
+            .unwrap()
+    );
+    assert!(
+        !kept
+            .verify_file("src/main.rs", &b"fn main() { evil() }"[..], &signed, &key)
+            .unwrap()
+    );
+    assert!(
+        !kept
+            .verify_file("src/other.rs", &b"fn main() {}"[..], &signed, &key)
+            .unwrap()
+    );
+
+    // A file added later is in another manifest, not this timestamp
+    fs::write(dir.path().join("late.txt"), "after the deadline").unwrap();
+    let later = Manifest::from_dir(dir.path()).unwrap();
+    assert!(!later.verify(&signed, &key));
+    let client = client::nonblocking::VtsClient::new(&server_url).unwrap();
+    let signed = client.request_manifest_timestamp(&later).await.unwrap();
+    assert!(later.verify(&signed, &key));
+}
+
+#[tokio::test]
+async fn test_multi_authority_timestamp_needs_k_of_n() {
+    let mut servers = Vec::new();
+    for _ in 0..3 {
+        servers.push(format!("http://{}", spawn_server().await));
+    }
+    let key = |url: String| async move { nonblocking::request_key(&url).await.unwrap() };
+    let mut keys = Vec::new();
+    for url in &servers {
+        keys.push(key(url.clone()).await);
+    }
+    let digest: [u8; 32] = Sha256::digest(b"contract").into();
+    let clients: Vec<client::nonblocking::VtsClient> = servers
+        .iter()
+        .map(|url| client::nonblocking::VtsClient::new(url).unwrap())
+        .collect();
+    let multi = multi::nonblocking::request(&clients, &digest, 3)
+        .await
+        .unwrap();
+    assert_eq!(multi.timestamps.len(), 3);
+    assert_eq!(multi.timestamps[1].server, servers[1]);
+
+    // k of the n keys, each a distinct authority
+    let multi = MultiTimestamp::from_token(&multi.to_token()).unwrap();
+    assert!(multi::verify_multi(&multi, &digest, &keys, 3));
+    let stranger = format!("http://{}", spawn_server().await);
+    let some = [
+        key(servers[0].clone()).await,
+        key(servers[1].clone()).await,
+        key(stranger).await,
+    ];
+    assert!(multi::verify_multi(&multi, &digest, &some, 2));
+    assert!(!multi::verify_multi(&multi, &digest, &some, 3));
+    let same = [key(servers[0].clone()).await, key(servers[0].clone()).await];
+    assert!(!multi::verify_multi(&multi, &digest, &same, 2));
+    let other: [u8; 32] = Sha256::digest(b"another contract").into();
+    assert!(!multi::verify_multi(&multi, &other, &keys, 1));
+
+    // An unreachable server is left out, unless it is needed
+    let mut with_down = clients.clone();
+    with_down.push(client::nonblocking::VtsClient::new("http://127.0.0.1:9").unwrap());
+    let multi = multi::nonblocking::request(&with_down, &digest, 3)
+        .await
+        .unwrap();
+    assert!(multi::verify_multi(&multi, &digest, &keys, 3));
+    let err = multi::nonblocking::request(&with_down, &digest, 4)
+        .await
+        .unwrap_err();
+    assert_eq!((err.answered, err.failures.len()), (3, 1));
+    assert_eq!(err.failures[0].0, "http://127.0.0.1:9");
+
+    // Blocking, one server after the other
+    let multi = task::spawn_blocking(move || {
+        let clients: Vec<client::VtsClient> = servers
+            .iter()
+            .map(|url| client::VtsClient::new(url).unwrap())
+            .collect();
+        multi::request(&clients, &digest, 2).unwrap()
+    })
+    .await
+    .unwrap();
+    assert!(multi::verify_multi(&multi, &digest, &keys, 3));
+}
+
+#[tokio::test]
+async fn test_email_timestamp_survives_transport() {
+    let mail = b"From: Alice <alice@example.com>\r\n\
+To: bob@example.com\r\n\
+Subject: Quarterly\r\n report\r\n\
+Date: Mon, 2 Jun 2025 09:00:00 +0000\r\n\
+Message-ID: <1234@example.com>\r\n\
+\r\n\
+Numbers attached.\r\n";
+    let canonical = email::canonicalize(mail).unwrap();
+    assert!(
+        canonical.starts_with("vts-email\nfrom:Alice <alice@example.com>\nto:bob@example.com\n")
+    );
+    assert!(canonical.contains("\nsubject:Quarterly report\n"));
+
+    let server_url = format!("http://{}", spawn_server().await);
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    let url = server_url.clone();
+    let signed = task::spawn_blocking(move || request_email_timestamp(&url, mail).unwrap())
+        .await
+        .unwrap();
+    assert!(signed.message.starts_with("sha-256:"));
+    assert!(email::verify(mail, &signed, &key).unwrap());
+
+    // Relayed: LF endings, an added Received header, refolded, blank lines appended
+    let relayed = b"Received: from mx.example.com\n\
+from: Alice <alice@example.com>\n\
+To: bob@example.com\n\
+Subject: Quarterly report\n\
+Date: Mon, 2 Jun 2025\n 09:00:00 +0000\n\
+Message-ID: <1234@example.com>\n\
+\n\
+Numbers attached.\n\n\n";
+    assert!(email::verify(relayed, &signed, &key).unwrap());
+    let edited = b"From: Alice <alice@example.com>\r\n\r\nNumbers attached, revised.\r\n";
+    assert!(!email::verify(edited, &signed, &key).unwrap());
+    assert_eq!(
+        email::canonicalize(b"To: bob@example.com\r\n\r\nHi\r\n"),
+        Err(email::EmailError::NoFrom)
+    );
+
+    // Carried as an attachment and read back
+    let part = email::to_mime_part(&signed);
+    assert!(part.starts_with(&format!("Content-Type: {}", email::CONTENT_TYPE)));
+    assert!(part.lines().all(|line| line.len() <= 76));
+    assert_eq!(
+        email::from_mime_part(&part).map(|read| read.to_token()),
+        Some(signed.to_token())
+    );
+    assert!(email::from_mime_part("Content-Type: text/plain\r\n\r\nhi").is_none());
+
+    let client = client::nonblocking::VtsClient::new(&server_url).unwrap();
+    let signed = client.request_email_timestamp(relayed).await.unwrap();
+    assert!(email::verify(mail, &signed, &key).unwrap());
+}
+
+/// A PDF prepared for signing: `/ByteRange` leaves out the hex `<...>` of
+/// `/Contents`
+fn pdf_with_placeholder(text: &str) -> Vec<u8> {
+    let head = format!("%PDF-1.7\n1 0 obj\n<< /Text ({}) >>\nendobj\n", text);
+    let placeholder = format!("<{}>", "0".repeat(64));
+    // The numbers are padded so writing them in doesn't move anything
+    let sig = "2 0 obj\n<< /Type /Sig /ByteRange [0000000000 0000000000 0000000000 0000000000] /Contents ";
+    let tail = " >>\nendobj\n%%EOF\n";
+    let start = head.len() + sig.len();
+    let end = start + placeholder.len();
+    let total = end + tail.len();
+    let range = format!("[{:010} {:010} {:010} {:010}]", 0, start, end, total - end);
+    let sig = sig.replace("[0000000000 0000000000 0000000000 0000000000]", &range);
+    format!("{}{}{}{}", head, sig, placeholder, tail).into_bytes()
+}
+
+#[tokio::test]
+async fn test_pdf_timestamp_covers_byte_range() {
+    let doc = pdf_with_placeholder("Contract");
+    assert_eq!(pdf::byte_range(&doc).unwrap().unwrap()[0], 0);
+
+    let server_url = format!("http://{}", spawn_server().await);
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    let url = server_url.clone();
+    let sent = doc.clone();
+    let proof = task::spawn_blocking(move || request_pdf_timestamp(&url, &sent).unwrap())
+        .await
+        .unwrap();
+    assert!(proof.verify(&doc, &key).unwrap());
+
+    // Saved beside the document and read back
+    let dir = tempfile::tempdir().unwrap();
+    let path = pdf::proof_path(dir.path().join("contract.pdf"));
+    assert!(path.ends_with("contract.pdf.vts.json"));
+    fs::write(&path, serde_json::to_vec(&proof).unwrap()).unwrap();
+    let kept: pdf::Proof = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+    assert!(kept.verify(&doc, &key).unwrap());
+
+    // A signature written into the placeholder is outside the range
+    let mut signed_doc = doc.clone();
+    let [_, start, end, _] = proof.byte_range.unwrap();
+    signed_doc[start + 1..end - 1].fill(b'a');
+    assert!(kept.verify(&signed_doc, &key).unwrap());
+    // ... but an edit to the content isn't
+    let edited = pdf_with_placeholder("Contrakt");
+    assert!(!kept.verify(&edited, &key).unwrap());
+    assert!(pdf::Proof::new(&edited, kept.timestamp).is_err());
+
+    assert_eq!(pdf::digest(b"not a pdf"), Err(PdfError::NotPdf));
+    assert_eq!(
+        pdf::digest(b"%PDF-1.7 /ByteRange [0 10 20 99999]"),
+        Err(PdfError::BadByteRange)
+    );
+
+    // Without a /ByteRange the whole file counts
+    let plain = b"%PDF-1.4\n%%EOF\n";
+    let client = client::nonblocking::VtsClient::new(&server_url).unwrap();
+    let proof = client.request_pdf_timestamp(plain).await.unwrap();
+    assert_eq!(proof.byte_range, None);
+    assert_eq!(proof.digest, hex::encode(Sha256::digest(plain)));
+    assert!(proof.verify(plain, &key).unwrap());
+}
+
+#[tokio::test]
+async fn test_hex_format_for_key_and_signature() {
+    let addr = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    let hex_key: serde_json::Value = client
+        .get(format!("http://{}/key?format=hex", addr))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(hex_key["format"], "hex");
+    let public_key = hex_key["public-key"].as_str().unwrap();
+    assert_eq!(hex::decode(public_key).unwrap().len(), 33);
+    let hex_key: lab4::EcdsaVerificationKey = serde_json::from_value(hex_key).unwrap();
+
+    // In the body...
+    let body: serde_json::Value = client
+        .post(format!("http://{}/sign", addr))
+        .json(&serde_json::json!({ "message": "Hex please", "format": "hex" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["format"], "hex");
+    assert_eq!(body["signature"].as_str().unwrap().len(), 128);
+    let signed: lab4::EcdsaSignedTimestamp = serde_json::from_value(body).unwrap();
+    assert!(verify_signature(&signed, &hex_key));
+
+    // ...or the query, and either format verifies against the other
+    let body: serde_json::Value = client
+        .post(format!("http://{}/sign?format=hex", addr))
+        .json(&serde_json::json!({ "message": "Hex please" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["format"], "hex");
+    let signed: lab4::EcdsaSignedTimestamp = serde_json::from_value(body).unwrap();
+    let b64_key = nonblocking::request_key(&format!("http://{}", addr))
+        .await
+        .unwrap();
+    assert!(b64_key.format.is_none());
+    assert!(verify_signature(&signed, &b64_key));
+}
+
+#[tokio::test]
+async fn test_key_stats_and_signature_limit() {
+    let config = ServerConfig {
+        key_stats: KeyStatsConfig {
+            file: String::new(),
+            max_signatures: 2,
+        },
+        ..test_config()
+    };
+    let addr = spawn_configured_server(&["other"], config).await;
+    let client = reqwest::Client::new();
+    let stats = |path: &'static str| {
+        let client = client.clone();
+        async move {
+            client
+                .get(format!("http://{}{}", addr, path))
+                .send()
+                .await
+                .unwrap()
+                .json::<serde_json::Value>()
+                .await
+                .unwrap()
+        }
+    };
+
+    let before = stats("/key/stats").await;
+    assert_eq!(before["signatures"], 0);
+    assert_eq!(before["max-signatures"], 2);
+    assert_eq!(before["remaining"], 2);
+    assert!(before["first-signed"].is_null());
+
+    let mut times = Vec::new();
+    for i in 0..2 {
+        let signed = nonblocking::request_timestamp(&format!("http://{}", addr), &i.to_string())
+            .await
+            .unwrap();
+        times.push(signed.time_signed);
+    }
+    let resp = client
+        .post(format!("http://{}/sign", addr))
+        .json(&serde_json::json!({ "message": "one too many" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let after = stats("/key/stats").await;
+    assert_eq!(after["key-id"], before["key-id"]);
+    assert_eq!(after["signatures"], 2);
+    assert_eq!(after["remaining"], 0);
+    assert_eq!(after["first-signed"], times[0]);
+    assert_eq!(after["last-signed"], times[1]);
+
+    // Other keys have their own budget
+    let other = stats("/t/other/key/stats").await;
+    assert_ne!(other["key-id"], before["key-id"]);
+    assert_eq!(other["signatures"], 0);
+    nonblocking::request_timestamp_for_tenant(&format!("http://{}", addr), "other", "fine")
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_oversized_messages_are_rejected() {
+    let config = ServerConfig {
+        max_message_bytes: 16,
+        ..test_config()
+    };
+    let addr = spawn_configured_server(&[], config).await;
+    let client = reqwest::Client::new();
+    let post = |message: String| {
+        let client = client.clone();
+        async move {
+            let resp = client
+                .post(format!("http://{}/sign", addr))
+                .json(&serde_json::json!({ "message": message }))
+                .send()
+                .await
+                .unwrap();
+            let status = resp.status();
+            (status, resp.json::<serde_json::Value>().await.unwrap())
+        }
+    };
+
+    let (status, _) = post("x".repeat(16)).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+
+    // The body fits, the message doesn't
+    let (status, body) = post("x".repeat(17)).await;
+    assert_eq!(status, reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["error"], "Message too large");
+
+    // The body alone is over the limit, so it isn't even read
+    let (status, body) = post("x".repeat(64 * 1024)).await;
+    assert_eq!(status, reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["error"], "Request body too large");
+}
+
+#[tokio::test]
+async fn test_errors_are_api_errors() {
+    let addr = spawn_configured_server(
+        &[],
+        ServerConfig {
+            max_message_bytes: 4,
+            ..test_config()
+        },
+    )
+    .await;
+    let server_url = format!("http://{}", addr);
+
+    // The client functions hand back the server's ApiError
+    let err = nonblocking::request_key_for_tenant(&server_url, "nope")
+        .await
+        .unwrap_err();
+    let api = err.downcast_ref::<ApiError>().expect("an ApiError");
+    assert_eq!(api.code, "unknown_tenant");
+    assert_eq!(api.status, Some(404));
+    assert!(api.request_id.is_some());
+
+    let err = nonblocking::request_timestamp(&server_url, "too long")
+        .await
+        .unwrap_err();
+    let api = err.downcast_ref::<ApiError>().unwrap();
+    assert_eq!(api.code, "message_too_large");
+    assert_eq!(api.status, Some(413));
+
+    // A client-chosen request id is echoed in the header and the body
+    let resp = reqwest::Client::new()
+        .get(format!("{}/nonexistent", server_url))
+        .header("X-Request-Id", "trace-123")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["x-request-id"], "trace-123");
+    let api: ApiError = resp.json().await.unwrap();
+    assert_eq!(api.code, "not_found");
+    assert_eq!(api.request_id.as_deref(), Some("trace-123"));
+    // The status is the response's, not a body field
+    assert_eq!(api.status, None);
+
+    // Successful responses get an id too
+    let resp = reqwest::get(format!("{}/key", server_url)).await.unwrap();
+    assert!(resp.headers().contains_key("x-request-id"));
+}
+
+#[tokio::test]
+async fn test_invalid_private_key_fails_at_startup() {
+    let (_, pub_bytes) = generate_key_bytes();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let result = server::run_configured_server_with_listener(
+        vec![0u8; 5],
+        pub_bytes,
+        TenantKeys::new(),
+        test_config(),
+        Box::new(SystemClock),
+        listener,
+    )
+    .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_mismatched_public_key_fails_at_startup() {
+    let (priv_bytes, _) = generate_key_bytes();
+    let (_, other_pub_bytes) = generate_key_bytes();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let result = server::run_configured_server_with_listener(
+        priv_bytes,
+        other_pub_bytes,
+        TenantKeys::new(),
+        test_config(),
+        Box::new(SystemClock),
+        listener,
+    )
+    .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_responses_name_their_algorithms() {
+    let addr = spawn_server().await;
+    let server_url = format!("http://{}", addr);
+    let client = reqwest::Client::new();
+
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    assert_eq!(key.signature_alg.as_deref(), Some("ecdsa-secp256k1"));
+    assert_eq!(key.hash_alg.as_deref(), Some("sha-256"));
+
+    // Asking for the algorithms the server uses is fine
+    let signed: lab4::EcdsaSignedTimestamp = client
+        .post(format!("{}/sign", server_url))
+        .json(&serde_json::json!({
+            "message": "agile",
+            "signature-alg": "ecdsa-secp256k1",
+            "hash-alg": "sha-256",
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(signed.signature_alg.as_deref(), Some("ecdsa-secp256k1"));
+    assert_eq!(signed.hash_alg.as_deref(), Some("sha-256"));
+    assert!(verify_signature(&signed, &key));
+
+    // Any other is refused rather than silently replaced
+    for body in [
+        serde_json::json!({ "message": "agile", "signature-alg": "ed25519" }),
+        serde_json::json!({ "message": "agile", "hash-alg": "sha3-256" }),
+    ] {
+        let resp = client
+            .post(format!("{}/sign", server_url))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 400);
+        let err: ApiError = resp.json().await.unwrap();
+        assert_eq!(err.code, "unsupported_algorithm");
+    }
+}
+
+#[tokio::test]
+async fn test_renew_timestamp() {
+    let addr = spawn_server().await;
+    let server_url = format!("http://{}", addr);
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    let signed = nonblocking::request_timestamp(&server_url, "Archive me")
+        .await
+        .unwrap();
+
+    // The renewal signs the old token plus a later time
+    let token = signed.to_token();
+    let renewed = nonblocking::renew_timestamp(&server_url, &token)
+        .await
+        .unwrap();
+    assert_eq!(renewed.request, "RENEW");
+    assert_eq!(renewed.message, token);
+    assert!(renewed.time_signed > signed.time_signed);
+    assert!(verify_signature(&renewed, &key));
+
+    // The original comes back out, and renewing again chains
+    let inner = renewed.renewed().unwrap();
+    assert_eq!(inner.signature, signed.signature);
+    assert!(verify_signature(&inner, &key));
+    let again = nonblocking::renew_timestamp(&server_url, &renewed.to_token())
+        .await
+        .unwrap();
+    assert_eq!(
+        again.renewed().unwrap().renewed().unwrap().message,
+        "Archive me"
+    );
+    assert!(signed.renewed().is_none());
+
+    // A body without a token is rejected
+    let resp = reqwest::Client::new()
+        .post(format!("{}/renew", server_url))
+        .json(&serde_json::json!({ "message": "Archive me" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 422);
+}
+
+#[tokio::test]
+async fn test_issued_timestamps_are_audited() {
+    let dir = tempfile::tempdir().unwrap();
+    let audit_file = dir.path().join("audit.log");
+    let config = ServerConfig {
+        audit_file: audit_file.to_string_lossy().into_owned(),
+        ..test_config()
+    };
+    let addr = spawn_configured_server(&["cs55"], config).await;
+    let server_url = format!("http://{}", addr);
+
+    // One plain timestamp, one JWS, one for a tenant
+    nonblocking::request_timestamp(&server_url, "audited")
+        .await
+        .unwrap();
+    let status = reqwest::Client::new()
+        .post(format!("{}/sign?format=jws", server_url))
+        .json(&serde_json::json!({ "message": "audited" }))
+        .send()
+        .await
+        .unwrap()
+        .status();
+    assert!(status.is_success());
+    nonblocking::request_timestamp_for_tenant(&server_url, "cs55", "audited")
+        .await
+        .unwrap();
+
+    // Every line is signed by the default tenant's key and chained
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    assert_eq!(verify_file(&audit_file, &key.key().unwrap()).unwrap(), 3);
+    let entries: Vec<AuditEntry> = fs::read_to_string(&audit_file)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let hash = hex::encode(Sha256::digest(b"audited"));
+    assert!(entries.iter().all(|e| e.hash == hash));
+    assert_eq!(entries[0].client.as_deref(), Some("127.0.0.1"));
+    assert_eq!(
+        entries.iter().map(|e| e.kind.as_str()).collect::<Vec<_>>(),
+        ["sign", "sign_jws", "sign"]
+    );
+    assert_eq!(
+        entries.iter().map(|e| e.serial).collect::<Vec<_>>(),
+        [1, 2, 1]
+    );
+    assert_eq!(entries[2].tenant.as_deref(), Some("cs55"));
+}
+
+#[tokio::test]
+async fn test_lookup_by_hash_returns_every_timestamp() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = || ServerConfig {
+        audit_file: dir.path().join("audit.log").to_string_lossy().into_owned(),
+        ..test_config()
+    };
+    let addr = spawn_configured_server(&["cs55"], config()).await;
+    let server_url = format!("http://{}", addr);
+    let client = reqwest::Client::new();
+    let lookup = |server_url: String, path: String| {
+        let request = client.get(format!("{}{}", server_url, path));
+        async move {
+            let resp = request.send().await.unwrap();
+            (
+                resp.status(),
+                resp.json::<serde_json::Value>().await.unwrap(),
+            )
+        }
+    };
+
+    // Twice by the default tenant (once as a JWS), once by another
+    let first = nonblocking::request_timestamp(&server_url, "looked up")
+        .await
+        .unwrap();
+    let status = client
+        .post(format!("{}/sign?format=jws", server_url))
+        .json(&serde_json::json!({ "message": "looked up" }))
+        .send()
+        .await
+        .unwrap()
+        .status();
+    assert!(status.is_success());
+    nonblocking::request_timestamp_for_tenant(&server_url, "cs55", "looked up")
+        .await
+        .unwrap();
+
+    let hash = hex::encode(Sha256::digest(b"looked up"));
+    let (status, body) = lookup(
+        server_url.clone(),
+        format!("/v1/timestamp/by-hash/{}", hash.to_uppercase()),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(body["hash"], hash);
+    let found = body["timestamps"].as_array().unwrap();
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0]["time-signed"], first.time_signed);
+    assert_eq!(found[1]["type"], "sign_jws");
+
+    // Each signature checks out for the message, and each proof is signed by
+    // the audit key
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    for timestamp in found {
+        let signed = lab4::EcdsaSignedTimestamp {
+            request: "POST".to_string(),
+            message: "looked up".to_string(),
+            message_b64: None,
+            time_signed: timestamp["time-signed"].as_str().unwrap().to_string(),
+            signature: timestamp["signature"].as_str().unwrap().to_string(),
+            accuracy: None,
+            format: None,
+            signature_alg: None,
+            hash_alg: None,
+            kid: None,
+            policy: None,
+        };
+        assert!(verify_signature(&signed, &key));
+        let proof: AuditEntry = serde_json::from_value(timestamp["proof"].clone()).unwrap();
+        assert!(proof.verify(&key.key().unwrap()));
+    }
+    let (_, body) = lookup(
+        server_url.clone(),
+        format!("/t/cs55/timestamp/by-hash/{}", hash),
+    )
+    .await;
+    assert_eq!(body["timestamps"].as_array().unwrap().len(), 1);
+
+    // Never timestamped, and not a hash
+    let unseen = hex::encode(Sha256::digest(b"never"));
+    let (status, body) = lookup(server_url.clone(), format!("/timestamp/by-hash/{}", unseen)).await;
+    assert_eq!(status, 200);
+    assert!(body["timestamps"].as_array().unwrap().is_empty());
+    let (status, body) = lookup(server_url.clone(), "/timestamp/by-hash/abc".to_string()).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["code"], "invalid_hash");
+
+    // The index is rebuilt from the log on restart
+    let addr = spawn_configured_server(&["cs55"], config()).await;
+    let (_, body) = lookup(
+        format!("http://{}", addr),
+        format!("/timestamp/by-hash/{}", hash),
+    )
+    .await;
+    assert_eq!(body["timestamps"].as_array().unwrap().len(), 2);
+
+    // Without an audit log there is nothing to look in
+    let addr = spawn_server().await;
+    let (status, body) = lookup(
+        format!("http://{}", addr),
+        format!("/timestamp/by-hash/{}", hash),
+    )
+    .await;
+    assert_eq!(status, 404);
+    assert_eq!(body["code"], "not_enabled");
+}
+
+#[tokio::test]
+async fn test_duplicates_get_new_timestamps_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = ServerConfig {
+        audit_file: dir.path().join("audit.log").to_string_lossy().into_owned(),
+        ..test_config()
+    };
+    let addr = spawn_configured_server(&[], config).await;
+
+    let (_, first) = post_sign(addr, "Earliest", None).await;
+    let resp = reqwest::Client::new()
+        .post(format!("http://{}/sign", addr))
+        .json(&serde_json::json!({ "message": "Earliest" }))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.headers().get(server::FIRST_SEEN_HEADER).is_none());
+    let again: serde_json::Value = resp.json().await.unwrap();
+    assert!(again["time-signed"].as_str() > first["time-signed"].as_str());
+    assert!(again.get("first-seen").is_none());
+}
+
+/// Spawns a server with the given default key and `config`
+async fn spawn_server_with_keys(keys: (Vec<u8>, Vec<u8>), config: ServerConfig) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    task::spawn(async move {
+        server::run_configured_server_with_listener(
+            keys.0,
+            keys.1,
+            TenantKeys::new(),
+            config,
+            Box::new(SystemClock),
+            listener,
+        )
+        .await
+        .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+    });
+    sleep(Duration::from_millis(100)).await;
+    addr
+}
+
+#[tokio::test]
+async fn test_serials_keep_increasing_across_restarts() {
+    let dir = tempfile::tempdir().unwrap();
+    let keys = generate_key_bytes();
+    let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+    let serials = |addr: SocketAddr, n: usize| async move {
+        let server_url = format!("http://{}", addr);
+        let key = nonblocking::request_key(&server_url).await.unwrap();
+        let client = reqwest::Client::new();
+        let mut serials = Vec::new();
+        for i in 0..n {
+            let token = client
+                .post(format!("{}/sign?format=jws", server_url))
+                .json(&serde_json::json!({ "message": format!("serial {}", i) }))
+                .send()
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap();
+            serials.push(jws::verify(&token, &key).unwrap().serial);
+        }
+        serials
+    };
+
+    // Persisted in `serial_file`
+    let config = || ServerConfig {
+        serial_file: path("serials.json"),
+        ..test_config()
+    };
+    let addr = spawn_server_with_keys(keys.clone(), config()).await;
+    assert_eq!(serials(addr, 2).await, [1, 2]);
+    let addr = spawn_server_with_keys(keys.clone(), config()).await;
+    assert_eq!(serials(addr, 2).await, [3, 4]);
+
+    // Or taken from the audit log, when that is further along
+    let config = || ServerConfig {
+        audit_file: path("audit.log"),
+        ..test_config()
+    };
+    let addr = spawn_server_with_keys(keys.clone(), config()).await;
+    assert_eq!(serials(addr, 3).await, [1, 2, 3]);
+    let addr = spawn_server_with_keys(keys.clone(), config()).await;
+    assert_eq!(serials(addr, 1).await, [4]);
+}
+
+#[tokio::test]
+async fn test_first_seen_returns_earliest_timestamp() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = || ServerConfig {
+        audit_file: dir.path().join("audit.log").to_string_lossy().into_owned(),
+        duplicates: DuplicatePolicy::FirstSeen,
+        ..test_config()
+    };
+    let keys = generate_key_bytes();
+    let addr = spawn_server_with_keys(keys.clone(), config()).await;
+    let server_url = format!("http://{}", addr);
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    let client = reqwest::Client::new();
+    let sign = |query: &str, body: serde_json::Value| {
+        let request = client
+            .post(format!("{}/sign{}", server_url, query))
+            .json(&body);
+        async move {
+            let resp = request.send().await.unwrap();
+            let first_seen = resp.headers().get(server::FIRST_SEEN_HEADER).is_some();
+            (first_seen, resp.text().await.unwrap())
+        }
+    };
+
+    // The first request is issued as usual
+    let (first_seen, body) = sign("", serde_json::json!({ "message": "Earliest" })).await;
+    assert!(!first_seen);
+    let first: lab4::EcdsaSignedTimestamp = serde_json::from_str(&body).unwrap();
+    assert!(!body.contains("first-seen"));
+
+    // Repeats get that timestamp back, in whatever encoding they ask for
+    sleep(Duration::from_millis(5)).await;
+    let (first_seen, body) = sign(
+        "?format=hex",
+        serde_json::json!({ "message": "Earliest", "encoding": "der" }),
+    )
+    .await;
+    assert!(first_seen);
+    let value: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(value["first-seen"], true);
+    let again: lab4::EcdsaSignedTimestamp = serde_json::from_str(&body).unwrap();
+    assert_eq!(again.time_signed, first.time_signed);
+    assert_ne!(again.signature, first.signature);
+    assert!(verify_signature(&again, &key));
+
+    // A token carries the first serial and time
+    let (first_seen, token) =
+        sign("?format=jws", serde_json::json!({ "message": "Earliest" })).await;
+    assert!(first_seen);
+    let claims = jws::verify(&token, &key).unwrap();
+    assert_eq!(claims.serial, 1);
+    assert_eq!(
+        claims.iat,
+        chrono::DateTime::parse_from_rfc3339(&first.time_signed)
+            .unwrap()
+            .timestamp()
+    );
+
+    // Renewals and other messages are new
+    let renewed = nonblocking::renew_timestamp(&server_url, &first.to_token())
+        .await
+        .unwrap();
+    assert!(renewed.time_signed > first.time_signed);
+    let (first_seen, _) = sign("", serde_json::json!({ "message": "Later" })).await;
+    assert!(!first_seen);
+
+    // Still the first after a restart with the same key
+    let addr = spawn_server_with_keys(keys, config()).await;
+    let (_, again) = post_sign(addr, "Earliest", None).await;
+    assert_eq!(again["time-signed"], first.time_signed);
+    assert_eq!(again["first-seen"], true);
+
+    // Under a new key the old signature is no use; the message is issued anew
+    let addr = spawn_configured_server(&[], config()).await;
+    let (_, fresh) = post_sign(addr, "Earliest", None).await;
+    assert_ne!(fresh["time-signed"], first.time_signed);
+    assert!(fresh.get("first-seen").is_none());
+}
+
+#[tokio::test]
+async fn test_first_seen_needs_audit_log() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let (priv_bytes, pub_bytes) = generate_key_bytes();
+    let config = ServerConfig {
+        duplicates: DuplicatePolicy::FirstSeen,
+        ..test_config()
+    };
+    let err = server::run_configured_server_with_listener(
+        priv_bytes,
+        pub_bytes,
+        TenantKeys::new(),
+        config,
+        Box::new(SystemClock),
+        listener,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("audit_file"), "{}", err);
+}
+
+/// A signer standing in for a hardware token that can be unplugged, or
+/// misbehave and sign with some other key
+struct TokenSigner {
+    keypair: KeyPair,
+    unplugged: AtomicBool,
+    wrong_key: AtomicBool,
+}
+
+impl Signer for TokenSigner {
+    fn sign(&self, message: &[u8]) -> Result<k256::ecdsa::Signature, SignerError> {
+        if self.unplugged.load(Ordering::SeqCst) {
+            return Err("token not present".into());
+        }
+        if self.wrong_key.load(Ordering::SeqCst) {
+            return Ok(KeyPair::generate().sign(message));
+        }
+        Ok(self.keypair.sign(message))
+    }
+
+    fn public_key(&self) -> ecdsa_lib::PublicKey {
+        self.keypair.to_public_key()
+    }
+}
+
+#[tokio::test]
+async fn test_custom_signer() {
+    let signer = Arc::new(TokenSigner {
+        keypair: KeyPair::from_seed(&[9u8; 32]),
+        unplugged: AtomicBool::new(false),
+        wrong_key: AtomicBool::new(false),
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = signer.clone();
+    task::spawn(async move {
+        server::run_server_with_signers(
+            Box::new(handle),
+            TenantSigners::new(),
+            test_config(),
+            Box::new(SystemClock),
+            listener,
+        )
+        .await
+        .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+    });
+    sleep(Duration::from_millis(100)).await;
+
+    // The signer's key is served and signs
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    assert_eq!(key.key(), Some(signer.keypair.to_public_key()));
+    let signed = nonblocking::request_timestamp(&server_url, "From the token")
+        .await
+        .unwrap();
+    assert!(verify_signature(&signed, &key));
+
+    // A failing signer, or one whose signature doesn't verify, is a 500
+    for (flag, code) in [
+        (&signer.unplugged, "signer_error"),
+        (&signer.wrong_key, "self_check_failed"),
+    ] {
+        flag.store(true, Ordering::SeqCst);
+        let err = nonblocking::request_timestamp(&server_url, "From the token")
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<ApiError>().unwrap().code, code);
+        flag.store(false, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn test_unknown_route_returns_not_found() {
+    let addr = spawn_server().await;
+    let client = reqwest::Client::new();
+    let url = format!("http://{}/nonexistent", addr);
+    let resp = client.get(&url).send().await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["code"], "not_found");
+}
+
+#[tokio::test]
+async fn test_wrong_method_and_bad_json_errors() {
+    let addr = spawn_server().await;
+    let client = reqwest::Client::new();
+
+    // Known path, wrong method → 405 with `Allow`
+    let resp = client
+        .get(format!("http://{}/sign", addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(resp.headers()["allow"], "POST");
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["code"], "method_not_allowed");
+
+    let resp = client
+        .delete(format!("http://{}/key", addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::METHOD_NOT_ALLOWED);
+    assert!(resp.headers()["allow"].to_str().unwrap().contains("GET"));
+
+    // Malformed and mistyped JSON → 422
+    for body in [
+        r#"{"message": "#,
+        r#"{"message": 42}"#,
+        r#"{"encoding": "raw"}"#,
+    ] {
+        let resp = client
+            .post(format!("http://{}/sign", addr))
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["code"], "invalid_json");
+        assert!(body["error"].is_string());
+    }
+
+    // Not JSON at all
+    let resp = client
+        .post(format!("http://{}/sign", addr))
+        .body("message=hi")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn test_log_stream_pushes_issued_entries() {
+    let addr = spawn_server().await;
+    let server_url = format!("http://{}", addr);
+    let client = reqwest::Client::new();
+
+    // Subscribe first: only entries issued after connecting are pushed
+    let mut stream = client
+        .get(format!("{}/log/stream", server_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stream.status(), reqwest::StatusCode::OK);
+
+    let body = serde_json::json!({ "message": "Streamed!" });
+    let signed: lab4::EcdsaSignedTimestamp = client
+        .post(format!("{}/sign", server_url))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    // Read until the first complete event (terminated by a blank line) arrives
+    let mut buf = String::new();
+    while !buf.contains("\n\n") {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), stream.chunk())
+            .await
+            .expect("timed out waiting for log entry")
+            .unwrap()
+            .expect("stream ended early");
+        buf.push_str(std::str::from_utf8(&chunk).unwrap());
+    }
+    let data = buf.lines().find_map(|l| l.strip_prefix("data:")).unwrap();
+    let entry: serde_json::Value = serde_json::from_str(data.trim()).unwrap();
+
+    assert_eq!(entry["serial"], 1);
+    assert_eq!(entry["time-signed"], signed.time_signed.as_str());
+    assert_eq!(entry["signature"], signed.signature.as_str());
+    assert_eq!(
+        entry["hash"],
+        hex::encode(Sha256::digest(b"Streamed!")).as_str()
+    );
+}
+
+/// Serves `app` on an ephemeral port, standing in for another server build
+async fn spawn_router(app: axum::Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    task::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn test_time_precision_truncates_time_signed() {
+    let config = ServerConfig {
+        time_precision: TimePrecision::Millis,
+        ..test_config()
+    };
+    let addr = spawn_configured_server(&[], config).await;
+    let server_url = format!("http://{}", addr);
+
+    let version = nonblocking::request_version(&server_url).await.unwrap();
+    assert_eq!(version.time_precision, TimePrecision::Millis);
+
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    for message in ["one", "two", "three"] {
+        let signed = nonblocking::request_timestamp(&server_url, message)
+            .await
+            .unwrap();
+        // Six digits still, the last three zero, and signed as such
+        assert!(
+            TimePrecision::Millis.holds(&signed.time_signed),
+            "{}",
+            signed.time_signed
+        );
+        assert!(
+            signed.time_signed.ends_with("000Z"),
+            "{}",
+            signed.time_signed
+        );
+        assert!(verify_signature(&signed, &key));
+    }
+
+    // So does the preview
+    let preview: serde_json::Value = reqwest::Client::new()
+        .post(format!("{}/sign/preview", server_url))
+        .json(&serde_json::json!({ "message": "preview" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(TimePrecision::Millis.holds(preview["time-signed"].as_str().unwrap()));
+
+    // And the signed clock, which is never earlier than a timestamp issued
+    let issued = nonblocking::request_timestamp(&server_url, "four")
+        .await
+        .unwrap();
+    let client = client::nonblocking::VtsClient::new(&server_url).unwrap();
+    let signed = client.request_time("n-0001").await.unwrap();
+    assert!(signed.verify(&key.key().unwrap(), "n-0001"));
+    assert!(TimePrecision::Millis.holds(&signed.time), "{}", signed.time);
+    assert!(signed.time >= issued.time_signed, "{}", signed.time);
+}
+
+#[tokio::test]
+async fn test_versioned_routes_and_negotiation() {
+    let addr = spawn_tenant_server(&["acme"]).await;
+    let server_url = format!("http://{}", addr);
+
+    let version = nonblocking::request_version(&server_url).await.unwrap();
+    assert_eq!(version.request, "GET");
+    assert_eq!(version.server_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(version.protocol_versions, ["v1"]);
+    assert!(version.features.iter().any(|f| f == "jws"));
+    assert!(!version.features.iter().any(|f| f == "audit"));
+    assert_eq!(version.time_precision, TimePrecision::Micros);
+
+    // The same protocol under /v1 and at the legacy paths
+    let base = nonblocking::negotiate(&server_url).await.unwrap();
+    assert_eq!(base, format!("{}/v1", server_url));
+    let key = nonblocking::request_key(&base).await.unwrap();
+    let signed = nonblocking::request_timestamp(&base, "versioned")
+        .await
+        .unwrap();
+    assert!(verify_signature(&signed, &key));
+    assert_eq!(
+        key.public_key,
+        nonblocking::request_key(&server_url)
+            .await
+            .unwrap()
+            .public_key
+    );
+    let tenant_key = nonblocking::request_key_for_tenant(&base, "acme")
+        .await
+        .unwrap();
+    assert_eq!(
+        tenant_key.public_key,
+        nonblocking::request_key_for_tenant(&server_url, "acme")
+            .await
+            .unwrap()
+            .public_key
+    );
+
+    // A server without /version is used as it is
+    let legacy = spawn_router(axum::Router::new()).await;
+    assert_eq!(nonblocking::negotiate(&legacy).await.unwrap(), legacy);
+
+    // One speaking only versions this client doesn't is refused
+    let future = spawn_router(axum::Router::new().route(
+        "/version",
+        axum::routing::get(|| async {
+            axum::Json(serde_json::json!({
+                "request": "GET",
+                "server-version": "9.0.0",
+                "protocol-versions": ["v9"],
+            }))
+        }),
+    ))
+    .await;
+    let err = nonblocking::negotiate(&future).await.unwrap_err();
+    let err = err.downcast_ref::<ApiError>().unwrap();
+    assert_eq!(err.code, "incompatible_version");
+    assert!(err.message.contains("v9"));
+}
+
+#[tokio::test]
+async fn test_openapi_matches_responses() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = ServerConfig {
+        audit_file: dir.path().join("audit.log").to_string_lossy().into_owned(),
+        ..test_config()
+    };
+    let addr = spawn_configured_server(&[], config).await;
+    let server_url = format!("http://{}", addr);
+    let client = reqwest::Client::new();
+    let call = |method: reqwest::Method, path: &str, body: Option<serde_json::Value>| {
+        let mut request = client.request(method, format!("{}{}", server_url, path));
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        async move {
+            let resp = request.send().await.unwrap();
+            (resp.status().as_u16(), resp.text().await.unwrap())
+        }
+    };
+
+    let (_, doc) = call(reqwest::Method::GET, "/openapi.json", None).await;
+    let doc: serde_json::Value = serde_json::from_str(&doc).unwrap();
+    assert_eq!(doc["openapi"], "3.0.3");
+    for path in [
+        "/version",
+        "/metrics",
+        "/v1/key",
+        "/v1/keys",
+        "/v1/time",
+        "/.well-known/jwks.json",
+        "/v1/revocations",
+        "/v1/policies",
+        "/v1/key/stats",
+        "/v1/key/cert",
+        "/v1/sign",
+        "/v1/sign/preview",
+        "/v1/renew",
+        "/v1/timestamp/by-hash/{sha256}",
+        "/v1/anchor/{serial}",
+        "/v1/log/stream",
+        "/v1/log/entries",
+        "/v1/log/day/{date}",
+        "/v1/log/root",
+        "/v1/log/consistency",
+        "/v1/log/proof",
+    ] {
+        assert!(doc["paths"][path].is_object(), "{} is not documented", path);
+    }
+    assert_refs_resolve(&doc, &doc);
+
+    // Accepted request bodies fit the documented request schema
+    let jws = client
+        .post(format!("{}/v1/sign?format=jws", server_url))
+        .json(&serde_json::json!({ "message": "spec" }))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let digest = hex::encode(Sha256::digest(b"spec"));
+    let requests = [
+        (
+            "/v1/sign",
+            serde_json::json!({ "message": "spec", "encoding": "der", "format": "hex" }),
+        ),
+        (
+            "/v1/sign",
+            serde_json::json!({
+                "message-b64": "c3BlYw==",
+                "signature-alg": "ecdsa-secp256k1",
+                "hash-alg": "sha-256",
+            }),
+        ),
+        (
+            "/v1/sign",
+            serde_json::json!({ "digest": digest, "digest-alg": "sha-256" }),
+        ),
+        (
+            "/v1/sign/preview?format=hex",
+            serde_json::json!({ "message": "spec" }),
+        ),
+        ("/v1/renew", serde_json::json!({ "token": jws })),
+    ];
+    // Every response, checked against the schema its operation documents
+    // for its status, down through nested objects and arrays
+    let mut responses = Vec::new();
+    for (path, body) in requests {
+        let operation = path.split('?').next().unwrap();
+        let schema = &doc["paths"][operation]["post"]["requestBody"]["content"]["application/json"]
+            ["schema"];
+        assert_conforms(&doc, schema, &body, operation);
+        let (status, response) = call(reqwest::Method::POST, path, Some(body)).await;
+        assert_eq!(status, 200, "{}: {}", path, response);
+        responses.push(("post", operation, status, response));
+    }
+    let (status, response) = call(
+        reqwest::Method::POST,
+        "/v1/sign",
+        Some(serde_json::json!({ "message": "spec", "hash-alg": "md5" })),
+    )
+    .await;
+    responses.push(("post", "/v1/sign", status, response));
+    let by_hash = format!("/v1/timestamp/by-hash/{}", digest);
+    for path in [
+        "/version",
+        "/v1/key?format=hex",
+        "/v1/keys",
+        "/.well-known/jwks.json",
+        "/v1/revocations",
+        "/v1/policies",
+        "/v1/key/stats",
+        "/v1/time?nonce=spec",
+        "/v1/time?nonce=",
+        &by_hash,
+        "/v1/timestamp/by-hash/abc",
+        "/v1/anchor/1",
+        "/v1/anchor/first",
+        "/v1/log/root",
+        "/v1/log/consistency?old=1&new=3",
+        "/v1/log/consistency?old=3&new=1",
+        "/v1/log/proof?seq=2",
+        "/v1/log/proof?seq=0",
+        "/v1/log/day/not-a-date",
+    ] {
+        let (status, response) = call(reqwest::Method::GET, path, None).await;
+        let operation = match path.split('?').next().unwrap() {
+            by_hash if by_hash.starts_with("/v1/timestamp/by-hash/") => {
+                "/v1/timestamp/by-hash/{sha256}"
+            }
+            day if day.starts_with("/v1/log/day/") => "/v1/log/day/{date}",
+            anchor if anchor.starts_with("/v1/anchor/") => "/v1/anchor/{serial}",
+            operation => operation,
+        };
+        responses.push(("get", operation, status, response));
+    }
+    let statuses: Vec<u16> = responses.iter().map(|(_, _, status, _)| *status).collect();
+    for status in [200, 400] {
+        assert!(statuses.contains(&status), "no {} response checked", status);
+    }
+    for (method, operation, status, response) in &responses {
+        let schema = &doc["paths"][*operation][*method]["responses"][status.to_string()]["content"]
+            ["application/json"]["schema"];
+        assert!(
+            schema.is_object(),
+            "{} {} doesn't document a {} response",
+            method,
+            operation,
+            status
+        );
+        let response: serde_json::Value = serde_json::from_str(response).unwrap();
+        assert_conforms(
+            &doc,
+            schema,
+            &response,
+            &format!("{} {}", operation, status),
+        );
+    }
+
+    // The audit log lines of `/log/entries`, one JSON object per line
+    let (status, lines) = call(reqwest::Method::GET, "/v1/log/entries", None).await;
+    assert_eq!(status, 200);
+    let schema = &doc["paths"]["/v1/log/entries"]["get"]["responses"]["200"]["content"]["application/x-ndjson"]
+        ["schema"];
+    assert!(lines.lines().count() >= 5);
+    for line in lines.lines() {
+        let entry: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_conforms(&doc, schema, &entry, "/v1/log/entries");
+    }
+}
+
+/// Panics on a `$ref` anywhere in `value` that names no schema of `doc`
+fn assert_refs_resolve(doc: &serde_json::Value, value: &serde_json::Value) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            if let Some(name) = fields.get("$ref").and_then(|r| r.as_str()) {
+                resolve_schema(doc, value);
+                assert!(name.starts_with("#/components/schemas/"), "{}", name);
+            }
+            fields.values().for_each(|v| assert_refs_resolve(doc, v));
+        }
+        serde_json::Value::Array(items) => items.iter().for_each(|v| assert_refs_resolve(doc, v)),
+        _ => {}
+    }
+}
+
+/// `schema`, or the component schema its `$ref` names
+fn resolve_schema<'a>(
+    doc: &'a serde_json::Value,
+    schema: &'a serde_json::Value,
+) -> &'a serde_json::Value {
+    match schema["$ref"].as_str() {
+        Some(reference) => {
+            let name = reference.trim_start_matches("#/components/schemas/");
+            let target = &doc["components"]["schemas"][name];
+            assert!(target.is_object(), "{} names no schema", reference);
+            resolve_schema(doc, target)
+        }
+        None => schema,
+    }
+}
+
+/// Panics unless `value` fits `schema`: the documented type and enum
+/// values, no undocumented field and no missing required one, and the same
+/// for every nested value. An `allOf` of several parts (from
+/// `#[serde(flatten)]`) is the union of their fields.
+fn assert_conforms(
+    doc: &serde_json::Value,
+    schema: &serde_json::Value,
+    value: &serde_json::Value,
+    at: &str,
+) {
+    let schema = resolve_schema(doc, schema);
+    if value.is_null() && schema["nullable"] == true {
+        return;
+    }
+    let merged;
+    let schema = match schema["allOf"].as_array() {
+        // An `Option` of another schema
+        Some(parts) if parts.len() == 1 => return assert_conforms(doc, &parts[0], value, at),
+        Some(parts) => {
+            let mut properties = serde_json::Map::new();
+            let mut required = Vec::new();
+            for part in parts {
+                let part = resolve_schema(doc, part);
+                assert_eq!(part["type"], "object", "{}: allOf of a non-object", at);
+                properties.extend(part["properties"].as_object().cloned().unwrap_or_default());
+                required.extend(part["required"].as_array().cloned().unwrap_or_default());
+            }
+            merged = serde_json::json!({ "type": "object", "properties": properties, "required": required });
+            &merged
+        }
+        None => schema,
+    };
+    if let Some(values) = schema["enum"].as_array() {
+        assert!(
+            values.contains(value),
+            "{}: {} is not one of {:?}",
+            at,
+            value,
+            values
+        );
+    }
+    match schema["type"].as_str() {
+        Some("object") => {
+            let fields = value
+                .as_object()
+                .unwrap_or_else(|| panic!("{}: {} is not an object", at, value));
+            let properties = schema["properties"]
+                .as_object()
+                .cloned()
+                .unwrap_or_default();
+            for (field, field_value) in fields {
+                let property = properties
+                    .get(field)
+                    .unwrap_or_else(|| panic!("{}.{} is not documented", at, field));
+                assert_conforms(doc, property, field_value, &format!("{}.{}", at, field));
+            }
+            for field in schema["required"].as_array().into_iter().flatten() {
+                let field = field.as_str().unwrap();
+                assert!(
+                    fields.contains_key(field),
+                    "{} has no required {}",
+                    at,
+                    field
+                );
+            }
+        }
+        Some("array") => {
+            let items = value
+                .as_array()
+                .unwrap_or_else(|| panic!("{}: {} is not an array", at, value));
+            for (i, item) in items.iter().enumerate() {
+                assert_conforms(doc, &schema["items"], item, &format!("{}[{}]", at, i));
+            }
+        }
+        Some("string") => assert!(value.is_string(), "{}: {} is not a string", at, value),
+        Some("integer") => assert!(
+            value.is_u64() || value.is_i64(),
+            "{}: {} is not an integer",
+            at,
+            value
+        ),
+        Some("boolean") => assert!(value.is_boolean(), "{}: {} is not a boolean", at, value),
+        other => panic!("{}: unexpected schema type {:?}", at, other),
+    }
+}
+
+fn cors_config(origins: &[&str]) -> ServerConfig {
+    let mut config = test_config();
+    config.cors.allowed_origins = origins.iter().map(|o| o.to_string()).collect();
+    config
+}
+
+#[tokio::test]
+async fn test_cors_preflight_and_headers() {
+    let addr = spawn_configured_server(&[], cors_config(&["https://app.example"])).await;
+    let server_url = format!("http://{}", addr);
+    let client = reqwest::Client::new();
+
+    // The browser's preflight for a JSON POST
+    let resp = client
+        .request(reqwest::Method::OPTIONS, format!("{}/sign", server_url))
+        .header("Origin", "https://app.example")
+        .header("Access-Control-Request-Method", "POST")
+        .header("Access-Control-Request-Headers", "content-type")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+    let headers = resp.headers();
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://app.example"
+    );
+    assert_eq!(headers["access-control-allow-methods"], "GET, POST");
+    assert!(
+        headers["access-control-allow-headers"]
+            .to_str()
+            .unwrap()
+            .contains("content-type")
+    );
+    assert_eq!(headers["access-control-max-age"], "600");
+
+    // The request itself, and errors too, name the origin
+    let resp = client
+        .post(format!("{}/sign", server_url))
+        .header("Origin", "https://app.example")
+        .json(&serde_json::json!({ "message": "from the browser" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers()["access-control-allow-origin"],
+        "https://app.example"
+    );
+    assert_eq!(
+        resp.headers()["access-control-expose-headers"],
+        "x-request-id"
+    );
+    let resp = client
+        .get(format!("{}/nope", server_url))
+        .header("Origin", "https://app.example")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+    assert_eq!(
+        resp.headers()["access-control-allow-origin"],
+        "https://app.example"
+    );
+
+    // Other origins get no CORS headers, so the browser blocks them
+    let resp = client
+        .get(format!("{}/key", server_url))
+        .header("Origin", "https://evil.example")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(!resp.headers().contains_key("access-control-allow-origin"));
+
+    let features = nonblocking::request_version(&server_url)
+        .await
+        .unwrap()
+        .features;
+    assert!(features.iter().any(|f| f == "cors"));
+}
+
+#[tokio::test]
+async fn test_cors_off_by_default_and_wildcard() {
+    let client = reqwest::Client::new();
+    let preflight = |server_url: String| {
+        client
+            .request(reqwest::Method::OPTIONS, format!("{}/key", server_url))
+            .header("Origin", "https://any.example")
+            .header("Access-Control-Request-Method", "GET")
+            .send()
+    };
+
+    let addr = spawn_server().await;
+    let resp = preflight(format!("http://{}", addr)).await.unwrap();
+    assert_eq!(resp.status(), 405);
+    assert!(!resp.headers().contains_key("access-control-allow-origin"));
+
+    let addr = spawn_configured_server(&[], cors_config(&["*"])).await;
+    let resp = preflight(format!("http://{}", addr)).await.unwrap();
+    assert_eq!(resp.status(), 204);
+    assert_eq!(resp.headers()["access-control-allow-origin"], "*");
+}
+
+#[tokio::test]
+async fn test_invalid_cors_config_fails_at_startup() {
+    let (priv_bytes, pub_bytes) = generate_key_bytes();
+    let mut config = cors_config(&["https://app.example"]);
+    config.cors.allowed_methods = vec!["GET POST".to_string()];
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let result = server::run_configured_server_with_listener(
+        priv_bytes,
+        pub_bytes,
+        TenantKeys::new(),
+        config,
+        Box::new(SystemClock),
+        listener,
+    )
+    .await;
+    assert!(result.unwrap_err().to_string().contains("CORS method"));
+}
+
+fn fast_retries(retries: u32) -> ClientOptions {
+    ClientOptions {
+        retries,
+        backoff: Duration::from_millis(10),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_vts_client_reuses_one_client() {
+    let addr = spawn_tenant_server(&["acme"]).await;
+    let server_url = format!("http://{}/", addr);
+
+    let client = client::nonblocking::VtsClient::new(&server_url).unwrap();
+    assert_eq!(client.server_addr(), format!("http://{}", addr));
+    let key = client.request_key().await.unwrap();
+    for message in ["one", "two", "three"] {
+        let signed = client.request_timestamp(message).await.unwrap();
+        assert_eq!(signed.message, message);
+        assert!(verify_signature(&signed, &key));
+    }
+    let token = client.request_timestamp_cose("cose").await.unwrap();
+    assert!(verify_cose_token(&token, &key).is_some());
+
+    let acme = client.for_tenant("acme");
+    let acme_key = acme.request_key().await.unwrap();
+    assert_ne!(acme_key.public_key, key.public_key);
+    let signed = acme.request_timestamp("tenant").await.unwrap();
+    assert!(verify_signature(&signed, &acme_key));
+    let err = client.for_tenant("nobody").request_key().await.unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ApiError>().unwrap().code,
+        "unknown_tenant"
+    );
+
+    // The blocking client agrees
+    let blocking_url = server_url.clone();
+    let (blocking_key, signed) = task::spawn_blocking(move || {
+        let client = client::VtsClient::new(&blocking_url).unwrap();
+        let key = client.request_key().unwrap();
+        let signed = client
+            .request_file_timestamp(&b"file contents"[..])
+            .unwrap();
+        (key, signed)
+    })
+    .await
+    .unwrap();
+    assert_eq!(blocking_key.public_key, key.public_key);
+    assert!(verify_file_timestamp(&b"file contents"[..], &signed, &key).unwrap());
+}
+
+#[tokio::test]
+async fn test_vts_client_retries_with_same_idempotency_key() {
+    let failures = Arc::new(AtomicU32::new(2));
+    let keys = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let app = {
+        let (failures, keys) = (failures.clone(), keys.clone());
+        axum::Router::new().route(
+            "/sign",
+            axum::routing::post(move |headers: axum::http::HeaderMap| async move {
+                let key = headers["idempotency-key"].to_str().unwrap().to_string();
+                keys.lock().unwrap().push(key);
+                if failures
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    .is_ok()
+                {
+                    return (
+                        axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                        axum::Json(serde_json::json!({ "error": "busy", "code": "overloaded" })),
+                    );
+                }
+                (
+                    axum::http::StatusCode::OK,
+                    axum::Json(serde_json::json!({
+                        "request": "POST",
+                        "message": "retried",
+                        "time-signed": "2024-01-01T00:00:00Z",
+                        "signature": "c2ln",
+                    })),
+                )
+            }),
+        )
+    };
+    let server_url = spawn_router(app).await;
+
+    // Two 503s, then success on the third attempt, all with one key
+    let client =
+        client::nonblocking::VtsClient::with_options(&server_url, fast_retries(2)).unwrap();
+    let signed = client.request_timestamp("retried").await.unwrap();
+    assert_eq!(signed.signature, "c2ln");
+    {
+        let keys = keys.lock().unwrap();
+        assert_eq!(keys.len(), 3);
+        assert!(keys.iter().all(|k| *k == keys[0]));
+    }
+
+    // Out of retries: the last error response comes back
+    failures.store(2, Ordering::SeqCst);
+    keys.lock().unwrap().clear();
+    let client =
+        client::nonblocking::VtsClient::with_options(&server_url, fast_retries(1)).unwrap();
+    let err = client.request_timestamp("retried").await.unwrap_err();
+    assert_eq!(err.downcast_ref::<ApiError>().unwrap().code, "overloaded");
+    assert_eq!(keys.lock().unwrap().len(), 2);
+
+    // A new request gets a new key
+    client.request_timestamp("retried").await.unwrap();
+    let keys = keys.lock().unwrap();
+    assert_ne!(keys[2], keys[0]);
+}
+
+/// Records every attempt and failed check, and answers `GET /key` from
+/// the first one it saw
+#[derive(Default)]
+struct Recorder {
+    seen: std::sync::Mutex<Vec<String>>,
+    failures: std::sync::Mutex<Vec<String>>,
+    key: std::sync::Mutex<Option<Vec<u8>>>,
+}
+
+impl Interceptor for Recorder {
+    fn on_request(&self, request: &RequestInfo) -> Option<Vec<u8>> {
+        match request.url.ends_with("/key") {
+            true => self.key.lock().unwrap().clone(),
+            false => None,
+        }
+    }
+
+    fn on_response(&self, request: &RequestInfo, response: &ResponseInfo) {
+        let path = request.url.rsplit('/').next().unwrap_or_default();
+        self.seen.lock().unwrap().push(format!(
+            "{} /{} {:?}",
+            request.method, path, response.status
+        ));
+        if path == "key" && response.status == Some(200) {
+            *self.key.lock().unwrap() = Some(response.body.to_vec());
+        }
+    }
+
+    fn on_verify_failure(&self, url: &str, failure: &VerifyFailure) {
+        let VerifyFailure::Response(e) = failure else {
+            panic!("unexpected {:?}", failure);
+        };
+        self.failures.lock().unwrap().push(format!(
+            "{}: {}",
+            url.rsplit('/').next().unwrap_or_default(),
+            e
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_vts_client_interceptors() {
+    let config = ServerConfig {
+        http_signatures: true,
+        ..test_config()
+    };
+    let server_url = format!("http://{}", spawn_configured_server(&[], config).await);
+    let recorder = Arc::new(Recorder::default());
+    let client = client::nonblocking::VtsClient::with_options(&server_url, fast_retries(0))
+        .unwrap()
+        .with_interceptor(recorder.clone());
+
+    // Each attempt is seen with its answer, errors included
+    let key = client.request_key().await.unwrap();
+    let signed = client.request_timestamp("Hello").await.unwrap();
+    assert!(verify_signature(&signed, &key));
+    let err = client.for_tenant("nobody").request_timestamp("Hello").await;
+    assert!(err.is_err());
+    assert_eq!(
+        *recorder.seen.lock().unwrap(),
+        [
+            "GET /key Some(200)",
+            "POST /sign Some(200)",
+            "POST /sign Some(404)"
+        ]
+    );
+
+    // The cached key is answered without a request
+    let cached = client.request_key().await.unwrap();
+    assert_eq!(cached.public_key, key.public_key);
+    assert_eq!(recorder.seen.lock().unwrap().len(), 3);
+
+    // A response that fails its check is reported, then fails the call
+    let other = KeyPair::from_seed(&[9; 32]).to_public_key();
+    let options = ClientOptions {
+        response_key: Some(other),
+        ..fast_retries(0)
+    };
+    let client = client::nonblocking::VtsClient::with_options(&server_url, options)
+        .unwrap()
+        .with_interceptor(recorder.clone());
+    let err = client.request_timestamp("Hello").await.unwrap_err();
+    let err = err.downcast_ref::<SignatureError>().unwrap();
+    assert_eq!(
+        *recorder.failures.lock().unwrap(),
+        [format!("sign: {}", err)]
+    );
+}
+
+#[tokio::test]
+async fn test_vts_client_timeout_and_proxy() {
+    let hits = Arc::new(AtomicU32::new(0));
+    let app = {
+        let hits = hits.clone();
+        axum::Router::new().route(
+            "/key",
+            axum::routing::get(move || async move {
+                hits.fetch_add(1, Ordering::SeqCst);
+                sleep(Duration::from_secs(2)).await;
+                "too late"
+            }),
+        )
+    };
+    let slow_url = spawn_router(app).await;
+    let options = ClientOptions {
+        timeout: Some(Duration::from_millis(100)),
+        ..fast_retries(1)
+    };
+    let client = client::nonblocking::VtsClient::with_options(&slow_url, options).unwrap();
+    let err = client.request_key().await.unwrap_err();
+    assert!(err.downcast_ref::<reqwest::Error>().unwrap().is_timeout());
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    // Through a proxy: the server answers the absolute-URI request itself,
+    // so it only works if the request really goes to the proxy
+    let addr = spawn_server().await;
+    let options = ClientOptions {
+        proxy: Some(format!("http://{}", addr)),
+        ..Default::default()
+    };
+    let client =
+        client::nonblocking::VtsClient::with_options("http://vts.invalid", options).unwrap();
+    let key = client.request_key().await.unwrap();
+    let signed = client.request_timestamp("proxied").await.unwrap();
+    assert!(verify_signature(&signed, &key));
+
+    let options = ClientOptions {
+        proxy: Some("not a url".to_string()),
+        ..Default::default()
+    };
+    assert!(client::nonblocking::VtsClient::with_options("http://vts.invalid", options).is_err());
+}
+
+#[tokio::test]
+async fn test_vts_client_honours_retry_after_and_caller_keys() {
+    let failures = Arc::new(AtomicU32::new(0));
+    let app = {
+        let failures = failures.clone();
+        axum::Router::new().route(
+            "/version",
+            axum::routing::get(move || async move {
+                if failures
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    .is_ok()
+                {
+                    return (
+                        axum::http::StatusCode::TOO_MANY_REQUESTS,
+                        [("retry-after", "1")],
+                        "slow down",
+                    )
+                        .into_response();
+                }
+                axum::Json(serde_json::json!({
+                    "request": "GET",
+                    "server-version": "0.0.0",
+                    "protocol-versions": ["v1"],
+                    "features": [],
+                }))
+                .into_response()
+            }),
+        )
+    };
+    let server_url = spawn_router(app).await;
+
+    // The server's Retry-After wins over the (much shorter) backoff...
+    let options = ClientOptions {
+        jitter: false,
+        ..fast_retries(1)
+    };
+    let client =
+        client::nonblocking::VtsClient::with_options(&server_url, options.clone()).unwrap();
+    failures.store(1, Ordering::SeqCst);
+    let started = std::time::Instant::now();
+    client.request_version().await.unwrap();
+    assert!(started.elapsed() >= Duration::from_secs(1));
+
+    // ...up to max_backoff
+    let client = client::nonblocking::VtsClient::with_options(
+        &server_url,
+        ClientOptions {
+            max_backoff: Duration::from_millis(50),
+            ..options
+        },
+    )
+    .unwrap();
+    failures.store(1, Ordering::SeqCst);
+    let started = std::time::Instant::now();
+    client.request_version().await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    // A caller-chosen key gets the original timestamp back, even from a
+    // new client
+    let addr = spawn_configured_server(&[], sign_cache_config(false)).await;
+    let server_url = format!("http://{}", addr);
+    let first = client::nonblocking::VtsClient::new(&server_url)
+        .unwrap()
+        .request_timestamp_with_key("once", "order-42")
+        .await
+        .unwrap();
+    let client = client::nonblocking::VtsClient::new(&server_url).unwrap();
+    let again = client
+        .request_timestamp_with_key("once", "order-42")
+        .await
+        .unwrap();
+    assert_eq!(
+        (&again.time_signed, &again.signature),
+        (&first.time_signed, &first.signature)
+    );
+    let other = client.request_timestamp("once").await.unwrap();
+    assert_ne!(other.signature, first.signature);
+
+    // A per-request timeout on the same pool
+    let app = axum::Router::new().route(
+        "/key",
+        axum::routing::get(|| async {
+            sleep(Duration::from_millis(300)).await;
+            "late"
+        }),
+    );
+    let client = client::nonblocking::VtsClient::with_options(
+        &spawn_router(app).await,
+        ClientOptions {
+            timeout: Some(Duration::from_secs(5)),
+            ..fast_retries(0)
+        },
+    )
+    .unwrap();
+    let err = client
+        .with_timeout(Duration::from_millis(50))
+        .request_key()
+        .await
+        .unwrap_err();
+    assert!(err.downcast_ref::<reqwest::Error>().unwrap().is_timeout());
+    // (the slow answer arrives, but isn't a key)
+    let err = client.request_key().await.unwrap_err();
+    assert!(err.downcast_ref::<serde_json::Error>().unwrap().is_syntax());
+}
+
+#[tokio::test]
+async fn test_verify_signatures_batch() {
+    let addr = spawn_server().await;
+    let client = client::nonblocking::VtsClient::new(&format!("http://{}", addr)).unwrap();
+    let key = client.request_key().await.unwrap();
+    let mut archive = Vec::new();
+    for i in 0..80 {
+        archive.push(
+            client
+                .request_timestamp(&format!("doc {}", i))
+                .await
+                .unwrap(),
+        );
+    }
+    archive[3].message.push('!');
+    archive[40].signature = "not base64!".to_string();
+    archive[79].signature_alg = Some("rsa".to_string());
+
+    let outcomes = verify_signatures_batch(&archive, &key);
+    let one_by_one: Vec<_> = archive
+        .iter()
+        .map(|signed| verify_signature_with(signed, &key, &VerifyOptions::default()))
+        .collect();
+    assert_eq!(outcomes, one_by_one);
+    assert_eq!(outcomes[3], VerifyOutcome::BadSignature);
+    assert_eq!(outcomes[40], VerifyOutcome::BadEncoding);
+    assert_eq!(outcomes[79], VerifyOutcome::UnsupportedAlgorithm);
+    assert_eq!(outcomes.iter().filter(|o| o.is_ok()).count(), 77);
+
+    // Every timestamp fails under a key that can't be parsed
+    let bad_key = EcdsaVerificationKey {
+        public_key: "AAAA".to_string(),
+        ..key
+    };
+    let outcomes = verify_signatures_batch(&archive[..2], &bad_key);
+    assert_eq!(outcomes, [VerifyOutcome::BadKey; 2]);
+}
+
+/// Blocks every signature while `hold` is set, like a slow HSM
+struct HeldSigner {
+    keypair: KeyPair,
+    hold: AtomicBool,
+    signing: AtomicU32,
+}
+
+impl Signer for HeldSigner {
+    fn sign(&self, message: &[u8]) -> Result<k256::ecdsa::Signature, SignerError> {
+        self.signing.fetch_add(1, Ordering::SeqCst);
+        while self.hold.load(Ordering::SeqCst) {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        self.signing.fetch_sub(1, Ordering::SeqCst);
+        Ok(self.keypair.sign(message))
+    }
+
+    fn public_key(&self) -> ecdsa_lib::PublicKey {
+        self.keypair.to_public_key()
+    }
+}
+
+#[tokio::test]
+async fn test_sign_pool_backpressure() {
+    let signer = Arc::new(HeldSigner {
+        keypair: KeyPair::from_seed(&[11u8; 32]),
+        hold: AtomicBool::new(true),
+        signing: AtomicU32::new(0),
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_url = format!("http://{}", listener.local_addr().unwrap());
+    let config = ServerConfig {
+        sign_pool: SignPoolConfig {
+            workers: 1,
+            queue_depth: 0,
+            retry_after_secs: 3,
+        },
+        ..test_config()
+    };
+    let handle = signer.clone();
+    task::spawn(async move {
+        server::run_server_with_signers(
+            Box::new(handle),
+            TenantSigners::new(),
+            config,
+            Box::new(SystemClock),
+            listener,
+        )
+        .await
+        .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+    });
+    sleep(Duration::from_millis(100)).await;
+
+    // One request takes the only worker and blocks in the signer...
+    let first = task::spawn({
+        let server_url = server_url.clone();
+        async move { nonblocking::request_timestamp(&server_url, "first").await }
+    });
+    while signer.signing.load(Ordering::SeqCst) == 0 {
+        sleep(Duration::from_millis(5)).await;
+    }
+
+    // ...without holding up the (single-threaded) runtime
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+
+    // With no room to queue, the next is refused at once
+    let resp = reqwest::Client::new()
+        .post(format!("{}/sign", server_url))
+        .json(&serde_json::json!({ "message": "second" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 503);
+    assert_eq!(resp.headers()["retry-after"], "3");
+    let body: ApiError = resp.json().await.unwrap();
+    assert_eq!(body.code, "overloaded");
+
+    // So are the other signatures, which share the pool
+    for path in ["/time?nonce=queued", "/revocations"] {
+        let resp = reqwest::get(format!("{}{}", server_url, path))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 503, "{}", path);
+        assert_eq!(resp.headers()["retry-after"], "3", "{}", path);
+    }
+
+    // The first completes once the signer lets go, and the worker is free
+    signer.hold.store(false, Ordering::SeqCst);
+    let signed = first.await.unwrap().unwrap();
+    assert!(verify_signature(&signed, &key));
+    let signed = nonblocking::request_timestamp(&server_url, "third")
+        .await
+        .unwrap();
+    assert!(verify_signature(&signed, &key));
+}
+
+/// Panics in every signature once `panic` is set, like a buggy HSM driver
+struct PanickingSigner {
+    keypair: KeyPair,
+    panic: AtomicBool,
+}
+
+impl Signer for PanickingSigner {
+    fn sign(&self, message: &[u8]) -> Result<k256::ecdsa::Signature, SignerError> {
+        if self.panic.load(Ordering::SeqCst) {
+            panic!("signer driver crashed");
+        }
+        Ok(self.keypair.sign(message))
+    }
+
+    fn public_key(&self) -> ecdsa_lib::PublicKey {
+        self.keypair.to_public_key()
+    }
+}
+
+#[tokio::test]
+async fn test_sign_pool_answers_signer_panics_with_500() {
+    let signer = Arc::new(PanickingSigner {
+        keypair: KeyPair::from_seed(&[13u8; 32]),
+        panic: AtomicBool::new(false),
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_url = format!("http://{}", listener.local_addr().unwrap());
+    let config = ServerConfig {
+        sign_pool: SignPoolConfig {
+            workers: 1,
+            queue_depth: 0,
+            retry_after_secs: 3,
+        },
+        ..test_config()
+    };
+    let handle = signer.clone();
+    task::spawn(async move {
+        server::run_server_with_signers(
+            Box::new(handle),
+            TenantSigners::new(),
+            config,
+            Box::new(SystemClock),
+            listener,
+        )
+        .await
+        .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+    });
+    sleep(Duration::from_millis(100)).await;
+
+    // Every route signing on the pool answers a panic as a signer error,
+    // and frees the one worker for the next
+    signer.panic.store(true, Ordering::SeqCst);
+    let client = reqwest::Client::new();
+    for _ in 0..2 {
+        let requests = [
+            client
+                .post(format!("{}/sign", server_url))
+                .json(&serde_json::json!({ "message": "crash" })),
+            client.get(format!("{}/time?nonce=crash", server_url)),
+            client.get(format!("{}/revocations", server_url)),
+        ];
+        for request in requests {
+            let resp = request.send().await.unwrap();
+            assert_eq!(resp.status(), 500);
+            assert_eq!(resp.json::<ApiError>().await.unwrap().code, "signer_error");
+        }
+    }
+    signer.panic.store(false, Ordering::SeqCst);
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    let signed = nonblocking::request_timestamp(&server_url, "recovered")
+        .await
+        .unwrap();
+    assert!(verify_signature(&signed, &key));
+}
+
+#[tokio::test]
+async fn test_request_limits_queue_and_shed() {
+    let signer = Arc::new(HeldSigner {
+        keypair: KeyPair::from_seed(&[12u8; 32]),
+        hold: AtomicBool::new(true),
+        signing: AtomicU32::new(0),
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_url = format!("http://{}", listener.local_addr().unwrap());
+    let config = ServerConfig {
+        limits: LimitsConfig {
+            max_concurrent_requests: 1,
+            queue_depth: 1,
+            queue_timeout_ms: 300,
+            retry_after_secs: 2,
+        },
+        ..test_config()
+    };
+    let handle = signer.clone();
+    task::spawn(async move {
+        server::run_server_with_signers(
+            Box::new(handle),
+            TenantSigners::new(),
+            config,
+            Box::new(SystemClock),
+            listener,
+        )
+        .await
+        .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+    });
+    sleep(Duration::from_millis(100)).await;
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+
+    // The only slot is taken by a request stuck in the signer, and the
+    // only queue place by a key request
+    let first = task::spawn({
+        let server_url = server_url.clone();
+        async move { nonblocking::request_timestamp(&server_url, "first").await }
+    });
+    while signer.signing.load(Ordering::SeqCst) == 0 {
+        sleep(Duration::from_millis(5)).await;
+    }
+    let queued = task::spawn({
+        let server_url = server_url.clone();
+        async move { nonblocking::request_key(&server_url).await }
+    });
+    sleep(Duration::from_millis(50)).await;
+
+    // Any route is shed at once
+    let started = std::time::Instant::now();
+    let resp = reqwest::get(format!("{}/version", server_url))
+        .await
+        .unwrap();
+    assert!(started.elapsed() < Duration::from_millis(250));
+    assert_eq!(resp.status(), 503);
+    assert_eq!(resp.headers()["retry-after"], "2");
+    assert!(resp.headers().contains_key("x-request-id"));
+    let body: ApiError = resp.json().await.unwrap();
+    assert_eq!(body.code, "overloaded");
+
+    // The queued one gives up after queue_timeout_ms
+    let err = queued.await.unwrap().unwrap_err();
+    assert_eq!(err.downcast_ref::<ApiError>().unwrap().code, "overloaded");
+
+    signer.hold.store(false, Ordering::SeqCst);
+    let signed = first.await.unwrap().unwrap();
+    assert!(verify_signature(&signed, &key));
+    nonblocking::request_version(&server_url).await.unwrap();
+}
+
+/// Spawns a read-only mirror of `upstream`, copying its log into `audit_file`
+async fn spawn_mirror(upstream: &str, audit_file: &std::path::Path) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = ServerConfig {
+        audit_file: audit_file.to_string_lossy().into_owned(),
+        mirror: MirrorConfig {
+            upstream: upstream.to_string(),
+            interval_secs: 1,
+        },
+        ..test_config()
+    };
+    task::spawn(async move {
+        server::run_mirror_with_listener(config, Box::new(SystemClock), listener)
+            .await
+            .unwrap_or_else(|e| eprintln!("Mirror error: {}", e));
+    });
+    sleep(Duration::from_millis(300)).await;
+    addr
+}
+
+#[tokio::test]
+async fn test_mirror_copies_the_log_and_refuses_to_sign() {
+    let dir = tempfile::tempdir().unwrap();
+    let upstream_log = dir.path().join("upstream.log");
+    let config = ServerConfig {
+        audit_file: upstream_log.to_string_lossy().into_owned(),
+        ..test_config()
+    };
+    let upstream_url = format!("http://{}", spawn_configured_server(&[], config).await);
+    for message in ["one", "two", "three"] {
+        nonblocking::request_timestamp(&upstream_url, message)
+            .await
+            .unwrap();
+    }
+
+    let mirror_log = dir.path().join("mirror.log");
+    let mirror_url = format!("http://{}", spawn_mirror(&upstream_url, &mirror_log).await);
+    let client = reqwest::Client::new();
+    let entries = |server_url: &str, since: u64| {
+        let request = client.get(format!("{}/v1/log/entries?since={}", server_url, since));
+        async move { request.send().await.unwrap().text().await.unwrap() }
+    };
+
+    // The mirror serves the upstream's key and a byte-for-byte copy of its log
+    let key = nonblocking::request_key(&mirror_url).await.unwrap();
+    assert_eq!(
+        key.public_key,
+        nonblocking::request_key(&upstream_url)
+            .await
+            .unwrap()
+            .public_key
+    );
+    assert_eq!(
+        entries(&mirror_url, 0).await,
+        fs::read_to_string(&upstream_log).unwrap()
+    );
+    assert_eq!(entries(&mirror_url, 2).await.lines().count(), 1);
+
+    // Lookups work on the copy
+    let hash = hex::encode(Sha256::digest(b"two"));
+    let body: serde_json::Value = client
+        .get(format!("{}/timestamp/by-hash/{}", mirror_url, hash))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["timestamps"].as_array().unwrap().len(), 1);
+
+    // Signing is refused, over every route
+    for path in ["/sign", "/v1/sign"] {
+        let resp = client
+            .post(format!("{}{}", mirror_url, path))
+            .json(&serde_json::json!({ "message": "four" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 403);
+        let error: ApiError = resp.json().await.unwrap();
+        assert_eq!(error.code, "read_only");
+    }
+    assert!(
+        nonblocking::request_timestamp(&mirror_url, "four")
+            .await
+            .is_err()
+    );
+
+    // New entries upstream show up after the next sync
+    nonblocking::request_timestamp(&upstream_url, "four")
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(1500)).await;
+    assert_eq!(entries(&mirror_url, 0).await.lines().count(), 4);
+    assert!(verify_file(&mirror_log, &key.key().unwrap()).is_ok());
+}
+
+#[tokio::test]
+async fn test_mirror_stops_at_a_rewritten_upstream_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let keys = generate_key_bytes();
+    let config = |name: &str| ServerConfig {
+        audit_file: dir.path().join(name).to_string_lossy().into_owned(),
+        ..test_config()
+    };
+
+    // The mirror already has two lines of one history...
+    let original = format!(
+        "http://{}",
+        spawn_server_with_keys(keys.clone(), config("original.log")).await
+    );
+    for message in ["one", "two"] {
+        nonblocking::request_timestamp(&original, message)
+            .await
+            .unwrap();
+    }
+    let mirror_log = dir.path().join("mirror.log");
+    fs::copy(dir.path().join("original.log"), &mirror_log).unwrap();
+    let copied = fs::read_to_string(&mirror_log).unwrap();
+
+    // ...and the upstream, under the same key, now tells another
+    let rewritten = format!(
+        "http://{}",
+        spawn_server_with_keys(keys, config("rewritten.log")).await
+    );
+    for message in ["uno", "dos", "tres"] {
+        nonblocking::request_timestamp(&rewritten, message)
+            .await
+            .unwrap();
+    }
+    let mirror_url = format!("http://{}", spawn_mirror(&rewritten, &mirror_log).await);
+
+    // Line 3 does not follow the copy's line 2, so nothing is appended
+    sleep(Duration::from_millis(1500)).await;
+    assert_eq!(fs::read_to_string(&mirror_log).unwrap(), copied);
+    let body = reqwest::get(format!("{}/log/entries", mirror_url))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(body, copied);
+}
+
+#[tokio::test]
+async fn test_mirror_proves_the_lines_it_synced() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = ServerConfig {
+        audit_file: dir
+            .path()
+            .join("upstream.log")
+            .to_string_lossy()
+            .into_owned(),
+        ..test_config()
+    };
+    let start = "2030-06-01T12:00:00Z"
+        .parse::<chrono::DateTime<chrono::Utc>>()
+        .unwrap();
+    let clock = Arc::new(FakeClock::new(start));
+    let upstream_url = format!(
+        "http://{}",
+        spawn_server_with_clock(&[], config, Box::new(clock.clone())).await
+    );
+    let upstream = client::nonblocking::VtsClient::new(&upstream_url).unwrap();
+    let mut signed = Vec::new();
+    for message in ["one", "two", "three"] {
+        signed.push(upstream.request_timestamp(message).await.unwrap());
+    }
+    let first = upstream.request_tree_head().await.unwrap();
+
+    let mirror_log = dir.path().join("mirror.log");
+    let mirror_url = format!("http://{}", spawn_mirror(&upstream_url, &mirror_log).await);
+    let mirror = client::nonblocking::VtsClient::new(&mirror_url).unwrap();
+    let key = mirror.request_key().await.unwrap();
+
+    // The mirror serves the upstream's head, and the synced lines are in it
+    assert_eq!(mirror.request_tree_head().await.unwrap(), first);
+    let inclusion = mirror.request_inclusion_proof(2, &first).await.unwrap();
+    let report = transparency::verify_timestamp_proof(&signed[1], &inclusion, &first, &key);
+    assert!(report.is_verified(), "{:?}", report);
+
+    // The day isn't closed before a line of a later one is synced
+    let day = |date: &str| reqwest::get(format!("{}/log/day/{}", mirror_url, date));
+    let resp = day("2030-06-01").await.unwrap();
+    assert_eq!(resp.status(), 503);
+    assert_eq!(resp.json::<ApiError>().await.unwrap().code, "not_synced");
+
+    // Lines synced later are in a head extending the first, and close the
+    // day before them
+    clock.set(start + chrono::Duration::days(1));
+    signed.push(upstream.request_timestamp("four").await.unwrap());
+    let second = upstream.request_tree_head().await.unwrap();
+    sleep(Duration::from_millis(1500)).await;
+    assert_eq!(mirror.request_tree_head().await.unwrap(), second);
+    mirror.request_consistency(&first, &second).await.unwrap();
+    let inclusion = mirror.request_inclusion_proof(4, &second).await.unwrap();
+    let report = transparency::verify_timestamp_proof(&signed[3], &inclusion, &second, &key);
+    assert!(report.is_verified(), "{:?}", report);
+    let upstream_root: DayRoot = reqwest::get(format!("{}/log/day/2030-06-01", upstream_url))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let body: serde_json::Value = day("2030-06-01?seq=2").await.unwrap().json().await.unwrap();
+    let root: DayRoot = serde_json::from_value(body.clone()).unwrap();
+    let proof: DayProof = serde_json::from_value(body["proof"].clone()).unwrap();
+    assert_eq!(root, upstream_root);
+    assert!(root.verify(&key.key().unwrap()));
+    let copied = fs::read_to_string(&mirror_log).unwrap();
+    let line: AuditEntry = serde_json::from_str(copied.lines().nth(1).unwrap()).unwrap();
+    assert!(proof.verify(&line.line_hash(), &root));
+}
+
+/// Config with the admin API on a free local port, its token and every key
+/// file in `dir`; returns the admin base URL too
+fn admin_config(dir: &std::path::Path) -> (ServerConfig, String) {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let token_file = dir.join("admin_token");
+    fs::write(&token_file, "0123456789abcdef0123456789abcdef\n").unwrap();
+    let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+    let mut config = ServerConfig {
+        private_key_file: path("private_key.bin"),
+        public_key_file: path("public_key.bin"),
+        admin: AdminConfig {
+            listen: format!("127.0.0.1:{}", port),
+            token_file: token_file.to_string_lossy().into_owned(),
+        },
+        ..test_config()
+    };
+    config.tenants.insert(
+        "cs55".to_string(),
+        TenantConfig {
+            private_key_file: Some(path("cs55_private_key.bin")),
+            public_key_file: Some(path("cs55_public_key.bin")),
+            ..Default::default()
+        },
+    );
+    (config, format!("http://127.0.0.1:{}", port))
+}
+
+#[tokio::test]
+async fn test_admin_api_pauses_rotates_and_reports() {
+    let dir = tempfile::tempdir().unwrap();
+    let (config, admin_url) = admin_config(dir.path());
+    let server_url = format!(
+        "http://{}",
+        spawn_configured_server(&["cs55"], config).await
+    );
+    let client = reqwest::Client::new();
+    let admin = |method: reqwest::Method, path: &str, token: &str| {
+        client
+            .request(method, format!("{}{}", admin_url, path))
+            .bearer_auth(token)
+            .send()
+    };
+    const TOKEN: &str = "0123456789abcdef0123456789abcdef";
+
+    // Only with the token, and only on the admin listener
+    let resp = client
+        .get(format!("{}/admin/stats", admin_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+    assert_eq!(resp.headers()["www-authenticate"], "Bearer");
+    let resp = admin(reqwest::Method::GET, "/admin/stats", "not-the-token")
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+    let resp = client
+        .get(format!("{}/admin/stats", server_url))
+        .bearer_auth(TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    // Stats
+    let old = nonblocking::request_timestamp(&server_url, "before")
+        .await
+        .unwrap();
+    let stats: serde_json::Value = admin(reqwest::Method::GET, "/admin/stats", TOKEN)
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats["paused"], false);
+    assert_eq!(stats["tenants"][0]["tenant"], serde_json::Value::Null);
+    assert_eq!(stats["tenants"][0]["serial"], 1);
+    assert_eq!(stats["tenants"][1]["tenant"], "cs55");
+    assert_eq!(stats["tenants"][1]["serial"], 0);
+
+    // Paused, nothing is signed
+    let resp = admin(reqwest::Method::POST, "/admin/pause-signing", TOKEN)
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client
+        .post(format!("{}/sign", server_url))
+        .json(&serde_json::json!({ "message": "paused" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 503);
+    let error: ApiError = resp.json().await.unwrap();
+    assert_eq!(error.code, "signing_paused");
+    admin(reqwest::Method::POST, "/admin/resume-signing", TOKEN)
+        .await
+        .unwrap();
+    nonblocking::request_timestamp(&server_url, "resumed")
+        .await
+        .unwrap();
+
+    // After a rotation, /key and every new timestamp use the new key, which
+    // is in the key files
+    let old_key = nonblocking::request_key(&server_url).await.unwrap();
+    let rotated: serde_json::Value = admin(reqwest::Method::POST, "/admin/rotate-key", TOKEN)
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(rotated["previous-public-key"], old_key.public_key);
+    let new_key = nonblocking::request_key(&server_url).await.unwrap();
+    assert_eq!(rotated["public-key"], new_key.public_key);
+    assert_ne!(new_key.public_key, old_key.public_key);
+    assert_eq!(
+        fs::read(dir.path().join("public_key.bin")).unwrap(),
+        new_key.key().unwrap().to_sec1_bytes()
+    );
+    let new = nonblocking::request_timestamp(&server_url, "after")
+        .await
+        .unwrap();
+    assert!(verify_signature(&new, &new_key));
+    assert!(!verify_signature(&new, &old_key));
+    assert!(verify_signature(&old, &old_key));
+
+    // A named tenant's key rotates on its own
+    let cs55 = nonblocking::request_key(&format!("{}/t/cs55", server_url))
+        .await
+        .unwrap();
+    let resp = admin(
+        reqwest::Method::POST,
+        "/admin/rotate-key?tenant=cs55",
+        TOKEN,
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 200);
+    let rotated = nonblocking::request_key(&format!("{}/t/cs55", server_url))
+        .await
+        .unwrap();
+    assert_ne!(rotated.public_key, cs55.public_key);
+    assert_eq!(
+        nonblocking::request_key(&server_url)
+            .await
+            .unwrap()
+            .public_key,
+        new_key.public_key
+    );
+    let resp = admin(
+        reqwest::Method::POST,
+        "/admin/rotate-key?tenant=nope",
+        TOKEN,
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_jwks_lists_active_and_retired_keys() {
+    let dir = tempfile::tempdir().unwrap();
+    let (config, admin_url) = admin_config(dir.path());
+    let retired = KeyPair::generate().to_public_key();
+    let retired_file = dir.path().join("retired_public_key.bin");
+    fs::write(&retired_file, retired.to_sec1_bytes()).unwrap();
+    let config = ServerConfig {
+        extra_keys: vec![KeyFiles {
+            private_key_file: dir
+                .path()
+                .join("second_private.bin")
+                .to_string_lossy()
+                .into(),
+            public_key_file: dir
+                .path()
+                .join("second_public.bin")
+                .to_string_lossy()
+                .into(),
+            ..Default::default()
+        }],
+        retired_public_key_files: vec![retired_file.to_string_lossy().into()],
+        ..config
+    };
+    let server_url = format!(
+        "http://{}",
+        spawn_configured_server(&["cs55"], config).await
+    );
+    let jwks = |path: &str| {
+        let url = format!("{}{}/.well-known/jwks.json", server_url, path);
+        async move {
+            reqwest::get(url)
+                .await
+                .unwrap()
+                .json::<jws::JwkSet>()
+                .await
+                .unwrap()
+                .keys
+        }
+    };
+
+    // The keys of /keys, then the retired one, each usable by JOSE libraries
+    let keys = client::nonblocking::VtsClient::new(&server_url)
+        .unwrap()
+        .request_keys()
+        .await
+        .unwrap();
+    let set = jwks("").await;
+    assert_eq!(set.len(), 3);
+    for (jwk, key) in set.iter().zip(&keys) {
+        assert_eq!(Some(&jwk.kid), key.kid.as_ref());
+        assert_eq!(jwk.key(), key.key());
+    }
+    assert_eq!(set[2].key(), Some(retired));
+    assert_eq!(set[2].kid, jws::key_id(&retired));
+    for jwk in &set {
+        assert_eq!(
+            (&*jwk.kty, &*jwk.crv, &*jwk.use_, &*jwk.alg),
+            ("EC", "secp256k1", "sig", "ES256K")
+        );
+    }
+    let raw: serde_json::Value = reqwest::get(format!("{}/.well-known/jwks.json", server_url))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(raw["keys"][0]["use"], "sig");
+
+    // A rotated-out key stays listed
+    let old_kid = keys[0].kid.clone().unwrap();
+    reqwest::Client::new()
+        .post(format!("{}/admin/rotate-key", admin_url))
+        .bearer_auth("0123456789abcdef0123456789abcdef")
+        .send()
+        .await
+        .unwrap();
+    let set = jwks("").await;
+    assert_eq!(set.len(), 4);
+    assert_ne!(set[0].kid, old_kid);
+    assert_eq!(set[3].kid, old_kid);
+
+    // A tenant lists its own keys
+    let set = jwks("/t/cs55").await;
+    assert_eq!(set.len(), 1);
+    assert_eq!(
+        set[0].key(),
+        nonblocking::request_key(&format!("{}/t/cs55", server_url))
+            .await
+            .unwrap()
+            .key()
+    );
+}
+
+#[tokio::test]
+async fn test_admin_api_keeps_the_audit_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let (config, admin_url) = admin_config(dir.path());
+    let config = ServerConfig {
+        audit_file: dir.path().join("audit.log").to_string_lossy().into_owned(),
+        ..config
+    };
+    spawn_configured_server(&["cs55"], config).await;
+    let rotate = |query: &str| {
+        reqwest::Client::new()
+            .post(format!("{}/admin/rotate-key{}", admin_url, query))
+            .bearer_auth("0123456789abcdef0123456789abcdef")
+            .send()
+    };
+
+    let resp = rotate("").await.unwrap();
+    assert_eq!(resp.status(), 409);
+    let error: ApiError = resp.json().await.unwrap();
+    assert_eq!(error.code, "audit_key");
+    assert_eq!(rotate("?tenant=cs55").await.unwrap().status(), 200);
+}
+
+#[tokio::test]
+async fn test_admin_api_compacts_old_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let (config, admin_url) = admin_config(dir.path());
+    let audit_file = dir.path().join("audit.log");
+    let mut config = ServerConfig {
+        audit_file: audit_file.to_string_lossy().into_owned(),
+        ..config
+    };
+    config.retention.full_days = 1;
+    let start = "2030-01-01T00:00:00Z"
+        .parse::<chrono::DateTime<chrono::Utc>>()
+        .unwrap();
+    let clock = Arc::new(FakeClock::new(start));
+    let addr = spawn_server_with_clock(&["cs55"], config, Box::new(clock.clone())).await;
+    let server_url = format!("http://{}", addr);
+    let key = nonblocking::request_key(&server_url)
+        .await
+        .unwrap()
+        .key()
+        .unwrap();
+    let admin = |method: reqwest::Method, path: &str| {
+        reqwest::Client::new()
+            .request(method, format!("{}{}", admin_url, path))
+            .bearer_auth("0123456789abcdef0123456789abcdef")
+            .send()
+    };
+    let compact = || async {
+        let resp = admin(reqwest::Method::POST, "/admin/compact")
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        resp.json::<serde_json::Value>().await.unwrap()
+    };
+
+    // Nothing is a day old yet
+    nonblocking::request_timestamp(&server_url, "old")
+        .await
+        .unwrap();
+    nonblocking::request_timestamp(&server_url, "old")
+        .await
+        .unwrap();
+    assert_eq!(compact().await["moved"], 0);
+
+    // Two days on, the first two lines are compacted
+    clock.set(start + chrono::Duration::days(2));
+    nonblocking::request_timestamp(&server_url, "new")
+        .await
+        .unwrap();
+    let compacted = compact().await;
+    assert_eq!(compacted["moved"], 2);
+    assert_eq!(compacted["full-entries"], 1);
+    let status: serde_json::Value = admin(reqwest::Method::GET, "/admin/compaction")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["compacted-entries"], 2);
+    assert_eq!(status["checkpoint"]["count"], 2);
+    assert_eq!(verify_file(&audit_file, &key).unwrap(), 3);
+
+    // Looked up by hash, they come with proofs instead of signatures
+    let hash = hex::encode(Sha256::digest(b"old"));
+    let found: serde_json::Value =
+        reqwest::get(format!("{}/timestamp/by-hash/{}", server_url, hash))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    assert_eq!(found["timestamps"].as_array().unwrap().len(), 0);
+    let proofs: Vec<CompactedProof> = serde_json::from_value(found["compacted"].clone()).unwrap();
+    assert_eq!(proofs.len(), 2);
+    assert!(proofs.iter().all(|proof| proof.verify(&key)));
+
+    // Their lines are gone from /log/entries
+    let resp = reqwest::get(format!("{}/log/entries", server_url))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 410);
+    assert_eq!(resp.json::<ApiError>().await.unwrap().code, "compacted");
+    let lines = reqwest::get(format!("{}/log/entries?since=2", server_url))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(lines.lines().count(), 1);
+
+    // Without [retention] there is nothing to compact by
+    let other = tempfile::tempdir().unwrap();
+    let (config, admin_url) = admin_config(other.path());
+    spawn_configured_server(&["cs55"], config).await;
+    let resp = reqwest::Client::new()
+        .post(format!("{}/admin/compact", admin_url))
+        .bearer_auth("0123456789abcdef0123456789abcdef")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 409);
+    assert_eq!(
+        resp.json::<ApiError>().await.unwrap().code,
+        "retention_disabled"
+    );
+}
+
+#[tokio::test]
+async fn test_day_roots_prove_lines_and_go_into_tokens() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = ServerConfig {
+        audit_file: dir.path().join("audit.log").to_string_lossy().into_owned(),
+        ..test_config()
+    };
+    let start = "2030-06-01T12:00:00Z"
+        .parse::<chrono::DateTime<chrono::Utc>>()
+        .unwrap();
+    let clock = Arc::new(FakeClock::new(start));
+    let addr = spawn_server_with_clock(&[], config, Box::new(clock.clone())).await;
+    let server_url = format!("http://{}", addr);
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    let client = reqwest::Client::new();
+    let jws_token = |message: &'static str| {
+        let request = client
+            .post(format!("{}/sign?format=jws", server_url))
+            .json(&serde_json::json!({ "message": message }));
+        async move { request.send().await.unwrap().text().await.unwrap() }
+    };
+    let day = |path: &str| reqwest::get(format!("{}/log/day/{}", server_url, path));
+
+    // The first day has no day before it with lines, and isn't over yet
+    let first = jws_token("monday").await;
+    jws_token("also monday").await;
+    assert_eq!(jws::verify(&first, &key).unwrap().day_root, None);
+    let resp = day("2030-06-01").await.unwrap();
+    assert_eq!(resp.status(), 409);
+    assert_eq!(resp.json::<ApiError>().await.unwrap().code, "day_open");
+    assert_eq!(day("June-1st").await.unwrap().status(), 400);
+
+    // Once it is, its signed root proves each of its lines
+    clock.set(start + chrono::Duration::days(1));
+    let body: serde_json::Value = day("2030-06-01?seq=2").await.unwrap().json().await.unwrap();
+    let root: DayRoot = serde_json::from_value(body.clone()).unwrap();
+    let proof: DayProof = serde_json::from_value(body["proof"].clone()).unwrap();
+    assert_eq!((root.date.as_str(), root.count), ("2030-06-01", 2));
+    assert!(root.verify(&key.key().unwrap()));
+    let hash = hex::encode(Sha256::digest(b"also monday"));
+    let found: serde_json::Value =
+        reqwest::get(format!("{}/timestamp/by-hash/{}", server_url, hash))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    let line: AuditEntry = serde_json::from_value(found["timestamps"][0]["proof"].clone()).unwrap();
+    assert!(proof.verify(&line.line_hash(), &root));
+    assert!(!proof.verify(&[0u8; 32], &root));
+    assert_eq!(day("2030-06-01?seq=3").await.unwrap().status(), 404);
+
+    // Tokens of the next day carry it
+    let next = jws_token("tuesday").await;
+    assert_eq!(jws::verify(&next, &key).unwrap().day_root, Some(root.root));
+    let empty: DayRoot = day("2030-05-31").await.unwrap().json().await.unwrap();
+    assert_eq!(empty.count, 0);
+}
+
+#[tokio::test]
+async fn test_consistency_proofs_between_tree_heads() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = ServerConfig {
+        audit_file: dir.path().join("audit.log").to_string_lossy().into_owned(),
+        ..test_config()
+    };
+    let addr = spawn_configured_server(&[], config).await;
+    let server_url = format!("http://{}", addr);
+    let client = client::nonblocking::VtsClient::new(&server_url).unwrap();
+
+    // Heads at 1, 3 and 4 lines, each extending the ones before
+    let mut heads = Vec::new();
+    for signs in [1, 2, 1] {
+        for _ in 0..signs {
+            client.request_timestamp("consistent").await.unwrap();
+        }
+        heads.push(client.request_tree_head().await.unwrap());
+    }
+    let counts: Vec<u64> = heads.iter().map(|head| head.count).collect();
+    assert_eq!(counts, [1, 3, 4]);
+    for (i, old) in heads.iter().enumerate() {
+        for new in &heads[i..] {
+            client.request_consistency(old, new).await.unwrap();
+        }
+    }
+
+    // A head the log never had doesn't verify, nor does the proof backwards
+    let forged = TreeHead {
+        root: hex::encode([7u8; 32]),
+        ..heads[1].clone()
+    };
+    let err = client
+        .request_consistency(&heads[0], &forged)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("does not extend"), "{}", err);
+    let resp = reqwest::get(format!("{}/log/consistency?old=3&new=1", server_url))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let resp = reqwest::get(format!("{}/log/consistency?old=1&new=99", server_url))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+    assert_eq!(resp.json::<ApiError>().await.unwrap().code, "unknown_count");
+}
+
+#[tokio::test]
+async fn test_inclusion_proofs_tie_timestamps_to_tree_heads() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = ServerConfig {
+        audit_file: dir.path().join("audit.log").to_string_lossy().into_owned(),
+        ..test_config()
+    };
+    let addr = spawn_configured_server(&[], config).await;
+    let server_url = format!("http://{}", addr);
+    let client = client::nonblocking::VtsClient::new(&server_url).unwrap();
+    let key = client.request_key().await.unwrap();
+
+    // Three timestamps; the middle one's line is found through its hash
+    let mut signed = Vec::new();
+    for message in ["before", "included", "after"] {
+        signed.push(client.request_timestamp(message).await.unwrap());
+    }
+    let head = client.request_tree_head().await.unwrap();
+    let hash = hex::encode(Sha256::digest(b"included"));
+    let found: serde_json::Value =
+        reqwest::get(format!("{}/timestamp/by-hash/{}", server_url, hash))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    let seq = found["timestamps"][0]["proof"]["seq"].as_u64().unwrap();
+    assert_eq!(seq, 2);
+    let inclusion = client.request_inclusion_proof(seq, &head).await.unwrap();
+    assert_eq!(inclusion.count, head.count);
+    let report = transparency::verify_timestamp_proof(&signed[1], &inclusion, &head, &key);
+    assert!(report.is_verified(), "{:?}", report);
+
+    // Each step fails on its own: another timestamp, a head the log never
+    // had, another key
+    let report = transparency::verify_timestamp_proof(&signed[0], &inclusion, &head, &key);
+    assert_eq!(
+        (report.signature, report.line),
+        (Check::Verified, Check::Failed)
+    );
+    let forged = TreeHead {
+        root: hex::encode([7u8; 32]),
+        ..head.clone()
+    };
+    let report = transparency::verify_timestamp_proof(&signed[1], &inclusion, &forged, &key);
+    assert_eq!(
+        (report.inclusion, report.tree_head),
+        (Check::Failed, Check::Failed)
+    );
+    assert_eq!(report.line, Check::Verified);
+    let (_, other_key) = generate_key_bytes();
+    let other_key = EcdsaVerificationKey {
+        public_key: hex::encode(other_key),
+        format: Some("hex".to_string()),
+        ..client.request_key().await.unwrap()
+    };
+    let report = transparency::verify_timestamp_proof(&signed[1], &inclusion, &head, &other_key);
+    assert_eq!(
+        (report.signature, report.tree_head),
+        (Check::Failed, Check::Failed)
+    );
+    assert_eq!(
+        (report.line, report.inclusion),
+        (Check::Verified, Check::Verified)
+    );
+    let bad_key = EcdsaVerificationKey {
+        public_key: "AAAA".to_string(),
+        ..key
+    };
+    let report = transparency::verify_timestamp_proof(&signed[1], &inclusion, &head, &bad_key);
+    assert_eq!(report.signature, Check::Unverifiable);
+    assert!(!report.is_verified());
+
+    // The proof under an older head doesn't hold under the current one
+    let older: serde_json::Value = reqwest::get(format!("{}/log/proof?seq=2&count=2", server_url))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let older: InclusionProof = serde_json::from_value(older).unwrap();
+    assert_eq!(older.line, inclusion.line);
+    assert!(!older.verify(&head));
+
+    for (query, status, code) in [
+        ("seq=0", 400, "invalid_query"),
+        ("seq=3&count=2", 400, "invalid_query"),
+        ("seq=4", 404, "unknown_seq"),
+        ("seq=1&count=99", 404, "unknown_seq"),
+    ] {
+        let resp = reqwest::get(format!("{}/log/proof?{}", server_url, query))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), status, "{}", query);
+        assert_eq!(
+            resp.json::<ApiError>().await.unwrap().code,
+            code,
+            "{}",
+            query
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_monitor_alerts_on_split_views() {
+    let dir = tempfile::tempdir().unwrap();
+    let (private_key, public_key) = generate_key_bytes();
+    let key = PublicKey::from_sec1_bytes(&public_key).unwrap();
+
+    // One key, two logs: what a server showing each vantage point its own
+    // view would serve
+    let mut urls = Vec::new();
+    for i in 0..2 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        urls.push(format!("http://{}", listener.local_addr().unwrap()));
+        let config = ServerConfig {
+            audit_file: dir
+                .path()
+                .join(format!("audit{}.log", i))
+                .to_string_lossy()
+                .into_owned(),
+            ..test_config()
+        };
+        let (private_key, public_key) = (private_key.clone(), public_key.clone());
+        task::spawn(async move {
+            server::run_configured_server_with_listener(
+                private_key,
+                public_key,
+                TenantKeys::new(),
+                config,
+                Box::new(SystemClock),
+                listener,
+            )
+            .await
+            .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+        });
+    }
+    sleep(Duration::from_millis(100)).await;
+    let sign = |url: String, message: &'static str| async move {
+        nonblocking::request_timestamp(&url, message).await.unwrap();
+    };
+
+    // The same log through two names agrees, as it grows
+    let state = dir.path().join("monitor.ndjson");
+    let mut monitor = Monitor::open(key, &state).unwrap();
+    let localhost = urls[0].replace("127.0.0.1", "localhost");
+    sign(urls[0].clone(), "shared").await;
+    for _ in 0..2 {
+        assert_eq!(monitor.poll(&urls[0]).await.unwrap(), []);
+        assert_eq!(monitor.poll(&localhost).await.unwrap(), []);
+        sign(urls[0].clone(), "grown").await;
+    }
+    assert_eq!(fs::read_to_string(&state).unwrap().lines().count(), 2);
+
+    // The other log, at the same count and then past it, still alerts
+    // after a restart
+    let mut monitor = Monitor::open(key, &state).unwrap();
+    sign(urls[1].clone(), "forked").await;
+    sign(urls[1].clone(), "forked").await;
+    let alerts = monitor.poll(&urls[1]).await.unwrap();
+    assert!(
+        matches!(&alerts[..], [Alert::SplitView { first, second }]
+            if first.head.count == 2 && first.vantage == urls[0] && second.vantage == urls[1]),
+        "{:?}",
+        alerts
+    );
+    assert!(
+        alerts[0]
+            .to_string()
+            .starts_with("ALERT: split view at 2 lines")
+    );
+    sign(urls[1].clone(), "forked").await;
+    sign(urls[1].clone(), "forked").await;
+    let alerts = monitor.poll(&urls[1]).await.unwrap();
+    assert!(
+        matches!(&alerts[..], [Alert::Inconsistent { old, new }]
+            if old.head.count == 2 && new.head.count == 4),
+        "{:?}",
+        alerts
+    );
+    assert_eq!(fs::read_to_string(&state).unwrap().lines().count(), 2);
+
+    // The real log past it extends the first view; a head under another
+    // key is an error
+    assert_eq!(monitor.poll(&urls[0]).await.unwrap(), []);
+    let (_, other_key) = generate_key_bytes();
+    let mut stranger = Monitor::open(
+        PublicKey::from_sec1_bytes(&other_key).unwrap(),
+        dir.path().join("stranger.ndjson"),
+    )
+    .unwrap();
+    let err = stranger.poll(&urls[0]).await.unwrap_err();
+    assert_eq!(err.to_string(), LogError::Invalid.to_string());
+}
+
+#[tokio::test]
+async fn test_gossip_peers_cosign_each_others_heads() {
+    let dir = tempfile::tempdir().unwrap();
+    let keys = [generate_key_bytes(), generate_key_bytes()];
+    let public_keys: Vec<_> = keys
+        .iter()
+        .map(|(private_key, _)| {
+            KeyPair::from_private_key_bytes(private_key)
+                .unwrap()
+                .to_public_key()
+        })
+        .collect();
+    let mut listeners = Vec::new();
+    let mut urls = Vec::new();
+    for _ in 0..2 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        urls.push(format!("http://{}", listener.local_addr().unwrap()));
+        listeners.push(listener);
+    }
+
+    // Two servers, each the other's only peer
+    for (i, ((listener, (private_key, public_key)), peer)) in
+        listeners.into_iter().zip(keys).zip([1, 0]).enumerate()
+    {
+        let config = ServerConfig {
+            audit_file: dir
+                .path()
+                .join(format!("audit{}.log", i))
+                .to_string_lossy()
+                .into_owned(),
+            gossip: GossipConfig {
+                peers: vec![GossipPeer {
+                    url: urls[peer].clone(),
+                    kid: jws::key_id(&public_keys[peer]),
+                }],
+                interval_secs: 1,
+            },
+            ..test_config()
+        };
+        task::spawn(async move {
+            server::run_configured_server_with_listener(
+                private_key,
+                public_key,
+                TenantKeys::new(),
+                config,
+                Box::new(SystemClock),
+                listener,
+            )
+            .await
+            .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+        });
+    }
+    sleep(Duration::from_millis(100)).await;
+    for url in &urls {
+        nonblocking::request_timestamp(url, "gossiped")
+            .await
+            .unwrap();
+    }
+
+    // Each ends up with the other's co-signature of its one-line log, and
+    // its own of the other's
+    let root = |url: String| async move {
+        let body: serde_json::Value = reqwest::get(format!("{}/log/root", url))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let head: TreeHead = serde_json::from_value(body.clone()).unwrap();
+        let cosignatures: Vec<Cosignature> =
+            serde_json::from_value(body["cosignatures"].clone()).unwrap();
+        let witnessed: Vec<Cosignature> =
+            serde_json::from_value(body["witnessed"].clone()).unwrap();
+        (head, cosignatures, witnessed)
+    };
+    let mut waited = 0;
+    let (head, cosignatures, witnessed) = loop {
+        let (head, cosignatures, witnessed) = root(urls[0].clone()).await;
+        let covered = |c: &[Cosignature]| c.first().is_some_and(|c| c.count == 1);
+        if (covered(&cosignatures) && covered(&witnessed)) || waited >= 100 {
+            break (head, cosignatures, witnessed);
+        }
+        sleep(Duration::from_millis(100)).await;
+        waited += 1;
+    };
+    assert!(head.verify(&public_keys[0]));
+    assert_eq!(head.count, 1);
+    assert_eq!(cosignatures.len(), 1);
+    assert!(cosignatures[0].verify(&public_keys[1]));
+    assert!(cosignatures[0].covers(&head));
+    assert!(!cosignatures[0].verify(&public_keys[0]));
+    let (other, _, _) = root(urls[1].clone()).await;
+    assert_eq!(witnessed.len(), 1);
+    assert!(witnessed[0].verify(&public_keys[0]));
+    assert!(witnessed[0].covers(&other));
+
+    // A co-signature still speaks for the head it was made of
+    nonblocking::request_timestamp(&urls[0], "later")
+        .await
+        .unwrap();
+    let (grown, _, _) = root(urls[0].clone()).await;
+    assert_eq!(grown.count, 2);
+    assert!(!cosignatures[0].covers(&grown));
+
+    // The peer co-signs the grown head too, having checked it extends
+    let mut waited = 0;
+    let witnessed = loop {
+        let (_, _, witnessed) = root(urls[1].clone()).await;
+        if witnessed.first().is_some_and(|c| c.count == 2) || waited >= 100 {
+            break witnessed;
+        }
+        sleep(Duration::from_millis(100)).await;
+        waited += 1;
+    };
+    assert!(witnessed[0].covers(&grown));
+}
+
+/// Starts a witness that receipts every head posted to it with its count,
+/// keeping them in `heads`
+async fn spawn_fake_witness(heads: Arc<std::sync::Mutex<Vec<TreeHead>>>) -> String {
+    let app = axum::Router::new().route(
+        "/anchor",
+        axum::routing::post(move |axum::Json(head): axum::Json<TreeHead>| async move {
+            let receipt = serde_json::json!({ "seen": head.count });
+            heads.lock().unwrap().push(head);
+            axum::Json(receipt)
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    task::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}/anchor", addr)
+}
+
+#[tokio::test]
+async fn test_anchoring_ties_timestamps_to_witnessed_heads() {
+    let dir = tempfile::tempdir().unwrap();
+    let heads = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let anchor_file = dir.path().join("anchors.ndjson");
+    let config = ServerConfig {
+        audit_file: dir.path().join("audit.log").to_string_lossy().into_owned(),
+        anchor: AnchorConfig {
+            witness: spawn_fake_witness(heads.clone()).await,
+            file: anchor_file.to_string_lossy().into_owned(),
+            interval_secs: 1,
+        },
+        ..test_config()
+    };
+    let server_url = format!("http://{}", spawn_configured_server(&[], config).await);
+    let client = client::nonblocking::VtsClient::new(&server_url).unwrap();
+    let key = client.request_key().await.unwrap();
+    let mut signed = Vec::new();
+    for message in ["first", "second"] {
+        signed.push(client.request_timestamp(message).await.unwrap());
+    }
+    let anchor = |serial: u64| reqwest::get(format!("{}/anchor/{}", server_url, serial));
+
+    // After the next anchoring, each timestamp is in the witnessed head
+    sleep(Duration::from_millis(1500)).await;
+    let proof: AnchorProof = anchor(2).await.unwrap().json().await.unwrap();
+    assert_eq!(proof.serial, 2);
+    assert!(proof.verify(&signed[1], &key).is_verified());
+    assert!(!proof.verify(&signed[0], &key).is_verified());
+    assert_eq!(proof.anchor.receipt, serde_json::json!({ "seen": 2 }));
+    assert_eq!(*heads.lock().unwrap(), vec![proof.anchor.head.clone()]);
+    let kept: transparency::Anchor =
+        serde_json::from_str(fs::read_to_string(&anchor_file).unwrap().trim_end()).unwrap();
+    assert_eq!(kept, proof.anchor);
+
+    // A log that didn't grow isn't anchored again
+    sleep(Duration::from_millis(1200)).await;
+    assert_eq!(heads.lock().unwrap().len(), 1);
+    let resp = anchor(99).await.unwrap();
+    assert_eq!(resp.status(), 404);
+    assert_eq!(
+        resp.json::<ApiError>().await.unwrap().code,
+        "unknown_serial"
+    );
+}
+
+#[tokio::test]
+async fn test_admin_api_needs_a_token() {
+    let dir = tempfile::tempdir().unwrap();
+    let (config, _) = admin_config(dir.path());
+    fs::write(dir.path().join("admin_token"), "short").unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let (private_key, public_key) = generate_key_bytes();
+    let result = server::run_configured_server_with_listener(
+        private_key,
+        public_key,
+        TenantKeys::new(),
+        config,
+        Box::new(SystemClock),
+        listener,
+    )
+    .await;
+    assert!(result.unwrap_err().to_string().contains("at least 16"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unix_socket_server_and_client() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("vts.sock");
+    // A socket file left behind by an earlier server is replaced
+    drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+    let listen = format!("{}{}", server::UNIX_PREFIX, socket.display());
+    let listener = server::Listener::bind(&[&listen], 0).await.unwrap();
+    assert_eq!(listener.describe(), listen);
+    assert!(server::Listener::bind(&[&listen], 0).await.is_err());
+
+    let (private_key, public_key) = generate_key_bytes();
+    let tenant_keys: TenantKeys = [("acme".to_string(), generate_key_bytes())].into();
+    task::spawn(async move {
+        server::run_configured_server_with_listener(
+            private_key,
+            public_key,
+            tenant_keys,
+            test_config(),
+            Box::new(SystemClock),
+            listener,
+        )
+        .await
+        .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let server_url = format!(
+        "{}://{}",
+        client::UNIX_SCHEME,
+        socket.display().to_string().replace('/', "%2F")
+    );
+    let client = client::nonblocking::VtsClient::new(&server_url).unwrap();
+    let key = client.request_key().await.unwrap();
+    let signed = client.request_timestamp("over a socket").await.unwrap();
+    assert!(verify_signature(&signed, &key));
+    let acme_key = client.for_tenant("acme").request_key().await.unwrap();
+    assert_ne!(acme_key.public_key, key.public_key);
+    let err = client.for_tenant("nobody").request_key().await.unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ApiError>().unwrap().code,
+        "unknown_tenant"
+    );
+
+    let (blocking_key, signed) = task::spawn_blocking(move || {
+        let client = client::VtsClient::new(&server_url).unwrap();
+        let key = client.request_key().unwrap();
+        let signed = client
+            .request_file_timestamp(&b"file contents"[..])
+            .unwrap();
+        (key, signed)
+    })
+    .await
+    .unwrap();
+    assert_eq!(blocking_key.public_key, key.public_key);
+    assert!(verify_file_timestamp(&b"file contents"[..], &signed, &key).unwrap());
+
+    // Nobody is listening on a missing socket
+    let gone = client::nonblocking::VtsClient::new("http+unix://%2Fnonexistent%2Fvts.sock");
+    let err = gone.unwrap().request_key().await.unwrap_err();
+    let err = err.downcast_ref::<std::io::Error>().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+}
+
+#[tokio::test]
+async fn test_server_listens_on_several_addresses() {
+    // IPv4 and IPv6 on one port, given as a bare address plus `port`
+    let port = server::Listener::bind(&["127.0.0.1:0"], 0)
+        .await
+        .unwrap()
+        .local_addrs()[0]
+        .port();
+    let listen = [format!("127.0.0.1:{}", port), "::1".to_string()];
+    let listener = match server::Listener::bind(&listen, port).await {
+        Ok(listener) => listener,
+        // No IPv6 loopback here; check the two IPv4 listeners instead
+        Err(_) => server::Listener::bind(&["127.0.0.1:0", "127.0.0.1:0"], 0)
+            .await
+            .unwrap(),
+    };
+    let addrs = listener.local_addrs();
+    assert_eq!(addrs.len(), 2);
+    assert_ne!(addrs[0], addrs[1]);
+    assert!(listener.describe().contains(", "));
+
+    let (private_key, public_key) = generate_key_bytes();
+    task::spawn(async move {
+        server::run_configured_server_with_listener(
+            private_key,
+            public_key,
+            TenantKeys::new(),
+            test_config(),
+            Box::new(SystemClock),
+            listener,
+        )
+        .await
+        .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let mut keys = Vec::new();
+    for addr in addrs {
+        let server_url = format!("http://{}", addr);
+        let key = nonblocking::request_key(&server_url).await.unwrap();
+        let signed = nonblocking::request_timestamp(&server_url, "anywhere")
+            .await
+            .unwrap();
+        assert!(verify_signature(&signed, &key));
+        keys.push(key.public_key);
+    }
+    assert_eq!(keys[0], keys[1]);
+    assert!(
+        server::Listener::bind(&["not an address"], 0)
+            .await
+            .is_err()
+    );
+}
+
+/// A `/sign` body and what it gets: status, code, field, start of the error
+type BodyCase = (
+    &'static [u8],
+    u16,
+    &'static str,
+    Option<&'static str>,
+    &'static str,
+);
+
+#[tokio::test]
+async fn test_malformed_sign_bodies_name_the_field() {
+    let addr = spawn_configured_server(
+        &[],
+        ServerConfig {
+            max_message_bytes: 16,
+            ..test_config()
+        },
+    )
+    .await;
+    let client = reqwest::Client::new();
+    let post = |path: &str, body: Vec<u8>| {
+        client
+            .post(format!("http://{}{}", addr, path))
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+    };
+
+    let cases: [BodyCase; 8] = [
+        (
+            b"{}",
+            422,
+            "invalid_json",
+            Some("message"),
+            "missing field 'message'",
+        ),
+        (
+            br#"{"message": 42}"#,
+            422,
+            "invalid_json",
+            Some("message"),
+            "invalid value for 'message': invalid type: integer '42', expected a string",
+        ),
+        (
+            br#"{"message": "hi", "encoding": "pem"}"#,
+            422,
+            "invalid_json",
+            Some("encoding"),
+            "invalid value for 'encoding': unknown variant 'pem'",
+        ),
+        (
+            br#"{"message": "#,
+            422,
+            "invalid_json",
+            None,
+            "invalid JSON at line 1 column 12: EOF while parsing a value",
+        ),
+        (b"", 422, "invalid_json", None, "invalid JSON"),
+        (
+            b"{\"message\": \"caf\xe9\"}",
+            422,
+            "invalid_utf8",
+            Some("message"),
+            "string is not valid UTF-8",
+        ),
+        (
+            br#"{"message": "\udc00"}"#,
+            422,
+            "invalid_utf8",
+            Some("message"),
+            "string is not valid UTF-8",
+        ),
+        (
+            br#"{"message": "seventeen bytes!!!"}"#,
+            413,
+            "message_too_large",
+            None,
+            "",
+        ),
+    ];
+    for (body, status, code, field, error) in cases {
+        let resp = post("/sign", body.to_vec()).await.unwrap();
+        assert_eq!(resp.status(), status, "{:?}", String::from_utf8_lossy(body));
+        let api: ApiError = resp.json().await.unwrap();
+        assert_eq!(api.code, code, "{}", api.message);
+        assert_eq!(api.field.as_deref(), field);
+        assert!(api.message.starts_with(error), "{}", api.message);
+        assert!(api.request_id.is_some());
+    }
+
+    let resp = post("/renew", br#"{"message": "hi"}"#.to_vec())
+        .await
+        .unwrap();
+    let api: ApiError = resp.json().await.unwrap();
+    assert_eq!(api.message, "missing field 'token'");
+    assert_eq!(api.field.as_deref(), Some("token"));
+
+    // A body too large for any message is cut off before it is parsed
+    let resp = post("/sign", vec![b' '; 64 * 1024]).await.unwrap();
+    assert_eq!(resp.status(), 413);
+    let api: ApiError = resp.json().await.unwrap();
+    assert_eq!(api.code, "payload_too_large");
+}
+
+#[tokio::test]
+async fn test_binary_messages_sign_their_bytes() {
+    let addr = spawn_configured_server(
+        &[],
+        ServerConfig {
+            max_message_bytes: 16,
+            ..test_config()
+        },
+    )
+    .await;
+    let server_url = format!("http://{}", addr);
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    let bytes = b"\x00\xff\xfe binary\n";
+
+    let signed = nonblocking::request_binary_timestamp(&server_url, bytes)
+        .await
+        .unwrap();
+    assert!(signed.message.is_empty());
+    assert_eq!(signed.message_b64.as_deref(), Some("AP/+IGJpbmFyeQo="));
+    assert_eq!(signed.message_bytes().unwrap(), bytes);
+    // The signature is over the bytes themselves, then `time-signed`
+    let mut data = bytes.to_vec();
+    data.extend_from_slice(signed.time_signed.as_bytes());
+    assert_eq!(signed.signed_data().unwrap(), data);
+    assert!(verify_signature(&signed, &key));
+
+    let mut other = serde_json::from_str::<lab4::EcdsaSignedTimestamp>(&signed.to_token()).unwrap();
+    other.message_b64 = Some("AP/+IGJpbmFyeQ0=".to_string());
+    assert!(!verify_signature(&other, &key));
+    other.message_b64 = Some("not base64!".to_string());
+    assert_eq!(
+        verify_signature_with(&other, &key, &VerifyOptions::default()),
+        VerifyOutcome::BadEncoding
+    );
+
+    // The same bytes as text are the same message
+    let text = nonblocking::request_timestamp(&server_url, "text")
+        .await
+        .unwrap();
+    let client = client::nonblocking::VtsClient::new(&server_url).unwrap();
+    let binary = client.request_binary_timestamp(b"text").await.unwrap();
+    assert_eq!(binary.message_b64.as_deref(), Some("dGV4dA=="));
+    assert!(verify_signature(&text, &key) && verify_signature(&binary, &key));
+    let jws = reqwest::Client::new()
+        .post(format!("{}/sign?format=jws", server_url))
+        .json(&serde_json::json!({ "message-b64": "dGV4dA==" }))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let claims = jws::verify(&jws, &key).unwrap();
+    assert_eq!(claims.msg_hash, hex::encode(Sha256::digest(b"text")));
+
+    // Exactly one of the two, valid Base64, and no more than
+    // max_message_bytes once decoded
+    let post = |body: serde_json::Value| {
+        reqwest::Client::new()
+            .post(format!("{}/sign", server_url))
+            .json(&body)
+            .send()
+    };
+    let cases = [
+        (
+            serde_json::json!({ "message": "a", "message-b64": "YQ==" }),
+            422,
+            Some("message-b64"),
+        ),
+        (
+            serde_json::json!({ "message-b64": "***" }),
+            422,
+            Some("message-b64"),
+        ),
+        (
+            serde_json::json!({ "message-b64": "AAAAAAAAAAAAAAAAAAAAAAAA" }),
+            413,
+            None,
+        ),
+    ];
+    for (body, status, field) in cases {
+        let resp = post(body).await.unwrap();
+        assert_eq!(resp.status(), status);
+        let api: ApiError = resp.json().await.unwrap();
+        assert_eq!(api.field.as_deref(), field);
+    }
+
+    let blocking_url = server_url.clone();
+    let signed = task::spawn_blocking(move || {
+        lab4::ecdsa_requests::request_binary_timestamp(&blocking_url, &[1, 2, 3]).unwrap()
+    })
+    .await
+    .unwrap();
+    assert_eq!(signed.message_bytes().unwrap(), [1, 2, 3]);
+    assert!(verify_signature(&signed, &key));
+}
+
+#[tokio::test]
+async fn test_normalization_policy() {
+    let decomposed = "Cafe\u{301}";
+    let exact_url = format!("http://{}", spawn_server().await);
+    let nfc_url = format!(
+        "http://{}",
+        spawn_configured_server(
+            &[],
+            ServerConfig {
+                normalization: Normalization::Nfc,
+                ..test_config()
+            },
+        )
+        .await
+    );
+
+    // `exact` signs the bytes received
+    let key = nonblocking::request_key(&exact_url).await.unwrap();
+    let signed = nonblocking::request_timestamp(&exact_url, decomposed)
+        .await
+        .unwrap();
+    assert_eq!(signed.message, decomposed);
+    assert!(verify_signature(&signed, &key));
+
+    // `nfc` signs, and returns, the composed form, whichever was sent
+    let key = nonblocking::request_key(&nfc_url).await.unwrap();
+    for sent in [decomposed, "Caf\u{e9}"] {
+        let signed = nonblocking::request_timestamp(&nfc_url, sent)
+            .await
+            .unwrap();
+        assert_eq!(signed.message, "Caf\u{e9}");
+        assert!(verify_signature(&signed, &key));
+        let mut as_sent =
+            serde_json::from_str::<lab4::EcdsaSignedTimestamp>(&signed.to_token()).unwrap();
+        as_sent.message = decomposed.to_string();
+        assert!(!verify_signature(&as_sent, &key));
+    }
+    let jws = reqwest::Client::new()
+        .post(format!("{}/sign?format=jws", nfc_url))
+        .json(&serde_json::json!({ "message": decomposed }))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let claims = jws::verify(&jws, &key).unwrap();
+    assert_eq!(claims.msg_hash, hex::encode(Sha256::digest("Caf\u{e9}")));
+
+    // Bytes and renewal tokens are signed as they are
+    let bytes = decomposed.as_bytes();
+    let binary = nonblocking::request_binary_timestamp(&nfc_url, bytes)
+        .await
+        .unwrap();
+    assert_eq!(binary.message_bytes().unwrap(), bytes);
+    let token = format!("{{\"message\": \"{}\"}}", decomposed);
+    let renewed = nonblocking::renew_timestamp(&nfc_url, &token)
+        .await
+        .unwrap();
+    assert_eq!(renewed.message, token);
+    assert!(verify_signature(&renewed, &key));
+}
+
+/// Log output of every test in this binary (`set_global_default`)
+#[derive(Clone, Default)]
+struct CapturedLog(Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for CapturedLog {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_default_logging_leaves_messages_out() {
+    // Lines are written on the sign pool's threads too, so the subscriber
+    // is the process's; this is the only test that sets it
+    let log = CapturedLog::default();
+    let writer = log.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    tracing::subscriber::set_global_default(subscriber).unwrap();
+    let addr = spawn_server().await;
+    let server_url = format!("http://{}", addr);
+
+    let message = "the quarterly numbers, before anyone else sees them";
+    let signed = nonblocking::request_timestamp(&server_url, message)
+        .await
+        .unwrap();
+    let text = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+    let hash = hex::encode(Sha256::digest(message.as_bytes()));
+    let line = text
+        .lines()
+        .find(|line| line.contains("Request: POST /sign") && line.contains(&hash))
+        .unwrap_or_else(|| panic!("no /sign line for {} in {}", hash, text));
+    assert!(line.contains(&format!("hash={}", hash)), "{}", line);
+    assert!(line.contains("sig=<omitted>"), "{}", line);
+    assert!(!text.contains(message), "{}", line);
+    assert!(!text.contains(&signed.signature), "{}", line);
+}
+
+#[tokio::test]
+async fn test_privacy_mode_only_signs_digests() {
+    let config = ServerConfig {
+        privacy_mode: true,
+        ..test_config()
+    };
+    let server_url = format!("http://{}", spawn_configured_server(&[], config).await);
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    let post = |path: &str, body: serde_json::Value| {
+        reqwest::Client::new()
+            .post(format!("{}/{}", server_url, path))
+            .json(&body)
+            .send()
+    };
+
+    // 1) Plaintext is refused, as text or bytes, signed or previewed
+    for path in ["sign", "sign/preview"] {
+        for body in [
+            serde_json::json!({ "message": "secret" }),
+            serde_json::json!({ "message-b64": "c2VjcmV0" }),
+        ] {
+            let resp = post(path, body).await.unwrap();
+            assert_eq!(resp.status(), 400);
+            let err: ApiError = resp.json().await.unwrap();
+            assert_eq!(err.code, "plaintext_not_allowed");
+            assert_eq!(err.field.as_deref(), Some("message"));
+        }
+    }
+
+    // 2) A digest, or a message already in digest form, is signed
+    let digest = Sha256::digest(b"secret");
+    let resp = post("sign", serde_json::json!({ "digest": hex::encode(digest) }))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let signed: lab4::EcdsaSignedTimestamp = resp.json().await.unwrap();
+    assert_eq!(signed.message, DigestAlg::Sha256.message(&digest));
+    assert!(verify_signature(&signed, &key));
+    let signed = nonblocking::request_timestamp(&server_url, &signed.message)
+        .await
+        .unwrap();
+    assert!(verify_signature(&signed, &key));
+    let resp = post(
+        "sign/preview",
+        serde_json::json!({ "digest": hex::encode(digest) }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let version = nonblocking::request_version(&server_url).await.unwrap();
+    assert!(version.features.iter().any(|f| f == "privacy-mode"));
+}
+
+#[tokio::test]
+async fn test_metrics_report_latency_by_route() {
+    let server_url = format!("http://{}", spawn_tenant_server(&["acme"]).await);
+    for url in [
+        format!("{}/sign", server_url),
+        format!("{}/v1/sign", server_url),
+        format!("{}/t/acme/sign", server_url),
+    ] {
+        let resp = reqwest::Client::new()
+            .post(url)
+            .json(&serde_json::json!({ "message": "timed" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+    nonblocking::request_key(&server_url).await.unwrap();
+
+    let resp = reqwest::get(format!("{}/metrics", server_url))
+        .await
+        .unwrap();
+    assert_eq!(
+        resp.headers()[reqwest::header::CONTENT_TYPE],
+        "text/plain; version=0.0.4"
+    );
+    let metrics = resp.text().await.unwrap();
+    // Every version and tenant of `/sign` is one route
+    let sign = r#"method="POST",route="/sign""#;
+    for q in ["0.5", "0.95", "0.99"] {
+        let line = format!(
+            "vts_request_duration_seconds{{{},quantile=\"{}\"}} ",
+            sign, q
+        );
+        assert!(metrics.contains(&line), "no {} in {}", line, metrics);
+    }
+    assert!(metrics.contains(&format!(
+        "vts_request_duration_seconds_count{{{}}} 3\n",
+        sign
+    )));
+    assert!(metrics.contains(&format!("vts_slow_requests_total{{{}}} 0\n", sign)));
+    assert!(metrics.contains(r#"vts_request_duration_seconds_count{method="GET",route="/key"} 1"#));
+}
+
+#[tokio::test]
+async fn test_http_signatures_on_responses() {
+    let config = ServerConfig {
+        http_signatures: true,
+        ..test_config()
+    };
+    let server_url = format!(
+        "http://{}",
+        spawn_configured_server(&["acme"], config).await
+    );
+    let key =
+        |url: String| async move { nonblocking::request_key(&url).await.unwrap().key().unwrap() };
+    let (default_key, acme_key) = (
+        key(server_url.clone()).await,
+        key(format!("{}/t/acme", server_url)).await,
+    );
+    let options = |key| ClientOptions {
+        response_key: Some(key),
+        ..fast_retries(0)
+    };
+
+    // Successes and errors alike are signed, each tenant with its own key
+    let client =
+        client::nonblocking::VtsClient::with_options(&server_url, options(default_key)).unwrap();
+    let signed = client.request_timestamp("Hello").await.unwrap();
+    assert!(verify_signature(
+        &signed,
+        &client.request_key().await.unwrap()
+    ));
+    let err = client.for_tenant("nobody").request_key().await.unwrap_err();
+    let err = err.downcast_ref::<ApiError>().unwrap();
+    assert_eq!(err.code, "unknown_tenant");
+    let acme = client::nonblocking::VtsClient::with_options(&server_url, options(acme_key))
+        .unwrap()
+        .for_tenant("acme");
+    acme.request_timestamp("Hello").await.unwrap();
+    let err = client.for_tenant("acme").request_key().await.unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<SignatureError>(),
+        Some(&SignatureError::Key(jws::key_id(&acme_key)))
+    );
+
+    // A changed body or status doesn't verify
+    let resp = reqwest::get(format!("{}/key", server_url)).await.unwrap();
+    let (status, headers) = (resp.status().as_u16(), resp.headers().clone());
+    let body = resp.bytes().await.unwrap().to_vec();
+    let header = |name: &str| Some(headers.get(name)?.to_str().unwrap().to_string());
+    let signed = http_sig::verify(status, header, &body, &default_key).unwrap();
+    assert!(signed.nonce.is_some());
+    let mut changed = body.clone();
+    changed[0] = b' ';
+    assert_eq!(
+        http_sig::verify(status, header, &changed, &default_key),
+        Err(SignatureError::Digest)
+    );
+    assert_eq!(
+        http_sig::verify(404, header, &body, &default_key),
+        Err(SignatureError::Invalid)
+    );
+
+    // Each nonce is accepted once, and only while the response is recent
+    let replays = http_sig::ReplayGuard::new(Duration::from_secs(300));
+    assert_eq!(replays.check(&signed, signed.created), Ok(()));
+    assert_eq!(
+        replays.check(&signed, signed.created + 1),
+        Err(SignatureError::Replayed)
+    );
+    let fresh = http_sig::ReplayGuard::new(Duration::from_secs(300));
+    assert_eq!(
+        fresh.check(&signed, signed.created + 301),
+        Err(SignatureError::Stale(signed.created))
+    );
+    let again = reqwest::get(format!("{}/key", server_url)).await.unwrap();
+    let again_headers = again.headers().clone();
+    let again_header = |name: &str| Some(again_headers.get(name)?.to_str().unwrap().to_string());
+    let again_body = again.bytes().await.unwrap();
+    let again = http_sig::verify(status, again_header, &again_body, &default_key).unwrap();
+    assert_ne!(again.nonce, signed.nonce);
+    assert_eq!(replays.check(&again, again.created), Ok(()));
+
+    // So a cache handing out the same signed `/key` answer is caught
+    let mut cached_headers = axum::http::HeaderMap::new();
+    for (name, value) in &headers {
+        cached_headers.insert(
+            axum::http::HeaderName::from_bytes(name.as_str().as_bytes()).unwrap(),
+            axum::http::HeaderValue::from_bytes(value.as_bytes()).unwrap(),
+        );
+    }
+    let cached = spawn_router(axum::Router::new().route(
+        "/key",
+        axum::routing::get(move || {
+            let (headers, body) = (cached_headers.clone(), body.clone());
+            async move { (headers, body).into_response() }
+        }),
+    ))
+    .await;
+    let client =
+        client::nonblocking::VtsClient::with_options(&cached, options(default_key)).unwrap();
+    client.request_key().await.unwrap();
+    let err = client.request_key().await.unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<SignatureError>(),
+        Some(&SignatureError::Replayed)
+    );
+
+    // A client that expects signatures refuses a server that doesn't sign
+    let unsigned = format!("http://{}", spawn_server().await);
+    let client =
+        client::nonblocking::VtsClient::with_options(&unsigned, options(default_key)).unwrap();
+    let err = client.request_version().await.unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<SignatureError>(),
+        Some(&SignatureError::Unsigned)
+    );
+}
+
+#[tokio::test]
+async fn test_key_certificate() {
+    let dir = tempfile::tempdir().unwrap();
+    let keys = generate_key_bytes();
+    let keypair = lab4::signer::key_pair_signer(&keys.0, &keys.1).unwrap();
+    let not_before = chrono::DateTime::from_timestamp(chrono::Utc::now().timestamp(), 0).unwrap();
+    let not_after = not_before + chrono::Duration::days(30);
+    let der = x509::self_signed(&keypair, "VTS test", not_before, not_after).unwrap();
+    let cert_file = dir.path().join("cert.pem");
+    fs::write(&cert_file, x509::to_pem(&der, "CERTIFICATE")).unwrap();
+    let config = ServerConfig {
+        cert_file: cert_file.to_string_lossy().into_owned(),
+        ..test_config()
+    };
+    let server_url = format!("http://{}", spawn_server_with_keys(keys, config).await);
+
+    // DER by default, PEM when asked for; either certifies the served key
+    let http = reqwest::Client::new();
+    let resp = http
+        .get(format!("{}/v1/key/cert", server_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-type"], x509::CONTENT_TYPE);
+    let served = resp.bytes().await.unwrap();
+    assert_eq!(served, der);
+    let info = x509::parse(&served).unwrap();
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    assert_eq!(Some(info.public_key), key.key());
+    assert_eq!((info.not_before, info.not_after), (not_before, not_after));
+    let pem = http
+        .get(format!("{}/key/cert", server_url))
+        .header("accept", x509::PEM_CONTENT_TYPE)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(pem.starts_with("-----BEGIN CERTIFICATE-----\n"));
+    assert_eq!(x509::decode(pem.as_bytes()).unwrap(), der);
+
+    // No cert_file, no certificate
+    let plain_url = format!("http://{}", spawn_server().await);
+    let resp = reqwest::get(format!("{}/key/cert", plain_url))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+    let err: ApiError = resp.json().await.unwrap();
+    assert_eq!(err.code, "no_certificate");
+
+    // A certificate of another key is refused at startup
+    let (private_key, public_key) = generate_key_bytes();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let result = server::run_configured_server_with_listener(
+        private_key,
+        public_key,
+        TenantKeys::new(),
+        ServerConfig {
+            cert_file: cert_file.to_string_lossy().into_owned(),
+            ..test_config()
+        },
+        Box::new(SystemClock),
+        listener,
+    )
+    .await;
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("certifies a different key")
+    );
+}
+
+#[tokio::test]
+async fn test_key_validity_window() {
+    let dir = tempfile::tempdir().unwrap();
+    let now = chrono::Utc::now();
+    let config = ServerConfig {
+        key_validity: KeyValidity {
+            not_before: None,
+            not_after: Some(now + chrono::Duration::hours(1)),
+        },
+        extra_keys: vec![KeyFiles {
+            private_key_file: dir.path().join("old_private.bin").to_string_lossy().into(),
+            public_key_file: dir.path().join("old_public.bin").to_string_lossy().into(),
+            validity: KeyValidity {
+                not_before: Some(now - chrono::Duration::days(30)),
+                not_after: Some(now - chrono::Duration::hours(1)),
+            },
+        }],
+        ..test_config()
+    };
+    let server_url = format!("http://{}", spawn_configured_server(&[], config).await);
+    let client = client::nonblocking::VtsClient::new(&server_url).unwrap();
+
+    // /key and /keys say when each key may sign
+    let key = client.request_key().await.unwrap();
+    assert_eq!(
+        key.not_after.as_deref(),
+        Some(lab4::wire::canonical_time(now + chrono::Duration::hours(1)).as_str())
+    );
+    assert_eq!(key.not_before, None);
+    let keys = client.request_keys().await.unwrap();
+    assert!(keys[1].not_before.is_some() && keys[1].not_after.is_some());
+
+    // The primary key still signs, and its timestamps verify
+    let signed = client.request_timestamp("Hello").await.unwrap();
+    assert_eq!(
+        verify_signature_with(&signed, &key, &VerifyOptions::default()),
+        VerifyOutcome::Ok
+    );
+
+    // The expired one doesn't
+    let err = client::nonblocking::VtsClient::with_options(
+        &server_url,
+        ClientOptions {
+            kid: keys[1].kid.clone(),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+    .request_timestamp("Hello")
+    .await
+    .unwrap_err();
+    let err = err.downcast_ref::<ApiError>().unwrap();
+    assert_eq!((err.code.as_str(), err.status), ("key_expired", Some(403)));
+
+    // A timestamp outside the key's window fails to verify, saying why
+    let mut raw: serde_json::Value = reqwest::get(format!("{}/key", server_url))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    raw["not-after"] = "2000-01-01T00:00:00Z".into();
+    let expired: EcdsaVerificationKey = serde_json::from_value(raw.clone()).unwrap();
+    assert_eq!(
+        verify_signature_with(&signed, &expired, &VerifyOptions::default()),
+        VerifyOutcome::OutsideKeyValidity
+    );
+    assert!(!verify_signature(&signed, &expired));
+    assert_eq!(
+        verify_signatures_batch(std::slice::from_ref(&signed), &expired),
+        vec![VerifyOutcome::OutsideKeyValidity]
+    );
+    raw["not-after"] = "soon".into();
+    let garbled: EcdsaVerificationKey = serde_json::from_value(raw).unwrap();
+    assert_eq!(
+        verify_signature_with(&signed, &garbled, &VerifyOptions::default()),
+        VerifyOutcome::BadKey
+    );
+}
+
+#[tokio::test]
+async fn test_revoked_key_after_compromise() {
+    use base64::{Engine as _, engine::general_purpose};
+
+    let dir = tempfile::tempdir().unwrap();
+    let leaked = KeyPair::generate();
+    let files = KeyFiles {
+        private_key_file: dir
+            .path()
+            .join("leaked_private.bin")
+            .to_string_lossy()
+            .into(),
+        public_key_file: dir
+            .path()
+            .join("leaked_public.bin")
+            .to_string_lossy()
+            .into(),
+        ..Default::default()
+    };
+    leaked
+        .save_to_files(&files.private_key_file, &files.public_key_file)
+        .unwrap();
+    let leaked_kid = jws::key_id(&leaked.to_public_key());
+    let start = "2030-01-01T00:00:00Z"
+        .parse::<chrono::DateTime<chrono::Utc>>()
+        .unwrap();
+    let compromised_at = start + chrono::Duration::hours(1);
+    let config = ServerConfig {
+        extra_keys: vec![files],
+        revoked_keys: vec![RevokedKey {
+            kid: leaked_kid.clone(),
+            compromised_at,
+            reason: "key-compromise".to_string(),
+        }],
+        ..test_config()
+    };
+    let clock = Arc::new(FakeClock::new(start));
+    let server_url = format!(
+        "http://{}",
+        spawn_server_with_clock(&[], config, Box::new(clock.clone())).await
+    );
+    let client = client::nonblocking::VtsClient::new(&server_url).unwrap();
+    let keys = client.request_keys().await.unwrap();
+    let leaked_key = keys
+        .iter()
+        .find(|k| k.kid.as_deref() == Some(leaked_kid.as_str()))
+        .unwrap();
+    assert_eq!(
+        leaked_key.not_after.as_deref(),
+        Some(lab4::wire::canonical_time(compromised_at).as_str())
+    );
+
+    // Signed by the leaked key before the compromise: still valid
+    let with_leaked = client::nonblocking::VtsClient::with_options(
+        &server_url,
+        ClientOptions {
+            kid: Some(leaked_kid.clone()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let before = with_leaked.request_timestamp("before").await.unwrap();
+    let list = client.request_revocations().await.unwrap();
+    assert_eq!(list.kid, keys[0].kid.clone().unwrap());
+    assert_eq!(list.revoked.len(), 1);
+    assert_eq!(list.revoked[0].reason, "key-compromise");
+    assert_eq!(list.compromised_at(&leaked_kid), Some(compromised_at));
+    assert_eq!(list.check(&before, leaked_key), VerifyOutcome::Ok);
+
+    // After it, the server no longer signs with it...
+    clock.set(compromised_at + chrono::Duration::seconds(1));
+    let err = with_leaked.request_timestamp("after").await.unwrap_err();
+    assert_eq!(err.downcast_ref::<ApiError>().unwrap().code, "key_expired");
+    let after = client.request_timestamp("after").await.unwrap();
+    assert_eq!(list.check(&after, &keys[0]), VerifyOutcome::Ok);
+
+    // ...and what the thief signs is a valid signature, but revoked
+    let time_signed = "2030-01-01T02:00:00.000000Z";
+    let signature = leaked.sign(&lab4::wire::signing_input(b"forged", time_signed));
+    let forged: lab4::EcdsaSignedTimestamp = serde_json::from_value(serde_json::json!({
+        "request": "POST",
+        "message": "forged",
+        "time-signed": time_signed,
+        "signature": general_purpose::STANDARD.encode(signature.to_vec()),
+    }))
+    .unwrap();
+    let unchecked: EcdsaVerificationKey = serde_json::from_value(serde_json::json!({
+        "request": "GET",
+        "time-requested": time_signed,
+        "public-key": leaked_key.public_key,
+    }))
+    .unwrap();
+    assert!(verify_signature(&forged, &unchecked));
+    assert_eq!(list.check(&forged, &unchecked), VerifyOutcome::Revoked);
+
+    // The list only verifies as signed, and by the server's keys
+    let mut tampered = list.clone();
+    tampered.revoked.clear();
+    assert_eq!(tampered.verify(&keys), Err(RevocationError::Invalid));
+    assert_eq!(
+        list.verify(&keys[1..]),
+        Err(RevocationError::UnknownKey(list.kid.clone()))
+    );
+}
+
+#[tokio::test]
+async fn test_extra_keys_selected_by_kid() {
+    let dir = tempfile::tempdir().unwrap();
+    let files = KeyFiles {
+        private_key_file: dir
+            .path()
+            .join("second_private.bin")
+            .to_string_lossy()
+            .into(),
+        public_key_file: dir
+            .path()
+            .join("second_public.bin")
+            .to_string_lossy()
+            .into(),
+        ..Default::default()
+    };
+    let config = ServerConfig {
+        extra_keys: vec![files.clone()],
+        ..test_config()
+    };
+    let server_url = format!("http://{}", spawn_configured_server(&[], config).await);
+
+    // The primary key first, then the extra one (generated at startup)
+    let client = client::nonblocking::VtsClient::new(&server_url).unwrap();
+    let keys = client.request_keys().await.unwrap();
+    assert_eq!(keys.len(), 2);
+    let primary = client.request_key().await.unwrap();
+    assert_eq!(keys[0].public_key, primary.public_key);
+    assert_eq!(keys[0].kid, primary.kid);
+    let second_kid = keys[1].kid.clone().unwrap();
+    assert_eq!(
+        Some(keys[1].public_key.as_str()),
+        fs::read(&files.public_key_file)
+            .ok()
+            .and_then(|bytes| ecdsa_lib::PublicKey::from_sec1_bytes(&bytes).ok())
+            .map(|key| key.to_string())
+            .as_deref()
+    );
+
+    // Without a kid the primary signs; with one, that key, and says so
+    let signed = client.request_timestamp("Hello").await.unwrap();
+    assert_eq!(signed.kid, primary.kid);
+    assert!(std::ptr::eq(
+        lab4::ecdsa_requests::select_key(&keys, &signed).unwrap(),
+        &keys[0]
+    ));
+    let second = client::nonblocking::VtsClient::with_options(
+        &server_url,
+        ClientOptions {
+            kid: Some(second_kid.clone()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let signed = second.request_timestamp("Hello").await.unwrap();
+    assert_eq!(signed.kid.as_deref(), Some(second_kid.as_str()));
+    let key = lab4::ecdsa_requests::select_key(&keys, &signed).unwrap();
+    assert!(verify_signature(&signed, key));
+    assert!(!verify_signature(&signed, &keys[0]));
+    let renewed = second.renew_timestamp(&signed.to_token()).await.unwrap();
+    assert_eq!(renewed.kid.as_deref(), Some(second_kid.as_str()));
+
+    // A JWS names the key in its header
+    let token = reqwest::Client::new()
+        .post(format!("{}/sign?format=jws", server_url))
+        .json(&serde_json::json!({ "message": "Hello", "kid": second_kid }))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(jws::verify(&token, &keys[1]).is_some());
+    assert!(jws::verify(&token, &keys[0]).is_none());
+
+    // A kid the tenant doesn't have is refused
+    let err = client::nonblocking::VtsClient::with_options(
+        &server_url,
+        ClientOptions {
+            kid: Some("no-such-key".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+    .request_timestamp("Hello")
+    .await
+    .unwrap_err();
+    let err = err.downcast_ref::<ApiError>().unwrap();
+    assert_eq!((err.code.as_str(), err.status), ("unknown_kid", Some(400)));
+    assert_eq!(err.field.as_deref(), Some("kid"));
+
+    // The same key twice is refused at startup
+    let (private_key, public_key) = generate_key_bytes();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let result = server::run_configured_server_with_listener(
+        private_key,
+        public_key,
+        TenantKeys::new(),
+        ServerConfig {
+            extra_keys: vec![files.clone(), files],
+            ..test_config()
+        },
+        Box::new(SystemClock),
+        listener,
+    )
+    .await;
+    assert!(result.unwrap_err().to_string().contains("listed twice"));
+}
+
+#[tokio::test]
+async fn test_trust_root() {
+    let dir = tempfile::tempdir().unwrap();
+    let keys = generate_key_bytes();
+    let keypair = lab4::signer::key_pair_signer(&keys.0, &keys.1).unwrap();
+    let (root, other_root) = (KeyPair::from_seed(&[7; 32]), KeyPair::from_seed(&[8; 32]));
+    let now = chrono::Utc::now();
+    let not_after = now + chrono::Duration::days(30);
+    let issued = x509::issue(
+        &keypair.to_public_key(),
+        "VTS test",
+        &root,
+        "VTS root",
+        now,
+        not_after,
+    )
+    .unwrap();
+
+    // Issued by the root, for timestamping, until `not_after`
+    let info = x509::verify_issued(&issued, &root.to_public_key(), now).unwrap();
+    assert_eq!(info.public_key, keypair.to_public_key());
+    assert!(info.time_stamping);
+    assert_eq!(
+        x509::verify_issued(&issued, &other_root.to_public_key(), now),
+        Err(x509::EndorsementError::NotIssued)
+    );
+    assert_eq!(
+        x509::verify_issued(
+            &issued,
+            &root.to_public_key(),
+            not_after + chrono::Duration::days(1)
+        ),
+        Err(x509::EndorsementError::Expired)
+    );
+    let self_signed = x509::self_signed(&keypair, "VTS test", now, not_after).unwrap();
+    assert_eq!(
+        x509::verify_issued(&self_signed, &root.to_public_key(), now),
+        Err(x509::EndorsementError::NotIssued)
+    );
+    assert_eq!(
+        x509::verify_issued(b"not a certificate", &root.to_public_key(), now),
+        Err(x509::EndorsementError::Malformed)
+    );
+
+    // A client trusting the root takes the key; one trusting another doesn't
+    let cert_file = dir.path().join("cert.pem");
+    fs::write(&cert_file, x509::to_pem(&issued, "CERTIFICATE")).unwrap();
+    let config = ServerConfig {
+        cert_file: cert_file.to_string_lossy().into_owned(),
+        ..test_config()
+    };
+    let server_url = format!("http://{}", spawn_server_with_keys(keys, config).await);
+    let options = |root: &KeyPair| ClientOptions {
+        trust_root: Some(root.to_public_key()),
+        ..fast_retries(0)
+    };
+    let client = client::nonblocking::VtsClient::with_options(&server_url, options(&root)).unwrap();
+    let key = client.request_key().await.unwrap();
+    assert_eq!(key.key(), Some(keypair.to_public_key()));
+    let signed = client.request_timestamp("Hello").await.unwrap();
+    assert!(verify_signature(&signed, &key));
+    let err = client::nonblocking::VtsClient::with_options(&server_url, options(&other_root))
+        .unwrap()
+        .request_key()
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<x509::EndorsementError>(),
+        Some(&x509::EndorsementError::NotIssued)
+    );
+
+    // Nor does it take a key without a certificate
+    let plain_url = format!("http://{}", spawn_server().await);
+    let err = client::nonblocking::VtsClient::with_options(&plain_url, options(&root))
+        .unwrap()
+        .request_key()
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ApiError>().unwrap().code,
+        "no_certificate"
+    );
+}
+
+fn policies_config(dir: &std::path::Path, ntp: String) -> ServerConfig {
+    let policy = |description: &str, strict: bool| PolicyConfig {
+        description: description.to_string(),
+        require_audit: strict,
+        require_ntp: strict,
+    };
+    ServerConfig {
+        audit_file: dir.join("audit.log").to_string_lossy().into_owned(),
+        policies: [
+            ("default".to_string(), policy("Best effort", false)),
+            (
+                "high-assurance".to_string(),
+                policy("Audited, NTP-checked", true),
+            ),
+        ]
+        .into(),
+        ..ntp_config(ntp)
+    }
+}
+
+#[tokio::test]
+async fn test_policy_is_signed_and_echoed() {
+    let dir = tempfile::tempdir().unwrap();
+    let ntp = spawn_fake_ntp(chrono::Duration::zero()).await;
+    let addr = spawn_configured_server(&[], policies_config(dir.path(), ntp)).await;
+    sleep(Duration::from_millis(200)).await;
+    let server_url = format!("http://{}", addr);
+
+    let listed = reqwest::get(format!("{}/policies", server_url))
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let names: Vec<&str> = listed["policies"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["default", "high-assurance"]);
+    assert_eq!(listed["policies"][1]["require-ntp"], true);
+
+    // The policy is covered by the signature: dropping it breaks it
+    let key = nonblocking::request_key(&server_url).await.unwrap();
+    let strict = client::nonblocking::VtsClient::with_options(
+        &server_url,
+        ClientOptions {
+            policy: Some("high-assurance".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let mut signed = strict.request_timestamp("Assured").await.unwrap();
+    assert_eq!(signed.policy.as_deref(), Some("high-assurance"));
+    assert!(verify_signature(&signed, &key));
+    signed.policy = None;
+    assert!(!verify_signature(&signed, &key));
+
+    // Without one, a configured `default` applies
+    let signed = nonblocking::request_timestamp(&server_url, "Plain")
+        .await
+        .unwrap();
+    assert_eq!(signed.policy.as_deref(), Some("default"));
+    assert!(verify_signature(&signed, &key));
+
+    // Tokens carry it as a claim, and the audit log records it
+    let token = reqwest::Client::new()
+        .post(format!("{}/sign?format=jws", server_url))
+        .json(&serde_json::json!({ "message": "Token", "policy": "high-assurance" }))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let claims = jws::verify(&token, &key).unwrap();
+    assert_eq!(claims.policy.as_deref(), Some("high-assurance"));
+    let found = reqwest::get(format!(
+        "{}/timestamp/by-hash/{}",
+        server_url,
+        hex::encode(Sha256::digest(b"Token"))
+    ))
+    .await
+    .unwrap()
+    .json::<serde_json::Value>()
+    .await
+    .unwrap();
+    assert_eq!(found["timestamps"][0]["policy"], "high-assurance");
+    assert_eq!(found["timestamps"][0]["proof"]["policy"], "high-assurance");
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/sign", server_url))
+        .json(&serde_json::json!({ "message": "Unknown", "policy": "gold" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["code"], "unknown_policy");
+    assert_eq!(body["field"], "policy");
+}
+
+#[tokio::test]
+async fn test_policy_requirements() {
+    // Before the first NTP answer, only the lenient policy signs
+    let dir = tempfile::tempdir().unwrap();
+    let unanswered = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let ntp = unanswered.local_addr().unwrap().to_string();
+    let server_url = format!(
+        "http://{}",
+        spawn_configured_server(&[], policies_config(dir.path(), ntp.clone())).await
+    );
+    nonblocking::request_timestamp(&server_url, "Lenient")
+        .await
+        .unwrap();
+    let resp = reqwest::Client::new()
+        .post(format!("{}/sign", server_url))
+        .json(&serde_json::json!({ "message": "Strict", "policy": "high-assurance" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["code"], "clock_unverified");
+
+    // A policy that promises an audit log can't start without one
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let (priv_bytes, pub_bytes) = generate_key_bytes();
+    let config = ServerConfig {
+        audit_file: String::new(),
+        ..policies_config(dir.path(), ntp)
+    };
+    let err = server::run_configured_server_with_listener(
+        priv_bytes,
+        pub_bytes,
+        TenantKeys::new(),
+        config,
+        Box::new(SystemClock),
+        listener,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("audit_file"), "{}", err);
 }
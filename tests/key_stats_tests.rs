@@ -0,0 +1,49 @@
+//! Unit tests for the per-key signature counters
+
+use lab4::config::KeyStatsConfig;
+use lab4::key_stats::{KeyStats, RecordError};
+use std::fs;
+
+#[test]
+fn test_limit_survives_restart() {
+    let path = "test_key_stats.json";
+    let _ = fs::remove_file(path);
+    let config = KeyStatsConfig {
+        file: path.to_string(),
+        max_signatures: 2,
+    };
+
+    let stats = KeyStats::new(&config).unwrap();
+    stats.record("a", "2030-06-01T12:00:00.000000Z").unwrap();
+    stats.record("b", "2030-06-01T12:00:01.000000Z").unwrap();
+
+    // "Restart": the counts come back from the file
+    let restarted = KeyStats::new(&config).unwrap();
+    let usage = restarted
+        .record("a", "2030-06-01T12:00:02.000000Z")
+        .unwrap();
+    let exhausted = restarted.record("a", "2030-06-01T12:00:03.000000Z");
+    let _ = fs::remove_file(path);
+
+    assert_eq!(usage.signatures, 2);
+    assert_eq!(
+        usage.first_signed.as_deref(),
+        Some("2030-06-01T12:00:00.000000Z")
+    );
+    assert!(matches!(exhausted, Err(RecordError::Exhausted)));
+    assert_eq!(restarted.usage("a").signatures, 2);
+    assert_eq!(restarted.usage("b").signatures, 1);
+    assert_eq!(restarted.usage("c").signatures, 0);
+}
+
+#[test]
+fn test_corrupt_key_stats_is_an_error() {
+    let path = "test_key_stats_corrupt.json";
+    fs::write(path, "not json").unwrap();
+    let result = KeyStats::new(&KeyStatsConfig {
+        file: path.to_string(),
+        max_signatures: 0,
+    });
+    let _ = fs::remove_file(path);
+    assert!(result.is_err());
+}
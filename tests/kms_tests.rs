@@ -0,0 +1,163 @@
+//! KMS signer tests, against a fake KMS on an ephemeral port
+
+use axum::{Router, body::Bytes, http::HeaderMap, http::StatusCode, routing::post};
+use base64::{Engine as _, engine::general_purpose};
+use k256::ecdsa::{Signature, SigningKey, signature::hazmat::PrehashSigner};
+use k256::pkcs8::EncodePublicKey;
+use lab4::config::KmsConfig;
+use lab4::signer::Signer;
+use lab4::signer::kms::KmsSigner;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Once};
+
+const REGION: &str = "test-region";
+
+/// What the fake KMS holds, and how many `Sign` calls it should fail first
+struct FakeKms {
+    key: SigningKey,
+    failures_left: AtomicU32,
+}
+
+fn respond(
+    kms: &FakeKms,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<serde_json::Value, (StatusCode, String)> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+    };
+    let scope = format!("/{}/kms/aws4_request", REGION);
+    if !header("authorization").starts_with("AWS4-HMAC-SHA256 ")
+        || !header("authorization").contains("Credential=AKIDTEST/")
+        || !header("authorization").contains(&scope)
+    {
+        return Err((StatusCode::BAD_REQUEST, "IncompleteSignature".to_string()));
+    }
+    let request: serde_json::Value = serde_json::from_slice(body).unwrap();
+    if request["KeyId"] != "alias/vts" {
+        return Err((StatusCode::BAD_REQUEST, "NotFoundException".to_string()));
+    }
+
+    match header("x-amz-target") {
+        "TrentService.GetPublicKey" => {
+            let der = k256::PublicKey::from(kms.key.verifying_key())
+                .to_public_key_der()
+                .unwrap();
+            Ok(serde_json::json!({
+                "KeyId": "alias/vts",
+                "PublicKey": general_purpose::STANDARD.encode(der.as_bytes()),
+                "SigningAlgorithms": ["ECDSA_SHA_256"],
+            }))
+        }
+        "TrentService.Sign" => {
+            if kms
+                .failures_left
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok()
+            {
+                return Err((StatusCode::SERVICE_UNAVAILABLE, "KMSInternal".to_string()));
+            }
+            assert_eq!(request["MessageType"], "DIGEST");
+            let digest = general_purpose::STANDARD
+                .decode(request["Message"].as_str().unwrap())
+                .unwrap();
+            let signature: Signature = kms.key.sign_prehash(&digest).unwrap();
+            Ok(serde_json::json!({
+                "Signature": general_purpose::STANDARD.encode(signature.to_der().as_bytes()),
+            }))
+        }
+        other => Err((StatusCode::BAD_REQUEST, format!("Unknown target {}", other))),
+    }
+}
+
+/// Starts a fake KMS on its own runtime thread and returns its URL
+fn spawn_fake_kms(kms: Arc<FakeKms>) -> String {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let app = Router::new().route(
+                "/",
+                post(move |headers: HeaderMap, body: Bytes| async move {
+                    match respond(&kms, &headers, &body) {
+                        Ok(json) => (StatusCode::OK, json.to_string()),
+                        Err((status, error)) => {
+                            (status, serde_json::json!({ "__type": error }).to_string())
+                        }
+                    }
+                }),
+            );
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            tx.send(listener.local_addr().unwrap()).unwrap();
+            axum::serve(listener, app).await.unwrap();
+        });
+    });
+    format!("http://{}", rx.recv().unwrap())
+}
+
+fn set_credentials() {
+    static CREDENTIALS: Once = Once::new();
+    CREDENTIALS.call_once(|| {
+        // SAFETY: set once, before any test reads the environment
+        unsafe {
+            std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDTEST");
+            std::env::set_var("AWS_SECRET_ACCESS_KEY", "test-secret");
+        }
+    });
+}
+
+fn kms_config(endpoint: String, key_id: &str) -> KmsConfig {
+    KmsConfig {
+        key_id: key_id.to_string(),
+        region: REGION.to_string(),
+        endpoint: Some(endpoint),
+        max_attempts: 3,
+        timeout_ms: 2000,
+    }
+}
+
+#[test]
+fn test_kms_signer_signs_and_retries() {
+    set_credentials();
+    let kms = Arc::new(FakeKms {
+        key: SigningKey::from_slice(&[7u8; 32]).unwrap(),
+        failures_left: AtomicU32::new(2),
+    });
+    let signer = KmsSigner::connect(&kms_config(spawn_fake_kms(kms.clone()), "alias/vts")).unwrap();
+
+    // The public key is the KMS key's
+    let expected = ecdsa_lib::PublicKey::from_sec1_bytes(
+        &kms.key.verifying_key().to_encoded_point(true).to_bytes(),
+    )
+    .unwrap();
+    assert_eq!(signer.public_key(), expected);
+
+    // Two 503s, then a signature that verifies like KeyPair::sign's
+    let message = b"Hello, VTS!2030-06-01T12:00:00.000000Z";
+    let signature = signer.sign(message).unwrap();
+    assert!(expected.verify(message, &signature));
+    let stats = signer.stats();
+    assert_eq!((stats.signatures, stats.retries, stats.failures), (1, 2, 0));
+    assert!(stats.max_latency >= stats.last_latency);
+
+    // Out of attempts: three more failures are an error
+    kms.failures_left.store(3, Ordering::SeqCst);
+    assert!(signer.sign(message).is_err());
+    assert_eq!(signer.stats().failures, 1);
+}
+
+#[test]
+fn test_kms_signer_rejects_unknown_key() {
+    set_credentials();
+    let kms = Arc::new(FakeKms {
+        key: SigningKey::from_slice(&[8u8; 32]).unwrap(),
+        failures_left: AtomicU32::new(0),
+    });
+    // A client error is not retried
+    let result = KmsSigner::connect(&kms_config(spawn_fake_kms(kms), "alias/other"));
+    let err = result.err().expect("connect fails").to_string();
+    assert!(err.contains("NotFoundException"), "{}", err);
+}
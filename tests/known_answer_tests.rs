@@ -0,0 +1,296 @@
+//! Known-answer tests for the VTS wire format (Base64 key and signature,
+//! signed bytes = `message + time-signed`). The values match the fixed
+//! vectors in `ecdsa_lib::test_vectors`, so other implementations can
+//! check their verification against the same JSON.
+
+use lab4::ecdsa_requests::verify_signature;
+use lab4::{EcdsaSignedTimestamp, EcdsaVerificationKey};
+
+/// (public key, signature) for "Hello, VTS!" signed at 2025-06-02T05:05:35.784383Z
+const VECTORS: &[(&str, &str)] = &[
+    (
+        // private key 1
+        "Anm+Zn753LusVaBilc6HCwcCm/zbLc4o2VnygVsW+BeY",
+        "troU1Civk0g0nNAw1p0z8mb4fnxFSwmsuXttaY87jO93mPNjDnMFDnQGOfWpmUR/znFFrVb0MgcRXS/ORxnxLw==",
+    ),
+    (
+        // RFC 6979 A.2.5 private key
+        "AyyMMfyfmQxrVeOGWhhKTOUOCUgfLq6z5g7BzqE6auZF",
+        "dAJl+PjUT3vZzdMdUqpblPULk2aquQlHoEFXKObATfZcBHyfa7osdu8xXXPRQpChDNKLWdf/m7zBQWD7eFkySQ==",
+    ),
+];
+
+/// The RFC 6979 vector above in DER, as returned for `"encoding": "der"`,
+/// and the same signature with high S (as OpenSSL may produce it)
+const DER_VECTORS: &[(&str, &str)] = &[
+    (
+        "AyyMMfyfmQxrVeOGWhhKTOUOCUgfLq6z5g7BzqE6auZF",
+        "MEQCIHQCZfj41E972c3THVKqW5T1C5NmqrkJR6BBVyjmwE32AiBcBHyfa7osdu8xXXPRQpChDNKLWdf/m7zBQWD7eFkySQ==",
+    ),
+    (
+        "AyyMMfyfmQxrVeOGWhhKTOUOCUgfLq6z5g7BzqE6auZF",
+        "MEUCIHQCZfj41E972c3THVKqW5T1C5NmqrkJR6BBVyjmwE32AiEAo/uDYJRF04kQzqKMLr1vXa3cUYzXSQR+/pD9kVfdDvg=",
+    ),
+];
+
+fn wire_pair(public_key: &str, signature: &str) -> (EcdsaSignedTimestamp, EcdsaVerificationKey) {
+    let key: EcdsaVerificationKey = serde_json::from_value(serde_json::json!({
+        "request": "GET",
+        "time-requested": "2025-06-02T05:05:35.206739Z",
+        "public-key": public_key,
+    }))
+    .unwrap();
+    let signed: EcdsaSignedTimestamp = serde_json::from_value(serde_json::json!({
+        "request": "POST",
+        "message": "Hello, VTS!",
+        "time-signed": "2025-06-02T05:05:35.784383Z",
+        "signature": signature,
+    }))
+    .unwrap();
+    (signed, key)
+}
+
+#[test]
+fn test_known_answers_verify() {
+    for (public_key, signature) in VECTORS {
+        let (signed, key) = wire_pair(public_key, signature);
+        assert!(
+            verify_signature(&signed, &key),
+            "vector {} failed",
+            public_key
+        );
+    }
+}
+
+#[test]
+fn test_known_answers_reject_altered_fields() {
+    for (public_key, signature) in VECTORS {
+        let (mut signed, key) = wire_pair(public_key, signature);
+        signed.time_signed = "2025-06-02T05:05:35.784384Z".to_string();
+        assert!(!verify_signature(&signed, &key));
+
+        let (mut signed, key) = wire_pair(public_key, signature);
+        signed.message.push(' ');
+        assert!(!verify_signature(&signed, &key));
+    }
+}
+
+#[test]
+fn test_known_answers_verify_der() {
+    for (public_key, signature) in DER_VECTORS {
+        let (signed, key) = wire_pair(public_key, signature);
+        assert!(
+            verify_signature(&signed, &key),
+            "DER vector {} failed",
+            signature
+        );
+
+        let (mut signed, key) = wire_pair(public_key, signature);
+        signed.message.push(' ');
+        assert!(!verify_signature(&signed, &key));
+    }
+}
+
+/// RFC 7638 thumbprint (the JWS `kid`) of the RFC 6979 public key above
+#[cfg(any(feature = "client", feature = "server"))]
+#[test]
+fn test_known_answer_jws_key_id() {
+    let public_key: ecdsa_lib::PublicKey = "AyyMMfyfmQxrVeOGWhhKTOUOCUgfLq6z5g7BzqE6auZF"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        lab4::jws::key_id(&public_key),
+        "iZndHloCMIwsfYl4WtL74pGcYb7bhiKFcLPJIdwJyQE"
+    );
+}
+
+/// The RFC 6979 vector above with hex instead of Base64 fields, and no
+/// `format` hint: decoding is still unambiguous
+#[test]
+fn test_known_answers_verify_hex() {
+    let (signed, key) = wire_pair(
+        "032c8c31fc9f990c6b55e3865a184a4ce50e09481f2eaeb3e60ec1cea13a6ae645",
+        "740265f8f8d44f7bd9cdd31d52aa5b94f50b9366aab90947a0415728e6c04df65c047c9f6bba2c76ef315d73d14290a10cd28b59d7ff9bbcc14160fb78593249",
+    );
+    assert!(verify_signature(&signed, &key));
+    assert_eq!(signed.signature_bytes().unwrap().len(), 64);
+    assert_eq!(
+        key.key().unwrap().to_string(),
+        "AyyMMfyfmQxrVeOGWhhKTOUOCUgfLq6z5g7BzqE6auZF"
+    );
+}
+
+/// `time_signed` checks on top of the signature, against a fixed "now"
+#[test]
+fn test_known_answers_time_checks() {
+    use chrono::{DateTime, Duration, Utc};
+    use lab4::ecdsa_requests::{VerifyOptions, VerifyOutcome, verify_signature_with};
+
+    let (signed, key) = wire_pair(VECTORS[1].0, VECTORS[1].1);
+    let time_signed: DateTime<Utc> = "2025-06-02T05:05:35.784383Z".parse().unwrap();
+    let at = |now: DateTime<Utc>| VerifyOptions {
+        max_skew: Some(Duration::minutes(5)),
+        max_future: Some(Duration::zero()),
+        now: Some(now),
+        ..Default::default()
+    };
+
+    let check = |options: &VerifyOptions| verify_signature_with(&signed, &key, options);
+    assert_eq!(check(&VerifyOptions::default()), VerifyOutcome::Ok);
+    assert_eq!(check(&at(time_signed)), VerifyOutcome::Ok);
+    assert_eq!(
+        check(&at(time_signed + Duration::minutes(4))),
+        VerifyOutcome::Ok
+    );
+    assert_eq!(
+        check(&at(time_signed + Duration::minutes(6))),
+        VerifyOutcome::TimeSkew
+    );
+    assert_eq!(
+        check(&at(time_signed - Duration::seconds(1))),
+        VerifyOutcome::InFuture
+    );
+    let lenient = VerifyOptions {
+        max_future: Some(Duration::seconds(2)),
+        ..at(time_signed - Duration::seconds(1))
+    };
+    assert_eq!(check(&lenient), VerifyOutcome::Ok);
+
+    // Moving the time breaks the signature before any time check applies
+    let mut moved = wire_pair(VECTORS[1].0, VECTORS[1].1).0;
+    moved.time_signed = "2025-06-02T05:05:36.784383Z".to_string();
+    assert_eq!(
+        verify_signature_with(&moved, &key, &at(time_signed)),
+        VerifyOutcome::BadSignature
+    );
+}
+
+/// A correctly signed timestamp whose `time_signed` isn't RFC 3339
+#[test]
+fn test_unparseable_time_signed() {
+    use lab4::ecdsa_requests::{VerifyOptions, VerifyOutcome, verify_signature_with};
+
+    let keypair = ecdsa_lib::KeyPair::generate();
+    let signature = keypair.sign(b"Hello, VTS!yesterday");
+    let (mut signed, key) = wire_pair(
+        &keypair.to_public_key().to_string(),
+        &base64::Engine::encode(
+            &base64::engine::general_purpose::STANDARD,
+            signature.to_bytes(),
+        ),
+    );
+    signed.time_signed = "yesterday".to_string();
+
+    assert!(verify_signature(&signed, &key));
+    let strict = VerifyOptions {
+        require_rfc3339: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        verify_signature_with(&signed, &key, &strict),
+        VerifyOutcome::BadTimestamp
+    );
+}
+
+/// A correctly signed timestamp at millisecond precision, not as the server writes it
+#[test]
+fn test_non_canonical_time_signed() {
+    use lab4::ecdsa_requests::{VerifyOptions, VerifyOutcome, verify_signature_with};
+
+    let keypair = ecdsa_lib::KeyPair::generate();
+    let signature = keypair.sign(b"Hello, VTS!2025-06-02T05:05:35.784Z");
+    let (mut signed, key) = wire_pair(
+        &keypair.to_public_key().to_string(),
+        &base64::Engine::encode(
+            &base64::engine::general_purpose::STANDARD,
+            signature.to_bytes(),
+        ),
+    );
+    signed.time_signed = "2025-06-02T05:05:35.784Z".to_string();
+
+    let rfc3339 = VerifyOptions {
+        require_rfc3339: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        verify_signature_with(&signed, &key, &rfc3339),
+        VerifyOutcome::Ok
+    );
+    let canonical = VerifyOptions {
+        require_canonical: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        verify_signature_with(&signed, &key, &canonical),
+        VerifyOutcome::BadTimestamp
+    );
+}
+
+/// Each way the RFC 6979 vector can be broken has its own outcome
+#[test]
+fn test_known_answers_failure_outcomes() {
+    use lab4::ecdsa_requests::{VerifyOptions, VerifyOutcome, verify_signature_with};
+
+    let check = |public_key: &str, signature: &str, message: &str| {
+        let (mut signed, key) = wire_pair(public_key, signature);
+        signed.message = message.to_string();
+        verify_signature_with(&signed, &key, &VerifyOptions::default())
+    };
+    let (public_key, signature) = VECTORS[1];
+
+    assert_eq!(
+        check(public_key, signature, "Hello, VTS!"),
+        VerifyOutcome::Ok
+    );
+    assert_eq!(
+        check(public_key, signature, "Hello, VTS?"),
+        VerifyOutcome::BadSignature
+    );
+    assert_eq!(
+        check(VECTORS[0].0, signature, "Hello, VTS!"),
+        VerifyOutcome::BadSignature
+    );
+    assert_eq!(
+        check("not a key", signature, "Hello, VTS!"),
+        VerifyOutcome::BadKey
+    );
+    assert_eq!(
+        check(public_key, "%%%", "Hello, VTS!"),
+        VerifyOutcome::BadEncoding
+    );
+    // Valid Base64, but 3 bytes are no signature
+    assert_eq!(
+        check(public_key, "AAAA", "Hello, VTS!"),
+        VerifyOutcome::BadEncoding
+    );
+}
+
+/// `signature-alg` / `hash-alg`: the defaults when absent, dispatched on
+/// when present, and unknown names reported as such
+#[test]
+fn test_known_answers_algorithms() {
+    use lab4::ecdsa_requests::{VerifyOptions, VerifyOutcome, verify_signature_with};
+
+    let (public_key, signature) = VECTORS[1];
+    let check = |signature_alg: Option<&str>, hash_alg: Option<&str>| {
+        let (mut signed, mut key) = wire_pair(public_key, signature);
+        signed.signature_alg = signature_alg.map(str::to_string);
+        signed.hash_alg = hash_alg.map(str::to_string);
+        key.signature_alg = Some("ecdsa-secp256k1".to_string());
+        verify_signature_with(&signed, &key, &VerifyOptions::default())
+    };
+
+    assert_eq!(check(None, None), VerifyOutcome::Ok);
+    assert_eq!(
+        check(Some("ecdsa-secp256k1"), Some("sha-256")),
+        VerifyOutcome::Ok
+    );
+    assert_eq!(
+        check(Some("ed25519"), None),
+        VerifyOutcome::UnsupportedAlgorithm
+    );
+    assert_eq!(
+        check(None, Some("sha3-256")),
+        VerifyOutcome::UnsupportedAlgorithm
+    );
+}
@@ -0,0 +1,241 @@
+//! Malformed input never panics a parser. Arbitrary bytes and mutated
+//! valid tokens go through the decode paths the `fuzz/` targets cover, so
+//! the same properties are checked on stable, without libFuzzer.
+
+use ecdsa_lib::{KeyPair, PublicKey};
+use lab4::cose::{self, CoseClaims};
+use lab4::ecdsa_requests::{VerifyOptions, verify_signature_with};
+use lab4::jws::{self, JwsClaims};
+use lab4::wire::canonical_time;
+use lab4::{EcdsaSignedTimestamp, EcdsaVerificationKey, wire};
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::Index;
+use std::convert::Infallible;
+use std::sync::LazyLock;
+
+fn verification_key(keypair: &KeyPair) -> EcdsaVerificationKey {
+    EcdsaVerificationKey {
+        request: "GET".to_string(),
+        time_requested: String::new(),
+        public_key: keypair.to_public_key().to_string(),
+        format: None,
+        signature_alg: None,
+        hash_alg: None,
+        kid: None,
+        not_before: None,
+        not_after: None,
+    }
+}
+
+/// Feeds `data` to every parser, as bytes and (if it is UTF-8) as text
+fn parse_everything(data: &[u8], key: &EcdsaVerificationKey, public_key: &PublicKey) {
+    let _ = cose::verify(data, key);
+    let _ = PublicKey::from_sec1_bytes(data);
+    let _ = KeyPair::signature_from_der(data);
+    let _ = public_key.verify_encoded(b"Hello, VTS!", data);
+
+    let strict = VerifyOptions {
+        require_canonical: true,
+        max_skew: Some(chrono::Duration::minutes(5)),
+        ..Default::default()
+    };
+    if let Ok(signed) = serde_json::from_slice::<EcdsaSignedTimestamp>(data) {
+        let _ = signed.signature_bytes();
+        let _ = verify_signature_with(&signed, key, &strict);
+        let mut renewed = signed.renewed();
+        while let Some(inner) = renewed {
+            renewed = inner.renewed();
+        }
+    }
+    if let Ok(key) = serde_json::from_slice::<EcdsaVerificationKey>(data) {
+        let _ = key.key();
+    }
+
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let _ = jws::verify(text, key);
+    let _ = text.parse::<PublicKey>();
+    let _ = wire::is_canonical(text);
+    for format in [None, Some("hex"), Some("base64")] {
+        let signed = EcdsaSignedTimestamp {
+            request: "POST".to_string(),
+            message: text.to_string(),
+            message_b64: format.map(|_| text.to_string()),
+            time_signed: text.to_string(),
+            signature: text.to_string(),
+            accuracy: None,
+            format: format.map(str::to_string),
+            signature_alg: None,
+            hash_alg: None,
+            kid: None,
+            policy: None,
+        };
+        let _ = verify_signature_with(&signed, key, &strict);
+        let key = EcdsaVerificationKey {
+            request: "GET".to_string(),
+            time_requested: text.to_string(),
+            public_key: text.to_string(),
+            format: format.map(str::to_string),
+            signature_alg: None,
+            hash_alg: None,
+            kid: None,
+            not_before: None,
+            not_after: None,
+        };
+        let _ = key.key();
+    }
+}
+
+/// One change to a byte string; positions are taken modulo its length
+#[derive(Debug, Clone)]
+enum Mutation {
+    Flip(Index, u8),
+    Truncate(Index),
+    Insert(Index, Vec<u8>),
+    /// Repeats up to 16 bytes from there
+    Repeat(Index),
+}
+
+/// One to three of them
+fn mutations() -> impl Strategy<Value = Vec<Mutation>> {
+    let mutation = prop_oneof![
+        (any::<Index>(), 1..=255u8).prop_map(|(at, flip)| Mutation::Flip(at, flip)),
+        any::<Index>().prop_map(Mutation::Truncate),
+        (any::<Index>(), vec(any::<u8>(), 1..9))
+            .prop_map(|(at, extra)| Mutation::Insert(at, extra)),
+        any::<Index>().prop_map(Mutation::Repeat),
+    ];
+    vec(mutation, 1..4)
+}
+
+/// Flips, drops, inserts or repeats a few bytes of `input`
+fn mutate(input: &[u8], mutations: &[Mutation]) -> Vec<u8> {
+    let mut out = input.to_vec();
+    for mutation in mutations {
+        match mutation {
+            Mutation::Flip(at, flip) if !out.is_empty() => {
+                let at = at.index(out.len());
+                out[at] ^= flip;
+            }
+            Mutation::Flip(..) => {}
+            Mutation::Truncate(at) => out.truncate(at.index(out.len() + 1)),
+            Mutation::Insert(at, extra) => {
+                let at = at.index(out.len() + 1);
+                out.splice(at..at, extra.iter().copied());
+            }
+            Mutation::Repeat(at) => {
+                let at = at.index(out.len() + 1);
+                let repeat = out[at..].iter().take(16).copied().collect::<Vec<_>>();
+                out.splice(at..at, repeat);
+            }
+        }
+    }
+    out
+}
+
+static KEYPAIR: LazyLock<KeyPair> = LazyLock::new(|| KeyPair::from_seed(&[0x42; 32]));
+
+/// Valid tokens of every kind, signed by `KEYPAIR`
+struct Tokens {
+    jws: String,
+    cose: Vec<u8>,
+    /// A renewed timestamp, its DER signature in hex
+    json: String,
+    der: Vec<u8>,
+}
+
+static TOKENS: LazyLock<Tokens> = LazyLock::new(|| {
+    let keypair = &*KEYPAIR;
+    let public_key = keypair.to_public_key();
+    let sign = |input: &[u8]| Ok::<_, Infallible>(keypair.sign(input));
+
+    let jws = jws::encode(
+        &JwsClaims {
+            msg_hash: "00".repeat(32),
+            iat: 1_748_840_735,
+            serial: 1,
+            kid: jws::key_id(&public_key),
+            policy: None,
+            day_root: None,
+        },
+        sign,
+    )
+    .unwrap();
+    let cose = cose::encode(
+        &CoseClaims {
+            msg_hash: [0; 32],
+            iat: 1_748_840_735,
+            serial: 1,
+            kid: jws::key_thumbprint(&public_key),
+            policy: None,
+            day_root: None,
+        },
+        sign,
+    )
+    .unwrap();
+    let time_signed = canonical_time(chrono::Utc::now());
+    let signature = keypair.sign(format!("Hello, VTS!{}", time_signed).as_bytes());
+    let inner = serde_json::json!({
+        "request": "POST",
+        "message": "Hello, VTS!",
+        "time-signed": time_signed,
+        "signature": hex::encode(signature.to_bytes()),
+    })
+    .to_string();
+    let der = keypair
+        .sign(format!("{}{}", inner, time_signed).as_bytes())
+        .to_der();
+    let json = serde_json::json!({
+        "request": "RENEW",
+        "message": inner,
+        "time-signed": time_signed,
+        "signature": hex::encode(der.as_bytes()),
+        "format": "hex",
+    })
+    .to_string();
+    Tokens {
+        jws,
+        cose,
+        json,
+        der: der.as_bytes().to_vec(),
+    }
+});
+
+#[test]
+fn test_tokens_to_mutate_verify() {
+    let key = verification_key(&KEYPAIR);
+    assert!(jws::verify(&TOKENS.jws, &key).is_some());
+    assert!(cose::verify(&TOKENS.cose, &key).is_some());
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2_000))]
+
+    #[test]
+    fn test_arbitrary_bytes_do_not_panic(data in vec(any::<u8>(), 0..300)) {
+        let (key, public_key) = (verification_key(&KEYPAIR), KEYPAIR.to_public_key());
+        parse_everything(&data, &key, &public_key);
+    }
+
+    #[test]
+    fn test_mutated_tokens_do_not_panic(token in 0..4usize, mutations in mutations()) {
+        let (key, public_key) = (verification_key(&KEYPAIR), KEYPAIR.to_public_key());
+        let tokens = &*TOKENS;
+        let valid = [tokens.jws.as_bytes(), &tokens.cose, tokens.json.as_bytes(), &tokens.der];
+        parse_everything(&mutate(valid[token], &mutations), &key, &public_key);
+    }
+
+    // Everything in a JWS is signed, so no change to one verifies
+    #[test]
+    fn test_mutated_jws_does_not_verify(mutations in mutations()) {
+        let key = verification_key(&KEYPAIR);
+        let mutated = mutate(TOKENS.jws.as_bytes(), &mutations);
+        if let Ok(text) = std::str::from_utf8(&mutated)
+            && text != TOKENS.jws
+        {
+            prop_assert!(jws::verify(text, &key).is_none(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! OTLP tests: the spans of a traced `/sign`, as a stand-in collector
+//! receives them.
+
+use axum::Json;
+use axum::routing::post;
+use ecdsa_lib::KeyPair;
+use lab4::clock::SystemClock;
+use lab4::config::{KeyStatsConfig, OtlpConfig, ServerConfig, TenantKeys};
+use lab4::server;
+use serde_json::Value;
+use std::fs;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, sleep};
+
+static TEST_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// Derives a KeyPair from the test id (so every run uses the same keys) and
+/// returns its raw (private, public) bytes
+fn generate_key_bytes() -> (Vec<u8>, Vec<u8>) {
+    let test_id = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let private_key_file = format!("otlp_test_private_key_{}.bin", test_id);
+    let public_key_file = format!("otlp_test_public_key_{}.bin", test_id);
+
+    let mut seed = [0u8; 32];
+    seed[0] = b'o';
+    seed[1..5].copy_from_slice(&test_id.to_be_bytes());
+    KeyPair::from_seed(&seed)
+        .save_to_files(&private_key_file, &public_key_file)
+        .unwrap();
+    let priv_bytes = fs::read(&private_key_file).unwrap();
+    let pub_bytes = fs::read(&public_key_file).unwrap();
+    let _ = fs::remove_file(&private_key_file);
+    let _ = fs::remove_file(&public_key_file);
+
+    (priv_bytes, pub_bytes)
+}
+
+/// A collector that keeps every span it is sent; returns its base URL
+async fn spawn_collector() -> (String, Arc<Mutex<Vec<Value>>>) {
+    let spans = Arc::new(Mutex::new(Vec::new()));
+    let received = spans.clone();
+    let app = axum::Router::new().route(
+        "/v1/traces",
+        post(move |Json(body): Json<Value>| async move {
+            for resource in body["resourceSpans"].as_array().unwrap() {
+                assert_eq!(
+                    resource["resource"]["attributes"][0]["value"]["stringValue"],
+                    "vts-test"
+                );
+                for scope in resource["scopeSpans"].as_array().unwrap() {
+                    let spans = scope["spans"].as_array().unwrap();
+                    received.lock().unwrap().extend(spans.iter().cloned());
+                }
+            }
+            "{}"
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}", addr), spans)
+}
+
+/// Spawns a server exporting to `collector` every second; returns its URL
+async fn spawn_server(collector: &str) -> String {
+    let (priv_bytes, pub_bytes) = generate_key_bytes();
+    let config = ServerConfig {
+        high_water_file: String::new(),
+        serial_file: String::new(),
+        key_stats: KeyStatsConfig {
+            file: String::new(),
+            ..Default::default()
+        },
+        otlp: OtlpConfig {
+            endpoint: collector.to_string(),
+            service_name: "vts-test".to_string(),
+            interval_secs: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        server::run_configured_server_with_listener(
+            priv_bytes,
+            pub_bytes,
+            TenantKeys::new(),
+            config,
+            Box::new(SystemClock),
+            listener,
+        )
+        .await
+        .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+    });
+    sleep(Duration::from_millis(100)).await;
+    format!("http://{}", addr)
+}
+
+/// POSTs a message to `/sign` with `traceparent`, if any
+async fn sign(server_url: &str, message: &str, traceparent: Option<&str>) {
+    let mut request = reqwest::Client::new()
+        .post(format!("{}/sign", server_url))
+        .json(&serde_json::json!({ "message": message }));
+    if let Some(traceparent) = traceparent {
+        request = request.header("traceparent", traceparent);
+    }
+    assert_eq!(request.send().await.unwrap().status(), 200);
+}
+
+#[tokio::test]
+async fn test_sign_spans_continue_the_callers_trace() {
+    let (collector, spans) = spawn_collector().await;
+    let server_url = spawn_server(&collector).await;
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    let caller = "00f067aa0ba902b7";
+
+    sign(
+        &server_url,
+        "traced",
+        Some(&format!("00-{}-{}-01", trace_id, caller)),
+    )
+    .await;
+    // Not sampled by the caller: continued, but not exported
+    let quiet = "11111111111111111111111111111111";
+    sign(
+        &server_url,
+        "quiet",
+        Some(&format!("00-{}-{}-00", quiet, caller)),
+    )
+    .await;
+    // Malformed (uppercase): a trace of its own
+    sign(
+        &server_url,
+        "fresh",
+        Some(&format!("00-{}-{}-01", trace_id.to_uppercase(), caller)),
+    )
+    .await;
+    sleep(Duration::from_millis(1500)).await;
+
+    let spans = spans.lock().unwrap().clone();
+    assert!(spans.iter().all(|s| s["traceId"] != quiet));
+    let traced: Vec<&Value> = spans.iter().filter(|s| s["traceId"] == trace_id).collect();
+    let root = traced
+        .iter()
+        .find(|s| s["kind"] == 2)
+        .expect("a server span");
+    assert_eq!(root["name"], "POST /sign");
+    assert_eq!(root["parentSpanId"], caller);
+    let status = root["attributes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|a| a["key"] == "http.response.status_code")
+        .unwrap();
+    assert_eq!(status["value"]["intValue"], "200");
+
+    // Each step is a child of the request's span, in order
+    let mut steps: Vec<&&Value> = traced.iter().filter(|s| s["kind"] == 1).collect();
+    steps.sort_by_key(|s| {
+        s["startTimeUnixNano"]
+            .as_str()
+            .unwrap()
+            .parse::<u64>()
+            .unwrap()
+    });
+    let names: Vec<&str> = steps.iter().map(|s| s["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["parse", "sign", "store", "respond"]);
+    assert!(steps.iter().all(|s| s["parentSpanId"] == root["spanId"]));
+
+    // The malformed header's request still got a server span, in a new trace
+    let fresh: Vec<&Value> = spans
+        .iter()
+        .filter(|s| s["kind"] == 2 && s["traceId"] != trace_id)
+        .collect();
+    assert_eq!(fresh.len(), 1);
+    assert!(fresh[0].get("parentSpanId").is_none());
+}
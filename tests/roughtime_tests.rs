@@ -0,0 +1,207 @@
+//! Roughtime tests: the wire format, and the server's UDP responder checked
+//! against the key its HTTP API serves.
+
+use ecdsa_lib::KeyPair;
+use lab4::clock::SystemClock;
+use lab4::config::{KeyStatsConfig, RoughtimeConfig, ServerConfig, TenantKeys};
+use lab4::ecdsa_requests::nonblocking;
+use lab4::roughtime::{self, Message, RoughtimeError, Verified};
+use lab4::server;
+use std::fs;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tokio::task;
+use tokio::time::{Duration, sleep};
+
+static TEST_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// Derives a KeyPair from the test id (so every run uses the same keys) and
+/// returns its raw (private, public) bytes
+fn generate_key_bytes() -> (Vec<u8>, Vec<u8>) {
+    let test_id = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let private_key_file = format!("roughtime_test_private_key_{}.bin", test_id);
+    let public_key_file = format!("roughtime_test_public_key_{}.bin", test_id);
+
+    let mut seed = [0u8; 32];
+    seed[0] = b'r';
+    seed[1..5].copy_from_slice(&test_id.to_be_bytes());
+    KeyPair::from_seed(&seed)
+        .save_to_files(&private_key_file, &public_key_file)
+        .unwrap();
+    let priv_bytes = fs::read(&private_key_file).unwrap();
+    let pub_bytes = fs::read(&public_key_file).unwrap();
+    let _ = fs::remove_file(&private_key_file);
+    let _ = fs::remove_file(&public_key_file);
+
+    (priv_bytes, pub_bytes)
+}
+
+/// Spawns a server answering Roughtime on a free UDP port; returns its
+/// HTTP base URL and the UDP address
+async fn spawn_server() -> (String, String) {
+    let (priv_bytes, pub_bytes) = generate_key_bytes();
+    let udp = std::net::UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+    let config = ServerConfig {
+        high_water_file: String::new(),
+        serial_file: String::new(),
+        key_stats: KeyStatsConfig {
+            file: String::new(),
+            ..Default::default()
+        },
+        roughtime: RoughtimeConfig {
+            listen: udp.clone(),
+            radius_ms: 250,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        server::run_configured_server_with_listener(
+            priv_bytes,
+            pub_bytes,
+            TenantKeys::new(),
+            config,
+            Box::new(SystemClock),
+            listener,
+        )
+        .await
+        .unwrap_or_else(|e| eprintln!("Server error: {}", e));
+    });
+    sleep(Duration::from_millis(100)).await;
+    (format!("http://{}", addr), udp)
+}
+
+/// Sends `request` bytes as they are and returns the answer, if any came
+fn send_raw(udp: &str, request: &[u8]) -> Option<Vec<u8>> {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_millis(300)))
+        .unwrap();
+    socket.send_to(request, udp).unwrap();
+    let mut buf = vec![0; 4096];
+    let len = socket.recv(&mut buf).ok()?;
+    buf.truncate(len);
+    Some(buf)
+}
+
+#[test]
+fn test_message_round_trip() {
+    let mut message = Message::new();
+    message.insert(roughtime::MIDP, 7u64.to_le_bytes().to_vec());
+    message.insert(roughtime::SIG, vec![1; 64]);
+    message.insert(roughtime::PATH, Vec::new());
+    let bytes = message.encode();
+    // Sorted by tag as a little-endian u32: SIG\0 < PATH < MIDP
+    assert_eq!(&bytes[12..16], b"SIG\0");
+    assert_eq!(&bytes[20..24], b"MIDP");
+    let decoded = Message::decode(&bytes).unwrap();
+    assert_eq!(decoded, message);
+    assert_eq!(decoded.get(roughtime::MIDP).unwrap(), 7u64.to_le_bytes());
+
+    let request = roughtime::request(&[9; roughtime::NONCE_LEN]);
+    assert_eq!(request.len(), roughtime::MIN_REQUEST_LEN);
+    assert_eq!(
+        Message::decode(&request)
+            .unwrap()
+            .get(roughtime::NONC)
+            .unwrap(),
+        [9; roughtime::NONCE_LEN]
+    );
+
+    // Tags out of order, offsets past the end, a truncated header
+    let mut swapped = bytes.clone();
+    swapped[12..16].copy_from_slice(b"ZZZZ");
+    assert!(Message::decode(&swapped).is_err());
+    let mut past_end = bytes.clone();
+    past_end[4..8].copy_from_slice(&1000u32.to_le_bytes());
+    assert!(Message::decode(&past_end).is_err());
+    assert!(Message::decode(&bytes[..12]).is_err());
+}
+
+#[tokio::test]
+async fn test_roughtime_answers_verify_under_the_server_key() {
+    let (server_url, udp) = spawn_server().await;
+    let key = nonblocking::request_key(&server_url)
+        .await
+        .unwrap()
+        .key()
+        .unwrap();
+
+    let before = chrono::Utc::now();
+    let nonce = [0x5a; roughtime::NONCE_LEN];
+    let target = udp.clone();
+    let answer = task::spawn_blocking(move || {
+        roughtime::query(&target, &nonce, Duration::from_secs(2)).unwrap()
+    })
+    .await
+    .unwrap();
+    // Never more bytes out than came in
+    assert!(answer.len() <= roughtime::MIN_REQUEST_LEN);
+
+    // 1) It verifies under the HTTP key, with the time of the request
+    let verified = roughtime::verify_response(&answer, &nonce, &key).unwrap();
+    assert_eq!(verified.radius, Duration::from_millis(250));
+    let slack = chrono::Duration::seconds(1);
+    assert!(verified.midpoint >= before - slack);
+    assert!(verified.midpoint <= chrono::Utc::now() + slack);
+
+    // 2) Not for another nonce, nor another key
+    let other = [0x5b; roughtime::NONCE_LEN];
+    assert_eq!(
+        roughtime::verify_response(&answer, &other, &key),
+        Err(RoughtimeError::NotInTree)
+    );
+    let stranger = KeyPair::generate().to_public_key();
+    assert_eq!(
+        roughtime::verify_response(&answer, &nonce, &stranger),
+        Err(RoughtimeError::BadDelegation)
+    );
+
+    // 3) A changed midpoint breaks the online key's signature
+    let mut message = Message::decode(&answer).unwrap();
+    let mut srep = Message::decode(message.get(roughtime::SREP).unwrap()).unwrap();
+    srep.insert(roughtime::MIDP, 0u64.to_le_bytes().to_vec());
+    message.insert(roughtime::SREP, srep.encode());
+    assert_eq!(
+        roughtime::verify_response(&message.encode(), &nonce, &key),
+        Err(RoughtimeError::BadSignature)
+    );
+
+    // 4) A request shorter than 1024 bytes gets no answer
+    let mut short = Message::new();
+    short.insert(roughtime::NONC, nonce.to_vec());
+    let target = udp.clone();
+    let answered = task::spawn_blocking(move || send_raw(&target, &short.encode()))
+        .await
+        .unwrap();
+    assert!(answered.is_none());
+
+    // 5) A chained request verifies too, and is consistent with the first
+    let blind = [0x11; roughtime::NONCE_LEN];
+    let chained = roughtime::chain_nonce(&answer, &blind);
+    let next = task::spawn_blocking(move || {
+        roughtime::query(&udp, &chained, Duration::from_secs(2)).unwrap()
+    })
+    .await
+    .unwrap();
+    let later = roughtime::verify_response(&next, &chained, &key).unwrap();
+    assert!(!roughtime::inconsistent(&verified, &later));
+}
+
+#[test]
+fn test_inconsistent_answers() {
+    let at = |secs: i64, radius_ms: u64| Verified {
+        midpoint: chrono::DateTime::from_timestamp(secs, 0).unwrap(),
+        radius: Duration::from_millis(radius_ms),
+    };
+    assert!(!roughtime::inconsistent(&at(100, 0), &at(101, 0)));
+    // Earlier, but within the radii
+    assert!(!roughtime::inconsistent(&at(100, 600), &at(99, 600)));
+    assert!(roughtime::inconsistent(&at(100, 400), &at(99, 400)));
+}
@@ -0,0 +1,156 @@
+//! Property tests for the wire format: a timestamp signed the way the
+//! server signs it survives JSON serialization and verifies, for any
+//! message, and no single-byte change to its signature does.
+
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Utc};
+use ecdsa_lib::KeyPair;
+use lab4::ecdsa_requests::verify_signature;
+use lab4::wire::canonical_time;
+use lab4::{EcdsaSignedTimestamp, EcdsaVerificationKey};
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::{Index, select};
+
+/// Mostly short strings, sometimes empty or tens of KiB, mixing plain
+/// ASCII with control characters, JSON metacharacters and non-ASCII
+fn message() -> impl Strategy<Value = String> {
+    let chars = |len| vec(message_char(), len).prop_map(String::from_iter);
+    prop_oneof![
+        5 => Just(String::new()),
+        1 => chars(4096..64_096),
+        15 => chars(1..17),
+        29 => chars(1..257),
+    ]
+}
+
+fn message_char() -> impl Strategy<Value = char> {
+    const SPECIAL: &[char] = &[
+        '"',
+        '\\',
+        '/',
+        '\u{7f}',
+        '\u{a0}',
+        'é',
+        'ß',
+        '日',
+        '本',
+        '🦀',
+        '\u{301}',
+        '\u{200d}',
+        '\u{202e}',
+        '\u{feff}',
+        '\u{fffd}',
+        '\u{10ffff}',
+    ];
+    prop_oneof![
+        1 => (0u8..0x20).prop_map(char::from),
+        1 => select(SPECIAL),
+        // Any scalar value outside the surrogates
+        1 => any::<char>(),
+        5 => (0x20u8..0x7f).prop_map(char::from),
+    ]
+}
+
+/// How the server happened to sign: when, raw or DER, hex or Base64
+#[derive(Debug, Clone)]
+struct Signing {
+    time: DateTime<Utc>,
+    der: bool,
+    hex: bool,
+}
+
+fn signing() -> impl Strategy<Value = Signing> {
+    (
+        0..1i64 << 30,
+        0..1_000_000_000u32,
+        any::<bool>(),
+        any::<bool>(),
+    )
+        .prop_map(|(secs, nanos, der, hex)| Signing {
+            time: DateTime::from_timestamp(1_700_000_000 + secs, nanos).unwrap(),
+            der,
+            hex,
+        })
+}
+
+/// The `/sign` response for `message` signed as `signing` says, as JSON
+fn sign(signing: &Signing, keypair: &KeyPair, message: &str) -> String {
+    let time_signed = canonical_time(signing.time);
+    let signature = keypair.sign(format!("{}{}", message, time_signed).as_bytes());
+    let bytes = match signing.der {
+        false => signature.to_bytes().to_vec(),
+        true => signature.to_der().as_bytes().to_vec(),
+    };
+    serde_json::to_string(&EcdsaSignedTimestamp {
+        request: "POST".to_string(),
+        message: message.to_string(),
+        message_b64: None,
+        time_signed,
+        signature: match signing.hex {
+            true => hex::encode(&bytes),
+            false => general_purpose::STANDARD.encode(&bytes),
+        },
+        accuracy: None,
+        format: signing.hex.then(|| "hex".to_string()),
+        signature_alg: Some("ecdsa-secp256k1".to_string()),
+        hash_alg: Some("sha-256".to_string()),
+        kid: None,
+        policy: None,
+    })
+    .unwrap()
+}
+
+fn key(keypair: &KeyPair) -> EcdsaVerificationKey {
+    serde_json::from_value(serde_json::json!({
+        "request": "GET",
+        "time-requested": "2025-06-02T05:05:35.206739Z",
+        "public-key": keypair.to_public_key().to_string(),
+    }))
+    .unwrap()
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(300))]
+
+    #[test]
+    fn test_any_message_round_trips(message in message(), signing in signing()) {
+        let keypair = KeyPair::from_seed(&[0x42; 32]);
+        let key = key(&keypair);
+        let json = sign(&signing, &keypair, &message);
+        let signed: EcdsaSignedTimestamp = serde_json::from_str(&json).unwrap();
+        prop_assert_eq!(&signed.message, &message);
+        prop_assert!(verify_signature(&signed, &key), "{}", json);
+
+        // And again after it is written back out, as `to_token` does
+        let again: EcdsaSignedTimestamp =
+            serde_json::from_str(&serde_json::to_string(&signed).unwrap()).unwrap();
+        prop_assert!(verify_signature(&again, &key), "{}", json);
+    }
+
+    #[test]
+    fn test_any_signature_byte_change_fails(
+        message in message(),
+        signing in signing(),
+        at in any::<Index>(),
+        flip in 1..=255u8,
+    ) {
+        let keypair = KeyPair::from_seed(&[0x42; 32]);
+        let key = key(&keypair);
+        let mut signed: EcdsaSignedTimestamp =
+            serde_json::from_str(&sign(&signing, &keypair, &message)).unwrap();
+        let mut bytes = signed.signature_bytes().unwrap();
+        let at = at.index(bytes.len());
+        bytes[at] ^= flip;
+        signed.signature = match signed.format.as_deref() {
+            Some("hex") => hex::encode(&bytes),
+            _ => general_purpose::STANDARD.encode(&bytes),
+        };
+        prop_assert!(
+            !verify_signature(&signed, &key),
+            "byte {} of {}",
+            at,
+            signed.signature
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! `testing::MockVts`: the clients talk to it without a socket, it answers
+//! as the server does (the same bytes as `tests/golden/` for the same key
+//! and clock), and its injected failures reach the caller.
+
+use chrono::{DateTime, Utc};
+use lab4::alg::DigestAlg;
+use lab4::client::{ClientOptions, Interceptor, RequestInfo, ResponseInfo, VtsClient, nonblocking};
+use lab4::ecdsa_requests::{verify_cose_token, verify_signature};
+use lab4::testing::{Failure, MockVts};
+use lab4::{ApiError, EcdsaSignedTimestamp, EcdsaVerificationKey};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The key and clock of `tests/golden/`
+const GOLDEN_SEED: [u8; 32] = [7; 32];
+const SIGNED_AT: &str = "2025-06-02T05:05:35.784383Z";
+
+fn options(retries: u32) -> ClientOptions {
+    ClientOptions {
+        retries,
+        backoff: Duration::ZERO,
+        jitter: false,
+        ..Default::default()
+    }
+}
+
+fn client(mock: &MockVts, retries: u32) -> VtsClient {
+    VtsClient::with_options(&mock.url(), options(retries)).unwrap()
+}
+
+fn golden(name: &str) -> Vec<u8> {
+    std::fs::read(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(name),
+    )
+    .unwrap()
+}
+
+fn api_error(err: Box<dyn std::error::Error>) -> ApiError {
+    err.downcast_ref::<ApiError>().unwrap().clone()
+}
+
+#[test]
+fn test_mock_answers_like_the_server() {
+    let mock = MockVts::with_seed(&GOLDEN_SEED);
+    mock.set_time(SIGNED_AT.parse::<DateTime<Utc>>().unwrap());
+    let client = client(&mock, 0);
+
+    let key = client.request_key().unwrap();
+    let expected: EcdsaVerificationKey =
+        serde_json::from_slice(&golden("key.response.json")).unwrap();
+    assert_eq!(key.public_key, expected.public_key);
+    assert_eq!(key.kid, expected.kid);
+    assert_eq!(key.public_key, mock.public_key().to_string());
+
+    let signed = client.request_timestamp("Hello, VTS!").unwrap();
+    let expected: EcdsaSignedTimestamp =
+        serde_json::from_slice(&golden("sign.response.json")).unwrap();
+    assert_eq!(
+        serde_json::to_string(&signed).unwrap(),
+        serde_json::to_string(&expected).unwrap()
+    );
+
+    // A fresh mock, so the serial is the golden token's too
+    let mock = MockVts::with_seed(&GOLDEN_SEED);
+    mock.set_time(SIGNED_AT.parse::<DateTime<Utc>>().unwrap());
+    let token = self::client(&mock, 0)
+        .request_timestamp_cose("Hello, VTS!")
+        .unwrap();
+    assert_eq!(token, golden("sign-cose.response.cose"));
+    assert!(verify_cose_token(&token, &key).is_some());
+}
+
+#[test]
+fn test_mock_timestamps_verify_and_move_forward() {
+    let mock = MockVts::new();
+    let client = client(&mock, 0);
+    let key = client.request_key().unwrap();
+
+    let first = client.request_timestamp("a").unwrap();
+    let second = client.request_timestamp("a").unwrap();
+    assert!(verify_signature(&first, &key));
+    assert!(verify_signature(&second, &key));
+    assert!(second.time_signed > first.time_signed);
+
+    let binary = client.request_binary_timestamp(&[0, 159, 255]).unwrap();
+    assert!(verify_signature(&binary, &key));
+    let digest = client
+        .request_digest_timestamp(&[0xab; 32], DigestAlg::Sha256)
+        .unwrap();
+    assert_eq!(digest.message, DigestAlg::Sha256.message(&[0xab; 32]));
+    assert!(verify_signature(&digest, &key));
+
+    // Every tenant shares the mock's key
+    let tenant = client.for_tenant("acme");
+    assert!(verify_signature(
+        &tenant.request_timestamp("b").unwrap(),
+        &key
+    ));
+    assert_eq!(
+        mock.requests(),
+        [
+            "GET /key",
+            "POST /sign",
+            "POST /sign",
+            "POST /sign",
+            "POST /sign",
+            "POST /t/acme/sign"
+        ]
+    );
+    let err = api_error(client.renew_timestamp("token").unwrap_err());
+    assert_eq!(err.code, "not_found");
+    assert_eq!(err.status, Some(404));
+}
+
+#[test]
+fn test_mock_injected_failures() {
+    let mock = MockVts::new();
+    let key = client(&mock, 0).request_key().unwrap();
+
+    // A timeout is retried like one from a real server
+    mock.fail_next(Failure::Timeout);
+    assert!(verify_signature(
+        &client(&mock, 1).request_timestamp("a").unwrap(),
+        &key
+    ));
+    mock.fail_next(Failure::Timeout);
+    let err = client(&mock, 0).request_timestamp("a").unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<std::io::Error>().map(|e| e.kind()),
+        Some(std::io::ErrorKind::TimedOut)
+    );
+
+    mock.fail_next(Failure::Status(503, "overloaded".to_string()));
+    let err = api_error(client(&mock, 0).request_timestamp("a").unwrap_err());
+    assert_eq!((err.code.as_str(), err.status), ("overloaded", Some(503)));
+    assert!(err.request_id.is_some());
+
+    mock.fail_next(Failure::BadSignature);
+    let signed = client(&mock, 0).request_timestamp("a").unwrap();
+    assert!(!verify_signature(&signed, &key));
+
+    mock.fail_next(Failure::WrongKey);
+    let signed = client(&mock, 0).request_timestamp("a").unwrap();
+    assert_eq!(signed.kid, key.kid);
+    assert!(!verify_signature(&signed, &key));
+    mock.fail_next(Failure::WrongKey);
+    let other = client(&mock, 0).request_key().unwrap();
+    assert_ne!(other.public_key, key.public_key);
+
+    // Until cleared, every request fails
+    mock.fail_always(Some(Failure::Status(500, "internal".to_string())));
+    assert!(client(&mock, 2).request_key().is_err());
+    mock.fail_always(None);
+    assert!(verify_signature(
+        &client(&mock, 0).request_timestamp("a").unwrap(),
+        &key
+    ));
+}
+
+#[test]
+fn test_dropped_mock_refuses_connections() {
+    let mock = MockVts::new();
+    let client = client(&mock, 0);
+    drop(mock);
+    let err = client.request_key().unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<std::io::Error>().map(|e| e.kind()),
+        Some(std::io::ErrorKind::ConnectionRefused)
+    );
+}
+
+#[tokio::test]
+async fn test_mock_serves_the_async_client() {
+    let mock = MockVts::new();
+    let client = nonblocking::VtsClient::with_options(&mock.url(), options(0)).unwrap();
+    let key = client.request_key().await.unwrap();
+    let signed = client.request_timestamp("async").await.unwrap();
+    assert!(verify_signature(&signed, &key));
+    mock.fail_next(Failure::BadSignature);
+    let signed = client.request_timestamp("async").await.unwrap();
+    assert!(!verify_signature(&signed, &key));
+}
+
+/// (attempt, status) of every answer
+#[derive(Default)]
+struct Attempts(Mutex<Vec<(u32, Option<u16>)>>);
+
+impl Interceptor for Attempts {
+    fn on_response(&self, request: &RequestInfo, response: &ResponseInfo) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((request.attempt, response.status));
+    }
+}
+
+#[test]
+fn test_interceptors_see_every_attempt() {
+    let mock = MockVts::new();
+    let attempts = Arc::new(Attempts::default());
+    let client = client(&mock, 2).with_interceptor(attempts.clone());
+    mock.fail_next(Failure::Timeout);
+    mock.fail_next(Failure::Status(503, "overloaded".to_string()));
+    client.request_timestamp("a").unwrap();
+    assert_eq!(
+        *attempts.0.lock().unwrap(),
+        [(0, None), (1, Some(503)), (2, Some(200))]
+    );
+}
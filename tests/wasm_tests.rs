@@ -0,0 +1,76 @@
+//! The `wasm` bindings, called natively: they take and return plain JSON
+//! strings and bytes, so they run the same outside a browser.
+
+use base64::{Engine as _, engine::general_purpose};
+use ecdsa_lib::KeyPair;
+use lab4::cose::{self, CoseClaims};
+use lab4::jws::{self, JwsClaims};
+use lab4::wasm::{sha256_hex, verify_cose, verify_jws, verify_timestamp};
+use std::convert::Infallible;
+
+/// The RFC 6979 known-answer vector, as `/key` and `/sign` JSON
+const KEY_JSON: &str = r#"{"request":"GET","time-requested":"2025-06-02T05:05:35.206739Z","public-key":"AyyMMfyfmQxrVeOGWhhKTOUOCUgfLq6z5g7BzqE6auZF"}"#;
+const SIGNED_JSON: &str = r#"{"request":"POST","message":"Hello, VTS!","time-signed":"2025-06-02T05:05:35.784383Z","signature":"dAJl+PjUT3vZzdMdUqpblPULk2aquQlHoEFXKObATfZcBHyfa7osdu8xXXPRQpChDNKLWdf/m7zBQWD7eFkySQ=="}"#;
+
+fn key_json(keypair: &KeyPair) -> String {
+    serde_json::json!({
+        "request": "GET",
+        "time-requested": "2030-01-01T00:00:00Z",
+        "public-key": general_purpose::STANDARD.encode(keypair.to_public_key().to_sec1_bytes()),
+    })
+    .to_string()
+}
+
+#[test]
+fn test_verify_timestamp_outcomes() {
+    assert_eq!(verify_timestamp(SIGNED_JSON, KEY_JSON), "ok");
+    let tampered = SIGNED_JSON.replace("Hello", "Jello");
+    assert_eq!(verify_timestamp(&tampered, KEY_JSON), "bad_signature");
+    let other_alg = SIGNED_JSON.replace(
+        r#""signature":"#,
+        r#""signature-alg":"ed25519","signature":"#,
+    );
+    assert_eq!(
+        verify_timestamp(&other_alg, KEY_JSON),
+        "unsupported_algorithm"
+    );
+    assert_eq!(verify_timestamp("{", KEY_JSON), "invalid_json");
+    assert_eq!(verify_timestamp(SIGNED_JSON, "null"), "invalid_json");
+}
+
+#[test]
+fn test_verify_tokens() {
+    let keypair = KeyPair::from_seed(&[7u8; 32]);
+    let other = KeyPair::from_seed(&[8u8; 32]);
+    let public_key = keypair.to_public_key();
+    let sign = |bytes: &[u8]| Ok::<_, Infallible>(keypair.sign(bytes));
+
+    let claims = JwsClaims {
+        msg_hash: sha256_hex(b"Hello, VTS!"),
+        iat: 1_900_000_000,
+        serial: 3,
+        kid: jws::key_id(&public_key),
+        policy: None,
+        day_root: None,
+    };
+    let token = jws::encode(&claims, sign).unwrap();
+    let verified: JwsClaims =
+        serde_json::from_str(&verify_jws(&token, &key_json(&keypair)).unwrap()).unwrap();
+    assert_eq!(verified, claims);
+    assert_eq!(verify_jws(&token, &key_json(&other)), None);
+
+    let claims = CoseClaims {
+        msg_hash: *b"0123456789abcdef0123456789abcdef",
+        iat: 1_900_000_000,
+        serial: 4,
+        kid: jws::key_thumbprint(&public_key),
+        policy: None,
+        day_root: None,
+    };
+    let token = cose::encode(&claims, sign).unwrap();
+    let verified: serde_json::Value =
+        serde_json::from_str(&verify_cose(&token, &key_json(&keypair)).unwrap()).unwrap();
+    assert_eq!(verified["msg_hash"], hex::encode(claims.msg_hash));
+    assert_eq!(verified["serial"], 4);
+    assert_eq!(verify_cose(&token, &key_json(&other)), None);
+}
@@ -0,0 +1,224 @@
+//! Unit tests for the canonical `time-signed` format and message normalization
+
+use chrono::{DateTime, NaiveDate, Timelike, Utc};
+use lab4::wire::{
+    Normalization, TimePrecision, canonical_time, is_canonical, parse_time, signing_input,
+};
+use proptest::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+fn t(s: &str) -> DateTime<Utc> {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_canonical_round_trips() {
+    for s in [
+        "2025-06-02T05:05:35.123456Z",
+        "2025-06-02T05:05:35.000000Z",
+        "2025-06-02T05:05:35.000001Z",
+        "2025-06-02T05:05:35.999999Z",
+        "1969-12-31T23:59:59.999999Z",
+        "9999-12-31T23:59:59.999999Z",
+    ] {
+        let time = parse_time(s).unwrap();
+        assert_eq!(canonical_time(time), s);
+    }
+}
+
+#[test]
+fn test_always_six_digits_in_utc() {
+    assert_eq!(
+        canonical_time(t("2030-01-01T00:00:00Z")),
+        "2030-01-01T00:00:00.000000Z"
+    );
+    assert_eq!(
+        canonical_time(t("2030-01-01T00:00:00.5Z")),
+        "2030-01-01T00:00:00.500000Z"
+    );
+    // Other offsets are read as the same instant in UTC
+    assert_eq!(
+        canonical_time(parse_time("2030-01-01T01:00:00.25+01:00").unwrap()),
+        "2030-01-01T00:00:00.250000Z"
+    );
+}
+
+#[test]
+fn test_sub_microseconds_are_truncated() {
+    assert_eq!(
+        canonical_time(t("2030-01-01T00:00:00.123456789Z")),
+        "2030-01-01T00:00:00.123456Z"
+    );
+    // Not rounded up into the next second
+    assert_eq!(
+        canonical_time(t("2030-12-31T23:59:59.999999999Z")),
+        "2030-12-31T23:59:59.999999Z"
+    );
+    // What a non-canonical string reads as is canonical from then on
+    let time = parse_time("2030-01-01T00:00:00.1234567Z").unwrap();
+    assert_eq!(
+        canonical_time(parse_time(&canonical_time(time)).unwrap()),
+        canonical_time(time)
+    );
+}
+
+#[test]
+fn test_leap_second() {
+    let leap = NaiveDate::from_ymd_opt(2016, 12, 31)
+        .unwrap()
+        .and_hms_micro_opt(23, 59, 59, 1_500_000)
+        .unwrap()
+        .and_utc();
+    let s = canonical_time(leap);
+    assert_eq!(s, "2016-12-31T23:59:60.500000Z");
+    assert_eq!(parse_time(&s).unwrap(), leap);
+}
+
+#[test]
+fn test_rejects_non_rfc3339() {
+    for s in ["", "2030-01-01", "2030-01-01 00:00:00", "1893456000"] {
+        assert!(parse_time(s).is_err(), "{} parsed", s);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Stamped {
+    #[serde(rename = "time-signed", with = "lab4::wire::serde_canonical")]
+    time_signed: DateTime<Utc>,
+}
+
+#[test]
+fn test_serde_canonical() {
+    let stamped = Stamped {
+        time_signed: t("2030-01-01T00:00:00.123456789Z"),
+    };
+    let json = serde_json::to_string(&stamped).unwrap();
+    assert_eq!(json, r#"{"time-signed":"2030-01-01T00:00:00.123456Z"}"#);
+    let back: Stamped = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.time_signed, t("2030-01-01T00:00:00.123456Z"));
+    assert!(serde_json::from_str::<Stamped>(r#"{"time-signed":"yesterday"}"#).is_err());
+    assert!(serde_json::from_str::<Stamped>(r#"{"time-signed":"2030-01-01T00:00:00Z"}"#).is_err());
+}
+
+/// Any instant from year 1 to 9999, at nanosecond precision
+fn time() -> impl Strategy<Value = DateTime<Utc>> {
+    const MIN: i64 = -62_135_596_800;
+    const MAX: i64 = 253_402_300_799;
+    // Whole seconds and whole microseconds are the usual edge cases
+    let nanos = prop_oneof![
+        1 => Just(0),
+        1 => (0..1_000_000u32).prop_map(|micros| micros * 1_000),
+        2 => 0..1_000_000_000u32,
+    ];
+    (MIN..=MAX, nanos).prop_map(|(secs, nanos)| DateTime::from_timestamp(secs, nanos).unwrap())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10_000))]
+
+    #[test]
+    fn test_precision_policy_holds_for_any_time(time in time()) {
+        let s = canonical_time(time);
+
+        // Always `YYYY-MM-DDTHH:MM:SS.ffffffZ`
+        prop_assert_eq!(s.len(), 27, "{}", s);
+        prop_assert_eq!(&s[19..20], ".", "{}", s);
+        prop_assert!(s.ends_with('Z'), "{}", s);
+        prop_assert!(is_canonical(&s), "{}", s);
+
+        // Reads back as the time truncated to microseconds, and writes the
+        // same string again
+        let back = parse_time(&s).unwrap();
+        prop_assert_eq!(
+            back,
+            time.with_nanosecond(time.nanosecond() / 1_000 * 1_000)
+                .unwrap()
+        );
+        prop_assert_eq!(canonical_time(back), s);
+
+        let json = serde_json::to_string(&Stamped { time_signed: time }).unwrap();
+        let read: Stamped = serde_json::from_str(&json).unwrap();
+        prop_assert_eq!(serde_json::to_string(&read).unwrap(), json);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(1_000))]
+
+    #[test]
+    fn test_other_forms_are_not_canonical(time in time()) {
+        let s = canonical_time(time);
+        let (seconds, fraction) = s.trim_end_matches('Z').split_once('.').unwrap();
+        for other in [
+            format!("{}Z", seconds),
+            format!("{}.{}Z", seconds, &fraction[..3]),
+            format!("{}.{}000Z", seconds, fraction),
+            format!("{}.{}+00:00", seconds, fraction),
+            format!("{}.{}z", seconds, fraction),
+        ] {
+            // Valid RFC 3339, but not what the server writes
+            prop_assert!(parse_time(&other).is_ok(), "{}", other);
+            prop_assert!(!is_canonical(&other), "{}", other);
+            let json = format!(r#"{{"time-signed":"{}"}}"#, other);
+            prop_assert!(serde_json::from_str::<Stamped>(&json).is_err(), "{}", other);
+        }
+    }
+}
+
+#[test]
+fn test_exact_signs_the_bytes_received() {
+    let decomposed = "Cafe\u{301}";
+    assert!(matches!(
+        Normalization::Exact.apply(decomposed),
+        Cow::Borrowed("Cafe\u{301}")
+    ));
+    assert_eq!(Normalization::default(), Normalization::Exact);
+    assert_eq!(
+        signing_input(decomposed.as_bytes(), "2025-06-02T05:05:35.123456Z"),
+        b"Cafe\xcc\x812025-06-02T05:05:35.123456Z"
+    );
+}
+
+#[test]
+fn test_nfc_composes_and_leaves_nfc_text_alone() {
+    // Precomposed, decomposed, and decomposed out of canonical order
+    for text in ["Caf\u{e9}", "Cafe\u{301}"] {
+        assert_eq!(Normalization::Nfc.apply(text), "Caf\u{e9}");
+    }
+    assert_eq!(
+        Normalization::Nfc.apply("a\u{323}\u{302}"),
+        Normalization::Nfc.apply("a\u{302}\u{323}")
+    );
+    assert_eq!(Normalization::Nfc.apply("\u{212b}"), "\u{c5}"); // Angstrom sign
+    // Compatibility characters are kept (that would be NFKC)
+    assert_eq!(Normalization::Nfc.apply("\u{fb01}"), "\u{fb01}");
+    for text in ["Hello, VTS!", "", "Caf\u{e9}", "\u{1f600}"] {
+        assert!(matches!(Normalization::Nfc.apply(text), Cow::Borrowed(t) if t == text));
+    }
+    assert_eq!("nfc".parse(), Ok(Normalization::Nfc));
+    assert_eq!("exact".parse(), Ok(Normalization::Exact));
+    assert!("NFKC".parse::<Normalization>().is_err());
+}
+
+#[test]
+fn test_time_precision() {
+    let time = t("2025-06-02T05:05:35.123456Z");
+    assert_eq!(
+        canonical_time(TimePrecision::Millis.truncate(time)),
+        "2025-06-02T05:05:35.123000Z"
+    );
+    assert_eq!(
+        canonical_time(TimePrecision::Seconds.truncate(time)),
+        "2025-06-02T05:05:35.000000Z"
+    );
+    assert_eq!(TimePrecision::Micros.truncate(time), time);
+
+    assert!(TimePrecision::Seconds.holds("2025-06-02T05:05:35.000000Z"));
+    assert!(!TimePrecision::Seconds.holds("2025-06-02T05:05:35.123000Z"));
+    assert!(TimePrecision::Millis.holds("2025-06-02T05:05:35.123000Z"));
+    // Still the full canonical form, never fewer digits
+    assert!(!TimePrecision::Seconds.holds("2025-06-02T05:05:35Z"));
+    assert_eq!("millis".parse(), Ok(TimePrecision::Millis));
+    assert!("nanos".parse::<TimePrecision>().is_err());
+}